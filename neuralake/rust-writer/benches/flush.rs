@@ -0,0 +1,88 @@
+//! Latency of the flush path for small batches.
+//!
+//! Run with `cargo bench --features bench --bench flush`. Besides criterion's
+//! own report, the end-to-end group prints the p99 of single 1k-row writes to
+//! a local table, to compare against the 100 ms target.
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use deltalake::arrow::datatypes::{DataType, Field, Schema};
+use polars::prelude::{DataFrame, NamedFrom};
+use std::time::{Duration, Instant};
+use surgical_strike_writer::{config::parse_config, schema::matches_table, SurgicalStrikeOrchestrator};
+
+const ROWS: usize = 1_000;
+const P99_SAMPLES: usize = 200;
+
+fn batch(offset: i64) -> DataFrame {
+    let ids: Vec<i64> = (offset..offset + ROWS as i64).collect();
+    let names: Vec<String> = ids.iter().map(|id| format!("name-{}", id)).collect();
+    polars::df!("id" => ids, "name" => names).unwrap()
+}
+
+async fn local_orchestrator(dir: &tempfile::TempDir) -> SurgicalStrikeOrchestrator {
+    let toml = format!(
+        r#"table_uri = "file://{}"
+
+[[schema.columns]]
+name = "id"
+type = "long"
+
+[[schema.columns]]
+name = "name"
+type = "string"
+"#,
+        dir.path().display()
+    );
+    let config = parse_config(&toml).unwrap().remove(0);
+    SurgicalStrikeOrchestrator::new(config).await.unwrap()
+}
+
+fn p99(mut samples: Vec<Duration>) -> Duration {
+    samples.sort();
+    samples[(samples.len() * 99).div_ceil(100) - 1]
+}
+
+fn bench_matches_table(c: &mut Criterion) {
+    let fields: Vec<Field> = (0..32)
+        .map(|i| Field::new(format!("column_{}", i), DataType::Int64, true))
+        .collect();
+    let table = Schema::new(fields.clone());
+    let batch = Schema::new(fields);
+
+    c.bench_function("schema/matches_table_32_columns", |b| {
+        b.iter(|| matches_table(black_box(&table), black_box(&batch)))
+    });
+}
+
+fn bench_write_batch(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let orchestrator = &runtime.block_on(local_orchestrator(&dir));
+
+    let mut offset = 0;
+    let mut group = c.benchmark_group("flush");
+    group.sample_size(50);
+    group.bench_function("write_batch_1k_rows", |b| {
+        b.to_async(&runtime).iter(|| {
+            offset += ROWS as i64;
+            let df = batch(offset);
+            async move { orchestrator.write_batch(df).await.unwrap() }
+        })
+    });
+    group.finish();
+
+    let samples = runtime.block_on(async {
+        let mut samples = Vec::with_capacity(P99_SAMPLES);
+        for _ in 0..P99_SAMPLES {
+            offset += ROWS as i64;
+            let df = batch(offset);
+            let start = Instant::now();
+            orchestrator.write_batch(df).await.unwrap();
+            samples.push(start.elapsed());
+        }
+        samples
+    });
+    println!("flush/write_batch_1k_rows p99 over {} writes: {:?}", P99_SAMPLES, p99(samples));
+}
+
+criterion_group!(benches, bench_matches_table, bench_write_batch);
+criterion_main!(benches);
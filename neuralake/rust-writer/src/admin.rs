@@ -0,0 +1,386 @@
+//! gRPC admin and write API (`surgical_strike.admin.v1.Admin`, described in
+//! `proto/admin.proto`), so other services can ingest into and maintain a
+//! running orchestrator without the CLI. Messages are declared with prost
+//! derives and the service is routed by hand, which keeps protoc out of the
+//! build; a test checks the messages' field numbers and wire types against
+//! the proto file.
+
+use anyhow::{Context, Result};
+use axum::body::Bytes;
+use deltalake::DeltaTable;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
+use std::future::Future;
+use std::sync::Arc;
+use std::task::{Context as TaskContext, Poll};
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+use tonic::codegen::{empty_body, http, Body, BoxFuture, Service, StdError};
+use tonic::server::{Grpc, NamedService, UnaryService};
+use tonic::transport::Server;
+use tonic::{Code, Request, Response, Status};
+use crate::auth;
+use crate::compaction::{self, CompactionProcess};
+use crate::config::GrpcConfig;
+use crate::correlation::validate_batch_id;
+use crate::server::{decode_payload, TableEndpoint, ARROW_STREAM_CONTENT_TYPE};
+use crate::vacuum::VacuumProcess;
+
+/// Fully qualified gRPC service name
+const SERVICE_NAME: &str = "surgical_strike.admin.v1.Admin";
+
+/// Methods that write or run maintenance, and so need the `auth_token`
+const MUTATING_METHODS: &[&str] = &["WriteBatch", "TriggerCompaction", "TriggerVacuum"];
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct WriteBatchRequest {
+    /// Table name; may be empty when a single table is served
+    #[prost(string, tag = "1")]
+    pub table: String,
+    /// Rows as an Arrow IPC stream
+    #[prost(bytes = "vec", tag = "2")]
+    pub arrow_ipc: Vec<u8>,
+    /// Producer's own batch id; generated when empty
+    #[prost(string, tag = "3")]
+    pub batch_id: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct WriteBatchResponse {
+    #[prost(string, tag = "1")]
+    pub batch_id: String,
+    #[prost(uint64, tag = "2")]
+    pub rows: u64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TriggerCompactionRequest {
+    #[prost(string, tag = "1")]
+    pub table: String,
+    /// Partition filters such as `date=2024-01-01`; empty compacts every partition
+    #[prost(string, repeated, tag = "2")]
+    pub partition_filters: Vec<String>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TriggerCompactionResponse {
+    /// Table version after the pass
+    #[prost(int64, tag = "1")]
+    pub version: i64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TableRequest {
+    #[prost(string, tag = "1")]
+    pub table: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TriggerVacuumResponse {
+    #[prost(bool, tag = "1")]
+    pub dry_run: bool,
+    #[prost(uint64, tag = "2")]
+    pub files_deleted: u64,
+    #[prost(uint64, tag = "3")]
+    pub bytes_deleted: u64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetMetricsResponse {
+    #[prost(uint64, tag = "1")]
+    pub queued_batches: u64,
+    /// The counters of `GET /metrics`, by name
+    #[prost(map = "string, double", tag = "2")]
+    pub counters: HashMap<String, f64>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetStatusResponse {
+    #[prost(string, tag = "1")]
+    pub table_uri: String,
+    #[prost(bool, tag = "2")]
+    pub read_only: bool,
+    #[prost(uint64, tag = "3")]
+    pub queued_batches: u64,
+    /// Latest version committed by this process since it started
+    #[prost(int64, optional, tag = "4")]
+    pub last_version: Option<i64>,
+    /// RFC 3339 time of that commit
+    #[prost(string, optional, tag = "5")]
+    pub last_commit_at: Option<String>,
+    #[prost(uint64, tag = "6")]
+    pub recent_errors: u64,
+    /// Ingestion source status as JSON, when a source is configured
+    #[prost(string, optional, tag = "7")]
+    pub source_status_json: Option<String>,
+    #[prost(bool, tag = "8")]
+    pub writer_paused: bool,
+    #[prost(bool, tag = "9")]
+    pub compaction_paused: bool,
+    #[prost(bool, tag = "10")]
+    pub vacuum_paused: bool,
+}
+
+/// A managed table reachable through the admin API
+#[derive(Clone)]
+pub struct AdminTable {
+    pub endpoint: TableEndpoint,
+    pub table: Arc<Mutex<DeltaTable>>,
+    pub compaction: CompactionProcess,
+    pub vacuum: VacuumProcess,
+}
+
+/// The admin service over the tables it is given, keyed by table name
+#[derive(Clone)]
+pub struct AdminService {
+    tables: Arc<BTreeMap<String, AdminTable>>,
+}
+
+impl AdminService {
+    pub fn new(tables: BTreeMap<String, AdminTable>) -> Self {
+        Self { tables: Arc::new(tables) }
+    }
+
+    /// The named table; the name may be left empty when one table is served
+    fn table(&self, name: &str) -> Result<&AdminTable, Status> {
+        let found = match name {
+            "" if self.tables.len() == 1 => self.tables.values().next(),
+            "" => return Err(Status::invalid_argument("Specify the table")),
+            name => self.tables.get(name),
+        };
+        found.ok_or_else(|| Status::not_found(format!("Unknown table '{}'", name)))
+    }
+
+    fn writable(&self, name: &str) -> Result<&AdminTable, Status> {
+        let table = self.table(name)?;
+        if table.endpoint.read_only {
+            return Err(Status::failed_precondition(format!("{} is open read-only", table.endpoint.table_uri)));
+        }
+        Ok(table)
+    }
+
+    pub async fn write_batch(&self, request: WriteBatchRequest) -> Result<WriteBatchResponse, Status> {
+        let table = self.writable(&request.table)?;
+        let batch_id = match request.batch_id.as_str() {
+            "" => None,
+            id => {
+                validate_batch_id(id).map_err(|e| Status::invalid_argument(format!("{:#}", e)))?;
+                Some(id.to_string())
+            }
+        };
+        let df = decode_payload(ARROW_STREAM_CONTENT_TYPE, Bytes::from(request.arrow_ipc))
+            .map_err(|e| Status::invalid_argument(format!("{:#}", e)))?;
+        let rows = df.height() as u64;
+        let batch_id = table
+            .endpoint
+            .batches
+            .send_with_id(df, batch_id)
+            .await
+            .map_err(|_| Status::unavailable("Writer is shutting down"))?;
+        Ok(WriteBatchResponse { batch_id, rows })
+    }
+
+    pub async fn trigger_compaction(
+        &self,
+        request: TriggerCompactionRequest,
+    ) -> Result<TriggerCompactionResponse, Status> {
+        let table = self.writable(&request.table)?;
+        let filters = request
+            .partition_filters
+            .iter()
+            .map(|filter| compaction::parse_partition_filter(filter))
+            .collect::<Result<Vec<_>>>()
+            .map_err(|e| Status::invalid_argument(format!("{:#}", e)))?;
+        let mut handle = table.table.lock().await;
+        table.compaction.run_once_with_filters(&mut handle, &filters).await.map_err(internal)?;
+        Ok(TriggerCompactionResponse { version: handle.version() })
+    }
+
+    pub async fn trigger_vacuum(&self, request: TableRequest) -> Result<TriggerVacuumResponse, Status> {
+        let table = self.writable(&request.table)?;
+        let mut handle = table.table.lock().await;
+        let report = table.vacuum.run_once(&mut handle).await.map_err(internal)?;
+        Ok(TriggerVacuumResponse {
+            dry_run: report.dry_run,
+            files_deleted: report.files.len() as u64,
+            bytes_deleted: report.total_bytes,
+        })
+    }
+
+    pub async fn get_metrics(&self, request: TableRequest) -> Result<GetMetricsResponse, Status> {
+        let table = self.table(&request.table)?;
+        let snapshot = serde_json::to_value(table.endpoint.metrics.snapshot()).map_err(internal)?;
+        let counters = snapshot
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter_map(|(name, value)| Some((name.clone(), value.as_f64()?)))
+            .collect();
+        Ok(GetMetricsResponse { queued_batches: table.endpoint.batches.queued() as u64, counters })
+    }
+
+    pub async fn get_status(&self, request: TableRequest) -> Result<GetStatusResponse, Status> {
+        let endpoint = &self.table(&request.table)?.endpoint;
+        let last = endpoint.metrics.recent_batches(1).pop();
+        let pauses = endpoint.maintenance.pauses();
+        let source_status_json = match &endpoint.source_status {
+            Some(status) => Some(serde_json::to_string(&*status.lock().unwrap()).map_err(internal)?),
+            None => None,
+        };
+        Ok(GetStatusResponse {
+            table_uri: endpoint.table_uri.clone(),
+            read_only: endpoint.read_only,
+            queued_batches: endpoint.batches.queued() as u64,
+            last_version: last.as_ref().map(|r| r.version),
+            last_commit_at: last.as_ref().map(|r| r.committed_at.to_rfc3339()),
+            recent_errors: endpoint.metrics.recent_errors().len() as u64,
+            source_status_json,
+            writer_paused: endpoint.control.is_paused(),
+            compaction_paused: pauses.compaction,
+            vacuum_paused: pauses.vacuum,
+        })
+    }
+}
+
+fn internal(e: impl std::fmt::Display) -> Status {
+    Status::internal(format!("{:#}", e))
+}
+
+/// A unary RPC handled by a closure over the decoded request
+struct Unary<F>(F);
+
+impl<Req, Resp, F, Fut> UnaryService<Req> for Unary<F>
+where
+    F: FnMut(Req) -> Fut,
+    Fut: Future<Output = Result<Resp, Status>> + Send + 'static,
+{
+    type Response = Resp;
+    type Future = BoxFuture<Response<Resp>, Status>;
+
+    fn call(&mut self, request: Request<Req>) -> Self::Future {
+        let response = (self.0)(request.into_inner());
+        Box::pin(async move { response.await.map(Response::new) })
+    }
+}
+
+/// Decode the request body, run `handler` and encode its response
+fn unary<B, Req, Resp, Fut>(
+    request: http::Request<B>,
+    handler: impl FnMut(Req) -> Fut + Send + 'static,
+) -> BoxFuture<http::Response<tonic::body::BoxBody>, Infallible>
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+    Req: prost::Message + Default + Send + 'static,
+    Resp: prost::Message + Send + 'static,
+    Fut: Future<Output = Result<Resp, Status>> + Send + 'static,
+{
+    Box::pin(async move {
+        let mut grpc = Grpc::new(tonic::codec::ProstCodec::<Resp, Req>::default());
+        Ok(grpc.unary(Unary(handler), request).await)
+    })
+}
+
+/// Response carrying only a gRPC status code
+fn status_only(code: Code) -> BoxFuture<http::Response<tonic::body::BoxBody>, Infallible> {
+    Box::pin(async move {
+        let response = http::Response::builder()
+            .header("grpc-status", code as i32)
+            .header(http::header::CONTENT_TYPE, tonic::metadata::GRPC_CONTENT_TYPE)
+            .body(empty_body())
+            .unwrap();
+        Ok(response)
+    })
+}
+
+/// Routes gRPC calls to the methods of [`AdminService`]
+#[derive(Clone)]
+pub struct AdminServer {
+    service: AdminService,
+    auth_token: Option<Arc<str>>,
+}
+
+impl AdminServer {
+    pub fn new(service: AdminService) -> Self {
+        Self { service, auth_token: None }
+    }
+
+    /// Require `authorization: Bearer <token>` metadata on the writing and
+    /// maintenance methods
+    pub fn with_auth_token(mut self, token: &str) -> Self {
+        self.auth_token = Some(Arc::from(token));
+        self
+    }
+}
+
+impl NamedService for AdminServer {
+    const NAME: &'static str = SERVICE_NAME;
+}
+
+impl<B> Service<http::Request<B>> for AdminServer
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<tonic::body::BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut TaskContext<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        let service = self.service.clone();
+        let method = request.uri().path().strip_prefix(&format!("/{}/", SERVICE_NAME)).map(str::to_string);
+        if let (Some(token), Some(method)) = (&self.auth_token, &method) {
+            let authorization = request.headers().get(http::header::AUTHORIZATION).and_then(|v| v.to_str().ok());
+            if MUTATING_METHODS.contains(&method.as_str()) && !auth::bearer_matches(token, authorization) {
+                return status_only(Code::Unauthenticated);
+            }
+        }
+        match method.as_deref() {
+            Some("WriteBatch") => unary(request, move |r: WriteBatchRequest| {
+                let service = service.clone();
+                async move { service.write_batch(r).await }
+            }),
+            Some("TriggerCompaction") => unary(request, move |r: TriggerCompactionRequest| {
+                let service = service.clone();
+                async move { service.trigger_compaction(r).await }
+            }),
+            Some("TriggerVacuum") => unary(request, move |r: TableRequest| {
+                let service = service.clone();
+                async move { service.trigger_vacuum(r).await }
+            }),
+            Some("GetMetrics") => unary(request, move |r: TableRequest| {
+                let service = service.clone();
+                async move { service.get_metrics(r).await }
+            }),
+            Some("GetStatus") => unary(request, move |r: TableRequest| {
+                let service = service.clone();
+                async move { service.get_status(r).await }
+            }),
+            _ => status_only(Code::Unimplemented),
+        }
+    }
+}
+
+/// Serve the admin API until `shutdown` is cancelled
+pub async fn serve(service: AdminService, config: GrpcConfig, shutdown: CancellationToken) -> Result<()> {
+    let addr = config
+        .bind_address
+        .parse()
+        .with_context(|| format!("Invalid gRPC bind address {}", config.bind_address))?;
+    log::info!("gRPC admin API listening on {}", addr);
+    auth::warn_if_exposed("gRPC admin API", &config.bind_address, config.auth_token.as_deref());
+
+    let mut server = AdminServer::new(service);
+    if let Some(token) = &config.auth_token {
+        server = server.with_auth_token(token);
+    }
+    Server::builder()
+        .add_service(server)
+        .serve_with_shutdown(addr, async move { shutdown.cancelled().await })
+        .await
+        .context("gRPC admin server failed")
+}
@@ -0,0 +1,153 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tokio::time::Instant;
+use crate::config::{AlertDestination, AlertKind, AlertRoute, AlertsConfig};
+
+/// PagerDuty Events API v2 endpoint
+const PAGERDUTY_EVENTS_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+
+/// An alert as delivered to webhooks
+#[derive(Debug, Clone, Serialize)]
+pub struct Alert {
+    pub kind: AlertKind,
+    pub table: String,
+    pub message: String,
+    pub at: DateTime<Utc>,
+    /// Same for every alert of this type and table, so receivers can group
+    /// repeats into one incident
+    pub dedup_key: String,
+    /// Alerts of this type held back by the cool-down since the last one
+    pub suppressed: u64,
+}
+
+/// Sends the alerts of one table to the destinations routed for them
+#[derive(Debug, Default)]
+pub struct Alerter {
+    table: String,
+    routes: Vec<AlertRoute>,
+    http: reqwest::Client,
+    /// Per route and alert type: when it last notified, and how many alerts
+    /// the cool-down has held back since
+    sent: Mutex<HashMap<(usize, AlertKind), (Instant, u64)>>,
+}
+
+impl Alerter {
+    /// Alerter for the table named `table` (the last segment of its URI)
+    pub fn new(config: AlertsConfig, table: &str) -> Self {
+        Self {
+            table: table.to_string(),
+            routes: config.routes,
+            ..Self::default()
+        }
+    }
+
+    /// Raise an alert, notifying every matching route not cooling down.
+    /// Delivery happens in the background; failures are only logged.
+    pub fn fire(&self, kind: AlertKind, message: impl Into<String>) {
+        let message = message.into();
+        let now = Instant::now();
+        for (index, route) in self.routes.iter().enumerate() {
+            if !route.matches(kind, &self.table) {
+                continue;
+            }
+            let suppressed = {
+                let mut sent = self.sent.lock().unwrap();
+                if let Some((last, suppressed)) = sent.get_mut(&(index, kind)) {
+                    if now.duration_since(*last) < route.cooldown() {
+                        *suppressed += 1;
+                        continue;
+                    }
+                }
+                sent.insert((index, kind), (now, 0)).map_or(0, |(_, suppressed)| suppressed)
+            };
+
+            let alert = Alert {
+                kind,
+                table: self.table.clone(),
+                message: message.clone(),
+                at: Utc::now(),
+                dedup_key: format!("{}/{}", self.table, kind_name(kind)),
+                suppressed,
+            };
+            let destination = route.destination.clone();
+            let http = self.http.clone();
+            tokio::spawn(async move {
+                if let Err(e) = deliver(&http, &destination, &alert).await {
+                    log::warn!("Failed to deliver {} alert for {}: {:#}", kind_name(alert.kind), alert.table, e);
+                }
+            });
+        }
+    }
+}
+
+async fn deliver(http: &reqwest::Client, destination: &AlertDestination, alert: &Alert) -> Result<()> {
+    match destination {
+        AlertDestination::Log => {
+            log::warn!(
+                "[alert {}] {}: {} ({} suppressed)",
+                kind_name(alert.kind),
+                alert.table,
+                alert.message,
+                alert.suppressed
+            );
+            Ok(())
+        }
+        AlertDestination::Webhook { url } => {
+            http.post(url)
+                .json(alert)
+                .send()
+                .await
+                .context("Failed to reach webhook")?
+                .error_for_status()
+                .context("Webhook rejected the alert")?;
+            Ok(())
+        }
+        AlertDestination::Pagerduty { routing_key } => {
+            let mut summary = format!("{}: {}", alert.table, alert.message);
+            if alert.suppressed > 0 {
+                summary.push_str(&format!(" ({} more since the last page)", alert.suppressed));
+            }
+            let event = json!({
+                "routing_key": routing_key,
+                "event_action": "trigger",
+                "dedup_key": alert.dedup_key,
+                "payload": {
+                    "summary": summary,
+                    "source": alert.table,
+                    "severity": severity(alert.kind),
+                    "component": kind_name(alert.kind),
+                    "timestamp": alert.at.to_rfc3339(),
+                },
+            });
+            http.post(PAGERDUTY_EVENTS_URL)
+                .json(&event)
+                .send()
+                .await
+                .context("Failed to reach PagerDuty")?
+                .error_for_status()
+                .context("PagerDuty rejected the event")?;
+            Ok(())
+        }
+    }
+}
+
+fn kind_name(kind: AlertKind) -> &'static str {
+    match kind {
+        AlertKind::SlaMiss => "sla_miss",
+        AlertKind::CircuitOpen => "circuit_open",
+        AlertKind::VacuumOverrun => "vacuum_overrun",
+        AlertKind::ValidationSpike => "validation_spike",
+    }
+}
+
+fn severity(kind: AlertKind) -> &'static str {
+    match kind {
+        AlertKind::CircuitOpen => "critical",
+        AlertKind::ValidationSpike => "error",
+        AlertKind::SlaMiss | AlertKind::VacuumOverrun => "warning",
+    }
+}
@@ -0,0 +1,224 @@
+use anyhow::{bail, Context, Result};
+use chrono::{NaiveDate, Utc};
+use deltalake::datafusion::prelude::{col, lit, SessionContext};
+use deltalake::kernel::Transaction;
+use deltalake::operations::transaction::CommitProperties;
+use deltalake::protocol::SaveMode;
+use deltalake::{DeltaOps, DeltaTable, DeltaTableError, StorageOptions};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::time::{interval, Instant};
+use tokio_util::sync::CancellationToken;
+use crate::compat;
+use crate::config::ArchiveConfig;
+
+/// The Archive process - moves cold partitions from the hot table into an
+/// archive table.
+///
+/// Each partition moves in two commits: an append to the archive table and a
+/// delete from the hot table. Both carry an application transaction naming
+/// the partition, so a crash between them is detected on the next pass and
+/// the pending delete is completed instead of archiving the rows twice.
+#[derive(Debug, Clone)]
+pub struct ArchiveProcess {
+    config: ArchiveConfig,
+    /// Hot table options merged with the archive's overrides
+    archive_storage_options: StorageOptions,
+}
+
+impl ArchiveProcess {
+    /// Create a new archive process
+    pub fn new(config: ArchiveConfig, storage_options: &StorageOptions) -> Self {
+        let mut options = storage_options.0.clone();
+        options.extend(config.storage_options.clone());
+        Self { config, archive_storage_options: StorageOptions(options) }
+    }
+
+    /// Main run loop for the archive process. A pass in progress when
+    /// shutdown is requested runs to completion before the loop exits.
+    pub async fn run(&self, table: Arc<Mutex<DeltaTable>>, shutdown: CancellationToken) -> Result<()> {
+        log::info!("Starting Archive process into {}", self.config.archive_table_uri);
+
+        let mut interval_timer = interval(self.config.archive_interval());
+
+        loop {
+            tokio::select! {
+                _ = interval_timer.tick() => {
+                    let start_time = Instant::now();
+                    let mut locked_table = table.lock().await;
+                    match self.run_once(&mut locked_table).await {
+                        Ok(report) => log::info!("Archive completed in {:?}: {}", start_time.elapsed(), report),
+                        Err(e) => log::error!("Archive cycle failed: {}", e),
+                    }
+                }
+                _ = shutdown.cancelled() => {
+                    log::info!("Archive process received shutdown signal");
+                    break;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Archive every partition older than `max_age_days`
+    pub async fn run_once(&self, table: &mut DeltaTable) -> Result<ArchiveReport> {
+        table.update().await
+            .context("Failed to refresh table before archive")?;
+        compat::ensure_writable(table)?;
+
+        let column = &self.config.partition_column;
+        if !table.metadata()?.partition_columns.contains(column) {
+            bail!("Archive column '{}' is not a partition column of {}", column, table.table_uri());
+        }
+
+        let mut archive = self.open_archive().await?;
+        let mut report = ArchiveReport::default();
+
+        // Finish a partition that reached the archive but was not yet deleted
+        let archived = archive.as_ref().and_then(|a| txn_version(a, &self.archive_app_id(table)));
+        let deleted = txn_version(table, &self.hot_app_id());
+        if let Some(day) = archived.filter(|day| Some(*day) != deleted) {
+            let value = self.format_day(day);
+            log::warn!("Completing interrupted archive of partition {}={}", column, value);
+            self.delete_partition(table, &value, day).await?;
+            report.resumed = Some(value);
+        }
+
+        let cutoff = Utc::now().date_naive() - chrono::Duration::days(self.config.max_age_days as i64);
+
+        for (day, value) in self.cold_partitions(table, cutoff)? {
+            let batches = SessionContext::new()
+                .read_table(Arc::new(table.clone()))
+                .context("Failed to register hot table")?
+                .filter(col(column.as_str()).eq(lit(value.as_str())))?
+                .collect()
+                .await
+                .with_context(|| format!("Failed to read partition {}={}", column, value))?;
+            let rows: usize = batches.iter().map(|b| b.num_rows()).sum();
+
+            let commit = CommitProperties::default()
+                .with_application_transaction(Transaction::new(self.archive_app_id(table), day));
+            let ops = match archive.take() {
+                Some(archive) => DeltaOps(archive),
+                None => DeltaOps::try_from_uri_with_storage_options(
+                    &self.config.archive_table_uri,
+                    self.archive_storage_options.0.clone(),
+                )
+                .await?,
+            };
+            let updated = ops
+                .write(batches)
+                .with_save_mode(SaveMode::Append)
+                .with_partition_columns(vec![column.clone()])
+                .with_commit_properties(commit)
+                .await
+                .with_context(|| format!("Failed to write partition {}={} to archive", column, value))?;
+            archive = Some(updated);
+
+            self.delete_partition(table, &value, day).await?;
+
+            log::info!("Archived partition {}={} ({} rows)", column, value, rows);
+            report.partitions.push(value);
+            report.rows_archived += rows;
+        }
+
+        Ok(report)
+    }
+
+    /// Open the archive table, or `None` if the first archive write will create it
+    async fn open_archive(&self) -> Result<Option<DeltaTable>> {
+        match deltalake::open_table_with_storage_options(
+            &self.config.archive_table_uri,
+            self.archive_storage_options.0.clone(),
+        )
+        .await
+        {
+            Ok(table) => Ok(Some(table)),
+            Err(DeltaTableError::NotATable(_)) => Ok(None),
+            Err(e) => Err(e).context("Failed to open archive table"),
+        }
+    }
+
+    /// Remove an archived partition from the hot table
+    async fn delete_partition(&self, table: &mut DeltaTable, value: &str, day: i64) -> Result<()> {
+        let commit = CommitProperties::default()
+            .with_application_transaction(Transaction::new(self.hot_app_id(), day));
+        let (updated, _) = DeltaOps(table.clone())
+            .delete()
+            .with_predicate(format!("\"{}\" = '{}'", self.config.partition_column, value))
+            .with_commit_properties(commit)
+            .await
+            .with_context(|| format!("Failed to delete archived partition {}", value))?;
+        *table = updated;
+        Ok(())
+    }
+
+    /// Partitions whose date is before `cutoff`, oldest first
+    fn cold_partitions(&self, table: &DeltaTable, cutoff: NaiveDate) -> Result<BTreeMap<i64, String>> {
+        let mut partitions = BTreeMap::new();
+        for add in table.snapshot()?.file_actions()? {
+            let Some(Some(value)) = add.partition_values.get(&self.config.partition_column) else {
+                continue;
+            };
+            match NaiveDate::parse_from_str(value, &self.config.partition_format) {
+                Ok(date) if date < cutoff => {
+                    partitions.insert(day_number(date), value.clone());
+                }
+                Ok(_) => {}
+                Err(_) => log::debug!("Skipping partition value '{}' not matching format", value),
+            }
+        }
+        Ok(partitions)
+    }
+
+    fn format_day(&self, day: i64) -> String {
+        (NaiveDate::UNIX_EPOCH + chrono::Duration::days(day))
+            .format(&self.config.partition_format)
+            .to_string()
+    }
+
+    /// Application id recorded in the archive table for partitions taken from `hot`
+    fn archive_app_id(&self, hot: &DeltaTable) -> String {
+        format!("archive:{}", hot.table_uri())
+    }
+
+    /// Application id recorded in the hot table for partitions it has handed off
+    fn hot_app_id(&self) -> String {
+        format!("archive:{}", self.config.archive_table_uri)
+    }
+}
+
+/// Days since the Unix epoch, used as the application transaction version
+fn day_number(date: NaiveDate) -> i64 {
+    (date - NaiveDate::UNIX_EPOCH).num_days()
+}
+
+fn txn_version(table: &DeltaTable, app_id: &str) -> Option<i64> {
+    table.get_app_transaction_version().get(app_id).map(|txn| txn.version)
+}
+
+/// Partitions moved by an archive pass
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveReport {
+    /// Partition values moved in this pass, oldest first
+    pub partitions: Vec<String>,
+    pub rows_archived: usize,
+    /// Partition whose hot-table delete was completed after an interrupted pass
+    pub resumed: Option<String>,
+}
+
+impl fmt::Display for ArchiveReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(value) = &self.resumed {
+            write!(f, "completed interrupted partition {}; ", value)?;
+        }
+        write!(f, "archived {} partitions ({} rows)", self.partitions.len(), self.rows_archived)?;
+        if !self.partitions.is_empty() {
+            write!(f, ": {}", self.partitions.join(", "))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,38 @@
+//! Shared-secret authentication of the endpoints that change what a running
+//! orchestrator does: HTTP writes and controls, gRPC writes and maintenance,
+//! and the replication stream.
+
+/// Scheme of the `Authorization` header carrying the token
+const BEARER_PREFIX: &str = "Bearer ";
+
+/// `Authorization` header value presenting `token`
+pub fn bearer(token: &str) -> String {
+    format!("{}{}", BEARER_PREFIX, token)
+}
+
+/// Whether an `Authorization` header value presents `token` as a bearer token
+pub fn bearer_matches(token: &str, authorization: Option<&str>) -> bool {
+    authorization
+        .and_then(|value| value.strip_prefix(BEARER_PREFIX))
+        .is_some_and(|presented| token_matches(token, presented.trim()))
+}
+
+/// Compare tokens in time that does not depend on where they first differ
+pub fn token_matches(expected: &str, presented: &str) -> bool {
+    let (expected, presented) = (expected.as_bytes(), presented.as_bytes());
+    if expected.len() != presented.len() {
+        return false;
+    }
+    expected.iter().zip(presented).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Warn when an endpoint without a token listens beyond the loopback interface
+pub fn warn_if_exposed(what: &str, address: &str, token: Option<&str>) {
+    let loopback = address
+        .parse::<std::net::SocketAddr>()
+        .map(|address| address.ip().is_loopback())
+        .unwrap_or(address.starts_with("localhost:"));
+    if token.is_none() && !loopback {
+        log::warn!("{} on {} accepts unauthenticated requests; set an auth_token", what, address);
+    }
+}
@@ -0,0 +1,117 @@
+use anyhow::{Context, Result};
+use deltalake::checkpoints::create_checkpoint;
+use deltalake::{DeltaTable, ObjectStoreError, Path};
+use serde::Deserialize;
+use std::fmt;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::time::interval;
+use tokio_util::sync::CancellationToken;
+use crate::config::CheckpointConfig;
+
+/// Outcome of a checkpoint pass
+#[derive(Debug, Clone)]
+pub struct CheckpointReport {
+    pub table_version: i64,
+    /// Version of the checkpoint in place before this pass
+    pub previous_checkpoint: Option<i64>,
+    /// Whether a new checkpoint was written at `table_version`
+    pub created: bool,
+}
+
+impl fmt::Display for CheckpointReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let previous = match self.previous_checkpoint {
+            Some(version) => format!("version {}", version),
+            None => "none".to_string(),
+        };
+        if self.created {
+            writeln!(f, "Checkpoint written at version {} (previous: {})", self.table_version, previous)
+        } else {
+            writeln!(f, "No checkpoint needed at version {} (previous: {})", self.table_version, previous)
+        }
+    }
+}
+
+/// The subset of `_delta_log/_last_checkpoint` we need
+#[derive(Debug, Deserialize)]
+struct LastCheckpoint {
+    version: i64,
+}
+
+/// The Checkpoint process - writes a Parquet checkpoint every
+/// `checkpoint_interval` commits so readers replay few JSON log entries
+#[derive(Debug, Clone)]
+pub struct CheckpointProcess {
+    config: CheckpointConfig,
+}
+
+impl CheckpointProcess {
+    pub fn new(config: CheckpointConfig) -> Self {
+        Self { config }
+    }
+
+    /// Main run loop; disabled when `checkpoint_interval` is 0
+    pub async fn run(&self, table: Arc<Mutex<DeltaTable>>, shutdown: CancellationToken) -> Result<()> {
+        if self.config.checkpoint_interval == 0 {
+            return Ok(());
+        }
+        log::info!("Starting Checkpoint process (every {} commits)", self.config.checkpoint_interval);
+
+        let mut interval_timer = interval(self.config.check_interval());
+        loop {
+            tokio::select! {
+                _ = interval_timer.tick() => {
+                    let mut locked_table = table.lock().await;
+                    match self.run_once(&mut locked_table, false).await {
+                        Ok(report) if report.created => log::info!("{}", report.to_string().trim_end()),
+                        Ok(_) => {}
+                        Err(e) => log::error!("Checkpoint cycle failed: {:#}", e),
+                    }
+                }
+                _ = shutdown.cancelled() => {
+                    log::info!("Checkpoint process received shutdown signal");
+                    break;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write a checkpoint at the latest version if `checkpoint_interval`
+    /// commits have accumulated since the last one, or unconditionally with `force`
+    pub async fn run_once(&self, table: &mut DeltaTable, force: bool) -> Result<CheckpointReport> {
+        table.update().await
+            .context("Failed to refresh table before checkpoint")?;
+
+        let table_version = table.version();
+        let previous_checkpoint = last_checkpoint_version(table).await?;
+        let commits_since = table_version - previous_checkpoint.unwrap_or(-1);
+        let due = if force {
+            previous_checkpoint != Some(table_version)
+        } else {
+            commits_since >= self.config.checkpoint_interval as i64
+        };
+
+        if due {
+            create_checkpoint(table, None)
+                .await
+                .with_context(|| format!("Failed to write checkpoint at version {}", table_version))?;
+        }
+
+        Ok(CheckpointReport { table_version, previous_checkpoint, created: due })
+    }
+}
+
+/// Version recorded in `_delta_log/_last_checkpoint`, if the table has one
+async fn last_checkpoint_version(table: &DeltaTable) -> Result<Option<i64>> {
+    let path = Path::from("_delta_log/_last_checkpoint");
+    let bytes = match table.log_store().object_store(None).get(&path).await {
+        Ok(result) => result.bytes().await?,
+        Err(ObjectStoreError::NotFound { .. }) => return Ok(None),
+        Err(e) => return Err(e).context("Failed to read _last_checkpoint"),
+    };
+    let last: LastCheckpoint = serde_json::from_slice(&bytes).context("Invalid _last_checkpoint")?;
+    Ok(Some(last.version))
+}
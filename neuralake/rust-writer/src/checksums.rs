@@ -0,0 +1,238 @@
+use anyhow::{Context, Result};
+use deltalake::logstore::LogStoreRef;
+use deltalake::{DeltaTable, ObjectMeta, ObjectStore, ObjectStoreError, Path};
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use crate::inventory::FileInventory;
+use crate::partition_metrics::read_commit_adds;
+
+/// Directory of the checksum manifests below the table root; vacuum and
+/// partition cleanup leave `_`-prefixed directories alone
+pub const CHECKSUMS_DIR: &str = "_checksums";
+
+/// Checksum of one data file as stored right after its commit
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileChecksum {
+    /// Path relative to the table root, as in the Add action
+    pub path: String,
+    pub size: u64,
+    /// Entity tag the store assigned on upload; any rewrite changes it
+    pub e_tag: Option<String>,
+    /// Hex SHA-256 of the contents, when digests are enabled
+    pub sha256: Option<String>,
+}
+
+/// Checksums of the files added by one commit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChecksumManifest {
+    pub version: i64,
+    pub files: Vec<FileChecksum>,
+}
+
+/// What is wrong with a stored data file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FileProblem {
+    /// Referenced by the table but absent from the store
+    Missing,
+    /// Size differs from the Add action or the manifest
+    SizeMismatch { expected: u64, actual: u64 },
+    /// Same size, but the object was rewritten since its commit
+    Modified { expected: String, actual: String },
+    /// Contents no longer match the recorded digest
+    Corrupt { expected: String, actual: String },
+}
+
+impl fmt::Display for FileProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing => write!(f, "missing"),
+            Self::SizeMismatch { expected, actual } => write!(f, "size {} (expected {})", actual, expected),
+            Self::Modified { expected, actual } => write!(f, "etag {} (expected {})", actual, expected),
+            Self::Corrupt { expected, actual } => write!(f, "sha256 {} (expected {})", actual, expected),
+        }
+    }
+}
+
+/// Result of checking the active files of a table against the store
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct VerifyReport {
+    pub version: i64,
+    pub files_checked: usize,
+    /// Files checked against a manifest entry, rather than size alone
+    pub files_with_checksums: usize,
+    pub problems: Vec<(String, FileProblem)>,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+impl fmt::Display for VerifyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Verified {} files at version {} ({} against checksums): {} problems",
+            self.files_checked,
+            self.version,
+            self.files_with_checksums,
+            self.problems.len()
+        )?;
+        for (path, problem) in &self.problems {
+            writeln!(f, "  {}: {}", path, problem)?;
+        }
+        Ok(())
+    }
+}
+
+/// Record the checksums of the files added by commit `version` in its
+/// manifest. With `sha256` every file is read back to digest it.
+pub async fn record_commit(log_store: &LogStoreRef, version: i64, sha256: bool) -> Result<ChecksumManifest> {
+    let (_, adds) = read_commit_adds(log_store, version).await?;
+    let store = log_store.object_store(None);
+    let mut files = Vec::with_capacity(adds.len());
+    for add in adds {
+        files.push(
+            checksum(store.as_ref(), &add.path, sha256)
+                .await
+                .with_context(|| format!("Failed to checksum {}", add.path))?,
+        );
+    }
+
+    let manifest = ChecksumManifest { version, files };
+    let location = manifest_path(version);
+    store
+        .put(&location, serde_json::to_vec(&manifest)?.into())
+        .await
+        .with_context(|| format!("Failed to write checksum manifest {}", location))?;
+    Ok(manifest)
+}
+
+/// Record manifests for the commits in `from..=to` that have none yet,
+/// returning how many were written. Covers compaction commits and any a
+/// crashed writer did not get to.
+pub async fn record_missing(log_store: &LogStoreRef, from: i64, to: i64, sha256: bool) -> Result<usize> {
+    let store = log_store.object_store(None);
+    let mut written = 0;
+    for version in from..=to {
+        match store.head(&manifest_path(version)).await {
+            Ok(_) => continue,
+            Err(ObjectStoreError::NotFound { .. }) => {}
+            Err(e) => return Err(e).context("Failed to look up checksum manifest"),
+        }
+        record_commit(log_store, version, sha256).await?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+/// Check that every active file of `table` exists with the size its Add
+/// action records, going by `inventory` rather than a request per file.
+/// With `checksums`, also compare each file that has a manifest entry
+/// against its stored entity tag and digest.
+pub async fn verify(table: &DeltaTable, inventory: &FileInventory, checksums: bool) -> Result<VerifyReport> {
+    let store = table.object_store();
+    let recorded = if checksums { load_manifests(store.as_ref()).await? } else { HashMap::new() };
+
+    let mut report = VerifyReport { version: table.version(), ..Default::default() };
+    for add in table.snapshot()?.file_actions()? {
+        report.files_checked += 1;
+        let location = Path::from(add.path.as_str());
+        // Files committed after the inventory was listed need a HEAD request
+        let found = match inventory.get(&add.path) {
+            Some(meta) => Ok(meta.clone()),
+            None => store.head(&location).await,
+        };
+        let meta = match found {
+            Ok(meta) => meta,
+            Err(ObjectStoreError::NotFound { .. }) => {
+                report.problems.push((add.path, FileProblem::Missing));
+                continue;
+            }
+            Err(e) => return Err(e).with_context(|| format!("Failed to stat {}", add.path)),
+        };
+        if meta.size as u64 != add.size as u64 {
+            let problem = FileProblem::SizeMismatch { expected: add.size as u64, actual: meta.size as u64 };
+            report.problems.push((add.path, problem));
+            continue;
+        }
+
+        let Some(expected) = recorded.get(&add.path) else { continue };
+        report.files_with_checksums += 1;
+        if let Some(problem) = compare(store.as_ref(), expected, &meta).await? {
+            report.problems.push((add.path, problem));
+        }
+    }
+    Ok(report)
+}
+
+/// A recorded digest decides on its own, since copies between buckets
+/// legitimately get new entity tags; otherwise a changed tag is reported
+async fn compare(store: &dyn ObjectStore, expected: &FileChecksum, meta: &ObjectMeta) -> Result<Option<FileProblem>> {
+    if meta.size as u64 != expected.size {
+        return Ok(Some(FileProblem::SizeMismatch { expected: expected.size, actual: meta.size as u64 }));
+    }
+    if let Some(digest) = &expected.sha256 {
+        let actual = sha256_of(store, &meta.location).await?;
+        return Ok((&actual != digest).then(|| FileProblem::Corrupt { expected: digest.clone(), actual }));
+    }
+    Ok(match (&expected.e_tag, &meta.e_tag) {
+        (Some(expected), Some(actual)) if expected != actual => {
+            Some(FileProblem::Modified { expected: expected.clone(), actual: actual.clone() })
+        }
+        _ => None,
+    })
+}
+
+async fn checksum(store: &dyn ObjectStore, path: &str, sha256: bool) -> Result<FileChecksum> {
+    let location = Path::from(path);
+    let meta = store.head(&location).await?;
+    let sha256 = if sha256 { Some(sha256_of(store, &location).await?) } else { None };
+    Ok(FileChecksum { path: path.to_string(), size: meta.size as u64, e_tag: meta.e_tag, sha256 })
+}
+
+async fn sha256_of(store: &dyn ObjectStore, location: &Path) -> Result<String> {
+    let bytes = store
+        .get(location)
+        .await
+        .with_context(|| format!("Failed to read {}", location))?
+        .bytes()
+        .await
+        .with_context(|| format!("Failed to read {}", location))?;
+    Ok(format!("{:x}", Sha256::digest(&bytes)))
+}
+
+/// Every recorded checksum by path; later manifests win
+async fn load_manifests(store: &dyn ObjectStore) -> Result<HashMap<String, FileChecksum>> {
+    let prefix = Path::from(CHECKSUMS_DIR);
+    let mut objects: Vec<ObjectMeta> = store
+        .list(Some(&prefix))
+        .try_collect()
+        .await
+        .context("Failed to list checksum manifests")?;
+    // Zero-padded versions sort numerically
+    objects.sort_by(|a, b| a.location.cmp(&b.location));
+
+    let mut recorded = HashMap::new();
+    for meta in objects {
+        let bytes = store
+            .get(&meta.location)
+            .await
+            .with_context(|| format!("Failed to read {}", meta.location))?
+            .bytes()
+            .await?;
+        let manifest: ChecksumManifest = serde_json::from_slice(&bytes)
+            .with_context(|| format!("Invalid checksum manifest {}", meta.location))?;
+        recorded.extend(manifest.files.into_iter().map(|file| (file.path.clone(), file)));
+    }
+    Ok(recorded)
+}
+
+fn manifest_path(version: i64) -> Path {
+    Path::from(format!("{}/{:020}.json", CHECKSUMS_DIR, version))
+}
@@ -0,0 +1,251 @@
+use anyhow::{anyhow, bail, Context, Result};
+use polars::prelude::*;
+use reqwest::{header, StatusCode};
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::time::{sleep, Instant};
+use crate::auth;
+use crate::correlation::new_batch_id;
+use crate::maintenance::Maintenance;
+use crate::metrics::BatchReceipt;
+use crate::retry::{is_retryable, non_retryable};
+use crate::server::{TableSummary, ARROW_STREAM_CONTENT_TYPE, BATCH_ID_HEADER};
+
+/// How often [`IngestClient::wait_for_commit`] asks for a receipt
+const RECEIPT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Acknowledgement that the server queued a batch for its writer
+#[derive(Debug, Clone, Deserialize)]
+pub struct IngestAck {
+    pub table: String,
+    pub rows: usize,
+    pub batch_id: String,
+}
+
+/// Client for the HTTP ingestion API (`POST /ingest/{table}`), for producer
+/// services. Batches are sent as Arrow IPC streams, zstd-compressed by
+/// default, and retried on connection errors, 429s and 5xx responses.
+///
+/// Every attempt carries the same batch id, chosen before the first one.
+/// A retry after a lost response can queue the batch twice; the duplicate
+/// commits under the same id, so consumers can tell them apart.
+#[derive(Debug, Clone)]
+pub struct IngestClient {
+    http: reqwest::Client,
+    base_url: String,
+    compression: Option<IpcCompression>,
+    max_retries: u32,
+    retry_delay: Duration,
+    max_retry_delay: Duration,
+    auth_token: Option<String>,
+}
+
+impl IngestClient {
+    /// Client for the API served at `base_url`, e.g. `http://writer:8080`
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            compression: Some(IpcCompression::ZSTD),
+            max_retries: 3,
+            retry_delay: Duration::from_millis(100),
+            max_retry_delay: Duration::from_secs(5),
+            auth_token: None,
+        }
+    }
+
+    /// Compress the Arrow buffers with `compression`, or send them as is
+    pub fn with_compression(mut self, compression: Option<IpcCompression>) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Retry a failed request up to `max_retries` times, doubling `delay`
+    /// after each attempt
+    pub fn with_retries(mut self, max_retries: u32, delay: Duration) -> Self {
+        self.max_retries = max_retries;
+        self.retry_delay = delay;
+        self
+    }
+
+    /// Use a preconfigured HTTP client (timeouts, TLS, proxies)
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Present `token` to a server configured with `http.auth_token`
+    pub fn with_auth_token(mut self, token: impl Into<String>) -> Self {
+        self.auth_token = Some(token.into());
+        self
+    }
+
+    /// Queue the rows of `df` for `table`, returning once the server has
+    /// accepted them. The rows are committed asynchronously.
+    pub async fn write(&self, table: &str, mut df: DataFrame) -> Result<IngestAck> {
+        let mut payload = Vec::new();
+        IpcStreamWriter::new(&mut payload)
+            .with_compression(self.compression)
+            .finish(&mut df)
+            .context("Failed to encode batch as Arrow IPC")?;
+        let payload = axum::body::Bytes::from(payload);
+        let batch_id = new_batch_id();
+        let url = format!("{}/ingest/{}", self.base_url, table);
+
+        let (payload, batch_id, url) = (&payload, &batch_id, &url);
+        self.retrying(&format!("ingest batch {} into {}", batch_id, table), move || async move {
+            let response = authorize(self.http.post(url), self.auth_token.as_deref())
+                .header(header::CONTENT_TYPE, ARROW_STREAM_CONTENT_TYPE)
+                .header(BATCH_ID_HEADER, batch_id)
+                .body(payload.clone())
+                .send()
+                .await
+                .context("Failed to reach the ingestion API")?;
+            let response = check_status(response).await?;
+            response.json::<IngestAck>().await.context("Invalid ingestion response")
+        })
+        .await
+    }
+
+    /// [`Self::write`], then wait up to `timeout` for the batch to be committed
+    pub async fn write_and_wait(&self, table: &str, df: DataFrame, timeout: Duration) -> Result<BatchReceipt> {
+        let ack = self.write(table, df).await?;
+        self.wait_for_commit(table, &ack.batch_id, timeout).await
+    }
+
+    /// Wait up to `timeout` for the table version containing `batch_id`
+    pub async fn wait_for_commit(&self, table: &str, batch_id: &str, timeout: Duration) -> Result<BatchReceipt> {
+        let url = format!("{}/tables/{}/batches/{}", self.base_url, table, batch_id);
+        let deadline = Instant::now() + timeout;
+        loop {
+            let response = self.http.get(&url).send().await;
+            match response {
+                Ok(response) if response.status() == StatusCode::NOT_FOUND => {}
+                Ok(response) => {
+                    let response = check_status(response).await?;
+                    return response.json().await.context("Invalid batch receipt");
+                }
+                Err(e) => log::debug!("Failed to look up batch {}: {}", batch_id, e),
+            }
+            if Instant::now() + RECEIPT_POLL_INTERVAL > deadline {
+                bail!("Batch {} was not committed within {:?}", batch_id, timeout);
+            }
+            sleep(RECEIPT_POLL_INTERVAL).await;
+        }
+    }
+
+    async fn retrying<T, F, Fut>(&self, what: &str, mut attempt: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = Result<T>>,
+    {
+        let mut retries = 0;
+        let mut delay = self.retry_delay;
+        loop {
+            match attempt().await {
+                Ok(value) => return Ok(value),
+                Err(e) if retries < self.max_retries && is_retryable(&e) => {
+                    retries += 1;
+                    log::warn!("Failed to {} (attempt {}), retrying in {:?}: {:#}", what, retries, delay, e);
+                    sleep(delay).await;
+                    delay = (delay * 2).min(self.max_retry_delay);
+                }
+                Err(e) => return Err(e).with_context(|| format!("Failed to {}", what)),
+            }
+        }
+    }
+}
+
+/// Client for the admin endpoints of a running orchestrator, used by the
+/// `remote` commands instead of opening the tables themselves
+#[derive(Debug, Clone)]
+pub struct ControlClient {
+    http: reqwest::Client,
+    base_url: String,
+    auth_token: Option<String>,
+}
+
+impl ControlClient {
+    /// Client for the API served at `base_url`, e.g. `http://localhost:8080`
+    pub fn new(base_url: &str) -> Self {
+        Self { http: reqwest::Client::new(), base_url: base_url.trim_end_matches('/').to_string(), auth_token: None }
+    }
+
+    /// Present `token` to a server configured with `http.auth_token`
+    pub fn with_auth_token(mut self, token: impl Into<String>) -> Self {
+        self.auth_token = Some(token.into());
+        self
+    }
+
+    /// Served tables with queue depth, freshness and whether they are paused
+    pub async fn tables(&self) -> Result<Vec<TableSummary>> {
+        let response = self.http.get(format!("{}/tables", self.base_url)).send().await
+            .context("Failed to reach the orchestrator")?;
+        check_status(response).await?.json().await.context("Invalid table list")
+    }
+
+    /// Process and per-table counters, as served by `GET /metrics`
+    pub async fn metrics(&self) -> Result<serde_json::Value> {
+        let response = self.http.get(format!("{}/metrics", self.base_url)).send().await
+            .context("Failed to reach the orchestrator")?;
+        check_status(response).await?.json().await.context("Invalid metrics")
+    }
+
+    /// Flush the table's pending batches now
+    pub async fn flush(&self, table: &str) -> Result<TableSummary> {
+        self.post(table, "flush").await
+    }
+
+    /// Stop the table's writer and sources until [`Self::resume`]
+    pub async fn pause(&self, table: &str) -> Result<TableSummary> {
+        self.post(table, "pause").await
+    }
+
+    pub async fn resume(&self, table: &str) -> Result<TableSummary> {
+        self.post(table, "resume").await
+    }
+
+    /// Skip the table's scheduled `process` cycles until resumed
+    pub async fn pause_maintenance(&self, table: &str, process: Maintenance) -> Result<TableSummary> {
+        self.post(table, &format!("maintenance/{}/pause", process)).await
+    }
+
+    pub async fn resume_maintenance(&self, table: &str, process: Maintenance) -> Result<TableSummary> {
+        self.post(table, &format!("maintenance/{}/resume", process)).await
+    }
+
+    async fn post(&self, table: &str, action: &str) -> Result<TableSummary> {
+        let url = format!("{}/tables/{}/{}", self.base_url, table, action);
+        let response = authorize(self.http.post(url), self.auth_token.as_deref())
+            .send()
+            .await
+            .context("Failed to reach the orchestrator")?;
+        check_status(response)
+            .await
+            .with_context(|| format!("Failed to {} {}", action, table))?
+            .json()
+            .await
+            .context("Invalid table summary")
+    }
+}
+
+fn authorize(request: reqwest::RequestBuilder, token: Option<&str>) -> reqwest::RequestBuilder {
+    match token {
+        Some(token) => request.header(header::AUTHORIZATION, auth::bearer(token)),
+        None => request,
+    }
+}
+
+/// Client errors other than throttling fail the same way when repeated
+async fn check_status(response: reqwest::Response) -> Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.text().await.unwrap_or_default();
+    let error = anyhow!("HTTP API returned {}: {}", status, body.trim());
+    if status.is_client_error() && status != StatusCode::TOO_MANY_REQUESTS {
+        return Err(non_retryable(error));
+    }
+    Err(error)
+}
@@ -0,0 +1,171 @@
+use anyhow::{anyhow, Result};
+use deltalake::arrow::datatypes::SchemaRef;
+use deltalake::kernel::Add;
+use deltalake::operations::transaction::CommitProperties;
+use deltalake::DeltaTable;
+use serde_json::Value;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::oneshot;
+use crate::retry;
+use crate::writer::WriterProcess;
+
+/// Commit info key listing the batches whose rows a commit holds
+const BATCH_IDS_KEY: &str = "batchIds";
+
+/// Groups the files of flushes finishing within `commit_interval_ms` of each
+/// other into a single commit, so bursts of small batches do not each add a
+/// version to the log.
+///
+/// The first flush to arrive leads the group: it waits out the interval,
+/// then commits its own files together with those of every flush that joined
+/// meanwhile and hands them the result. Only flushes with the leader's batch
+/// schema and commit info (such as its aligned flush window) join; one that
+/// differs commits on its own. Callers still only return once their files
+/// are committed.
+#[derive(Debug)]
+pub struct CommitCoalescer {
+    interval: Duration,
+    /// Group of the current leader; None while there is no leader
+    group: Mutex<Option<Group>>,
+}
+
+#[derive(Debug)]
+struct Group {
+    /// Commit info shared by every flush of the group, without batch ids
+    commit_info: Vec<(String, Value)>,
+    schema: SchemaRef,
+    followers: Vec<Follower>,
+}
+
+#[derive(Debug)]
+struct Follower {
+    adds: Vec<Add>,
+    batch_ids: Vec<Value>,
+    done: oneshot::Sender<Result<DeltaTable>>,
+}
+
+/// How a flush takes part in the next commit
+enum Role {
+    Leader,
+    Follower(oneshot::Receiver<Result<DeltaTable>>),
+    /// The open group is for another schema or flush window
+    Alone,
+}
+
+/// Releases the followers of a leader that goes away before committing
+struct Leadership<'a>(&'a CommitCoalescer);
+
+impl Drop for Leadership<'_> {
+    fn drop(&mut self) {
+        self.0.group.lock().unwrap().take();
+    }
+}
+
+impl CommitCoalescer {
+    pub fn new(interval: Duration) -> Self {
+        Self { interval, group: Mutex::new(None) }
+    }
+
+    /// Commit `adds` as part of the next group commit, returning the table
+    /// at the version that contains them
+    pub(crate) async fn commit(
+        &self,
+        writer: &WriterProcess,
+        mut table: DeltaTable,
+        mut adds: Vec<Add>,
+        commit_info: &[(String, Value)],
+        batch_schema: &SchemaRef,
+    ) -> Result<DeltaTable> {
+        let (mut batch_ids, shared_info) = split_batch_ids(commit_info);
+        let role = {
+            let mut group = self.group.lock().unwrap();
+            match group.as_mut() {
+                Some(group) if group.commit_info == shared_info && &group.schema == batch_schema => {
+                    let (done, result) = oneshot::channel();
+                    group.followers.push(Follower { adds: std::mem::take(&mut adds), batch_ids: std::mem::take(&mut batch_ids), done });
+                    Role::Follower(result)
+                }
+                Some(_) => Role::Alone,
+                None => {
+                    *group = Some(Group {
+                        commit_info: shared_info.clone(),
+                        schema: batch_schema.clone(),
+                        followers: Vec::new(),
+                    });
+                    Role::Leader
+                }
+            }
+        };
+
+        match role {
+            Role::Follower(result) => {
+                return result.await.unwrap_or_else(|_| Err(anyhow!("Coalesced commit was abandoned")));
+            }
+            Role::Alone => {
+                let commit_properties = CommitProperties::default().with_metadata(commit_info.to_vec());
+                writer.commit_files(&mut table, adds, None, batch_schema, &[], commit_properties).await?;
+                return Ok(table);
+            }
+            Role::Leader => {}
+        }
+
+        let leadership = Leadership(self);
+        tokio::time::sleep(self.interval).await;
+        let followers = self.group.lock().unwrap().take().map(|group| group.followers).unwrap_or_default();
+        drop(leadership);
+
+        let mut waiting = Vec::with_capacity(followers.len());
+        for follower in followers {
+            adds.extend(follower.adds);
+            batch_ids.extend(follower.batch_ids);
+            waiting.push(follower.done);
+        }
+        if !waiting.is_empty() {
+            log::debug!("Coalescing {} flushes into one commit", waiting.len() + 1);
+        }
+
+        let mut commit_info = shared_info;
+        if !batch_ids.is_empty() {
+            commit_info.push((BATCH_IDS_KEY.to_string(), Value::Array(batch_ids)));
+        }
+        let commit_properties = CommitProperties::default().with_metadata(commit_info);
+        match writer.commit_files(&mut table, adds, None, batch_schema, &[], commit_properties).await {
+            Ok(_) => {
+                for done in waiting {
+                    let _ = done.send(Ok(table.clone()));
+                }
+                Ok(table)
+            }
+            Err(e) => {
+                for done in waiting {
+                    let _ = done.send(Err(follower_error(&e)));
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+/// The batch ids of a flush's commit info, and its other entries
+fn split_batch_ids(commit_info: &[(String, Value)]) -> (Vec<Value>, Vec<(String, Value)>) {
+    let mut batch_ids = Vec::new();
+    let mut rest = Vec::with_capacity(commit_info.len());
+    for (key, value) in commit_info {
+        match value {
+            Value::Array(ids) if key == BATCH_IDS_KEY => batch_ids.extend(ids.iter().cloned()),
+            _ => rest.push((key.clone(), value.clone())),
+        }
+    }
+    (batch_ids, rest)
+}
+
+/// The leader's commit error as handed to a follower, keeping whether it is
+/// worth retrying so that guardrail or permanent failures are not retried
+fn follower_error(error: &anyhow::Error) -> anyhow::Error {
+    let copy = anyhow!("{:#}", error);
+    match retry::is_retryable(error) {
+        true => copy,
+        false => retry::non_retryable(copy),
+    }
+}
@@ -0,0 +1,165 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use deltalake::kernel::Action;
+use deltalake::{DeltaTable, StorageOptions};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use tokio::sync::broadcast;
+use tokio::time::interval;
+use tokio_util::sync::CancellationToken;
+use crate::config::CommitFeedConfig;
+use crate::quality::QualityStatusHandle;
+use crate::spark_history::spark_operation_metrics;
+
+/// A commit observed in the Delta log
+#[derive(Debug, Clone, Serialize)]
+pub struct CommitNotification {
+    pub table_uri: String,
+    pub version: i64,
+    pub timestamp: Option<DateTime<Utc>>,
+    pub operation: Option<String>,
+    /// Metrics in Spark's shape, whichever engine made the commit
+    pub operation_metrics: BTreeMap<String, String>,
+    /// Data files added by the commit
+    pub files_added: usize,
+    /// Rows in the added files; `None` if any file lacks statistics
+    pub rows_added: Option<i64>,
+    /// Largest `watermark_column` value across the added files
+    pub watermark: Option<Value>,
+}
+
+/// Follows the Delta log and broadcasts every new commit, whichever process
+/// wrote it, to in-process subscribers and the HTTP event stream
+#[derive(Debug, Clone)]
+pub struct CommitFeed {
+    config: CommitFeedConfig,
+    sender: broadcast::Sender<CommitNotification>,
+    /// When set, versions past the last one to pass data tests are held back
+    watermark: Option<QualityStatusHandle>,
+}
+
+impl CommitFeed {
+    pub fn new(config: CommitFeedConfig) -> Self {
+        let (sender, _) = broadcast::channel(config.capacity);
+        Self { config, sender, watermark: None }
+    }
+
+    /// Only publish versions that passed the data tests
+    pub fn with_watermark(mut self, status: QualityStatusHandle) -> Self {
+        self.watermark = Some(status);
+        self
+    }
+
+    /// Receive commits from now on. A subscriber that falls more than
+    /// `capacity` commits behind is told how many it missed.
+    pub fn subscribe(&self) -> broadcast::Receiver<CommitNotification> {
+        self.sender.subscribe()
+    }
+
+    /// Poll the log until shutdown. Uses its own table handle so subscribers
+    /// are never delayed by compaction or vacuum holding the shared one.
+    pub async fn run(&self, table_uri: &str, storage_options: &StorageOptions, shutdown: CancellationToken) -> Result<()> {
+        let mut table = deltalake::open_table_with_storage_options(table_uri, storage_options.0.clone())
+            .await
+            .context("Failed to open table for commit feed")?;
+        let mut last_seen = table.version();
+
+        log::info!("Publishing commits of {} from version {}", table_uri, last_seen);
+
+        let mut poll = interval(self.config.poll_interval());
+        loop {
+            tokio::select! {
+                _ = poll.tick() => {
+                    if let Err(e) = table.update().await {
+                        log::warn!("Commit feed failed to refresh {}: {}", table_uri, e);
+                        continue;
+                    }
+                    let publish_up_to = match &self.watermark {
+                        Some(status) => status.lock().unwrap().validated_version.unwrap_or(last_seen),
+                        None => table.version(),
+                    };
+                    while last_seen < publish_up_to.min(table.version()) {
+                        let version = last_seen + 1;
+                        match self.read_commit(&table, version).await {
+                            Ok(notification) => {
+                                // No receivers is not an error: nobody is listening yet
+                                let _ = self.sender.send(notification);
+                            }
+                            Err(e) => log::warn!("Commit feed could not read version {}: {:#}", version, e),
+                        }
+                        last_seen = version;
+                    }
+                }
+                _ = shutdown.cancelled() => {
+                    log::info!("Commit feed received shutdown signal");
+                    break;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn read_commit(&self, table: &DeltaTable, version: i64) -> Result<CommitNotification> {
+        let bytes = table
+            .log_store()
+            .read_commit_entry(version)
+            .await?
+            .with_context(|| format!("Commit {} not found", version))?;
+
+        let mut notification = CommitNotification {
+            table_uri: table.table_uri(),
+            version,
+            timestamp: None,
+            operation: None,
+            operation_metrics: BTreeMap::new(),
+            files_added: 0,
+            rows_added: Some(0),
+            watermark: None,
+        };
+
+        for line in bytes.split(|b| *b == b'\n').filter(|l| !l.is_empty()) {
+            match serde_json::from_slice::<Action>(line)? {
+                Action::CommitInfo(info) => {
+                    notification.timestamp = info.timestamp.and_then(DateTime::from_timestamp_millis);
+                    if let Some(Value::Object(metrics)) = info.info.get("operationMetrics") {
+                        notification.operation_metrics = spark_operation_metrics(info.operation.as_deref(), metrics);
+                    }
+                    notification.operation = info.operation;
+                }
+                Action::Add(add) => {
+                    notification.files_added += 1;
+                    let stats = add.get_stats().ok().flatten();
+                    notification.rows_added = notification
+                        .rows_added
+                        .zip(stats.as_ref().map(|s| s.num_records))
+                        .map(|(total, n)| total + n);
+                    if let Some(column) = &self.config.watermark_column {
+                        let max = stats.and_then(|s| s.max_values.get(column).and_then(|v| v.as_value().cloned()));
+                        notification.watermark = max_value(notification.watermark.take(), max);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(notification)
+    }
+}
+
+/// Larger of two stats values; timestamps are ISO strings, so strings
+/// compare lexicographically and numbers numerically
+fn max_value(a: Option<Value>, b: Option<Value>) -> Option<Value> {
+    match (a, b) {
+        (Some(a), Some(b)) => {
+            let b_is_larger = match (&a, &b) {
+                (Value::Number(x), Value::Number(y)) => y.as_f64() > x.as_f64(),
+                (Value::String(x), Value::String(y)) => y > x,
+                _ => false,
+            };
+            Some(if b_is_larger { b } else { a })
+        }
+        (a, b) => a.or(b),
+    }
+}
@@ -1,36 +1,91 @@
-use anyhow::{Context, Result};
-use deltalake::DeltaTable;
+use anyhow::{bail, Context, Result};
+use deltalake::{DeltaOps, DeltaTable, PartitionFilter};
+use futures::future::join_all;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::sync::Arc;
-use tokio::sync::Mutex;
-use tokio::time::{interval, Instant};
-use crate::config::CompactionConfig;
+use std::time::Duration;
+use tokio::sync::{Mutex, Semaphore};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+use crate::checksums;
+use crate::compat;
+use crate::config::{ChecksumConfig, CompactionConfig, ParquetCompression};
+use crate::events::{EventBus, OrchestratorEvent};
+use crate::maintenance::{Maintenance, MaintenanceControl};
+use crate::manifest;
+use crate::metrics::MetricsRegistry;
+use crate::partition_metrics::read_commit_adds;
+use crate::schedule::Ticker;
+use crate::stats;
 
 /// The Compaction process - merges small files into larger, optimized ones
 #[derive(Debug, Clone)]
 pub struct CompactionProcess {
     config: CompactionConfig,
+    metrics: Arc<MetricsRegistry>,
+    checksums: ChecksumConfig,
+    control: Arc<MaintenanceControl>,
+    events: EventBus,
 }
 
 impl CompactionProcess {
     /// Create a new compaction process
     pub fn new(config: CompactionConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            metrics: Arc::default(),
+            checksums: ChecksumConfig::default(),
+            control: Arc::default(),
+            events: EventBus::default(),
+        }
+    }
+
+    /// Record into a registry shared with the other processes of the table
+    pub fn with_metrics(mut self, metrics: Arc<MetricsRegistry>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Record checksum manifests for the files compaction writes
+    pub fn with_checksums(mut self, checksums: ChecksumConfig) -> Self {
+        self.checksums = checksums;
+        self
+    }
+
+    /// Publish a `CompactionCompleted` event for every cycle on `events`
+    pub fn with_events(mut self, events: EventBus) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Skip scheduled cycles while `control` has compaction paused
+    pub fn with_control(mut self, control: Arc<MaintenanceControl>) -> Self {
+        self.control = control;
+        self
     }
 
-    /// Main run loop for the compaction process
-    pub async fn run(&self, table: Arc<Mutex<DeltaTable>>) -> Result<()> {
+    /// Main run loop for the compaction process. A cycle in progress when
+    /// shutdown is requested runs to completion before the loop exits.
+    pub async fn run(&self, table: Arc<Mutex<DeltaTable>>, shutdown: CancellationToken) -> Result<()> {
         log::info!("Starting Compaction process");
         
-        let mut interval_timer = interval(self.config.compaction_interval());
+        let mut ticker = Ticker::new(self.config.compaction_schedule.as_ref(), self.config.compaction_interval());
         
         loop {
             tokio::select! {
-                _ = interval_timer.tick() => {
+                _ = ticker.tick() => {
+                    if self.control.is_paused(Maintenance::Compaction) {
+                        log::debug!("Skipping compaction cycle: compaction is paused");
+                        self.metrics.record_paused_cycle(Maintenance::Compaction);
+                        continue;
+                    }
                     if let Err(e) = self.run_compaction_cycle(&table).await {
                         log::error!("Compaction cycle failed: {}", e);
+                        self.metrics.record_error("compaction", format!("{:#}", e));
                     }
                 }
-                _ = tokio::signal::ctrl_c() => {
+                _ = shutdown.cancelled() => {
                     log::info!("Compaction process received shutdown signal");
                     break;
                 }
@@ -40,70 +95,340 @@ impl CompactionProcess {
         Ok(())
     }
 
-    /// Run a single compaction cycle
+    /// Run a single compaction cycle, optimizing up to
+    /// `max_concurrent_compactions` partitions at a time
+    #[tracing::instrument(name = "compaction", skip_all)]
     async fn run_compaction_cycle(&self, table: &Arc<Mutex<DeltaTable>>) -> Result<()> {
         let start_time = Instant::now();
         
         // Lock the table for compaction
         let mut locked_table = table.lock().await;
         
-        // Check if compaction is needed
-        let file_count = locked_table.get_files_iter()?.count();
+        locked_table.update().await
+            .context("Failed to refresh table before compaction")?;
+        compat::ensure_no_deletion_vectors(&locked_table, "compact")?;
+        
+        // Check if compaction is needed; files at the target size are done
+        let version = locked_table.version();
+        let before = file_sizes(&locked_table)?;
+        let partitions = self.partitions_to_compact(&locked_table)?;
         
-        if file_count < self.config.min_files_to_compact {
-            log::debug!(
-                "Skipping compaction: {} files < {} minimum",
-                file_count,
-                self.config.min_files_to_compact
-            );
+        if partitions.is_empty() {
+            match self.config.small_file_ratio {
+                Some(ratio) => log::debug!(
+                    "Skipping compaction: no partition has {:.0}% of its files below {} bytes",
+                    ratio * 100.0,
+                    self.config.small_file_threshold()
+                ),
+                None => log::debug!(
+                    "Skipping compaction: no partition has {} files below target size",
+                    self.config.min_files_to_compact
+                ),
+            }
             return Ok(());
         }
         
-        log::info!("Starting compaction: {} files to process", file_count);
+        log::info!(
+            "Starting compaction: {} of {} files below target size in {} partitions",
+            self.small_files(&before),
+            before.len(),
+            partitions.len()
+        );
         
-        // Run the actual compaction
-        self.run_once(&mut locked_table).await?;
+        // Every partition is optimized from the same snapshot; their commits
+        // touch disjoint files, so delta-rs resolves the version races
+        let semaphore = Semaphore::new(self.config.max_concurrent_compactions.max(1));
+        let snapshot = &*locked_table;
+        let results = join_all(partitions.iter().map(|(partition, filters)| {
+            let semaphore = &semaphore;
+            async move {
+                let _permit = semaphore.acquire().await?;
+                let started = Instant::now();
+                self.optimize(snapshot.clone(), filters)
+                    .instrument(tracing::info_span!("compact_partition", partition = %partition))
+                    .await
+                    .with_context(|| format!("Failed to compact partition {}", partition))?;
+                let elapsed = started.elapsed();
+                self.metrics.record_partition_compaction(elapsed);
+                log::debug!("Compacted partition {} in {:?}", partition, elapsed);
+                Ok::<_, anyhow::Error>((partition, elapsed))
+            }
+        }))
+        .await;
+        
+        locked_table.update().await
+            .context("Failed to refresh table after compaction")?;
+        let after = file_sizes(&locked_table)?;
+        self.record(&locked_table, &before, &after, start_time.elapsed());
+        self.refresh_manifest(&locked_table).await;
+        self.record_checksums(&locked_table, version).await;
+        self.refresh_stats(&mut locked_table).await;
         
-        let elapsed = start_time.elapsed();
-        let new_file_count = locked_table.get_files_iter()?.count();
+        let mut failed = 0;
+        for error in results.iter().filter_map(|r| r.as_ref().err()) {
+            log::error!("{:#}", error);
+            failed += 1;
+        }
+        let slowest = results
+            .iter()
+            .filter_map(|r| r.as_ref().ok())
+            .max_by_key(|(_, elapsed)| *elapsed);
         
         log::info!(
-            "Compaction completed in {:?}: {} files -> {} files",
-            elapsed,
-            file_count,
-            new_file_count
+            "Compaction completed in {:?}: {} files -> {} files, average size {} -> {} bytes{}",
+            start_time.elapsed(),
+            before.len(),
+            after.len(),
+            average_size(&before),
+            average_size(&after),
+            slowest.map_or(String::new(), |(partition, elapsed)| {
+                format!(", slowest partition {} took {:?}", partition, elapsed)
+            })
         );
         
+        if failed > 0 {
+            bail!("{} of {} partitions failed to compact", failed, partitions.len());
+        }
         Ok(())
     }
 
     /// Run compaction once on the given table
     pub async fn run_once(&self, table: &mut DeltaTable) -> Result<()> {
+        self.run_once_with_filters(table, &[]).await
+    }
+
+    /// Run compaction once on the partitions matching `filters`
+    #[tracing::instrument(name = "compaction", skip_all, fields(table_uri = %table.table_uri()))]
+    pub async fn run_once_with_filters(&self, table: &mut DeltaTable, filters: &[PartitionFilter]) -> Result<()> {
+        let start_time = Instant::now();
+        
         // Refresh the table to get latest state
         table.update().await
-            .with_context("Failed to refresh table before compaction")?;
+            .context("Failed to refresh table before compaction")?;
+        compat::ensure_no_deletion_vectors(table, "compact")?;
             
-        // Run the optimize operation
-        // Note: In delta-rs, optimize() handles the compaction logic
-        table.optimize(None).await
-            .with_context("Failed to run optimize operation")?;
+        let version = table.version();
+        let before = file_sizes(table)?;
+        // Merging a single small file would only rewrite it
+        if filters.is_empty() && self.small_files(&before) < 2 {
+            log::debug!("Skipping compaction: no small files to merge");
+            return Ok(());
+        }
+            
+        *table = self.optimize(table.clone(), filters).await?;
+        
+        let after = file_sizes(table)?;
+        self.record(table, &before, &after, start_time.elapsed());
+        self.refresh_manifest(table).await;
+        self.record_checksums(table, version).await;
+        self.refresh_stats(table).await;
             
         Ok(())
     }
 
+    /// Bin-pack the files matching `filters`. Optimize only merges files
+    /// below the target size, so files at or above it are never rewritten.
+    async fn optimize(&self, table: DeltaTable, filters: &[PartitionFilter]) -> Result<DeltaTable> {
+        let writer_properties = self.config.parquet.writer_properties(ParquetCompression::Zstd)?;
+        let mut optimize = DeltaOps(table)
+            .optimize()
+            .with_filters(filters)
+            .with_target_size(self.config.target_file_size_bytes)
+            .with_writer_properties(writer_properties);
+        if let Some(tasks) = self.config.concurrent_tasks() {
+            optimize = optimize.with_max_concurrent_tasks(tasks);
+        }
+        let (optimized, _) = optimize.await
+            .context("Failed to run optimize operation")?;
+        Ok(optimized)
+    }
+
+    /// Files no longer active were rewritten into the new, larger ones
+    fn record(&self, table: &DeltaTable, before: &HashMap<String, i64>, after: &HashMap<String, i64>, elapsed: Duration) {
+        let (files, bytes) = before
+            .iter()
+            .filter(|(path, _)| !after.contains_key(*path))
+            .fold((0u64, 0u64), |(files, bytes), (_, size)| (files + 1, bytes + *size as u64));
+        self.metrics.record_compaction(files, bytes, elapsed);
+        self.metrics.record_file_sizes(average_size(before), average_size(after));
+        self.events.publish(OrchestratorEvent::CompactionCompleted {
+            table_uri: table.table_uri(),
+            version: table.version(),
+            files_removed: files,
+            bytes_removed: bytes,
+            elapsed_ms: elapsed.as_millis() as u64,
+        });
+    }
+
+    /// The table is consistent either way; readers fall back to the log
+    async fn refresh_manifest(&self, table: &DeltaTable) {
+        if !self.config.manifest_columns.is_empty() {
+            if let Err(e) = manifest::write_manifest(table, &self.config.manifest_columns).await {
+                log::warn!("Failed to refresh file manifest: {:#}", e);
+            }
+        }
+    }
+
+    /// Files compaction left alone keep missing statistics; the footers of
+    /// those written with row group statistics can fill them in
+    async fn refresh_stats(&self, table: &mut DeltaTable) {
+        if self.config.restat {
+            if let Err(e) = stats::restat(table, false).await {
+                log::warn!("Failed to refresh statistics after compaction: {:#}", e);
+            }
+        }
+    }
+
+    /// Manifests for the commits since `version`: those of this compaction,
+    /// and any writer commit whose manifest was not recorded
+    async fn record_checksums(&self, table: &DeltaTable, version: i64) {
+        if self.checksums.enabled {
+            let log_store = table.log_store();
+            if let Err(e) = checksums::record_missing(&log_store, version + 1, table.version(), self.checksums.sha256).await {
+                log::warn!("Failed to record checksum manifests: {:#}", e);
+            }
+        }
+    }
+
+    /// Partitions due for compaction by their count or ratio of small files,
+    /// named as `col=value/...` with the filters selecting them. An
+    /// unpartitioned table is a single partition with no filters. Records
+    /// the table's small-file ratio on the way.
+    fn partitions_to_compact(&self, table: &DeltaTable) -> Result<Vec<(String, Vec<PartitionFilter>)>> {
+        let partition_columns = table.metadata()?.partition_columns.clone();
+        let threshold = match self.config.small_file_ratio {
+            Some(_) => self.config.small_file_threshold(),
+            None => self.config.target_file_size_bytes,
+        };
+        // Small and total files per partition
+        let mut files: BTreeMap<Vec<Option<String>>, (usize, usize)> = BTreeMap::new();
+        for add in table.snapshot()?.file_actions()? {
+            let values = partition_columns
+                .iter()
+                .map(|column| add.partition_values.get(column).cloned().flatten())
+                .collect();
+            let (small, total) = files.entry(values).or_default();
+            *total += 1;
+            if (add.size as u64) < threshold {
+                *small += 1;
+            }
+        }
+        let (small, total) = files.values().fold((0, 0), |(small, total), (s, t)| (small + s, total + t));
+        self.metrics.record_small_file_ratio(if total == 0 { 0.0 } else { small as f64 / total as f64 });
+
+        let mut partitions = Vec::new();
+        for (values, (small, total)) in files {
+            if self.config.should_compact(small, total) {
+                partitions.extend(select_partition(&partition_columns, &values)?);
+            }
+        }
+        Ok(partitions)
+    }
+
+    /// Compact each partition that received files in the commits after
+    /// `since`, e.g. right after a bulk import, leaving the rest of the
+    /// table alone. Returns the partitions compacted.
+    pub async fn compact_touched(&self, table: &mut DeltaTable, since: i64) -> Result<Vec<String>> {
+        table.update().await
+            .context("Failed to refresh table before compaction")?;
+        let partition_columns = table.metadata()?.partition_columns.clone();
+        let log_store = table.log_store();
+        let mut touched = BTreeSet::new();
+        for version in since + 1..=table.version() {
+            let (_, adds) = read_commit_adds(&log_store, version).await?;
+            for add in adds {
+                touched.insert(
+                    partition_columns
+                        .iter()
+                        .map(|column| add.partition_values.get(column).cloned().flatten())
+                        .collect::<Vec<_>>(),
+                );
+            }
+        }
+
+        let mut compacted = Vec::new();
+        for values in touched {
+            let Some((name, filters)) = select_partition(&partition_columns, &values)? else {
+                continue;
+            };
+            self.run_once_with_filters(table, &filters)
+                .await
+                .with_context(|| format!("Failed to compact partition {}", name))?;
+            compacted.push(name);
+        }
+        Ok(compacted)
+    }
+
+    /// Files smaller than `target_file_size_bytes`
+    fn small_files(&self, sizes: &HashMap<String, i64>) -> usize {
+        sizes
+            .values()
+            .filter(|size| (**size as u64) < self.config.target_file_size_bytes)
+            .count()
+    }
+
     /// Get metrics about the compaction performance
     pub fn get_metrics(&self) -> CompactionMetrics {
         CompactionMetrics {
             config: self.config.clone(),
-            // In a real implementation, these would be tracked
-            total_compactions_run: 0,
-            total_files_compacted: 0,
-            total_bytes_compacted: 0,
-            average_compaction_time_ms: 0.0,
+            total_compactions_run: self.metrics.compactions_run(),
+            total_files_compacted: self.metrics.files_compacted(),
+            total_bytes_compacted: self.metrics.bytes_compacted(),
+            average_compaction_time_ms: self.metrics.compaction_time().mean_ms(),
+            average_partition_compaction_time_ms: self.metrics.partition_compaction_time().mean_ms(),
+            average_file_size_before: self.metrics.average_file_size_before(),
+            average_file_size_after: self.metrics.average_file_size_after(),
+            small_file_ratio: self.metrics.small_file_ratio(),
         }
     }
 }
 
+/// Size of every active file, keyed by path
+fn file_sizes(table: &DeltaTable) -> Result<HashMap<String, i64>> {
+    Ok(table
+        .snapshot()?
+        .file_actions()?
+        .into_iter()
+        .map(|add| (add.path, add.size))
+        .collect())
+}
+
+/// Name (`col=value/...`) and equality filters of the partition with
+/// `values` for `columns`; `None` for a null partition, which equality
+/// filters cannot select
+fn select_partition(columns: &[String], values: &[Option<String>]) -> Result<Option<(String, Vec<PartitionFilter>)>> {
+    let mut names = Vec::with_capacity(values.len());
+    let mut filters = Vec::with_capacity(values.len());
+    for (column, value) in columns.iter().zip(values) {
+        let Some(value) = value else {
+            log::debug!("Skipping compaction of null partition {}", column);
+            return Ok(None);
+        };
+        names.push(format!("{}={}", column, value));
+        filters.push(PartitionFilter::try_from((column.as_str(), "=", value.as_str()))?);
+    }
+    let name = if names.is_empty() { "(unpartitioned)".to_string() } else { names.join("/") };
+    Ok(Some((name, filters)))
+}
+
+/// Parse a partition filter such as `day=2024-01-01` or `region!=eu`
+pub fn parse_partition_filter(filter: &str) -> Result<PartitionFilter> {
+    for op in ["!=", ">=", "<=", "=", ">", "<"] {
+        if let Some((column, value)) = filter.split_once(op) {
+            return PartitionFilter::try_from((column.trim(), op, value.trim()))
+                .with_context(|| format!("Invalid partition filter '{}'", filter));
+        }
+    }
+    bail!("Expected a partition filter like column=value, got '{}'", filter)
+}
+
+fn average_size(sizes: &HashMap<String, i64>) -> u64 {
+    match sizes.len() {
+        0 => 0,
+        n => sizes.values().map(|size| *size as u64).sum::<u64>() / n as u64,
+    }
+}
+
 /// Metrics for the compaction process
 #[derive(Debug, Clone)]
 pub struct CompactionMetrics {
@@ -112,4 +437,11 @@ pub struct CompactionMetrics {
     pub total_files_compacted: u64,
     pub total_bytes_compacted: u64,
     pub average_compaction_time_ms: f64,
+    /// Average time to optimize a single partition within a cycle
+    pub average_partition_compaction_time_ms: f64,
+    /// Average active file size in bytes before and after the last compaction
+    pub average_file_size_before: u64,
+    pub average_file_size_after: u64,
+    /// Fraction of active files counted as small at the latest check
+    pub small_file_ratio: f64,
 } 
\ No newline at end of file
@@ -0,0 +1,260 @@
+use anyhow::{bail, Result};
+use deltalake::kernel::{DataType, PrimitiveType, ReaderFeatures, WriterFeatures};
+use deltalake::DeltaTable;
+use std::fmt;
+
+/// Writer features this crate can append under without corrupting the table
+const SUPPORTED_WRITER_FEATURES: &[WriterFeatures] = &[
+    WriterFeatures::AppendOnly,
+    WriterFeatures::Invariants,
+    WriterFeatures::CheckConstraints,
+    WriterFeatures::TimestampWithoutTimezone,
+    WriterFeatures::ChangeDataFeed,
+    WriterFeatures::GeneratedColumns,
+];
+
+/// How serious an interoperability finding is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// Writes are refused until the table is changed
+    Error,
+    /// Writes proceed but behaviour differs from Spark
+    Warning,
+    Info,
+}
+
+/// A single interoperability finding
+#[derive(Debug, Clone)]
+pub struct CompatIssue {
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Result of checking a table written by another engine (typically Spark)
+#[derive(Debug, Clone, Default)]
+pub struct CompatReport {
+    pub table_uri: String,
+    pub min_reader_version: i32,
+    pub min_writer_version: i32,
+    pub issues: Vec<CompatIssue>,
+}
+
+impl CompatReport {
+    /// Whether this writer may append to the table
+    pub fn is_writable(&self) -> bool {
+        !self.issues.iter().any(|i| i.severity == Severity::Error)
+    }
+
+    fn push(&mut self, severity: Severity, message: impl Into<String>) {
+        self.issues.push(CompatIssue { severity, message: message.into() });
+    }
+}
+
+impl fmt::Display for CompatReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Table: {}", self.table_uri)?;
+        writeln!(
+            f,
+            "Protocol: reader v{}, writer v{}",
+            self.min_reader_version, self.min_writer_version
+        )?;
+        if self.issues.is_empty() {
+            writeln!(f, "No interoperability issues found")?;
+        }
+        for issue in &self.issues {
+            writeln!(f, "  [{:?}] {}", issue.severity, issue.message)?;
+        }
+        write!(f, "Writable: {}", if self.is_writable() { "yes" } else { "no" })
+    }
+}
+
+/// Inspect protocol, properties, schema and every active file for features
+/// that affect appends
+pub fn check_table(table: &DeltaTable) -> Result<CompatReport> {
+    check(table, true)
+}
+
+/// [`check_table`] without scanning the files, so its cost does not grow
+/// with the table. `scan_files` adds the deletion vector counts.
+fn check(table: &DeltaTable, scan_files: bool) -> Result<CompatReport> {
+    let protocol = table.protocol()?;
+    let metadata = table.metadata()?;
+    let schema = table.get_schema()?;
+
+    let mut report = CompatReport {
+        table_uri: table.table_uri(),
+        min_reader_version: protocol.min_reader_version,
+        min_writer_version: protocol.min_writer_version,
+        issues: Vec::new(),
+    };
+
+    let property = |key: &str| metadata.configuration.get(key).cloned().flatten();
+
+    // Deletion vectors: rows hidden by a DV would reappear if we rewrote files
+    let dvs = match scan_files {
+        true => deletion_vectors(table)?,
+        false => DeletionVectorSummary { enabled: deletion_vectors_enabled(table)?, ..Default::default() },
+    };
+    if dvs.present() {
+        let usage = match scan_files {
+            true => format!(" ({} files, {} deleted rows)", dvs.files_with_dvs, dvs.deleted_rows),
+            false => String::new(),
+        };
+        report.push(
+            Severity::Error,
+            format!(
+                "Deletion vectors are enabled{}; disable them and purge existing DVs \
+                 (REORG TABLE ... APPLY (PURGE)) before writing from this tool",
+                usage
+            ),
+        );
+    }
+
+    if let Some(mode) = property("delta.columnMapping.mode").filter(|m| m != "none") {
+        report.push(
+            Severity::Error,
+            format!("Column mapping mode '{}' is not supported for writes", mode),
+        );
+    }
+
+    if let Some(features) = &protocol.writer_features {
+        for feature in features {
+            if matches!(feature, WriterFeatures::DeletionVectors | WriterFeatures::ColumnMapping) {
+                continue; // reported above with a more specific message
+            }
+            if !SUPPORTED_WRITER_FEATURES.contains(feature) {
+                report.push(
+                    Severity::Error,
+                    format!("Unsupported writer feature: {}", feature),
+                );
+            }
+        }
+    }
+
+    let ntz_columns: Vec<&str> = schema
+        .fields()
+        .filter(|f| matches!(f.data_type(), DataType::Primitive(PrimitiveType::TimestampNtz)))
+        .map(|f| f.name().as_str())
+        .collect();
+    if !ntz_columns.is_empty() {
+        report.push(
+            Severity::Warning,
+            format!(
+                "Columns {:?} are TIMESTAMP_NTZ; incoming timestamps are cast to the table type",
+                ntz_columns
+            ),
+        );
+    }
+
+    if let Some(columns) = property("delta.dataSkippingStatsColumns") {
+        for column in columns.split(',').map(str::trim).filter(|c| !c.is_empty()) {
+            if schema.field(column).is_none() {
+                report.push(
+                    Severity::Warning,
+                    format!("Stats column '{}' is not in the table schema", column),
+                );
+            }
+        }
+    } else if let Some(n) = property("delta.dataSkippingNumIndexedCols") {
+        report.push(
+            Severity::Info,
+            format!("Statistics are collected for the first {} columns", n),
+        );
+    }
+
+    if property("delta.appendOnly").as_deref() == Some("true") {
+        report.push(Severity::Info, "Table is append-only; deletes and merges will be rejected");
+    }
+
+    Ok(report)
+}
+
+/// Deletion vector usage across the active files of a table
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeletionVectorSummary {
+    /// The protocol or table properties allow deletion vectors
+    pub enabled: bool,
+    /// Active files carrying a deletion vector
+    pub files_with_dvs: usize,
+    /// Rows hidden by those deletion vectors
+    pub deleted_rows: u64,
+}
+
+impl DeletionVectorSummary {
+    /// Whether writes, compaction and vacuum are refused. Enabled deletion
+    /// vectors count even before any file carries one, as another engine
+    /// may add them between our listing and our commit.
+    pub fn present(&self) -> bool {
+        self.enabled || self.files_with_dvs > 0
+    }
+}
+
+/// Whether the protocol or table properties allow deletion vectors. Reads
+/// only the table metadata.
+pub fn deletion_vectors_enabled(table: &DeltaTable) -> Result<bool> {
+    let protocol = table.protocol()?;
+    let metadata = table.metadata()?;
+
+    let feature = protocol
+        .reader_features
+        .as_ref()
+        .is_some_and(|f| f.contains(&ReaderFeatures::DeletionVectors));
+    let property = metadata
+        .configuration
+        .get("delta.enableDeletionVectors")
+        .cloned()
+        .flatten()
+        .as_deref()
+        == Some("true");
+    Ok(feature || property)
+}
+
+/// Detect deletion vectors, either enabled on the table or attached to
+/// files. Reads every active file's add action.
+pub fn deletion_vectors(table: &DeltaTable) -> Result<DeletionVectorSummary> {
+    let mut summary = DeletionVectorSummary { enabled: deletion_vectors_enabled(table)?, ..Default::default() };
+
+    for add in table.snapshot()?.file_actions()? {
+        if let Some(dv) = &add.deletion_vector {
+            summary.files_with_dvs += 1;
+            summary.deleted_rows += dv.cardinality as u64;
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Refuse `operation` (compaction, vacuum) on a table with deletion vectors,
+/// by the rule of [`DeletionVectorSummary::present`]: rewriting files without
+/// applying their DVs would resurrect deleted rows, which delta-rs cannot
+/// do yet, and vacuum could take DV files for orphans
+pub fn ensure_no_deletion_vectors(table: &DeltaTable, operation: &str) -> Result<()> {
+    let dvs = deletion_vectors(table)?;
+    if dvs.present() {
+        bail!(
+            "Refusing to {}: table uses deletion vectors ({} files, {} deleted rows); disable them \
+             and purge existing ones with REORG TABLE ... APPLY (PURGE) first",
+            operation,
+            dvs.files_with_dvs,
+            dvs.deleted_rows
+        );
+    }
+    Ok(())
+}
+
+/// Refuse to write when the table uses features this writer cannot honour.
+/// Checks only the protocol, properties and schema, as it runs on every
+/// flush; a table whose files carry deletion vectors has them enabled.
+pub fn ensure_writable(table: &DeltaTable) -> Result<()> {
+    let report = check(table, false)?;
+    if !report.is_writable() {
+        let reasons: Vec<&str> = report
+            .issues
+            .iter()
+            .filter(|i| i.severity == Severity::Error)
+            .map(|i| i.message.as_str())
+            .collect();
+        bail!("Refusing to write to {}: {}", report.table_uri, reasons.join("; "));
+    }
+    Ok(())
+}
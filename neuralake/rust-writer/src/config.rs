@@ -1,8 +1,1155 @@
+use anyhow::{bail, Context, Result};
+use deltalake::StorageOptions;
+use deltalake::parquet::basic::{Compression, ZstdLevel};
+use deltalake::parquet::file::properties::WriterProperties;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
 use std::time::Duration;
+use crate::retention;
+use crate::schedule::CronSchedule;
+use crate::storage::resolve_storage_options;
+
+/// Top-level configuration for the orchestrator and its three processes
+#[derive(Debug, Clone, Default)]
+pub struct SurgicalStrikeConfig {
+    /// URI of the Delta table managed by this orchestrator
+    pub table_uri: String,
+    /// Object store options passed to delta-rs
+    pub storage_options: StorageOptions,
+    pub writer: WriterConfig,
+    pub compaction: CompactionConfig,
+    pub vacuum: VacuumConfig,
+    pub checkpoint: CheckpointConfig,
+    /// Sidecar checksum manifests of committed data files
+    pub checksums: ChecksumConfig,
+    pub locking: LockingConfig,
+    /// Credentials for `abfss://` tables, on top of the `AZURE_*` environment
+    pub azure: Option<AzureConfig>,
+    pub http: HttpConfig,
+    pub flight: FlightConfig,
+    /// gRPC admin and write API (requires the `grpc` feature)
+    pub grpc: GrpcConfig,
+    /// Flush slots shared between tables in multi-table mode
+    pub scheduling: SchedulingConfig,
+    /// Optional Kafka topic consumed into the table (requires the `kafka` feature)
+    pub kafka: Option<KafkaSourceConfig>,
+    /// Optional Pulsar topics consumed into the table (requires the `pulsar` feature)
+    pub pulsar: Option<PulsarSourceConfig>,
+    /// Optional Kinesis stream consumed into the table (requires the `kinesis` feature)
+    pub kinesis: Option<KinesisSourceConfig>,
+    /// Optional SQS queue of S3 object notifications whose objects are
+    /// appended to the table (requires the `sqs` feature)
+    pub sqs: Option<SqsSourceConfig>,
+    /// Optional PostgreSQL table whose changes are read from a logical
+    /// replication slot into the table
+    pub postgres_cdc: Option<PostgresCdcConfig>,
+    /// Gap and out-of-order tracking of producer sequence numbers in the
+    /// source's messages
+    pub sequence: Option<SequenceTrackingConfig>,
+    pub jobs: JobsConfig,
+    /// Local state directory shared by the processes that persist state
+    pub state: StateConfig,
+    /// Optional archival of cold partitions into a separate table
+    pub archive: Option<ArchiveConfig>,
+    /// Secondary stores receiving a copy of every committed batch
+    pub sinks: Vec<SinkConfig>,
+    pub commit_feed: CommitFeedConfig,
+    /// Synchronous replication of queued batches to a warm standby
+    pub replication: ReplicationConfig,
+    /// Data tests run against every new table version
+    pub quality: QualityConfig,
+    /// Table layout checked against the actual table on startup
+    pub expectations: ExpectationsConfig,
+    /// Where operational alerts about the table are sent
+    pub alerts: AlertsConfig,
+    /// Schema the table is created with if it does not exist yet
+    pub schema: Option<TableSchemaConfig>,
+    /// Columns min/max/null count statistics are collected for on write
+    /// and compaction, set as `delta.dataSkippingStatsColumns` on startup;
+    /// empty leaves the table's setting alone
+    pub stats_columns: Vec<String>,
+    /// Serve metrics, queries and the API without writing to the table:
+    /// the writer, maintenance, sources and jobs stay off and every
+    /// mutating operation is refused
+    pub read_only: bool,
+    /// Days of history every version stays readable for time travel.
+    /// Vacuum retention and the table's log and deleted-file retention are
+    /// derived from it; shorter explicit settings are refused.
+    pub time_travel_days: Option<u64>,
+}
+
+/// One table as written in the TOML config file
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct TableSection {
+    table_uri: String,
+    storage_options: HashMap<String, String>,
+    writer: WriterConfig,
+    compaction: CompactionConfig,
+    vacuum: VacuumConfig,
+    checkpoint: CheckpointConfig,
+    checksums: ChecksumConfig,
+    locking: LockingConfig,
+    azure: Option<AzureConfig>,
+    http: HttpConfig,
+    flight: FlightConfig,
+    grpc: GrpcConfig,
+    scheduling: SchedulingConfig,
+    kafka: Option<KafkaSourceConfig>,
+    pulsar: Option<PulsarSourceConfig>,
+    kinesis: Option<KinesisSourceConfig>,
+    sqs: Option<SqsSourceConfig>,
+    postgres_cdc: Option<PostgresCdcConfig>,
+    sequence: Option<SequenceTrackingConfig>,
+    jobs: JobsConfig,
+    state: StateConfig,
+    archive: Option<ArchiveConfig>,
+    sinks: Vec<SinkConfig>,
+    commit_feed: CommitFeedConfig,
+    replication: ReplicationConfig,
+    quality: QualityConfig,
+    expectations: ExpectationsConfig,
+    alerts: AlertsConfig,
+    schema: Option<TableSchemaConfig>,
+    stats_columns: Vec<String>,
+    read_only: bool,
+    time_travel_days: Option<u64>,
+}
+
+impl From<TableSection> for SurgicalStrikeConfig {
+    fn from(section: TableSection) -> Self {
+        Self {
+            table_uri: section.table_uri,
+            storage_options: StorageOptions(section.storage_options),
+            writer: section.writer,
+            compaction: section.compaction,
+            vacuum: section.vacuum,
+            checkpoint: section.checkpoint,
+            checksums: section.checksums,
+            locking: section.locking,
+            azure: section.azure,
+            http: section.http,
+            flight: section.flight,
+            grpc: section.grpc,
+            scheduling: section.scheduling,
+            kafka: section.kafka,
+            pulsar: section.pulsar,
+            kinesis: section.kinesis,
+            sqs: section.sqs,
+            postgres_cdc: section.postgres_cdc,
+            sequence: section.sequence,
+            jobs: section.jobs,
+            state: section.state,
+            archive: section.archive,
+            sinks: section.sinks,
+            commit_feed: section.commit_feed,
+            replication: section.replication,
+            quality: section.quality,
+            expectations: section.expectations,
+            alerts: section.alerts,
+            schema: section.schema,
+            stats_columns: section.stats_columns,
+            read_only: section.read_only,
+            time_travel_days: section.time_travel_days,
+        }
+    }
+}
+
+/// Parse a TOML config into one config per table.
+///
+/// Top-level keys are defaults shared by every `[[tables]]` entry; each entry
+/// overrides them key by key, so `[tables.writer] max_batch_size = 10` keeps
+/// the shared writer settings apart from the batch size. A file without
+/// `[[tables]]` describes a single table.
+pub fn parse_config(contents: &str) -> Result<Vec<SurgicalStrikeConfig>> {
+    let mut root: toml::Table = toml::from_str(contents).context("Invalid TOML config")?;
+
+    let tables = match root.remove("tables") {
+        None => vec![toml::Table::new()],
+        Some(toml::Value::Array(tables)) => tables
+            .into_iter()
+            .map(|table| match table {
+                toml::Value::Table(table) => Ok(table),
+                _ => bail!("Each [[tables]] entry must be a table"),
+            })
+            .collect::<Result<Vec<_>>>()?,
+        Some(_) => bail!("`tables` must be an array of tables ([[tables]])"),
+    };
+
+    let mut configs: Vec<SurgicalStrikeConfig> = Vec::with_capacity(tables.len());
+    for overrides in tables {
+        let mut merged = root.clone();
+        merge_toml(&mut merged, overrides);
+        let explicit_retention = merged.get("vacuum").and_then(|vacuum| vacuum.get("retention_hours")).is_some();
+
+        let section: TableSection = toml::Value::Table(merged)
+            .try_into()
+            .context("Invalid table configuration")?;
+        if section.table_uri.is_empty() {
+            bail!("Every table needs a `table_uri`");
+        }
+        let mut config: SurgicalStrikeConfig = section.into();
+        config.apply_time_travel(explicit_retention)?;
+        configs.push(config);
+    }
+
+    let mut uris: Vec<&str> = configs.iter().map(|c| c.table_uri.as_str()).collect();
+    uris.sort_unstable();
+    if let Some(pair) = uris.windows(2).find(|pair| pair[0] == pair[1]) {
+        bail!("Table {} is configured more than once", pair[0]);
+    }
+
+    Ok(configs)
+}
+
+/// Load and parse a TOML config file, see [`parse_config`]. Each table's
+/// `storage_options` are layered over its storage backend's environment.
+pub fn load_config(path: impl AsRef<Path>) -> Result<Vec<SurgicalStrikeConfig>> {
+    let path = path.as_ref();
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file {}", path.display()))?;
+    let mut configs = parse_config(&contents).with_context(|| format!("Failed to load {}", path.display()))?;
+    for config in &mut configs {
+        config.storage_options = resolve_storage_options(&config.table_uri, &config.storage_options.0);
+    }
+    Ok(configs)
+}
+
+/// Recursively overlay `overrides` onto `base`; non-table values replace
+fn merge_toml(base: &mut toml::Table, overrides: toml::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(existing)), toml::Value::Table(value)) => merge_toml(existing, value),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+impl SurgicalStrikeConfig {
+    /// Short table name used in API routes: the last segment of the table URI
+    pub fn table_name(&self) -> &str {
+        self.table_uri
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .unwrap_or(&self.table_uri)
+    }
+
+    /// Where batches that exhaust their retries are kept: `writer.dead_letter_uri`,
+    /// or the local fallback when the HTTP or gRPC API acknowledges batches
+    /// before they are written, or a source commits positions past batches
+    /// that cannot be written
+    pub fn dead_letter_uri(&self) -> Option<String> {
+        match &self.writer.dead_letter_uri {
+            Some(uri) => Some(uri.clone()),
+            None if self.http.enabled || self.grpc.enabled || self.has_source() => Some(self.fallback_dead_letter_uri()),
+            None => None,
+        }
+    }
+
+    /// Whether a source is configured to feed the table
+    pub fn has_source(&self) -> bool {
+        self.kafka.is_some()
+            || self.pulsar.is_some()
+            || self.kinesis.is_some()
+            || self.sqs.is_some()
+            || self.postgres_cdc.is_some()
+    }
+
+    /// Directory in the state directory used when no `dead_letter_uri` is set
+    pub fn fallback_dead_letter_uri(&self) -> String {
+        format!("{}/dead_letter/{}", self.state.dir.trim_end_matches('/'), self.table_name())
+    }
+
+    /// Derive vacuum retention from `time_travel_days`. An explicit
+    /// `vacuum.retention_hours`, or retention property of the declared
+    /// schema, shorter than the guarantee is refused.
+    pub fn apply_time_travel(&mut self, explicit_retention: bool) -> Result<()> {
+        let Some(days) = self.time_travel_days else {
+            return Ok(());
+        };
+        let required_hours = days * 24;
+        if explicit_retention && self.vacuum.retention_hours < required_hours {
+            bail!(
+                "vacuum.retention_hours = {} conflicts with time_travel_days = {} for {}; remove it or raise it to at least {}",
+                self.vacuum.retention_hours,
+                days,
+                self.table_uri,
+                required_hours
+            );
+        }
+        if !explicit_retention {
+            self.vacuum.retention_hours = required_hours;
+        }
+
+        let properties = self.schema.iter().flat_map(|schema| &schema.properties);
+        for (key, value) in properties {
+            if key != retention::LOG_RETENTION_PROPERTY && key != retention::DELETED_FILE_RETENTION_PROPERTY {
+                continue;
+            }
+            let shorter = retention::parse_interval(value).is_none_or(|kept| kept < chrono::Duration::days(days as i64));
+            if shorter {
+                bail!("Schema property {} = {} conflicts with time_travel_days = {}", key, value, days);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Persistent queue of ad-hoc maintenance jobs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JobsConfig {
+    /// Execute queued jobs from the running orchestrator
+    pub enabled: bool,
+    /// SQLite database holding the queue
+    pub db_path: String,
+    /// How often to look for new jobs, in seconds
+    pub poll_interval_secs: u64,
+}
+
+impl Default for JobsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            db_path: "surgical_strike_jobs.db".to_string(),
+            poll_interval_secs: 5,
+        }
+    }
+}
+
+impl JobsConfig {
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_secs(self.poll_interval_secs)
+    }
+}
+
+/// How much of a state write survives a crash or power loss
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FsyncPolicy {
+    /// Sync the entry and the directory holding it: the write is durable
+    /// once it returns
+    #[default]
+    Full,
+    /// Sync the entry but not the rename; a power loss may bring back the
+    /// previous contents, never a partial write
+    Data,
+    /// Leave flushing to the OS; fastest, for caches that can be rebuilt
+    Off,
+}
+
+/// Directory of crash-safe local state, see [`crate::state::StateDir`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StateConfig {
+    pub dir: String,
+    pub fsync: FsyncPolicy,
+}
+
+impl Default for StateConfig {
+    fn default() -> Self {
+        Self {
+            dir: "surgical_strike_state".to_string(),
+            fsync: FsyncPolicy::default(),
+        }
+    }
+}
+
+/// Role of this instance in standby replication
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReplicationRole {
+    /// No replication; batches are acknowledged once queued
+    #[default]
+    None,
+    /// Acknowledge a batch only after the standby has it
+    Primary,
+    /// Hold the primary's uncommitted batches and replay them on takeover
+    Standby,
+}
+
+/// Replication of the in-memory writer queue to a warm standby
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReplicationConfig {
+    pub role: ReplicationRole,
+    /// Standby address the primary streams batches to
+    pub standby_addr: Option<String>,
+    /// Address the standby accepts the primary's stream on; the loopback
+    /// default has to be widened for a standby on another host, together
+    /// with an `auth_token`
+    pub listen_addr: String,
+    /// Shared secret the primary presents when it connects; a standby with
+    /// one set drops connections that do not present it
+    pub auth_token: Option<String>,
+    /// How long the primary waits for the standby to acknowledge a batch, in milliseconds
+    pub ack_timeout_ms: u64,
+    /// How often the primary signals it is alive, in milliseconds
+    pub heartbeat_interval_ms: u64,
+    /// Silence after which the standby takes over, in milliseconds
+    pub takeover_timeout_ms: u64,
+}
+
+impl Default for ReplicationConfig {
+    fn default() -> Self {
+        Self {
+            role: ReplicationRole::None,
+            standby_addr: None,
+            listen_addr: "127.0.0.1:9400".to_string(),
+            auth_token: None,
+            ack_timeout_ms: 5000,
+            heartbeat_interval_ms: 2000,
+            takeover_timeout_ms: 15000,
+        }
+    }
+}
+
+impl ReplicationConfig {
+    pub fn ack_timeout(&self) -> Duration {
+        Duration::from_millis(self.ack_timeout_ms)
+    }
+
+    pub fn heartbeat_interval(&self) -> Duration {
+        Duration::from_millis(self.heartbeat_interval_ms)
+    }
+
+    pub fn takeover_timeout(&self) -> Duration {
+        Duration::from_millis(self.takeover_timeout_ms)
+    }
+}
+
+/// Notifications of new commits for downstream incremental consumers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CommitFeedConfig {
+    /// Follow the Delta log and publish each new commit
+    pub enabled: bool,
+    /// How often to check the log for new commits, in milliseconds
+    pub poll_interval_ms: u64,
+    /// Commits buffered per subscriber before a slow one starts missing them
+    pub capacity: usize,
+    /// Column whose maximum across added files is reported as the watermark
+    pub watermark_column: Option<String>,
+}
+
+impl Default for CommitFeedConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            poll_interval_ms: 1000,
+            capacity: 256,
+            watermark_column: None,
+        }
+    }
+}
+
+impl CommitFeedConfig {
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.poll_interval_ms)
+    }
+}
+
+/// Post-commit data tests, in the spirit of dbt tests: each one is a query
+/// returning the offending rows, and passes when it returns none
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QualityConfig {
+    pub enabled: bool,
+    /// How often to check for new versions to test, in milliseconds
+    pub poll_interval_ms: u64,
+    /// Hold the validated watermark (and the commit feed) at the last
+    /// passing version while any test fails
+    pub quarantine: bool,
+    pub tests: Vec<QualityTest>,
+}
+
+impl Default for QualityConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            poll_interval_ms: 5000,
+            quarantine: false,
+            tests: Vec::new(),
+        }
+    }
+}
+
+impl QualityConfig {
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.poll_interval_ms)
+    }
+}
+
+/// Routing of operational alerts to the teams owning each table. Every
+/// route matching an alert is notified.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AlertsConfig {
+    pub routes: Vec<AlertRoute>,
+}
+
+/// Alerts of some types, for some tables, sent to one destination
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertRoute {
+    /// Alert types routed here; empty matches every type
+    #[serde(default)]
+    pub alerts: Vec<AlertKind>,
+    /// Table names (the last segment of the URI) routed here; empty matches
+    /// every table
+    #[serde(default)]
+    pub tables: Vec<String>,
+    pub destination: AlertDestination,
+    /// After notifying, further alerts of the same type for the table are
+    /// held back this many seconds and counted into the next notification
+    #[serde(default = "default_alert_cooldown_secs")]
+    pub cooldown_secs: u64,
+}
+
+/// Conditions the orchestrator raises alerts for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertKind {
+    /// A write took longer than `writer.max_latency_ms`
+    SlaMiss,
+    /// Writes keep failing after retries and batches are being given up on
+    CircuitOpen,
+    /// A vacuum cycle took longer than its interval
+    VacuumOverrun,
+    /// Data tests found offending rows in a new version
+    ValidationSpike,
+}
+
+/// Where an alert is sent
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AlertDestination {
+    /// POST the alert as JSON
+    Webhook { url: String },
+    /// PagerDuty Events API v2
+    Pagerduty { routing_key: String },
+    /// Only log the alert
+    Log,
+}
+
+impl AlertRoute {
+    pub fn matches(&self, kind: AlertKind, table: &str) -> bool {
+        (self.alerts.is_empty() || self.alerts.contains(&kind))
+            && (self.tables.is_empty() || self.tables.iter().any(|t| t == table))
+    }
+
+    pub fn cooldown(&self) -> Duration {
+        Duration::from_secs(self.cooldown_secs)
+    }
+}
+
+fn default_alert_cooldown_secs() -> u64 {
+    300
+}
+
+/// Declared schema used to create a missing table on startup
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TableSchemaConfig {
+    pub columns: Vec<SchemaColumn>,
+    /// Table properties set at creation, e.g. `delta.appendOnly = "true"`
+    pub properties: BTreeMap<String, String>,
+}
+
+/// A column of a declared table schema
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemaColumn {
+    pub name: String,
+    /// Delta type name, e.g. `long`, `string`, `timestamp` or `decimal(10,2)`
+    #[serde(rename = "type")]
+    pub data_type: String,
+    #[serde(default = "default_nullable")]
+    pub nullable: bool,
+    /// Partition the table by this column
+    #[serde(default)]
+    pub partition: bool,
+}
+
+fn default_nullable() -> bool {
+    true
+}
+
+/// What to do when the table differs from the declared expectations
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DriftPolicy {
+    /// Refuse to start
+    Fail,
+    /// Log each difference and start anyway
+    #[default]
+    Warn,
+    /// Set differing table properties and constraints to the declared
+    /// values; refuse to start if anything else differs
+    Reconcile,
+}
+
+/// Declared layout of the table, compared against its metadata on startup.
+/// Anything left unset is not checked.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExpectationsConfig {
+    pub policy: DriftPolicy,
+    pub partition_columns: Option<Vec<String>>,
+    /// Columns that must exist; other table columns are ignored
+    pub columns: Vec<ExpectedColumn>,
+    /// Expected value of `delta.appendOnly`
+    pub append_only: Option<bool>,
+    /// CHECK constraints by name, as SQL expressions
+    pub constraints: BTreeMap<String, String>,
+}
+
+/// A column the table must have
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExpectedColumn {
+    pub name: String,
+    /// Delta type name, e.g. `long`, `string` or `timestamp`
+    pub data_type: Option<String>,
+    pub nullable: Option<bool>,
+}
+
+/// A named data test
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QualityTest {
+    pub name: String,
+    #[serde(flatten)]
+    pub kind: QualityTestKind,
+}
+
+/// Built-in tests; the table under test is available to SQL as `this`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum QualityTestKind {
+    /// No two rows share the same values in `columns`
+    Unique { columns: Vec<String> },
+    NotNull { column: String },
+    /// Table row count stays within bounds
+    RowCount { min: Option<u64>, max: Option<u64> },
+    /// The newest `column` value is at most `max_age_secs` old
+    Freshness { column: String, max_age_secs: u64 },
+    /// Every non-null `column` value exists in `to_column` of the table at
+    /// `to_table_uri`, which is available to SQL as `ref`
+    Relationship { column: String, to_table_uri: String, to_column: String },
+    /// Custom query returning the failing rows
+    Sql { query: String },
+}
+
+/// A secondary store written after each Delta commit. Sink failures never
+/// block the writer: batches are retried independently and dead-lettered to
+/// Parquet files once retries are exhausted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SinkConfig {
+    /// Name used in logs, metrics and dead-letter file names
+    pub name: String,
+    #[serde(flatten)]
+    pub kind: SinkKind,
+    /// Committed batches that may wait for the sink before being dead-lettered
+    #[serde(default = "default_queue_capacity")]
+    pub queue_capacity: usize,
+    #[serde(default = "default_sink_max_retries")]
+    pub max_retries: u32,
+    /// Delay before the first retry in milliseconds, doubled per attempt
+    #[serde(default = "default_sink_retry_delay_ms")]
+    pub retry_delay_ms: u64,
+    /// Upper bound on the retry delay in milliseconds
+    #[serde(default = "default_sink_max_retry_delay_ms")]
+    pub max_retry_delay_ms: u64,
+    /// Directory receiving batches the sink could not accept
+    #[serde(default = "default_dead_letter_dir")]
+    pub dead_letter_dir: String,
+}
+
+/// Connection details of a secondary sink
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SinkKind {
+    /// ClickHouse over its HTTP interface
+    Clickhouse {
+        /// Base URL, e.g. `http://localhost:8123`
+        url: String,
+        table: String,
+        #[serde(default = "default_progress_table")]
+        progress_table: String,
+        #[serde(default)]
+        user: Option<String>,
+        #[serde(default)]
+        password: Option<String>,
+    },
+    /// PostgreSQL; rows and progress are written in one transaction
+    Postgres {
+        /// libpq-style connection string
+        connection_string: String,
+        table: String,
+        #[serde(default = "default_progress_table")]
+        progress_table: String,
+    },
+}
+
+fn default_sink_max_retries() -> u32 {
+    5
+}
+
+fn default_sink_retry_delay_ms() -> u64 {
+    500
+}
+
+fn default_sink_max_retry_delay_ms() -> u64 {
+    30_000
+}
+
+fn default_dead_letter_dir() -> String {
+    "dead_letter".to_string()
+}
+
+fn default_progress_table() -> String {
+    "surgical_strike_progress".to_string()
+}
+
+/// Moves partitions older than `max_age_days` into an archive table. Rows
+/// are no longer expected to arrive for archived partitions, so the age must
+/// exceed the latest data the writers can receive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveConfig {
+    /// URI of the archive Delta table, created on first use
+    pub archive_table_uri: String,
+    /// Date partition column of the hot table
+    pub partition_column: String,
+    /// chrono format of the partition values
+    #[serde(default = "default_partition_format")]
+    pub partition_format: String,
+    /// Partitions dated more than this many days ago are archived
+    pub max_age_days: u64,
+    /// Archive interval in seconds
+    #[serde(default = "default_archive_interval_secs")]
+    pub archive_interval_secs: u64,
+    /// Options overriding the hot table's for the archive, e.g. another bucket's credentials
+    #[serde(default)]
+    pub storage_options: HashMap<String, String>,
+}
+
+fn default_partition_format() -> String {
+    "%Y-%m-%d".to_string()
+}
+
+fn default_archive_interval_secs() -> u64 {
+    6 * 3600 // 6 hours
+}
+
+impl ArchiveConfig {
+    pub fn archive_interval(&self) -> Duration {
+        Duration::from_secs(self.archive_interval_secs)
+    }
+}
+
+/// Kafka topic consumed with exactly-once delivery into the table
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KafkaSourceConfig {
+    /// Comma-separated bootstrap servers
+    pub brokers: String,
+    pub topic: String,
+    /// Consumer group, also part of the offsets' application transaction id
+    pub group_id: String,
+    /// Where to start when the table has no committed offsets for a partition
+    #[serde(default = "default_auto_offset_reset")]
+    pub auto_offset_reset: String,
+    /// Extra librdkafka properties (security, timeouts, ...)
+    #[serde(default)]
+    pub properties: HashMap<String, String>,
+    /// Pause the partitions once the writer queue is this full (0-1)
+    #[serde(default = "default_pause_queue_fraction")]
+    pub pause_queue_fraction: f64,
+    /// Resume them once the writer queue has drained to this fraction
+    #[serde(default = "default_resume_queue_fraction")]
+    pub resume_queue_fraction: f64,
+}
+
+fn default_auto_offset_reset() -> String {
+    "earliest".to_string()
+}
+
+fn default_pause_queue_fraction() -> f64 {
+    0.9
+}
+
+fn default_resume_queue_fraction() -> f64 {
+    0.5
+}
+
+/// Pulsar topics consumed into the table. With several `[[tables]]`, each
+/// table subscribes to the topics routed to it by `topics` or `topic_pattern`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PulsarSourceConfig {
+    /// Broker service URL, e.g. `pulsar://localhost:6650`
+    pub service_url: String,
+    /// Admin REST endpoint used to look up topic schemas, e.g. `http://localhost:8080`.
+    /// Without it, column types are inferred from the messages.
+    pub admin_url: Option<String>,
+    /// Topics to consume
+    #[serde(default)]
+    pub topics: Vec<String>,
+    /// Regex of full topic names to consume, re-evaluated as topics are created
+    pub topic_pattern: Option<String>,
+    /// Namespace searched for `topic_pattern` matches, e.g. `public/default`
+    pub namespace: Option<String>,
+    /// Subscription, also part of the positions' application transaction id
+    pub subscription: String,
+    #[serde(default)]
+    pub subscription_type: PulsarSubscriptionType,
+    /// Where a new subscription starts: `earliest` or `latest`
+    #[serde(default = "default_auto_offset_reset")]
+    pub initial_position: String,
+    /// JWT for the broker and the admin API
+    pub auth_token: Option<String>,
+}
+
+/// Kinesis stream consumed with exactly-once delivery into the table
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KinesisSourceConfig {
+    pub stream_name: String,
+    /// Region of the stream; the AWS provider chain resolves one otherwise
+    pub region: Option<String>,
+    /// Endpoint override, e.g. LocalStack
+    pub endpoint_url: Option<String>,
+    /// Where shards without a committed position start: `earliest` or `latest`
+    #[serde(default = "default_auto_offset_reset")]
+    pub initial_position: String,
+    /// Interval between looks for shards created by resharding, in seconds
+    #[serde(default = "default_shard_discovery_interval_secs")]
+    pub shard_discovery_interval_secs: u64,
+}
+
+fn default_shard_discovery_interval_secs() -> u64 {
+    60
+}
+
+impl KinesisSourceConfig {
+    pub fn shard_discovery_interval(&self) -> Duration {
+        Duration::from_secs(self.shard_discovery_interval_secs)
+    }
+}
+
+/// SQS queue receiving S3 `ObjectCreated` notifications, directly or through
+/// SNS. The objects are read with the table's storage options and appended.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SqsSourceConfig {
+    pub queue_url: String,
+    /// Region of the queue; the AWS provider chain resolves one otherwise
+    pub region: Option<String>,
+    /// Endpoint override, e.g. LocalStack
+    pub endpoint_url: Option<String>,
+    /// Format of every object; inferred from each key's extension when unset
+    pub format: Option<ObjectFormat>,
+    /// Glob the object keys must match, e.g. `events/**/*.json`; other
+    /// notifications are acknowledged without reading the object
+    pub key_pattern: Option<String>,
+    /// Long-poll wait of each receive in seconds, at most 20
+    #[serde(default = "default_sqs_wait_time_secs")]
+    pub wait_time_secs: u64,
+    /// Seconds a received notification stays hidden from other consumers;
+    /// it must outlast reading and committing a batch. The queue's own
+    /// setting applies when unset.
+    pub visibility_timeout_secs: Option<u64>,
+}
+
+fn default_sqs_wait_time_secs() -> u64 {
+    20
+}
+
+/// Changes of one PostgreSQL table, decoded from a `pgoutput` logical
+/// replication slot. Each row carries `_change_type` (`insert`, `update` or
+/// `delete`) and `_commit_lsn`; append them as a change log, or apply them
+/// with `write_mode = { mode = "merge", key_columns = [...],
+/// delete_predicate = "source._change_type = 'delete'" }`. Deletes carry only
+/// the replica identity columns, and updates omit unchanged TOAST values
+/// unless the table has `REPLICA IDENTITY FULL`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostgresCdcConfig {
+    /// libpq connection string of a role with the REPLICATION attribute
+    pub connection_string: String,
+    /// Replication slot, created with the `pgoutput` plugin if missing
+    pub slot: String,
+    /// Publication including the table
+    pub publication: String,
+    /// Table as `schema.table`; changes to other published tables are skipped
+    pub table: String,
+    /// Interval between polls of a slot with nothing new, in milliseconds
+    #[serde(default = "default_cdc_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+}
+
+fn default_cdc_poll_interval_ms() -> u64 {
+    1000
+}
+
+impl PostgresCdcConfig {
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.poll_interval_ms)
+    }
+}
+
+/// Encoding of objects ingested from storage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ObjectFormat {
+    /// Newline-delimited JSON, or a single JSON array
+    Json,
+    /// CSV with a header row
+    Csv,
+    Parquet,
+}
+
+impl ObjectFormat {
+    /// Format implied by a key's extension, e.g. `.ndjson` or `.parquet`
+    pub fn from_key(key: &str) -> Option<Self> {
+        let extension = key.rsplit_once('.')?.1.to_ascii_lowercase();
+        match extension.as_str() {
+            "json" | "jsonl" | "ndjson" => Some(Self::Json),
+            "csv" => Some(Self::Csv),
+            "parquet" => Some(Self::Parquet),
+            _ => None,
+        }
+    }
+}
+
+/// Producer sequence numbers carried in the messages of a source, checked
+/// for gaps, out-of-order and duplicate deliveries per key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SequenceTrackingConfig {
+    /// Column holding the producer's sequence number
+    pub column: String,
+    /// Columns identifying an independently numbered stream, e.g. the
+    /// producer id or partition; empty treats the source as one stream
+    #[serde(default)]
+    pub key_columns: Vec<String>,
+    /// Delta table each gaps report is appended to, created on first use
+    pub gaps_table_uri: Option<String>,
+    /// Interval between gaps reports in seconds
+    #[serde(default = "default_gap_report_interval_secs")]
+    pub report_interval_secs: u64,
+}
+
+fn default_gap_report_interval_secs() -> u64 {
+    60
+}
+
+impl SequenceTrackingConfig {
+    pub fn report_interval(&self) -> Duration {
+        Duration::from_secs(self.report_interval_secs)
+    }
+}
+
+/// How consumers of one subscription share its topics
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PulsarSubscriptionType {
+    /// One active consumer per partition, in order; positions are committed
+    /// with the rows for exactly-once delivery
+    #[default]
+    Failover,
+    /// Messages spread over every consumer; delivery is at-least-once
+    Shared,
+}
+
+/// Optional HTTP server for row ingestion
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpConfig {
+    /// Serve the HTTP API alongside the three processes
+    pub enabled: bool,
+    /// Socket address to listen on
+    pub bind_address: String,
+    /// Largest accepted request body in bytes
+    pub max_body_bytes: usize,
+    /// Bearer token every POST request (ingestion, jobs, flush, pause and
+    /// resume) must present in its `Authorization` header. Without one those
+    /// routes are open to anyone who can reach `bind_address`.
+    pub auth_token: Option<String>,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind_address: "127.0.0.1:8080".to_string(),
+            max_body_bytes: 64 * 1024 * 1024, // 64 MB
+            auth_token: None,
+        }
+    }
+}
+
+/// Optional Arrow Flight SQL endpoint for reading the tables (requires the `flight` feature)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FlightConfig {
+    pub enabled: bool,
+    /// Socket address to listen on
+    pub bind_address: String,
+}
+
+impl Default for FlightConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind_address: "0.0.0.0:50051".to_string(),
+        }
+    }
+}
+
+/// Optional gRPC endpoint for writing batches and triggering maintenance
+/// (requires the `grpc` feature)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GrpcConfig {
+    pub enabled: bool,
+    /// Socket address to listen on
+    pub bind_address: String,
+    /// Bearer token `WriteBatch`, `TriggerCompaction` and `TriggerVacuum`
+    /// calls must send as `authorization` metadata
+    pub auth_token: Option<String>,
+}
+
+impl Default for GrpcConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind_address: "127.0.0.1:50052".to_string(),
+            auth_token: None,
+        }
+    }
+}
+
+/// Upload concurrency shared by the tables of a multi-table deployment
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SchedulingConfig {
+    /// Flushes that may upload at once across all tables, handed out in
+    /// proportion to each table's `writer.flush_weight`; 0 means no limit.
+    /// Taken from the first table that sets it.
+    pub max_concurrent_flushes: usize,
+}
+
+/// DynamoDB-based commit locking for safe concurrent writers on S3.
+///
+/// delta-rs' `S3DynamoDbLogStore` does not take leases: a writer uploads its
+/// commit to a temporary file, claims the version with a conditional put of
+/// an incomplete entry, copies the file into `_delta_log` and marks the entry
+/// complete. An entry left incomplete by a crashed writer is repaired by the
+/// next writer or reader that finds it, and only completed entries get the
+/// `expireTime` that DynamoDB's TTL acts on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LockingConfig {
+    /// Coordinate commits through DynamoDB (required for multi-host S3 writers)
+    pub enabled: bool,
+    /// DynamoDB table holding the commit locks
+    pub table_name: String,
+    /// How long delta-rs keeps retrying throttled or failed DynamoDB requests,
+    /// in seconds; unset keeps the delta-rs default of 60
+    pub max_elapsed_request_secs: Option<u64>,
+    /// Age in seconds of an incomplete commit entry's temporary file before
+    /// the lock monitor repairs it. This is the monitor's own grace period
+    /// for a writer still finishing its commit, not a delta-rs setting.
+    #[serde(alias = "lease_duration_secs")]
+    pub stale_entry_secs: u64,
+    /// Custom DynamoDB endpoint, e.g. DynamoDB Local
+    pub dynamodb_endpoint: Option<String>,
+    /// Create the lock table on startup if it does not exist
+    pub create_table: bool,
+    /// How often to look for commit entries abandoned by crashed writers, in seconds
+    pub health_check_interval_secs: u64,
+}
+
+impl Default for LockingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            table_name: "delta_log".to_string(),
+            max_elapsed_request_secs: None,
+            stale_entry_secs: 20,
+            dynamodb_endpoint: None,
+            create_table: true,
+            health_check_interval_secs: 30,
+        }
+    }
+}
+
+impl LockingConfig {
+    pub fn health_check_interval(&self) -> Duration {
+        Duration::from_secs(self.health_check_interval_secs)
+    }
+}
+
+/// Azure Data Lake Storage Gen2 account and credential. Without a
+/// `credential`, the `AZURE_*` environment variables are used, falling back
+/// to the host's managed identity.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AzureConfig {
+    /// Storage account; taken from the table URI when omitted
+    pub account_name: Option<String>,
+    pub credential: Option<AzureCredential>,
+    /// Talk to a local Azurite emulator (at `AZURITE_BLOB_STORAGE_URL`)
+    pub use_emulator: bool,
+    /// Custom blob endpoint, e.g. for sovereign clouds or private links
+    pub endpoint: Option<String>,
+}
+
+/// How to authenticate against the storage account
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AzureCredential {
+    /// Shared account access key
+    AccountKey { key: String },
+    /// Shared access signature query string
+    Sas { token: String },
+    /// Entra ID application with a client secret
+    ServicePrincipal { tenant_id: String, client_id: String, client_secret: String },
+    /// Managed identity of the host; `client_id` selects a user-assigned identity
+    ManagedIdentity { client_id: Option<String>, msi_endpoint: Option<String> },
+}
+
+/// How the writer applies incoming batches to the table
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum WriteMode {
+    /// Append every row as new data
+    #[default]
+    Append,
+    /// Update rows whose key columns match and insert the rest (upsert)
+    Merge {
+        key_columns: Vec<String>,
+        /// SQL predicate over the incoming row, referenced as `source`, that
+        /// deletes the matching row instead, e.g. `source._change_type = 'delete'`
+        #[serde(default)]
+        delete_predicate: Option<String>,
+    },
+}
+
+/// How the writer reacts when an incoming batch does not match the table schema
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SchemaEvolutionMode {
+    /// Reject batches whose schema differs from the table
+    #[default]
+    None,
+    /// Add columns that are new in the batch to the table schema
+    AddColumns,
+    /// Update the table schema in place to take the batch's new columns;
+    /// type changes are refused, since they would need every existing file
+    /// rewritten. Existing rows are always kept.
+    Overwrite,
+}
 
 /// Configuration for the Writer process
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct WriterConfig {
     /// Maximum batch size before forcing a write
     pub max_batch_size: usize,
@@ -12,8 +1159,342 @@ pub struct WriterConfig {
     pub max_latency_ms: u64,
     /// Number of retries on write failure
     pub max_retries: u32,
-    /// Backoff delay between retries in milliseconds
+    /// Delay before the first retry in milliseconds
     pub retry_delay_ms: u64,
+    /// Factor applied to the retry delay after each failed attempt
+    pub retry_backoff_multiplier: f64,
+    /// Upper bound on the retry delay in milliseconds
+    pub max_retry_delay_ms: u64,
+    /// Random spread applied to each delay, as a fraction (0.2 = ±20%)
+    pub retry_jitter: f64,
+    /// Times a commit that lost a race with another writer is retried
+    /// against the new table version, reusing the already written files
+    pub max_conflict_retries: u32,
+    /// Number of batches that may wait in the writer queue before producers block
+    #[serde(default = "default_queue_capacity")]
+    pub queue_capacity: usize,
+    /// How incoming batches are applied to the table
+    #[serde(default)]
+    pub write_mode: WriteMode,
+    /// Schema evolution behaviour for batches with new or changed columns
+    #[serde(default)]
+    pub schema_evolution: SchemaEvolutionMode,
+    /// Rolled-up companion tables maintained from the same input batches
+    #[serde(default)]
+    pub rollups: Vec<RollupConfig>,
+    /// Window over which per-partition write volume is tracked, in seconds
+    pub partition_metrics_window_secs: u64,
+    /// Number of hottest partitions reported in writer metrics
+    pub hot_partitions: usize,
+    /// Align time-based flushes to wall-clock multiples of this many seconds
+    /// (15 flushes at :00/:15/:30/:45) instead of `max_batch_time`, and tag
+    /// each commit with its window
+    pub flush_alignment_secs: Option<u64>,
+    /// Local directory or object store prefix where queued batches that
+    /// exhaust their retries are kept for replay. Without it they are
+    /// dropped, unless the HTTP or gRPC API already acknowledged them or a
+    /// source reads the table's input, in which case they go to
+    /// `dead_letter/{table}` in the state directory.
+    pub dead_letter_uri: Option<String>,
+    /// Share of the flush slots this table gets when
+    /// `scheduling.max_concurrent_flushes` limits them across tables
+    pub flush_weight: u32,
+    /// Keep rows older than an event-time watermark out of the table's
+    /// finalized partitions
+    pub lateness: Option<LatenessConfig>,
+    /// Workers encoding and uploading the Parquet files of a flush in
+    /// parallel, sharded by partition key; the files still go into one commit
+    pub write_workers: usize,
+    /// Commit the files of all flushes finishing within this many
+    /// milliseconds together, instead of one commit per flush
+    pub commit_interval_ms: Option<u64>,
+    /// Limits a write is checked against before it is committed
+    pub guardrails: GuardrailsConfig,
+    /// Encoding of the Parquet files flushes write
+    pub parquet: ParquetConfig,
+    /// Columns each flush is sorted by before encoding, e.g. `device_id`
+    /// then `ts`, so that repeated values sit next to each other
+    pub sort_columns: Vec<String>,
+    /// Row-level rules checked before each flush is written; breaking rows
+    /// are quarantined instead of failing the flush
+    pub validation: Option<ValidationConfig>,
+    /// Reshaping applied, in order, to every flush before anything else
+    pub transforms: Vec<Transform>,
+    /// Dimension tables `enrich` transforms join against, by name
+    pub lookups: BTreeMap<String, LookupConfig>,
+    /// Partition columns computed from each row's event time, after the
+    /// transforms, so producers do not have to send them
+    pub partition_by_event_time: Option<EventTimePartitioning>,
+    /// Days an idempotency key is remembered. Every key is an application
+    /// transaction the table keeps forever, growing its log state and
+    /// checkpoints; with this set, `delta.setTransactionRetentionDuration`
+    /// expires keys older than that and a later resubmission writes again.
+    pub idempotency_key_retention_days: Option<u64>,
+}
+
+/// Partition values derived from an event-time column. The derived columns
+/// must be partition columns of the table, e.g. declared in `[schema]`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventTimePartitioning {
+    /// Date or timestamp column; cast string timestamps with a transform first
+    pub event_time_column: String,
+    pub partitions: Vec<DerivedPartition>,
+}
+
+/// A partition column holding the event time in a chrono format
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DerivedPartition {
+    pub column: String,
+    /// e.g. `%Y-%m-%d` for `date=2024-01-31` or `%H` for `hour=07`
+    pub format: String,
+}
+
+/// One step of the transform chain
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Transform {
+    Rename { from: String, to: String },
+    /// Cast to a Delta type name such as `long` or `date`; values that do
+    /// not convert become null
+    Cast { column: String, data_type: String },
+    /// Add or replace `column` with a SQL expression over the batch, e.g.
+    /// `amount * 100` or `'web'` for a literal
+    Derive { column: String, expr: String },
+    Drop { columns: Vec<String> },
+    /// Add the columns of a configured lookup to each row whose `on`
+    /// column matches its key, replacing batch columns of the same name;
+    /// rows without a match get nulls
+    Enrich { lookup: String, on: String },
+}
+
+/// Delta table of dimension rows kept in memory for `enrich` transforms,
+/// e.g. the site of each device
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LookupConfig {
+    /// Read with the storage options of the table being written
+    pub table_uri: String,
+    /// Column identifying a row; the last row read wins for repeated keys
+    pub key_column: String,
+    /// Columns added to enriched rows
+    pub columns: Vec<String>,
+    /// Seconds the loaded rows are used before the table is checked for a
+    /// new version
+    #[serde(default = "default_lookup_ttl_secs")]
+    pub ttl_secs: u64,
+}
+
+fn default_lookup_ttl_secs() -> u64 {
+    300
+}
+
+impl LookupConfig {
+    pub fn ttl(&self) -> Duration {
+        Duration::from_secs(self.ttl_secs)
+    }
+}
+
+/// Rules every row must pass, and where the rows that do not go
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationConfig {
+    pub rules: Vec<ValidationRule>,
+    #[serde(flatten)]
+    pub quarantine: QuarantineTarget,
+}
+
+/// A check on one column of each incoming row
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum ValidationRule {
+    NotNull { column: String },
+    /// Inclusive bounds on a numeric column
+    Range { column: String, min: Option<f64>, max: Option<f64> },
+    /// Regular expression the whole string value must match
+    Regex { column: String, pattern: String },
+    /// Values, compared as strings, the column may hold
+    AllowedValues { column: String, values: Vec<String> },
+}
+
+impl ValidationRule {
+    pub fn column(&self) -> &str {
+        match self {
+            Self::NotNull { column }
+            | Self::Range { column, .. }
+            | Self::Regex { column, .. }
+            | Self::AllowedValues { column, .. } => column,
+        }
+    }
+}
+
+impl std::fmt::Display for ValidationRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotNull { column } => write!(f, "{} is null", column),
+            Self::Range { column, min, max } => {
+                let bound = |b: &Option<f64>| b.map_or("-".to_string(), |b| b.to_string());
+                write!(f, "{} outside [{}, {}]", column, bound(min), bound(max))
+            }
+            Self::Regex { column, pattern } => write!(f, "{} does not match {}", column, pattern),
+            Self::AllowedValues { column, .. } => write!(f, "{} not an allowed value", column),
+        }
+    }
+}
+
+/// Where rows breaking a validation rule are kept
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "quarantine", rename_all = "snake_case")]
+pub enum QuarantineTarget {
+    /// Append them to a separate table, with a `_violation` column naming
+    /// the rule each broke
+    Table { quarantine_table_uri: String },
+    /// Keep them unchanged in the writer's dead letter queue
+    /// (`dead_letter_uri`), for replay once the rules or data are fixed
+    DeadLetter,
+}
+
+/// Limits that keep a misbehaving producer from degrading the table. A
+/// write breaking one fails at once, without retries; unset limits are not
+/// enforced.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GuardrailsConfig {
+    /// Columns the table may grow to through schema evolution
+    pub max_columns: Option<usize>,
+    /// Data files a single flush may add; with `commit_interval_ms` the
+    /// limit applies to each flush joining a commit
+    pub max_files_per_commit: Option<usize>,
+    /// Commits the writer may make in any 60 second window
+    pub max_commits_per_minute: Option<u32>,
+}
+
+/// Parquet writer settings. Unset values keep the defaults of the Parquet
+/// writer, except the codec: Snappy for flushes and zstd for compaction.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ParquetConfig {
+    pub compression: Option<ParquetCompression>,
+    /// zstd level from 1 to 22, 4 when unset; other codecs have no levels
+    pub compression_level: Option<i32>,
+    /// Maximum rows per row group
+    pub max_row_group_size: Option<usize>,
+    /// Target size of a data page in bytes
+    pub data_page_size: Option<usize>,
+    /// Dictionary-encode columns, falling back to plain encoding per column
+    /// once its dictionary page grows too large
+    pub dictionary_enabled: bool,
+}
+
+/// Parquet compression codecs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParquetCompression {
+    Zstd,
+    Snappy,
+    Uncompressed,
+}
+
+impl Default for ParquetConfig {
+    fn default() -> Self {
+        Self {
+            compression: None,
+            compression_level: None,
+            max_row_group_size: None,
+            data_page_size: None,
+            dictionary_enabled: true,
+        }
+    }
+}
+
+impl ParquetConfig {
+    /// Writer properties for these settings, with `default_compression`
+    /// when no codec is configured
+    pub fn writer_properties(&self, default_compression: ParquetCompression) -> Result<WriterProperties> {
+        let compression = match self.compression.unwrap_or(default_compression) {
+            ParquetCompression::Zstd => {
+                let level = ZstdLevel::try_new(self.compression_level.unwrap_or(4))
+                    .context("Invalid zstd compression level")?;
+                Compression::ZSTD(level)
+            }
+            ParquetCompression::Snappy => Compression::SNAPPY,
+            ParquetCompression::Uncompressed => Compression::UNCOMPRESSED,
+        };
+        let mut builder = WriterProperties::builder()
+            .set_compression(compression)
+            .set_dictionary_enabled(self.dictionary_enabled);
+        if let Some(rows) = self.max_row_group_size {
+            builder = builder.set_max_row_group_size(rows);
+        }
+        if let Some(bytes) = self.data_page_size {
+            builder = builder.set_data_page_size_limit(bytes);
+        }
+        Ok(builder.build())
+    }
+}
+
+/// Rows whose event time is more than `horizon_secs` behind the clock
+/// belong to partitions downstream jobs already consider complete
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LatenessConfig {
+    /// Timestamp or date column holding each row's event time
+    pub event_time_column: String,
+    /// How far behind now the watermark trails, in seconds
+    pub horizon_secs: u64,
+    #[serde(flatten)]
+    pub policy: LatePolicy,
+}
+
+/// What the writer does with late rows
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "policy", rename_all = "snake_case")]
+pub enum LatePolicy {
+    /// Append them to a separate table instead
+    Route { late_table_uri: String },
+    /// Keep them in the table with a `_late` column set to true; the table
+    /// needs that column, or `schema_evolution` allowing it to be added
+    Flag,
+    /// Discard them, counting them in the writer metrics
+    Drop,
+}
+
+impl LatenessConfig {
+    pub fn horizon(&self) -> Duration {
+        Duration::from_secs(self.horizon_secs)
+    }
+}
+
+/// A companion table holding windowed aggregates of the raw table
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RollupConfig {
+    /// URI of the rollup Delta table
+    pub table_uri: String,
+    /// Event-time column used to assign rows to windows
+    pub time_column: String,
+    /// Window size as a Polars duration string, e.g. "1m" or "1h"
+    pub window: String,
+    /// Columns to group by within each window
+    #[serde(default)]
+    pub group_by: Vec<String>,
+    /// Aggregates computed per group
+    pub aggregates: Vec<AggregateSpec>,
+}
+
+/// A single aggregate column in a rollup table
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AggregateSpec {
+    /// Source column in the raw batch
+    pub column: String,
+    pub function: AggregateFunction,
+    /// Output column name in the rollup table
+    pub alias: String,
+}
+
+/// Aggregates that can be folded incrementally across batches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AggregateFunction {
+    Count,
+    Sum,
+    Min,
+    Max,
 }
 
 impl Default for WriterConfig {
@@ -24,21 +1505,76 @@ impl Default for WriterConfig {
             max_latency_ms: 250,     // 250ms SLA
             max_retries: 3,
             retry_delay_ms: 100,
+            retry_backoff_multiplier: 2.0,
+            max_retry_delay_ms: 10_000, // 10 seconds
+            retry_jitter: 0.2,
+            max_conflict_retries: 10,
+            queue_capacity: default_queue_capacity(),
+            write_mode: WriteMode::Append,
+            schema_evolution: SchemaEvolutionMode::None,
+            rollups: Vec::new(),
+            partition_metrics_window_secs: 900, // 15 minutes
+            hot_partitions: 10,
+            flush_alignment_secs: None,
+            dead_letter_uri: None,
+            flush_weight: 1,
+            lateness: None,
+            write_workers: 1,
+            commit_interval_ms: None,
+            guardrails: GuardrailsConfig::default(),
+            parquet: ParquetConfig::default(),
+            sort_columns: Vec::new(),
+            validation: None,
+            transforms: Vec::new(),
+            lookups: BTreeMap::new(),
+            partition_by_event_time: None,
+            idempotency_key_retention_days: None,
         }
     }
 }
 
+fn default_queue_capacity() -> usize {
+    1024
+}
+
 /// Configuration for the Compaction process
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct CompactionConfig {
     /// Target file size in bytes for compacted files
     pub target_file_size_bytes: u64,
     /// Minimum number of files to trigger compaction
     pub min_files_to_compact: usize,
+    /// Files below this size count towards `small_file_ratio`; defaults to
+    /// `target_file_size_bytes`
+    pub small_file_threshold_bytes: Option<u64>,
+    /// Compact a partition once this fraction (0-1) of its files is small,
+    /// instead of once it holds `min_files_to_compact` small files
+    pub small_file_ratio: Option<f64>,
     /// Compaction interval in seconds
     pub compaction_interval_secs: u64,
-    /// Maximum concurrent compaction tasks
+    /// Cron expression (UTC) to compact at instead of every interval,
+    /// e.g. `0 1-5 * * *` for off-peak hours only
+    pub compaction_schedule: Option<CronSchedule>,
+    /// Maximum partitions optimized concurrently within a compaction cycle
     pub max_concurrent_compactions: usize,
+    /// Columns summarized in the file manifest rewritten after each
+    /// compaction; empty disables the manifest
+    pub manifest_columns: Vec<String>,
+    /// Encoding of the compacted files, usually with larger row groups and
+    /// stronger compression than the writer's
+    pub parquet: ParquetConfig,
+    /// Maximum file groups of a partition that delta-rs rewrites
+    /// concurrently; unset keeps its default of one task per CPU
+    pub max_concurrent_tasks: Option<usize>,
+    /// After each compaction, re-compute statistics of files still missing
+    /// them on a stats column from their Parquet footers
+    pub restat: bool,
+    /// Compact the partitions an `import` or `register` touched as soon as
+    /// it completes, instead of waiting for the next cycle
+    pub optimize_after_import: bool,
+    /// Also write a checkpoint after that compaction
+    pub checkpoint_after_import: bool,
 }
 
 impl Default for CompactionConfig {
@@ -46,21 +1582,45 @@ impl Default for CompactionConfig {
         Self {
             target_file_size_bytes: 128 * 1024 * 1024, // 128 MB
             min_files_to_compact: 5,
+            small_file_threshold_bytes: None,
+            small_file_ratio: None,
             compaction_interval_secs: 300, // 5 minutes
+            compaction_schedule: None,
             max_concurrent_compactions: 2,
+            manifest_columns: Vec::new(),
+            parquet: ParquetConfig::default(),
+            max_concurrent_tasks: None,
+            restat: false,
+            optimize_after_import: false,
+            checkpoint_after_import: false,
         }
     }
 }
 
 /// Configuration for the Vacuum process
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct VacuumConfig {
     /// Retention period in hours
     pub retention_hours: u64,
     /// Vacuum interval in seconds
     pub vacuum_interval_secs: u64,
+    /// Cron expression (UTC) to vacuum at instead of every interval
+    pub vacuum_schedule: Option<CronSchedule>,
     /// Whether to perform dry runs first
     pub dry_run: bool,
+    /// Also run a cycle whenever compaction replaced files, instead of only
+    /// at the next scheduled one. Retention still applies: each file is
+    /// deleted once it has been out of the table for `retention_hours`.
+    pub after_compaction: bool,
+    /// Afterwards delete directory markers and empty directories of
+    /// partitions left without data
+    pub remove_empty_partitions: bool,
+    /// Partition prefixes listed concurrently when taking a file inventory
+    pub list_concurrency: usize,
+    /// How long a file inventory is reused by later vacuum and verify runs
+    /// before the table is listed again, in seconds
+    pub inventory_ttl_secs: u64,
 }
 
 impl Default for VacuumConfig {
@@ -68,7 +1628,42 @@ impl Default for VacuumConfig {
         Self {
             retention_hours: 72, // 3 days
             vacuum_interval_secs: 3600, // 1 hour
+            vacuum_schedule: None,
             dry_run: false,
+            after_compaction: false,
+            remove_empty_partitions: true,
+            list_concurrency: 16,
+            inventory_ttl_secs: 600, // 10 minutes
+        }
+    }
+}
+
+/// Per-file checksums recorded at commit time, checked by `verify --checksums`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChecksumConfig {
+    /// Record a manifest of every file added by each commit
+    pub enabled: bool,
+    /// Also read back each file and store its SHA-256, instead of relying
+    /// on the entity tag the store assigned on upload
+    pub sha256: bool,
+}
+
+/// Configuration for the Checkpoint process
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CheckpointConfig {
+    /// Commits between checkpoints; 0 disables the process
+    pub checkpoint_interval: u64,
+    /// How often to check whether a checkpoint is due, in seconds
+    pub check_interval_secs: u64,
+}
+
+impl Default for CheckpointConfig {
+    fn default() -> Self {
+        Self {
+            checkpoint_interval: 100,
+            check_interval_secs: 60,
         }
     }
 }
@@ -85,16 +1680,58 @@ impl WriterConfig {
     pub fn retry_delay(&self) -> Duration {
         Duration::from_millis(self.retry_delay_ms)
     }
+    
+    pub fn partition_metrics_window(&self) -> Duration {
+        Duration::from_secs(self.partition_metrics_window_secs)
+    }
+
+    pub fn flush_alignment(&self) -> Option<Duration> {
+        self.flush_alignment_secs.filter(|secs| *secs > 0).map(Duration::from_secs)
+    }
+
+    pub fn commit_interval(&self) -> Option<Duration> {
+        self.commit_interval_ms.filter(|ms| *ms > 0).map(Duration::from_millis)
+    }
 }
 
 impl CompactionConfig {
     pub fn compaction_interval(&self) -> Duration {
         Duration::from_secs(self.compaction_interval_secs)
     }
+
+    /// Size below which a file counts as small for `small_file_ratio`
+    pub fn small_file_threshold(&self) -> u64 {
+        self.small_file_threshold_bytes.unwrap_or(self.target_file_size_bytes)
+    }
+
+    /// Whether a partition of `files` files, `small` of them small, is due
+    /// for compaction. Merging needs at least two small files either way.
+    pub fn should_compact(&self, small: usize, files: usize) -> bool {
+        match self.small_file_ratio {
+            Some(ratio) => small >= 2 && small as f64 >= ratio * files as f64,
+            None => small >= self.min_files_to_compact,
+        }
+    }
+
+    /// File groups rewritten concurrently per partition, `None` for
+    /// delta-rs's default. At least one group always runs.
+    pub fn concurrent_tasks(&self) -> Option<usize> {
+        self.max_concurrent_tasks.map(|tasks| tasks.max(1))
+    }
 }
 
 impl VacuumConfig {
     pub fn vacuum_interval(&self) -> Duration {
         Duration::from_secs(self.vacuum_interval_secs)
     }
+
+    pub fn inventory_ttl(&self) -> Duration {
+        Duration::from_secs(self.inventory_ttl_secs)
+    }
+}
+
+impl CheckpointConfig {
+    pub fn check_interval(&self) -> Duration {
+        Duration::from_secs(self.check_interval_secs)
+    }
 } 
\ No newline at end of file
@@ -0,0 +1,187 @@
+use anyhow::{bail, Context, Result};
+use chrono::NaiveDate;
+use deltalake::kernel::{Action, DataType, PrimitiveType, StructField, StructType};
+use deltalake::operations::transaction::CommitProperties;
+use deltalake::parquet::arrow::parquet_to_arrow_schema;
+use deltalake::{DeltaOps, DeltaTable, ObjectMeta, StorageOptions};
+use futures::TryStreamExt;
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+use crate::query;
+use crate::register::{add_actions, partition_values_from_path, read_footer};
+use crate::storage;
+
+/// Outcome of converting a Parquet directory tree into a Delta table
+#[derive(Debug, Clone, Serialize)]
+pub struct ConversionReport {
+    pub table_uri: String,
+    pub version: i64,
+    pub partition_columns: Vec<String>,
+    pub files: usize,
+    pub rows: i64,
+    pub bytes: u64,
+}
+
+impl fmt::Display for ConversionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Converted {} at version {}", self.table_uri, self.version)?;
+        if self.partition_columns.is_empty() {
+            writeln!(f, "  Partition columns: (none)")?;
+        } else {
+            writeln!(f, "  Partition columns: {}", self.partition_columns.join(", "))?;
+        }
+        writeln!(f, "  {} files, {} rows, {} bytes", self.files, self.rows, self.bytes)
+    }
+}
+
+/// Turn the Parquet files below `table_uri` into a Delta table in place,
+/// like Spark's `CONVERT TO DELTA`: partition columns and their values come
+/// from Hive-style `column=value` directories, the schema from the first
+/// file's footer, and the statistics of every file from its own footer. No
+/// data is copied or rewritten. Afterwards the table is read back and its
+/// row count compared with the footers.
+pub async fn convert_to_delta(table_uri: &str, storage_options: &StorageOptions) -> Result<ConversionReport> {
+    storage::register_handlers();
+    let ops = DeltaOps::try_from_uri_with_storage_options(table_uri, storage_options.0.clone())
+        .await
+        .with_context(|| format!("Failed to open {}", table_uri))?;
+    if ops.0.log_store().is_delta_table_location().await? {
+        bail!("{} is already a Delta table", table_uri);
+    }
+    let store = ops.0.object_store();
+
+    let mut files: Vec<ObjectMeta> = store
+        .list(None)
+        .try_filter(|meta| futures::future::ready(is_data_file(meta.location.as_ref())))
+        .try_collect()
+        .await
+        .with_context(|| format!("Failed to list {}", table_uri))?;
+    if files.is_empty() {
+        bail!("No Parquet files below {}", table_uri);
+    }
+    files.sort_by(|a, b| a.location.cmp(&b.location));
+
+    let partition_columns = partition_layout(&files)?;
+    let footer = read_footer(&store, &files[0]).await?;
+    let file_schema = parquet_to_arrow_schema(
+        footer.file_metadata().schema_descr(),
+        footer.file_metadata().key_value_metadata(),
+    )
+    .with_context(|| format!("Failed to read the schema of {}", files[0].location))?;
+    let file_schema = StructType::try_from(&file_schema).context("Unsupported column types")?;
+
+    // Files written over time rarely agree on nullability, so nothing is NOT NULL
+    let mut columns: Vec<StructField> = file_schema
+        .fields()
+        .map(|f| StructField::new(f.name().clone(), f.data_type().clone(), true))
+        .collect();
+    for column in &partition_columns {
+        if file_schema.field(column).is_some() {
+            bail!("Partition column {} is also stored in the data files", column);
+        }
+        let values = files
+            .iter()
+            .map(|meta| partition_values_from_path(meta.location.as_ref(), std::slice::from_ref(column)))
+            .collect::<Result<Vec<_>>>()?;
+        let values: Vec<Option<String>> = values.into_iter().flatten().map(|(_, value)| value).collect();
+        columns.push(StructField::new(column.clone(), partition_type(&values), true));
+    }
+    let schema = StructType::new(columns.clone());
+
+    let file_count = files.len();
+    let adds = add_actions(store, files, &schema, &partition_columns).await?;
+    let rows: i64 = adds.iter().map(|add| add.get_stats().ok().flatten().map_or(0, |s| s.num_records)).sum();
+    let bytes: u64 = adds.iter().map(|add| add.size as u64).sum();
+
+    let table = ops
+        .create()
+        .with_columns(columns)
+        .with_partition_columns(partition_columns.clone())
+        .with_actions(adds.into_iter().map(Action::Add))
+        .with_commit_properties(
+            CommitProperties::default().with_metadata(vec![("convertedFrom".to_string(), Value::from("parquet"))]),
+        )
+        .await
+        .with_context(|| format!("Failed to create the Delta log of {}", table_uri))?;
+
+    validate(&table, file_count, rows).await?;
+    log::info!("Converted {} with {} files and {} rows", table_uri, file_count, rows);
+
+    Ok(ConversionReport {
+        table_uri: table.table_uri(),
+        version: table.version(),
+        partition_columns,
+        files: file_count,
+        rows,
+        bytes,
+    })
+}
+
+/// Parquet files that hold data: skips `_`- and `.`-prefixed entries such
+/// as `_SUCCESS`, `_delta_log` and checksum files
+fn is_data_file(path: &str) -> bool {
+    path.ends_with(".parquet") && !path.split('/').any(|segment| segment.starts_with(['_', '.']))
+}
+
+/// Partition columns from the `column=value` directories of the files,
+/// which must all follow the same layout
+pub fn partition_layout(files: &[ObjectMeta]) -> Result<Vec<String>> {
+    let layout = |meta: &ObjectMeta| -> Vec<String> {
+        let path = meta.location.as_ref();
+        let directories = path.rsplit_once('/').map_or("", |(directories, _)| directories);
+        directories
+            .split('/')
+            .filter_map(|segment| segment.split_once('=').map(|(column, _)| column.to_string()))
+            .collect()
+    };
+    let expected = files.first().map(layout).unwrap_or_default();
+    for meta in files {
+        let found = layout(meta);
+        if found != expected {
+            bail!(
+                "{} is partitioned by {:?}, other files by {:?}; mixed layouts cannot be converted",
+                meta.location,
+                found,
+                expected
+            );
+        }
+    }
+    Ok(expected)
+}
+
+/// The narrowest type every non-null value of a partition column parses as
+pub fn partition_type(values: &[Option<String>]) -> DataType {
+    let values: Vec<&str> = values.iter().flatten().map(String::as_str).collect();
+    if values.is_empty() {
+        return DataType::Primitive(PrimitiveType::String);
+    }
+    if values.iter().all(|v| v.parse::<i64>().is_ok()) {
+        DataType::Primitive(PrimitiveType::Long)
+    } else if values.iter().all(|v| NaiveDate::parse_from_str(v, "%Y-%m-%d").is_ok()) {
+        DataType::Primitive(PrimitiveType::Date)
+    } else {
+        DataType::Primitive(PrimitiveType::String)
+    }
+}
+
+/// Read the new table back: every file must be active and a full count
+/// must match the row counts of the footers
+async fn validate(table: &DeltaTable, files: usize, rows: i64) -> Result<()> {
+    let active = table.get_files_count();
+    if active != files {
+        bail!("Converted table has {} active files, expected {}", active, files);
+    }
+    let result = query::run_query(table.clone(), "t", "SELECT count(*) AS n FROM t").await?;
+    let counted = result
+        .batches
+        .first()
+        .and_then(|batch| batch.column(0).as_any().downcast_ref::<deltalake::arrow::array::Int64Array>())
+        .filter(|counts| !counts.is_empty())
+        .map(|counts| counts.value(0))
+        .context("Row count query returned nothing")?;
+    if counted != rows {
+        bail!("Converted table reads back {} rows, the file footers hold {}", counted, rows);
+    }
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use anyhow::{anyhow, bail, Result};
+use rand::Rng;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Longest caller-supplied batch id accepted on the ingestion APIs
+pub const MAX_BATCH_ID_LEN: usize = 128;
+
+/// Crockford base32, as used by ULIDs
+const CROCKFORD: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Source of the ids that follow a batch from ingestion through logs,
+/// commit metadata, sink dead letters and receipts
+pub trait IdGenerator: Send + Sync {
+    fn generate(&self) -> String;
+}
+
+/// Lexicographically time-ordered 26-character ULIDs
+#[derive(Debug, Default)]
+pub struct UlidGenerator;
+
+impl IdGenerator for UlidGenerator {
+    fn generate(&self) -> String {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64);
+        encode_ulid(timestamp_ms, rand::thread_rng().gen())
+    }
+}
+
+static GENERATOR: OnceLock<Box<dyn IdGenerator>> = OnceLock::new();
+
+/// Replace the default ULID generator. Must be called before the first id
+/// is generated, like installing a logger.
+pub fn set_id_generator(generator: Box<dyn IdGenerator>) -> Result<()> {
+    GENERATOR
+        .set(generator)
+        .map_err(|_| anyhow!("A batch id generator is already installed"))
+}
+
+/// A fresh id from the installed generator
+pub fn new_batch_id() -> String {
+    GENERATOR.get_or_init(|| Box::new(UlidGenerator)).generate()
+}
+
+/// Check a caller-supplied id before it ends up in logs and commit metadata
+pub fn validate_batch_id(id: &str) -> Result<()> {
+    if id.is_empty() || id.len() > MAX_BATCH_ID_LEN {
+        bail!("Batch id must be 1 to {} characters", MAX_BATCH_ID_LEN);
+    }
+    if !id.bytes().all(|b| b.is_ascii_graphic()) {
+        bail!("Batch id may only contain printable ASCII without spaces");
+    }
+    Ok(())
+}
+
+/// Encode a 48-bit millisecond timestamp and 80 bits of randomness as a ULID
+pub fn encode_ulid(timestamp_ms: u64, random: u128) -> String {
+    let value = ((timestamp_ms as u128 & 0xFFFF_FFFF_FFFF) << 80) | (random & ((1 << 80) - 1));
+    (0..26)
+        .rev()
+        .map(|i| CROCKFORD[((value >> (i * 5)) & 0x1F) as usize] as char)
+        .collect()
+}
@@ -0,0 +1,134 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use deltalake::kernel::Transaction;
+use deltalake::{DeltaTableBuilder, ObjectStore, ObjectStoreError, Path, StorageOptions};
+use futures::TryStreamExt;
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use std::sync::Arc;
+use crate::correlation::new_batch_id;
+
+/// Description of a dead-lettered batch, stored as `{id}.json` next to its
+/// rows in `{id}.parquet`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeadLetterEntry {
+    pub id: String,
+    pub table_uri: String,
+    /// Ids of the ingested batches whose rows the entry holds
+    pub batch_ids: Vec<String>,
+    pub rows: usize,
+    pub error: String,
+    pub failed_at: DateTime<Utc>,
+}
+
+impl DeadLetterEntry {
+    /// Application transaction recorded when the entry is replayed, so a
+    /// replay interrupted before the entry is removed is not applied twice
+    pub fn replay_txn(&self) -> Transaction {
+        Transaction::new(format!("dead-letter:{}", self.id), 0)
+    }
+}
+
+/// Batches the writer gave up on, kept in a local directory or under an
+/// object store prefix until they are replayed
+#[derive(Debug, Clone)]
+pub struct DeadLetterQueue {
+    uri: String,
+    store: Arc<dyn ObjectStore>,
+}
+
+impl DeadLetterQueue {
+    pub fn open(uri: &str, storage_options: &StorageOptions) -> Result<Self> {
+        let store = DeltaTableBuilder::from_uri(uri)
+            .with_storage_options(storage_options.0.clone())
+            .build_storage()
+            .with_context(|| format!("Failed to open dead letter queue {}", uri))?
+            .object_store(None);
+        Ok(Self { uri: uri.to_string(), store })
+    }
+
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    /// Store a batch that could not be written, returning the entry id
+    pub async fn put(&self, table_uri: &str, df: &DataFrame, batch_ids: &[String], error: &anyhow::Error) -> Result<String> {
+        let entry = DeadLetterEntry {
+            id: new_batch_id(),
+            table_uri: table_uri.to_string(),
+            batch_ids: batch_ids.to_vec(),
+            rows: df.height(),
+            error: format!("{:#}", error),
+            failed_at: Utc::now(),
+        };
+
+        let mut rows = Vec::new();
+        ParquetWriter::new(&mut rows)
+            .finish(&mut df.clone())
+            .context("Failed to encode dead-lettered batch")?;
+        // Rows first, so a listed entry always has its data
+        self.store
+            .put(&data_path(&entry.id), rows.into())
+            .await
+            .context("Failed to write dead-lettered batch")?;
+        self.store
+            .put(&entry_path(&entry.id), serde_json::to_vec(&entry)?.into())
+            .await
+            .context("Failed to write dead letter entry")?;
+        Ok(entry.id)
+    }
+
+    /// Every entry, oldest first
+    pub async fn list(&self) -> Result<Vec<DeadLetterEntry>> {
+        let metas: Vec<_> = self
+            .store
+            .list(None)
+            .try_filter(|meta| futures::future::ready(meta.location.as_ref().ends_with(".json")))
+            .try_collect()
+            .await
+            .with_context(|| format!("Failed to list dead letter queue {}", self.uri))?;
+
+        let mut entries = Vec::with_capacity(metas.len());
+        for meta in metas {
+            let bytes = self.store.get(&meta.location).await?.bytes().await?;
+            let entry: DeadLetterEntry = serde_json::from_slice(&bytes)
+                .with_context(|| format!("Invalid dead letter entry {}", meta.location))?;
+            entries.push(entry);
+        }
+        // Ids are ULIDs, so they sort by failure time
+        entries.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(entries)
+    }
+
+    /// Rows of the entry with the given id
+    pub async fn read(&self, id: &str) -> Result<DataFrame> {
+        let bytes = match self.store.get(&data_path(id)).await {
+            Ok(result) => result.bytes().await?,
+            Err(ObjectStoreError::NotFound { .. }) => return Err(anyhow!("No dead letter entry {}", id)),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read dead letter entry {}", id)),
+        };
+        ParquetReader::new(Cursor::new(bytes))
+            .finish()
+            .with_context(|| format!("Invalid dead letter entry {}", id))
+    }
+
+    /// Delete an entry once it has been replayed
+    pub async fn remove(&self, id: &str) -> Result<()> {
+        for path in [entry_path(id), data_path(id)] {
+            match self.store.delete(&path).await {
+                Ok(()) | Err(ObjectStoreError::NotFound { .. }) => {}
+                Err(e) => return Err(e).with_context(|| format!("Failed to delete {}", path)),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn entry_path(id: &str) -> Path {
+    Path::from(format!("{}.json", id))
+}
+
+fn data_path(id: &str) -> Path {
+    Path::from(format!("{}.parquet", id))
+}
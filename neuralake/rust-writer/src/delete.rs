@@ -0,0 +1,87 @@
+use anyhow::{bail, Context, Result};
+use deltalake::datafusion::prelude::SessionContext;
+use deltalake::{DeltaOps, DeltaTable};
+use polars::prelude::*;
+use std::fs::File;
+use std::path::Path;
+
+/// Outcome of a keyed delete
+#[derive(Debug, Clone)]
+pub struct DeleteReport {
+    /// Distinct keys read from the keys file
+    pub keys_requested: usize,
+    /// Rows removed from the table
+    pub rows_deleted: usize,
+    /// Table version produced by the delete commit
+    pub version: i64,
+}
+
+/// Load a Parquet file of keys, keeping only the distinct key columns
+pub fn read_keys_file(path: &Path, key_columns: &[String]) -> Result<DataFrame> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open keys file {}", path.display()))?;
+
+    let keys = ParquetReader::new(file)
+        .finish()
+        .context("Failed to read keys file as Parquet")?;
+
+    let keys = keys
+        .select(key_columns.iter().map(|c| c.as_str()))
+        .context("Keys file is missing one of the key columns")?
+        .unique_stable(None, UniqueKeepStrategy::First, None)?;
+
+    Ok(keys)
+}
+
+/// Delete every row whose key columns match a row in `keys`, using a MERGE
+/// so the whole key list is removed in a single commit
+pub async fn delete_by_keys(
+    table: DeltaTable,
+    keys: &DataFrame,
+    key_columns: &[String],
+) -> Result<(DeltaTable, DeleteReport)> {
+    if key_columns.is_empty() {
+        bail!("At least one key column is required");
+    }
+
+    let keys_requested = keys.height();
+    if keys_requested == 0 {
+        log::info!("Keys file is empty, nothing to delete");
+        let version = table.version();
+        return Ok((table, DeleteReport { keys_requested, rows_deleted: 0, version }));
+    }
+
+    let ctx = SessionContext::new();
+    let source = ctx
+        .read_batch(keys.to_arrow(None).context("Failed to convert keys to Arrow")?)
+        .context("Failed to register keys as a DataFusion source")?;
+
+    let predicate = key_columns
+        .iter()
+        .map(|c| format!("target.\"{c}\" = source.\"{c}\""))
+        .collect::<Vec<_>>()
+        .join(" AND ");
+
+    let (table, metrics) = DeltaOps(table)
+        .merge(source, predicate)
+        .with_source_alias("source")
+        .with_target_alias("target")
+        .when_matched_delete(|delete| delete)?
+        .await
+        .context("Failed to delete rows by key")?;
+
+    let report = DeleteReport {
+        keys_requested,
+        rows_deleted: metrics.num_target_rows_deleted,
+        version: table.version(),
+    };
+
+    log::info!(
+        "Deleted {} rows for {} keys at version {}",
+        report.rows_deleted,
+        report.keys_requested,
+        report.version
+    );
+
+    Ok((table, report))
+}
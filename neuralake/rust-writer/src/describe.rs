@@ -0,0 +1,140 @@
+use anyhow::Result;
+use deltalake::DeltaTable;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use crate::retention;
+
+/// A column of the table schema
+#[derive(Debug, Clone, Serialize)]
+pub struct ColumnDescription {
+    pub name: String,
+    pub data_type: String,
+    pub nullable: bool,
+}
+
+/// Schema, properties, protocol and size of a table at its current version
+#[derive(Debug, Clone, Serialize)]
+pub struct TableDescription {
+    pub table_uri: String,
+    pub version: i64,
+    pub columns: Vec<ColumnDescription>,
+    pub partition_columns: Vec<String>,
+    pub properties: BTreeMap<String, String>,
+    pub min_reader_version: i32,
+    pub min_writer_version: i32,
+    pub reader_features: Vec<String>,
+    pub writer_features: Vec<String>,
+    pub num_files: usize,
+    /// Sum of per-file row counts; `None` if any file lacks statistics
+    pub num_rows: Option<i64>,
+    pub size_bytes: i64,
+    /// How far back every version stays readable, from the retention properties
+    pub time_travel_hours: i64,
+}
+
+/// Describe the loaded version of `table`
+pub fn describe_table(table: &DeltaTable) -> Result<TableDescription> {
+    let metadata = table.metadata()?;
+    let protocol = table.protocol()?;
+    let schema = table.get_schema()?;
+
+    let columns = schema
+        .fields()
+        .map(|f| ColumnDescription {
+            name: f.name().clone(),
+            data_type: f.data_type().to_string(),
+            nullable: f.is_nullable(),
+        })
+        .collect();
+
+    let properties = metadata
+        .configuration
+        .iter()
+        .filter_map(|(k, v)| v.as_ref().map(|v| (k.clone(), v.clone())))
+        .collect();
+
+    let mut num_files = 0;
+    let mut size_bytes = 0;
+    let mut num_rows = Some(0i64);
+    for add in table.snapshot()?.file_actions()? {
+        num_files += 1;
+        size_bytes += add.size;
+        let records = add.get_stats().ok().flatten().map(|s| s.num_records);
+        num_rows = num_rows.zip(records).map(|(total, n)| total + n);
+    }
+
+    Ok(TableDescription {
+        table_uri: table.table_uri(),
+        version: table.version(),
+        columns,
+        partition_columns: metadata.partition_columns.clone(),
+        properties,
+        min_reader_version: protocol.min_reader_version,
+        min_writer_version: protocol.min_writer_version,
+        reader_features: protocol
+            .reader_features
+            .iter()
+            .flatten()
+            .map(|f| f.to_string())
+            .collect(),
+        writer_features: protocol
+            .writer_features
+            .iter()
+            .flatten()
+            .map(|f| f.to_string())
+            .collect(),
+        num_files,
+        num_rows,
+        size_bytes,
+        time_travel_hours: retention::time_travel_window(table)?.num_hours(),
+    })
+}
+
+impl fmt::Display for TableDescription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Table: {}", self.table_uri)?;
+        writeln!(f, "Version: {}", self.version)?;
+        writeln!(f, "Schema:")?;
+        for column in &self.columns {
+            writeln!(
+                f,
+                "  {:<30} {:<20} {}",
+                column.name,
+                column.data_type,
+                if column.nullable { "NULL" } else { "NOT NULL" }
+            )?;
+        }
+        if self.partition_columns.is_empty() {
+            writeln!(f, "Partition columns: (none)")?;
+        } else {
+            writeln!(f, "Partition columns: {}", self.partition_columns.join(", "))?;
+        }
+        writeln!(f, "Properties:")?;
+        for (key, value) in &self.properties {
+            writeln!(f, "  {} = {}", key, value)?;
+        }
+        writeln!(
+            f,
+            "Protocol: reader v{}, writer v{}",
+            self.min_reader_version, self.min_writer_version
+        )?;
+        if !self.reader_features.is_empty() {
+            writeln!(f, "Reader features: {}", self.reader_features.join(", "))?;
+        }
+        if !self.writer_features.is_empty() {
+            writeln!(f, "Writer features: {}", self.writer_features.join(", "))?;
+        }
+        writeln!(f, "Files: {}", self.num_files)?;
+        match self.num_rows {
+            Some(rows) => writeln!(f, "Rows: {}", rows)?,
+            None => writeln!(f, "Rows: unknown (files without statistics)")?,
+        }
+        writeln!(f, "Size: {} bytes", self.size_bytes)?;
+        if self.time_travel_hours % 24 == 0 {
+            write!(f, "Time travel: last {} days", self.time_travel_hours / 24)
+        } else {
+            write!(f, "Time travel: last {} hours", self.time_travel_hours)
+        }
+    }
+}
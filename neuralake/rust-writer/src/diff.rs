@@ -0,0 +1,215 @@
+use anyhow::{bail, Context, Result};
+use deltalake::arrow::array::RecordBatch;
+use deltalake::arrow::util::pretty::pretty_format_batches;
+use deltalake::datafusion::prelude::SessionContext;
+use deltalake::delta_datafusion::DeltaCdfTableProvider;
+use deltalake::{DeltaOps, DeltaTable};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::Arc;
+use crate::describe::{describe_table, ColumnDescription};
+use crate::history::table_history;
+
+/// A column whose type or nullability differs between the two versions
+#[derive(Debug, Clone, Serialize)]
+pub struct ColumnChange {
+    pub name: String,
+    pub from: String,
+    pub to: String,
+}
+
+/// What changed in a table between two versions
+#[derive(Debug, Clone, Serialize)]
+pub struct TableDiff {
+    pub from_version: i64,
+    pub to_version: i64,
+    pub columns_added: Vec<ColumnDescription>,
+    pub columns_removed: Vec<ColumnDescription>,
+    pub columns_changed: Vec<ColumnChange>,
+    pub files_added: usize,
+    pub files_removed: usize,
+    pub bytes_added: i64,
+    pub bytes_removed: i64,
+    /// Rows at `to_version` minus rows at `from_version`; `None` if any file lacks statistics
+    pub row_delta: Option<i64>,
+    /// Commit count per operation within the range
+    pub operations: BTreeMap<String, usize>,
+    /// A sample of changed rows from the change data feed, when requested
+    #[serde(skip)]
+    pub sample_changes: Option<Vec<RecordBatch>>,
+}
+
+impl fmt::Display for TableDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Diff of versions {} -> {}", self.from_version, self.to_version)?;
+        for (operation, commits) in &self.operations {
+            writeln!(f, "  {} x{}", operation, commits)?;
+        }
+
+        if self.columns_added.is_empty() && self.columns_removed.is_empty() && self.columns_changed.is_empty() {
+            writeln!(f, "Schema: unchanged")?;
+        } else {
+            writeln!(f, "Schema:")?;
+            for column in &self.columns_added {
+                writeln!(f, "  + {} {}", column.name, column.data_type)?;
+            }
+            for column in &self.columns_removed {
+                writeln!(f, "  - {} {}", column.name, column.data_type)?;
+            }
+            for change in &self.columns_changed {
+                writeln!(f, "  ~ {} {} -> {}", change.name, change.from, change.to)?;
+            }
+        }
+
+        writeln!(f, "Files added: {} ({} bytes)", self.files_added, self.bytes_added)?;
+        writeln!(f, "Files removed: {} ({} bytes)", self.files_removed, self.bytes_removed)?;
+        match self.row_delta {
+            Some(delta) => writeln!(f, "Row delta: {:+}", delta)?,
+            None => writeln!(f, "Row delta: unknown (missing file statistics)")?,
+        }
+
+        if let Some(batches) = &self.sample_changes {
+            writeln!(f, "Sample changes:")?;
+            match pretty_format_batches(batches) {
+                Ok(table) => writeln!(f, "{}", table)?,
+                Err(e) => writeln!(f, "  (could not format rows: {})", e)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Compare versions `from_version` and `to_version` of `table`. With
+/// `sample_rows`, up to that many changed rows are read from the change data
+/// feed, which requires `delta.enableChangeDataFeed` over the whole range.
+pub async fn diff_versions(
+    table: &DeltaTable,
+    from_version: i64,
+    to_version: i64,
+    sample_rows: Option<usize>,
+) -> Result<TableDiff> {
+    if from_version < 0 || from_version >= to_version || to_version > table.version() {
+        bail!(
+            "Invalid diff range {}..{} for table at version {}",
+            from_version,
+            to_version,
+            table.version()
+        );
+    }
+
+    let before = snapshot_at(table, from_version).await?;
+    let after = snapshot_at(table, to_version).await?;
+    let before_description = describe_table(&before)?;
+    let after_description = describe_table(&after)?;
+
+    let before_columns: HashMap<&str, &ColumnDescription> =
+        before_description.columns.iter().map(|c| (c.name.as_str(), c)).collect();
+    let after_columns: HashMap<&str, &ColumnDescription> =
+        after_description.columns.iter().map(|c| (c.name.as_str(), c)).collect();
+    let signature = |c: &ColumnDescription| {
+        format!("{}{}", c.data_type, if c.nullable { "" } else { " NOT NULL" })
+    };
+
+    let columns_changed = after_description
+        .columns
+        .iter()
+        .filter_map(|column| {
+            let previous = before_columns.get(column.name.as_str())?;
+            (signature(previous) != signature(column)).then(|| ColumnChange {
+                name: column.name.clone(),
+                from: signature(previous),
+                to: signature(column),
+            })
+        })
+        .collect();
+
+    let before_files: HashMap<String, i64> = file_sizes(&before)?;
+    let after_files: HashMap<String, i64> = file_sizes(&after)?;
+    let added: Vec<i64> = after_files
+        .iter()
+        .filter(|(path, _)| !before_files.contains_key(*path))
+        .map(|(_, size)| *size)
+        .collect();
+    let removed: Vec<i64> = before_files
+        .iter()
+        .filter(|(path, _)| !after_files.contains_key(*path))
+        .map(|(_, size)| *size)
+        .collect();
+
+    // History is newest first; the oldest commit in range is `latest - from_version` back
+    let history_depth = (table.version() - from_version) as usize;
+    let mut operations = BTreeMap::new();
+    for entry in table_history(table, Some(history_depth)).await? {
+        if entry.version > from_version && entry.version <= to_version {
+            let operation = entry.operation.unwrap_or_else(|| "UNKNOWN".to_string());
+            *operations.entry(operation).or_insert(0) += 1;
+        }
+    }
+
+    let sample_changes = match sample_rows {
+        Some(limit) => Some(sample_changes(table, from_version + 1, to_version, limit).await?),
+        None => None,
+    };
+
+    Ok(TableDiff {
+        from_version,
+        to_version,
+        columns_added: after_description
+            .columns
+            .iter()
+            .filter(|c| !before_columns.contains_key(c.name.as_str()))
+            .cloned()
+            .collect(),
+        columns_removed: before_description
+            .columns
+            .iter()
+            .filter(|c| !after_columns.contains_key(c.name.as_str()))
+            .cloned()
+            .collect(),
+        columns_changed,
+        files_added: added.len(),
+        files_removed: removed.len(),
+        bytes_added: added.iter().sum(),
+        bytes_removed: removed.iter().sum(),
+        row_delta: after_description
+            .num_rows
+            .zip(before_description.num_rows)
+            .map(|(after, before)| after - before),
+        operations,
+        sample_changes,
+    })
+}
+
+/// Up to `limit` rows of the change data feed for versions `start..=end`
+async fn sample_changes(table: &DeltaTable, start: i64, end: i64, limit: usize) -> Result<Vec<RecordBatch>> {
+    let cdf = DeltaOps(table.clone())
+        .load_cdf()
+        .with_starting_version(start)
+        .with_ending_version(end);
+    let provider = DeltaCdfTableProvider::try_new(cdf)
+        .context("Change data feed is not available for this range")?;
+
+    SessionContext::new()
+        .read_table(Arc::new(provider))?
+        .limit(0, Some(limit))?
+        .collect()
+        .await
+        .context("Failed to read change data feed (is delta.enableChangeDataFeed set?)")
+}
+
+async fn snapshot_at(table: &DeltaTable, version: i64) -> Result<DeltaTable> {
+    let mut snapshot = table.clone();
+    snapshot.load_version(version).await
+        .with_context(|| format!("Failed to load version {}", version))?;
+    Ok(snapshot)
+}
+
+fn file_sizes(table: &DeltaTable) -> Result<HashMap<String, i64>> {
+    Ok(table
+        .snapshot()?
+        .file_actions()?
+        .into_iter()
+        .map(|add| (add.path, add.size))
+        .collect())
+}
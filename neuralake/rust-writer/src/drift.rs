@@ -0,0 +1,182 @@
+use anyhow::{bail, Context, Result};
+use deltalake::{DeltaOps, DeltaTable};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use crate::config::{DriftPolicy, ExpectationsConfig};
+
+/// Prefix of the table properties holding CHECK constraints
+const CONSTRAINT_PREFIX: &str = "delta.constraints.";
+
+/// One way the table differs from the declared expectations
+#[derive(Debug, Clone, Serialize)]
+pub struct Drift {
+    /// What differs, e.g. `column amount` or `constraint positive_amount`
+    pub subject: String,
+    pub expected: String,
+    pub actual: String,
+    /// Whether `reconcile` policy can fix it by changing table metadata
+    pub reconcilable: bool,
+}
+
+/// Differences between the declared expectations and the table
+#[derive(Debug, Clone, Serialize)]
+pub struct DriftReport {
+    pub table_uri: String,
+    pub version: i64,
+    pub drifts: Vec<Drift>,
+}
+
+impl fmt::Display for DriftReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Drift of {} at version {}", self.table_uri, self.version)?;
+        if self.drifts.is_empty() {
+            return writeln!(f, "  none");
+        }
+        for drift in &self.drifts {
+            writeln!(f, "  {}: expected {}, found {}", drift.subject, drift.expected, drift.actual)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compare the loaded version of `table` with `expectations`
+pub fn check_drift(table: &DeltaTable, expectations: &ExpectationsConfig) -> Result<DriftReport> {
+    let metadata = table.metadata()?;
+    let schema = table.get_schema()?;
+    let property = |key: &str| metadata.configuration.get(key).cloned().flatten();
+    let mut drifts = Vec::new();
+
+    if let Some(expected) = &expectations.partition_columns {
+        if expected != &metadata.partition_columns {
+            drifts.push(Drift {
+                subject: "partition columns".to_string(),
+                expected: format!("{:?}", expected),
+                actual: format!("{:?}", metadata.partition_columns),
+                reconcilable: false,
+            });
+        }
+    }
+
+    for column in &expectations.columns {
+        let subject = format!("column {}", column.name);
+        let Some(field) = schema.field(&column.name) else {
+            drifts.push(Drift { subject, expected: "present".to_string(), actual: "missing".to_string(), reconcilable: false });
+            continue;
+        };
+        let actual_type = field.data_type().to_string();
+        if let Some(expected) = column.data_type.as_ref().filter(|t| !t.eq_ignore_ascii_case(&actual_type)) {
+            drifts.push(Drift { subject: subject.clone(), expected: expected.clone(), actual: actual_type, reconcilable: false });
+        }
+        if let Some(expected) = column.nullable.filter(|n| *n != field.is_nullable()) {
+            let describe = |nullable: bool| if nullable { "nullable" } else { "NOT NULL" }.to_string();
+            drifts.push(Drift { subject, expected: describe(expected), actual: describe(!expected), reconcilable: false });
+        }
+    }
+
+    if let Some(expected) = expectations.append_only {
+        let actual = property("delta.appendOnly").as_deref() == Some("true");
+        if expected != actual {
+            drifts.push(Drift {
+                subject: "delta.appendOnly".to_string(),
+                expected: expected.to_string(),
+                actual: actual.to_string(),
+                reconcilable: true,
+            });
+        }
+    }
+
+    for (name, expected) in &expectations.constraints {
+        let actual = property(&format!("{}{}", CONSTRAINT_PREFIX, name));
+        if actual.as_deref().map(normalize) != Some(normalize(expected)) {
+            drifts.push(Drift {
+                subject: format!("constraint {}", name),
+                expected: expected.clone(),
+                actual: actual.unwrap_or_else(|| "missing".to_string()),
+                reconcilable: true,
+            });
+        }
+    }
+
+    Ok(DriftReport { table_uri: table.table_uri(), version: table.version(), drifts })
+}
+
+/// Check `table` on startup and apply the configured policy, returning the
+/// table (at a new version if anything was reconciled)
+pub async fn enforce(table: DeltaTable, expectations: &ExpectationsConfig) -> Result<DeltaTable> {
+    let report = check_drift(&table, expectations)?;
+    if report.drifts.is_empty() {
+        return Ok(table);
+    }
+
+    match expectations.policy {
+        DriftPolicy::Warn => {
+            for drift in &report.drifts {
+                log::warn!(
+                    "{} drifted from config: {} expected {}, found {}",
+                    report.table_uri, drift.subject, drift.expected, drift.actual
+                );
+            }
+            Ok(table)
+        }
+        DriftPolicy::Fail => bail!("Table does not match the configured expectations\n{}", report),
+        DriftPolicy::Reconcile => {
+            if report.drifts.iter().any(|d| !d.reconcilable) {
+                bail!("Table drift cannot be reconciled automatically\n{}", report);
+            }
+            reconcile(table, expectations).await
+        }
+    }
+}
+
+/// Set `delta.appendOnly` and the CHECK constraints to the declared values
+async fn reconcile(mut table: DeltaTable, expectations: &ExpectationsConfig) -> Result<DeltaTable> {
+    let property = |table: &DeltaTable, key: &str| -> Result<Option<String>> {
+        Ok(table.metadata()?.configuration.get(key).cloned().flatten())
+    };
+
+    if let Some(append_only) = expectations.append_only {
+        if property(&table, "delta.appendOnly")?.as_deref() != Some(&append_only.to_string()) {
+            log::warn!("Setting delta.appendOnly={} on {}", append_only, table.table_uri());
+            table = DeltaOps(table)
+                .set_tbl_properties()
+                .with_properties(HashMap::from([("delta.appendOnly".to_string(), append_only.to_string())]))
+                .await
+                .context("Failed to set delta.appendOnly")?;
+        }
+    }
+
+    for (name, expression) in &expectations.constraints {
+        let actual = property(&table, &format!("{}{}", CONSTRAINT_PREFIX, name))?;
+        if actual.as_deref().map(normalize) == Some(normalize(expression)) {
+            continue;
+        }
+        if actual.is_some() {
+            log::warn!("Replacing constraint {} on {}", name, table.table_uri());
+            table = DeltaOps(table)
+                .drop_constraints()
+                .with_constraint(name)
+                .await
+                .with_context(|| format!("Failed to drop constraint {}", name))?;
+        } else {
+            log::warn!("Adding constraint {} on {}", name, table.table_uri());
+        }
+        // Existing rows are validated against the new expression
+        table = DeltaOps(table)
+            .add_constraint()
+            .with_constraint(name, expression)
+            .await
+            .with_context(|| format!("Failed to add constraint {}", name))?;
+    }
+
+    Ok(table)
+}
+
+/// Constraint expressions compare without regard to whitespace or case
+fn normalize(expression: &str) -> String {
+    expression
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
@@ -0,0 +1,134 @@
+use anyhow::{Context, Result};
+use deltalake::arrow::ipc::writer::StreamWriter;
+use deltalake::datafusion::prelude::SessionContext;
+use deltalake::{DeltaTable, StorageOptions};
+use polars::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Cursor;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use crate::config::{LookupConfig, Transform};
+
+/// Dimension rows of a lookup, one per key
+#[derive(Debug, Clone)]
+pub struct LookupTable {
+    pub key_column: String,
+    /// The key column followed by the configured columns
+    pub rows: Arc<DataFrame>,
+}
+
+#[derive(Debug)]
+struct CachedLookup {
+    table: LookupTable,
+    /// Version of the Delta table the rows were read at
+    version: i64,
+    checked_at: Instant,
+}
+
+/// Lookup tables kept in memory between flushes, so enriching a batch does
+/// not scan the dimension table. Once its TTL passes a lookup is checked on
+/// the next flush and read again only if the table has a new version.
+#[derive(Debug, Default)]
+pub struct EnrichmentCache {
+    lookups: Mutex<HashMap<String, CachedLookup>>,
+}
+
+impl EnrichmentCache {
+    /// The lookups `transforms` enrich from, loading missing and expired ones.
+    /// A lookup that fails to refresh keeps serving its previous rows.
+    pub async fn resolve(
+        &self,
+        transforms: &[Transform],
+        configs: &BTreeMap<String, LookupConfig>,
+        storage_options: &StorageOptions,
+    ) -> Result<HashMap<String, LookupTable>> {
+        let mut cached = self.lookups.lock().await;
+        let mut resolved = HashMap::new();
+        for transform in transforms {
+            let Transform::Enrich { lookup: name, .. } = transform else {
+                continue;
+            };
+            if resolved.contains_key(name) {
+                continue;
+            }
+            let config = configs
+                .get(name)
+                .with_context(|| format!("Transform enriches from lookup {}, which is not configured", name))?;
+
+            let expired = cached.get(name).is_none_or(|lookup| lookup.checked_at.elapsed() >= config.ttl());
+            if expired {
+                match refresh(cached.get(name), config, storage_options).await {
+                    Ok(lookup) => {
+                        cached.insert(name.clone(), lookup);
+                    }
+                    Err(e) => match cached.get_mut(name) {
+                        Some(stale) => {
+                            log::warn!("Using stale rows of lookup {}: {:#}", name, e);
+                            stale.checked_at = Instant::now();
+                        }
+                        None => return Err(e),
+                    },
+                }
+            }
+            resolved.insert(name.clone(), cached[name].table.clone());
+        }
+        Ok(resolved)
+    }
+}
+
+/// Check the lookup table for a new version, reading its rows only if there is one
+async fn refresh(
+    previous: Option<&CachedLookup>,
+    config: &LookupConfig,
+    storage_options: &StorageOptions,
+) -> Result<CachedLookup> {
+    let table = deltalake::open_table_with_storage_options(&config.table_uri, storage_options.0.clone())
+        .await
+        .with_context(|| format!("Failed to open lookup table {}", config.table_uri))?;
+    let version = table.version();
+    if let Some(previous) = previous.filter(|previous| previous.version == version) {
+        return Ok(CachedLookup { table: previous.table.clone(), version, checked_at: Instant::now() });
+    }
+
+    let rows = read_rows(table, config).await?;
+    log::info!("Loaded {} rows of lookup table {} at version {}", rows.height(), config.table_uri, version);
+    Ok(CachedLookup {
+        table: LookupTable { key_column: config.key_column.clone(), rows: Arc::new(rows) },
+        version,
+        checked_at: Instant::now(),
+    })
+}
+
+async fn read_rows(table: DeltaTable, config: &LookupConfig) -> Result<DataFrame> {
+    let mut columns = vec![config.key_column.as_str()];
+    columns.extend(config.columns.iter().map(String::as_str));
+    let frame = SessionContext::new()
+        .read_table(Arc::new(table))
+        .context("Failed to register lookup table")?
+        .select_columns(&columns)
+        .with_context(|| format!("Lookup table {} lacks a configured column", config.table_uri))?;
+    let schema = Arc::new(frame.schema().as_arrow().clone());
+    let batches = frame
+        .collect()
+        .await
+        .with_context(|| format!("Failed to read lookup table {}", config.table_uri))?;
+
+    // Handed to Polars as an Arrow IPC stream, as the HTTP ingest path does
+    let mut payload = Vec::new();
+    let mut writer = StreamWriter::try_new(&mut payload, &schema)?;
+    for batch in &batches {
+        writer.write(batch)?;
+    }
+    writer.finish()?;
+    drop(writer);
+    let rows = IpcStreamReader::new(Cursor::new(payload)).finish()?;
+
+    // One row per key, so that enriching never multiplies rows
+    let last: Vec<Expr> = config.columns.iter().map(|column| col(column.as_str()).last()).collect();
+    rows.lazy()
+        .group_by_stable([col(config.key_column.as_str())])
+        .agg(last)
+        .collect()
+        .with_context(|| format!("Failed to index lookup table {} by {}", config.table_uri, config.key_column))
+}
@@ -0,0 +1,75 @@
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+/// Events a subscriber may fall behind by before it is told it lagged
+const EVENT_CAPACITY: usize = 1024;
+
+/// Something a table's processes did, for applications embedding the
+/// orchestrator to react to without scraping logs
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OrchestratorEvent {
+    /// The writer committed `batch_ids` as `version`
+    BatchCommitted {
+        table_uri: String,
+        version: i64,
+        rows: usize,
+        batch_ids: Vec<String>,
+        latency_ms: u64,
+    },
+    /// A compaction cycle replaced `files_removed` small files
+    CompactionCompleted {
+        table_uri: String,
+        version: i64,
+        files_removed: u64,
+        bytes_removed: u64,
+        elapsed_ms: u64,
+    },
+    /// A vacuum cycle deleted `files` (or, in dry-run mode, found them eligible)
+    VacuumCompleted {
+        table_uri: String,
+        files: usize,
+        bytes: u64,
+        dry_run: bool,
+        elapsed_ms: u64,
+    },
+    /// A write took longer than `writer.max_latency_ms`
+    SlaMissed {
+        table_uri: String,
+        latency_ms: u64,
+        sla_ms: u64,
+    },
+    /// The writer gave up on a flush after exhausting its retries
+    CircuitOpened {
+        table_uri: String,
+        rows: usize,
+        batch_ids: Vec<String>,
+        error: String,
+    },
+}
+
+/// Broadcasts the [`OrchestratorEvent`]s of one table. Publishing never
+/// waits: without subscribers events are dropped, and a subscriber more
+/// than `EVENT_CAPACITY` events behind is told how many it missed.
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<OrchestratorEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self { sender: broadcast::channel(EVENT_CAPACITY).0 }
+    }
+}
+
+impl EventBus {
+    /// Receive events from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<OrchestratorEvent> {
+        self.sender.subscribe()
+    }
+
+    pub(crate) fn publish(&self, event: OrchestratorEvent) {
+        // Only fails when nobody is subscribed
+        let _ = self.sender.send(event);
+    }
+}
@@ -0,0 +1,99 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use deltalake::arrow::array::AsArray;
+use deltalake::arrow::datatypes::UInt64Type;
+use deltalake::arrow::record_batch::RecordBatch;
+use deltalake::datafusion::dataframe::DataFrameWriteOptions;
+use deltalake::datafusion::prelude::SessionContext;
+use deltalake::DeltaTable;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+
+/// Version of the table to read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsOf {
+    Latest,
+    Version(i64),
+    /// The latest version committed at or before this time
+    Timestamp(DateTime<Utc>),
+}
+
+/// File format of an export
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Parquet,
+    Csv,
+    /// One JSON object per line
+    Jsonl,
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parquet => write!(f, "parquet"),
+            Self::Csv => write!(f, "csv"),
+            Self::Jsonl => write!(f, "jsonl"),
+        }
+    }
+}
+
+/// Outcome of an export
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportReport {
+    pub version: i64,
+    pub format: ExportFormat,
+    pub output: String,
+    pub rows: u64,
+}
+
+impl fmt::Display for ExportReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Exported {} rows of version {} to {} ({})", self.rows, self.version, self.output, self.format)
+    }
+}
+
+/// Write every row of `table` as of `as_of` to a single file at `output`.
+/// The table itself is left at its current version.
+pub async fn export_table(table: &DeltaTable, as_of: AsOf, format: ExportFormat, output: &str) -> Result<ExportReport> {
+    let mut snapshot = table.clone();
+    match as_of {
+        AsOf::Latest => {}
+        AsOf::Version(version) => {
+            if version < 0 || version > table.version() {
+                bail!("Cannot export version {}: table is at version {}", version, table.version());
+            }
+            snapshot.load_version(version).await
+                .with_context(|| format!("Failed to load version {}", version))?;
+        }
+        AsOf::Timestamp(timestamp) => {
+            snapshot.load_with_datetime(timestamp).await
+                .with_context(|| format!("Failed to load the table as of {}", timestamp))?;
+        }
+    }
+    let version = snapshot.version();
+
+    let df = SessionContext::new()
+        .read_table(Arc::new(snapshot))
+        .with_context(|| format!("Failed to register version {}", version))?;
+    let options = DataFrameWriteOptions::new().with_single_file_output(true);
+    let written = match format {
+        ExportFormat::Parquet => df.write_parquet(output, options, None).await,
+        ExportFormat::Csv => df.write_csv(output, options, None).await,
+        ExportFormat::Jsonl => df.write_json(output, options, None).await,
+    }
+    .with_context(|| format!("Failed to export version {} to {}", version, output))?;
+
+    Ok(ExportReport { version, format, output: output.to_string(), rows: written_rows(&written) })
+}
+
+/// DataFusion reports the rows it wrote as a single `count` value
+fn written_rows(batches: &[RecordBatch]) -> u64 {
+    batches
+        .iter()
+        .filter_map(|batch| batch.column(0).as_primitive_opt::<UInt64Type>())
+        .filter(|counts| !counts.is_empty())
+        .map(|counts| counts.value(0))
+        .sum()
+}
@@ -0,0 +1,236 @@
+use anyhow::{Context, Result};
+use arrow_flight::encode::FlightDataEncoderBuilder;
+use arrow_flight::error::FlightError;
+use arrow_flight::flight_service_server::{FlightService, FlightServiceServer};
+use arrow_flight::sql::server::FlightSqlService;
+use arrow_flight::sql::{
+    CommandGetCatalogs, CommandGetDbSchemas, CommandGetTables, CommandStatementQuery, ProstMessageExt, SqlInfo,
+    TicketStatementQuery,
+};
+use arrow_flight::{FlightDescriptor, FlightEndpoint, FlightInfo, HandshakeRequest, HandshakeResponse, Ticket};
+use deltalake::arrow::datatypes::{Schema, SchemaRef};
+use deltalake::arrow::error::ArrowError;
+use deltalake::arrow::record_batch::RecordBatch;
+use deltalake::datafusion::execution::context::SQLOptions;
+use deltalake::datafusion::prelude::{DataFrame, SessionContext};
+use deltalake::datafusion::sql::TableReference;
+use deltalake::DeltaTable;
+use futures::stream::{self, Stream, StreamExt, TryStreamExt};
+use prost::Message;
+use std::collections::BTreeMap;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status, Streaming};
+use crate::config::FlightConfig;
+
+/// Catalog and schema the managed tables are exposed under
+const CATALOG: &str = "datafusion";
+const DB_SCHEMA: &str = "public";
+
+type DoGetStream = <FlightSqlServer as FlightService>::DoGetStream;
+
+/// Read-only Flight SQL endpoint over the managed tables. Every query runs
+/// on a copy of each table's cached snapshot, brought up to date with any
+/// newer commits, so readers see fresh data without object store access.
+#[derive(Clone)]
+pub struct FlightSqlServer {
+    /// Table handles keyed by the name they are queried under
+    tables: Arc<BTreeMap<String, Arc<Mutex<DeltaTable>>>>,
+}
+
+impl FlightSqlServer {
+    pub fn new(tables: BTreeMap<String, Arc<Mutex<DeltaTable>>>) -> Self {
+        Self { tables: Arc::new(tables) }
+    }
+
+    /// Session with the latest version of every table registered
+    async fn session(&self) -> Result<SessionContext, Status> {
+        let ctx = SessionContext::new();
+        for (name, handle) in self.tables.iter() {
+            // Clone first so a long compaction holding the lock is only
+            // waited on for the copy, not the refresh
+            let mut table = handle.lock().await.clone();
+            table.update().await.map_err(internal)?;
+            ctx.register_table(TableReference::bare(name.as_str()), Arc::new(table))
+                .map_err(internal)?;
+        }
+        Ok(ctx)
+    }
+
+    /// Plan a query, refusing anything but reads
+    async fn plan(&self, sql: &str) -> Result<DataFrame, Status> {
+        let options = SQLOptions::new()
+            .with_allow_ddl(false)
+            .with_allow_dml(false)
+            .with_allow_statements(false);
+        self.session()
+            .await?
+            .sql_with_options(sql, options)
+            .await
+            .map_err(|e| Status::invalid_argument(e.to_string()))
+    }
+
+    /// Table schemas, for the metadata commands
+    async fn table_schemas(&self) -> Result<Vec<(String, SchemaRef)>, Status> {
+        let mut schemas = Vec::with_capacity(self.tables.len());
+        for (name, handle) in self.tables.iter() {
+            let schema = handle.lock().await.snapshot().map_err(internal)?.arrow_schema().map_err(internal)?;
+            schemas.push((name.clone(), schema));
+        }
+        Ok(schemas)
+    }
+}
+
+#[tonic::async_trait]
+impl FlightSqlService for FlightSqlServer {
+    type FlightService = Self;
+
+    /// No authentication: the endpoint is meant for trusted networks
+    async fn do_handshake(
+        &self,
+        _request: Request<Streaming<HandshakeRequest>>,
+    ) -> Result<Response<Pin<Box<dyn Stream<Item = Result<HandshakeResponse, Status>> + Send>>>, Status> {
+        let response = HandshakeResponse { protocol_version: 0, payload: Default::default() };
+        Ok(Response::new(Box::pin(stream::once(async { Ok(response) }))))
+    }
+
+    async fn get_flight_info_statement(
+        &self,
+        query: CommandStatementQuery,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        let df = self.plan(&query.query).await?;
+        // The query is planned again on do_get; the ticket only carries the SQL
+        let ticket = TicketStatementQuery { statement_handle: query.query.into_bytes().into() };
+        flight_info(df.schema().as_arrow(), ticket.as_any().encode_to_vec(), request)
+    }
+
+    async fn do_get_statement(
+        &self,
+        ticket: TicketStatementQuery,
+        _request: Request<Ticket>,
+    ) -> Result<Response<DoGetStream>, Status> {
+        let sql = String::from_utf8(ticket.statement_handle.to_vec())
+            .map_err(|_| Status::invalid_argument("Statement handle is not a query"))?;
+        let df = self.plan(&sql).await?;
+        let schema = Arc::new(df.schema().as_arrow().clone());
+        let batches = df
+            .execute_stream()
+            .await
+            .map_err(internal)?
+            .map_err(|e| FlightError::ExternalError(Box::new(e)));
+        Ok(Response::new(encode(schema, batches)))
+    }
+
+    async fn get_flight_info_catalogs(
+        &self,
+        query: CommandGetCatalogs,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        flight_info(&query.clone().into_builder().schema(), query.as_any().encode_to_vec(), request)
+    }
+
+    async fn do_get_catalogs(
+        &self,
+        query: CommandGetCatalogs,
+        _request: Request<Ticket>,
+    ) -> Result<Response<DoGetStream>, Status> {
+        let mut builder = query.into_builder();
+        builder.append(CATALOG);
+        single_batch(builder.schema(), builder.build())
+    }
+
+    async fn get_flight_info_schemas(
+        &self,
+        query: CommandGetDbSchemas,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        flight_info(&query.clone().into_builder().schema(), query.as_any().encode_to_vec(), request)
+    }
+
+    async fn do_get_schemas(
+        &self,
+        query: CommandGetDbSchemas,
+        _request: Request<Ticket>,
+    ) -> Result<Response<DoGetStream>, Status> {
+        let mut builder = query.into_builder();
+        builder.append(CATALOG, DB_SCHEMA);
+        single_batch(builder.schema(), builder.build())
+    }
+
+    async fn get_flight_info_tables(
+        &self,
+        query: CommandGetTables,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        flight_info(&query.clone().into_builder().schema(), query.as_any().encode_to_vec(), request)
+    }
+
+    async fn do_get_tables(
+        &self,
+        query: CommandGetTables,
+        _request: Request<Ticket>,
+    ) -> Result<Response<DoGetStream>, Status> {
+        let mut builder = query.into_builder();
+        for (name, schema) in self.table_schemas().await? {
+            builder
+                .append(CATALOG, DB_SCHEMA, &name, "TABLE", &schema)
+                .map_err(internal)?;
+        }
+        single_batch(builder.schema(), builder.build())
+    }
+
+    async fn register_sql_info(&self, _id: i32, _result: &SqlInfo) {}
+}
+
+/// Serve Flight SQL until shutdown
+pub async fn serve(server: FlightSqlServer, config: FlightConfig, shutdown: CancellationToken) -> Result<()> {
+    let addr = config
+        .bind_address
+        .parse()
+        .with_context(|| format!("Invalid Flight SQL bind address {}", config.bind_address))?;
+    log::info!("Flight SQL endpoint listening on {}", addr);
+
+    Server::builder()
+        .add_service(FlightServiceServer::new(server))
+        .serve_with_shutdown(addr, async move { shutdown.cancelled().await })
+        .await
+        .context("Flight SQL server failed")
+}
+
+/// Flight info with a single endpoint, redeemed at this server with `ticket`
+fn flight_info(
+    schema: &Schema,
+    ticket: Vec<u8>,
+    request: Request<FlightDescriptor>,
+) -> Result<Response<FlightInfo>, Status> {
+    let info = FlightInfo::new()
+        .try_with_schema(schema)
+        .map_err(internal)?
+        .with_endpoint(FlightEndpoint::new().with_ticket(Ticket::new(ticket)))
+        .with_descriptor(request.into_inner());
+    Ok(Response::new(info))
+}
+
+fn single_batch(schema: SchemaRef, batch: Result<RecordBatch, ArrowError>) -> Result<Response<DoGetStream>, Status> {
+    let batch = batch.map_err(internal)?;
+    Ok(Response::new(encode(schema, stream::once(async { Ok(batch) }))))
+}
+
+fn encode(
+    schema: SchemaRef,
+    batches: impl Stream<Item = Result<RecordBatch, FlightError>> + Send + 'static,
+) -> DoGetStream {
+    FlightDataEncoderBuilder::new()
+        .with_schema(schema)
+        .build(batches)
+        .map_err(Status::from)
+        .boxed()
+}
+
+fn internal(e: impl std::fmt::Display) -> Status {
+    Status::internal(e.to_string())
+}
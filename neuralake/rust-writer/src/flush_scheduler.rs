@@ -0,0 +1,119 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+
+/// Shares a fixed number of concurrent flush slots between tables in
+/// proportion to their weights (stride scheduling). Each grant advances the
+/// table's virtual time by `1 / weight`, and the waiting table furthest
+/// behind is served next, so a table flushing constantly cannot starve a
+/// quiet one. A table that was idle rejoins at the current virtual time
+/// rather than with credit banked while it had nothing to flush.
+#[derive(Debug)]
+pub struct FlushScheduler {
+    state: Mutex<SchedulerState>,
+}
+
+#[derive(Debug)]
+struct SchedulerState {
+    free: usize,
+    /// Virtual time of the most recent grant
+    now: f64,
+    tables: BTreeMap<String, TableQueue>,
+}
+
+#[derive(Debug)]
+struct TableQueue {
+    weight: u32,
+    /// Virtual time at which the table is next due a slot
+    pass: f64,
+    waiters: VecDeque<oneshot::Sender<FlushPermit>>,
+}
+
+impl TableQueue {
+    fn new(weight: u32) -> Self {
+        Self { weight: weight.max(1), pass: 0.0, waiters: VecDeque::new() }
+    }
+}
+
+/// A flush slot, handed to the next waiting table when dropped
+#[derive(Debug)]
+pub struct FlushPermit {
+    scheduler: Arc<FlushScheduler>,
+}
+
+impl FlushScheduler {
+    pub fn new(slots: usize) -> Arc<Self> {
+        Arc::new(Self {
+            state: Mutex::new(SchedulerState { free: slots.max(1), now: 0.0, tables: BTreeMap::new() }),
+        })
+    }
+
+    /// Give `table` a share of the slots proportional to `weight` (at least 1);
+    /// unregistered tables get weight 1
+    pub fn register(&self, table: &str, weight: u32) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .tables
+            .entry(table.to_string())
+            .and_modify(|queue| queue.weight = weight.max(1))
+            .or_insert_with(|| TableQueue::new(weight));
+    }
+
+    /// Wait for a flush slot for `table`
+    pub async fn acquire(self: &Arc<Self>, table: &str) -> FlushPermit {
+        let waiter = {
+            let mut guard = self.state.lock().unwrap();
+            let state = &mut *guard;
+            let queue = state.tables.entry(table.to_string()).or_insert_with(|| TableQueue::new(1));
+            if queue.waiters.is_empty() {
+                queue.pass = queue.pass.max(state.now);
+            }
+            let (tx, rx) = oneshot::channel();
+            queue.waiters.push_back(tx);
+            rx
+        };
+        self.dispatch();
+        // Senders are only dropped once sent to, and `self` keeps them alive
+        waiter.await.expect("flush scheduler dropped a waiting table")
+    }
+
+    /// Grant free slots to the waiting tables furthest behind
+    fn dispatch(self: &Arc<Self>) {
+        let mut grants = Vec::new();
+        {
+            let mut guard = self.state.lock().unwrap();
+            let state = &mut *guard;
+            while state.free > 0 {
+                let Some(queue) = state
+                    .tables
+                    .values_mut()
+                    .filter(|queue| !queue.waiters.is_empty())
+                    .min_by(|a, b| a.pass.total_cmp(&b.pass))
+                else {
+                    break;
+                };
+                let Some(waiter) = queue.waiters.pop_front() else { break };
+                // A flush cancelled while waiting is not charged for
+                if waiter.is_closed() {
+                    continue;
+                }
+                state.now = queue.pass;
+                queue.pass += 1.0 / queue.weight as f64;
+                state.free -= 1;
+                grants.push(waiter);
+            }
+        }
+        // Outside the lock, as a permit refused by a waiter that went away
+        // in the meantime is dropped and so released right here
+        for waiter in grants {
+            let _ = waiter.send(FlushPermit { scheduler: self.clone() });
+        }
+    }
+}
+
+impl Drop for FlushPermit {
+    fn drop(&mut self) {
+        self.scheduler.state.lock().unwrap().free += 1;
+        self.scheduler.dispatch();
+    }
+}
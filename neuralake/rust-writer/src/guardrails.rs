@@ -0,0 +1,76 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+use crate::config::GuardrailsConfig;
+
+/// Window the commit rate limit is counted over
+const COMMIT_RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// A write refused because it would breach a configured limit. Never retried.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum GuardrailViolation {
+    #[error("Write would give the table {columns} columns, over the limit of {limit}")]
+    TooManyColumns { columns: usize, limit: usize },
+    #[error("Commit would add {files} files, over the limit of {limit}")]
+    TooManyFiles { files: usize, limit: usize },
+    #[error("Table already has {commits} commits in the last minute, the limit")]
+    CommitRateExceeded { commits: usize },
+}
+
+/// Checks writes against the configured limits before they reach the log
+#[derive(Debug, Default)]
+pub struct Guardrails {
+    config: GuardrailsConfig,
+    /// Times of the commits within the rate window, oldest first
+    recent_commits: Mutex<VecDeque<Instant>>,
+}
+
+impl Guardrails {
+    pub fn new(config: GuardrailsConfig) -> Self {
+        Self { config, recent_commits: Mutex::default() }
+    }
+
+    /// A write leaving the table with `columns` columns
+    pub fn check_columns(&self, columns: usize) -> Result<(), GuardrailViolation> {
+        match self.config.max_columns {
+            Some(limit) if columns > limit => Err(GuardrailViolation::TooManyColumns { columns, limit }),
+            _ => Ok(()),
+        }
+    }
+
+    /// A commit adding `files` data files
+    pub fn check_files(&self, files: usize) -> Result<(), GuardrailViolation> {
+        match self.config.max_files_per_commit {
+            Some(limit) if files > limit => Err(GuardrailViolation::TooManyFiles { files, limit }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether another commit fits in the rate limit
+    pub fn check_commit_rate(&self) -> Result<(), GuardrailViolation> {
+        let Some(limit) = self.config.max_commits_per_minute else { return Ok(()) };
+        let mut recent = self.recent_commits.lock().unwrap();
+        expire(&mut recent);
+        if recent.len() >= limit as usize {
+            return Err(GuardrailViolation::CommitRateExceeded { commits: recent.len() });
+        }
+        Ok(())
+    }
+
+    /// Count a commit towards the rate limit
+    pub fn record_commit(&self) {
+        if self.config.max_commits_per_minute.is_none() {
+            return;
+        }
+        let mut recent = self.recent_commits.lock().unwrap();
+        expire(&mut recent);
+        recent.push_back(Instant::now());
+    }
+}
+
+fn expire(recent: &mut VecDeque<Instant>) {
+    while recent.front().is_some_and(|at| at.elapsed() >= COMMIT_RATE_WINDOW) {
+        recent.pop_front();
+    }
+}
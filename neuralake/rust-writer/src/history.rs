@@ -0,0 +1,62 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use deltalake::DeltaTable;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use crate::spark_history::spark_operation_metrics;
+
+/// One commit from the Delta log
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryEntry {
+    pub version: i64,
+    pub timestamp: Option<DateTime<Utc>>,
+    pub operation: Option<String>,
+    pub operation_parameters: HashMap<String, Value>,
+    /// Engine-reported metrics such as `numOutputRows`, in Spark's shape
+    pub operation_metrics: BTreeMap<String, String>,
+}
+
+/// Most recent commits first, at most `limit` of them
+pub async fn table_history(table: &DeltaTable, limit: Option<usize>) -> Result<Vec<HistoryEntry>> {
+    let commits = table.history(limit).await
+        .context("Failed to read table history")?;
+
+    // History is returned newest first and contiguous from the current version
+    let latest = table.version();
+    let entries = commits
+        .into_iter()
+        .enumerate()
+        .map(|(i, commit)| {
+            let operation_metrics = match commit.info.get("operationMetrics") {
+                Some(Value::Object(metrics)) => spark_operation_metrics(commit.operation.as_deref(), metrics),
+                _ => BTreeMap::new(),
+            };
+            HistoryEntry {
+                version: latest - i as i64,
+                timestamp: commit.timestamp.and_then(DateTime::from_timestamp_millis),
+                operation: commit.operation,
+                operation_parameters: commit.operation_parameters.unwrap_or_default(),
+                operation_metrics,
+            }
+        })
+        .collect();
+
+    Ok(entries)
+}
+
+impl fmt::Display for HistoryEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let metrics: Vec<String> = self.operation_metrics.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+
+        write!(
+            f,
+            "{:>8}  {:<25}  {:<16}  {}",
+            self.version,
+            self.timestamp.map(|t| t.to_rfc3339()).unwrap_or_else(|| "-".to_string()),
+            self.operation.as_deref().unwrap_or("-"),
+            metrics.join(" ")
+        )
+    }
+}
@@ -0,0 +1,183 @@
+use anyhow::{bail, Context, Result};
+use deltalake::kernel::Transaction;
+use deltalake::{DeltaTable, DeltaTableBuilder, ObjectMeta, StorageOptions};
+use futures::TryStreamExt;
+use polars::prelude::*;
+use std::fmt;
+use std::io::Cursor;
+use crate::writer::{concat_frames, WriterProcess};
+
+/// Progress of a Parquet import
+#[derive(Debug, Clone, Default)]
+pub struct ImportReport {
+    /// Files matching the source pattern
+    pub files_total: usize,
+    pub files_read: usize,
+    pub bytes_read: u64,
+    /// Rows appended by this run
+    pub rows_imported: usize,
+    pub batches_committed: usize,
+    /// Batches already committed by an earlier, interrupted run
+    pub batches_skipped: usize,
+    /// Partitions compacted after the import (`optimize_after_import`)
+    pub partitions_compacted: Vec<String>,
+}
+
+impl fmt::Display for ImportReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{} files, {} bytes read, {} rows imported in {} batches ({} already committed)",
+            self.files_read,
+            self.files_total,
+            self.bytes_read,
+            self.rows_imported,
+            self.batches_committed,
+            self.batches_skipped
+        )?;
+        if !self.partitions_compacted.is_empty() {
+            write!(f, ", {} partitions compacted", self.partitions_compacted.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// Application transaction id recording how many rows of `source` are in the table
+pub fn import_app_id(source: &str) -> String {
+    format!("import:{}", source)
+}
+
+/// Rows of `source` already committed to `table` by earlier runs
+pub fn committed_rows(table: &DeltaTable, source: &str) -> i64 {
+    table
+        .get_app_transaction_version()
+        .get(&import_app_id(source))
+        .map_or(0, |txn| txn.version)
+}
+
+/// Append every Parquet file matching `source` (a local path or object store
+/// URI, optionally with `*`, `?` and `**` wildcards) in batches of
+/// `batch_rows` rows, regardless of how rows are spread across files.
+///
+/// Each batch commits the running row count as an application transaction,
+/// so rerunning an interrupted import with the same source and batch size
+/// skips the batches already committed.
+pub async fn import_parquet(
+    writer: &WriterProcess,
+    table_uri: &str,
+    storage_options: &StorageOptions,
+    source: &str,
+    batch_rows: usize,
+    resume_after: i64,
+    mut progress: impl FnMut(&ImportReport),
+) -> Result<ImportReport> {
+    if batch_rows == 0 {
+        bail!("Batch size must be at least one row");
+    }
+
+    let (root, pattern) = split_glob(source);
+    let store = DeltaTableBuilder::from_uri(root)
+        .with_storage_options(storage_options.0.clone())
+        .build_storage()
+        .with_context(|| format!("Failed to open {}", root))?
+        .object_store(None);
+
+    let mut files: Vec<ObjectMeta> = store
+        .list(None)
+        .try_filter(|meta| {
+            let path = meta.location.as_ref();
+            let matched = if pattern.is_empty() { path.ends_with(".parquet") } else { glob_match(pattern, path) };
+            futures::future::ready(matched)
+        })
+        .try_collect()
+        .await
+        .with_context(|| format!("Failed to list {}", root))?;
+    if files.is_empty() {
+        bail!("No Parquet files match {}", source);
+    }
+    // A stable order keeps batch boundaries identical across reruns
+    files.sort_by(|a, b| a.location.cmp(&b.location));
+
+    let app_id = import_app_id(source);
+    let mut report = ImportReport { files_total: files.len(), ..Default::default() };
+    let mut pending: Vec<DataFrame> = Vec::new();
+    let mut pending_rows = 0usize;
+    let mut rows_seen = 0i64;
+
+    for (i, file) in files.iter().enumerate() {
+        let bytes = store
+            .get(&file.location)
+            .await
+            .with_context(|| format!("Failed to read {}", file.location))?
+            .bytes()
+            .await
+            .with_context(|| format!("Failed to read {}", file.location))?;
+        let df = ParquetReader::new(Cursor::new(bytes))
+            .finish()
+            .with_context(|| format!("Failed to decode {} as Parquet", file.location))?;
+
+        report.files_read += 1;
+        report.bytes_read += file.size as u64;
+        pending_rows += df.height();
+        pending.push(df);
+
+        let last_file = i + 1 == files.len();
+        while pending_rows >= batch_rows || (last_file && pending_rows > 0) {
+            let combined = concat_frames(pending.drain(..)).context("Input files have incompatible schemas")?;
+            let batch = combined.slice(0, batch_rows);
+            let rest = combined.slice(batch_rows as i64, combined.height().saturating_sub(batch_rows));
+            pending_rows = rest.height();
+            if pending_rows > 0 {
+                pending.push(rest);
+            }
+
+            rows_seen += batch.height() as i64;
+            if rows_seen <= resume_after {
+                report.batches_skipped += 1;
+                continue;
+            }
+
+            let rows = batch.height();
+            writer
+                .write_batch_with_txns(batch, &[Transaction::new(&app_id, rows_seen)], storage_options, table_uri)
+                .await
+                .with_context(|| format!("Failed to import batch ending at row {}", rows_seen))?;
+            report.rows_imported += rows;
+            report.batches_committed += 1;
+            log::info!("Imported {} rows from {} ({})", rows, source, report);
+            progress(&report);
+        }
+    }
+
+    Ok(report)
+}
+
+/// Split a source into the directory to list and the pattern below it, e.g.
+/// `s3://bucket/events/2023-*/*.parquet` into `s3://bucket/events/` and
+/// `2023-*/*.parquet`
+pub fn split_glob(source: &str) -> (&str, &str) {
+    let first_wildcard = source.find(['*', '?']).unwrap_or(source.len());
+    let root_end = source[..first_wildcard].rfind('/').map_or(0, |i| i + 1);
+    (&source[..root_end], &source[root_end..])
+}
+
+/// Match a `/`-separated path against a pattern where `*` and `?` stay
+/// within one path segment and `**` spans any number of segments
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    fn matches(pattern: &[u8], path: &[u8]) -> bool {
+        match pattern {
+            [] => path.is_empty(),
+            [b'*', b'*', rest @ ..] => {
+                // `**/` may also match no directories at all
+                rest.strip_prefix(b"/").is_some_and(|r| matches(r, path))
+                    || (0..=path.len()).any(|i| matches(rest, &path[i..]))
+            }
+            [b'*', rest @ ..] => (0..=path.len())
+                .take_while(|&i| i == 0 || path[i - 1] != b'/')
+                .any(|i| matches(rest, &path[i..])),
+            [b'?', rest @ ..] => path.first().is_some_and(|c| *c != b'/') && matches(rest, &path[1..]),
+            [c, rest @ ..] => path.first() == Some(c) && matches(rest, &path[1..]),
+        }
+    }
+    matches(pattern.as_bytes(), path.as_bytes())
+}
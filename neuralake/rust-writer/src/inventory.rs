@@ -0,0 +1,129 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use deltalake::logstore::ObjectStoreRef;
+use deltalake::{DeltaTable, ObjectMeta, Path};
+use futures::{stream, StreamExt, TryStreamExt};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+/// Top-level entries that never hold data files
+const SKIPPED_PREFIXES: &[&str] = &["_delta_log", "_checksums"];
+
+/// The objects under a table root at one point in time, log excluded
+#[derive(Debug, Clone)]
+pub struct FileInventory {
+    pub listed_at: DateTime<Utc>,
+    objects: HashMap<String, ObjectMeta>,
+}
+
+impl FileInventory {
+    /// The object at `path`, relative to the table root as in add actions
+    pub fn get(&self, path: &str) -> Option<&ObjectMeta> {
+        self.objects.get(Path::from(path).as_ref())
+    }
+
+    pub fn objects(&self) -> impl Iterator<Item = &ObjectMeta> {
+        self.objects.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+}
+
+/// List the table's objects, walking partition directories level by level
+/// with up to `concurrency` LIST calls in flight. A flat listing pages
+/// through every key of a large table one request at a time; one listing
+/// per partition prefix lets those pages be fetched side by side.
+pub async fn list_table_files(store: ObjectStoreRef, concurrency: usize) -> Result<FileInventory> {
+    let listed_at = Utc::now();
+    let mut objects = HashMap::new();
+    let mut prefixes = vec![None];
+    while !prefixes.is_empty() {
+        let listings: Vec<_> = stream::iter(prefixes)
+            .map(|prefix: Option<Path>| {
+                let store = store.clone();
+                async move {
+                    let name = prefix.as_ref().map_or("table root", |p| p.as_ref()).to_string();
+                    store.list_with_delimiter(prefix.as_ref()).await
+                        .with_context(|| format!("Failed to list {}", name))
+                }
+            })
+            .buffer_unordered(concurrency.max(1))
+            .try_collect()
+            .await?;
+
+        prefixes = Vec::new();
+        for listing in listings {
+            for meta in listing.objects {
+                objects.insert(meta.location.to_string(), meta);
+            }
+            prefixes.extend(
+                listing
+                    .common_prefixes
+                    .into_iter()
+                    .filter(|prefix| !SKIPPED_PREFIXES.contains(&prefix.as_ref()))
+                    .map(Some),
+            );
+        }
+    }
+    Ok(FileInventory { listed_at, objects })
+}
+
+/// An inventory shared by vacuum and verify runs, listed again once older
+/// than `ttl`. Files added since the listing are not in it, so readers fall
+/// back to a HEAD request for paths it does not know.
+#[derive(Debug)]
+pub struct InventoryCache {
+    ttl: Duration,
+    concurrency: usize,
+    /// Held while listing, so concurrent readers share one listing
+    cached: Mutex<Option<Arc<FileInventory>>>,
+}
+
+impl InventoryCache {
+    pub fn new(ttl: Duration, concurrency: usize) -> Self {
+        Self { ttl, concurrency, cached: Mutex::new(None) }
+    }
+
+    /// The cached inventory of `table`, or a fresh listing
+    pub async fn get(&self, table: &DeltaTable) -> Result<Arc<FileInventory>> {
+        let mut cached = self.cached.lock().await;
+        if let Some(inventory) = cached.as_ref() {
+            let age = (Utc::now() - inventory.listed_at).to_std().unwrap_or_default();
+            if age < self.ttl {
+                log::debug!("Reusing inventory of {} files listed {:?} ago", inventory.len(), age);
+                return Ok(inventory.clone());
+            }
+        }
+        let inventory = Arc::new(list_table_files(table.object_store(), self.concurrency).await?);
+        log::debug!("Listed {} files of {}", inventory.len(), table.table_uri());
+        *cached = Some(inventory.clone());
+        Ok(inventory)
+    }
+
+    /// Drop deleted files from the cached inventory, returning what remains
+    pub async fn forget(&self, paths: &[String]) -> Option<Arc<FileInventory>> {
+        let mut cached = self.cached.lock().await;
+        let inventory = cached.as_ref()?;
+        let deleted: HashSet<String> = paths.iter().map(|path| Path::from(path.as_str()).to_string()).collect();
+        let remaining = FileInventory {
+            listed_at: inventory.listed_at,
+            objects: inventory
+                .objects
+                .iter()
+                .filter(|(path, _)| !deleted.contains(*path))
+                .map(|(path, meta)| (path.clone(), meta.clone()))
+                .collect(),
+        };
+        let remaining = Arc::new(remaining);
+        *cached = Some(remaining.clone());
+        Some(remaining)
+    }
+}
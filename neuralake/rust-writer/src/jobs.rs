@@ -0,0 +1,232 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use crate::export::ExportFormat;
+
+/// An ad-hoc maintenance operation queued for the orchestrator
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JobKind {
+    /// Run one compaction pass
+    Compact,
+    /// Run one compaction pass on the partitions matching every filter,
+    /// written like `day=2024-01-01` or `region!=eu`
+    CompactPartitions { filters: Vec<String> },
+    /// Run one vacuum pass, optionally overriding the configured settings
+    Vacuum {
+        #[serde(default)]
+        retention_hours: Option<u64>,
+        #[serde(default)]
+        dry_run: bool,
+    },
+    /// Write the table as of `version` (the latest if unset) to one file
+    Export {
+        #[serde(default)]
+        version: Option<i64>,
+        format: ExportFormat,
+        output: String,
+    },
+}
+
+/// Lifecycle of a queued job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+impl JobStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Succeeded => "succeeded",
+            JobStatus::Failed => "failed",
+        }
+    }
+
+    fn parse(s: &str) -> Self {
+        match s {
+            "running" => JobStatus::Running,
+            "succeeded" => JobStatus::Succeeded,
+            "failed" => JobStatus::Failed,
+            _ => JobStatus::Queued,
+        }
+    }
+}
+
+/// A job as stored in the queue
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: i64,
+    pub table_uri: String,
+    pub kind: JobKind,
+    pub status: JobStatus,
+    pub submitted_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Result summary or error message
+    pub message: Option<String>,
+}
+
+/// Persistent job queue backed by a local SQLite database. Its calls block
+/// on SQLite; async code goes through [`JobQueue::blocking`].
+pub struct JobQueue {
+    conn: Mutex<Connection>,
+}
+
+const JOB_COLUMNS: &str =
+    "id, table_uri, kind, status, submitted_at, started_at, finished_at, message";
+
+impl JobQueue {
+    /// Open (or create) the queue database at `path`
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed to open job queue at {}", path.display()))?;
+
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS jobs (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                table_uri TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                status TEXT NOT NULL,
+                submitted_at TEXT NOT NULL,
+                started_at TEXT,
+                finished_at TEXT,
+                message TEXT
+            );
+            CREATE INDEX IF NOT EXISTS jobs_by_status ON jobs (status, table_uri, id);",
+        )
+        .context("Failed to initialise job queue schema")?;
+
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    /// Run `f` against the queue on a blocking thread, off the async workers
+    pub async fn blocking<T, F>(self: &Arc<Self>, f: F) -> Result<T>
+    where
+        F: FnOnce(&JobQueue) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let queue = self.clone();
+        tokio::task::spawn_blocking(move || f(&queue))
+            .await
+            .context("Job queue call panicked")?
+    }
+
+    /// Enqueue a job and return its id
+    pub fn submit(&self, table_uri: &str, kind: &JobKind) -> Result<i64> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO jobs (table_uri, kind, status, submitted_at) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                table_uri,
+                serde_json::to_string(kind)?,
+                JobStatus::Queued.as_str(),
+                Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Look up a single job
+    pub fn get(&self, id: i64) -> Result<Option<Job>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            &format!("SELECT {JOB_COLUMNS} FROM jobs WHERE id = ?1"),
+            params![id],
+            row_to_job,
+        )
+        .optional()
+        .context("Failed to read job")
+    }
+
+    /// Most recent jobs first
+    pub fn list(&self, limit: usize) -> Result<Vec<Job>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {JOB_COLUMNS} FROM jobs ORDER BY id DESC LIMIT ?1"
+        ))?;
+        let jobs = stmt
+            .query_map(params![limit as i64], row_to_job)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(jobs)
+    }
+
+    /// Atomically mark the oldest queued job for `table_uri` as running
+    pub fn claim_next(&self, table_uri: &str) -> Result<Option<Job>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            &format!(
+                "UPDATE jobs SET status = ?1, started_at = ?2
+                 WHERE id = (SELECT id FROM jobs WHERE status = ?3 AND table_uri = ?4
+                             ORDER BY id LIMIT 1)
+                 RETURNING {JOB_COLUMNS}"
+            ),
+            params![
+                JobStatus::Running.as_str(),
+                Utc::now().to_rfc3339(),
+                JobStatus::Queued.as_str(),
+                table_uri
+            ],
+            row_to_job,
+        )
+        .optional()
+        .context("Failed to claim next job")
+    }
+
+    /// Record the outcome of a job
+    pub fn finish(&self, id: i64, outcome: &Result<String>) -> Result<()> {
+        let (status, message) = match outcome {
+            Ok(summary) => (JobStatus::Succeeded, summary.clone()),
+            Err(e) => (JobStatus::Failed, format!("{:#}", e)),
+        };
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE jobs SET status = ?1, finished_at = ?2, message = ?3 WHERE id = ?4",
+            params![status.as_str(), Utc::now().to_rfc3339(), message, id],
+        )?;
+        Ok(())
+    }
+
+    /// Requeue jobs left running by a crashed orchestrator
+    pub fn requeue_interrupted(&self, table_uri: &str) -> Result<usize> {
+        let conn = self.conn.lock().unwrap();
+        let count = conn.execute(
+            "UPDATE jobs SET status = ?1, started_at = NULL WHERE status = ?2 AND table_uri = ?3",
+            params![JobStatus::Queued.as_str(), JobStatus::Running.as_str(), table_uri],
+        )?;
+        Ok(count)
+    }
+}
+
+fn row_to_job(row: &Row<'_>) -> rusqlite::Result<Job> {
+    let kind: String = row.get(2)?;
+    let status: String = row.get(3)?;
+    let timestamp = |idx: usize| -> rusqlite::Result<Option<DateTime<Utc>>> {
+        let value: Option<String> = row.get(idx)?;
+        Ok(value
+            .and_then(|v| DateTime::parse_from_rfc3339(&v).ok())
+            .map(|t| t.with_timezone(&Utc)))
+    };
+
+    Ok(Job {
+        id: row.get(0)?,
+        table_uri: row.get(1)?,
+        kind: serde_json::from_str(&kind).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Text, Box::new(e))
+        })?,
+        status: JobStatus::parse(&status),
+        submitted_at: timestamp(4)?.unwrap_or_default(),
+        started_at: timestamp(5)?,
+        finished_at: timestamp(6)?,
+        message: row.get(7)?,
+    })
+}
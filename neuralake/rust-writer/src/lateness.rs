@@ -0,0 +1,60 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use deltalake::protocol::SaveMode;
+use deltalake::{DeltaOps, StorageOptions};
+use polars::prelude::*;
+use crate::config::LatenessConfig;
+
+/// Boolean column marking late rows under [`crate::config::LatePolicy::Flag`]
+pub const LATE_COLUMN: &str = "_late";
+
+/// Event times before this are late
+pub fn watermark(config: &LatenessConfig) -> DateTime<Utc> {
+    Utc::now() - config.horizon()
+}
+
+/// Which rows of `df` have an event time before `watermark`. Rows without an
+/// event time are never late.
+pub fn late_mask(df: &DataFrame, column: &str, watermark: DateTime<Utc>) -> Result<BooleanChunked> {
+    df.column(column)
+        .with_context(|| format!("Batch has no event-time column {}", column))?;
+    let mask = df
+        .clone()
+        .lazy()
+        .select([col(column)
+            .dt()
+            .timestamp(TimeUnit::Milliseconds)
+            .lt(lit(watermark.timestamp_millis()))
+            .fill_null(lit(false))
+            .alias(LATE_COLUMN)])
+        .collect()
+        .with_context(|| format!("Failed to compare {} with the watermark", column))?;
+    Ok(mask.column(LATE_COLUMN)?.bool()?.clone())
+}
+
+/// Split `df` into its on-time and late rows
+pub fn split_late(df: &DataFrame, mask: &BooleanChunked) -> Result<(DataFrame, DataFrame)> {
+    let on_time = df.filter(&!mask).context("Failed to select on-time rows")?;
+    let late = df.filter(mask).context("Failed to select late rows")?;
+    Ok((on_time, late))
+}
+
+/// `df` with a [`LATE_COLUMN`] holding `mask`
+pub fn flag_late(mut df: DataFrame, mask: BooleanChunked) -> Result<DataFrame> {
+    df.with_column(mask.into_series().with_name(LATE_COLUMN.into()))
+        .context("Failed to add late flag column")?;
+    Ok(df)
+}
+
+/// Append late rows to the late table, creating it on first use
+pub async fn append_late(df: &DataFrame, late_table_uri: &str, storage_options: &StorageOptions) -> Result<()> {
+    let batch = df.to_arrow(None)
+        .context("Failed to convert late rows to Arrow")?;
+    DeltaOps::try_from_uri_with_storage_options(late_table_uri, storage_options.0.clone())
+        .await?
+        .write(vec![batch])
+        .with_save_mode(SaveMode::Append)
+        .await
+        .with_context(|| format!("Failed to write late rows to {}", late_table_uri))?;
+    Ok(())
+}
@@ -1 +1,1267 @@
- 
\ No newline at end of file
+//! Surgical Strike writer - low-latency Delta Lake ingestion built on the
+//! three-process architecture (Writer, Compaction, Vacuum).
+
+#[cfg(feature = "grpc")]
+pub mod admin;
+pub mod alerts;
+pub mod archive;
+pub mod auth;
+pub mod checkpoint;
+pub mod checksums;
+pub mod client;
+pub mod coalescer;
+pub mod commit_feed;
+pub mod compaction;
+pub mod compat;
+pub mod config;
+pub mod convert;
+pub mod correlation;
+pub mod dead_letter;
+pub mod delete;
+pub mod describe;
+pub mod diff;
+pub mod drift;
+pub mod enrichment;
+pub mod events;
+pub mod export;
+#[cfg(feature = "flight")]
+pub mod flight;
+pub mod flush_scheduler;
+pub mod guardrails;
+pub mod history;
+pub mod import;
+pub mod inventory;
+pub mod jobs;
+pub mod lateness;
+pub mod locking;
+pub mod maintenance;
+pub mod manifest;
+pub mod merge;
+pub mod metrics;
+pub mod multi_table;
+pub mod partition_gc;
+pub mod partition_metrics;
+pub mod plan;
+pub mod quality;
+pub mod query;
+pub mod register;
+pub mod replication;
+pub mod retention;
+pub mod retry;
+pub mod restore;
+pub mod rollback;
+pub mod rollup;
+pub mod schedule;
+pub mod schema;
+pub mod server;
+pub mod sequence;
+pub mod session;
+pub mod sinks;
+pub mod sources;
+pub mod spark_history;
+pub mod state;
+pub mod stats;
+pub mod storage;
+#[cfg(feature = "otlp")]
+pub mod telemetry;
+pub mod transform;
+pub mod type_inference;
+pub mod validation;
+pub mod vacuum;
+pub mod write_pool;
+pub mod writer;
+
+pub use alerts::Alerter;
+pub use archive::{ArchiveProcess, ArchiveReport};
+pub use checkpoint::{CheckpointProcess, CheckpointReport};
+pub use checksums::VerifyReport;
+pub use client::{ControlClient, IngestAck, IngestClient};
+pub use commit_feed::{CommitFeed, CommitNotification};
+pub use compaction::{CompactionMetrics, CompactionProcess};
+pub use compat::CompatReport;
+pub use config::*;
+pub use convert::ConversionReport;
+pub use dead_letter::{DeadLetterEntry, DeadLetterQueue};
+pub use events::{EventBus, OrchestratorEvent};
+pub use flush_scheduler::FlushScheduler;
+pub use delete::DeleteReport;
+pub use describe::TableDescription;
+pub use diff::TableDiff;
+pub use drift::DriftReport;
+pub use export::{AsOf, ExportFormat, ExportReport};
+pub use history::HistoryEntry;
+pub use import::ImportReport;
+pub use jobs::{Job, JobKind, JobQueue, JobStatus};
+pub use maintenance::{Maintenance, MaintenanceControl, MaintenancePauses};
+pub use metrics::MetricsRegistry;
+pub use multi_table::MultiTableOrchestrator;
+pub use partition_gc::PartitionCleanup;
+pub use plan::StartPlan;
+pub use quality::{QualityProcess, QualityStatus};
+pub use query::QueryResult;
+pub use register::RegisterReport;
+pub use replication::Replicator;
+pub use restore::{RestorePlan, RestoreTarget};
+pub use rollback::{RollbackPlan, RollbackStrategy};
+pub use session::WriteSession;
+pub use state::{EntryStatus, StateDir, StateEntry};
+pub use stats::{ColumnProfile, RestatReport, StatsCoverage, TableProfile};
+pub use storage::{resolve_storage_options, StorageBackend};
+pub use type_inference::TypeAnalysis;
+pub use vacuum::{VacuumMetrics, VacuumProcess, VacuumReport};
+pub use writer::{BatchSender, QueuedBatch, WriterControl, WriterMetrics, WriterProcess};
+
+use anyhow::{anyhow, bail, Context, Result};
+use deltalake::{DeltaTable, DeltaTableBuilder, DeltaTableError};
+use polars::prelude::DataFrame;
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex};
+use tokio_util::sync::CancellationToken;
+
+/// Owns the shared table handle and runs the Writer, Compaction and Vacuum processes
+pub struct SurgicalStrikeOrchestrator {
+    config: SurgicalStrikeConfig,
+    table: Arc<Mutex<DeltaTable>>,
+    writer: WriterProcess,
+    compaction: CompactionProcess,
+    vacuum: VacuumProcess,
+    checkpoint: CheckpointProcess,
+    archive: Option<ArchiveProcess>,
+    /// Recovers commit entries left behind by crashed writers, when locking is enabled
+    lock_monitor: Option<locking::LockMonitor>,
+    /// Standby replication of the writer queue, when configured
+    replication: Option<Arc<Replicator>>,
+    /// Counters shared by the writer, compaction and vacuum processes
+    metrics: Arc<MetricsRegistry>,
+    /// Pause switches of compaction and vacuum
+    maintenance: Arc<MaintenanceControl>,
+    /// Lifecycle events of the writer, compaction and vacuum processes
+    events: EventBus,
+    /// Local state directory for processes that persist state between runs
+    state: Arc<StateDir>,
+    batches: BatchSender,
+    /// Receiving half of the writer queue, handed to the writer on `start`
+    batch_receiver: Mutex<Option<mpsc::Receiver<QueuedBatch>>>,
+    jobs: Option<Arc<JobQueue>>,
+    /// Sink processes, handed off on `start`
+    sink_processes: Mutex<Vec<sinks::SinkProcess>>,
+    sink_status: Vec<sinks::SinkStatusHandle>,
+    /// Publishes new commits when the commit feed is enabled
+    commit_feed: Option<CommitFeed>,
+    /// Post-commit data tests, when enabled
+    quality: Option<QualityProcess>,
+    /// Live status of the configured ingestion source, if any
+    source_status: Option<sources::SourceStatusHandle>,
+    /// Cancelled once to stop every process; each drains its own work first
+    shutdown: CancellationToken,
+}
+
+impl SurgicalStrikeOrchestrator {
+    /// Open the configured table and build the three processes
+    pub async fn new(mut config: SurgicalStrikeConfig) -> Result<Self> {
+        storage::register_handlers();
+        let sources = [
+            config.kafka.is_some(),
+            config.pulsar.is_some(),
+            config.kinesis.is_some(),
+            config.sqs.is_some(),
+            config.postgres_cdc.is_some(),
+        ];
+        if sources.into_iter().filter(|configured| *configured).count() > 1 {
+            bail!(
+                "Configure at most one of a Kafka, Pulsar, Kinesis, SQS or PostgreSQL CDC source for {}",
+                config.table_uri
+            );
+        }
+
+        if config.locking.enabled && StorageBackend::from_uri(&config.table_uri) == StorageBackend::Local {
+            // Typically a shared config meant for S3 tables run against a local directory
+            log::warn!("Ignoring DynamoDB commit locking for local table {}", config.table_uri);
+            config.locking.enabled = false;
+        }
+        if config.locking.enabled {
+            // Only S3 lacks atomic put-if-absent; GCS and local stores need no lock
+            if StorageBackend::from_uri(&config.table_uri) != StorageBackend::S3 {
+                bail!("DynamoDB commit locking only applies to s3:// tables, not {}", config.table_uri);
+            }
+            if config.locking.create_table && !config.read_only {
+                locking::ensure_lock_table(&config.locking, &config.storage_options.0).await?;
+            }
+            config.locking.apply_to(&mut config.storage_options.0);
+            log::info!("DynamoDB commit locking enabled via table {}", config.locking.table_name);
+        }
+        if let Some(azure) = &config.azure {
+            azure.apply_to(&mut config.storage_options.0);
+        }
+
+        let loaded = DeltaTableBuilder::from_uri(&config.table_uri)
+            .with_storage_options(config.storage_options.0.clone())
+            .load()
+            .await;
+        let table = match (loaded, &config.schema) {
+            (Ok(table), _) => table,
+            (Err(DeltaTableError::NotATable(_)), Some(schema)) if !config.read_only => {
+                log::info!("Creating table {} from the declared schema", config.table_uri);
+                schema::create_table(&config.table_uri, &config.storage_options, schema).await?
+            }
+            (Err(e), _) => {
+                return Err(e).with_context(|| format!("Failed to open Delta table at {}", config.table_uri))
+            }
+        };
+        let table = if config.read_only {
+            log::warn!("Opening {} read-only; nothing will be written to it", config.table_uri);
+            table
+        } else {
+            // Catch a config written for a different table layout before writing to it
+            let table = drift::enforce(table, &config.expectations).await?;
+            let table = stats::ensure_stats_columns(table, &config.stats_columns).await?;
+            let table = match config.time_travel_days {
+                Some(days) => retention::ensure_time_travel(table, days).await?,
+                None => table,
+            };
+            match config.writer.idempotency_key_retention_days {
+                Some(days) => retention::ensure_transaction_retention(table, days).await?,
+                None => table,
+            }
+        };
+
+        let replication = match config.replication.role {
+            ReplicationRole::None => None,
+            _ => Some(Arc::new(Replicator::new(config.replication.clone(), &table)?)),
+        };
+
+        let metrics = Arc::new(MetricsRegistry::default());
+        let (sender, batch_receiver) = mpsc::channel(config.writer.queue_capacity);
+        let batches = BatchSender::new(sender, replication.clone(), metrics.clone());
+
+        let jobs = if config.jobs.enabled {
+            Some(Arc::new(JobQueue::open(&config.jobs.db_path)?))
+        } else {
+            None
+        };
+
+        let (sink_processes, sink_senders): (Vec<_>, Vec<_>) =
+            config.sinks.iter().cloned().map(sinks::SinkProcess::new).unzip();
+        let sink_status = sink_processes.iter().map(|p| p.status()).collect();
+
+        let alerts = Arc::new(Alerter::new(config.alerts.clone(), config.table_name()));
+        let events = EventBus::default();
+        let mut writer = WriterProcess::new(config.writer.clone())
+            .with_sinks(sink_senders)
+            .with_metrics(metrics.clone())
+            .with_events(events.clone())
+            .with_alerts(alerts.clone())
+            .with_checksums(config.checksums.clone());
+        if let Some(replication) = replication.as_ref().filter(|r| r.role() == ReplicationRole::Primary) {
+            writer = writer.with_replication(replication.clone());
+        }
+        if let Some(uri) = config.dead_letter_uri() {
+            if config.writer.dead_letter_uri.is_none() {
+                log::info!("No dead_letter_uri set; acknowledged batches that cannot be written go to {}", uri);
+            }
+            writer = writer.with_dead_letter(Arc::new(DeadLetterQueue::open(&uri, &config.storage_options)?));
+        }
+
+        let quality = config
+            .quality
+            .enabled
+            .then(|| QualityProcess::new(config.quality.clone()).with_alerts(alerts.clone()));
+        let mut commit_feed = config
+            .commit_feed
+            .enabled
+            .then(|| CommitFeed::new(config.commit_feed.clone()));
+        if let Some(quality) = quality.as_ref().filter(|_| config.quality.quarantine) {
+            commit_feed = commit_feed.map(|feed| feed.with_watermark(quality.status()));
+        }
+
+        let maintenance = Arc::new(MaintenanceControl::default());
+        Ok(Self {
+            writer,
+            replication,
+            compaction: CompactionProcess::new(config.compaction.clone())
+                .with_metrics(metrics.clone())
+                .with_checksums(config.checksums.clone())
+                .with_control(maintenance.clone())
+                .with_events(events.clone()),
+            vacuum: VacuumProcess::new(config.vacuum.clone())
+                .with_metrics(metrics.clone())
+                .with_alerts(alerts)
+                .with_control(maintenance.clone())
+                .with_events(events.clone()),
+            checkpoint: CheckpointProcess::new(config.checkpoint.clone()),
+            metrics,
+            maintenance,
+            events,
+            state: Arc::new(StateDir::from_config(&config.state)),
+            lock_monitor: config.locking.enabled.then(|| {
+                locking::LockMonitor::new(config.locking.clone(), config.storage_options.0.clone())
+            }),
+            archive: config
+                .archive
+                .clone()
+                .map(|archive| ArchiveProcess::new(archive, &config.storage_options)),
+            table: Arc::new(Mutex::new(table)),
+            batches,
+            batch_receiver: Mutex::new(Some(batch_receiver)),
+            jobs,
+            sink_processes: Mutex::new(sink_processes),
+            commit_feed,
+            quality,
+            sink_status,
+            source_status: sources.contains(&true).then(Default::default),
+            shutdown: CancellationToken::new(),
+            config,
+        })
+    }
+
+    /// Run all three processes until shutdown
+    pub async fn start(&self) -> Result<()> {
+        log::info!("Starting Surgical Strike orchestrator for {}", self.config.table_uri);
+
+        let batch_receiver = self
+            .batch_receiver
+            .lock()
+            .await
+            .take()
+            .ok_or_else(|| anyhow!("Orchestrator has already been started"))?;
+
+        // Translate Ctrl-C into a cooperative shutdown of every process
+        let shutdown = self.shutdown.clone();
+        tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                log::info!("Shutdown requested, draining in-flight work");
+                shutdown.cancel();
+            }
+        });
+
+        if self.config.read_only {
+            log::info!("{} is read-only: serving observability only", self.config.table_uri);
+            tokio::try_join!(
+                self.run_commit_feed(),
+                self.run_quality(),
+                self.serve_http(),
+                self.serve_flight(),
+                self.serve_grpc(),
+            )?;
+            return Ok(());
+        }
+
+        tokio::try_join!(
+            self.writer.run(
+                self.table.clone(),
+                self.config.storage_options.clone(),
+                batch_receiver,
+                self.shutdown.clone(),
+            ),
+            self.compaction.run(self.table.clone(), self.shutdown.clone()),
+            self.vacuum.run(self.table.clone(), self.shutdown.clone()),
+            self.checkpoint.run(self.table.clone(), self.shutdown.clone()),
+            self.run_archive(),
+            self.run_lock_monitor(),
+            self.run_replication(),
+            self.run_sinks(),
+            self.run_commit_feed(),
+            self.run_quality(),
+            self.serve_http(),
+            self.serve_flight(),
+            self.serve_grpc(),
+            self.run_sources(),
+            self.run_jobs(),
+        )?;
+
+        Ok(())
+    }
+
+    /// Run the archive process if archival is configured
+    async fn run_archive(&self) -> Result<()> {
+        match &self.archive {
+            Some(archive) => archive.run(self.table.clone(), self.shutdown.clone()).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Watch the commit lock table if DynamoDB locking is enabled
+    async fn run_lock_monitor(&self) -> Result<()> {
+        match &self.lock_monitor {
+            Some(monitor) => monitor.run(self.table.clone(), self.shutdown.clone()).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Stream the writer queue to the standby, or follow the primary
+    async fn run_replication(&self) -> Result<()> {
+        let Some(replication) = &self.replication else {
+            return Ok(());
+        };
+        match replication.role() {
+            ReplicationRole::Primary => replication.run_primary(self.shutdown.clone()).await,
+            ReplicationRole::Standby => {
+                replication
+                    .run_standby(
+                        &self.writer,
+                        self.table.clone(),
+                        &self.config.storage_options,
+                        self.shutdown.clone(),
+                    )
+                    .await
+            }
+            ReplicationRole::None => Ok(()),
+        }
+    }
+
+    /// Deliver committed batches to the configured secondary sinks
+    async fn run_sinks(&self) -> Result<()> {
+        let processes = std::mem::take(&mut *self.sink_processes.lock().await);
+        futures::future::try_join_all(
+            processes
+                .into_iter()
+                .map(|p| p.run(self.config.table_uri.clone(), self.shutdown.clone())),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Follow the log and publish new commits if the feed is enabled
+    async fn run_commit_feed(&self) -> Result<()> {
+        match &self.commit_feed {
+            Some(feed) => {
+                feed.run(&self.config.table_uri, &self.config.storage_options, self.shutdown.clone())
+                    .await
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Run the data tests against new versions if they are enabled
+    async fn run_quality(&self) -> Result<()> {
+        match &self.quality {
+            Some(quality) => {
+                quality
+                    .run(&self.config.table_uri, &self.config.storage_options, self.shutdown.clone())
+                    .await
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Serve the HTTP ingestion API if it is enabled
+    async fn serve_http(&self) -> Result<()> {
+        if !self.config.http.enabled {
+            return Ok(());
+        }
+
+        let state = server::ApiState {
+            tables: Arc::new([(self.config.table_name().to_string(), self.api_endpoint())].into()),
+            process_metrics: Default::default(),
+            shutdown: self.shutdown.clone(),
+        };
+        server::serve(state, self.config.http.clone(), self.shutdown.clone()).await
+    }
+
+    /// Serve Flight SQL reads of the table if enabled
+    #[cfg(feature = "flight")]
+    async fn serve_flight(&self) -> Result<()> {
+        if !self.config.flight.enabled {
+            return Ok(());
+        }
+        let tables = [(self.config.table_name().to_string(), self.table.clone())].into();
+        flight::serve(flight::FlightSqlServer::new(tables), self.config.flight.clone(), self.shutdown.clone()).await
+    }
+
+    #[cfg(not(feature = "flight"))]
+    async fn serve_flight(&self) -> Result<()> {
+        if self.config.flight.enabled {
+            bail!("Flight SQL endpoint enabled but the `flight` feature is not enabled");
+        }
+        Ok(())
+    }
+
+    /// Serve the gRPC admin API for the table if enabled
+    #[cfg(feature = "grpc")]
+    async fn serve_grpc(&self) -> Result<()> {
+        if !self.config.grpc.enabled {
+            return Ok(());
+        }
+        let tables = [(self.config.table_name().to_string(), self.admin_table())].into();
+        admin::serve(admin::AdminService::new(tables), self.config.grpc.clone(), self.shutdown.clone()).await
+    }
+
+    #[cfg(not(feature = "grpc"))]
+    async fn serve_grpc(&self) -> Result<()> {
+        if self.config.grpc.enabled {
+            bail!("gRPC admin API enabled but the `grpc` feature is not enabled");
+        }
+        Ok(())
+    }
+
+    /// Run the configured ingestion sources
+    async fn run_sources(&self) -> Result<()> {
+        #[cfg(feature = "kafka")]
+        if let Some(kafka) = &self.config.kafka {
+            let status = self.source_status.clone().unwrap_or_default();
+            let source = sources::kafka::KafkaSource::new(kafka.clone(), status)?
+                .with_backpressure(self.batches.clone());
+            return sources::run_source(
+                source,
+                &self.writer,
+                self.table.clone(),
+                self.config.storage_options.clone(),
+                self.shutdown.clone(),
+                self.config.sequence.clone(),
+            )
+            .await;
+        }
+
+        #[cfg(not(feature = "kafka"))]
+        if self.config.kafka.is_some() {
+            anyhow::bail!("Kafka source configured but the `kafka` feature is not enabled");
+        }
+
+        #[cfg(feature = "pulsar")]
+        if let Some(pulsar) = &self.config.pulsar {
+            let status = self.source_status.clone().unwrap_or_default();
+            let source = sources::pulsar::PulsarSource::new(pulsar.clone(), status).await?;
+            return sources::run_source(
+                source,
+                &self.writer,
+                self.table.clone(),
+                self.config.storage_options.clone(),
+                self.shutdown.clone(),
+                self.config.sequence.clone(),
+            )
+            .await;
+        }
+
+        #[cfg(not(feature = "pulsar"))]
+        if self.config.pulsar.is_some() {
+            anyhow::bail!("Pulsar source configured but the `pulsar` feature is not enabled");
+        }
+
+        #[cfg(feature = "kinesis")]
+        if let Some(kinesis) = &self.config.kinesis {
+            let status = self.source_status.clone().unwrap_or_default();
+            let source = sources::kinesis::KinesisSource::new(kinesis.clone(), status).await?;
+            return sources::run_source(
+                source,
+                &self.writer,
+                self.table.clone(),
+                self.config.storage_options.clone(),
+                self.shutdown.clone(),
+                self.config.sequence.clone(),
+            )
+            .await;
+        }
+
+        #[cfg(not(feature = "kinesis"))]
+        if self.config.kinesis.is_some() {
+            anyhow::bail!("Kinesis source configured but the `kinesis` feature is not enabled");
+        }
+
+        #[cfg(feature = "sqs")]
+        if let Some(sqs) = &self.config.sqs {
+            let status = self.source_status.clone().unwrap_or_default();
+            let source =
+                sources::sqs::SqsSource::new(sqs.clone(), self.config.storage_options.clone(), status).await?;
+            return sources::run_source(
+                source,
+                &self.writer,
+                self.table.clone(),
+                self.config.storage_options.clone(),
+                self.shutdown.clone(),
+                self.config.sequence.clone(),
+            )
+            .await;
+        }
+
+        #[cfg(not(feature = "sqs"))]
+        if self.config.sqs.is_some() {
+            anyhow::bail!("SQS source configured but the `sqs` feature is not enabled");
+        }
+
+        if let Some(postgres_cdc) = &self.config.postgres_cdc {
+            let status = self.source_status.clone().unwrap_or_default();
+            let source = sources::postgres_cdc::PostgresCdcSource::connect(postgres_cdc.clone(), status).await?;
+            return sources::run_source(
+                source,
+                &self.writer,
+                self.table.clone(),
+                self.config.storage_options.clone(),
+                self.shutdown.clone(),
+                self.config.sequence.clone(),
+            )
+            .await;
+        }
+
+        Ok(())
+    }
+
+    /// Execute queued maintenance jobs one at a time. Jobs take the same
+    /// table lock as the scheduled processes, so they never run concurrently
+    /// with compaction or vacuum. A queue that cannot be read is retried at
+    /// the next poll; a job still running at shutdown is abandoned and
+    /// requeued on the next start.
+    async fn run_jobs(&self) -> Result<()> {
+        let Some(jobs) = &self.jobs else {
+            return Ok(());
+        };
+
+        let table_uri = self.config.table_uri.clone();
+        match jobs.blocking(move |jobs| jobs.requeue_interrupted(&table_uri)).await {
+            Ok(0) => {}
+            Ok(requeued) => log::warn!("Requeued {} jobs interrupted by a previous shutdown", requeued),
+            Err(e) => log::error!("Failed to requeue interrupted jobs: {:#}", e),
+        }
+
+        let mut poll = tokio::time::interval(self.config.jobs.poll_interval());
+
+        loop {
+            tokio::select! {
+                _ = poll.tick() => {}
+                _ = self.shutdown.cancelled() => {
+                    log::info!("Job runner received shutdown signal");
+                    break;
+                }
+            }
+
+            while !self.shutdown.is_cancelled() {
+                let table_uri = self.config.table_uri.clone();
+                let job = match jobs.blocking(move |jobs| jobs.claim_next(&table_uri)).await {
+                    Ok(Some(job)) => job,
+                    Ok(None) => break,
+                    Err(e) => {
+                        log::error!("Failed to read the job queue, retrying at the next poll: {:#}", e);
+                        break;
+                    }
+                };
+
+                log::info!("Running job {}: {:?}", job.id, job.kind);
+                let outcome = tokio::select! {
+                    outcome = self.execute_job(&job.kind) => outcome,
+                    _ = self.shutdown.cancelled() => {
+                        log::warn!("Abandoning job {} for shutdown; it runs again on the next start", job.id);
+                        return Ok(());
+                    }
+                };
+                if let Err(e) = &outcome {
+                    log::error!("Job {} failed: {:#}", job.id, e);
+                }
+                let id = job.id;
+                if let Err(e) = jobs.blocking(move |jobs| jobs.finish(id, &outcome)).await {
+                    log::error!("Failed to record the outcome of job {}: {:#}", id, e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Run a single job, returning a short summary for the queue
+    pub async fn execute_job(&self, kind: &JobKind) -> Result<String> {
+        self.ensure_writable("running jobs")?;
+        match kind {
+            JobKind::Compact => {
+                self.compact().await?;
+                Ok("Compaction completed".to_string())
+            }
+            JobKind::CompactPartitions { filters } => {
+                let parsed = filters
+                    .iter()
+                    .map(|filter| compaction::parse_partition_filter(filter))
+                    .collect::<Result<Vec<_>>>()
+                    .map_err(retry::non_retryable)?;
+                self.compact_partitions(&parsed).await?;
+                Ok(format!("Compaction of {} completed", filters.join(", ")))
+            }
+            JobKind::Export { version, format, output } => {
+                let as_of = version.map_or(AsOf::Latest, AsOf::Version);
+                let report = self.export(as_of, *format, output).await?;
+                Ok(report.to_string().trim_end().to_string())
+            }
+            JobKind::Vacuum { retention_hours, dry_run } => {
+                let mut config = self.config.vacuum.clone();
+                if let Some(hours) = retention_hours {
+                    if let Some(days) = self.config.time_travel_days.filter(|days| *hours < days * 24) {
+                        return Err(retry::non_retryable(anyhow!(
+                            "Retention of {} hours would break the {} day time travel guarantee of {}",
+                            hours,
+                            days,
+                            self.config.table_uri
+                        )));
+                    }
+                    config.retention_hours = *hours;
+                }
+                config.dry_run = *dry_run;
+
+                let mut table = self.table.lock().await;
+                let report = VacuumProcess::new(config)
+                    .with_metrics(self.metrics.clone())
+                    .run_once(&mut table)
+                    .await?;
+                Ok(format!(
+                    "{} {} files ({} bytes) and {} empty partitions",
+                    if report.dry_run { "Would delete" } else { "Deleted" },
+                    report.files.len(),
+                    report.total_bytes,
+                    report.partitions.partitions_removed.len()
+                ))
+            }
+        }
+    }
+
+    /// Share a shutdown token with other orchestrators in the same process
+    pub fn with_shutdown_token(mut self, shutdown: CancellationToken) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// Take queued flushes through slots shared with other tables
+    pub fn with_flush_scheduler(mut self, scheduler: Arc<FlushScheduler>) -> Self {
+        let table = self.config.table_name().to_string();
+        self.writer = self.writer.with_flush_scheduler(scheduler, &table);
+        self
+    }
+
+    /// How this table is exposed through the HTTP API
+    pub(crate) fn api_endpoint(&self) -> server::TableEndpoint {
+        server::TableEndpoint {
+            table_uri: self.config.table_uri.clone(),
+            batches: self.batches.clone(),
+            source_status: self.source_status.clone(),
+            sink_status: self.sink_status.clone(),
+            commit_feed: self.commit_feed.clone(),
+            quality_status: self.quality.as_ref().map(QualityProcess::status),
+            metrics: self.metrics.clone(),
+            control: self.writer.control(),
+            maintenance: self.maintenance.clone(),
+            jobs: self.jobs.clone(),
+            read_only: self.config.read_only,
+        }
+    }
+
+    /// Refuse `operation` when the table is open read-only
+    fn ensure_writable(&self, operation: &str) -> Result<()> {
+        if self.config.read_only {
+            bail!("{} is open read-only; {} is disabled", self.config.table_uri, operation);
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "flight")]
+    pub(crate) fn table_handle(&self) -> Arc<Mutex<DeltaTable>> {
+        self.table.clone()
+    }
+
+    /// How this table is exposed through the gRPC admin API
+    #[cfg(feature = "grpc")]
+    pub(crate) fn admin_table(&self) -> admin::AdminTable {
+        admin::AdminTable {
+            endpoint: self.api_endpoint(),
+            table: self.table.clone(),
+            compaction: self.compaction.clone(),
+            vacuum: self.vacuum.clone(),
+        }
+    }
+
+    /// Receive a notification for every commit made after this call, by
+    /// this or any other writer. Requires `commit_feed.enabled`.
+    pub fn subscribe(&self) -> Result<tokio::sync::broadcast::Receiver<CommitNotification>> {
+        self.commit_feed
+            .as_ref()
+            .map(CommitFeed::subscribe)
+            .ok_or_else(|| anyhow!("Commit feed is not enabled for {}", self.config.table_uri))
+    }
+
+    /// Request a graceful shutdown of a running orchestrator
+    pub fn shutdown(&self) {
+        self.shutdown.cancel();
+    }
+
+    /// Token cancelled when the orchestrator shuts down, for embedding callers
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.shutdown.clone()
+    }
+
+    /// Queue a batch for the running writer process, waiting if the queue is
+    /// full. Returns the id the batch is logged and committed under.
+    pub async fn enqueue(&self, df: DataFrame) -> Result<String> {
+        self.ensure_writable("writing")?;
+        self.batches.send(df).await
+    }
+
+    /// Version that committed a recently enqueued batch
+    pub fn batch_receipt(&self, batch_id: &str) -> Option<metrics::BatchReceipt> {
+        self.metrics.batch_receipt(batch_id)
+    }
+
+    /// Write a single batch through the writer process
+    pub async fn write_batch(&self, df: DataFrame) -> Result<()> {
+        self.ensure_writable("writing")?;
+        self.writer
+            .write_batch(df, &self.config.storage_options, &self.config.table_uri)
+            .await
+    }
+
+    /// Write a batch unless a write with the same idempotency key is already
+    /// committed, returning whether it was written. A concurrent duplicate
+    /// is still skipped at commit time, though it may report `true`.
+    pub async fn write_batch_idempotent(&self, df: DataFrame, idempotency_key: &str) -> Result<bool> {
+        self.ensure_writable("writing")?;
+        let txn = writer::idempotency_txn(idempotency_key)?;
+        {
+            let mut table = self.table.lock().await;
+            table.update().await
+                .context("Failed to refresh table before idempotent write")?;
+            if writer::txns_already_committed(&table, &[txn]) {
+                return Ok(false);
+            }
+        }
+        self.writer
+            .write_batch_idempotent(df, idempotency_key, &self.config.storage_options, &self.config.table_uri)
+            .await?;
+        Ok(true)
+    }
+
+    /// Start a session whose appends are published together as one commit
+    pub async fn begin(&self) -> Result<WriteSession> {
+        self.ensure_writable("writing")?;
+        let table = self.table.lock().await.clone();
+        WriteSession::begin(self.writer.clone(), table).await
+    }
+
+    /// Entries in the configured dead letter queue, oldest first
+    pub async fn dead_letters(&self) -> Result<Vec<DeadLetterEntry>> {
+        self.dead_letter_queue()?.list().await
+    }
+
+    /// Write dead-lettered batches to the table (all of them, or only the
+    /// entry `id`) and remove them from the queue, returning the replayed ids.
+    /// Each replay commits an application transaction for its entry, so
+    /// running it again after an interruption does not duplicate rows.
+    pub async fn replay_dead_letters(&self, id: Option<&str>) -> Result<Vec<String>> {
+        self.ensure_writable("replaying dead letters")?;
+        let queue = self.dead_letter_queue()?;
+        let mut replayed = Vec::new();
+        for entry in queue.list().await? {
+            if id.is_some_and(|id| id != entry.id) {
+                continue;
+            }
+            let df = queue.read(&entry.id).await?;
+            self.writer
+                .write_batches(
+                    df,
+                    &entry.batch_ids,
+                    &[entry.replay_txn()],
+                    Vec::new(),
+                    &self.config.storage_options,
+                    &self.config.table_uri,
+                )
+                .await
+                .with_context(|| format!("Failed to replay dead letter entry {}", entry.id))?;
+            queue.remove(&entry.id).await?;
+            log::info!("Replayed dead letter entry {} ({} rows)", entry.id, entry.rows);
+            replayed.push(entry.id);
+        }
+        if let Some(id) = id.filter(|_| replayed.is_empty()) {
+            return Err(anyhow!("No dead letter entry {} in {}", id, queue.uri()));
+        }
+        Ok(replayed)
+    }
+
+    fn dead_letter_queue(&self) -> Result<DeadLetterQueue> {
+        let uri = self
+            .config
+            .dead_letter_uri()
+            .ok_or_else(|| anyhow!("No dead letter queue configured for {}", self.config.table_uri))?;
+        DeadLetterQueue::open(&uri, &self.config.storage_options)
+    }
+
+    /// Run one compaction pass on the table
+    pub async fn compact(&self) -> Result<()> {
+        self.compact_partitions(&[]).await
+    }
+
+    /// Run one compaction pass on the partitions matching `filters`
+    pub async fn compact_partitions(&self, filters: &[deltalake::PartitionFilter]) -> Result<()> {
+        self.ensure_writable("compaction")?;
+        let mut table = self.table.lock().await;
+        self.compaction.run_once_with_filters(&mut table, filters).await
+    }
+
+    /// Run one vacuum pass on the table
+    pub async fn vacuum(&self) -> Result<VacuumReport> {
+        self.ensure_writable("vacuum")?;
+        let mut table = self.table.lock().await;
+        self.vacuum.run_once(&mut table).await
+    }
+
+    /// Write a checkpoint at the latest version unless one already exists there
+    pub async fn checkpoint(&self) -> Result<CheckpointReport> {
+        self.ensure_writable("checkpointing")?;
+        let mut table = self.table.lock().await;
+        self.checkpoint.run_once(&mut table, true).await
+    }
+
+    /// Rebuild the file manifest over `columns`, or the configured
+    /// `manifest_columns` when empty
+    pub async fn refresh_manifest(&self, columns: &[String]) -> Result<manifest::Manifest> {
+        let columns = if columns.is_empty() { &self.config.compaction.manifest_columns } else { columns };
+        if columns.is_empty() {
+            return Err(anyhow!("No manifest columns given or configured for {}", self.config.table_uri));
+        }
+        self.ensure_writable("writing the manifest")?;
+        let mut table = self.table.lock().await;
+        table.update().await
+            .context("Failed to refresh table before writing manifest")?;
+        manifest::write_manifest(&table, columns).await
+    }
+
+    /// Active data files that may hold rows matching every predicate,
+    /// pruned with the file manifest when one exists
+    pub async fn prune_files(&self, predicates: &[manifest::RangePredicate]) -> Result<Vec<String>> {
+        let mut table = self.table.lock().await;
+        table.update().await
+            .context("Failed to refresh table before pruning")?;
+        manifest::prune_files(&table, predicates).await
+    }
+
+    /// Run one archive pass on the table
+    pub async fn archive(&self) -> Result<ArchiveReport> {
+        self.ensure_writable("archival")?;
+        let archive = self
+            .archive
+            .as_ref()
+            .ok_or_else(|| anyhow!("Archival is not configured for {}", self.config.table_uri))?;
+        let mut table = self.table.lock().await;
+        archive.run_once(&mut table).await
+    }
+
+    /// Drive a source into the table outside `start`, until it is exhausted
+    /// or the orchestrator is shut down
+    pub async fn ingest<S: sources::Source>(&self, source: S) -> Result<()> {
+        self.ensure_writable("ingestion")?;
+        sources::run_source(
+            source,
+            &self.writer,
+            self.table.clone(),
+            self.config.storage_options.clone(),
+            self.shutdown.clone(),
+            self.config.sequence.clone(),
+        )
+        .await
+    }
+
+    /// Append the Parquet files matching `source` in batches of `batch_rows`,
+    /// resuming after the batches an interrupted run already committed
+    pub async fn import_parquet(
+        &self,
+        source: &str,
+        batch_rows: usize,
+        progress: impl FnMut(&ImportReport),
+    ) -> Result<ImportReport> {
+        self.ensure_writable("importing")?;
+        let (resume_after, since) = {
+            let mut table = self.table.lock().await;
+            table.update().await
+                .context("Failed to refresh table before import")?;
+            (import::committed_rows(&table, source), table.version())
+        };
+        if resume_after > 0 {
+            log::info!("Resuming import of {} after {} committed rows", source, resume_after);
+        }
+
+        let mut report = import::import_parquet(
+            &self.writer,
+            &self.config.table_uri,
+            &self.config.storage_options,
+            source,
+            batch_rows,
+            resume_after,
+            progress,
+        )
+        .await?;
+        if report.batches_committed > 0 {
+            let mut table = self.table.lock().await;
+            report.partitions_compacted = self.optimize_after_import(&mut table, since).await?;
+        }
+        Ok(report)
+    }
+
+    /// With `optimize_after_import`, compact the partitions that received
+    /// files after version `since` and, with `checkpoint_after_import`,
+    /// checkpoint the result
+    async fn optimize_after_import(&self, table: &mut DeltaTable, since: i64) -> Result<Vec<String>> {
+        if !self.config.compaction.optimize_after_import {
+            return Ok(Vec::new());
+        }
+        let compacted = self.compaction.compact_touched(table, since).await?;
+        log::info!("Compacted {} partitions touched by the import", compacted.len());
+        if self.config.compaction.checkpoint_after_import {
+            self.checkpoint.run_once(table, true).await?;
+        }
+        Ok(compacted)
+    }
+
+    /// Delete all rows matching the given keys in a single commit
+    pub async fn delete_keys(&self, keys: &DataFrame, key_columns: &[String]) -> Result<DeleteReport> {
+        self.ensure_writable("deleting")?;
+        let mut table = self.table.lock().await;
+        table.update().await
+            .context("Failed to refresh table before delete")?;
+
+        let (updated, report) = delete::delete_by_keys(table.clone(), keys, key_columns).await?;
+        *table = updated;
+
+        Ok(report)
+    }
+
+    /// Partitions with the most rows written within `window`, from the Delta log
+    pub async fn hot_partitions(
+        &self,
+        window: std::time::Duration,
+        top: usize,
+    ) -> Result<Vec<partition_metrics::PartitionWrites>> {
+        let mut table = self.table.lock().await;
+        table.update().await
+            .context("Failed to refresh table before scanning partitions")?;
+        let tracker = partition_metrics::recent_partition_writes(&table, window).await?;
+        Ok(tracker.top(top, chrono::Utc::now()))
+    }
+
+    /// Schema, properties, protocol and size of the table
+    pub async fn describe(&self) -> Result<TableDescription> {
+        let mut table = self.table.lock().await;
+        table.update().await
+            .context("Failed to refresh table before describe")?;
+        describe::describe_table(&table)
+    }
+
+    /// Add existing Parquet files below the table root to the table in
+    /// place, without rewriting them
+    pub async fn register(&self, source: &str) -> Result<RegisterReport> {
+        self.ensure_writable("registering files")?;
+        let mut table = self.table.lock().await;
+        table.update().await
+            .context("Failed to refresh table before register")?;
+        let since = table.version();
+        let mut report = register::register_files(&mut table, &self.config.table_uri, source).await?;
+        if report.version.is_some() {
+            report.partitions_compacted = self.optimize_after_import(&mut table, since).await?;
+        }
+        Ok(report)
+    }
+
+    /// Statistics coverage of the active files at the latest version
+    pub async fn stats(&self) -> Result<StatsCoverage> {
+        let mut table = self.table.lock().await;
+        table.update().await
+            .context("Failed to refresh table before stats")?;
+        stats::stats_coverage(&table)
+    }
+
+    /// Column statistics aggregated from the file statistics of the latest version
+    pub async fn profile(&self) -> Result<TableProfile> {
+        let mut table = self.table.lock().await;
+        table.update().await
+            .context("Failed to refresh table before profiling")?;
+        stats::table_profile(&table)
+    }
+
+    /// Re-commit statistics of files missing them on a stats column,
+    /// computed from their Parquet footers
+    pub async fn restat(&self, dry_run: bool) -> Result<RestatReport> {
+        if !dry_run {
+            self.ensure_writable("re-computing statistics")?;
+        }
+        let mut table = self.table.lock().await;
+        table.update().await
+            .context("Failed to refresh table before restat")?;
+        stats::restat(&mut table, dry_run).await
+    }
+
+    /// Check the stored data files of the latest version, optionally
+    /// against their checksum manifests
+    pub async fn verify(&self, checksums: bool) -> Result<VerifyReport> {
+        let mut table = self.table.lock().await;
+        table.update().await
+            .context("Failed to refresh table before verify")?;
+        let inventory = self.vacuum.inventory().get(&table).await?;
+        checksums::verify(&table, &inventory, checksums).await
+    }
+
+    /// Commit history of the table, most recent first
+    pub async fn history(&self, limit: Option<usize>) -> Result<Vec<HistoryEntry>> {
+        let mut table = self.table.lock().await;
+        table.update().await
+            .context("Failed to refresh table before reading history")?;
+        history::table_history(&table, limit).await
+    }
+
+    /// Summarize what changed between two versions, optionally sampling
+    /// changed rows from the change data feed
+    pub async fn diff(&self, from_version: i64, to_version: i64, sample_rows: Option<usize>) -> Result<TableDiff> {
+        let mut table = self.table.lock().await;
+        table.update().await
+            .context("Failed to refresh table before diff")?;
+        diff::diff_versions(&table, from_version, to_version, sample_rows).await
+    }
+
+    /// Plan how to undo versions `from_version..=to_version`, applying the
+    /// plan unless `dry_run` is set
+    pub async fn rollback(&self, from_version: i64, to_version: i64, dry_run: bool) -> Result<RollbackPlan> {
+        if !dry_run {
+            self.ensure_writable("rollback")?;
+        }
+        let mut table = self.table.lock().await;
+        table.update().await
+            .context("Failed to refresh table before rollback")?;
+
+        let plan = rollback::plan_rollback(&table, from_version, to_version).await?;
+        if !dry_run {
+            *table = rollback::apply_rollback(table.clone(), &plan).await?;
+            log::info!("Rolled back versions {}..={} at version {}", from_version, to_version, table.version());
+        }
+
+        Ok(plan)
+    }
+
+    /// Plan a RESTORE of the whole table to an earlier version, applying it
+    /// unless `dry_run` is set
+    pub async fn restore(&self, target: RestoreTarget, dry_run: bool) -> Result<RestorePlan> {
+        if !dry_run {
+            self.ensure_writable("restore")?;
+        }
+        let mut table = self.table.lock().await;
+        table.update().await
+            .context("Failed to refresh table before restore")?;
+
+        let plan = restore::plan_restore(&table, target).await?;
+        if !dry_run {
+            *table = restore::apply_restore(table.clone(), &plan).await?;
+            log::info!("Restored version {} as version {}", plan.target_version, table.version());
+        }
+
+        Ok(plan)
+    }
+
+    /// Write the table as of `as_of` to a single file at `output`
+    pub async fn export(&self, as_of: AsOf, format: ExportFormat, output: &str) -> Result<ExportReport> {
+        let table = {
+            let mut table = self.table.lock().await;
+            table.update().await
+                .context("Failed to refresh table before export")?;
+            table.clone()
+        };
+        export::export_table(&table, as_of, format, output).await
+    }
+
+    /// Run a read-only SQL query against the latest version of the table,
+    /// registered under `name`
+    pub async fn query(&self, name: &str, sql: &str) -> Result<QueryResult> {
+        let table = {
+            let mut table = self.table.lock().await;
+            table.update().await
+                .context("Failed to refresh table before query")?;
+            table.clone()
+        };
+        query::run_query(table, name, sql).await
+    }
+
+    /// Sample up to `sample_rows` recent rows and suggest tighter types for
+    /// string columns whose values all parse as one
+    pub async fn suggest_types(&self, sample_rows: usize) -> Result<TypeAnalysis> {
+        let mut table = self.table.lock().await;
+        table.update().await
+            .context("Failed to refresh table before type analysis")?;
+        type_inference::suggest_types(&table, sample_rows).await
+    }
+
+    /// Report interoperability issues with tables written by other engines
+    pub async fn compat_check(&self) -> Result<CompatReport> {
+        let mut table = self.table.lock().await;
+        table.update().await
+            .context("Failed to refresh table before compatibility check")?;
+        compat::check_table(&table)
+    }
+
+    /// Differences between the configured expectations and the table
+    pub async fn drift_report(&self) -> Result<DriftReport> {
+        let mut table = self.table.lock().await;
+        table.update().await
+            .context("Failed to refresh table before drift check")?;
+        drift::check_drift(&table, &self.config.expectations)
+    }
+
+    /// Receive commits, maintenance completions, SLA misses and given-up
+    /// flushes of this table from now on. A receiver that falls behind is
+    /// told how many events it missed.
+    pub fn subscribe_events(&self) -> tokio::sync::broadcast::Receiver<OrchestratorEvent> {
+        self.events.subscribe()
+    }
+
+    /// The local state directory configured under `[state]`
+    pub fn state(&self) -> Arc<StateDir> {
+        self.state.clone()
+    }
+
+    /// Snapshot of the ingestion source's lag and counters
+    pub fn source_status(&self) -> Option<sources::SourceStatus> {
+        self.source_status.as_ref().map(|status| status.lock().unwrap().clone())
+    }
+
+    /// Live writer throughput and latency
+    pub fn writer_metrics(&self) -> WriterMetrics {
+        self.writer.get_metrics()
+    }
+
+    pub fn compaction_metrics(&self) -> CompactionMetrics {
+        self.compaction.get_metrics()
+    }
+
+    pub fn vacuum_metrics(&self) -> VacuumMetrics {
+        self.vacuum.get_metrics()
+    }
+
+    /// Skip the scheduled cycles of `process` until [`Self::resume_maintenance`];
+    /// returns false if it was already paused
+    pub fn pause_maintenance(&self, process: Maintenance) -> bool {
+        let paused = self.maintenance.pause(process);
+        if paused {
+            log::warn!("Paused {} of {}", process, self.config.table_uri);
+        }
+        paused
+    }
+
+    /// Returns false if `process` was not paused
+    pub fn resume_maintenance(&self, process: Maintenance) -> bool {
+        let resumed = self.maintenance.resume(process);
+        if resumed {
+            log::info!("Resumed {} of {}", process, self.config.table_uri);
+        }
+        resumed
+    }
+
+    /// Which maintenance processes are paused
+    pub fn maintenance_pauses(&self) -> MaintenancePauses {
+        self.maintenance.pauses()
+    }
+
+    /// Commit lock health, when DynamoDB locking is enabled
+    pub fn lock_health(&self) -> Option<locking::LockHealth> {
+        self.lock_monitor.as_ref().map(|m| m.health())
+    }
+
+    /// Outcome of the latest data test run, when data tests are enabled
+    pub fn quality_status(&self) -> Option<QualityStatus> {
+        self.quality.as_ref().map(|q| q.status().lock().unwrap().clone())
+    }
+
+    /// Snapshot of each secondary sink's delivery lag
+    pub fn sink_status(&self) -> Vec<sinks::SinkStatus> {
+        self.sink_status.iter().map(|status| status.lock().unwrap().clone()).collect()
+    }
+
+    /// The configuration this orchestrator was built with
+    pub fn config(&self) -> &SurgicalStrikeConfig {
+        &self.config
+    }
+}
@@ -0,0 +1,355 @@
+use anyhow::{Context, Result};
+use aws_config::{BehaviorVersion, Region};
+use aws_sdk_dynamodb::config::Credentials;
+use aws_sdk_dynamodb::types::{
+    AttributeDefinition, AttributeValue, BillingMode, KeySchemaElement, KeyType,
+    ScalarAttributeType, TimeToLiveSpecification,
+};
+use aws_sdk_dynamodb::Client as DynamoClient;
+use chrono::{DateTime, Utc};
+use deltalake::{DeltaTable, ObjectStore, Path};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::interval;
+use tokio_util::sync::CancellationToken;
+use crate::config::LockingConfig;
+
+/// Storage option selecting the S3 commit coordination provider
+pub const LOCKING_PROVIDER_KEY: &str = "AWS_S3_LOCKING_PROVIDER";
+/// Storage option naming the DynamoDB table used for commit coordination
+pub const LOCK_TABLE_NAME_KEY: &str = "DELTA_DYNAMO_TABLE_NAME";
+/// Storage option overriding the DynamoDB endpoint (DynamoDB Local, LocalStack)
+pub const DYNAMODB_ENDPOINT_KEY: &str = "AWS_ENDPOINT_URL_DYNAMODB";
+/// Storage option bounding how long delta-rs retries a DynamoDB request
+pub const MAX_ELAPSED_REQUEST_TIME_KEY: &str = "DELTA_DYNAMO_MAX_ELAPSED_REQUEST_TIME";
+
+/// Attribute delta-rs stamps on completed commit entries, used as the table TTL
+const EXPIRE_TIME_ATTRIBUTE: &str = "expireTime";
+
+/// How long a repaired entry is kept before DynamoDB expires it
+const COMPLETED_ENTRY_TTL_SECS: i64 = 24 * 3600;
+
+impl LockingConfig {
+    /// Add the delta-rs DynamoDB locking options to a set of storage options
+    pub fn apply_to(&self, storage_options: &mut HashMap<String, String>) {
+        storage_options.insert(LOCKING_PROVIDER_KEY.to_string(), "dynamodb".to_string());
+        storage_options.insert(LOCK_TABLE_NAME_KEY.to_string(), self.table_name.clone());
+        if let Some(secs) = self.max_elapsed_request_secs {
+            storage_options.insert(MAX_ELAPSED_REQUEST_TIME_KEY.to_string(), secs.to_string());
+        }
+
+        if let Some(endpoint) = &self.dynamodb_endpoint {
+            storage_options.insert(DYNAMODB_ENDPOINT_KEY.to_string(), endpoint.clone());
+        }
+    }
+}
+
+/// Create the DynamoDB lock table with the key schema delta-rs expects if it
+/// does not exist yet, and enable TTL so completed commit entries expire
+pub async fn ensure_lock_table(
+    config: &LockingConfig,
+    storage_options: &HashMap<String, String>,
+) -> Result<()> {
+    let client = dynamo_client(config, storage_options).await;
+
+    let created = client
+        .create_table()
+        .table_name(&config.table_name)
+        .key_schema(
+            KeySchemaElement::builder()
+                .attribute_name("tablePath")
+                .key_type(KeyType::Hash)
+                .build()?,
+        )
+        .key_schema(
+            KeySchemaElement::builder()
+                .attribute_name("fileName")
+                .key_type(KeyType::Range)
+                .build()?,
+        )
+        .attribute_definitions(
+            AttributeDefinition::builder()
+                .attribute_name("tablePath")
+                .attribute_type(ScalarAttributeType::S)
+                .build()?,
+        )
+        .attribute_definitions(
+            AttributeDefinition::builder()
+                .attribute_name("fileName")
+                .attribute_type(ScalarAttributeType::S)
+                .build()?,
+        )
+        .billing_mode(BillingMode::PayPerRequest)
+        .send()
+        .await;
+
+    match created {
+        Ok(_) => {
+            log::info!("Created DynamoDB lock table {}", config.table_name);
+
+            client
+                .wait_until_table_exists()
+                .table_name(&config.table_name)
+                .wait(Duration::from_secs(60))
+                .await
+                .context("Timed out waiting for lock table to become active")?;
+
+            client
+                .update_time_to_live()
+                .table_name(&config.table_name)
+                .time_to_live_specification(
+                    TimeToLiveSpecification::builder()
+                        .attribute_name(EXPIRE_TIME_ATTRIBUTE)
+                        .enabled(true)
+                        .build()?,
+                )
+                .send()
+                .await
+                .context("Failed to enable TTL on lock table")?;
+        }
+        Err(e) if e.as_service_error().is_some_and(|e| e.is_resource_in_use_exception()) => {
+            log::debug!("DynamoDB lock table {} already exists", config.table_name);
+        }
+        Err(e) => {
+            return Err(e).with_context(|| {
+                format!("Failed to create DynamoDB lock table {}", config.table_name)
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Build a DynamoDB client from the same credentials and region as the table
+async fn dynamo_client(
+    config: &LockingConfig,
+    storage_options: &HashMap<String, String>,
+) -> DynamoClient {
+    // Without an explicit region the provider chain resolves one from the
+    // environment or shared profile
+    let mut loader = aws_config::defaults(BehaviorVersion::latest());
+    if let Some(region) = storage_options.get("AWS_REGION") {
+        loader = loader.region(Region::new(region.clone()));
+    }
+    if let Some(profile) = storage_options.get("AWS_PROFILE") {
+        loader = loader.profile_name(profile);
+    }
+
+    if let Some(endpoint) = &config.dynamodb_endpoint {
+        loader = loader.endpoint_url(endpoint);
+    }
+
+    if let (Some(key), Some(secret)) = (
+        storage_options.get("AWS_ACCESS_KEY_ID"),
+        storage_options.get("AWS_SECRET_ACCESS_KEY"),
+    ) {
+        loader = loader.credentials_provider(Credentials::new(
+            key,
+            secret,
+            storage_options.get("AWS_SESSION_TOKEN").cloned(),
+            None,
+            "storage_options",
+        ));
+    }
+
+    DynamoClient::new(&loader.load().await)
+}
+
+/// Counters describing commit lock health for one table
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LockHealth {
+    pub checks: u64,
+    /// Incomplete commit entries seen on the last check, stale or not
+    pub pending_entries: u64,
+    /// Entries abandoned by a crashed writer and completed by this monitor
+    pub stale_entries_recovered: u64,
+    pub recovery_failures: u64,
+    pub last_check: Option<DateTime<Utc>>,
+}
+
+/// An incomplete commit entry in the lock table
+struct PendingEntry {
+    file_name: String,
+    temp_path: String,
+}
+
+/// Periodically looks for commit entries a crashed writer left incomplete
+/// and finishes them the way delta-rs would: copy the temporary commit file
+/// to its final log path, then mark the entry complete. delta-rs only
+/// repairs an entry when the next commit or log listing runs into it, so
+/// without the monitor an idle table keeps the entry until then.
+pub struct LockMonitor {
+    config: LockingConfig,
+    storage_options: HashMap<String, String>,
+    health: Arc<std::sync::Mutex<LockHealth>>,
+}
+
+impl LockMonitor {
+    pub fn new(config: LockingConfig, storage_options: HashMap<String, String>) -> Self {
+        Self {
+            config,
+            storage_options,
+            health: Arc::new(std::sync::Mutex::new(LockHealth::default())),
+        }
+    }
+
+    /// Snapshot of the lock health counters
+    pub fn health(&self) -> LockHealth {
+        self.health.lock().unwrap().clone()
+    }
+
+    /// Main run loop for the lock monitor
+    pub async fn run(&self, table: Arc<Mutex<DeltaTable>>, shutdown: CancellationToken) -> Result<()> {
+        let client = dynamo_client(&self.config, &self.storage_options).await;
+        let (table_uri, store) = {
+            let table = table.lock().await;
+            (table.table_uri(), table.object_store())
+        };
+
+        log::info!("Starting lock monitor for {}", table_uri);
+
+        let mut interval_timer = interval(self.config.health_check_interval());
+        loop {
+            tokio::select! {
+                _ = interval_timer.tick() => {
+                    if let Err(e) = self.check_once(&client, &table_uri, store.as_ref()).await {
+                        log::error!("Lock health check failed: {:#}", e);
+                    }
+                }
+                _ = shutdown.cancelled() => {
+                    log::info!("Lock monitor received shutdown signal");
+                    break;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Inspect incomplete entries once, recovering those whose temporary file
+    /// is older than `stale_entry_secs`
+    async fn check_once(&self, client: &DynamoClient, table_uri: &str, store: &dyn ObjectStore) -> Result<()> {
+        let pending = self.pending_entries(client, table_uri).await?;
+        let stale_after = chrono::Duration::seconds(self.config.stale_entry_secs as i64);
+        let now = Utc::now();
+
+        let mut recovered = 0;
+        let mut failures = 0;
+        for entry in &pending {
+            let temp = Path::from(entry.temp_path.as_str());
+            let target = Path::from(format!("_delta_log/{}", entry.file_name));
+
+            let age = match store.head(&temp).await {
+                Ok(meta) => now - meta.last_modified,
+                // The temp file is only removed after the copy succeeded
+                Err(_) => stale_after,
+            };
+            if age < stale_after {
+                continue;
+            }
+
+            log::warn!(
+                "Commit entry {} for {} is stale (temp file age {}s), recovering",
+                entry.file_name,
+                table_uri,
+                age.num_seconds()
+            );
+            match self.recover(client, table_uri, store, entry, &temp, &target).await {
+                Ok(()) => recovered += 1,
+                Err(e) => {
+                    failures += 1;
+                    log::error!("Failed to recover commit entry {}: {:#}", entry.file_name, e);
+                }
+            }
+        }
+
+        if pending.len() > recovered {
+            log::info!(
+                "{} commit entries in flight for {}",
+                pending.len() - recovered,
+                table_uri
+            );
+        }
+
+        let mut health = self.health.lock().unwrap();
+        health.checks += 1;
+        health.pending_entries = pending.len() as u64;
+        health.stale_entries_recovered += recovered as u64;
+        health.recovery_failures += failures;
+        health.last_check = Some(now);
+
+        Ok(())
+    }
+
+    async fn pending_entries(&self, client: &DynamoClient, table_uri: &str) -> Result<Vec<PendingEntry>> {
+        let mut entries = Vec::new();
+        let mut start_key = None;
+        loop {
+            let page = client
+                .query()
+                .table_name(&self.config.table_name)
+                .key_condition_expression("tablePath = :path")
+                .filter_expression("complete = :incomplete")
+                .expression_attribute_values(":path", AttributeValue::S(table_uri.trim_end_matches('/').to_string()))
+                .expression_attribute_values(":incomplete", AttributeValue::S("false".to_string()))
+                .set_exclusive_start_key(start_key)
+                .send()
+                .await
+                .context("Failed to query lock table")?;
+
+            for item in page.items() {
+                let attr = |name: &str| item.get(name).and_then(|v| v.as_s().ok()).cloned();
+                if let (Some(file_name), Some(temp_path)) = (attr("fileName"), attr("tempPath")) {
+                    entries.push(PendingEntry { file_name, temp_path });
+                }
+            }
+
+            start_key = page.last_evaluated_key().cloned();
+            if start_key.is_none() {
+                return Ok(entries);
+            }
+        }
+    }
+
+    async fn recover(
+        &self,
+        client: &DynamoClient,
+        table_uri: &str,
+        store: &dyn ObjectStore,
+        entry: &PendingEntry,
+        temp: &Path,
+        target: &Path,
+    ) -> Result<()> {
+        if store.head(target).await.is_err() {
+            store.copy(temp, target).await
+                .with_context(|| format!("Failed to copy {} to {}", temp, target))?;
+        }
+
+        let expire_time = Utc::now().timestamp() + COMPLETED_ENTRY_TTL_SECS;
+        let updated = client
+            .update_item()
+            .table_name(&self.config.table_name)
+            .key("tablePath", AttributeValue::S(table_uri.trim_end_matches('/').to_string()))
+            .key("fileName", AttributeValue::S(entry.file_name.clone()))
+            .update_expression("SET complete = :complete, expireTime = :expire")
+            .condition_expression("complete = :incomplete")
+            .expression_attribute_values(":complete", AttributeValue::S("true".to_string()))
+            .expression_attribute_values(":incomplete", AttributeValue::S("false".to_string()))
+            .expression_attribute_values(":expire", AttributeValue::N(expire_time.to_string()))
+            .send()
+            .await;
+
+        match updated {
+            Ok(_) => {
+                log::warn!("Recovered stale commit entry {} for {}", entry.file_name, table_uri);
+                Ok(())
+            }
+            // Another writer repaired it first
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_conditional_check_failed_exception()) => Ok(()),
+            Err(e) => Err(e).context("Failed to mark commit entry complete"),
+        }
+    }
+}
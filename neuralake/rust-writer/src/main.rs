@@ -1,14 +1,60 @@
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use polars::prelude::*;
 use surgical_strike_writer::*;
 use std::collections::HashMap;
+use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// Log output format; `json` emits one object per line for log pipelines
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+    /// Export tracing spans of writes, compaction and vacuum to this OTLP
+    /// gRPC collector, e.g. http://localhost:4317 (requires the `otlp` feature)
+    #[arg(long, global = true)]
+    otlp_endpoint: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum LogFormat {
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum FileFormat {
+    Parquet,
+    Csv,
+    Jsonl,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum MaintenanceProcess {
+    Compaction,
+    Vacuum,
+}
+
+impl From<MaintenanceProcess> for Maintenance {
+    fn from(process: MaintenanceProcess) -> Self {
+        match process {
+            MaintenanceProcess::Compaction => Maintenance::Compaction,
+            MaintenanceProcess::Vacuum => Maintenance::Vacuum,
+        }
+    }
+}
+
+impl From<FileFormat> for ExportFormat {
+    fn from(format: FileFormat) -> Self {
+        match format {
+            FileFormat::Parquet => ExportFormat::Parquet,
+            FileFormat::Csv => ExportFormat::Csv,
+            FileFormat::Jsonl => ExportFormat::Jsonl,
+        }
+    }
 }
 
 #[derive(Subcommand)]
@@ -17,6 +63,14 @@ enum Commands {
     Start {
         #[arg(short, long, default_value = "config.toml")]
         config: String,
+        /// Only serve metrics, queries and the API; never write to or
+        /// maintain the tables, e.g. while investigating an incident
+        #[arg(long)]
+        read_only: bool,
+        /// Print what would run and what startup would change, without
+        /// writing to the tables or starting any process
+        #[arg(long)]
+        plan: bool,
     },
     /// Write a single test batch
     WriteBatch {
@@ -24,11 +78,90 @@ enum Commands {
         table_uri: String,
         #[arg(short, long, default_value = "10")]
         rows: usize,
+        /// Upsert on these comma-separated key columns instead of appending
+        #[arg(long, value_delimiter = ',')]
+        merge_keys: Vec<String>,
+        /// Skip the write if a batch with this key was already committed
+        #[arg(long)]
+        idempotency_key: Option<String>,
+    },
+    /// Write newline-delimited JSON from stdin in micro-batches until EOF
+    StreamStdin {
+        #[arg(short, long, alias = "table")]
+        table_uri: String,
+        /// Rows per batch, overriding the writer default
+        #[arg(long)]
+        max_batch_size: Option<usize>,
+        /// Longest a partial batch waits for more rows, in milliseconds
+        #[arg(long)]
+        max_batch_time_ms: Option<u64>,
+    },
+    /// Append existing Parquet files (local or object store) to a table
+    Import {
+        #[arg(short, long, alias = "table")]
+        table_uri: String,
+        /// Files to import; `*`, `?` and `**` wildcards are supported
+        #[arg(short, long)]
+        path: String,
+        /// Rows per commit; input files are split or combined to this size
+        #[arg(short, long, default_value = "100000")]
+        batch_rows: usize,
+        /// Compact the partitions the import touched once it completes
+        #[arg(long)]
+        optimize_after: bool,
+        /// Also checkpoint after that compaction
+        #[arg(long, requires = "optimize_after")]
+        checkpoint_after: bool,
+    },
+    /// Add existing Parquet files below the table root to the table without rewriting them
+    Register {
+        #[arg(short, long, alias = "table")]
+        table_uri: String,
+        /// Files to add, relative to the table root or prefixed with its URI;
+        /// `*`, `?` and `**` wildcards are supported
+        #[arg(short, long)]
+        path: String,
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+        /// Compact the partitions the new files touched once it completes
+        #[arg(long)]
+        optimize_after: bool,
+        /// Also checkpoint after that compaction
+        #[arg(long, requires = "optimize_after")]
+        checkpoint_after: bool,
+    },
+    /// Turn a Hive-partitioned directory of Parquet files into a Delta table in place
+    ConvertToDelta {
+        #[arg(short, long, alias = "table")]
+        table_uri: String,
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
     },
     /// Run compaction once
     Compact {
         #[arg(short, long)]
         table_uri: String,
+        /// Only compact matching partitions, e.g. `--partition-filter day=2024-01-01`; repeatable
+        #[arg(long)]
+        partition_filter: Vec<String>,
+    },
+    /// Rebuild the per-file min/max manifest and optionally list the files a filter keeps
+    Manifest {
+        #[arg(short, long)]
+        table_uri: String,
+        /// Comma-separated columns to index; defaults to compaction.manifest_columns
+        #[arg(long, value_delimiter = ',')]
+        columns: Vec<String>,
+        /// Only list files, e.g. `--filter ts=2024-01-01..2024-02-01`, without rebuilding
+        #[arg(long)]
+        filter: Vec<String>,
+    },
+    /// Write a Delta checkpoint at the latest version
+    Checkpoint {
+        #[arg(short, long)]
+        table_uri: String,
     },
     /// Run vacuum once
     Vacuum {
@@ -36,91 +169,1029 @@ enum Commands {
         table_uri: String,
         #[arg(short, long, default_value = "72")]
         retention_hours: u64,
+        /// List the files that would be deleted without deleting them
+        #[arg(long)]
+        dry_run: bool,
+        /// Partition prefixes listed concurrently
+        #[arg(long, default_value = "16")]
+        list_concurrency: usize,
+    },
+    /// Move partitions older than a given age into an archive table
+    Archive {
+        #[arg(short, long, alias = "table")]
+        table_uri: String,
+        /// Archive table, created on first use
+        #[arg(short, long)]
+        archive_uri: String,
+        /// Date partition column of the table
+        #[arg(short, long)]
+        partition_column: String,
+        #[arg(long, default_value = "%Y-%m-%d")]
+        partition_format: String,
+        #[arg(short, long)]
+        max_age_days: u64,
+    },
+    /// Delete every row whose key columns match a row in a Parquet keys file
+    DeleteKeys {
+        #[arg(short, long, alias = "table")]
+        table_uri: String,
+        /// Parquet file containing the keys to delete
+        #[arg(short, long)]
+        keys: PathBuf,
+        /// Comma-separated key columns used to match rows
+        #[arg(long, value_delimiter = ',', required = true)]
+        key_columns: Vec<String>,
+    },
+    /// Show the schema, properties, protocol and size of a table
+    Describe {
+        #[arg(short, long, alias = "table")]
+        table_uri: String,
+        /// Print the description as JSON
+        #[arg(long)]
+        json: bool,
+        /// Also list the partitions receiving the most rows recently
+        #[arg(long)]
+        hot_partitions: bool,
+        /// Window for --hot-partitions, in seconds
+        #[arg(long, default_value = "900")]
+        window_secs: u64,
+        /// Number of partitions listed by --hot-partitions
+        #[arg(long, default_value = "10")]
+        top: usize,
+        /// Also aggregate per-column min/max, null fraction and approximate
+        /// distinct count from the file statistics
+        #[arg(long)]
+        stats: bool,
+    },
+    /// Show which columns the files of a table have min/max and null count statistics for
+    Stats {
+        #[arg(short, long, alias = "table")]
+        table_uri: String,
+        /// Print the coverage, file by file, as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Fill in statistics missing from older files, from their Parquet footers
+    Restat {
+        #[arg(short, long, alias = "table")]
+        table_uri: String,
+        /// Only report the files that would get statistics
+        #[arg(long)]
+        dry_run: bool,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Check that the data files of a table are intact in the store
+    Verify {
+        #[arg(short, long, alias = "table")]
+        table_uri: String,
+        /// Also compare files against the checksum manifests recorded at commit time
+        #[arg(long)]
+        checksums: bool,
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Show the commit history of a table
+    History {
+        #[arg(short, long, alias = "table")]
+        table_uri: String,
+        #[arg(short, long, default_value = "20")]
+        limit: usize,
+        /// Print the history as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Summarize schema, file and row changes between two versions of a table
+    Diff {
+        #[arg(short, long, alias = "table")]
+        table_uri: String,
+        #[arg(long)]
+        from: i64,
+        #[arg(long)]
+        to: i64,
+        /// Also show up to this many changed rows from the change data feed
+        #[arg(long)]
+        sample_rows: Option<usize>,
+        /// Print the summary as JSON (without sample rows)
+        #[arg(long)]
+        json: bool,
+    },
+    /// Undo a range of bad commits with an inverse commit, or RESTORE when needed
+    Rollback {
+        #[arg(short, long, alias = "table")]
+        table_uri: String,
+        /// First bad version
+        #[arg(long)]
+        from_version: i64,
+        /// Last bad version
+        #[arg(long)]
+        to_version: i64,
+        /// Print the plan without changing the table
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Revert the whole table to an earlier version with Delta RESTORE
+    Restore {
+        #[arg(short, long, alias = "table")]
+        table_uri: String,
+        /// Version to restore
+        #[arg(long, required_unless_present = "timestamp", conflicts_with = "timestamp")]
+        version: Option<i64>,
+        /// Restore the latest version committed at or before this RFC 3339 time
+        #[arg(long)]
+        timestamp: Option<chrono::DateTime<chrono::Utc>>,
+        /// Show the files that would be re-added and removed without changing the table
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Write the table as of a version or time to a single file
+    Export {
+        #[arg(short, long, alias = "table")]
+        table_uri: String,
+        /// Version to export (defaults to the latest)
+        #[arg(long, conflicts_with = "timestamp")]
+        version: Option<i64>,
+        /// Export the latest version committed at or before this RFC 3339 time
+        #[arg(long)]
+        timestamp: Option<chrono::DateTime<chrono::Utc>>,
+        #[arg(long, value_enum, default_value = "parquet")]
+        format: FileFormat,
+        /// File to write
+        #[arg(short, long)]
+        output: String,
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Run a read-only SQL query against a table
+    Query {
+        #[arg(short, long, alias = "table")]
+        table_uri: String,
+        /// Query, e.g. "SELECT count(*) FROM t"
+        #[arg(long)]
+        sql: String,
+        /// Name the table is queried under
+        #[arg(long, default_value = "t")]
+        name: String,
+        /// Print the rows as a JSON array
+        #[arg(long)]
+        json: bool,
+    },
+    /// Analyze recent data of a table
+    Analyze {
+        #[arg(short, long, alias = "table")]
+        table_uri: String,
+        /// Suggest tighter types for string columns from a sample of recent rows
+        #[arg(long)]
+        suggest_types: bool,
+        /// Rows to sample from the most recently added files
+        #[arg(long, default_value = "10000")]
+        sample_rows: usize,
+        /// Also print the statements migrating the columns to the suggested types
+        #[arg(long, requires = "suggest_types")]
+        migration_plan: bool,
+        /// Print the analysis as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Submit and inspect ad-hoc maintenance jobs
+    Jobs {
+        /// Job queue database shared with the orchestrator
+        #[arg(long, default_value = "surgical_strike_jobs.db")]
+        db: PathBuf,
+        #[command(subcommand)]
+        command: JobCommands,
+    },
+    /// Control a running orchestrator through its HTTP API
+    Remote {
+        /// Base URL of the orchestrator's HTTP API
+        #[arg(long, default_value = "http://localhost:8080")]
+        url: String,
+        /// Bearer token matching the orchestrator's `http.auth_token`
+        #[arg(long)]
+        token: Option<String>,
+        #[command(subcommand)]
+        command: RemoteCommands,
+    },
+    /// Inspect ingestion sources of a running orchestrator
+    Source {
+        #[command(subcommand)]
+        command: SourceCommands,
+    },
+    /// Inspect and replay batches the writer gave up on
+    DeadLetter {
+        #[command(subcommand)]
+        command: DeadLetterCommands,
+    },
+    /// Interoperability checks for tables written by other engines
+    Compat {
+        #[command(subcommand)]
+        command: CompatCommands,
+    },
+    /// Inspect or clear the local state directory
+    State {
+        /// State directory of the orchestrator (`[state] dir`)
+        #[arg(long, default_value = "surgical_strike_state")]
+        dir: PathBuf,
+        #[command(subcommand)]
+        command: StateCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum StateCommands {
+    /// List the entries with their size, age and checksum health
+    Inspect {
+        /// Print the entries as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Delete entries, e.g. a corrupt one, so they are rebuilt from scratch
+    Reset {
+        /// Entries to delete
+        #[arg(required_unless_present = "all")]
+        names: Vec<String>,
+        /// Delete every entry and leftover temporary file
+        #[arg(long, conflicts_with = "names")]
+        all: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum JobCommands {
+    /// Queue a compaction job for a running orchestrator
+    Compact {
+        #[arg(short, long)]
+        table_uri: String,
+        /// Only compact matching partitions, e.g. `--partition-filter day=2024-01-01`; repeatable
+        #[arg(long)]
+        partition_filter: Vec<String>,
+    },
+    /// Queue a vacuum job for a running orchestrator
+    Vacuum {
+        #[arg(short, long)]
+        table_uri: String,
+        #[arg(short, long)]
+        retention_hours: Option<u64>,
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Queue an export of a table version to one file for a running orchestrator
+    Export {
+        #[arg(short, long)]
+        table_uri: String,
+        /// Version to export (defaults to the latest when the job runs)
+        #[arg(long)]
+        version: Option<i64>,
+        #[arg(long, value_enum, default_value = "parquet")]
+        format: FileFormat,
+        /// File to write
+        #[arg(short, long)]
+        output: String,
+    },
+    /// Show the status of a job
+    Status {
+        id: i64,
+    },
+    /// List the most recent jobs
+    List {
+        #[arg(short, long, default_value = "20")]
+        limit: usize,
+    },
+}
+
+#[derive(Subcommand)]
+enum SourceCommands {
+    /// Show per-partition consumer lag and commit lag
+    Status {
+        /// Base URL of the orchestrator's HTTP API
+        #[arg(long, default_value = "http://localhost:8080")]
+        url: String,
+        /// Table name, required when the orchestrator serves several tables
+        #[arg(short, long)]
+        table: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum RemoteCommands {
+    /// Show the served tables with queue depth, freshness and pause state
+    Status {
+        /// Print the tables as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Flush a table's pending batches now
+    Flush {
+        table: String,
+    },
+    /// Stop a table's writer and sources; producers wait once its queue fills
+    Pause {
+        table: String,
+    },
+    /// Resume a paused table
+    Resume {
+        table: String,
+    },
+    /// Skip a table's scheduled compaction or vacuum cycles, e.g. during a backfill
+    PauseMaintenance {
+        table: String,
+        #[arg(value_enum, required = true)]
+        processes: Vec<MaintenanceProcess>,
+    },
+    /// Resume paused compaction or vacuum
+    ResumeMaintenance {
+        table: String,
+        #[arg(value_enum, required = true)]
+        processes: Vec<MaintenanceProcess>,
+    },
+    /// Print process and per-table metrics as JSON
+    Metrics,
+}
+
+#[derive(Subcommand)]
+enum DeadLetterCommands {
+    /// List dead-lettered batches, oldest first
+    List {
+        /// Dead letter directory or object store prefix
+        #[arg(short, long)]
+        uri: String,
+    },
+    /// Write dead-lettered batches to their table and remove them from the queue
+    Replay {
+        #[arg(short, long, alias = "table")]
+        table_uri: String,
+        /// Dead letter directory or object store prefix
+        #[arg(short, long)]
+        uri: String,
+        /// Replay only this entry
+        #[arg(long)]
+        id: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum CompatCommands {
+    /// Report issues that would prevent or affect appends to the table
+    Check {
+        #[arg(short, long, alias = "table")]
+        table_uri: String,
     },
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    env_logger::init();
-    
     let cli = Cli::parse();
+    // Flushes buffered spans when main returns
+    let _telemetry = init_logging(cli.log_format, cli.otlp_endpoint.as_deref())?;
 
     match &cli.command {
-        Commands::Start { config } => {
-            println!("Starting Surgical Strike Writer with config: {}", config);
-            
-            // For now, use default config
-            let config = create_default_config();
-            let orchestrator = SurgicalStrikeOrchestrator::new(config).await?;
+        Commands::Start { config, read_only, plan } => {
+            if !*plan {
+                println!("Starting Surgical Strike Writer with config: {}", config);
+            }
+
+            let mut configs = if std::path::Path::new(config).exists() {
+                load_config(config)?
+            } else {
+                println!("Config file not found, using the default table with AWS credentials from the environment");
+                vec![create_default_config()]
+            };
+            if *read_only {
+                println!("Read-only mode: writes, maintenance, sources and jobs are disabled");
+                for config in &mut configs {
+                    config.read_only = true;
+                }
+            }
+            if *plan {
+                let start_plan = plan::plan_start(&configs).await?;
+                print!("{}", start_plan);
+                if start_plan.has_problems() {
+                    anyhow::bail!("Startup would fail; see the problems above");
+                }
+                return Ok(());
+            }
+            let orchestrator = MultiTableOrchestrator::new(configs).await?;
             
             orchestrator.start().await?;
         }
-        Commands::WriteBatch { table_uri, rows } => {
+        Commands::WriteBatch { table_uri, rows, merge_keys, idempotency_key } => {
             println!("Writing test batch with {} rows to {}", rows, table_uri);
             
-            let config = create_config_for_table(table_uri);
+            let mut config = create_config_for_table(table_uri);
+            if !merge_keys.is_empty() {
+                config.writer.write_mode = WriteMode::Merge { key_columns: merge_keys.clone(), delete_predicate: None };
+            }
             let orchestrator = SurgicalStrikeOrchestrator::new(config).await?;
             
             let test_df = create_test_dataframe(*rows)?;
-            orchestrator.write_batch(test_df).await?;
+            if let Some(key) = idempotency_key {
+                if !orchestrator.write_batch_idempotent(test_df, key).await? {
+                    println!("Skipped: a batch with idempotency key {} was already committed", key);
+                    return Ok(());
+                }
+            } else {
+                orchestrator.write_batch(test_df).await?;
+            }
             
             println!("Successfully wrote {} rows", rows);
         }
-        Commands::Compact { table_uri } => {
+        Commands::Compact { table_uri, partition_filter } => {
             println!("Running compaction on {}", table_uri);
             
+            let filters = partition_filter
+                .iter()
+                .map(|f| compaction::parse_partition_filter(f))
+                .collect::<Result<Vec<_>>>()?;
             let config = create_config_for_table(table_uri);
             let orchestrator = SurgicalStrikeOrchestrator::new(config).await?;
             
-            orchestrator.compact().await?;
+            orchestrator.compact_partitions(&filters).await?;
             
             println!("Compaction completed");
         }
-        Commands::Vacuum { table_uri, retention_hours } => {
+        Commands::Manifest { table_uri, columns, filter } => {
+            let config = create_config_for_table(table_uri);
+            let orchestrator = SurgicalStrikeOrchestrator::new(config).await?;
+
+            if filter.is_empty() {
+                let manifest = orchestrator.refresh_manifest(columns).await?;
+                println!(
+                    "Manifest of {} files over {:?} written at version {}",
+                    manifest.files.len(),
+                    manifest.columns,
+                    manifest.table_version
+                );
+            } else {
+                let predicates = filter
+                    .iter()
+                    .map(|f| manifest::RangePredicate::parse(f))
+                    .collect::<Result<Vec<_>>>()?;
+                let files = orchestrator.prune_files(&predicates).await?;
+                for file in &files {
+                    println!("{}", file);
+                }
+                eprintln!("{} files may match", files.len());
+            }
+        }
+        Commands::Checkpoint { table_uri } => {
+            let config = create_config_for_table(table_uri);
+            let orchestrator = SurgicalStrikeOrchestrator::new(config).await?;
+
+            let report = orchestrator.checkpoint().await?;
+
+            print!("{}", report);
+        }
+        Commands::Vacuum { table_uri, retention_hours, dry_run, list_concurrency } => {
             println!("Running vacuum on {} with retention {} hours", table_uri, retention_hours);
             
             let mut config = create_config_for_table(table_uri);
             config.vacuum.retention_hours = *retention_hours;
+            config.vacuum.dry_run = *dry_run;
+            config.vacuum.list_concurrency = *list_concurrency;
             
             let orchestrator = SurgicalStrikeOrchestrator::new(config).await?;
             
-            orchestrator.vacuum().await?;
+            let report = orchestrator.vacuum().await?;
             
+            print!("{}", report);
             println!("Vacuum completed");
         }
+        Commands::StreamStdin { table_uri, max_batch_size, max_batch_time_ms } => {
+            let mut config = create_config_for_table(table_uri);
+            if let Some(size) = max_batch_size {
+                config.writer.max_batch_size = *size;
+            }
+            if let Some(ms) = max_batch_time_ms {
+                config.writer.max_batch_time_ms = *ms;
+            }
+            let orchestrator = SurgicalStrikeOrchestrator::new(config).await?;
+
+            let shutdown = orchestrator.shutdown_token();
+            tokio::spawn(async move {
+                if tokio::signal::ctrl_c().await.is_ok() {
+                    shutdown.cancel();
+                }
+            });
+
+            let status = sources::SourceStatusHandle::default();
+            orchestrator.ingest(sources::stdin::StdinSource::new(status.clone())).await?;
+
+            let status = status.lock().unwrap();
+            eprintln!(
+                "Wrote {} lines in {} batches to {} ({} invalid lines skipped)",
+                status.messages_consumed - status.decode_errors,
+                status.batches_committed,
+                table_uri,
+                status.decode_errors
+            );
+        }
+        Commands::Archive { table_uri, archive_uri, partition_column, partition_format, max_age_days } => {
+            println!("Archiving partitions of {} older than {} days into {}", table_uri, max_age_days, archive_uri);
+            
+            let mut config = create_config_for_table(table_uri);
+            config.archive = Some(ArchiveConfig {
+                archive_table_uri: archive_uri.clone(),
+                partition_column: partition_column.clone(),
+                partition_format: partition_format.clone(),
+                max_age_days: *max_age_days,
+                archive_interval_secs: 6 * 3600,
+                storage_options: HashMap::new(),
+            });
+            let orchestrator = SurgicalStrikeOrchestrator::new(config).await?;
+            
+            let report = orchestrator.archive().await?;
+            
+            println!("Archive completed: {}", report);
+        }
+        Commands::Import { table_uri, path, batch_rows, optimize_after, checkpoint_after } => {
+            println!("Importing {} into {}", path, table_uri);
+            
+            let mut config = create_config_for_table(table_uri);
+            config.compaction.optimize_after_import |= *optimize_after;
+            config.compaction.checkpoint_after_import |= *checkpoint_after;
+            let orchestrator = SurgicalStrikeOrchestrator::new(config).await?;
+            
+            let report = orchestrator
+                .import_parquet(path, *batch_rows, |progress| println!("  {}", progress))
+                .await?;
+            
+            println!("Import completed: {}", report);
+        }
+        Commands::Register { table_uri, path, json, optimize_after, checkpoint_after } => {
+            let mut config = create_config_for_table(table_uri);
+            config.compaction.optimize_after_import |= *optimize_after;
+            config.compaction.checkpoint_after_import |= *checkpoint_after;
+            let orchestrator = SurgicalStrikeOrchestrator::new(config).await?;
+            
+            let report = orchestrator.register(path).await?;
+            
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                println!("Register completed: {}", report);
+            }
+        }
+        Commands::ConvertToDelta { table_uri, json } => {
+            let config = create_config_for_table(table_uri);
+            let report = convert::convert_to_delta(table_uri, &config.storage_options).await?;
+            
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print!("{}", report);
+            }
+        }
+        Commands::DeleteKeys { table_uri, keys, key_columns } => {
+            println!("Deleting keys from {} listed in {}", table_uri, keys.display());
+            
+            let config = create_config_for_table(table_uri);
+            let orchestrator = SurgicalStrikeOrchestrator::new(config).await?;
+            
+            let keys_df = delete::read_keys_file(keys, key_columns)?;
+            let report = orchestrator.delete_keys(&keys_df, key_columns).await?;
+            
+            println!(
+                "Deleted {} rows for {} keys (version {})",
+                report.rows_deleted, report.keys_requested, report.version
+            );
+        }
+        Commands::Describe { table_uri, json, hot_partitions, window_secs, top, stats } => {
+            let config = create_config_for_table(table_uri);
+            let orchestrator = SurgicalStrikeOrchestrator::new(config).await?;
+            
+            let description = orchestrator.describe().await?;
+            let hot = if *hot_partitions {
+                let window = std::time::Duration::from_secs(*window_secs);
+                Some(orchestrator.hot_partitions(window, *top).await?)
+            } else {
+                None
+            };
+            let profile = if *stats { Some(orchestrator.profile().await?) } else { None };
+            
+            if *json {
+                let mut value = serde_json::to_value(&description)?;
+                if let Some(hot) = &hot {
+                    value["hot_partitions"] = serde_json::to_value(hot)?;
+                }
+                if let Some(profile) = &profile {
+                    value["column_stats"] = serde_json::to_value(&profile.columns)?;
+                }
+                println!("{}", serde_json::to_string_pretty(&value)?);
+            } else {
+                println!("{}", description);
+                if let Some(profile) = &profile {
+                    println!();
+                    println!("Column statistics:");
+                    print!("{}", profile);
+                }
+                if let Some(hot) = &hot {
+                    println!();
+                    println!("Hot partitions (last {}s):", window_secs);
+                    println!("{:<40}  {:>12}  {:>14}  {:>6}", "PARTITION", "ROWS", "BYTES", "FILES");
+                    for writes in hot {
+                        println!("{}", writes);
+                    }
+                }
+            }
+        }
+        Commands::Stats { table_uri, json } => {
+            let config = create_config_for_table(table_uri);
+            let orchestrator = SurgicalStrikeOrchestrator::new(config).await?;
+            
+            let coverage = orchestrator.stats().await?;
+            
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&coverage)?);
+            } else {
+                print!("{}", coverage);
+            }
+        }
+        Commands::Restat { table_uri, dry_run, json } => {
+            let config = create_config_for_table(table_uri);
+            let orchestrator = SurgicalStrikeOrchestrator::new(config).await?;
+
+            let report = orchestrator.restat(*dry_run).await?;
+
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print!("{}", report);
+            }
+        }
+        Commands::Verify { table_uri, checksums, json } => {
+            let config = create_config_for_table(table_uri);
+            let orchestrator = SurgicalStrikeOrchestrator::new(config).await?;
+            
+            let report = orchestrator.verify(*checksums).await?;
+            
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print!("{}", report);
+            }
+            if !report.is_ok() {
+                std::process::exit(1);
+            }
+        }
+        Commands::History { table_uri, limit, json } => {
+            let config = create_config_for_table(table_uri);
+            let orchestrator = SurgicalStrikeOrchestrator::new(config).await?;
+            
+            let history = orchestrator.history(Some(*limit)).await?;
+            
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&history)?);
+            } else {
+                println!("{:>8}  {:<25}  {:<16}  {}", "VERSION", "TIMESTAMP", "OPERATION", "METRICS");
+                for entry in &history {
+                    println!("{}", entry);
+                }
+            }
+        }
+        Commands::Diff { table_uri, from, to, sample_rows, json } => {
+            let config = create_config_for_table(table_uri);
+            let orchestrator = SurgicalStrikeOrchestrator::new(config).await?;
+            
+            let diff = orchestrator.diff(*from, *to, *sample_rows).await?;
+            
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&diff)?);
+            } else {
+                print!("{}", diff);
+            }
+        }
+        Commands::Rollback { table_uri, from_version, to_version, dry_run } => {
+            let config = create_config_for_table(table_uri);
+            let orchestrator = SurgicalStrikeOrchestrator::new(config).await?;
+            
+            let plan = orchestrator.rollback(*from_version, *to_version, *dry_run).await?;
+            
+            print!("{}", plan);
+            println!("{}", if *dry_run { "Preview only, table unchanged" } else { "Rollback committed" });
+        }
+        Commands::Restore { table_uri, version, timestamp, dry_run } => {
+            let target = match (version, timestamp) {
+                (Some(version), _) => RestoreTarget::Version(*version),
+                (None, Some(timestamp)) => RestoreTarget::Timestamp(*timestamp),
+                (None, None) => unreachable!("clap requires --version or --timestamp"),
+            };
+            let config = create_config_for_table(table_uri);
+            let orchestrator = SurgicalStrikeOrchestrator::new(config).await?;
+            
+            let plan = orchestrator.restore(target, *dry_run).await?;
+            
+            print!("{}", plan);
+            println!("{}", if *dry_run { "Preview only, table unchanged" } else { "Restore committed" });
+        }
+        Commands::Export { table_uri, version, timestamp, format, output, json } => {
+            let as_of = match (version, timestamp) {
+                (Some(version), _) => AsOf::Version(*version),
+                (None, Some(timestamp)) => AsOf::Timestamp(*timestamp),
+                (None, None) => AsOf::Latest,
+            };
+            let config = create_config_for_table(table_uri);
+            let orchestrator = SurgicalStrikeOrchestrator::new(config).await?;
+            
+            let report = orchestrator.export(as_of, (*format).into(), output).await?;
+            
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print!("{}", report);
+            }
+        }
+        Commands::Query { table_uri, sql, name, json } => {
+            let config = create_config_for_table(table_uri);
+            let orchestrator = SurgicalStrikeOrchestrator::new(config).await?;
+            
+            let result = orchestrator.query(name, sql).await?;
+            
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&result.to_json()?)?);
+            } else {
+                print!("{}", result);
+            }
+        }
+        Commands::Analyze { table_uri, suggest_types, sample_rows, migration_plan, json } => {
+            if !*suggest_types {
+                anyhow::bail!("Nothing to analyze, pass --suggest-types");
+            }
+            let config = create_config_for_table(table_uri);
+            let orchestrator = SurgicalStrikeOrchestrator::new(config).await?;
+            
+            let analysis = orchestrator.suggest_types(*sample_rows).await?;
+            let plan = if *migration_plan { type_inference::migration_plan(&analysis) } else { Vec::new() };
+            
+            if *json {
+                let output = serde_json::json!({ "analysis": analysis, "migration_plan": plan });
+                println!("{}", serde_json::to_string_pretty(&output)?);
+            } else {
+                print!("{}", analysis);
+                if !plan.is_empty() {
+                    println!("\nMigration plan:");
+                    for statement in &plan {
+                        println!("{}", statement);
+                    }
+                }
+            }
+        }
+        Commands::Jobs { db, command } => {
+            let queue = JobQueue::open(db)?;
+            
+            match command {
+                JobCommands::Compact { table_uri, partition_filter } => {
+                    let kind = if partition_filter.is_empty() {
+                        JobKind::Compact
+                    } else {
+                        // Refuse malformed filters now rather than when the job runs
+                        for filter in partition_filter {
+                            compaction::parse_partition_filter(filter)?;
+                        }
+                        JobKind::CompactPartitions { filters: partition_filter.clone() }
+                    };
+                    let id = queue.submit(table_uri, &kind)?;
+                    println!("Queued compaction job {}", id);
+                }
+                JobCommands::Export { table_uri, version, format, output } => {
+                    let kind = JobKind::Export {
+                        version: *version,
+                        format: (*format).into(),
+                        output: output.clone(),
+                    };
+                    let id = queue.submit(table_uri, &kind)?;
+                    println!("Queued export job {}", id);
+                }
+                JobCommands::Vacuum { table_uri, retention_hours, dry_run } => {
+                    let kind = JobKind::Vacuum {
+                        retention_hours: *retention_hours,
+                        dry_run: *dry_run,
+                    };
+                    let id = queue.submit(table_uri, &kind)?;
+                    println!("Queued vacuum job {}", id);
+                }
+                JobCommands::Status { id } => match queue.get(*id)? {
+                    Some(job) => println!("{}", serde_json::to_string_pretty(&job)?),
+                    None => println!("Job {} not found", id),
+                },
+                JobCommands::List { limit } => {
+                    for job in queue.list(*limit)? {
+                        println!(
+                            "{:>6}  {:<10}  {:<40}  {:?}  {}",
+                            job.id,
+                            format!("{:?}", job.status),
+                            job.table_uri,
+                            job.kind,
+                            job.message.unwrap_or_default()
+                        );
+                    }
+                }
+            }
+        }
+        Commands::Remote { url, token, command } => {
+            let mut client = ControlClient::new(url);
+            if let Some(token) = token {
+                client = client.with_auth_token(token.as_str());
+            }
+            let tables = match command {
+                RemoteCommands::Status { json } => {
+                    let tables = client.tables().await?;
+                    if *json {
+                        println!("{}", serde_json::to_string_pretty(&tables)?);
+                        return Ok(());
+                    }
+                    tables
+                }
+                RemoteCommands::Flush { table } => vec![client.flush(table).await?],
+                RemoteCommands::Pause { table } => vec![client.pause(table).await?],
+                RemoteCommands::Resume { table } => vec![client.resume(table).await?],
+                RemoteCommands::PauseMaintenance { table, processes } => {
+                    let mut summary = None;
+                    for process in processes {
+                        summary = Some(client.pause_maintenance(table, (*process).into()).await?);
+                    }
+                    summary.into_iter().collect()
+                }
+                RemoteCommands::ResumeMaintenance { table, processes } => {
+                    let mut summary = None;
+                    for process in processes {
+                        summary = Some(client.resume_maintenance(table, (*process).into()).await?);
+                    }
+                    summary.into_iter().collect()
+                }
+                RemoteCommands::Metrics => {
+                    println!("{}", serde_json::to_string_pretty(&client.metrics().await?)?);
+                    return Ok(());
+                }
+            };
+            println!(
+                "{:<24}  {:>8}  {:>10}  {:>10}  {:>8}  {}",
+                "TABLE", "QUEUED", "VERSION", "FRESHNESS", "ERRORS", "STATE"
+            );
+            for table in tables {
+                println!(
+                    "{:<24}  {:>8}  {:>10}  {:>10}  {:>8}  {}",
+                    table.name,
+                    table.queued_batches,
+                    table.last_version.map_or_else(|| "-".to_string(), |v| v.to_string()),
+                    table.freshness_secs.map_or_else(|| "-".to_string(), |s| format!("{}s", s)),
+                    table.recent_errors,
+                    remote_state(&table)
+                );
+            }
+        }
+        Commands::Source { command: SourceCommands::Status { url, table } } => {
+            let mut endpoint = format!("{}/sources/status", url.trim_end_matches('/'));
+            if let Some(table) = table {
+                endpoint.push_str(&format!("?table={}", table));
+            }
+            let status: sources::SourceStatus = reqwest::get(endpoint)
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            
+            // Consumer lag is unread data in Kafka; the gap between consumer
+            // and commit lag is data read but still in the write path
+            print!("{}", status);
+        }
+        Commands::DeadLetter { command } => match command {
+            DeadLetterCommands::List { uri } => {
+                let queue = DeadLetterQueue::open(uri, &resolve_storage_options(uri, &HashMap::new()))?;
+                for entry in queue.list().await? {
+                    println!(
+                        "{}  {}  {:>8} rows  {}  {}",
+                        entry.id,
+                        entry.failed_at.to_rfc3339(),
+                        entry.rows,
+                        entry.table_uri,
+                        entry.error
+                    );
+                }
+            }
+            DeadLetterCommands::Replay { table_uri, uri, id } => {
+                let mut config = create_config_for_table(table_uri);
+                config.writer.dead_letter_uri = Some(uri.clone());
+                let orchestrator = SurgicalStrikeOrchestrator::new(config).await?;
+                
+                let replayed = orchestrator.replay_dead_letters(id.as_deref()).await?;
+                println!("Replayed {} dead letter entries into {}", replayed.len(), table_uri);
+            }
+        },
+        Commands::Compat { command: CompatCommands::Check { table_uri } } => {
+            let config = create_config_for_table(table_uri);
+            let orchestrator = SurgicalStrikeOrchestrator::new(config).await?;
+            
+            let report = orchestrator.compat_check().await?;
+            println!("{}", report);
+            
+            if !report.is_writable() {
+                std::process::exit(1);
+            }
+        }
+        Commands::State { dir, command } => {
+            let state = StateDir::new(dir, FsyncPolicy::default());
+            
+            match command {
+                StateCommands::Inspect { json } => {
+                    let entries = state.inspect()?;
+                    if *json {
+                        println!("{}", serde_json::to_string_pretty(&entries)?);
+                    } else {
+                        for entry in &entries {
+                            println!("{}", entry);
+                        }
+                        let unhealthy = entries.iter().filter(|e| e.status != EntryStatus::Ok).count();
+                        println!("{} entries in {}, {} unhealthy", entries.len(), dir.display(), unhealthy);
+                    }
+                }
+                StateCommands::Reset { names, .. } => {
+                    let removed = state.reset(names)?;
+                    println!("Removed {} entries from {}", removed, dir.display());
+                }
+            }
+        }
     }
 
     Ok(())
 }
 
-fn create_default_config() -> SurgicalStrikeConfig {
-    SurgicalStrikeConfig {
-        table_uri: "s3://neuralake-bucket/test-table".to_string(),
-        storage_options: deltalake::StorageOptions(
-            HashMap::from([
-                ("AWS_ENDPOINT_URL".to_string(), "http://localhost:9000".to_string()),
-                ("AWS_ACCESS_KEY_ID".to_string(), "minioadmin".to_string()),
-                ("AWS_SECRET_ACCESS_KEY".to_string(), "minioadmin".to_string()),
-                ("AWS_REGION".to_string(), "us-east-1".to_string()),
-            ])
-            .into(),
-        ),
-        ..Default::default()
+/// State column of `remote` output: the writer, then any paused maintenance
+fn remote_state(table: &server::TableSummary) -> String {
+    let mut state = vec![if table.paused { "paused" } else { "running" }];
+    if table.paused_maintenance.compaction {
+        state.push("compaction paused");
     }
+    if table.paused_maintenance.vacuum {
+        state.push("vacuum paused");
+    }
+    state.join(", ")
+}
+
+/// Both formats honour `RUST_LOG`. In JSON mode `log` records are bridged
+/// into tracing, and structured fields (table_uri, rows, version, ...) become
+/// top-level keys.
+fn init_log_output(format: LogFormat) {
+    match format {
+        LogFormat::Text => env_logger::init(),
+        LogFormat::Json => {
+            tracing_subscriber::fmt()
+                .json()
+                .flatten_event(true)
+                .with_current_span(false)
+                .with_env_filter(env_filter())
+                .init();
+        }
+    }
+}
+
+/// With an OTLP endpoint, spans are exported next to the usual log output,
+/// which then always goes through tracing
+#[cfg(feature = "otlp")]
+fn init_logging(format: LogFormat, otlp_endpoint: Option<&str>) -> Result<Option<telemetry::OtlpExporter>> {
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::util::SubscriberInitExt;
+
+    let Some(endpoint) = otlp_endpoint else {
+        init_log_output(format);
+        return Ok(None);
+    };
+    let exporter = telemetry::OtlpExporter::new(endpoint)?;
+    let registry = tracing_subscriber::registry().with(env_filter()).with(exporter.layer());
+    match format {
+        LogFormat::Text => registry.with(tracing_subscriber::fmt::layer()).init(),
+        LogFormat::Json => registry
+            .with(tracing_subscriber::fmt::layer().json().flatten_event(true).with_current_span(false))
+            .init(),
+    }
+    Ok(Some(exporter))
+}
+
+/// There is never an exporter to hold on to without the feature
+#[cfg(not(feature = "otlp"))]
+fn init_logging(format: LogFormat, otlp_endpoint: Option<&str>) -> Result<Option<std::convert::Infallible>> {
+    if otlp_endpoint.is_some() {
+        anyhow::bail!("--otlp-endpoint requires the `otlp` feature");
+    }
+    init_log_output(format);
+    Ok(None)
+}
+
+fn env_filter() -> tracing_subscriber::EnvFilter {
+    tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info"))
+}
+
+fn create_default_config() -> SurgicalStrikeConfig {
+    create_config_for_table("s3://neuralake-bucket/test-table")
 }
 
+/// Config for a single table, with credentials from its backend's environment
 fn create_config_for_table(table_uri: &str) -> SurgicalStrikeConfig {
     SurgicalStrikeConfig {
         table_uri: table_uri.to_string(),
-        storage_options: deltalake::StorageOptions(
-            HashMap::from([
-                ("AWS_ENDPOINT_URL".to_string(), "http://localhost:9000".to_string()),
-                ("AWS_ACCESS_KEY_ID".to_string(), "minioadmin".to_string()),
-                ("AWS_SECRET_ACCESS_KEY".to_string(), "minioadmin".to_string()),
-                ("AWS_REGION".to_string(), "us-east-1".to_string()),
-            ])
-            .into(),
-        ),
+        storage_options: resolve_storage_options(table_uri, &HashMap::new()),
         ..Default::default()
     }
 }
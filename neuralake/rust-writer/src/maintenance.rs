@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Scheduled maintenance process that can be paused while the orchestrator runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Maintenance {
+    Compaction,
+    Vacuum,
+}
+
+impl fmt::Display for Maintenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Maintenance::Compaction => "compaction",
+            Maintenance::Vacuum => "vacuum",
+        })
+    }
+}
+
+/// Pause switches of a table's compaction and vacuum loops, e.g. for the
+/// length of a backfill or an incident. A paused loop skips its scheduled
+/// cycles; passes requested explicitly (CLI, jobs, admin API) still run.
+#[derive(Debug, Default)]
+pub struct MaintenanceControl {
+    compaction: AtomicBool,
+    vacuum: AtomicBool,
+}
+
+/// Which maintenance processes are paused
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MaintenancePauses {
+    pub compaction: bool,
+    pub vacuum: bool,
+}
+
+impl MaintenanceControl {
+    fn switch(&self, process: Maintenance) -> &AtomicBool {
+        match process {
+            Maintenance::Compaction => &self.compaction,
+            Maintenance::Vacuum => &self.vacuum,
+        }
+    }
+
+    /// Skip the scheduled cycles of `process`; returns false if already paused
+    pub fn pause(&self, process: Maintenance) -> bool {
+        !self.switch(process).swap(true, Ordering::Relaxed)
+    }
+
+    /// Undo [`Self::pause`]; returns false if not paused
+    pub fn resume(&self, process: Maintenance) -> bool {
+        self.switch(process).swap(false, Ordering::Relaxed)
+    }
+
+    pub fn is_paused(&self, process: Maintenance) -> bool {
+        self.switch(process).load(Ordering::Relaxed)
+    }
+
+    pub fn pauses(&self) -> MaintenancePauses {
+        MaintenancePauses {
+            compaction: self.is_paused(Maintenance::Compaction),
+            vacuum: self.is_paused(Maintenance::Vacuum),
+        }
+    }
+}
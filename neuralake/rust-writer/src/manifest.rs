@@ -0,0 +1,217 @@
+use anyhow::{bail, Context, Result};
+use deltalake::protocol::ColumnValueStat;
+use deltalake::{DeltaTable, ObjectStoreError, Path};
+use polars::prelude::*;
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::io::Cursor;
+
+/// Location of the manifest below the table root. Vacuum leaves
+/// `_`-prefixed directories alone.
+pub const MANIFEST_PATH: &str = "_manifest/files.parquet";
+
+/// Min/max of the indexed columns for one data file. Values are kept as
+/// they appear in the Delta log statistics.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileRange {
+    pub path: String,
+    pub size: i64,
+    pub num_records: Option<i64>,
+    pub min: HashMap<String, Value>,
+    pub max: HashMap<String, Value>,
+}
+
+/// Compact per-file summary of chosen columns, so readers can prune files
+/// without replaying the statistics of the whole Delta log
+#[derive(Debug, Clone, PartialEq)]
+pub struct Manifest {
+    /// Table version the manifest describes
+    pub table_version: i64,
+    pub columns: Vec<String>,
+    pub files: Vec<FileRange>,
+}
+
+impl Manifest {
+    /// Summarize the active files of the loaded table version
+    pub fn from_table(table: &DeltaTable, columns: &[String]) -> Result<Self> {
+        let files = table
+            .snapshot()?
+            .file_actions()?
+            .into_iter()
+            .map(|add| {
+                let stats = add.get_stats().ok().flatten();
+                let pick = |values: Option<&HashMap<String, ColumnValueStat>>| {
+                    columns
+                        .iter()
+                        .filter_map(|c| Some((c.clone(), values?.get(c)?.as_value()?.clone())))
+                        .collect()
+                };
+                FileRange {
+                    path: add.path.clone(),
+                    size: add.size,
+                    num_records: stats.as_ref().map(|s| s.num_records),
+                    min: pick(stats.as_ref().map(|s| &s.min_values)),
+                    max: pick(stats.as_ref().map(|s| &s.max_values)),
+                }
+            })
+            .collect();
+
+        Ok(Self { table_version: table.version(), columns: columns.to_vec(), files })
+    }
+
+    /// One row per file; bounds are JSON-encoded in `min.<column>` and
+    /// `max.<column>` so every column type round-trips unchanged
+    pub fn to_dataframe(&self) -> Result<DataFrame> {
+        let mut columns = vec![
+            Column::new("table_version".into(), vec![self.table_version; self.files.len()]),
+            Column::new("path".into(), self.files.iter().map(|f| f.path.as_str()).collect::<Vec<_>>()),
+            Column::new("size".into(), self.files.iter().map(|f| f.size).collect::<Vec<_>>()),
+            Column::new("num_records".into(), self.files.iter().map(|f| f.num_records).collect::<Vec<_>>()),
+        ];
+        for column in &self.columns {
+            let min: Vec<Option<String>> =
+                self.files.iter().map(|f| f.min.get(column).map(Value::to_string)).collect();
+            let max: Vec<Option<String>> =
+                self.files.iter().map(|f| f.max.get(column).map(Value::to_string)).collect();
+            columns.push(Column::new(format!("min.{}", column).into(), min));
+            columns.push(Column::new(format!("max.{}", column).into(), max));
+        }
+        Ok(DataFrame::new(columns)?)
+    }
+
+    pub fn from_dataframe(df: &DataFrame) -> Result<Self> {
+        let columns: Vec<String> = df
+            .get_column_names()
+            .iter()
+            .filter_map(|name| name.strip_prefix("min.").map(str::to_string))
+            .collect();
+        let table_version = df.column("table_version")?.i64()?.get(0).unwrap_or(-1);
+        let paths = df.column("path")?.str()?;
+        let sizes = df.column("size")?.i64()?;
+        let records = df.column("num_records")?.i64()?;
+
+        let mut files: Vec<FileRange> = (0..df.height())
+            .map(|i| FileRange {
+                path: paths.get(i).unwrap_or_default().to_string(),
+                size: sizes.get(i).unwrap_or_default(),
+                num_records: records.get(i),
+                min: HashMap::new(),
+                max: HashMap::new(),
+            })
+            .collect();
+        for column in &columns {
+            for prefix in ["min", "max"] {
+                let values = df.column(&format!("{}.{}", prefix, column))?.str()?;
+                for (file, value) in files.iter_mut().zip(values.into_iter()) {
+                    let Some(value) = value.and_then(|v| serde_json::from_str(v).ok()) else { continue };
+                    let bound = if prefix == "min" { &mut file.min } else { &mut file.max };
+                    bound.insert(column.clone(), value);
+                }
+            }
+        }
+
+        Ok(Self { table_version, columns, files })
+    }
+
+    /// Files that may contain rows matching every predicate
+    pub fn prune<'a>(&'a self, predicates: &'a [RangePredicate]) -> impl Iterator<Item = &'a FileRange> {
+        self.files.iter().filter(|file| predicates.iter().all(|p| p.may_match(file)))
+    }
+}
+
+/// Rebuild the manifest for the loaded table version and store it next to the table
+pub async fn write_manifest(table: &DeltaTable, columns: &[String]) -> Result<Manifest> {
+    let manifest = Manifest::from_table(table, columns)?;
+    let mut buffer = Vec::new();
+    ParquetWriter::new(&mut buffer)
+        .finish(&mut manifest.to_dataframe()?)
+        .context("Failed to encode manifest")?;
+    table
+        .object_store()
+        .put(&Path::from(MANIFEST_PATH), buffer.into())
+        .await
+        .context("Failed to write manifest")?;
+    Ok(manifest)
+}
+
+/// The stored manifest, if one has been written
+pub async fn read_manifest(table: &DeltaTable) -> Result<Option<Manifest>> {
+    let bytes = match table.object_store().get(&Path::from(MANIFEST_PATH)).await {
+        Ok(result) => result.bytes().await?,
+        Err(ObjectStoreError::NotFound { .. }) => return Ok(None),
+        Err(e) => return Err(e).context("Failed to read manifest"),
+    };
+    let df = ParquetReader::new(Cursor::new(bytes)).finish().context("Invalid manifest")?;
+    Ok(Some(Manifest::from_dataframe(&df)?))
+}
+
+/// Active files of the loaded table version that may match `predicates`.
+/// Files the manifest does not know about yet (added after it was written)
+/// are always kept, so a stale manifest never hides data.
+pub async fn prune_files(table: &DeltaTable, predicates: &[RangePredicate]) -> Result<Vec<String>> {
+    let active: Vec<String> = table.get_files_iter()?.map(|p| p.to_string()).collect();
+    let Some(manifest) = read_manifest(table).await? else {
+        return Ok(active);
+    };
+    if let Some(p) = predicates.iter().find(|p| !manifest.columns.contains(&p.column)) {
+        bail!("Column {} is not indexed by the manifest", p.column);
+    }
+
+    let indexed: HashSet<&str> = manifest.files.iter().map(|f| f.path.as_str()).collect();
+    let candidates: HashSet<&str> = manifest.prune(predicates).map(|f| f.path.as_str()).collect();
+    Ok(active
+        .into_iter()
+        .filter(|path| !indexed.contains(path.as_str()) || candidates.contains(path.as_str()))
+        .collect())
+}
+
+/// Inclusive range filter on one indexed column
+#[derive(Debug, Clone, PartialEq)]
+pub struct RangePredicate {
+    pub column: String,
+    pub min: Option<Value>,
+    pub max: Option<Value>,
+}
+
+impl RangePredicate {
+    /// Parse `col=value`, `col=lo..hi`, `col=lo..` or `col=..hi`. Bounds that
+    /// are valid JSON (numbers, quoted strings) are taken as such, anything
+    /// else as a string.
+    pub fn parse(filter: &str) -> Result<Self> {
+        let Some((column, range)) = filter.split_once('=') else {
+            bail!("Expected column=value or column=min..max, got '{}'", filter);
+        };
+        let bound = |s: &str| {
+            (!s.is_empty()).then(|| serde_json::from_str(s).unwrap_or_else(|_| Value::String(s.to_string())))
+        };
+        let (min, max) = match range.split_once("..") {
+            Some((lo, hi)) => (bound(lo), bound(hi)),
+            None => (bound(range), bound(range)),
+        };
+        Ok(Self { column: column.trim().to_string(), min, max })
+    }
+
+    /// False only when the file's statistics prove it holds no matching row
+    pub fn may_match(&self, file: &FileRange) -> bool {
+        let below = match (&self.min, file.max.get(&self.column)) {
+            (Some(min), Some(file_max)) => compare(file_max, min) == Some(Ordering::Less),
+            _ => false,
+        };
+        let above = match (&self.max, file.min.get(&self.column)) {
+            (Some(max), Some(file_min)) => compare(file_min, max) == Some(Ordering::Greater),
+            _ => false,
+        };
+        !below && !above
+    }
+}
+
+/// Numbers compare numerically and strings (including ISO timestamps)
+/// lexicographically; other combinations are not comparable
+fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x.as_f64()?.partial_cmp(&y.as_f64()?),
+        (Value::String(x), Value::String(y)) => Some(x.cmp(y)),
+        _ => None,
+    }
+}
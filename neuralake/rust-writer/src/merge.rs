@@ -0,0 +1,87 @@
+use anyhow::{bail, Context, Result};
+use deltalake::arrow::record_batch::RecordBatch;
+use deltalake::datafusion::prelude::SessionContext;
+use deltalake::operations::merge::MergeMetrics;
+use deltalake::operations::transaction::CommitProperties;
+use deltalake::parquet::file::properties::WriterProperties;
+use deltalake::{DeltaOps, DeltaTable};
+
+/// Update rows whose key columns match a row in `batch` and insert the rest,
+/// in a single commit. `batch` must not contain duplicate keys. Rows
+/// matching `delete_predicate` delete their key's row instead and are not
+/// inserted.
+pub async fn upsert(
+    table: DeltaTable,
+    batch: RecordBatch,
+    key_columns: &[String],
+    delete_predicate: Option<&str>,
+    merge_schema: bool,
+    commit_properties: CommitProperties,
+    writer_properties: WriterProperties,
+) -> Result<(DeltaTable, MergeMetrics)> {
+    if key_columns.is_empty() {
+        bail!("Merge write mode requires at least one key column");
+    }
+
+    let columns: Vec<String> = batch
+        .schema()
+        .fields()
+        .iter()
+        .map(|f| f.name().clone())
+        .collect();
+
+    for key in key_columns {
+        if !columns.contains(key) {
+            bail!("Merge key column '{}' is missing from the batch", key);
+        }
+    }
+
+    let predicate = key_columns
+        .iter()
+        .map(|c| format!("target.\"{c}\" = source.\"{c}\""))
+        .collect::<Vec<_>>()
+        .join(" AND ");
+
+    let source = SessionContext::new()
+        .read_batch(batch)
+        .context("Failed to register batch as merge source")?;
+
+    let mut merge = DeltaOps(table)
+        .merge(source, predicate)
+        .with_source_alias("source")
+        .with_target_alias("target")
+        .with_merge_schema(merge_schema)
+        .with_commit_properties(commit_properties)
+        .with_writer_properties(writer_properties);
+    // Clauses apply in order, so a deleting row never reaches the update
+    if let Some(delete_predicate) = delete_predicate {
+        merge = merge.when_matched_delete(|delete| delete.predicate(delete_predicate))?;
+    }
+    let (table, metrics) = merge
+        .when_matched_update(|update| {
+            columns
+                .iter()
+                .filter(|c| !key_columns.contains(c))
+                .fold(update, |update, c| update.update(c.as_str(), format!("source.\"{c}\"")))
+        })?
+        .when_not_matched_insert(|insert| {
+            let insert = match delete_predicate {
+                Some(delete_predicate) => insert.predicate(format!("NOT ({})", delete_predicate)),
+                None => insert,
+            };
+            columns
+                .iter()
+                .fold(insert, |insert, c| insert.set(c.as_str(), format!("source.\"{c}\"")))
+        })?
+        .await
+        .context("Failed to merge batch")?;
+
+    log::debug!(
+        "Merged batch: {} rows updated, {} rows inserted, {} rows deleted",
+        metrics.num_target_rows_updated,
+        metrics.num_target_rows_inserted,
+        metrics.num_target_rows_deleted
+    );
+
+    Ok((table, metrics))
+}
@@ -0,0 +1,664 @@
+use chrono::{DateTime, Utc};
+use deltalake::kernel::Add;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use crate::maintenance::Maintenance;
+use crate::sequence::GapsReport;
+
+/// Sub-buckets per power of two; bounds the relative error of a recorded
+/// value to 1/16 (about 6%)
+const SUB_BUCKETS: u64 = 16;
+const SUB_BUCKET_BITS: u32 = 4;
+/// Covers up to 2^40 microseconds (about 12 days)
+const MAX_EXPONENT: u32 = 40;
+/// Committed batches remembered for receipt lookups
+const RECENT_BATCHES: usize = 1024;
+/// Written files remembered for compression reporting
+const RECENT_FILES: usize = 1024;
+/// Failures remembered for the error feed
+const RECENT_ERRORS: usize = 100;
+const BUCKETS: usize = ((MAX_EXPONENT - SUB_BUCKET_BITS + 2) as u64 * SUB_BUCKETS) as usize;
+
+/// Lock-free latency histogram with log-linear buckets, in the style of
+/// HdrHistogram: constant relative precision across the whole range
+#[derive(Debug)]
+pub struct LatencyHistogram {
+    buckets: Box<[AtomicU64]>,
+    count: AtomicU64,
+    sum_us: AtomicU64,
+    max_us: AtomicU64,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self {
+            buckets: (0..BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+            count: AtomicU64::new(0),
+            sum_us: AtomicU64::new(0),
+            max_us: AtomicU64::new(0),
+        }
+    }
+}
+
+impl LatencyHistogram {
+    pub fn record(&self, latency: Duration) {
+        let us = (latency.as_micros() as u64).min((1 << MAX_EXPONENT) - 1);
+        self.buckets[bucket_index(us)].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_us.fetch_add(us, Ordering::Relaxed);
+        self.max_us.fetch_max(us, Ordering::Relaxed);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// Mean latency in milliseconds, 0 when nothing was recorded
+    pub fn mean_ms(&self) -> f64 {
+        match self.count() {
+            0 => 0.0,
+            n => self.sum_us.load(Ordering::Relaxed) as f64 / n as f64 / 1000.0,
+        }
+    }
+
+    /// Latency in milliseconds at or below which `quantile` (0.0-1.0) of the
+    /// recorded values fall, reported as the upper bound of its bucket
+    pub fn quantile_ms(&self, quantile: f64) -> f64 {
+        let count = self.count();
+        if count == 0 {
+            return 0.0;
+        }
+        let rank = ((quantile.clamp(0.0, 1.0) * count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (index, bucket) in self.buckets.iter().enumerate() {
+            seen += bucket.load(Ordering::Relaxed);
+            if seen >= rank {
+                let upper = bucket_lower_bound(index + 1) - 1;
+                return upper.min(self.max_us.load(Ordering::Relaxed)) as f64 / 1000.0;
+            }
+        }
+        self.max_us.load(Ordering::Relaxed) as f64 / 1000.0
+    }
+}
+
+fn bucket_index(us: u64) -> usize {
+    if us < SUB_BUCKETS {
+        return us as usize;
+    }
+    let exponent = 63 - us.leading_zeros();
+    let sub = (us >> (exponent - SUB_BUCKET_BITS)) & (SUB_BUCKETS - 1);
+    ((exponent - SUB_BUCKET_BITS + 1) as u64 * SUB_BUCKETS + sub) as usize
+}
+
+fn bucket_lower_bound(index: usize) -> u64 {
+    let index = index as u64;
+    if index < SUB_BUCKETS {
+        return index;
+    }
+    let exponent = (index / SUB_BUCKETS) as u32 + SUB_BUCKET_BITS - 1;
+    let sub = index % SUB_BUCKETS;
+    (SUB_BUCKETS + sub) << (exponent - SUB_BUCKET_BITS)
+}
+
+/// Where a batch ended up: the table version that contains its rows
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchReceipt {
+    pub batch_id: String,
+    pub version: i64,
+    pub committed_at: DateTime<Utc>,
+}
+
+/// A data file a flush wrote, and how well its rows compressed
+#[derive(Debug, Clone, Serialize)]
+pub struct FileReceipt {
+    pub path: String,
+    pub rows: u64,
+    pub size_bytes: u64,
+    /// In-memory Arrow size of the rows over the Parquet file size
+    pub compression_ratio: f64,
+}
+
+impl FileReceipt {
+    /// Receipt for `add`, whose rows took `arrow_bytes_per_row` in memory
+    pub fn for_add(add: &Add, arrow_bytes_per_row: f64) -> Self {
+        let rows = add
+            .get_stats()
+            .ok()
+            .flatten()
+            .map_or(0, |stats| stats.num_records.max(0) as u64);
+        let size_bytes = add.size.max(0) as u64;
+        Self {
+            path: add.path.clone(),
+            rows,
+            size_bytes,
+            compression_ratio: rows as f64 * arrow_bytes_per_row / size_bytes.max(1) as f64,
+        }
+    }
+}
+
+/// A failure reported by one of the table's processes
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorEvent {
+    /// `writer`, `compaction` or `vacuum`
+    pub process: String,
+    pub message: String,
+    pub at: DateTime<Utc>,
+}
+
+/// Live counters shared by the writer, compaction and vacuum processes of
+/// one table
+#[derive(Debug, Default)]
+pub struct MetricsRegistry {
+    batches_written: AtomicU64,
+    rows_written: AtomicU64,
+    write_retries: AtomicU64,
+    write_failures: AtomicU64,
+    dead_lettered: AtomicU64,
+    commit_conflicts: AtomicU64,
+    /// Rows older than the event-time watermark, whether routed, flagged or dropped
+    late_rows: AtomicU64,
+    /// Rows breaking a validation rule, kept out of the table
+    quarantined_rows: AtomicU64,
+    /// Totals of the latest gaps report of a sequence-numbered source
+    sequence_missing: AtomicU64,
+    sequence_out_of_order: AtomicU64,
+    sequence_duplicates: AtomicU64,
+    write_latency: LatencyHistogram,
+    /// Flushes that opened the table, and how long loading its log took
+    table_opens: AtomicU64,
+    table_open_time: LatencyHistogram,
+    /// Flushes that reused the table handle of an earlier flush
+    table_reuses: AtomicU64,
+    /// Time flushes waited for a slot shared with other tables
+    flush_slot_wait: LatencyHistogram,
+    /// Flushes that waited longer for a slot than the whole latency SLA
+    flush_starvations: AtomicU64,
+    /// Estimated in-memory size of batches queued or awaiting a flush
+    queued_bytes: AtomicU64,
+    /// The most recent committed batches, oldest first
+    recent_batches: Mutex<VecDeque<BatchReceipt>>,
+    /// The most recent failures, oldest first
+    recent_errors: Mutex<VecDeque<ErrorEvent>>,
+    /// The most recently written files, oldest first
+    recent_files: Mutex<VecDeque<FileReceipt>>,
+    /// Arrow size of the rows flushes wrote, and the size of their files
+    arrow_bytes_written: AtomicU64,
+    file_bytes_written: AtomicU64,
+    compactions_run: AtomicU64,
+    files_compacted: AtomicU64,
+    bytes_compacted: AtomicU64,
+    compaction_time: LatencyHistogram,
+    /// Duration of each partition optimized within a compaction cycle
+    partition_compaction_time: LatencyHistogram,
+    average_file_size_before: AtomicU64,
+    average_file_size_after: AtomicU64,
+    /// Fraction of active files counted as small at the latest compaction
+    /// check, as `f64` bits
+    small_file_ratio: AtomicU64,
+    vacuum_runs: AtomicU64,
+    files_vacuumed: AtomicU64,
+    bytes_vacuumed: AtomicU64,
+    vacuum_time: LatencyHistogram,
+    /// Scheduled cycles skipped because the process was paused
+    compaction_cycles_paused: AtomicU64,
+    vacuum_cycles_paused: AtomicU64,
+}
+
+impl MetricsRegistry {
+    /// A committed batch and its end-to-end write latency, retries included
+    pub fn record_write(&self, rows: usize, latency: Duration) {
+        self.batches_written.fetch_add(1, Ordering::Relaxed);
+        self.rows_written.fetch_add(rows as u64, Ordering::Relaxed);
+        self.write_latency.record(latency);
+    }
+
+    pub fn record_write_retry(&self) {
+        self.write_retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A batch given up on after exhausting retries or a permanent error
+    pub fn record_write_failure(&self) {
+        self.write_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A failed batch kept in the dead letter queue
+    pub fn record_dead_lettered(&self) {
+        self.dead_lettered.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A commit lost to a concurrent writer and retried on the new version
+    pub fn record_commit_conflict(&self) {
+        self.commit_conflicts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_quarantined_rows(&self, rows: usize) {
+        self.quarantined_rows.fetch_add(rows as u64, Ordering::Relaxed);
+    }
+
+    /// Totals of a periodic gaps report, replacing the previous ones
+    pub fn record_gaps_report(&self, report: &GapsReport) {
+        self.sequence_missing.store(report.missing(), Ordering::Relaxed);
+        self.sequence_out_of_order.store(report.out_of_order(), Ordering::Relaxed);
+        self.sequence_duplicates.store(report.duplicates(), Ordering::Relaxed);
+    }
+
+    pub fn record_table_open(&self, elapsed: Duration) {
+        self.table_opens.fetch_add(1, Ordering::Relaxed);
+        self.table_open_time.record(elapsed);
+    }
+
+    pub fn record_table_reuse(&self) {
+        self.table_reuses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_late_rows(&self, rows: usize) {
+        self.late_rows.fetch_add(rows as u64, Ordering::Relaxed);
+    }
+
+    /// A flush that waited `wait` for a shared slot; waiting longer than the
+    /// SLA counts as starvation
+    pub fn record_flush_slot_wait(&self, wait: Duration, sla: Duration) {
+        self.flush_slot_wait.record(wait);
+        if wait > sla {
+            self.flush_starvations.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// The batches whose rows were committed as `version`
+    pub fn record_batches_committed(&self, batch_ids: &[String], version: i64) {
+        let committed_at = Utc::now();
+        let mut recent = self.recent_batches.lock().unwrap();
+        for batch_id in batch_ids {
+            if recent.len() == RECENT_BATCHES {
+                recent.pop_front();
+            }
+            recent.push_back(BatchReceipt { batch_id: batch_id.clone(), version, committed_at });
+        }
+    }
+
+    /// Receipt for a recently committed batch. Only the last
+    /// `RECENT_BATCHES` are kept, so an older batch is reported as unknown.
+    pub fn batch_receipt(&self, batch_id: &str) -> Option<BatchReceipt> {
+        let recent = self.recent_batches.lock().unwrap();
+        recent.iter().rev().find(|r| r.batch_id == batch_id).cloned()
+    }
+
+    /// The most recently committed batches, newest first
+    pub fn recent_batches(&self, limit: usize) -> Vec<BatchReceipt> {
+        let recent = self.recent_batches.lock().unwrap();
+        recent.iter().rev().take(limit).cloned().collect()
+    }
+
+    /// Files uploaded by a flush, ahead of its commit
+    pub fn record_files_written(&self, files: Vec<FileReceipt>) {
+        let mut recent = self.recent_files.lock().unwrap();
+        for file in files {
+            let arrow_bytes = file.compression_ratio * file.size_bytes as f64;
+            self.arrow_bytes_written.fetch_add(arrow_bytes as u64, Ordering::Relaxed);
+            self.file_bytes_written.fetch_add(file.size_bytes, Ordering::Relaxed);
+            if recent.len() == RECENT_FILES {
+                recent.pop_front();
+            }
+            recent.push_back(file);
+        }
+    }
+
+    /// The most recently written files, newest first
+    pub fn recent_files(&self, limit: usize) -> Vec<FileReceipt> {
+        let recent = self.recent_files.lock().unwrap();
+        recent.iter().rev().take(limit).cloned().collect()
+    }
+
+    /// Arrow size over Parquet size across every file written so far
+    pub fn compression_ratio(&self) -> f64 {
+        let file_bytes = self.file_bytes_written.load(Ordering::Relaxed);
+        if file_bytes == 0 {
+            return 0.0;
+        }
+        self.arrow_bytes_written.load(Ordering::Relaxed) as f64 / file_bytes as f64
+    }
+
+    /// A failed cycle or flush, for the error feed
+    pub fn record_error(&self, process: &str, message: String) {
+        let mut recent = self.recent_errors.lock().unwrap();
+        if recent.len() == RECENT_ERRORS {
+            recent.pop_front();
+        }
+        recent.push_back(ErrorEvent { process: process.to_string(), message, at: Utc::now() });
+    }
+
+    /// The most recent failures, newest first
+    pub fn recent_errors(&self) -> Vec<ErrorEvent> {
+        self.recent_errors.lock().unwrap().iter().rev().cloned().collect()
+    }
+
+    /// A batch accepted into the writer queue
+    pub fn record_enqueued(&self, bytes: u64) {
+        self.queued_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Batches handed to a flush (or rejected by the queue)
+    pub fn record_dequeued(&self, bytes: u64) {
+        self.queued_bytes.fetch_sub(bytes, Ordering::Relaxed);
+    }
+
+    pub fn record_compaction(&self, files: u64, bytes: u64, elapsed: Duration) {
+        self.compactions_run.fetch_add(1, Ordering::Relaxed);
+        self.files_compacted.fetch_add(files, Ordering::Relaxed);
+        self.bytes_compacted.fetch_add(bytes, Ordering::Relaxed);
+        self.compaction_time.record(elapsed);
+    }
+
+    pub fn record_partition_compaction(&self, elapsed: Duration) {
+        self.partition_compaction_time.record(elapsed);
+    }
+
+    /// Average active file size around the latest compaction
+    pub fn record_file_sizes(&self, before: u64, after: u64) {
+        self.average_file_size_before.store(before, Ordering::Relaxed);
+        self.average_file_size_after.store(after, Ordering::Relaxed);
+    }
+
+    pub fn record_small_file_ratio(&self, ratio: f64) {
+        self.small_file_ratio.store(ratio.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn record_vacuum(&self, files: u64, bytes: u64, elapsed: Duration) {
+        self.vacuum_runs.fetch_add(1, Ordering::Relaxed);
+        self.files_vacuumed.fetch_add(files, Ordering::Relaxed);
+        self.bytes_vacuumed.fetch_add(bytes, Ordering::Relaxed);
+        self.vacuum_time.record(elapsed);
+    }
+
+    /// A scheduled maintenance cycle skipped while `process` was paused
+    pub fn record_paused_cycle(&self, process: Maintenance) {
+        match process {
+            Maintenance::Compaction => self.compaction_cycles_paused.fetch_add(1, Ordering::Relaxed),
+            Maintenance::Vacuum => self.vacuum_cycles_paused.fetch_add(1, Ordering::Relaxed),
+        };
+    }
+
+    pub fn batches_written(&self) -> u64 {
+        self.batches_written.load(Ordering::Relaxed)
+    }
+
+    pub fn rows_written(&self) -> u64 {
+        self.rows_written.load(Ordering::Relaxed)
+    }
+
+    pub fn write_retries(&self) -> u64 {
+        self.write_retries.load(Ordering::Relaxed)
+    }
+
+    pub fn write_failures(&self) -> u64 {
+        self.write_failures.load(Ordering::Relaxed)
+    }
+
+    pub fn dead_lettered(&self) -> u64 {
+        self.dead_lettered.load(Ordering::Relaxed)
+    }
+
+    pub fn commit_conflicts(&self) -> u64 {
+        self.commit_conflicts.load(Ordering::Relaxed)
+    }
+
+    pub fn late_rows(&self) -> u64 {
+        self.late_rows.load(Ordering::Relaxed)
+    }
+
+    pub fn quarantined_rows(&self) -> u64 {
+        self.quarantined_rows.load(Ordering::Relaxed)
+    }
+
+    pub fn sequence_missing(&self) -> u64 {
+        self.sequence_missing.load(Ordering::Relaxed)
+    }
+
+    pub fn sequence_out_of_order(&self) -> u64 {
+        self.sequence_out_of_order.load(Ordering::Relaxed)
+    }
+
+    pub fn sequence_duplicates(&self) -> u64 {
+        self.sequence_duplicates.load(Ordering::Relaxed)
+    }
+
+    pub fn table_opens(&self) -> u64 {
+        self.table_opens.load(Ordering::Relaxed)
+    }
+
+    pub fn table_open_time(&self) -> &LatencyHistogram {
+        &self.table_open_time
+    }
+
+    pub fn table_reuses(&self) -> u64 {
+        self.table_reuses.load(Ordering::Relaxed)
+    }
+
+    pub fn flush_slot_wait(&self) -> &LatencyHistogram {
+        &self.flush_slot_wait
+    }
+
+    pub fn flush_starvations(&self) -> u64 {
+        self.flush_starvations.load(Ordering::Relaxed)
+    }
+
+    pub fn queued_bytes(&self) -> u64 {
+        self.queued_bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn write_latency(&self) -> &LatencyHistogram {
+        &self.write_latency
+    }
+
+    pub fn compactions_run(&self) -> u64 {
+        self.compactions_run.load(Ordering::Relaxed)
+    }
+
+    pub fn files_compacted(&self) -> u64 {
+        self.files_compacted.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_compacted(&self) -> u64 {
+        self.bytes_compacted.load(Ordering::Relaxed)
+    }
+
+    pub fn compaction_time(&self) -> &LatencyHistogram {
+        &self.compaction_time
+    }
+
+    pub fn partition_compaction_time(&self) -> &LatencyHistogram {
+        &self.partition_compaction_time
+    }
+
+    pub fn average_file_size_before(&self) -> u64 {
+        self.average_file_size_before.load(Ordering::Relaxed)
+    }
+
+    pub fn average_file_size_after(&self) -> u64 {
+        self.average_file_size_after.load(Ordering::Relaxed)
+    }
+
+    pub fn small_file_ratio(&self) -> f64 {
+        f64::from_bits(self.small_file_ratio.load(Ordering::Relaxed))
+    }
+
+    pub fn vacuum_runs(&self) -> u64 {
+        self.vacuum_runs.load(Ordering::Relaxed)
+    }
+
+    pub fn files_vacuumed(&self) -> u64 {
+        self.files_vacuumed.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_vacuumed(&self) -> u64 {
+        self.bytes_vacuumed.load(Ordering::Relaxed)
+    }
+
+    pub fn vacuum_time(&self) -> &LatencyHistogram {
+        &self.vacuum_time
+    }
+
+    pub fn compaction_cycles_paused(&self) -> u64 {
+        self.compaction_cycles_paused.load(Ordering::Relaxed)
+    }
+
+    pub fn vacuum_cycles_paused(&self) -> u64 {
+        self.vacuum_cycles_paused.load(Ordering::Relaxed)
+    }
+
+    /// Point-in-time copy of the counters for the metrics endpoint
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            batches_written: self.batches_written(),
+            rows_written: self.rows_written(),
+            write_retries: self.write_retries(),
+            write_failures: self.write_failures(),
+            dead_lettered: self.dead_lettered(),
+            commit_conflicts: self.commit_conflicts(),
+            late_rows: self.late_rows(),
+            quarantined_rows: self.quarantined_rows(),
+            sequence_missing: self.sequence_missing(),
+            sequence_out_of_order: self.sequence_out_of_order(),
+            sequence_duplicates: self.sequence_duplicates(),
+            write_latency_mean_ms: self.write_latency.mean_ms(),
+            write_latency_p99_ms: self.write_latency.quantile_ms(0.99),
+            table_opens: self.table_opens(),
+            table_open_mean_ms: self.table_open_time.mean_ms(),
+            table_reuses: self.table_reuses(),
+            compression_ratio: self.compression_ratio(),
+            flush_slot_wait_mean_ms: self.flush_slot_wait.mean_ms(),
+            flush_slot_wait_p99_ms: self.flush_slot_wait.quantile_ms(0.99),
+            flush_starvations: self.flush_starvations(),
+            queued_bytes: self.queued_bytes(),
+            compactions_run: self.compactions_run(),
+            files_compacted: self.files_compacted(),
+            bytes_compacted: self.bytes_compacted(),
+            partition_compaction_mean_ms: self.partition_compaction_time.mean_ms(),
+            partition_compaction_p99_ms: self.partition_compaction_time.quantile_ms(0.99),
+            average_file_size_before: self.average_file_size_before(),
+            average_file_size_after: self.average_file_size_after(),
+            small_file_ratio: self.small_file_ratio(),
+            vacuum_runs: self.vacuum_runs(),
+            files_vacuumed: self.files_vacuumed(),
+            bytes_vacuumed: self.bytes_vacuumed(),
+            compaction_cycles_paused: self.compaction_cycles_paused(),
+            vacuum_cycles_paused: self.vacuum_cycles_paused(),
+        }
+    }
+}
+
+/// Serializable view of a [`MetricsRegistry`]
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricsSnapshot {
+    pub batches_written: u64,
+    pub rows_written: u64,
+    pub write_retries: u64,
+    pub write_failures: u64,
+    pub dead_lettered: u64,
+    pub commit_conflicts: u64,
+    pub late_rows: u64,
+    pub quarantined_rows: u64,
+    pub sequence_missing: u64,
+    pub sequence_out_of_order: u64,
+    pub sequence_duplicates: u64,
+    pub write_latency_mean_ms: f64,
+    pub write_latency_p99_ms: f64,
+    pub table_opens: u64,
+    pub table_open_mean_ms: f64,
+    pub table_reuses: u64,
+    pub compression_ratio: f64,
+    pub flush_slot_wait_mean_ms: f64,
+    pub flush_slot_wait_p99_ms: f64,
+    pub flush_starvations: u64,
+    pub queued_bytes: u64,
+    pub compactions_run: u64,
+    pub files_compacted: u64,
+    pub bytes_compacted: u64,
+    pub partition_compaction_mean_ms: f64,
+    pub partition_compaction_p99_ms: f64,
+    pub average_file_size_before: u64,
+    pub average_file_size_after: u64,
+    pub small_file_ratio: f64,
+    pub vacuum_runs: u64,
+    pub files_vacuumed: u64,
+    pub bytes_vacuumed: u64,
+    pub compaction_cycles_paused: u64,
+    pub vacuum_cycles_paused: u64,
+}
+
+/// `USER_HZ`, the unit of CPU times in `/proc`; 100 on every mainstream
+/// Linux architecture
+const CLOCK_TICKS_PER_SEC: f64 = 100.0;
+
+/// Resource usage of this process. OS-level values are read from `/proc`
+/// and are `None` on platforms without it.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ProcessMetrics {
+    /// Total user and system CPU time consumed
+    pub cpu_seconds_total: Option<f64>,
+    /// CPU usage since the previous sample, where 100 is one full core
+    pub cpu_percent: Option<f64>,
+    pub resident_memory_bytes: Option<u64>,
+    pub open_fds: Option<u64>,
+    pub tokio_workers: usize,
+    pub tokio_alive_tasks: usize,
+    /// Tasks waiting in the runtime's global queue
+    pub tokio_global_queue_depth: usize,
+}
+
+/// Samples [`ProcessMetrics`], remembering the previous CPU time so usage
+/// can be reported as a rate
+#[derive(Debug, Default)]
+pub struct ProcessSampler {
+    previous: Mutex<Option<(Instant, f64)>>,
+}
+
+impl ProcessSampler {
+    pub fn sample(&self) -> ProcessMetrics {
+        let cpu_seconds_total = std::fs::read_to_string("/proc/self/stat")
+            .ok()
+            .and_then(|stat| parse_cpu_ticks(&stat))
+            .map(|ticks| ticks as f64 / CLOCK_TICKS_PER_SEC);
+
+        let now = Instant::now();
+        let cpu_percent = cpu_seconds_total.and_then(|total| {
+            let previous = self.previous.lock().unwrap().replace((now, total));
+            previous
+                .map(|(at, used)| (now.duration_since(at).as_secs_f64(), total - used))
+                .filter(|(elapsed, _)| *elapsed > 0.0)
+                .map(|(elapsed, used)| used / elapsed * 100.0)
+        });
+
+        let runtime = tokio::runtime::Handle::try_current().ok().map(|h| h.metrics());
+        ProcessMetrics {
+            cpu_seconds_total,
+            cpu_percent,
+            resident_memory_bytes: std::fs::read_to_string("/proc/self/status")
+                .ok()
+                .and_then(|status| parse_rss_bytes(&status)),
+            open_fds: std::fs::read_dir("/proc/self/fd").ok().map(|fds| fds.count() as u64),
+            tokio_workers: runtime.as_ref().map_or(0, |m| m.num_workers()),
+            tokio_alive_tasks: runtime.as_ref().map_or(0, |m| m.num_alive_tasks()),
+            tokio_global_queue_depth: runtime.as_ref().map_or(0, |m| m.global_queue_depth()),
+        }
+    }
+}
+
+/// User plus system CPU ticks from the contents of `/proc/<pid>/stat`
+pub fn parse_cpu_ticks(stat: &str) -> Option<u64> {
+    // The command name may contain spaces, so fields are counted after it
+    let fields: Vec<&str> = stat[stat.rfind(')')? + 1..].split_whitespace().collect();
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    Some(utime + stime)
+}
+
+/// Resident set size from the contents of `/proc/<pid>/status`
+pub fn parse_rss_bytes(status: &str) -> Option<u64> {
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
@@ -0,0 +1,167 @@
+use anyhow::{bail, Result};
+use futures::future::join_all;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
+use crate::config::{FlightConfig, GrpcConfig, HttpConfig, SurgicalStrikeConfig};
+use crate::flush_scheduler::FlushScheduler;
+use crate::server;
+use crate::SurgicalStrikeOrchestrator;
+
+/// Runs an independent Writer/Compaction/Vacuum trio per table on one
+/// runtime, with a single HTTP API routing requests by table name.
+pub struct MultiTableOrchestrator {
+    tables: Vec<SurgicalStrikeOrchestrator>,
+    /// Shared API settings, taken from the first table that enables HTTP
+    http: Option<HttpConfig>,
+    /// Shared Flight SQL settings, taken from the first table that enables it
+    flight: Option<FlightConfig>,
+    /// Shared gRPC admin settings, taken from the first table that enables it
+    grpc: Option<GrpcConfig>,
+    shutdown: CancellationToken,
+}
+
+impl MultiTableOrchestrator {
+    /// Open every configured table
+    pub async fn new(configs: Vec<SurgicalStrikeConfig>) -> Result<Self> {
+        if configs.is_empty() {
+            bail!("No tables configured");
+        }
+
+        let http = configs.iter().find(|c| c.http.enabled).map(|c| c.http.clone());
+        let flight = configs.iter().find(|c| c.flight.enabled).map(|c| c.flight.clone());
+        let grpc = configs.iter().find(|c| c.grpc.enabled).map(|c| c.grpc.clone());
+        let scheduler = configs
+            .iter()
+            .map(|c| c.scheduling.max_concurrent_flushes)
+            .find(|&slots| slots > 0)
+            .map(FlushScheduler::new);
+        let shutdown = CancellationToken::new();
+
+        let mut names = BTreeMap::new();
+        let mut tables = Vec::with_capacity(configs.len());
+        for mut config in configs {
+            if let Some(other) = names.insert(config.table_name().to_string(), config.table_uri.clone()) {
+                bail!(
+                    "Tables {} and {} share the API name '{}'",
+                    other,
+                    config.table_uri,
+                    config.table_name()
+                );
+            }
+
+            // The API, Flight SQL and gRPC are served once for all tables
+            // below; batches they acknowledge still need a dead letter queue
+            if (http.is_some() || grpc.is_some()) && config.writer.dead_letter_uri.is_none() {
+                config.writer.dead_letter_uri = Some(config.fallback_dead_letter_uri());
+            }
+            config.http.enabled = false;
+            config.flight.enabled = false;
+            config.grpc.enabled = false;
+            let mut orchestrator = SurgicalStrikeOrchestrator::new(config)
+                .await?
+                .with_shutdown_token(shutdown.clone());
+            if let Some(scheduler) = &scheduler {
+                orchestrator = orchestrator.with_flush_scheduler(scheduler.clone());
+            }
+            tables.push(orchestrator);
+        }
+
+        Ok(Self { tables, http, flight, grpc, shutdown })
+    }
+
+    /// Run every table's processes until shutdown. A failure in one table
+    /// shuts the others down gracefully.
+    pub async fn start(&self) -> Result<()> {
+        log::info!("Starting orchestrators for {} tables", self.tables.len());
+
+        let (results, served, flight, grpc) = tokio::join!(
+            join_all(self.tables.iter().map(|table| async move {
+                let result = table.start().await;
+                if result.is_err() {
+                    // Let the other tables drain instead of being dropped mid-write
+                    self.shutdown.cancel();
+                }
+                result
+            })),
+            self.serve_http(),
+            self.serve_flight(),
+            self.serve_grpc(),
+        );
+
+        results.into_iter().collect::<Result<Vec<_>>>()?;
+        served?;
+        flight?;
+        grpc
+    }
+
+    async fn serve_http(&self) -> Result<()> {
+        let Some(http) = &self.http else {
+            return Ok(());
+        };
+
+        let state = server::ApiState {
+            tables: Arc::new(
+                self.tables
+                    .iter()
+                    .map(|t| (t.config().table_name().to_string(), t.api_endpoint()))
+                    .collect(),
+            ),
+            process_metrics: Default::default(),
+            shutdown: self.shutdown.clone(),
+        };
+        server::serve(state, http.clone(), self.shutdown.clone()).await
+    }
+
+    #[cfg(feature = "flight")]
+    async fn serve_flight(&self) -> Result<()> {
+        let Some(flight) = &self.flight else {
+            return Ok(());
+        };
+        let tables = self
+            .tables
+            .iter()
+            .map(|t| (t.config().table_name().to_string(), t.table_handle()))
+            .collect();
+        crate::flight::serve(crate::flight::FlightSqlServer::new(tables), flight.clone(), self.shutdown.clone()).await
+    }
+
+    #[cfg(not(feature = "flight"))]
+    async fn serve_flight(&self) -> Result<()> {
+        if self.flight.is_some() {
+            bail!("Flight SQL endpoint enabled but the `flight` feature is not enabled");
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "grpc")]
+    async fn serve_grpc(&self) -> Result<()> {
+        let Some(grpc) = &self.grpc else {
+            return Ok(());
+        };
+        let tables = self
+            .tables
+            .iter()
+            .map(|t| (t.config().table_name().to_string(), t.admin_table()))
+            .collect();
+        crate::admin::serve(crate::admin::AdminService::new(tables), grpc.clone(), self.shutdown.clone()).await
+    }
+
+    #[cfg(not(feature = "grpc"))]
+    async fn serve_grpc(&self) -> Result<()> {
+        if self.grpc.is_some() {
+            bail!("gRPC admin API enabled but the `grpc` feature is not enabled");
+        }
+        Ok(())
+    }
+
+    /// Request a graceful shutdown of every table
+    pub fn shutdown(&self) {
+        self.shutdown.cancel();
+    }
+
+    /// The per-table orchestrators, in config order
+    pub fn tables(&self) -> &[SurgicalStrikeOrchestrator] {
+        &self.tables
+    }
+}
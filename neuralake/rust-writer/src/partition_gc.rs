@@ -0,0 +1,137 @@
+use anyhow::{Context, Result};
+use deltalake::{DeltaTable, ObjectMeta, ObjectStoreError};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashSet};
+use std::path::Path as FsPath;
+use crate::inventory::FileInventory;
+use crate::storage::StorageBackend;
+
+/// Suffix of the zero-byte objects Hadoop tools create for directories
+const HADOOP_MARKER_SUFFIX: &str = "_$folder$";
+
+/// Partition directories left without data, and what was removed with them
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PartitionCleanup {
+    /// Partition paths (`col=value/...`) that no longer exist at all; nested
+    /// partitions of a removed one are not listed separately
+    pub partitions_removed: Vec<String>,
+    /// Zero-byte directory marker objects deleted
+    pub markers_removed: Vec<String>,
+    /// Empty local directories deleted
+    pub directories_removed: usize,
+}
+
+/// Delete directory markers and empty local directories of partitions that
+/// hold no data file any more, going by `inventory`. Run after vacuum, once
+/// files past retention are gone; a partition still holding any file is
+/// left alone.
+pub async fn remove_empty_partitions(
+    table: &DeltaTable,
+    inventory: &FileInventory,
+    dry_run: bool,
+) -> Result<PartitionCleanup> {
+    let store = table.object_store();
+    let mut occupied = HashSet::new();
+    let mut markers = Vec::new();
+    for meta in inventory.objects() {
+        let path = meta.location.as_ref();
+        // `_delta_log`, `_manifest` and friends are not partitions
+        if path.starts_with('_') {
+            continue;
+        }
+        match marker_partition(meta) {
+            Some(partition) => markers.push((meta, partition.to_string())),
+            None => occupied.extend(partition_ancestors(path)),
+        }
+    }
+
+    let mut cleanup = PartitionCleanup::default();
+    let mut removed = BTreeSet::new();
+    for (meta, partition) in markers {
+        if occupied.contains(partition.as_str()) {
+            continue;
+        }
+        if !dry_run {
+            match store.delete(&meta.location).await {
+                Ok(()) | Err(ObjectStoreError::NotFound { .. }) => {}
+                Err(e) => return Err(e).with_context(|| format!("Failed to delete marker {}", meta.location)),
+            }
+        }
+        cleanup.markers_removed.push(meta.location.to_string());
+        removed.insert(partition);
+    }
+
+    // Local stores have real directories, which outlive their files
+    if StorageBackend::from_uri(&table.table_uri()) == StorageBackend::Local {
+        if let Ok(root) = table.log_store().config().location.to_file_path() {
+            let mut directories = Vec::new();
+            prune_directories(&root, "", dry_run, &mut directories)?;
+            cleanup.directories_removed = directories.len();
+            removed.extend(directories);
+        }
+    }
+
+    // Report each removed tree once, by its top-most partition
+    cleanup.partitions_removed = removed
+        .iter()
+        .filter(|partition| !removed.iter().any(|other| partition.starts_with(&format!("{}/", other))))
+        .cloned()
+        .collect();
+    Ok(cleanup)
+}
+
+/// Partition a zero-byte directory marker stands for. S3 `col=value/` keys
+/// surface as `col=value` once the trailing slash is dropped.
+fn marker_partition(meta: &ObjectMeta) -> Option<&str> {
+    if meta.size != 0 {
+        return None;
+    }
+    let path = meta.location.as_ref();
+    let path = path.strip_suffix(HADOOP_MARKER_SUFFIX).unwrap_or(path);
+    (!path.is_empty() && path.split('/').all(is_partition_segment)).then_some(path)
+}
+
+/// Every partition prefix a data file lives under, e.g. `a=1` and `a=1/b=2`
+fn partition_ancestors(path: &str) -> Vec<&str> {
+    let mut ancestors = Vec::new();
+    let mut end = 0;
+    let segments: Vec<&str> = path.split('/').collect();
+    for segment in &segments[..segments.len().saturating_sub(1)] {
+        if !is_partition_segment(segment) {
+            break;
+        }
+        end += segment.len() + usize::from(end > 0);
+        ancestors.push(&path[..end]);
+    }
+    ancestors
+}
+
+fn is_partition_segment(segment: &str) -> bool {
+    segment.contains('=')
+}
+
+/// Remove empty partition directories below `dir` bottom-up, adding their
+/// paths relative to the table root to `removed`. Returns whether `dir` is
+/// (or, in dry-run mode, would be) left empty.
+fn prune_directories(dir: &FsPath, relative: &str, dry_run: bool, removed: &mut Vec<String>) -> Result<bool> {
+    let mut empty = true;
+    let entries = std::fs::read_dir(dir).with_context(|| format!("Failed to list {}", dir.display()))?;
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !entry.file_type()?.is_dir() || !is_partition_segment(&name) {
+            empty = false;
+            continue;
+        }
+        let child = if relative.is_empty() { name } else { format!("{}/{}", relative, name) };
+        // Removal fails harmlessly if a writer just put a file there
+        if prune_directories(&entry.path(), &child, dry_run, removed)?
+            && (dry_run || std::fs::remove_dir(entry.path()).is_ok())
+        {
+            removed.push(child);
+        } else {
+            empty = false;
+        }
+    }
+    Ok(empty)
+}
@@ -0,0 +1,155 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use deltalake::kernel::{Action, Add};
+use deltalake::logstore::LogStoreRef;
+use deltalake::DeltaTable;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+/// Label used for tables without partition columns
+const UNPARTITIONED: &str = "(unpartitioned)";
+
+/// Data written to one partition within the window
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PartitionWrites {
+    /// `col=value` pairs in partition column order
+    pub partition: String,
+    pub rows: i64,
+    pub bytes: i64,
+    pub files: u64,
+}
+
+impl fmt::Display for PartitionWrites {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:<40}  {:>12}  {:>14}  {:>6}",
+            self.partition, self.rows, self.bytes, self.files
+        )
+    }
+}
+
+/// Rolling window of data-changing file additions, keyed by partition
+#[derive(Debug, Clone)]
+pub struct PartitionWriteTracker {
+    window: ChronoDuration,
+    events: VecDeque<(DateTime<Utc>, PartitionWrites)>,
+}
+
+impl PartitionWriteTracker {
+    pub fn new(window: std::time::Duration) -> Self {
+        Self {
+            window: ChronoDuration::from_std(window).unwrap_or(ChronoDuration::MAX),
+            events: VecDeque::new(),
+        }
+    }
+
+    /// Record the files a commit added. Files added without `dataChange`
+    /// (compaction rewrites) are not new writes and are ignored.
+    pub fn record(&mut self, at: DateTime<Utc>, adds: &[Add], partition_columns: &[String]) {
+        for add in adds.iter().filter(|a| a.data_change) {
+            let rows = add.get_stats().ok().flatten().map_or(0, |s| s.num_records);
+            self.events.push_back((
+                at,
+                PartitionWrites {
+                    partition: partition_key(add, partition_columns),
+                    rows,
+                    bytes: add.size,
+                    files: 1,
+                },
+            ));
+        }
+        self.prune(Utc::now());
+    }
+
+    /// The `n` partitions with the most rows written within the window
+    pub fn top(&self, n: usize, now: DateTime<Utc>) -> Vec<PartitionWrites> {
+        let cutoff = now - self.window;
+        let mut totals: HashMap<&str, PartitionWrites> = HashMap::new();
+        for (_, writes) in self.events.iter().filter(|(at, _)| *at >= cutoff) {
+            let total = totals.entry(&writes.partition).or_insert_with(|| PartitionWrites {
+                partition: writes.partition.clone(),
+                ..Default::default()
+            });
+            total.rows += writes.rows;
+            total.bytes += writes.bytes;
+            total.files += writes.files;
+        }
+
+        let mut top: Vec<PartitionWrites> = totals.into_values().collect();
+        top.sort_by(|a, b| b.rows.cmp(&a.rows).then(b.bytes.cmp(&a.bytes)).then(a.partition.cmp(&b.partition)));
+        top.truncate(n);
+        top
+    }
+
+    fn prune(&mut self, now: DateTime<Utc>) {
+        let cutoff = now - self.window;
+        while self.events.front().is_some_and(|(at, _)| *at < cutoff) {
+            self.events.pop_front();
+        }
+    }
+}
+
+/// `col=value/col2=value2` for the file's partition, in partition column order
+pub fn partition_key(add: &Add, partition_columns: &[String]) -> String {
+    if partition_columns.is_empty() {
+        return UNPARTITIONED.to_string();
+    }
+    partition_columns
+        .iter()
+        .map(|column| {
+            let value = add.partition_values.get(column).cloned().flatten();
+            format!("{}={}", column, value.as_deref().unwrap_or("null"))
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Commit timestamp and added files of one log entry
+pub async fn read_commit_adds(log_store: &LogStoreRef, version: i64) -> Result<(Option<DateTime<Utc>>, Vec<Add>)> {
+    let bytes = log_store
+        .read_commit_entry(version)
+        .await?
+        .with_context(|| format!("Commit {} not found", version))?;
+
+    let mut timestamp = None;
+    let mut adds = Vec::new();
+    for line in bytes.split(|b| *b == b'\n').filter(|l| !l.is_empty()) {
+        match serde_json::from_slice::<Action>(line)? {
+            Action::CommitInfo(info) => timestamp = info.timestamp.and_then(DateTime::from_timestamp_millis),
+            Action::Add(add) => adds.push(add),
+            _ => {}
+        }
+    }
+    Ok((timestamp, adds))
+}
+
+/// Rebuild the window from the Delta log, walking back from the latest
+/// commit until one is older than `window`
+pub async fn recent_partition_writes(table: &DeltaTable, window: std::time::Duration) -> Result<PartitionWriteTracker> {
+    let partition_columns = table.metadata()?.partition_columns.clone();
+    let log_store = table.log_store();
+    let mut tracker = PartitionWriteTracker::new(window);
+    let cutoff = Utc::now() - tracker.window;
+
+    let mut version = table.version();
+    while version >= 0 {
+        let (timestamp, adds) = match read_commit_adds(&log_store, version).await {
+            Ok(commit) => commit,
+            // Older entries may already be cleaned up after a checkpoint
+            Err(e) => {
+                log::debug!("Stopping partition scan at version {}: {:#}", version, e);
+                break;
+            }
+        };
+        let Some(timestamp) = timestamp else { break };
+        if timestamp < cutoff {
+            break;
+        }
+        tracker.record(timestamp, &adds, &partition_columns);
+        version -= 1;
+    }
+
+    Ok(tracker)
+}
@@ -0,0 +1,320 @@
+use anyhow::{Context, Result};
+use deltalake::{DeltaTable, DeltaTableBuilder, DeltaTableError};
+use serde::Serialize;
+use std::fmt;
+use crate::config::{DriftPolicy, ReplicationRole, SurgicalStrikeConfig};
+use crate::drift::{self, Drift};
+use crate::retention;
+use crate::schedule::CronSchedule;
+use crate::stats::STATS_COLUMNS_PROPERTY;
+use crate::storage::{self, StorageBackend};
+
+/// What `start` would do for one table, worked out without changing it
+#[derive(Debug, Clone, Serialize)]
+pub struct TablePlan {
+    pub name: String,
+    pub table_uri: String,
+    /// Current version, or `None` if the table does not exist yet
+    pub version: Option<i64>,
+    pub read_only: bool,
+    /// Changes made to the table or its surroundings before any loop starts
+    pub changes: Vec<String>,
+    /// Differences from the declared expectations, and the policy applied to them
+    pub drifts: Vec<Drift>,
+    pub drift_policy: DriftPolicy,
+    pub sources: Vec<String>,
+    /// Processes that would run, with their schedules
+    pub processes: Vec<String>,
+    /// Problems that would make `start` fail or misbehave
+    pub problems: Vec<String>,
+}
+
+/// What `start` would do across every configured table
+#[derive(Debug, Clone, Serialize)]
+pub struct StartPlan {
+    pub tables: Vec<TablePlan>,
+    /// Endpoints shared by all tables
+    pub endpoints: Vec<String>,
+}
+
+impl StartPlan {
+    /// Whether `start` is expected to fail
+    pub fn has_problems(&self) -> bool {
+        self.tables.iter().any(|table| !table.problems.is_empty())
+    }
+}
+
+impl fmt::Display for TablePlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.version {
+            Some(version) => writeln!(f, "Table {} ({}) at version {}", self.name, self.table_uri, version)?,
+            None => writeln!(f, "Table {} ({}), not created yet", self.name, self.table_uri)?,
+        }
+        if self.read_only {
+            writeln!(f, "  Read-only: nothing is written to the table")?;
+        }
+        for change in &self.changes {
+            writeln!(f, "  + {}", change)?;
+        }
+        for drift in &self.drifts {
+            writeln!(
+                f,
+                "  ~ {}: expected {}, found {} ({:?} policy)",
+                drift.subject, drift.expected, drift.actual, self.drift_policy
+            )?;
+        }
+        for problem in &self.problems {
+            writeln!(f, "  ! {}", problem)?;
+        }
+        if !self.sources.is_empty() {
+            writeln!(f, "  Sources:")?;
+            for source in &self.sources {
+                writeln!(f, "    {}", source)?;
+            }
+        }
+        writeln!(f, "  Processes:")?;
+        for process in &self.processes {
+            writeln!(f, "    {}", process)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for StartPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for table in &self.tables {
+            write!(f, "{}", table)?;
+        }
+        if !self.endpoints.is_empty() {
+            writeln!(f, "Endpoints:")?;
+            for endpoint in &self.endpoints {
+                writeln!(f, "  {}", endpoint)?;
+            }
+        }
+        let changes: usize = self.tables.iter().map(|t| t.changes.len()).sum();
+        let problems: usize = self.tables.iter().map(|t| t.problems.len()).sum();
+        writeln!(f, "Plan: {} tables, {} changes, {} problems", self.tables.len(), changes, problems)
+    }
+}
+
+/// Resolve what `start` would do with `configs`: open every table without
+/// writing to it and list the tables, sources, schedules, drift and the
+/// creations and property changes startup would make
+pub async fn plan_start(configs: &[SurgicalStrikeConfig]) -> Result<StartPlan> {
+    storage::register_handlers();
+    let mut tables = Vec::with_capacity(configs.len());
+    for config in configs {
+        tables.push(plan_table(config).await?);
+    }
+
+    let mut endpoints = Vec::new();
+    if let Some(http) = configs.iter().find(|c| c.http.enabled).map(|c| &c.http) {
+        endpoints.push(format!("HTTP API on {}", http.bind_address));
+    }
+    if let Some(flight) = configs.iter().find(|c| c.flight.enabled).map(|c| &c.flight) {
+        endpoints.push(format!("Flight SQL on {}", flight.bind_address));
+    }
+    if let Some(grpc) = configs.iter().find(|c| c.grpc.enabled).map(|c| &c.grpc) {
+        endpoints.push(format!("gRPC admin API on {}", grpc.bind_address));
+    }
+    if let Some(slots) = configs.iter().map(|c| c.scheduling.max_concurrent_flushes).find(|&slots| slots > 0) {
+        endpoints.push(format!("{} flush slots shared between tables", slots));
+    }
+    Ok(StartPlan { tables, endpoints })
+}
+
+async fn plan_table(config: &SurgicalStrikeConfig) -> Result<TablePlan> {
+    let mut plan = TablePlan {
+        name: config.table_name().to_string(),
+        table_uri: config.table_uri.clone(),
+        version: None,
+        read_only: config.read_only,
+        changes: Vec::new(),
+        drifts: Vec::new(),
+        drift_policy: config.expectations.policy,
+        sources: sources(config),
+        processes: Vec::new(),
+        problems: Vec::new(),
+    };
+    if plan.sources.len() > 1 {
+        plan.problems.push("More than one source is configured".to_string());
+    }
+
+    let mut storage_options = config.storage_options.0.clone();
+    let backend = StorageBackend::from_uri(&config.table_uri);
+    if config.locking.enabled && backend != StorageBackend::Local {
+        if backend != StorageBackend::S3 {
+            plan.problems.push("DynamoDB commit locking only applies to s3:// tables".to_string());
+        } else {
+            if config.locking.create_table && !config.read_only {
+                plan.changes.push(format!("ensure DynamoDB lock table {}", config.locking.table_name));
+            }
+            config.locking.apply_to(&mut storage_options);
+        }
+    }
+    if let Some(azure) = &config.azure {
+        azure.apply_to(&mut storage_options);
+    }
+
+    let loaded = DeltaTableBuilder::from_uri(&config.table_uri)
+        .with_storage_options(storage_options)
+        .load()
+        .await;
+    match loaded {
+        Ok(table) => {
+            plan.version = Some(table.version());
+            if !config.read_only {
+                plan_table_changes(&table, config, &mut plan)?;
+            }
+        }
+        Err(DeltaTableError::NotATable(_)) => match &config.schema {
+            Some(schema) if !config.read_only => {
+                let partitions: Vec<&str> =
+                    schema.columns.iter().filter(|c| c.partition).map(|c| c.name.as_str()).collect();
+                plan.changes.push(format!(
+                    "create the table with {} declared columns, partitioned by {:?}",
+                    schema.columns.len(),
+                    partitions
+                ));
+            }
+            _ => plan.problems.push("The table does not exist and no schema is declared to create it".to_string()),
+        },
+        Err(e) => return Err(e).with_context(|| format!("Failed to open Delta table at {}", config.table_uri)),
+    }
+
+    plan.processes = processes(config);
+    Ok(plan)
+}
+
+/// Property changes and drift handling of an existing table
+fn plan_table_changes(table: &DeltaTable, config: &SurgicalStrikeConfig, plan: &mut TablePlan) -> Result<()> {
+    let report = drift::check_drift(table, &config.expectations)?;
+    match config.expectations.policy {
+        DriftPolicy::Fail if !report.drifts.is_empty() => {
+            plan.problems.push("The table drifted from its expectations and the policy is to fail".to_string());
+        }
+        DriftPolicy::Reconcile if report.drifts.iter().any(|d| !d.reconcilable) => {
+            plan.problems.push("The table drifted in ways reconciling cannot fix".to_string());
+        }
+        DriftPolicy::Reconcile => {
+            for drift in &report.drifts {
+                plan.changes.push(format!("reconcile {} to {}", drift.subject, drift.expected));
+            }
+        }
+        _ => {}
+    }
+    plan.drifts = report.drifts;
+
+    if !config.stats_columns.is_empty() {
+        let schema = table.get_schema()?;
+        let unknown: Vec<&String> = config.stats_columns.iter().filter(|c| schema.field(c).is_none()).collect();
+        let value = config.stats_columns.join(",");
+        let current = table.metadata()?.configuration.get(STATS_COLUMNS_PROPERTY).cloned().flatten();
+        if !unknown.is_empty() {
+            plan.problems.push(format!("Stats columns {:?} are not in the table schema", unknown));
+        } else if current.as_deref() != Some(value.as_str()) {
+            plan.changes.push(format!("set {}={}", STATS_COLUMNS_PROPERTY, value));
+        }
+    }
+
+    if let Some(days) = config.time_travel_days {
+        let window = retention::time_travel_window(table)?;
+        if window < chrono::Duration::days(days as i64) {
+            plan.changes.push(format!(
+                "raise log and deleted-file retention from {} to {} days",
+                window.num_days(),
+                days
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn sources(config: &SurgicalStrikeConfig) -> Vec<String> {
+    let mut sources = Vec::new();
+    if let Some(kafka) = &config.kafka {
+        sources.push(format!("Kafka topic {} as group {}", kafka.topic, kafka.group_id));
+    }
+    if let Some(pulsar) = &config.pulsar {
+        let topics = match &pulsar.topic_pattern {
+            Some(pattern) => format!("topics matching {}", pattern),
+            None => format!("topics {:?}", pulsar.topics),
+        };
+        sources.push(format!("Pulsar {} as subscription {}", topics, pulsar.subscription));
+    }
+    if let Some(kinesis) = &config.kinesis {
+        sources.push(format!("Kinesis stream {}", kinesis.stream_name));
+    }
+    if let Some(sqs) = &config.sqs {
+        sources.push(format!("S3 notifications from SQS queue {}", sqs.queue_url));
+    }
+    if let Some(postgres_cdc) = &config.postgres_cdc {
+        sources.push(format!("PostgreSQL table {} from slot {}", postgres_cdc.table, postgres_cdc.slot));
+    }
+    sources
+}
+
+fn processes(config: &SurgicalStrikeConfig) -> Vec<String> {
+    let mut processes = Vec::new();
+    if !config.read_only {
+        let writer = &config.writer;
+        processes.push(format!(
+            "writer: flush at {} rows or every {:?}",
+            writer.max_batch_size,
+            writer.flush_alignment().unwrap_or(writer.max_batch_time())
+        ));
+        let schedule = |cron: Option<&CronSchedule>, secs: u64| match cron {
+            Some(cron) => format!("at '{}' UTC", cron),
+            None => format!("every {}s", secs),
+        };
+        processes.push(format!(
+            "compaction: {}",
+            schedule(config.compaction.compaction_schedule.as_ref(), config.compaction.compaction_interval_secs)
+        ));
+        processes.push(format!(
+            "vacuum: {}{}, retaining {} hours{}",
+            schedule(config.vacuum.vacuum_schedule.as_ref(), config.vacuum.vacuum_interval_secs),
+            if config.vacuum.after_compaction { " and after each compaction" } else { "" },
+            config.vacuum.retention_hours,
+            if config.vacuum.dry_run { " (dry run)" } else { "" }
+        ));
+        if config.checkpoint.checkpoint_interval > 0 {
+            processes.push(format!(
+                "checkpoint: every {} commits, checked every {}s",
+                config.checkpoint.checkpoint_interval, config.checkpoint.check_interval_secs
+            ));
+        }
+        if let Some(archive) = &config.archive {
+            processes.push(format!(
+                "archive: partitions older than {} days to {} every {}s",
+                archive.max_age_days, archive.archive_table_uri, archive.archive_interval_secs
+            ));
+        }
+        if config.locking.enabled {
+            processes.push("lock monitor: recovering commits of crashed writers".to_string());
+        }
+        match config.replication.role {
+            ReplicationRole::None => {}
+            ReplicationRole::Primary => processes.push(format!(
+                "replication: primary streaming to {}",
+                config.replication.standby_addr.as_deref().unwrap_or("-")
+            )),
+            ReplicationRole::Standby => {
+                processes.push(format!("replication: standby listening on {}", config.replication.listen_addr))
+            }
+        }
+        for sink in &config.sinks {
+            processes.push(format!("sink: {}", sink.name));
+        }
+        if config.jobs.enabled {
+            processes.push(format!("jobs: polling {} every {}s", config.jobs.db_path, config.jobs.poll_interval_secs));
+        }
+    }
+    if config.commit_feed.enabled {
+        processes.push("commit feed".to_string());
+    }
+    if config.quality.enabled {
+        processes.push("quality checks on every new version".to_string());
+    }
+    processes
+}
@@ -0,0 +1,213 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use deltalake::datafusion::prelude::SessionContext;
+use deltalake::{DeltaTable, StorageOptions};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use tokio::time::interval;
+use tokio_util::sync::CancellationToken;
+use crate::alerts::Alerter;
+use crate::config::{AlertKind, QualityConfig, QualityTest, QualityTestKind};
+
+/// A data test that found offending rows or could not run
+#[derive(Debug, Clone, Serialize)]
+pub struct TestFailure {
+    pub name: String,
+    pub failing_rows: usize,
+    /// Set when the test query itself failed
+    pub error: Option<String>,
+}
+
+/// Outcome of the most recent test run
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct QualityStatus {
+    /// Latest version that passed every test. With quarantine enabled this
+    /// is the watermark the commit feed is held at.
+    pub validated_version: Option<i64>,
+    pub last_checked_version: Option<i64>,
+    pub last_checked: Option<DateTime<Utc>>,
+    pub failures: Vec<TestFailure>,
+}
+
+pub type QualityStatusHandle = Arc<Mutex<QualityStatus>>;
+
+/// Runs the configured data tests against each new table version
+#[derive(Debug)]
+pub struct QualityProcess {
+    config: QualityConfig,
+    status: QualityStatusHandle,
+    alerts: Arc<Alerter>,
+}
+
+impl QualityProcess {
+    pub fn new(config: QualityConfig) -> Self {
+        Self { config, status: Default::default(), alerts: Arc::default() }
+    }
+
+    /// Raise versions failing data tests through `alerts`
+    pub fn with_alerts(mut self, alerts: Arc<Alerter>) -> Self {
+        self.alerts = alerts;
+        self
+    }
+
+    pub fn status(&self) -> QualityStatusHandle {
+        self.status.clone()
+    }
+
+    /// Poll for new versions until shutdown. Uses its own table handle so
+    /// long-running tests never hold up the writer.
+    pub async fn run(&self, table_uri: &str, storage_options: &StorageOptions, shutdown: CancellationToken) -> Result<()> {
+        let mut table = deltalake::open_table_with_storage_options(table_uri, storage_options.0.clone())
+            .await
+            .context("Failed to open table for data tests")?;
+        // Versions committed before startup are trusted
+        self.status.lock().unwrap().validated_version = Some(table.version());
+
+        log::info!("Running {} data tests on new versions of {}", self.config.tests.len(), table_uri);
+
+        let mut poll = interval(self.config.poll_interval());
+        loop {
+            tokio::select! {
+                _ = poll.tick() => {
+                    if let Err(e) = table.update().await {
+                        log::warn!("Data tests failed to refresh {}: {}", table_uri, e);
+                        continue;
+                    }
+                    let version = table.version();
+                    if self.status.lock().unwrap().last_checked_version >= Some(version) {
+                        continue;
+                    }
+
+                    let failures = self.check(&table, storage_options).await;
+                    for failure in &failures {
+                        match &failure.error {
+                            Some(error) => log::error!(
+                                "Data test '{}' could not run on {} version {}: {}",
+                                failure.name, table_uri, version, error
+                            ),
+                            None => log::error!(
+                                "Data test '{}' failed on {} version {}: {} offending rows",
+                                failure.name, table_uri, version, failure.failing_rows
+                            ),
+                        }
+                    }
+                    if !failures.is_empty() {
+                        let names: Vec<&str> = failures.iter().map(|f| f.name.as_str()).collect();
+                        self.alerts.fire(
+                            AlertKind::ValidationSpike,
+                            format!("Version {} failed data tests: {}", version, names.join(", ")),
+                        );
+                    }
+
+                    let mut status = self.status.lock().unwrap();
+                    if failures.is_empty() || !self.config.quarantine {
+                        status.validated_version = Some(version);
+                    } else {
+                        log::warn!(
+                            "Quarantining {} at version {} until data tests pass",
+                            table_uri,
+                            status.validated_version.unwrap_or_default()
+                        );
+                    }
+                    status.last_checked_version = Some(version);
+                    status.last_checked = Some(Utc::now());
+                    status.failures = failures;
+                }
+                _ = shutdown.cancelled() => {
+                    log::info!("Data test runner received shutdown signal");
+                    break;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Run every test against the loaded version of `table`
+    pub async fn check(&self, table: &DeltaTable, storage_options: &StorageOptions) -> Vec<TestFailure> {
+        let mut failures = Vec::new();
+        for test in &self.config.tests {
+            match run_test(table, test, storage_options).await {
+                Ok(0) => {}
+                Ok(failing_rows) => failures.push(TestFailure { name: test.name.clone(), failing_rows, error: None }),
+                Err(e) => failures.push(TestFailure {
+                    name: test.name.clone(),
+                    failing_rows: 0,
+                    error: Some(format!("{:#}", e)),
+                }),
+            }
+        }
+        failures
+    }
+}
+
+/// Number of rows the test's query returns
+async fn run_test(table: &DeltaTable, test: &QualityTest, storage_options: &StorageOptions) -> Result<usize> {
+    let ctx = SessionContext::new();
+    ctx.register_table("this", Arc::new(table.clone()))?;
+    if let QualityTestKind::Relationship { to_table_uri, .. } = &test.kind {
+        let referenced = deltalake::open_table_with_storage_options(to_table_uri, storage_options.0.clone())
+            .await
+            .with_context(|| format!("Failed to open referenced table {}", to_table_uri))?;
+        ctx.register_table("ref", Arc::new(referenced))?;
+    }
+
+    let sql = test_sql(&test.kind);
+    let failing_rows = ctx
+        .sql(&sql)
+        .await
+        .with_context(|| format!("Invalid test query: {}", sql))?
+        .count()
+        .await?;
+    Ok(failing_rows)
+}
+
+/// Query returning the rows that violate a test, against `this` (and `ref`
+/// for relationships)
+pub fn test_sql(kind: &QualityTestKind) -> String {
+    match kind {
+        QualityTestKind::Unique { columns } => {
+            let columns = columns.iter().map(|c| quote(c)).collect::<Vec<_>>().join(", ");
+            format!(
+                "SELECT {}, COUNT(*) AS occurrences FROM this GROUP BY {} HAVING COUNT(*) > 1",
+                columns, columns
+            )
+        }
+        QualityTestKind::NotNull { column } => {
+            format!("SELECT * FROM this WHERE {} IS NULL", quote(column))
+        }
+        QualityTestKind::RowCount { min, max } => {
+            let mut conditions: Vec<String> = Vec::new();
+            if let Some(min) = min {
+                conditions.push(format!("row_count < {}", min));
+            }
+            if let Some(max) = max {
+                conditions.push(format!("row_count > {}", max));
+            }
+            if conditions.is_empty() {
+                conditions.push("FALSE".to_string());
+            }
+            format!(
+                "SELECT row_count FROM (SELECT COUNT(*) AS row_count FROM this) counts WHERE {}",
+                conditions.join(" OR ")
+            )
+        }
+        QualityTestKind::Freshness { column, max_age_secs } => format!(
+            "SELECT latest FROM (SELECT MAX({}) AS latest FROM this) freshness \
+             WHERE latest IS NULL OR latest < NOW() - INTERVAL '{} seconds'",
+            quote(column),
+            max_age_secs
+        ),
+        QualityTestKind::Relationship { column, to_column, .. } => format!(
+            "SELECT this.{column} FROM this LEFT JOIN ref ON this.{column} = ref.{to_column} \
+             WHERE this.{column} IS NOT NULL AND ref.{to_column} IS NULL",
+            column = quote(column),
+            to_column = quote(to_column)
+        ),
+        QualityTestKind::Sql { query } => query.clone(),
+    }
+}
+
+fn quote(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
@@ -0,0 +1,65 @@
+use anyhow::{Context, Result};
+use deltalake::arrow::array::RecordBatch;
+use deltalake::arrow::json::ArrayWriter;
+use deltalake::arrow::util::pretty::pretty_format_batches;
+use deltalake::datafusion::execution::context::SQLOptions;
+use deltalake::datafusion::prelude::SessionContext;
+use deltalake::datafusion::sql::TableReference;
+use deltalake::DeltaTable;
+use serde_json::Value;
+use std::fmt;
+use std::sync::Arc;
+
+/// Rows returned by an ad-hoc query
+#[derive(Debug, Clone)]
+pub struct QueryResult {
+    pub batches: Vec<RecordBatch>,
+}
+
+impl QueryResult {
+    pub fn num_rows(&self) -> usize {
+        self.batches.iter().map(|b| b.num_rows()).sum()
+    }
+
+    /// The rows as an array of JSON objects keyed by column name
+    pub fn to_json(&self) -> Result<Value> {
+        let mut writer = ArrayWriter::new(Vec::new());
+        writer.write_batches(&self.batches.iter().collect::<Vec<_>>())
+            .context("Failed to encode rows as JSON")?;
+        writer.finish()?;
+        let bytes = writer.into_inner();
+        if bytes.is_empty() {
+            return Ok(Value::Array(Vec::new()));
+        }
+        serde_json::from_slice(&bytes).context("Failed to encode rows as JSON")
+    }
+}
+
+impl fmt::Display for QueryResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match pretty_format_batches(&self.batches) {
+            Ok(table) => writeln!(f, "{}", table)?,
+            Err(e) => writeln!(f, "(could not format rows: {})", e)?,
+        }
+        writeln!(f, "{} rows", self.num_rows())
+    }
+}
+
+/// Run a read-only SQL query against `table`, registered under `name`
+pub async fn run_query(table: DeltaTable, name: &str, sql: &str) -> Result<QueryResult> {
+    let ctx = SessionContext::new();
+    ctx.register_table(TableReference::bare(name), Arc::new(table))
+        .context("Failed to register table")?;
+    let options = SQLOptions::new()
+        .with_allow_ddl(false)
+        .with_allow_dml(false)
+        .with_allow_statements(false);
+    let batches = ctx
+        .sql_with_options(sql, options)
+        .await
+        .context("Failed to plan query")?
+        .collect()
+        .await
+        .context("Failed to run query")?;
+    Ok(QueryResult { batches })
+}
@@ -0,0 +1,386 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, NaiveDate};
+use deltalake::arrow::datatypes::{DataType, Schema as ArrowSchema, TimeUnit};
+use deltalake::kernel::{Action, Add, StructType};
+use deltalake::logstore::ObjectStoreRef;
+use deltalake::operations::transaction::{CommitBuilder, CommitProperties};
+use deltalake::parquet::arrow::parquet_to_arrow_schema;
+use deltalake::parquet::file::metadata::{ParquetMetaData, ParquetMetaDataReader};
+use deltalake::parquet::file::statistics::Statistics;
+use deltalake::protocol::{DeltaOperation, SaveMode};
+use deltalake::{DeltaTable, ObjectMeta};
+use futures::{stream, StreamExt, TryStreamExt};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use crate::import::{glob_match, split_glob};
+
+/// Parquet footers read at once while registering
+const FOOTER_CONCURRENCY: usize = 16;
+/// Hive's directory name for null partition values
+const HIVE_NULL_PARTITION: &str = "__HIVE_DEFAULT_PARTITION__";
+
+/// Outcome of registering existing files with a table
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RegisterReport {
+    /// Version that added the files; `None` if there was nothing to add
+    pub version: Option<i64>,
+    pub files_registered: usize,
+    /// Matching files the table already references
+    pub files_skipped: usize,
+    pub rows: i64,
+    pub bytes: u64,
+    /// Partitions compacted after registering (`optimize_after_import`)
+    pub partitions_compacted: Vec<String>,
+}
+
+impl fmt::Display for RegisterReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} files ({} rows, {} bytes) registered, {} already in the table",
+            self.files_registered, self.rows, self.bytes, self.files_skipped
+        )?;
+        if let Some(version) = self.version {
+            write!(f, " (version {})", version)?;
+        }
+        if !self.partitions_compacted.is_empty() {
+            write!(f, ", {} partitions compacted", self.partitions_compacted.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// Add the Parquet files matching `source` to `table` as they are, in one
+/// commit. `source` is a pattern below the table root, either relative to it
+/// or prefixed with `table_uri`; files are referenced in place, never copied
+/// or rewritten. Each file's schema must fit the table's, its statistics
+/// are taken from the Parquet footer and, for partitioned tables, its
+/// partition values from `column=value` directories in its path.
+pub async fn register_files(table: &mut DeltaTable, table_uri: &str, source: &str) -> Result<RegisterReport> {
+    let relative = relative_to_root(table_uri, source)?;
+    let (prefix, pattern) = split_glob(relative);
+    let store = table.object_store();
+    let prefix_path = (!prefix.is_empty()).then(|| deltalake::Path::from(prefix.trim_end_matches('/')));
+
+    let active: HashSet<String> = table.get_files_iter()?.map(|path| path.to_string()).collect();
+    let mut files: Vec<ObjectMeta> = store
+        .list(prefix_path.as_ref())
+        .try_filter(|meta| {
+            let path = meta.location.as_ref();
+            let wanted = if pattern.is_empty() { path.ends_with(".parquet") } else { glob_match(relative, path) };
+            futures::future::ready(wanted && !path.starts_with("_delta_log/"))
+        })
+        .try_collect()
+        .await
+        .with_context(|| format!("Failed to list {}", source))?;
+    if files.is_empty() {
+        bail!("No Parquet files match {}", source);
+    }
+    files.sort_by(|a, b| a.location.cmp(&b.location));
+
+    let mut report = RegisterReport::default();
+    files.retain(|meta| {
+        let known = active.contains(meta.location.as_ref());
+        report.files_skipped += known as usize;
+        !known
+    });
+    if files.is_empty() {
+        return Ok(report);
+    }
+
+    let table_schema = table.get_schema()?.clone();
+    let partition_columns = table.metadata()?.partition_columns.clone();
+    let adds = add_actions(store, files, &table_schema, &partition_columns).await?;
+    for add in &adds {
+        report.files_registered += 1;
+        report.bytes += add.size as u64;
+        report.rows += add.get_stats().ok().flatten().map_or(0, |s| s.num_records);
+    }
+
+    let operation = DeltaOperation::Write {
+        mode: SaveMode::Append,
+        partition_by: (!partition_columns.is_empty()).then_some(partition_columns),
+        predicate: None,
+    };
+    let commit_properties = CommitProperties::default()
+        .with_metadata(vec![("registeredFrom".to_string(), Value::from(source))]);
+    let commit = CommitBuilder::from(commit_properties)
+        .with_actions(adds.into_iter().map(Action::Add).collect())
+        .build(Some(table.snapshot()?), table.log_store(), operation)
+        .await
+        .context("Failed to commit registered files")?;
+    table.update_incremental(Some(commit.version())).await
+        .context("Failed to load committed version")?;
+    report.version = Some(commit.version());
+    Ok(report)
+}
+
+/// Add actions for `files`, checked against `schema`, reading the footers
+/// concurrently
+pub(crate) async fn add_actions(
+    store: ObjectStoreRef,
+    files: Vec<ObjectMeta>,
+    schema: &StructType,
+    partition_columns: &[String],
+) -> Result<Vec<Add>> {
+    stream::iter(files)
+        .map(|meta| {
+            let store = store.clone();
+            async move {
+                let path = meta.location.to_string();
+                let footer = read_footer(&store, &meta).await?;
+                let file_schema = parquet_to_arrow_schema(
+                    footer.file_metadata().schema_descr(),
+                    footer.file_metadata().key_value_metadata(),
+                )
+                .with_context(|| format!("Failed to read the schema of {}", path))?;
+                check_schema(&file_schema, schema, partition_columns)
+                    .with_context(|| format!("{} does not match the table schema", path))?;
+                let partition_values = partition_values_from_path(&path, partition_columns)?;
+                Ok(Add {
+                    path,
+                    partition_values: partition_values.into_iter().collect(),
+                    size: meta.size as i64,
+                    modification_time: meta.last_modified.timestamp_millis(),
+                    data_change: true,
+                    stats: Some(file_stats(&footer, &file_schema).to_string()),
+                    ..Default::default()
+                })
+            }
+        })
+        .buffered(FOOTER_CONCURRENCY)
+        .try_collect()
+        .await
+}
+
+/// `source` relative to the table root
+fn relative_to_root<'a>(table_uri: &str, source: &'a str) -> Result<&'a str> {
+    if let Some(rest) = source.strip_prefix(table_uri.trim_end_matches('/')) {
+        if rest.is_empty() || rest.starts_with('/') {
+            return Ok(rest.trim_start_matches('/'));
+        }
+    }
+    if source.contains("://") || source.starts_with('/') {
+        bail!("{} is not below the table root {}; files are registered in place", source, table_uri);
+    }
+    Ok(source)
+}
+
+/// Fetch and decode the footer of a Parquet file with two ranged reads
+pub(crate) async fn read_footer(store: &ObjectStoreRef, meta: &ObjectMeta) -> Result<ParquetMetaData> {
+    let size = meta.size as u64;
+    if size < 12 {
+        bail!("{} is too small to be a Parquet file", meta.location);
+    }
+    let tail = store
+        .get_range(&meta.location, size - 8..size)
+        .await
+        .with_context(|| format!("Failed to read {}", meta.location))?;
+    if &tail[4..] != b"PAR1" {
+        bail!("{} is not a Parquet file", meta.location);
+    }
+    let length = u32::from_le_bytes(tail[..4].try_into().unwrap()) as u64;
+    if length + 8 > size {
+        bail!("{} has a corrupt footer", meta.location);
+    }
+    let footer = store
+        .get_range(&meta.location, size - 8 - length..size - 8)
+        .await
+        .with_context(|| format!("Failed to read {}", meta.location))?;
+    ParquetMetaDataReader::decode_metadata(&footer)
+        .with_context(|| format!("Failed to decode the footer of {}", meta.location))
+}
+
+/// Every file column must be a table column of the same type, and every
+/// table column missing from the file nullable
+fn check_schema(file: &ArrowSchema, table: &StructType, partition_columns: &[String]) -> Result<()> {
+    let file = StructType::try_from(file).context("Unsupported column types")?;
+    for field in file.fields() {
+        match table.field(field.name()) {
+            None => bail!("Column {} is not in the table", field.name()),
+            Some(expected) if expected.data_type() != field.data_type() => bail!(
+                "Column {} is {}, the table has {}",
+                field.name(),
+                field.data_type(),
+                expected.data_type()
+            ),
+            Some(_) => {}
+        }
+    }
+    for field in table.fields() {
+        let stored = file.field(field.name()).is_some() || partition_columns.contains(field.name());
+        if !stored && !field.is_nullable() {
+            bail!("Column {} is NOT NULL but missing", field.name());
+        }
+    }
+    Ok(())
+}
+
+/// Partition values encoded in `column=value` segments of `path`
+pub fn partition_values_from_path(path: &str, partition_columns: &[String]) -> Result<Vec<(String, Option<String>)>> {
+    let segments: HashMap<&str, &str> = path
+        .split('/')
+        .filter_map(|segment| segment.split_once('='))
+        .collect();
+    partition_columns
+        .iter()
+        .map(|column| {
+            let value = segments
+                .get(column.as_str())
+                .with_context(|| format!("{} has no {}= directory for its partition value", path, column))?;
+            let value = (*value != HIVE_NULL_PARTITION).then(|| percent_decode(value));
+            Ok((column.clone(), value))
+        })
+        .collect()
+}
+
+/// Undo the `%XX` escaping Hive applies to partition directory names
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = (bytes[i] == b'%')
+            .then(|| value.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match hex {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// A min or max value of a column chunk
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+enum Bound {
+    Int(i64),
+    Float(f64),
+    Text(String),
+    Bool(bool),
+}
+
+/// Delta statistics JSON for a file, from its row group statistics. Min/max
+/// are kept for top-level columns whose every row group has them; other
+/// columns only get a null count, if that.
+pub(crate) fn file_stats(footer: &ParquetMetaData, schema: &ArrowSchema) -> Value {
+    let row_groups = footer.row_groups();
+    let num_records: i64 = row_groups.iter().map(|rg| rg.num_rows()).sum();
+    let mut min_values = Map::new();
+    let mut max_values = Map::new();
+    let mut null_count = Map::new();
+
+    for field in schema.fields() {
+        // Top-level leaf columns map one to one onto arrow fields
+        let leaf = footer
+            .file_metadata()
+            .schema_descr()
+            .columns()
+            .iter()
+            .position(|c| c.path().parts().len() == 1 && c.name() == field.name());
+        let Some(leaf) = leaf else { continue };
+
+        let mut nulls = Some(0i64);
+        let mut range: Option<(Bound, Bound)> = None;
+        let mut complete = true;
+        for row_group in row_groups {
+            let Some(stats) = row_group.column(leaf).statistics() else {
+                nulls = None;
+                complete = false;
+                continue;
+            };
+            nulls = nulls.zip(stats.null_count_opt()).map(|(total, n)| total + n as i64);
+            match bounds(stats) {
+                Some((min, max)) => {
+                    range = Some(match range {
+                        None => (min, max),
+                        Some((lo, hi)) => (
+                            if min < lo { min } else { lo },
+                            if max > hi { max } else { hi },
+                        ),
+                    });
+                }
+                // A chunk of only nulls has no bounds and narrows nothing
+                None if row_group.num_rows() as u64 == stats.null_count_opt().unwrap_or(0) => {}
+                None => complete = false,
+            }
+        }
+
+        if let Some(nulls) = nulls {
+            null_count.insert(field.name().clone(), json!(nulls));
+        }
+        if let (true, Some((min, max))) = (complete, range) {
+            if let (Some(min), Some(max)) = (to_json(min, field.data_type()), to_json(max, field.data_type())) {
+                min_values.insert(field.name().clone(), min);
+                max_values.insert(field.name().clone(), max);
+            }
+        }
+    }
+
+    json!({
+        "numRecords": num_records,
+        "minValues": min_values,
+        "maxValues": max_values,
+        "nullCount": null_count,
+    })
+}
+
+fn bounds(stats: &Statistics) -> Option<(Bound, Bound)> {
+    let pair = |min: Option<Bound>, max: Option<Bound>| min.zip(max);
+    match stats {
+        Statistics::Boolean(s) => pair(s.min_opt().map(|v| Bound::Bool(*v)), s.max_opt().map(|v| Bound::Bool(*v))),
+        Statistics::Int32(s) => pair(s.min_opt().map(|v| Bound::Int(*v as i64)), s.max_opt().map(|v| Bound::Int(*v as i64))),
+        Statistics::Int64(s) => pair(s.min_opt().map(|v| Bound::Int(*v)), s.max_opt().map(|v| Bound::Int(*v))),
+        Statistics::Float(s) => pair(s.min_opt().map(|v| Bound::Float(*v as f64)), s.max_opt().map(|v| Bound::Float(*v as f64))),
+        Statistics::Double(s) => pair(s.min_opt().map(|v| Bound::Float(*v)), s.max_opt().map(|v| Bound::Float(*v))),
+        // A truncated max is no upper bound; a truncated min still is a lower one
+        Statistics::ByteArray(s) => pair(
+            s.min_opt().and_then(|v| v.as_utf8().ok()).map(|v| Bound::Text(v.to_string())),
+            s.max_opt()
+                .filter(|_| s.max_is_exact())
+                .and_then(|v| v.as_utf8().ok())
+                .map(|v| Bound::Text(v.to_string())),
+        ),
+        _ => None,
+    }
+}
+
+/// A bound as Delta statistics spell it for the column type; `None` for
+/// types whose bounds are not recorded
+fn to_json(bound: Bound, data_type: &DataType) -> Option<Value> {
+    match (bound, data_type) {
+        (Bound::Int(v), DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64) => Some(json!(v)),
+        (Bound::Float(v), DataType::Float32 | DataType::Float64) if v.is_finite() => Some(json!(v)),
+        (Bound::Text(v), DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View) => Some(json!(v)),
+        (Bound::Int(days), DataType::Date32) => {
+            let epoch = NaiveDate::from_ymd_opt(1970, 1, 1)?;
+            Some(json!(epoch.checked_add_signed(chrono::Duration::days(days))?.format("%Y-%m-%d").to_string()))
+        }
+        (Bound::Int(v), DataType::Timestamp(unit, tz)) => {
+            let micros = match unit {
+                TimeUnit::Second => v.checked_mul(1_000_000)?,
+                TimeUnit::Millisecond => v.checked_mul(1_000)?,
+                TimeUnit::Microsecond => v,
+                // Rounding to microseconds could move a bound past real values
+                TimeUnit::Nanosecond => return None,
+            };
+            let at = DateTime::from_timestamp_micros(micros)?;
+            let formatted = if tz.is_some() {
+                at.format("%Y-%m-%dT%H:%M:%S%.6fZ").to_string()
+            } else {
+                at.naive_utc().format("%Y-%m-%dT%H:%M:%S%.6f").to_string()
+            };
+            Some(json!(formatted))
+        }
+        _ => None,
+    }
+}
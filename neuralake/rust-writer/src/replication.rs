@@ -0,0 +1,386 @@
+use anyhow::{anyhow, bail, Context, Result};
+use deltalake::kernel::Transaction;
+use deltalake::{DeltaTable, StorageOptions};
+use polars::prelude::*;
+use std::collections::BTreeMap;
+use std::io::Cursor;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, Mutex};
+use tokio::time::{interval, sleep_until, timeout, Instant};
+use tokio_util::sync::CancellationToken;
+use crate::auth;
+use crate::config::{ReplicationConfig, ReplicationRole};
+use crate::writer::{QueuedBatch, WriterProcess};
+
+/// Message types on the primary -> standby stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameKind {
+    /// A queued batch: its id, length-prefixed, then its rows as an Arrow
+    /// IPC stream
+    Batch = 1,
+    /// Sent by the standby once it holds the batch with the same sequence
+    Ack = 2,
+    /// Liveness signal carrying the highest committed sequence
+    Heartbeat = 3,
+    /// First frame of a connection, carrying the primary's `auth_token`
+    Hello = 4,
+}
+
+struct Frame {
+    kind: FrameKind,
+    seq: i64,
+    payload: Vec<u8>,
+}
+
+/// Synchronous replication of the writer queue to a warm standby.
+///
+/// The primary numbers each batch and only queues (and so acknowledges) it
+/// once the standby holds a copy. The writer commits the highest sequence it
+/// has flushed as an application transaction, so on takeover the standby
+/// replays exactly the batches the table does not contain yet.
+#[derive(Debug)]
+pub struct Replicator {
+    config: ReplicationConfig,
+    app_id: String,
+    link: Mutex<PrimaryLink>,
+    /// Highest sequence the primary's writer has committed
+    committed: AtomicI64,
+    /// Standby only: batches not yet known to be committed
+    buffer: std::sync::Mutex<BTreeMap<i64, QueuedBatch>>,
+    /// Standby only: set once it has taken over and accepts writes
+    active: AtomicBool,
+}
+
+#[derive(Debug)]
+struct PrimaryLink {
+    stream: Option<TcpStream>,
+    next_seq: i64,
+}
+
+impl Replicator {
+    /// Continue numbering after the last sequence committed to `table`
+    pub fn new(config: ReplicationConfig, table: &DeltaTable) -> Result<Self> {
+        if config.role == ReplicationRole::Primary && config.standby_addr.is_none() {
+            bail!("Replication role primary requires standby_addr");
+        }
+
+        let app_id = format!("replication:{}", table.table_uri());
+        let committed = table
+            .get_app_transaction_version()
+            .get(&app_id)
+            .map_or(0, |txn| txn.version);
+
+        Ok(Self {
+            config,
+            app_id,
+            link: Mutex::new(PrimaryLink { stream: None, next_seq: committed + 1 }),
+            committed: AtomicI64::new(committed),
+            buffer: std::sync::Mutex::new(BTreeMap::new()),
+            active: AtomicBool::new(false),
+        })
+    }
+
+    pub fn role(&self) -> ReplicationRole {
+        self.config.role
+    }
+
+    /// Highest sequence committed to the table so far
+    pub fn committed_seq(&self) -> i64 {
+        self.committed.load(Ordering::SeqCst)
+    }
+
+    /// Application transaction marking batches up to `seq` as committed
+    pub fn checkpoint(&self, seq: i64) -> Transaction {
+        Transaction::new(&self.app_id, seq)
+    }
+
+    /// Queue a batch for the writer. A primary first waits for the standby to
+    /// hold it; a standby refuses writes until it has taken over.
+    pub async fn queue(&self, batch: QueuedBatch, queue: &mpsc::Sender<QueuedBatch>) -> Result<()> {
+        match self.config.role {
+            ReplicationRole::Standby if !self.active.load(Ordering::SeqCst) => {
+                bail!("Standby is not accepting writes until it takes over")
+            }
+            ReplicationRole::Primary => {
+                // Held until the batch is queued so queue order matches sequence order
+                let mut link = self.link.lock().await;
+                let seq = link.next_seq;
+                if let Err(e) = self.replicate(&mut link, seq, &batch).await {
+                    link.stream = None;
+                    return Err(e).with_context(|| format!("Standby did not acknowledge batch {}", batch.id));
+                }
+                queue.send(batch).await.map_err(|_| anyhow!("Writer queue is closed"))?;
+                link.next_seq += 1;
+                Ok(())
+            }
+            _ => queue.send(batch).await.map_err(|_| anyhow!("Writer queue is closed")),
+        }
+    }
+
+    async fn replicate(&self, link: &mut PrimaryLink, seq: i64, batch: &QueuedBatch) -> Result<()> {
+        let mut payload = Vec::new();
+        payload.extend_from_slice(&(batch.id.len() as u16).to_be_bytes());
+        payload.extend_from_slice(batch.id.as_bytes());
+        IpcStreamWriter::new(&mut payload)
+            .finish(&mut batch.df.clone())
+            .context("Failed to encode batch for standby")?;
+
+        let stream = self.connect(link).await?;
+        timeout(self.config.ack_timeout(), async {
+            write_frame(stream, FrameKind::Batch, seq, &payload).await?;
+            loop {
+                let frame = read_frame(stream).await?;
+                if frame.kind == FrameKind::Ack && frame.seq == seq {
+                    return Ok(());
+                }
+            }
+        })
+        .await
+        .map_err(|_| anyhow!("Timed out waiting for standby acknowledgement"))?
+    }
+
+    async fn connect<'a>(&self, link: &'a mut PrimaryLink) -> Result<&'a mut TcpStream> {
+        if link.stream.is_none() {
+            let addr = self.config.standby_addr.as_deref().unwrap_or_default();
+            let mut stream = timeout(self.config.ack_timeout(), TcpStream::connect(addr))
+                .await
+                .map_err(|_| anyhow!("Timed out connecting to standby {}", addr))?
+                .with_context(|| format!("Failed to connect to standby {}", addr))?;
+            stream.set_nodelay(true)?;
+            if let Some(token) = &self.config.auth_token {
+                write_frame(&mut stream, FrameKind::Hello, 0, token.as_bytes()).await?;
+            }
+            log::info!("Connected to standby {}", addr);
+            link.stream = Some(stream);
+        }
+        Ok(link.stream.as_mut().unwrap())
+    }
+
+    /// Record that the writer committed every batch up to `seq`. Never waits
+    /// on the link: the next heartbeat tells the standby if it is busy.
+    pub async fn committed(&self, seq: i64) {
+        self.committed.fetch_max(seq, Ordering::SeqCst);
+        if let Ok(mut link) = self.link.try_lock() {
+            if link.stream.is_some() {
+                self.heartbeat(&mut link).await;
+            }
+        }
+    }
+
+    async fn heartbeat(&self, link: &mut PrimaryLink) {
+        let result = match self.connect(link).await {
+            Ok(stream) => write_frame(stream, FrameKind::Heartbeat, self.committed_seq(), &[]).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            log::warn!("Failed to reach standby: {:#}", e);
+            link.stream = None;
+        }
+    }
+
+    /// Keep the standby connected and informed until shutdown (primary only)
+    pub async fn run_primary(&self, shutdown: CancellationToken) -> Result<()> {
+        log::info!(
+            "Replicating writer queue to standby {}",
+            self.config.standby_addr.as_deref().unwrap_or_default()
+        );
+
+        let mut interval_timer = interval(self.config.heartbeat_interval());
+        loop {
+            tokio::select! {
+                _ = interval_timer.tick() => {
+                    let mut link = self.link.lock().await;
+                    self.heartbeat(&mut link).await;
+                }
+                _ = shutdown.cancelled() => {
+                    log::info!("Replication received shutdown signal");
+                    break;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Follow the primary, taking over once it has been silent for
+    /// `takeover_timeout` (standby only)
+    pub async fn run_standby(
+        &self,
+        writer: &WriterProcess,
+        table: Arc<Mutex<DeltaTable>>,
+        storage_options: &StorageOptions,
+        shutdown: CancellationToken,
+    ) -> Result<()> {
+        let listener = TcpListener::bind(&self.config.listen_addr)
+            .await
+            .with_context(|| format!("Failed to bind standby listener on {}", self.config.listen_addr))?;
+        log::info!("Standby listening for the primary on {}", self.config.listen_addr);
+        auth::warn_if_exposed("Standby listener", &self.config.listen_addr, self.config.auth_token.as_deref());
+
+        // The takeover clock only starts once a primary has been seen
+        let mut last_seen: Option<Instant> = None;
+        loop {
+            let takeover_at = last_seen.map(|seen| seen + self.config.takeover_timeout());
+            tokio::select! {
+                accepted = listener.accept() => {
+                    let (mut stream, peer) = accepted.context("Failed to accept primary connection")?;
+                    if let Err(e) = self.authenticate(&mut stream).await {
+                        log::warn!("Rejected replication connection from {}: {:#}", peer, e);
+                        continue;
+                    }
+                    log::info!("Primary connected from {}", peer);
+                    last_seen = Some(self.follow(stream, &shutdown).await);
+                }
+                _ = sleep_until(takeover_at.unwrap_or_else(Instant::now)), if takeover_at.is_some() => {
+                    return self.take_over(writer, table, storage_options).await;
+                }
+                _ = shutdown.cancelled() => {
+                    log::info!("Standby received shutdown signal");
+                    break;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// With an `auth_token` configured, require it in the connection's first
+    /// frame. Rejected connections do not count as the primary being alive.
+    async fn authenticate(&self, stream: &mut TcpStream) -> Result<()> {
+        let Some(token) = &self.config.auth_token else {
+            return Ok(());
+        };
+        let frame = timeout(self.config.ack_timeout(), read_frame(stream))
+            .await
+            .map_err(|_| anyhow!("No hello frame within {:?}", self.config.ack_timeout()))??;
+        let presented = String::from_utf8_lossy(&frame.payload);
+        if frame.kind != FrameKind::Hello || !auth::token_matches(token, &presented) {
+            bail!("Missing or invalid auth token");
+        }
+        Ok(())
+    }
+
+    /// Buffer the primary's batches until the connection closes or goes
+    /// silent, returning when the primary was last heard from
+    async fn follow(&self, mut stream: TcpStream, shutdown: &CancellationToken) -> Instant {
+        let mut last_seen = Instant::now();
+        loop {
+            let frame = tokio::select! {
+                frame = timeout(self.config.takeover_timeout(), read_frame(&mut stream)) => frame,
+                _ = shutdown.cancelled() => return last_seen,
+            };
+            let frame = match frame {
+                Ok(Ok(frame)) => frame,
+                Ok(Err(e)) => {
+                    log::warn!("Lost connection to primary: {:#}", e);
+                    return last_seen;
+                }
+                Err(_) => {
+                    log::warn!("Primary silent for {:?}", self.config.takeover_timeout());
+                    return last_seen;
+                }
+            };
+            last_seen = Instant::now();
+
+            match frame.kind {
+                FrameKind::Batch => {
+                    let batch = match decode_batch(frame.payload) {
+                        Ok(batch) => batch,
+                        Err(e) => {
+                            log::error!("Undecodable batch {} from primary: {:#}", frame.seq, e);
+                            return last_seen;
+                        }
+                    };
+                    self.buffer.lock().unwrap().insert(frame.seq, batch);
+                    if let Err(e) = write_frame(&mut stream, FrameKind::Ack, frame.seq, &[]).await {
+                        log::warn!("Failed to acknowledge batch {}: {:#}", frame.seq, e);
+                        return last_seen;
+                    }
+                }
+                FrameKind::Heartbeat => {
+                    self.committed.fetch_max(frame.seq, Ordering::SeqCst);
+                    let mut buffer = self.buffer.lock().unwrap();
+                    *buffer = buffer.split_off(&(frame.seq + 1));
+                }
+                FrameKind::Ack | FrameKind::Hello => {}
+            }
+        }
+    }
+
+    /// Replay buffered batches the table does not contain, then accept writes
+    async fn take_over(
+        &self,
+        writer: &WriterProcess,
+        table: Arc<Mutex<DeltaTable>>,
+        storage_options: &StorageOptions,
+    ) -> Result<()> {
+        let (table_uri, committed) = {
+            let mut table = table.lock().await;
+            table.update().await
+                .context("Failed to refresh table before takeover")?;
+            let committed = table
+                .get_app_transaction_version()
+                .get(&self.app_id)
+                .map_or(0, |txn| txn.version);
+            (table.table_uri(), committed)
+        };
+
+        let pending = self.buffer.lock().unwrap().split_off(&(committed + 1));
+        log::warn!(
+            "Primary lost, taking over {} with {} uncommitted batches after sequence {}",
+            table_uri,
+            pending.len(),
+            committed
+        );
+
+        for (seq, batch) in pending {
+            writer
+                .write_batches(batch.df, &[batch.id.clone()], &[self.checkpoint(seq)], Vec::new(), storage_options, &table_uri)
+                .await
+                .with_context(|| format!("Failed to replay batch {} ({}) on takeover", seq, batch.id))?;
+        }
+
+        self.active.store(true, Ordering::SeqCst);
+        log::warn!("Standby has taken over writes to {}", table_uri);
+        Ok(())
+    }
+}
+
+/// Split a batch frame payload into the batch id and its rows
+fn decode_batch(payload: Vec<u8>) -> Result<QueuedBatch> {
+    let (len, rest) = payload.split_first_chunk::<2>().context("Truncated batch frame")?;
+    let len = u16::from_be_bytes(*len) as usize;
+    if rest.len() < len {
+        bail!("Truncated batch id");
+    }
+    let id = String::from_utf8(rest[..len].to_vec()).context("Batch id is not UTF-8")?;
+    let df = IpcStreamReader::new(Cursor::new(&rest[len..])).finish()?;
+    Ok(QueuedBatch { id, df })
+}
+
+async fn write_frame(stream: &mut TcpStream, kind: FrameKind, seq: i64, payload: &[u8]) -> Result<()> {
+    stream.write_u8(kind as u8).await?;
+    stream.write_i64(seq).await?;
+    stream.write_u32(payload.len() as u32).await?;
+    stream.write_all(payload).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+async fn read_frame(stream: &mut TcpStream) -> Result<Frame> {
+    let kind = match stream.read_u8().await? {
+        1 => FrameKind::Batch,
+        2 => FrameKind::Ack,
+        3 => FrameKind::Heartbeat,
+        4 => FrameKind::Hello,
+        other => bail!("Unknown replication frame type {}", other),
+    };
+    let seq = stream.read_i64().await?;
+    let len = stream.read_u32().await?;
+    let mut payload = vec![0; len as usize];
+    stream.read_exact(&mut payload).await?;
+    Ok(Frame { kind, seq, payload })
+}
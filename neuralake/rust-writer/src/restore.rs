@@ -0,0 +1,103 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use deltalake::{DeltaOps, DeltaTable, Path};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt;
+
+/// Point in the table history to restore
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestoreTarget {
+    Version(i64),
+    /// The latest version committed at or before this time
+    Timestamp(DateTime<Utc>),
+}
+
+/// What restoring the table to `target_version` changes
+#[derive(Debug, Clone, Serialize)]
+pub struct RestorePlan {
+    pub current_version: i64,
+    pub target_version: i64,
+    /// Files active at the target but not now, which are re-added
+    pub files_to_add: Vec<String>,
+    /// Files active now but not at the target, which are removed
+    pub files_to_remove: Vec<String>,
+}
+
+impl fmt::Display for RestorePlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Restore from version {} to version {}", self.current_version, self.target_version)?;
+        writeln!(f, "Files to add back: {}", self.files_to_add.len())?;
+        for file in &self.files_to_add {
+            writeln!(f, "  + {}", file)?;
+        }
+        writeln!(f, "Files to remove: {}", self.files_to_remove.len())?;
+        for file in &self.files_to_remove {
+            writeln!(f, "  - {}", file)?;
+        }
+        Ok(())
+    }
+}
+
+/// Work out what restoring `table` to `target` would change. A timestamp is
+/// resolved to a version here, so applying the plan later restores exactly
+/// the version that was previewed.
+pub async fn plan_restore(table: &DeltaTable, target: RestoreTarget) -> Result<RestorePlan> {
+    let mut snapshot = table.clone();
+    match target {
+        RestoreTarget::Version(version) => {
+            if version < 0 || version >= table.version() {
+                bail!("Cannot restore to version {}: table is at version {}", version, table.version());
+            }
+            snapshot.load_version(version).await
+                .with_context(|| format!("Failed to load version {}", version))?;
+        }
+        RestoreTarget::Timestamp(timestamp) => {
+            snapshot.load_with_datetime(timestamp).await
+                .with_context(|| format!("Failed to load the table as of {}", timestamp))?;
+            if snapshot.version() == table.version() {
+                bail!("Table has not changed since {} (version {})", timestamp, table.version());
+            }
+        }
+    }
+
+    let current = active_paths(table)?;
+    let restored = active_paths(&snapshot)?;
+    let mut plan = RestorePlan {
+        current_version: table.version(),
+        target_version: snapshot.version(),
+        files_to_add: restored.difference(&current).cloned().collect(),
+        files_to_remove: current.difference(&restored).cloned().collect(),
+    };
+    plan.files_to_add.sort();
+    plan.files_to_remove.sort();
+
+    let store = table.object_store();
+    for path in &plan.files_to_add {
+        if store.head(&Path::from(path.as_str())).await.is_err() {
+            bail!("Cannot restore to version {}: {} has already been vacuumed", plan.target_version, path);
+        }
+    }
+
+    Ok(plan)
+}
+
+/// Apply a plan from [`plan_restore`] with the Delta RESTORE operation,
+/// returning the refreshed table
+pub async fn apply_restore(table: DeltaTable, plan: &RestorePlan) -> Result<DeltaTable> {
+    let (table, metrics) = DeltaOps(table)
+        .restore()
+        .with_version_to_restore(plan.target_version)
+        .await
+        .with_context(|| format!("Failed to restore table to version {}", plan.target_version))?;
+    log::debug!(
+        "Restore re-added {} and removed {} files",
+        metrics.num_restored_file,
+        metrics.num_removed_file
+    );
+    Ok(table)
+}
+
+fn active_paths(table: &DeltaTable) -> Result<HashSet<String>> {
+    Ok(table.snapshot()?.file_actions()?.into_iter().map(|add| add.path).collect())
+}
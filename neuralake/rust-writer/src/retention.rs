@@ -0,0 +1,97 @@
+use anyhow::{Context, Result};
+use chrono::Duration;
+use deltalake::{DeltaOps, DeltaTable};
+use std::collections::HashMap;
+
+/// How long commit JSON files are kept once covered by a checkpoint
+pub const LOG_RETENTION_PROPERTY: &str = "delta.logRetentionDuration";
+/// How long files removed from the table are kept before vacuum may delete them
+pub const DELETED_FILE_RETENTION_PROPERTY: &str = "delta.deletedFileRetentionDuration";
+/// How long application transactions carrying a `lastUpdated` time are kept
+pub const TRANSACTION_RETENTION_PROPERTY: &str = "delta.setTransactionRetentionDuration";
+
+/// Delta's defaults for the two properties above
+const DEFAULT_LOG_RETENTION_DAYS: i64 = 30;
+const DEFAULT_DELETED_FILE_RETENTION_DAYS: i64 = 7;
+
+/// Parse a Delta interval such as `interval 30 days` or `interval 1 week`
+pub fn parse_interval(value: &str) -> Option<Duration> {
+    let mut words = value.split_whitespace();
+    if !words.next()?.eq_ignore_ascii_case("interval") {
+        return None;
+    }
+    let amount: i64 = words.next()?.parse().ok()?;
+    let unit = words.next()?.to_ascii_lowercase();
+    if words.next().is_some() {
+        return None;
+    }
+    match unit.trim_end_matches('s') {
+        "second" => Some(Duration::seconds(amount)),
+        "minute" => Some(Duration::minutes(amount)),
+        "hour" => Some(Duration::hours(amount)),
+        "day" => Some(Duration::days(amount)),
+        "week" => Some(Duration::weeks(amount)),
+        _ => None,
+    }
+}
+
+fn property_or(table: &DeltaTable, key: &str, default_days: i64) -> Result<Duration> {
+    let metadata = table.metadata()?;
+    Ok(metadata
+        .configuration
+        .get(key)
+        .cloned()
+        .flatten()
+        .and_then(|value| parse_interval(&value))
+        .unwrap_or_else(|| Duration::days(default_days)))
+}
+
+/// How far back every version stays readable: commits are kept for the log
+/// retention and the files they reference for the deleted-file retention,
+/// which vacuum refuses to go below
+pub fn time_travel_window(table: &DeltaTable) -> Result<Duration> {
+    let log = property_or(table, LOG_RETENTION_PROPERTY, DEFAULT_LOG_RETENTION_DAYS)?;
+    let files = property_or(table, DELETED_FILE_RETENTION_PROPERTY, DEFAULT_DELETED_FILE_RETENTION_DAYS)?;
+    Ok(log.min(files))
+}
+
+/// Raise the log and deleted-file retention of the table to `days` where
+/// they are shorter, so every version of the last `days` days can be read
+pub async fn ensure_time_travel(table: DeltaTable, days: u64) -> Result<DeltaTable> {
+    let required = Duration::days(days as i64);
+    let mut properties = HashMap::new();
+    for (key, default_days) in [
+        (LOG_RETENTION_PROPERTY, DEFAULT_LOG_RETENTION_DAYS),
+        (DELETED_FILE_RETENTION_PROPERTY, DEFAULT_DELETED_FILE_RETENTION_DAYS),
+    ] {
+        if property_or(&table, key, default_days)? < required {
+            properties.insert(key.to_string(), format!("interval {} days", days));
+        }
+    }
+    if properties.is_empty() {
+        return Ok(table);
+    }
+    log::info!("Setting {:?} on {} for {} days of time travel", properties, table.table_uri(), days);
+    DeltaOps(table)
+        .set_tbl_properties()
+        .with_properties(properties)
+        .await
+        .context("Failed to set retention properties")
+}
+
+/// Expire application transactions stamped more than `days` ago, which is
+/// how idempotency keys are forgotten. Source offsets carry no timestamp and
+/// are never expired.
+pub async fn ensure_transaction_retention(table: DeltaTable, days: u64) -> Result<DeltaTable> {
+    let value = format!("interval {} days", days);
+    let current = table.metadata()?.configuration.get(TRANSACTION_RETENTION_PROPERTY).cloned().flatten();
+    if current.as_deref() == Some(value.as_str()) {
+        return Ok(table);
+    }
+    log::info!("Setting {} on {} to {}", TRANSACTION_RETENTION_PROPERTY, table.table_uri(), value);
+    DeltaOps(table)
+        .set_tbl_properties()
+        .with_properties(HashMap::from([(TRANSACTION_RETENTION_PROPERTY.to_string(), value)]))
+        .await
+        .context("Failed to set transaction retention")
+}
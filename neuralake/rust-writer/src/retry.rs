@@ -0,0 +1,102 @@
+use deltalake::operations::transaction::TransactionError;
+use deltalake::{DeltaTableError, ObjectStoreError};
+use rand::Rng;
+use std::time::Duration;
+use crate::config::WriterConfig;
+use crate::guardrails::GuardrailViolation;
+
+/// An error that will fail the same way on every attempt, such as a schema
+/// mismatch. Retry loops give up on it immediately.
+#[derive(Debug, thiserror::Error)]
+#[error(transparent)]
+pub struct NonRetryable(#[from] anyhow::Error);
+
+/// Mark an error as not worth retrying
+pub fn non_retryable(error: anyhow::Error) -> anyhow::Error {
+    NonRetryable(error).into()
+}
+
+/// Whether another attempt could succeed. Unknown errors are assumed to be
+/// transient (throttling, 503s, network resets) and are retried.
+pub fn is_retryable(error: &anyhow::Error) -> bool {
+    if error.downcast_ref::<NonRetryable>().is_some() {
+        return false;
+    }
+
+    for cause in error.chain() {
+        if cause.is::<GuardrailViolation>() {
+            return false;
+        }
+        if let Some(e) = cause.downcast_ref::<DeltaTableError>() {
+            match e {
+                DeltaTableError::SchemaMismatch { .. }
+                | DeltaTableError::InvalidData { .. }
+                | DeltaTableError::Arrow { .. }
+                | DeltaTableError::NotATable(_) => return false,
+                _ => {}
+            }
+        }
+        if let Some(e) = cause.downcast_ref::<ObjectStoreError>() {
+            match e {
+                ObjectStoreError::PermissionDenied { .. }
+                | ObjectStoreError::Unauthenticated { .. }
+                | ObjectStoreError::NotImplemented => return false,
+                _ => {}
+            }
+        }
+    }
+
+    true
+}
+
+/// Whether a commit failed because another writer committed the same
+/// version first. The data files are still valid and can be committed again
+/// on top of the winning version.
+pub fn is_commit_conflict(error: &anyhow::Error) -> bool {
+    let is_conflict = |e: &TransactionError| {
+        matches!(
+            e,
+            TransactionError::VersionAlreadyExists(_)
+                | TransactionError::CommitConflict(_)
+                | TransactionError::MaxCommitAttempts(_)
+        )
+    };
+
+    error.chain().any(|cause| {
+        match cause.downcast_ref::<DeltaTableError>() {
+            Some(DeltaTableError::VersionAlreadyExists(_)) => return true,
+            Some(DeltaTableError::Transaction { source }) => return is_conflict(source),
+            _ => {}
+        }
+        cause.downcast_ref::<TransactionError>().is_some_and(is_conflict)
+    })
+}
+
+/// Delay before retry number `attempt` (starting at 1): `base_ms` grown by
+/// `multiplier` per attempt, capped at `max_ms`, then spread by up to ±`jitter`
+pub fn backoff_delay(base_ms: u64, multiplier: f64, max_ms: u64, jitter: f64, attempt: u32) -> Duration {
+    let exponent = attempt.saturating_sub(1) as i32;
+    let delay_ms = (base_ms as f64 * multiplier.powi(exponent)).min(max_ms as f64);
+
+    let jitter = jitter.clamp(0.0, 1.0);
+    let factor = if jitter > 0.0 {
+        rand::thread_rng().gen_range(1.0 - jitter..=1.0 + jitter)
+    } else {
+        1.0
+    };
+
+    Duration::from_millis((delay_ms * factor).max(0.0) as u64)
+}
+
+impl WriterConfig {
+    /// Delay before write retry number `attempt`, see [`backoff_delay`]
+    pub fn retry_backoff(&self, attempt: u32) -> Duration {
+        backoff_delay(
+            self.retry_delay_ms,
+            self.retry_backoff_multiplier,
+            self.max_retry_delay_ms,
+            self.retry_jitter,
+            attempt,
+        )
+    }
+}
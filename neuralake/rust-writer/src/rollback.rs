@@ -0,0 +1,221 @@
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use deltalake::kernel::{Action, Add, Remove};
+use deltalake::operations::transaction::{CommitBuilder, CommitProperties};
+use deltalake::protocol::{DeltaOperation, SaveMode};
+use deltalake::{DeltaOps, DeltaTable, Path};
+use serde_json::json;
+use std::collections::HashMap;
+use std::fmt;
+use crate::spark_history;
+
+/// How a bad commit range is undone
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RollbackStrategy {
+    /// One commit removing files the range added and re-adding files it
+    /// removed, recorded as a DELETE (or a WRITE when files come back) with
+    /// the range under `rollbackOf` in its commit info
+    Inverse,
+    /// RESTORE to the version before the range, for changes the inverse
+    /// cannot express (schema/protocol changes, files rewritten since)
+    Restore { reason: String },
+}
+
+/// What a rollback of versions `from_version..=to_version` will do
+#[derive(Debug, Clone)]
+pub struct RollbackPlan {
+    pub from_version: i64,
+    pub to_version: i64,
+    pub strategy: RollbackStrategy,
+    /// Files added by the range that will be removed
+    pub files_to_remove: Vec<String>,
+    /// Files removed by the range that will be added back
+    pub files_to_restore: Vec<String>,
+    removes: Vec<Add>,
+    restores: Vec<Add>,
+}
+
+impl fmt::Display for RollbackPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Rollback of versions {}..={}", self.from_version, self.to_version)?;
+        match &self.strategy {
+            RollbackStrategy::Inverse => writeln!(f, "Strategy: inverse commit")?,
+            RollbackStrategy::Restore { reason } => {
+                writeln!(f, "Strategy: RESTORE to version {} ({})", self.from_version - 1, reason)?
+            }
+        }
+        writeln!(f, "Files to remove: {}", self.files_to_remove.len())?;
+        for file in &self.files_to_remove {
+            writeln!(f, "  - {}", file)?;
+        }
+        writeln!(f, "Files to restore: {}", self.files_to_restore.len())?;
+        for file in &self.files_to_restore {
+            writeln!(f, "  + {}", file)?;
+        }
+        Ok(())
+    }
+}
+
+/// Work out how to undo versions `from_version..=to_version` of `table`
+pub async fn plan_rollback(table: &DeltaTable, from_version: i64, to_version: i64) -> Result<RollbackPlan> {
+    let latest = table.version();
+    if from_version < 1 || from_version > to_version || to_version > latest {
+        bail!(
+            "Invalid rollback range {}..={} for table at version {}",
+            from_version,
+            to_version,
+            latest
+        );
+    }
+
+    let before = snapshot_at(table, from_version - 1).await?;
+    let after = snapshot_at(table, to_version).await?;
+
+    let before_files = active_files(&before)?;
+    let after_files = active_files(&after)?;
+    let current_files = active_files(table)?;
+
+    let added: Vec<&Add> = after_files
+        .iter()
+        .filter(|(path, _)| !before_files.contains_key(*path))
+        .map(|(_, add)| add)
+        .collect();
+    let removed: Vec<&Add> = before_files
+        .iter()
+        .filter(|(path, _)| !after_files.contains_key(*path))
+        .map(|(_, add)| add)
+        .collect();
+
+    let mut plan = RollbackPlan {
+        from_version,
+        to_version,
+        strategy: RollbackStrategy::Inverse,
+        files_to_remove: added.iter().map(|a| a.path.clone()).collect(),
+        files_to_restore: removed.iter().map(|a| a.path.clone()).collect(),
+        removes: added.iter().map(|a| (*a).clone()).collect(),
+        restores: removed.iter().map(|a| (*a).clone()).collect(),
+    };
+    plan.files_to_remove.sort();
+    plan.files_to_restore.sort();
+
+    // Cases an inverse commit cannot express fall back to RESTORE
+    let fallback = if before.metadata()?.schema_string != after.metadata()?.schema_string
+        || before.metadata()?.partition_columns != after.metadata()?.partition_columns
+    {
+        Some("schema or partitioning changed in the range".to_string())
+    } else if before.protocol()? != after.protocol()? {
+        Some("protocol changed in the range".to_string())
+    } else if let Some(add) = added.iter().find(|a| !current_files.contains_key(&a.path)) {
+        Some(format!("{} was rewritten by a later commit", add.path))
+    } else {
+        removed
+            .iter()
+            .find(|a| current_files.contains_key(&a.path))
+            .map(|add| format!("{} was re-added by a later commit", add.path))
+    };
+
+    if let Some(reason) = fallback {
+        if to_version != latest {
+            bail!(
+                "Cannot roll back {}..={}: {}, and RESTORE would also undo versions {}..={}",
+                from_version,
+                to_version,
+                reason,
+                to_version + 1,
+                latest
+            );
+        }
+        plan.strategy = RollbackStrategy::Restore { reason };
+    }
+
+    // Both strategies need the removed files to still be in storage
+    let store = table.object_store();
+    for add in &plan.restores {
+        if store.head(&Path::from(add.path.as_str())).await.is_err() {
+            bail!(
+                "Cannot roll back {}..={}: {} has already been vacuumed",
+                from_version,
+                to_version,
+                add.path
+            );
+        }
+    }
+
+    Ok(plan)
+}
+
+/// Apply a plan from [`plan_rollback`], returning the refreshed table
+pub async fn apply_rollback(table: DeltaTable, plan: &RollbackPlan) -> Result<DeltaTable> {
+    let restore_version = plan.from_version - 1;
+
+    match &plan.strategy {
+        RollbackStrategy::Restore { .. } => {
+            let (table, _) = DeltaOps(table)
+                .restore()
+                .with_version_to_restore(restore_version)
+                .await
+                .with_context(|| format!("Failed to restore table to version {}", restore_version))?;
+            Ok(table)
+        }
+        RollbackStrategy::Inverse => {
+            let now = Utc::now().timestamp_millis();
+            let mut actions: Vec<Action> = plan
+                .removes
+                .iter()
+                .map(|add| {
+                    Action::Remove(Remove {
+                        path: add.path.clone(),
+                        data_change: true,
+                        deletion_timestamp: Some(now),
+                        extended_file_metadata: Some(true),
+                        partition_values: Some(add.partition_values.clone()),
+                        size: Some(add.size),
+                        tags: add.tags.clone(),
+                        ..Default::default()
+                    })
+                })
+                .collect();
+            actions.extend(plan.restores.iter().map(|add| {
+                Action::Add(Add { data_change: true, modification_time: now, ..add.clone() })
+            }));
+
+            // Not a RESTORE: the versions after the range stay in place
+            let metrics = spark_history::rollback_metrics(&plan.removes, &plan.restores);
+            let rollback_of = json!({ "fromVersion": plan.from_version, "toVersion": plan.to_version });
+            let commit_properties = CommitProperties::default().with_metadata(vec![
+                ("operationMetrics".to_string(), metrics),
+                ("rollbackOf".to_string(), rollback_of),
+            ]);
+
+            let mut table = table;
+            let operation = if plan.restores.is_empty() {
+                DeltaOperation::Delete { predicate: None }
+            } else {
+                DeltaOperation::Write { mode: SaveMode::Append, partition_by: None, predicate: None }
+            };
+            CommitBuilder::from(commit_properties)
+                .with_actions(actions)
+                .build(Some(table.snapshot()?), table.log_store(), operation)
+                .await
+                .context("Failed to commit rollback")?;
+            table.update().await?;
+            Ok(table)
+        }
+    }
+}
+
+async fn snapshot_at(table: &DeltaTable, version: i64) -> Result<DeltaTable> {
+    let mut snapshot = table.clone();
+    snapshot.load_version(version).await
+        .with_context(|| format!("Failed to load version {}", version))?;
+    Ok(snapshot)
+}
+
+fn active_files(table: &DeltaTable) -> Result<HashMap<String, Add>> {
+    Ok(table
+        .snapshot()?
+        .file_actions()?
+        .into_iter()
+        .map(|add| (add.path.clone(), add))
+        .collect())
+}
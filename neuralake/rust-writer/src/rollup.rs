@@ -0,0 +1,268 @@
+use anyhow::{bail, Context, Result};
+use deltalake::datafusion::prelude::SessionContext;
+use deltalake::kernel::{Action, Add, Transaction};
+use deltalake::operations::transaction::CommitProperties;
+use deltalake::{DeltaOps, DeltaTable, DeltaTableError, Path, StorageOptions};
+use polars::prelude::*;
+use std::io::Cursor;
+use crate::config::{AggregateFunction, AggregateSpec, RollupConfig};
+
+/// Column holding the truncated window start in every rollup table
+pub const WINDOW_START_COLUMN: &str = "window_start";
+
+/// Application id under which a rollup table records the last version of
+/// `raw_table_uri` folded into it
+pub fn progress_app_id(raw_table_uri: &str) -> String {
+    format!("rollup:{}", raw_table_uri)
+}
+
+/// Last raw table version folded into `rollup`, if it recorded one
+pub fn rollup_progress(rollup: &DeltaTable, raw_table_uri: &str) -> Option<i64> {
+    rollup
+        .get_app_transaction_version()
+        .get(&progress_app_id(raw_table_uri))
+        .map(|txn| txn.version)
+}
+
+impl AggregateSpec {
+    /// Polars expression computing this aggregate over one batch
+    fn batch_expr(&self) -> Expr {
+        let expr = match self.function {
+            AggregateFunction::Count => col(&self.column).count().cast(DataType::Int64),
+            AggregateFunction::Sum => col(&self.column).sum(),
+            AggregateFunction::Min => col(&self.column).min(),
+            AggregateFunction::Max => col(&self.column).max(),
+        };
+        expr.alias(&self.alias)
+    }
+
+    /// Polars expression combining per-batch aggregates of the same group
+    fn combine_expr(&self) -> Expr {
+        let expr = match self.function {
+            AggregateFunction::Count | AggregateFunction::Sum => col(&self.alias).sum(),
+            AggregateFunction::Min => col(&self.alias).min(),
+            AggregateFunction::Max => col(&self.alias).max(),
+        };
+        expr.alias(&self.alias)
+    }
+
+    /// SQL expression folding a batch aggregate into the stored value
+    fn merge_expr(&self) -> String {
+        let (t, s) = (format!("target.\"{}\"", self.alias), format!("source.\"{}\"", self.alias));
+        match self.function {
+            AggregateFunction::Count | AggregateFunction::Sum => {
+                format!("coalesce({t}, 0) + coalesce({s}, 0)")
+            }
+            AggregateFunction::Min => {
+                format!("CASE WHEN {t} IS NULL OR {s} < {t} THEN {s} ELSE {t} END")
+            }
+            AggregateFunction::Max => {
+                format!("CASE WHEN {t} IS NULL OR {s} > {t} THEN {s} ELSE {t} END")
+            }
+        }
+    }
+}
+
+/// Aggregate a raw batch into one row per (window, group-by keys)
+pub fn aggregate_batch(df: &DataFrame, config: &RollupConfig) -> Result<DataFrame> {
+    if config.aggregates.is_empty() {
+        bail!("Rollup for {} has no aggregates configured", config.table_uri);
+    }
+
+    let mut keys = vec![col(&config.time_column)
+        .dt()
+        .truncate(lit(config.window.clone()))
+        .alias(WINDOW_START_COLUMN)];
+    keys.extend(config.group_by.iter().map(|c| col(c)));
+
+    let aggs: Vec<Expr> = config.aggregates.iter().map(|a| a.batch_expr()).collect();
+
+    df.clone()
+        .lazy()
+        .group_by(keys)
+        .agg(aggs)
+        .collect()
+        .with_context(|| format!("Failed to aggregate batch for rollup {}", config.table_uri))
+}
+
+/// Combine the aggregates of several batches into one row per group
+fn combine_partials(mut partials: Vec<DataFrame>, config: &RollupConfig) -> Result<DataFrame> {
+    if partials.len() <= 1 {
+        return Ok(partials.pop().unwrap_or_else(DataFrame::empty));
+    }
+    let mut combined = partials.remove(0);
+    for partial in &partials {
+        combined.vstack_mut(partial).context("Rollup aggregates changed type between versions")?;
+    }
+
+    let keys: Vec<Expr> = std::iter::once(WINDOW_START_COLUMN)
+        .chain(config.group_by.iter().map(|c| c.as_str()))
+        .map(col)
+        .collect();
+    let aggs: Vec<Expr> = config.aggregates.iter().map(|a| a.combine_expr()).collect();
+    combined
+        .lazy()
+        .group_by(keys)
+        .agg(aggs)
+        .collect()
+        .with_context(|| format!("Failed to combine aggregates for rollup {}", config.table_uri))
+}
+
+/// Operation and data files of one raw table commit
+async fn read_commit(raw: &DeltaTable, version: i64) -> Result<(Option<String>, Vec<Add>)> {
+    let bytes = raw
+        .log_store()
+        .read_commit_entry(version)
+        .await?
+        .with_context(|| format!("Commit {} not found", version))?;
+
+    let (mut operation, mut adds) = (None, Vec::new());
+    for line in bytes.split(|b| *b == b'\n').filter(|l| !l.is_empty()) {
+        match serde_json::from_slice::<Action>(line)? {
+            Action::CommitInfo(info) => operation = info.operation,
+            Action::Add(add) if add.data_change => adds.push(add),
+            _ => {}
+        }
+    }
+    Ok((operation, adds))
+}
+
+/// Rows of an appended data file, with its partition values as columns
+/// typed like those of `like`
+async fn read_added_file(raw: &DeltaTable, add: &Add, like: &DataFrame) -> Result<DataFrame> {
+    let bytes = raw
+        .object_store()
+        .get(&Path::from(add.path.as_str()))
+        .await
+        .with_context(|| format!("Failed to read data file {}", add.path))?
+        .bytes()
+        .await?;
+    let mut df = ParquetReader::new(Cursor::new(bytes))
+        .finish()
+        .with_context(|| format!("Invalid data file {}", add.path))?;
+
+    for (column, value) in &add.partition_values {
+        let dtype = like.column(column).map(|c| c.dtype().clone()).unwrap_or(DataType::String);
+        let value = match value {
+            Some(value) => lit(value.clone()),
+            None => lit(NULL),
+        };
+        df = df.lazy().with_column(value.cast(dtype).alias(column.as_str())).collect()?;
+    }
+    Ok(df)
+}
+
+/// Fold the rows of raw table `version` into the rollup table, creating it
+/// on first use. `df` holds the rows the writer committed in that version.
+///
+/// Each rollup commit records the raw version as an application transaction
+/// ([`progress_app_id`]), so no version is folded in twice. Appends since the
+/// recorded version, left out by a failed rollup or committed alongside `df`
+/// by another flush, are read back from their data files and folded in too.
+pub async fn apply_rollup(
+    raw: &DeltaTable,
+    df: &DataFrame,
+    version: i64,
+    config: &RollupConfig,
+    storage_options: &StorageOptions,
+) -> Result<()> {
+    let raw_uri = raw.table_uri();
+    let table = match deltalake::open_table_with_storage_options(
+        &config.table_uri,
+        storage_options.0.clone(),
+    )
+    .await
+    {
+        Ok(table) => Some(table),
+        Err(DeltaTableError::NotATable(_)) => None,
+        Err(e) => return Err(e).context("Failed to open rollup table"),
+    };
+    let folded = table.as_ref().and_then(|table| rollup_progress(table, &raw_uri));
+    if folded.is_some_and(|folded| folded >= version) {
+        return Ok(());
+    }
+
+    // Without recorded progress there is nothing to catch up on
+    let mut partials = Vec::new();
+    for v in folded.map_or(version, |folded| folded + 1)..=version {
+        let (operation, adds) = read_commit(raw, v).await?;
+        let appended = operation.as_deref() == Some("WRITE");
+        if v == version {
+            let rows: Option<i64> = adds
+                .iter()
+                .map(|add| add.get_stats().ok().flatten().map(|stats| stats.num_records))
+                .sum();
+            // Upserts and commits of `df` alone are folded from memory
+            if !appended || rows == Some(df.height() as i64) {
+                partials.push(aggregate_batch(df, config)?);
+                continue;
+            }
+        }
+        if !appended {
+            continue;
+        }
+        if v < version {
+            log::info!("Catching up rollup {} with version {} of {}", config.table_uri, v, raw_uri);
+        }
+        for add in &adds {
+            partials.push(aggregate_batch(&read_added_file(raw, add, df).await?, config)?);
+        }
+    }
+
+    let rolled = combine_partials(partials, config)?;
+    if rolled.height() == 0 {
+        return Ok(());
+    }
+    let commit = CommitProperties::default()
+        .with_application_transaction(Transaction::new(progress_app_id(&raw_uri), version));
+
+    let batch = rolled.to_arrow(None)
+        .context("Failed to convert rollup to Arrow")?;
+
+    let Some(table) = table else {
+        log::info!("Creating rollup table {}", config.table_uri);
+        DeltaOps::try_from_uri_with_storage_options(&config.table_uri, storage_options.0.clone())
+            .await?
+            .write(vec![batch])
+            .with_commit_properties(commit)
+            .await
+            .context("Failed to create rollup table")?;
+        return Ok(());
+    };
+    let predicate = std::iter::once(WINDOW_START_COLUMN)
+        .chain(config.group_by.iter().map(|c| c.as_str()))
+        .map(|c| format!("target.\"{c}\" = source.\"{c}\""))
+        .collect::<Vec<_>>()
+        .join(" AND ");
+
+    let columns: Vec<String> = rolled
+        .get_column_names()
+        .iter()
+        .map(|c| c.to_string())
+        .collect();
+
+    let source = SessionContext::new()
+        .read_batch(batch)
+        .context("Failed to register rollup batch")?;
+
+    DeltaOps(table)
+        .merge(source, predicate)
+        .with_source_alias("source")
+        .with_target_alias("target")
+        .with_commit_properties(commit)
+        .when_matched_update(|update| {
+            config
+                .aggregates
+                .iter()
+                .fold(update, |update, agg| update.update(agg.alias.as_str(), agg.merge_expr()))
+        })?
+        .when_not_matched_insert(|insert| {
+            columns
+                .iter()
+                .fold(insert, |insert, c| insert.set(c.as_str(), format!("source.\"{c}\"")))
+        })?
+        .await
+        .with_context(|| format!("Failed to merge into rollup table {}", config.table_uri))?;
+
+    Ok(())
+}
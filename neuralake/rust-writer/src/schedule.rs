@@ -0,0 +1,180 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, DurationRound, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::time::{interval, sleep, Interval};
+
+/// Days searched for the next run before a schedule is considered never to
+/// fire (e.g. `0 0 30 2 *`)
+const MAX_SEARCH_DAYS: i64 = 5 * 366;
+
+/// A five-field cron expression (`minute hour day-of-month month
+/// day-of-week`), evaluated in UTC. Fields accept `*`, values, ranges
+/// (`1-5`), lists (`1,15`) and steps (`*/15`, `0-30/10`); day of week runs
+/// from 0 (Sunday) to 6, with 7 also meaning Sunday. As in cron, when both
+/// day fields are restricted a day matching either one fires.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct CronSchedule {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+impl CronSchedule {
+    /// The first minute strictly after `after` that the schedule fires at
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut at = after.duration_trunc(ChronoDuration::minutes(1)).ok()? + ChronoDuration::minutes(1);
+        let limit = after + ChronoDuration::days(MAX_SEARCH_DAYS);
+        while at <= limit {
+            if !allows(self.months, at.month()) || !self.matches_day(at) {
+                let midnight = at.duration_trunc(ChronoDuration::days(1)).ok()?;
+                at = midnight + ChronoDuration::days(1);
+            } else if !allows(self.hours, at.hour()) {
+                at = at.duration_trunc(ChronoDuration::hours(1)).ok()? + ChronoDuration::hours(1);
+            } else if !allows(self.minutes, at.minute()) {
+                at += ChronoDuration::minutes(1);
+            } else {
+                return Some(at);
+            }
+        }
+        None
+    }
+
+    fn matches_day(&self, at: DateTime<Utc>) -> bool {
+        let day_of_month = allows(self.days_of_month, at.day());
+        let day_of_week = allows(self.days_of_week, at.weekday().num_days_from_sunday());
+        match (self.any_day_of_month, self.any_day_of_week) {
+            (false, false) => day_of_month || day_of_week,
+            _ => day_of_month && day_of_week,
+        }
+    }
+}
+
+fn allows(field: u64, value: u32) -> bool {
+    field & (1u64 << value) != 0
+}
+
+/// Parse one field into a bit set of the values it allows within `min..=max`
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64> {
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().with_context(|| format!("Invalid step in '{}'", part))?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            bail!("Step must be positive in '{}'", part);
+        }
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (parse_value(start, part)?, parse_value(end, part)?),
+                // `5/10` runs from 5 to the end of the range
+                None if step > 1 => (parse_value(range, part)?, max),
+                None => {
+                    let value = parse_value(range, part)?;
+                    (value, value)
+                }
+            },
+        };
+        if start < min || end > max || start > end {
+            bail!("'{}' is outside {}-{}", part, min, max);
+        }
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1u64 << value;
+        }
+    }
+    Ok(bits)
+}
+
+fn parse_value(value: &str, part: &str) -> Result<u32> {
+    value.parse().with_context(|| format!("Invalid value in '{}'", part))
+}
+
+impl FromStr for CronSchedule {
+    type Err = anyhow::Error;
+
+    fn from_str(expression: &str) -> Result<Self> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minute, hour, day_of_month, month, day_of_week] = fields[..] else {
+            bail!("Cron expression '{}' must have 5 fields: minute hour day-of-month month day-of-week", expression);
+        };
+        let context = || format!("Invalid cron expression '{}'", expression);
+        let mut days_of_week = parse_field(day_of_week, 0, 7).with_context(context)?;
+        // 7 is an alias of Sunday
+        if days_of_week & (1u64 << 7) != 0 {
+            days_of_week = (days_of_week | 1) & !(1u64 << 7);
+        }
+        Ok(Self {
+            expression: fields.join(" "),
+            minutes: parse_field(minute, 0, 59).with_context(context)?,
+            hours: parse_field(hour, 0, 23).with_context(context)?,
+            days_of_month: parse_field(day_of_month, 1, 31).with_context(context)?,
+            months: parse_field(month, 1, 12).with_context(context)?,
+            days_of_week,
+            any_day_of_month: day_of_month == "*",
+            any_day_of_week: day_of_week == "*",
+        })
+    }
+}
+
+impl TryFrom<String> for CronSchedule {
+    type Error = anyhow::Error;
+
+    fn try_from(expression: String) -> Result<Self> {
+        expression.parse()
+    }
+}
+
+impl From<CronSchedule> for String {
+    fn from(schedule: CronSchedule) -> Self {
+        schedule.expression
+    }
+}
+
+impl fmt::Display for CronSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+/// When a periodic process runs its next cycle: at its cron schedule's times
+/// if it has one, otherwise every interval starting immediately
+pub(crate) enum Ticker {
+    Interval(Interval),
+    Cron(CronSchedule),
+}
+
+impl Ticker {
+    pub(crate) fn new(schedule: Option<&CronSchedule>, period: Duration) -> Self {
+        match schedule {
+            Some(schedule) => Ticker::Cron(schedule.clone()),
+            None => Ticker::Interval(interval(period)),
+        }
+    }
+
+    /// Wait for the next cycle
+    pub(crate) async fn tick(&mut self) {
+        match self {
+            Ticker::Interval(interval) => {
+                interval.tick().await;
+            }
+            Ticker::Cron(schedule) => {
+                let now = Utc::now();
+                let Some(next) = schedule.next_after(now) else {
+                    log::warn!("Schedule '{}' never fires", schedule);
+                    return std::future::pending().await;
+                };
+                sleep((next - now).to_std().unwrap_or_default()).await;
+            }
+        }
+    }
+}
@@ -0,0 +1,267 @@
+use anyhow::{bail, Context, Result};
+use deltalake::arrow::compute::cast;
+use deltalake::arrow::datatypes::{DataType, Field, Schema as ArrowSchema};
+use deltalake::arrow::record_batch::RecordBatch;
+use deltalake::kernel::{DataType as DeltaDataType, DecimalType, PrimitiveType, StructField};
+use deltalake::{DeltaOps, DeltaTable, StorageOptions};
+use polars::prelude::{DataType as PolarsDataType, Field as PolarsField, Schema as PolarsSchema, TimeUnit};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use crate::config::{SchemaEvolutionMode, TableSchemaConfig};
+
+/// What has to happen to the table schema before a batch can be committed
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaChange {
+    /// The batch fits the current table schema
+    Unchanged,
+    /// The batch introduces columns that must be added to the table
+    AddColumns(Vec<Field>),
+}
+
+/// Cheap check that a batch already has the table's column order and types,
+/// which is the common case and lets the write path skip all schema work
+pub fn matches_table(table_schema: &ArrowSchema, batch_schema: &ArrowSchema) -> bool {
+    table_schema.fields().len() == batch_schema.fields().len()
+        && table_schema
+            .fields()
+            .iter()
+            .zip(batch_schema.fields().iter())
+            .all(|(t, b)| t.name() == b.name() && t.data_type() == b.data_type())
+}
+
+/// Compare an incoming batch schema with the table schema and decide,
+/// according to the configured evolution mode, how the write should proceed
+pub fn plan_schema_change(
+    mode: SchemaEvolutionMode,
+    table_schema: &ArrowSchema,
+    batch_schema: &ArrowSchema,
+) -> Result<SchemaChange> {
+    if matches_table(table_schema, batch_schema) {
+        return Ok(SchemaChange::Unchanged);
+    }
+
+    let mut new_fields = Vec::new();
+    let mut type_changes = Vec::new();
+
+    for field in batch_schema.fields() {
+        match table_schema.field_with_name(field.name()) {
+            Ok(existing) if existing.data_type() != field.data_type() => {
+                type_changes.push(format!(
+                    "{} ({} -> {})",
+                    field.name(),
+                    existing.data_type(),
+                    field.data_type()
+                ));
+            }
+            Ok(_) => {}
+            // New columns must be nullable so existing files stay readable
+            Err(_) => new_fields.push(field.as_ref().clone().with_nullable(true)),
+        }
+    }
+
+    if new_fields.is_empty() && type_changes.is_empty() {
+        return Ok(SchemaChange::Unchanged);
+    }
+
+    let new_names: Vec<&str> = new_fields.iter().map(|f| f.name().as_str()).collect();
+
+    match mode {
+        SchemaEvolutionMode::None => bail!(
+            "Batch schema does not match table schema (new columns: {:?}, type changes: {:?}); \
+             set writer.schema_evolution to allow schema changes",
+            new_names,
+            type_changes
+        ),
+        SchemaEvolutionMode::AddColumns => {
+            if !type_changes.is_empty() {
+                bail!(
+                    "Schema evolution mode add-columns cannot change existing column types: {:?}",
+                    type_changes
+                );
+            }
+            Ok(SchemaChange::AddColumns(new_fields))
+        }
+        SchemaEvolutionMode::Overwrite => {
+            // Existing files keep their column types, so a new type would
+            // mean rewriting the table; its contents are never replaced
+            if !type_changes.is_empty() {
+                bail!(
+                    "Column types can only change by rewriting the table, which the writer never does: {:?}",
+                    type_changes
+                );
+            }
+            Ok(SchemaChange::AddColumns(new_fields))
+        }
+    }
+}
+
+/// Reorder batch columns by name to follow the table schema, and cast
+/// timestamps to the table's timestamp flavour (UTC vs. NTZ, unit).
+/// Tables written by Spark rarely share the producer's column order, and
+/// Polars emits timezone-naive timestamps regardless of the table type.
+pub fn align_batch(batch: RecordBatch, table_schema: &ArrowSchema) -> Result<RecordBatch> {
+    let batch_schema = batch.schema();
+    if matches_table(table_schema, &batch_schema) {
+        return Ok(batch);
+    }
+
+    let mut order: Vec<usize> = table_schema
+        .fields()
+        .iter()
+        .filter_map(|f| batch_schema.index_of(f.name()).ok())
+        .collect();
+    // Columns unknown to the table go last, for schema evolution to handle
+    order.extend(
+        batch_schema
+            .fields()
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| table_schema.field_with_name(f.name()).is_err())
+            .map(|(i, _)| i),
+    );
+
+    let mut fields = Vec::with_capacity(order.len());
+    let mut columns = Vec::with_capacity(order.len());
+
+    for index in order {
+        let field = batch_schema.field(index);
+        let column = batch.column(index);
+
+        let target = table_schema.field_with_name(field.name()).map(|f| f.data_type());
+
+        match (field.data_type(), target) {
+            (DataType::Timestamp(_, _), Ok(target @ DataType::Timestamp(_, _)))
+                if field.data_type() != target =>
+            {
+                let column = cast(column, target)
+                    .with_context(|| format!("Failed to cast column {}", field.name()))?;
+                columns.push(column);
+                fields.push(field.clone().with_data_type(target.clone()));
+            }
+            _ => {
+                columns.push(column.clone());
+                fields.push(field.clone());
+            }
+        }
+    }
+
+    RecordBatch::try_new(Arc::new(ArrowSchema::new(fields)), columns)
+        .context("Failed to build aligned batch")
+}
+
+/// Parse a Delta primitive type name as written in the config; common SQL
+/// aliases (`bigint`, `int`, `varchar`, ...) are accepted too
+pub fn parse_delta_type(name: &str) -> Result<DeltaDataType> {
+    let name = name.trim().to_lowercase();
+    if let Some(args) = name.strip_prefix("decimal(").and_then(|rest| rest.strip_suffix(')')) {
+        let (precision, scale) = args
+            .split_once(',')
+            .with_context(|| format!("Expected decimal(precision,scale), got '{}'", name))?;
+        let decimal = DecimalType::try_new(precision.trim().parse()?, scale.trim().parse()?)
+            .with_context(|| format!("Invalid decimal type '{}'", name))?;
+        return Ok(DeltaDataType::Primitive(PrimitiveType::Decimal(decimal)));
+    }
+
+    let primitive = match name.as_str() {
+        "string" | "varchar" | "text" => PrimitiveType::String,
+        "long" | "bigint" | "int64" => PrimitiveType::Long,
+        "integer" | "int" | "int32" => PrimitiveType::Integer,
+        "short" | "smallint" | "int16" => PrimitiveType::Short,
+        "byte" | "tinyint" | "int8" => PrimitiveType::Byte,
+        "float" | "real" | "float32" => PrimitiveType::Float,
+        "double" | "float64" => PrimitiveType::Double,
+        "boolean" | "bool" => PrimitiveType::Boolean,
+        "binary" => PrimitiveType::Binary,
+        "date" => PrimitiveType::Date,
+        "timestamp" => PrimitiveType::Timestamp,
+        "timestamp_ntz" => PrimitiveType::TimestampNtz,
+        other => bail!("Unsupported column type '{}'", other),
+    };
+    Ok(DeltaDataType::Primitive(primitive))
+}
+
+/// Polars schema for the flat Avro record definition of a schema registry
+/// entry, used to decode JSON messages with stable column types. Nested
+/// records, arrays and maps are not supported.
+pub fn avro_record_to_polars(record: &Value) -> Result<PolarsSchema> {
+    if record["type"] != "record" {
+        bail!("Expected an Avro record schema, got {}", record["type"]);
+    }
+    let fields = record["fields"].as_array().context("Avro record schema has no fields")?;
+    fields
+        .iter()
+        .map(|field| {
+            let name = field["name"].as_str().context("Avro field without a name")?;
+            let dtype = avro_type_to_polars(&field["type"]).with_context(|| format!("Unsupported type for field {}", name))?;
+            Ok(PolarsField::new(name.into(), dtype))
+        })
+        .collect()
+}
+
+fn avro_type_to_polars(avro: &Value) -> Result<PolarsDataType> {
+    // Nullable fields are unions with "null"; every Polars column is nullable
+    if let Some(variants) = avro.as_array() {
+        let non_null: Vec<&Value> = variants.iter().filter(|v| *v != "null").collect();
+        let [single] = non_null.as_slice() else {
+            bail!("Only unions of one type with null are supported, got {}", avro);
+        };
+        return avro_type_to_polars(single);
+    }
+
+    let (name, logical) = match avro {
+        Value::String(name) => (name.as_str(), None),
+        Value::Object(spec) => (
+            spec.get("type").and_then(Value::as_str).unwrap_or_default(),
+            spec.get("logicalType").and_then(Value::as_str),
+        ),
+        _ => bail!("Invalid Avro type {}", avro),
+    };
+    Ok(match (name, logical) {
+        ("int", Some("date")) => PolarsDataType::Date,
+        ("long", Some("timestamp-millis")) => PolarsDataType::Datetime(TimeUnit::Milliseconds, None),
+        ("long", Some("timestamp-micros")) => PolarsDataType::Datetime(TimeUnit::Microseconds, None),
+        ("string", _) | ("enum", _) => PolarsDataType::String,
+        ("int", _) => PolarsDataType::Int32,
+        ("long", _) => PolarsDataType::Int64,
+        ("float", _) => PolarsDataType::Float32,
+        ("double", _) => PolarsDataType::Float64,
+        ("boolean", _) => PolarsDataType::Boolean,
+        ("bytes", _) => PolarsDataType::Binary,
+        (other, _) => bail!("Unsupported Avro type '{}'", other),
+    })
+}
+
+/// Create a table at `table_uri` with the declared columns, partitioning
+/// and properties
+pub async fn create_table(
+    table_uri: &str,
+    storage_options: &StorageOptions,
+    schema: &TableSchemaConfig,
+) -> Result<DeltaTable> {
+    if schema.columns.is_empty() {
+        bail!("Cannot create {} from a schema without columns", table_uri);
+    }
+    let columns = schema
+        .columns
+        .iter()
+        .map(|c| {
+            let data_type = parse_delta_type(&c.data_type).with_context(|| format!("Invalid type for column {}", c.name))?;
+            Ok(StructField::new(c.name.clone(), data_type, c.nullable))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let partition_columns: Vec<String> =
+        schema.columns.iter().filter(|c| c.partition).map(|c| c.name.clone()).collect();
+    let properties: HashMap<String, Option<String>> =
+        schema.properties.iter().map(|(k, v)| (k.clone(), Some(v.clone()))).collect();
+
+    DeltaOps::try_from_uri_with_storage_options(table_uri, storage_options.0.clone())
+        .await
+        .with_context(|| format!("Failed to open {}", table_uri))?
+        .create()
+        .with_columns(columns)
+        .with_partition_columns(partition_columns)
+        .with_configuration(properties)
+        .await
+        .with_context(|| format!("Failed to create table {}", table_uri))
+}
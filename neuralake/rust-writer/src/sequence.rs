@@ -0,0 +1,275 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use deltalake::protocol::SaveMode;
+use deltalake::{DeltaOps, StorageOptions};
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use crate::config::SequenceTrackingConfig;
+
+/// Key of the one stream of a source without key columns
+const SINGLE_STREAM_KEY: &str = "*";
+/// Unfilled gaps remembered per stream. Older gaps stay counted as missing,
+/// but a late delivery into them is taken for a duplicate.
+const MAX_OPEN_GAPS: usize = 1024;
+/// Gaps listed per stream in the text report
+const GAPS_DISPLAYED: usize = 10;
+
+/// Sequence numbers seen so far on one stream
+#[derive(Debug)]
+struct StreamState {
+    /// Next sequence number expected in order
+    next: i64,
+    received: u64,
+    missing: u64,
+    out_of_order: u64,
+    duplicates: u64,
+    /// Unfilled ranges of missing numbers, first to last inclusive
+    open_gaps: BTreeMap<i64, i64>,
+}
+
+impl StreamState {
+    fn starting_at(sequence: i64) -> Self {
+        Self {
+            next: sequence + 1,
+            received: 1,
+            missing: 0,
+            out_of_order: 0,
+            duplicates: 0,
+            open_gaps: BTreeMap::new(),
+        }
+    }
+
+    fn observe(&mut self, sequence: i64) {
+        self.received += 1;
+        if sequence >= self.next {
+            if sequence > self.next {
+                self.missing += (sequence - self.next) as u64;
+                self.open_gaps.insert(self.next, sequence - 1);
+                if self.open_gaps.len() > MAX_OPEN_GAPS {
+                    self.open_gaps.pop_first();
+                }
+            }
+            self.next = sequence + 1;
+            return;
+        }
+
+        // Behind the expected number: either it fills a gap or it was seen before
+        let Some((&first, &last)) = self.open_gaps.range(..=sequence).next_back() else {
+            self.duplicates += 1;
+            return;
+        };
+        if sequence > last {
+            self.duplicates += 1;
+            return;
+        }
+        self.out_of_order += 1;
+        self.missing -= 1;
+        self.open_gaps.remove(&first);
+        if first < sequence {
+            self.open_gaps.insert(first, sequence - 1);
+        }
+        if sequence < last {
+            self.open_gaps.insert(sequence + 1, last);
+        }
+    }
+}
+
+/// Completeness of one stream since tracking started
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StreamGaps {
+    pub key: String,
+    /// Highest sequence number received
+    pub last_sequence: i64,
+    pub received: u64,
+    /// Numbers skipped and not delivered since
+    pub missing: u64,
+    /// Deliveries that filled a gap after later numbers arrived
+    pub out_of_order: u64,
+    /// Deliveries of a number already received
+    pub duplicates: u64,
+    /// Missing ranges, first to last inclusive
+    pub open_gaps: Vec<(i64, i64)>,
+}
+
+/// Periodic evidence of which sequence numbers the table has received
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GapsReport {
+    pub reported_at: DateTime<Utc>,
+    pub streams: Vec<StreamGaps>,
+}
+
+impl GapsReport {
+    pub fn missing(&self) -> u64 {
+        self.streams.iter().map(|s| s.missing).sum()
+    }
+
+    pub fn out_of_order(&self) -> u64 {
+        self.streams.iter().map(|s| s.out_of_order).sum()
+    }
+
+    pub fn duplicates(&self) -> u64 {
+        self.streams.iter().map(|s| s.duplicates).sum()
+    }
+
+    /// One row per stream, as appended to the gaps table
+    pub fn to_dataframe(&self) -> Result<DataFrame> {
+        let column = |name: &str, values: Vec<u64>| Column::new(name.into(), values);
+        let reported_at = Column::new("reported_at".into(), vec![self.reported_at.timestamp_micros(); self.streams.len()])
+            .cast(&DataType::Datetime(TimeUnit::Microseconds, None))?;
+        let open_gaps: Vec<String> = self.streams.iter().map(|s| format_gaps(&s.open_gaps, usize::MAX)).collect();
+        DataFrame::new(vec![
+            reported_at,
+            Column::new("key".into(), self.streams.iter().map(|s| s.key.clone()).collect::<Vec<_>>()),
+            Column::new("last_sequence".into(), self.streams.iter().map(|s| s.last_sequence).collect::<Vec<_>>()),
+            column("received", self.streams.iter().map(|s| s.received).collect()),
+            column("missing", self.streams.iter().map(|s| s.missing).collect()),
+            column("out_of_order", self.streams.iter().map(|s| s.out_of_order).collect()),
+            column("duplicates", self.streams.iter().map(|s| s.duplicates).collect()),
+            Column::new("open_gaps".into(), open_gaps),
+        ])
+        .context("Failed to build gaps report rows")
+    }
+}
+
+/// `3-5, 9` style list of the first `limit` ranges
+fn format_gaps(gaps: &[(i64, i64)], limit: usize) -> String {
+    let mut listed: Vec<String> = gaps
+        .iter()
+        .take(limit)
+        .map(|(first, last)| if first == last { first.to_string() } else { format!("{}-{}", first, last) })
+        .collect();
+    if gaps.len() > limit {
+        listed.push(format!("... {} more", gaps.len() - limit));
+    }
+    listed.join(", ")
+}
+
+impl fmt::Display for GapsReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Sequence gaps at {}: {} streams, {} missing, {} out of order, {} duplicates",
+            self.reported_at.to_rfc3339(),
+            self.streams.len(),
+            self.missing(),
+            self.out_of_order(),
+            self.duplicates()
+        )?;
+        for stream in self.streams.iter().filter(|s| s.missing > 0) {
+            writeln!(
+                f,
+                "  {}: {} missing up to {}: {}",
+                stream.key,
+                stream.missing,
+                stream.last_sequence,
+                format_gaps(&stream.open_gaps, GAPS_DISPLAYED)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Follows the sequence numbers of the rows a source commits. State lives in
+/// memory only, so after a restart each stream starts over at the first
+/// number it receives.
+pub struct SequenceTracker {
+    config: SequenceTrackingConfig,
+    streams: BTreeMap<String, StreamState>,
+}
+
+impl SequenceTracker {
+    pub fn new(config: SequenceTrackingConfig) -> Self {
+        Self { config, streams: BTreeMap::new() }
+    }
+
+    pub fn config(&self) -> &SequenceTrackingConfig {
+        &self.config
+    }
+
+    /// Stream key and sequence number of each row of `df`, in arrival order.
+    /// Rows without a sequence number are left out.
+    pub fn sequences(&self, df: &DataFrame) -> Result<Vec<(String, i64)>> {
+        if df.height() == 0 {
+            return Ok(Vec::new());
+        }
+        let sequences = df
+            .column(&self.config.column)
+            .with_context(|| format!("Batch has no sequence column {}", self.config.column))?
+            .cast(&DataType::Int64)?;
+        let keys = self
+            .config
+            .key_columns
+            .iter()
+            .map(|column| {
+                df.column(column)
+                    .with_context(|| format!("Batch has no sequence key column {}", column))?
+                    .cast(&DataType::String)
+                    .map_err(Into::into)
+            })
+            .collect::<Result<Vec<Column>>>()?;
+        let keys = keys.iter().map(|key| key.str()).collect::<PolarsResult<Vec<_>>>()?;
+
+        Ok(sequences
+            .i64()?
+            .into_iter()
+            .enumerate()
+            .filter_map(|(row, sequence)| {
+                let key = if keys.is_empty() {
+                    SINGLE_STREAM_KEY.to_string()
+                } else {
+                    keys.iter().map(|key| key.get(row).unwrap_or("null")).collect::<Vec<_>>().join("/")
+                };
+                sequence.map(|sequence| (key, sequence))
+            })
+            .collect())
+    }
+
+    /// Account for deliveries in the order they arrived
+    pub fn observe(&mut self, sequences: &[(String, i64)]) {
+        for (key, sequence) in sequences {
+            match self.streams.get_mut(key) {
+                Some(stream) => stream.observe(*sequence),
+                None => {
+                    self.streams.insert(key.clone(), StreamState::starting_at(*sequence));
+                }
+            }
+        }
+    }
+
+    pub fn report(&self) -> GapsReport {
+        GapsReport {
+            reported_at: Utc::now(),
+            streams: self
+                .streams
+                .iter()
+                .map(|(key, stream)| StreamGaps {
+                    key: key.clone(),
+                    last_sequence: stream.next - 1,
+                    received: stream.received,
+                    missing: stream.missing,
+                    out_of_order: stream.out_of_order,
+                    duplicates: stream.duplicates,
+                    open_gaps: stream.open_gaps.iter().map(|(first, last)| (*first, *last)).collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Append a report to the gaps table, creating it on first use
+pub async fn append_report(report: &GapsReport, table_uri: &str, storage_options: &StorageOptions) -> Result<()> {
+    if report.streams.is_empty() {
+        return Ok(());
+    }
+    let batch = report.to_dataframe()?.to_arrow(None)
+        .context("Failed to convert gaps report to Arrow")?;
+    DeltaOps::try_from_uri_with_storage_options(table_uri, storage_options.0.clone())
+        .await?
+        .write(vec![batch])
+        .with_save_mode(SaveMode::Append)
+        .await
+        .with_context(|| format!("Failed to write gaps report to {}", table_uri))?;
+    Ok(())
+}
@@ -0,0 +1,597 @@
+use anyhow::{bail, Context, Result};
+use axum::body::Bytes;
+use axum::extract::{DefaultBodyLimit, Path, Query, Request, State};
+use axum::http::{header, HeaderMap, Method, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use polars::prelude::*;
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Cursor;
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tokio_util::sync::CancellationToken;
+use crate::auth;
+use crate::commit_feed::CommitFeed;
+use crate::config::HttpConfig;
+use crate::correlation::validate_batch_id;
+use crate::jobs::{Job, JobKind, JobQueue};
+use crate::maintenance::{Maintenance, MaintenanceControl, MaintenancePauses};
+use crate::metrics::{ErrorEvent, MetricsRegistry, MetricsSnapshot, ProcessMetrics, ProcessSampler};
+use crate::quality::QualityStatusHandle;
+use crate::sinks::SinkStatusHandle;
+use crate::sources::SourceStatusHandle;
+use crate::writer::{BatchSender, WriterControl};
+
+/// Content type for Arrow IPC stream payloads
+pub const ARROW_STREAM_CONTENT_TYPE: &str = "application/vnd.apache.arrow.stream";
+
+/// Single-page triage UI served at `/ui`, built on the JSON endpoints below
+const UI_HTML: &str = include_str!("ui.html");
+
+/// Optional request header carrying the producer's own batch id
+pub const BATCH_ID_HEADER: &str = "x-batch-id";
+
+/// A managed table reachable through the API
+#[derive(Clone)]
+pub struct TableEndpoint {
+    pub table_uri: String,
+    pub batches: BatchSender,
+    /// Ingestion source status, when a source is configured
+    pub source_status: Option<SourceStatusHandle>,
+    /// Secondary sinks fed from this table
+    pub sink_status: Vec<SinkStatusHandle>,
+    /// Commit notifications, when the commit feed is enabled
+    pub commit_feed: Option<CommitFeed>,
+    /// Data test results, when data tests are enabled
+    pub quality_status: Option<QualityStatusHandle>,
+    /// Counters shared by the table's processes
+    pub metrics: Arc<MetricsRegistry>,
+    /// Flush, pause and resume of the table's writer
+    pub control: Arc<WriterControl>,
+    /// Pause switches of compaction and vacuum
+    pub maintenance: Arc<MaintenanceControl>,
+    /// Queue the table's job runner polls, when enabled
+    pub jobs: Option<Arc<JobQueue>>,
+    /// Ingestion and maintenance jobs are refused
+    pub read_only: bool,
+}
+
+impl TableEndpoint {
+    fn refuse_read_only(&self) -> Result<(), Response> {
+        if self.read_only {
+            return Err((StatusCode::FORBIDDEN, format!("{} is open read-only", self.table_uri)).into_response());
+        }
+        Ok(())
+    }
+}
+
+/// Shared state handed to every request handler
+#[derive(Clone)]
+pub struct ApiState {
+    /// Tables keyed by the name they are exposed under, e.g. `/ingest/{table}`
+    pub tables: Arc<BTreeMap<String, TableEndpoint>>,
+    /// Resource usage of the whole process, sampled per request
+    pub process_metrics: Arc<ProcessSampler>,
+    /// Ends open event streams so graceful shutdown is not held up by them
+    pub shutdown: CancellationToken,
+}
+
+/// Optional `?table=` selector; may be omitted when only one table is served
+#[derive(Debug, Deserialize)]
+struct TableQuery {
+    table: Option<String>,
+}
+
+impl ApiState {
+    fn table(&self, name: Option<&str>) -> Result<&TableEndpoint, Response> {
+        let found = match name {
+            Some(name) => self.tables.get(name),
+            None if self.tables.len() == 1 => self.tables.values().next(),
+            None => {
+                return Err((StatusCode::BAD_REQUEST, "Specify the table with ?table=").into_response())
+            }
+        };
+        found.ok_or_else(|| {
+            (StatusCode::NOT_FOUND, format!("Unknown table '{}'", name.unwrap_or_default()))
+                .into_response()
+        })
+    }
+}
+
+/// Body of `GET /metrics`
+#[derive(Debug, Serialize)]
+struct MetricsResponse {
+    process: ProcessMetrics,
+    tables: BTreeMap<String, TableMetrics>,
+}
+
+#[derive(Debug, Serialize)]
+struct TableMetrics {
+    /// Batches waiting in the writer queue
+    queued_batches: usize,
+    paused_maintenance: MaintenancePauses,
+    #[serde(flatten)]
+    counters: MetricsSnapshot,
+}
+
+/// Entry of `GET /tables`, also returned by the flush, pause and resume endpoints
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableSummary {
+    pub name: String,
+    pub table_uri: String,
+    pub queued_batches: usize,
+    /// Latest version committed by this process since it started
+    pub last_version: Option<i64>,
+    pub last_commit_at: Option<DateTime<Utc>>,
+    /// Seconds since `last_commit_at`
+    pub freshness_secs: Option<i64>,
+    pub recent_errors: usize,
+    /// Whether the writer and sources are paused
+    #[serde(default)]
+    pub paused: bool,
+    #[serde(default)]
+    pub paused_maintenance: MaintenancePauses,
+}
+
+impl TableSummary {
+    fn of(name: &str, endpoint: &TableEndpoint, now: DateTime<Utc>) -> Self {
+        let last = endpoint.metrics.recent_batches(1).pop();
+        Self {
+            name: name.to_string(),
+            table_uri: endpoint.table_uri.clone(),
+            queued_batches: endpoint.batches.queued(),
+            last_version: last.as_ref().map(|r| r.version),
+            last_commit_at: last.as_ref().map(|r| r.committed_at),
+            freshness_secs: last.as_ref().map(|r| (now - r.committed_at).num_seconds()),
+            recent_errors: endpoint.metrics.recent_errors().len(),
+            paused: endpoint.control.is_paused(),
+            paused_maintenance: endpoint.maintenance.pauses(),
+        }
+    }
+}
+
+/// A recent commit of ingested batches
+#[derive(Debug, Serialize)]
+struct CommitSummary {
+    version: i64,
+    committed_at: DateTime<Utc>,
+    batch_ids: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RecentCommitsQuery {
+    #[serde(default = "default_commits_limit")]
+    limit: usize,
+}
+
+fn default_commits_limit() -> usize {
+    20
+}
+
+#[derive(Debug, Serialize)]
+struct IngestResponse {
+    table: String,
+    rows: usize,
+    /// Id under which the batch is logged and recorded in the commit
+    batch_id: String,
+}
+
+/// Build the API router
+pub fn router(state: ApiState, config: &HttpConfig) -> Router {
+    Router::new()
+        .route("/ingest/{table}", post(ingest))
+        .route("/jobs", get(list_jobs).post(submit_job))
+        .route("/jobs/{id}", get(get_job))
+        .route("/sources/status", get(source_status))
+        .route("/sinks/status", get(sink_status))
+        .route("/metrics", get(metrics))
+        .route("/ui", get(ui))
+        .route("/tables", get(list_tables))
+        .route("/tables/{table}/flush", post(flush_table))
+        .route("/tables/{table}/pause", post(pause_table))
+        .route("/tables/{table}/resume", post(resume_table))
+        .route("/tables/{table}/maintenance/{process}/pause", post(pause_maintenance))
+        .route("/tables/{table}/maintenance/{process}/resume", post(resume_maintenance))
+        .route("/tables/{table}/commits", get(recent_commits))
+        .route("/tables/{table}/errors", get(recent_errors))
+        .route("/tables/{table}/commits/stream", get(commit_stream))
+        .route("/tables/{table}/quality", get(quality_status))
+        .route("/tables/{table}/batches/{id}", get(batch_receipt))
+        .layer(middleware::from_fn_with_state(config.auth_token.clone().map(Arc::<str>::from), require_token))
+        .layer(DefaultBodyLimit::max(config.max_body_bytes))
+        .with_state(state)
+}
+
+/// Every route that changes state is a POST; with `auth_token` configured
+/// those need the token, while reads stay open
+async fn require_token(State(token): State<Option<Arc<str>>>, request: Request, next: Next) -> Response {
+    if let Some(token) = token {
+        let authorization = request.headers().get(header::AUTHORIZATION).and_then(|v| v.to_str().ok());
+        if request.method() == Method::POST && !auth::bearer_matches(&token, authorization) {
+            return (StatusCode::UNAUTHORIZED, "Missing or invalid bearer token").into_response();
+        }
+    }
+    next.run(request).await
+}
+
+/// Serve the API until shutdown is requested
+pub async fn serve(state: ApiState, config: HttpConfig, shutdown: CancellationToken) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(&config.bind_address)
+        .await
+        .with_context(|| format!("Failed to bind HTTP server to {}", config.bind_address))?;
+
+    log::info!("HTTP ingestion endpoint listening on {}", config.bind_address);
+    auth::warn_if_exposed("HTTP API", &config.bind_address, config.auth_token.as_deref());
+
+    axum::serve(listener, router(state, &config))
+        .with_graceful_shutdown(shutdown.cancelled_owned())
+        .await
+        .context("HTTP server failed")?;
+
+    Ok(())
+}
+
+/// `POST /ingest/{table}` - accept NDJSON or Arrow IPC rows and queue them for the writer
+async fn ingest(
+    State(state): State<ApiState>,
+    Path(table): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let endpoint = match state.table(Some(&table)) {
+        Ok(endpoint) => endpoint,
+        Err(response) => return response,
+    };
+    if let Err(response) = endpoint.refuse_read_only() {
+        return response;
+    }
+
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("application/x-ndjson");
+
+    let batch_id = match headers.get(BATCH_ID_HEADER).map(|v| v.to_str()) {
+        None => None,
+        Some(Ok(id)) if validate_batch_id(id).is_ok() => Some(id.to_string()),
+        Some(_) => return (StatusCode::BAD_REQUEST, "Invalid X-Batch-Id header").into_response(),
+    };
+
+    let df = match decode_payload(content_type, body) {
+        Ok(df) => df,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("{:#}", e)).into_response(),
+    };
+
+    let rows = df.height();
+    let batch_id = match endpoint.batches.send_with_id(df, batch_id).await {
+        Ok(batch_id) => batch_id,
+        Err(e) => {
+            log::warn!("Rejected batch for {}: {:#}", table, e);
+            return (StatusCode::SERVICE_UNAVAILABLE, "Writer is shutting down").into_response();
+        }
+    };
+    log::debug!("Queued batch {} ({} rows) for {}", batch_id, rows, table);
+
+    (StatusCode::ACCEPTED, Json(IngestResponse { table, rows, batch_id })).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+struct ListJobsQuery {
+    table: Option<String>,
+    #[serde(default = "default_jobs_limit")]
+    limit: usize,
+}
+
+fn default_jobs_limit() -> usize {
+    50
+}
+
+#[derive(Debug, Serialize)]
+struct SubmitJobResponse {
+    id: i64,
+}
+
+/// Job queue of the `table` selected as in [`ApiState::table`]; tables may
+/// keep their jobs in separate databases
+fn job_queue<'a>(state: &'a ApiState, table: Option<&str>) -> Result<(&'a TableEndpoint, &'a Arc<JobQueue>), Response> {
+    let endpoint = state.table(table)?;
+    let jobs = endpoint
+        .jobs
+        .as_ref()
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Job queue is not enabled for {}", endpoint.table_uri)).into_response())?;
+    Ok((endpoint, jobs))
+}
+
+fn internal_error(e: anyhow::Error) -> Response {
+    (StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)).into_response()
+}
+
+/// `POST /jobs` - enqueue a maintenance job for a managed table
+async fn submit_job(
+    State(state): State<ApiState>,
+    Query(query): Query<TableQuery>,
+    Json(kind): Json<JobKind>,
+) -> Response {
+    let (endpoint, jobs) = match job_queue(&state, query.table.as_deref()) {
+        Ok(found) => found,
+        Err(response) => return response,
+    };
+    if let Err(response) = endpoint.refuse_read_only() {
+        return response;
+    }
+    let table_uri = endpoint.table_uri.clone();
+    match jobs.blocking(move |jobs| jobs.submit(&table_uri, &kind)).await {
+        Ok(id) => (StatusCode::ACCEPTED, Json(SubmitJobResponse { id })).into_response(),
+        Err(e) => internal_error(e),
+    }
+}
+
+/// `GET /jobs` - most recent jobs first
+async fn list_jobs(State(state): State<ApiState>, Query(query): Query<ListJobsQuery>) -> Response {
+    let jobs = match job_queue(&state, query.table.as_deref()) {
+        Ok((_, jobs)) => jobs,
+        Err(response) => return response,
+    };
+    let limit = query.limit;
+    match jobs.blocking(move |jobs| jobs.list(limit)).await {
+        Ok(list) => Json::<Vec<Job>>(list).into_response(),
+        Err(e) => internal_error(e),
+    }
+}
+
+/// `GET /jobs/{id}` - status of a single job
+async fn get_job(State(state): State<ApiState>, Path(id): Path<i64>, Query(query): Query<TableQuery>) -> Response {
+    let jobs = match job_queue(&state, query.table.as_deref()) {
+        Ok((_, jobs)) => jobs,
+        Err(response) => return response,
+    };
+    match jobs.blocking(move |jobs| jobs.get(id)).await {
+        Ok(Some(job)) => Json(job).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, format!("Job {} not found", id)).into_response(),
+        Err(e) => internal_error(e),
+    }
+}
+
+/// `GET /sources/status` - consumption and commit lag of the ingestion source
+async fn source_status(State(state): State<ApiState>, Query(query): Query<TableQuery>) -> Response {
+    let endpoint = match state.table(query.table.as_deref()) {
+        Ok(endpoint) => endpoint,
+        Err(response) => return response,
+    };
+    match &endpoint.source_status {
+        Some(status) => Json(status.lock().unwrap().clone()).into_response(),
+        None => (StatusCode::NOT_FOUND, "No ingestion source is configured").into_response(),
+    }
+}
+
+/// `GET /sinks/status` - delivery lag of each secondary sink behind the table
+async fn sink_status(State(state): State<ApiState>, Query(query): Query<TableQuery>) -> Response {
+    let endpoint = match state.table(query.table.as_deref()) {
+        Ok(endpoint) => endpoint,
+        Err(response) => return response,
+    };
+    let statuses: Vec<_> = endpoint
+        .sink_status
+        .iter()
+        .map(|status| status.lock().unwrap().clone())
+        .collect();
+    Json(statuses).into_response()
+}
+
+/// `GET /metrics` - process resource usage and per-table writer, compaction
+/// and vacuum counters
+async fn metrics(State(state): State<ApiState>) -> Response {
+    let tables = state
+        .tables
+        .iter()
+        .map(|(name, endpoint)| {
+            let metrics = TableMetrics {
+                queued_batches: endpoint.batches.queued(),
+                paused_maintenance: endpoint.maintenance.pauses(),
+                counters: endpoint.metrics.snapshot(),
+            };
+            (name.clone(), metrics)
+        })
+        .collect();
+    Json(MetricsResponse { process: state.process_metrics.sample(), tables }).into_response()
+}
+
+/// `GET /ui` - triage page polling the admin API
+async fn ui() -> Html<&'static str> {
+    Html(UI_HTML)
+}
+
+/// `GET /tables` - served tables with queue depth and commit freshness
+async fn list_tables(State(state): State<ApiState>) -> Response {
+    let now = Utc::now();
+    let tables: Vec<TableSummary> =
+        state.tables.iter().map(|(name, endpoint)| TableSummary::of(name, endpoint, now)).collect();
+    Json(tables).into_response()
+}
+
+/// `POST /tables/{table}/flush` - flush the writer's pending and queued
+/// batches without waiting for the size or time trigger
+async fn flush_table(State(state): State<ApiState>, Path(table): Path<String>) -> Response {
+    let endpoint = match state.table(Some(&table)) {
+        Ok(endpoint) => endpoint,
+        Err(response) => return response,
+    };
+    if let Err(response) = endpoint.refuse_read_only() {
+        return response;
+    }
+    endpoint.control.request_flush();
+    (StatusCode::ACCEPTED, Json(TableSummary::of(&table, endpoint, Utc::now()))).into_response()
+}
+
+/// `POST /tables/{table}/pause` - stop flushing and reading sources; ingestion
+/// requests wait once the writer queue is full
+async fn pause_table(State(state): State<ApiState>, Path(table): Path<String>) -> Response {
+    let endpoint = match state.table(Some(&table)) {
+        Ok(endpoint) => endpoint,
+        Err(response) => return response,
+    };
+    if let Err(response) = endpoint.refuse_read_only() {
+        return response;
+    }
+    if endpoint.control.pause() {
+        log::warn!("Writer of {} paused through the admin API", table);
+    }
+    Json(TableSummary::of(&table, endpoint, Utc::now())).into_response()
+}
+
+/// `POST /tables/{table}/maintenance/{process}/pause` - skip the scheduled
+/// compaction or vacuum cycles until resumed
+async fn pause_maintenance(
+    State(state): State<ApiState>,
+    Path((table, process)): Path<(String, Maintenance)>,
+) -> Response {
+    let endpoint = match state.table(Some(&table)) {
+        Ok(endpoint) => endpoint,
+        Err(response) => return response,
+    };
+    if let Err(response) = endpoint.refuse_read_only() {
+        return response;
+    }
+    if endpoint.maintenance.pause(process) {
+        log::warn!("{} of {} paused through the admin API", process, table);
+    }
+    Json(TableSummary::of(&table, endpoint, Utc::now())).into_response()
+}
+
+/// `POST /tables/{table}/maintenance/{process}/resume`
+async fn resume_maintenance(
+    State(state): State<ApiState>,
+    Path((table, process)): Path<(String, Maintenance)>,
+) -> Response {
+    let endpoint = match state.table(Some(&table)) {
+        Ok(endpoint) => endpoint,
+        Err(response) => return response,
+    };
+    if endpoint.maintenance.resume(process) {
+        log::info!("{} of {} resumed through the admin API", process, table);
+    }
+    Json(TableSummary::of(&table, endpoint, Utc::now())).into_response()
+}
+
+/// `POST /tables/{table}/resume` - undo `pause`
+async fn resume_table(State(state): State<ApiState>, Path(table): Path<String>) -> Response {
+    let endpoint = match state.table(Some(&table)) {
+        Ok(endpoint) => endpoint,
+        Err(response) => return response,
+    };
+    if endpoint.control.resume() {
+        log::info!("Writer of {} resumed through the admin API", table);
+    }
+    Json(TableSummary::of(&table, endpoint, Utc::now())).into_response()
+}
+
+/// `GET /tables/{table}/commits` - versions recently committed by the writer,
+/// newest first, with the batches each one contains
+async fn recent_commits(
+    State(state): State<ApiState>,
+    Path(table): Path<String>,
+    Query(query): Query<RecentCommitsQuery>,
+) -> Response {
+    let endpoint = match state.table(Some(&table)) {
+        Ok(endpoint) => endpoint,
+        Err(response) => return response,
+    };
+    let mut commits: Vec<CommitSummary> = Vec::new();
+    for receipt in endpoint.metrics.recent_batches(usize::MAX) {
+        match commits.last_mut() {
+            Some(commit) if commit.version == receipt.version => commit.batch_ids.push(receipt.batch_id),
+            _ if commits.len() == query.limit => break,
+            _ => commits.push(CommitSummary {
+                version: receipt.version,
+                committed_at: receipt.committed_at,
+                batch_ids: vec![receipt.batch_id],
+            }),
+        }
+    }
+    Json(commits).into_response()
+}
+
+/// `GET /tables/{table}/errors` - recent failures of the table's processes, newest first
+async fn recent_errors(State(state): State<ApiState>, Path(table): Path<String>) -> Response {
+    match state.table(Some(&table)) {
+        Ok(endpoint) => Json::<Vec<ErrorEvent>>(endpoint.metrics.recent_errors()).into_response(),
+        Err(response) => response,
+    }
+}
+
+/// `GET /tables/{table}/batches/{id}` - the version that committed a recently
+/// ingested batch; unknown batches may still be queued or too old to track
+async fn batch_receipt(State(state): State<ApiState>, Path((table, id)): Path<(String, String)>) -> Response {
+    let endpoint = match state.table(Some(&table)) {
+        Ok(endpoint) => endpoint,
+        Err(response) => return response,
+    };
+    match endpoint.metrics.batch_receipt(&id) {
+        Some(receipt) => Json(receipt).into_response(),
+        None => (StatusCode::NOT_FOUND, format!("Batch {} has no recent commit", id)).into_response(),
+    }
+}
+
+/// `GET /tables/{table}/commits/stream` - server-sent `commit` events for every
+/// new table version; a `lagged` event carries the number of commits a slow
+/// client missed
+async fn commit_stream(State(state): State<ApiState>, Path(table): Path<String>) -> Response {
+    let endpoint = match state.table(Some(&table)) {
+        Ok(endpoint) => endpoint,
+        Err(response) => return response,
+    };
+    let Some(feed) = &endpoint.commit_feed else {
+        return (StatusCode::NOT_FOUND, "Commit feed is not enabled for this table").into_response();
+    };
+
+    let events = stream::unfold(feed.subscribe(), |mut commits| async move {
+        let event = match commits.recv().await {
+            Ok(commit) => Event::default().event("commit").json_data(commit),
+            Err(RecvError::Lagged(missed)) => Ok(Event::default().event("lagged").data(missed.to_string())),
+            Err(RecvError::Closed) => return None,
+        };
+        Some((event, commits))
+    })
+    .take_until(state.shutdown.clone().cancelled_owned());
+
+    Sse::new(events).keep_alive(KeepAlive::default()).into_response()
+}
+
+/// `GET /tables/{table}/quality` - failures from the latest data test run and
+/// the last version that passed
+async fn quality_status(State(state): State<ApiState>, Path(table): Path<String>) -> Response {
+    let endpoint = match state.table(Some(&table)) {
+        Ok(endpoint) => endpoint,
+        Err(response) => return response,
+    };
+    match &endpoint.quality_status {
+        Some(status) => Json(status.lock().unwrap().clone()).into_response(),
+        None => (StatusCode::NOT_FOUND, "Data tests are not enabled for this table").into_response(),
+    }
+}
+
+/// Decode a request body into a DataFrame based on its content type
+pub fn decode_payload(content_type: &str, body: Bytes) -> Result<DataFrame> {
+    let mime = content_type.split(';').next().unwrap_or_default().trim();
+
+    let df = match mime {
+        "application/x-ndjson" | "application/jsonl" | "application/json" => {
+            JsonReader::new(Cursor::new(body))
+                .with_json_format(JsonFormat::JsonLines)
+                .finish()
+                .context("Invalid NDJSON payload")?
+        }
+        ARROW_STREAM_CONTENT_TYPE => IpcStreamReader::new(Cursor::new(body))
+            .finish()
+            .context("Invalid Arrow IPC stream payload")?,
+        other => bail!("Unsupported content type '{}'", other),
+    };
+
+    Ok(df)
+}
@@ -0,0 +1,154 @@
+use anyhow::{bail, Context, Result};
+use deltalake::kernel::{Add, Transaction};
+use deltalake::operations::transaction::CommitProperties;
+use deltalake::writer::{DeltaWriter, RecordBatchWriter};
+use deltalake::{DeltaTable, ObjectStoreError, Path};
+use polars::prelude::DataFrame;
+use serde_json::Value;
+use tokio::time::Instant;
+use crate::compat;
+use crate::config::WriteMode;
+use crate::correlation::new_batch_id;
+use crate::schema::{align_batch, plan_schema_change, SchemaChange};
+use crate::writer::WriterProcess;
+
+/// A group of appends published as a single Delta commit. Each append is
+/// uploaded right away, so the session holds file paths rather than rows;
+/// nothing is visible to readers until [`WriteSession::commit`]. A session
+/// dropped without `commit` or `abort` leaves its files for vacuum.
+pub struct WriteSession {
+    writer: WriterProcess,
+    table: DeltaTable,
+    adds: Vec<Add>,
+    batch_ids: Vec<String>,
+    rows: usize,
+    started: Instant,
+}
+
+impl WriteSession {
+    /// Start a session on the latest version of `table`
+    pub(crate) async fn begin(writer: WriterProcess, mut table: DeltaTable) -> Result<Self> {
+        if let WriteMode::Merge { .. } = writer.config().write_mode {
+            bail!("Write sessions only support append mode");
+        }
+        table.update().await
+            .context("Failed to refresh table before starting a write session")?;
+        compat::ensure_writable(&table)?;
+
+        Ok(Self {
+            writer,
+            table,
+            adds: Vec::new(),
+            batch_ids: Vec::new(),
+            rows: 0,
+            started: Instant::now(),
+        })
+    }
+
+    /// Upload the rows of `df`, returning the id they will be committed
+    /// under. Sessions never evolve the table schema, so the batch must
+    /// match it.
+    pub async fn append(&mut self, df: DataFrame) -> Result<String> {
+        let batch = df.to_arrow(None)
+            .context("Failed to convert DataFrame to Arrow")?;
+        let table_schema = self.writer.table_arrow_schema(&self.table)?;
+        let batch = align_batch(batch, &table_schema)?;
+        let change = plan_schema_change(self.writer.config().schema_evolution, &table_schema, &batch.schema())?;
+        if change != SchemaChange::Unchanged {
+            bail!("Batch does not match the table schema; write sessions cannot change it");
+        }
+
+        let mut writer = RecordBatchWriter::for_table(&self.table)
+            .context("Failed to create RecordBatchWriter")?;
+        writer.write(batch).await.context("Failed to write batch")?;
+        let adds = writer.flush().await.context("Failed to upload batch")?;
+
+        let id = new_batch_id();
+        log::debug!("Write session uploaded batch {} as {} files", id, adds.len());
+        self.adds.extend(adds);
+        self.batch_ids.push(id.clone());
+        self.rows += df.height();
+        Ok(id)
+    }
+
+    /// Rows appended so far
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Publish every appended file in one commit, returning its version
+    pub async fn commit(self) -> Result<i64> {
+        self.commit_with_txns(&[]).await
+    }
+
+    /// [`Self::commit`], recording the given application transactions in the
+    /// same commit. If they are already committed the uploads are discarded.
+    pub async fn commit_with_txns(mut self, txns: &[Transaction]) -> Result<i64> {
+        if self.adds.is_empty() {
+            return Ok(self.table.version());
+        }
+
+        let commit_properties = CommitProperties::default()
+            .with_application_transactions(txns.to_vec())
+            .with_metadata(vec![("batchIds".to_string(), Value::from(self.batch_ids.clone()))]);
+        let table_schema = self.writer.table_arrow_schema(&self.table)?;
+        let adds = std::mem::take(&mut self.adds);
+        let committed = self
+            .writer
+            .commit_files(&mut self.table, adds.clone(), None, &table_schema, txns, commit_properties)
+            .await
+            // The commit may or may not have landed, so the files stay
+            .inspect_err(|_| log::warn!("Write session commit failed; vacuum removes its files if unreferenced"))?;
+
+        if !committed {
+            delete_files(&self.table, &adds).await?;
+            return Ok(self.table.version());
+        }
+
+        let version = self.table.version();
+        let metrics = self.writer.metrics();
+        metrics.record_write(self.rows, self.started.elapsed());
+        metrics.record_batches_committed(&self.batch_ids, version);
+        self.writer.record_checksums(&self.table, version);
+        tracing::info!(
+            table_uri = %self.table.table_uri(),
+            rows = self.rows,
+            latency_ms = self.started.elapsed().as_millis() as u64,
+            version,
+            batches = self.batch_ids.len(),
+            "Write session committed"
+        );
+        Ok(version)
+    }
+
+    /// Discard the session and delete the files it uploaded
+    pub async fn abort(mut self) -> Result<()> {
+        let adds = std::mem::take(&mut self.adds);
+        delete_files(&self.table, &adds).await?;
+        log::info!("Write session aborted, removed {} uploaded files", adds.len());
+        Ok(())
+    }
+}
+
+impl Drop for WriteSession {
+    fn drop(&mut self) {
+        if !self.adds.is_empty() {
+            log::warn!(
+                "Write session dropped with {} uncommitted files; vacuum will remove them",
+                self.adds.len()
+            );
+        }
+    }
+}
+
+/// Remove uploaded files that no commit references
+async fn delete_files(table: &DeltaTable, adds: &[Add]) -> Result<()> {
+    let store = table.object_store();
+    for add in adds {
+        match store.delete(&Path::from(add.path.as_str())).await {
+            Ok(()) | Err(ObjectStoreError::NotFound { .. }) => {}
+            Err(e) => return Err(e).with_context(|| format!("Failed to delete {}", add.path)),
+        }
+    }
+    Ok(())
+}
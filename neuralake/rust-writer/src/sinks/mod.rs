@@ -0,0 +1,359 @@
+//! Secondary sinks that receive a copy of every batch committed to Delta,
+//! for serving workloads that need a row store or an OLAP engine.
+//!
+//! Each sink records the Delta version of the last batch it accepted (its
+//! high-water mark), so replays after a restart are skipped and the lag
+//! between the table and the sink can be reported.
+
+pub mod clickhouse;
+pub mod postgres;
+
+use anyhow::{Context, Result};
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use crate::config::{SinkConfig, SinkKind};
+use crate::retry;
+
+/// A committed batch together with the Delta version that contains it
+#[derive(Debug, Clone)]
+pub struct SinkBatch {
+    pub df: DataFrame,
+    pub version: i64,
+    /// Ids of the ingested batches whose rows make up `df`
+    pub batch_ids: Vec<String>,
+}
+
+/// Connector to a secondary store
+#[allow(async_fn_in_trait)]
+pub trait Sink {
+    /// Prepare the store, e.g. create the progress table
+    async fn init(&self) -> Result<()>;
+
+    /// Insert the batch and advance the high-water mark to `batch.version`
+    async fn insert(&self, batch: &SinkBatch) -> Result<()>;
+
+    /// Delta version of the last batch the store accepted
+    async fn high_water_mark(&self) -> Result<Option<i64>>;
+}
+
+/// The configured sink implementations
+pub enum AnySink {
+    Clickhouse(clickhouse::ClickHouseSink),
+    Postgres(postgres::PostgresSink),
+}
+
+impl AnySink {
+    /// Build the connector for `config`; `source` identifies the Delta table
+    /// in the sink's progress table
+    pub async fn connect(config: &SinkConfig, source: &str) -> Result<Self> {
+        Ok(match &config.kind {
+            SinkKind::Clickhouse { url, table, progress_table, user, password } => {
+                AnySink::Clickhouse(clickhouse::ClickHouseSink::new(
+                    url,
+                    table,
+                    progress_table,
+                    user.clone(),
+                    password.clone(),
+                    source,
+                ))
+            }
+            SinkKind::Postgres { connection_string, table, progress_table } => AnySink::Postgres(
+                postgres::PostgresSink::connect(connection_string, table, progress_table, source).await?,
+            ),
+        })
+    }
+}
+
+impl Sink for AnySink {
+    async fn init(&self) -> Result<()> {
+        match self {
+            AnySink::Clickhouse(sink) => sink.init().await,
+            AnySink::Postgres(sink) => sink.init().await,
+        }
+    }
+
+    async fn insert(&self, batch: &SinkBatch) -> Result<()> {
+        match self {
+            AnySink::Clickhouse(sink) => sink.insert(batch).await,
+            AnySink::Postgres(sink) => sink.insert(batch).await,
+        }
+    }
+
+    async fn high_water_mark(&self) -> Result<Option<i64>> {
+        match self {
+            AnySink::Clickhouse(sink) => sink.high_water_mark().await,
+            AnySink::Postgres(sink) => sink.high_water_mark().await,
+        }
+    }
+}
+
+/// Consistency between the Delta table and one sink
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SinkStatus {
+    pub name: String,
+    /// Latest Delta version handed to the sink
+    pub delta_version: Option<i64>,
+    /// Latest Delta version the sink has accepted
+    pub high_water_mark: Option<i64>,
+    /// Whether the sink is connected and initialised; batches are
+    /// dead-lettered until it is
+    pub connected: bool,
+    pub batches_inserted: u64,
+    pub retries: u64,
+    /// Batches written to the dead-letter directory instead of the sink
+    pub dead_lettered: u64,
+}
+
+impl SinkStatus {
+    /// Delta versions committed but not yet visible in the sink
+    pub fn lag_versions(&self) -> i64 {
+        match (self.delta_version, self.high_water_mark) {
+            (Some(delta), Some(hwm)) => (delta - hwm).max(0),
+            (Some(delta), None) => delta + 1,
+            _ => 0,
+        }
+    }
+}
+
+/// Handle through which a sink publishes its status
+pub type SinkStatusHandle = Arc<std::sync::Mutex<SinkStatus>>;
+
+/// Sending half of a sink's queue, held by the writer
+#[derive(Debug, Clone)]
+pub struct SinkSender {
+    name: String,
+    sender: mpsc::Sender<SinkBatch>,
+    status: SinkStatusHandle,
+    dead_letter_dir: PathBuf,
+}
+
+impl SinkSender {
+    /// Hand a committed batch to the sink without waiting. A full queue means
+    /// the sink is far behind, so the batch is dead-lettered instead.
+    pub fn dispatch(&self, batch: SinkBatch) {
+        self.status.lock().unwrap().delta_version = Some(batch.version);
+
+        if let Err(mpsc::error::TrySendError::Full(batch) | mpsc::error::TrySendError::Closed(batch)) =
+            self.sender.try_send(batch)
+        {
+            log::warn!("Sink {} queue unavailable, dead-lettering version {}", self.name, batch.version);
+            dead_letter(&self.dead_letter_dir, &self.name, &batch, &self.status);
+        }
+    }
+}
+
+/// The Sink process - drains one sink's queue with its own retries
+pub struct SinkProcess {
+    config: SinkConfig,
+    receiver: mpsc::Receiver<SinkBatch>,
+    status: SinkStatusHandle,
+}
+
+impl SinkProcess {
+    /// Create the process and the sender the writer dispatches batches to
+    pub fn new(config: SinkConfig) -> (Self, SinkSender) {
+        let (sender, receiver) = mpsc::channel(config.queue_capacity);
+        let status = Arc::new(std::sync::Mutex::new(SinkStatus {
+            name: config.name.clone(),
+            ..Default::default()
+        }));
+        let handle = SinkSender {
+            name: config.name.clone(),
+            sender,
+            status: status.clone(),
+            dead_letter_dir: PathBuf::from(&config.dead_letter_dir),
+        };
+        (Self { config, receiver, status }, handle)
+    }
+
+    pub fn status(&self) -> SinkStatusHandle {
+        self.status.clone()
+    }
+
+    /// Main run loop for the sink process. On shutdown every batch already
+    /// queued is still delivered (or dead-lettered) before exiting. Never
+    /// fails: a sink that is down only affects its own batches, never the
+    /// writer or maintenance.
+    pub async fn run(mut self, source: String, shutdown: CancellationToken) -> Result<()> {
+        log::info!("Starting sink {}", self.config.name);
+
+        let Some((sink, mut high_water_mark)) = self.open(&source, &shutdown).await else {
+            self.receiver.close();
+            while let Some(batch) = self.receiver.recv().await {
+                self.dead_letter(&batch);
+            }
+            return Ok(());
+        };
+
+        loop {
+            let batch = tokio::select! {
+                batch = self.receiver.recv() => batch,
+                _ = shutdown.cancelled() => {
+                    log::info!("Sink {} received shutdown signal", self.config.name);
+                    break;
+                }
+            };
+            let Some(batch) = batch else { break };
+            self.deliver(&sink, batch, &mut high_water_mark).await;
+        }
+
+        self.receiver.close();
+        while let Some(batch) = self.receiver.recv().await {
+            self.deliver(&sink, batch, &mut high_water_mark).await;
+        }
+
+        Ok(())
+    }
+
+    /// Connect to and initialise the sink, retrying with backoff for as long
+    /// as it takes and dead-lettering the batches dispatched meanwhile.
+    /// Returns the sink and its high-water mark; `None` on shutdown.
+    async fn open(&mut self, source: &str, shutdown: &CancellationToken) -> Option<(AnySink, Option<i64>)> {
+        let mut attempt = 0;
+        loop {
+            let connected = async {
+                let sink = AnySink::connect(&self.config, source).await?;
+                sink.init().await?;
+                let high_water_mark = sink.high_water_mark().await?;
+                Ok::<_, anyhow::Error>((sink, high_water_mark))
+            };
+            let error = match connected.await {
+                Ok((sink, high_water_mark)) => {
+                    let mut status = self.status.lock().unwrap();
+                    status.connected = true;
+                    status.high_water_mark = high_water_mark;
+                    return Some((sink, high_water_mark));
+                }
+                Err(e) => e,
+            };
+
+            attempt += 1;
+            self.status.lock().unwrap().retries += 1;
+            let delay = retry::backoff_delay(self.config.retry_delay_ms, 2.0, self.config.max_retry_delay_ms, 0.2, attempt);
+            log::warn!(
+                "Failed to connect sink {} (attempt {}), retrying in {:?}: {:#}",
+                self.config.name,
+                attempt,
+                delay,
+                error
+            );
+            let retry_at = tokio::time::sleep(delay);
+            tokio::pin!(retry_at);
+            loop {
+                tokio::select! {
+                    _ = &mut retry_at => break,
+                    Some(batch) = self.receiver.recv() => self.dead_letter(&batch),
+                    _ = shutdown.cancelled() => return None,
+                }
+            }
+        }
+    }
+
+    fn dead_letter(&self, batch: &SinkBatch) {
+        dead_letter(Path::new(&self.config.dead_letter_dir), &self.config.name, batch, &self.status);
+    }
+
+    async fn deliver(&self, sink: &AnySink, batch: SinkBatch, high_water_mark: &mut Option<i64>) {
+        // Replayed after a restart: the sink already has this version
+        if high_water_mark.is_some_and(|hwm| batch.version <= hwm) {
+            log::debug!("Sink {} already has version {}", self.config.name, batch.version);
+            return;
+        }
+
+        let mut attempt = 0;
+        loop {
+            match sink.insert(&batch).await {
+                Ok(()) => {
+                    *high_water_mark = Some(batch.version);
+                    let mut status = self.status.lock().unwrap();
+                    status.high_water_mark = Some(batch.version);
+                    status.batches_inserted += 1;
+                    return;
+                }
+                Err(e) if attempt < self.config.max_retries => {
+                    attempt += 1;
+                    self.status.lock().unwrap().retries += 1;
+                    let delay = retry::backoff_delay(
+                        self.config.retry_delay_ms,
+                        2.0,
+                        self.config.max_retry_delay_ms,
+                        0.2,
+                        attempt,
+                    );
+                    log::warn!(
+                        "Sink {} insert of version {} failed (attempt {}), retrying in {:?}: {:#}",
+                        self.config.name,
+                        batch.version,
+                        attempt,
+                        delay,
+                        e
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                Err(e) => {
+                    log::error!(
+                        "Sink {} gave up on version {}: {:#}",
+                        self.config.name,
+                        batch.version,
+                        e
+                    );
+                    self.dead_letter(&batch);
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Write a batch the sink could not take to `{dir}/{sink}-v{version}.parquet`,
+/// with its batch ids alongside in `{sink}-v{version}.json`
+fn dead_letter(dir: &Path, sink: &str, batch: &SinkBatch, status: &SinkStatusHandle) {
+    let path = dir.join(format!("{}-v{}.parquet", sink, batch.version));
+    let record = serde_json::json!({ "sink": sink, "version": batch.version, "batch_ids": batch.batch_ids });
+    let result = fs::create_dir_all(dir)
+        .map_err(anyhow::Error::from)
+        .and_then(|_| File::create(&path).map_err(anyhow::Error::from))
+        .and_then(|file| {
+            ParquetWriter::new(file)
+                .finish(&mut batch.df.clone())
+                .map_err(anyhow::Error::from)
+        })
+        .and_then(|_| {
+            fs::write(path.with_extension("json"), record.to_string()).map_err(anyhow::Error::from)
+        });
+
+    match result {
+        Ok(_) => {
+            status.lock().unwrap().dead_lettered += 1;
+            log::warn!(
+                "Dead-lettered sink {} version {} (batches {:?}) to {}",
+                sink,
+                batch.version,
+                batch.batch_ids,
+                path.display()
+            );
+        }
+        Err(e) => log::error!(
+            "Failed to dead-letter sink {} version {} to {}: {:#}",
+            sink,
+            batch.version,
+            path.display(),
+            e
+        ),
+    }
+}
+
+/// Serialize a batch as newline-delimited JSON
+pub(crate) fn to_ndjson(df: &DataFrame) -> Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    JsonWriter::new(&mut buffer)
+        .with_json_format(JsonFormat::JsonLines)
+        .finish(&mut df.clone())
+        .context("Failed to serialize batch as JSON")?;
+    Ok(buffer)
+}
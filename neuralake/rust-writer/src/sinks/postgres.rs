@@ -0,0 +1,104 @@
+use anyhow::{Context, Result};
+use polars::prelude::*;
+use tokio::sync::Mutex;
+use tokio_postgres::{Client, NoTls};
+use crate::sinks::{Sink, SinkBatch};
+
+/// PostgreSQL sink. Rows are expanded server-side with
+/// `json_populate_recordset`, so the target table's column types drive the
+/// conversion, and the progress row commits in the same transaction.
+pub struct PostgresSink {
+    client: Mutex<Client>,
+    table: String,
+    progress_table: String,
+    source: String,
+}
+
+impl PostgresSink {
+    /// Connect and spawn the connection driver
+    pub async fn connect(
+        connection_string: &str,
+        table: &str,
+        progress_table: &str,
+        source: &str,
+    ) -> Result<Self> {
+        let (client, connection) = tokio_postgres::connect(connection_string, NoTls)
+            .await
+            .context("Failed to connect to PostgreSQL sink")?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                log::error!("PostgreSQL sink connection closed: {}", e);
+            }
+        });
+
+        Ok(Self {
+            client: Mutex::new(client),
+            table: table.to_string(),
+            progress_table: progress_table.to_string(),
+            source: source.to_string(),
+        })
+    }
+}
+
+impl Sink for PostgresSink {
+    async fn init(&self) -> Result<()> {
+        self.client
+            .lock()
+            .await
+            .batch_execute(&format!(
+                "CREATE TABLE IF NOT EXISTS {} (source TEXT PRIMARY KEY, version BIGINT NOT NULL)",
+                self.progress_table
+            ))
+            .await
+            .context("Failed to create PostgreSQL progress table")?;
+        Ok(())
+    }
+
+    async fn insert(&self, batch: &SinkBatch) -> Result<()> {
+        let mut rows = Vec::new();
+        JsonWriter::new(&mut rows)
+            .with_json_format(JsonFormat::Json)
+            .finish(&mut batch.df.clone())
+            .context("Failed to serialize batch as JSON")?;
+        let rows = String::from_utf8(rows)?;
+
+        let mut client = self.client.lock().await;
+        let txn = client.transaction().await?;
+        txn.execute(
+            &format!(
+                "INSERT INTO {table} SELECT * FROM json_populate_recordset(NULL::{table}, $1::text::json)",
+                table = self.table
+            ),
+            &[&rows],
+        )
+        .await
+        .with_context(|| format!("Failed to insert into PostgreSQL table {}", self.table))?;
+        txn.execute(
+            &format!(
+                "INSERT INTO {} (source, version) VALUES ($1, $2) \
+                 ON CONFLICT (source) DO UPDATE SET version = EXCLUDED.version",
+                self.progress_table
+            ),
+            &[&self.source, &batch.version],
+        )
+        .await
+        .context("Failed to record PostgreSQL sink progress")?;
+        txn.commit().await?;
+
+        Ok(())
+    }
+
+    async fn high_water_mark(&self) -> Result<Option<i64>> {
+        let row = self
+            .client
+            .lock()
+            .await
+            .query_opt(
+                &format!("SELECT version FROM {} WHERE source = $1", self.progress_table),
+                &[&self.source],
+            )
+            .await
+            .context("Failed to read PostgreSQL sink progress")?;
+        Ok(row.map(|row| row.get(0)))
+    }
+}
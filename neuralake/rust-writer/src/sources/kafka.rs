@@ -0,0 +1,281 @@
+use anyhow::{bail, Context, Result};
+use deltalake::kernel::Transaction;
+use deltalake::DeltaTable;
+use polars::prelude::*;
+use rdkafka::config::ClientConfig;
+use rdkafka::consumer::{BaseConsumer, CommitMode, Consumer, ConsumerContext, Rebalance, StreamConsumer};
+use rdkafka::message::Message;
+use rdkafka::statistics::Statistics;
+use rdkafka::{ClientContext, Offset, TopicPartitionList};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tokio::time::{sleep, timeout_at, Instant};
+use crate::config::KafkaSourceConfig;
+use crate::sources::{committed_positions, decode_json_messages, Source, SourceBatch, SourceStatusHandle};
+use crate::writer::BatchSender;
+
+/// How often librdkafka reports broker high watermarks, unless overridden
+const DEFAULT_STATISTICS_INTERVAL_MS: &str = "5000";
+/// How often the writer queue is checked while the partitions are paused
+const BACKPRESSURE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Kafka source with exactly-once delivery into Delta: each partition's next
+/// offset is committed as an application transaction alongside the rows.
+pub struct KafkaSource {
+    config: KafkaSourceConfig,
+    consumer: StreamConsumer<StatusContext>,
+    /// Next offset to commit per partition, advanced as messages are read
+    positions: BTreeMap<i32, i64>,
+    status: SourceStatusHandle,
+    backpressure: Option<Backpressure>,
+}
+
+/// The writer queue whose saturation pauses the partitions
+struct Backpressure {
+    queue: BatchSender,
+    paused_at: Option<Instant>,
+}
+
+/// Feeds librdkafka statistics and rebalance events into the source status
+pub struct StatusContext {
+    topic: String,
+    status: SourceStatusHandle,
+}
+
+impl ClientContext for StatusContext {
+    fn stats(&self, statistics: Statistics) {
+        let Some(topic) = statistics.topics.get(&self.topic) else {
+            return;
+        };
+        let mut status = self.status.lock().unwrap();
+        for (id, partition) in &topic.partitions {
+            // librdkafka reports an internal "unassigned" partition as -1
+            if *id < 0 {
+                continue;
+            }
+            if let Some(entry) = status.partitions.get_mut(&id.to_string()) {
+                entry.high_watermark = Some(partition.hi_offset);
+            }
+        }
+    }
+}
+
+impl ConsumerContext for StatusContext {
+    fn pre_rebalance(&self, _consumer: &BaseConsumer<Self>, rebalance: &Rebalance<'_>) {
+        // Partitions are assigned manually, so a rebalance means another
+        // member of the group is subscribing to the same topic
+        log::warn!("Kafka consumer group rebalance observed: {:?}", rebalance);
+        self.status.lock().unwrap().rebalances += 1;
+    }
+}
+
+impl KafkaSource {
+    /// Create a consumer for the configured topic; partitions are assigned on `resume`
+    pub fn new(config: KafkaSourceConfig, status: SourceStatusHandle) -> Result<Self> {
+        if !(0.0 < config.resume_queue_fraction && config.resume_queue_fraction < config.pause_queue_fraction) {
+            bail!("Kafka resume_queue_fraction must be above 0 and below pause_queue_fraction");
+        }
+
+        let mut client = ClientConfig::new();
+        client
+            .set("bootstrap.servers", &config.brokers)
+            .set("group.id", &config.group_id)
+            .set("enable.auto.commit", "false")
+            .set("auto.offset.reset", &config.auto_offset_reset)
+            .set("statistics.interval.ms", DEFAULT_STATISTICS_INTERVAL_MS);
+        for (key, value) in &config.properties {
+            client.set(key, value);
+        }
+
+        status.lock().unwrap().name = config.topic.clone();
+        let context = StatusContext { topic: config.topic.clone(), status: status.clone() };
+
+        let consumer: StreamConsumer<StatusContext> = client.create_with_context(context)
+            .context("Failed to create Kafka consumer")?;
+
+        Ok(Self { config, consumer, positions: BTreeMap::new(), status, backpressure: None })
+    }
+
+    /// Stop fetching while `queue` is saturated, rather than letting
+    /// librdkafka buffer messages the writer cannot keep up with
+    pub fn with_backpressure(mut self, queue: BatchSender) -> Self {
+        self.backpressure = Some(Backpressure { queue, paused_at: None });
+        self
+    }
+
+    /// Pause the assigned partitions once the writer queue reaches
+    /// `pause_queue_fraction` of its capacity and resume them once it drains
+    /// to `resume_queue_fraction`. Returns whether they are paused.
+    fn apply_backpressure(&mut self) -> Result<bool> {
+        let Some(backpressure) = &mut self.backpressure else {
+            return Ok(false);
+        };
+        let queued = backpressure.queue.queued();
+        let fill = queued as f64 / backpressure.queue.max_queued().max(1) as f64;
+        match backpressure.paused_at {
+            None if fill >= self.config.pause_queue_fraction => {
+                let assignment = self.consumer.assignment().context("Failed to read Kafka assignment")?;
+                self.consumer.pause(&assignment).context("Failed to pause Kafka partitions")?;
+                log::warn!("Writer queue holds {} batches; pausing Kafka {}", queued, self.config.topic);
+                backpressure.paused_at = Some(Instant::now());
+                let mut status = self.status.lock().unwrap();
+                status.backpressure_pauses += 1;
+                status.paused = true;
+                Ok(true)
+            }
+            Some(paused_at) if fill <= self.config.resume_queue_fraction => {
+                let assignment = self.consumer.assignment().context("Failed to read Kafka assignment")?;
+                self.consumer.resume(&assignment).context("Failed to resume Kafka partitions")?;
+                let paused_for = paused_at.elapsed();
+                log::info!(
+                    "Writer queue drained to {} batches; resuming Kafka {} after {:?}",
+                    queued,
+                    self.config.topic,
+                    paused_for
+                );
+                backpressure.paused_at = None;
+                let mut status = self.status.lock().unwrap();
+                status.paused_ms += paused_for.as_millis() as u64;
+                status.paused = false;
+                Ok(false)
+            }
+            paused_at => Ok(paused_at.is_some()),
+        }
+    }
+
+    /// Application transaction id prefix shared by all partitions of this source
+    fn app_id_prefix(&self) -> String {
+        format!("kafka:{}:{}:", self.config.group_id, self.config.topic)
+    }
+
+    fn app_id(&self, partition: i32) -> String {
+        format!("{}{}", self.app_id_prefix(), partition)
+    }
+}
+
+impl Source for KafkaSource {
+    fn name(&self) -> &str {
+        &self.config.topic
+    }
+
+    async fn resume(&mut self, table: &DeltaTable) -> Result<()> {
+        let committed = committed_positions(table, &self.app_id_prefix());
+
+        let metadata = self
+            .consumer
+            .fetch_metadata(Some(&self.config.topic), Duration::from_secs(10))
+            .context("Failed to fetch Kafka topic metadata")?;
+        let Some(topic) = metadata.topics().first() else {
+            bail!("Kafka topic {} not found", self.config.topic);
+        };
+
+        // Partitions are assigned manually so the table, not the group
+        // coordinator, is the source of truth for where to resume
+        let mut assignment = TopicPartitionList::new();
+        let mut status = self.status.lock().unwrap();
+        status.partitions.clear();
+        for partition in topic.partitions() {
+            let next = committed.get(&self.app_id(partition.id())).copied();
+            let offset = match next {
+                Some(next) => Offset::Offset(next),
+                None => Offset::Stored,
+            };
+            log::info!(
+                "Kafka {}[{}] resuming from {:?}",
+                self.config.topic,
+                partition.id(),
+                offset
+            );
+            assignment.add_partition_offset(&self.config.topic, partition.id(), offset)?;
+
+            let entry = status.partitions.entry(partition.id().to_string()).or_default();
+            entry.consumed = next.unwrap_or_default();
+            entry.committed = next.unwrap_or_default();
+        }
+        status.assignment_changes += 1;
+        drop(status);
+
+        self.consumer.assign(&assignment)
+            .context("Failed to assign Kafka partitions")?;
+
+        Ok(())
+    }
+
+    async fn next_batch(&mut self, max_rows: usize, max_wait: Duration) -> Result<Option<SourceBatch>> {
+        let deadline = Instant::now() + max_wait;
+        while self.apply_backpressure()? {
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+            sleep(BACKPRESSURE_POLL_INTERVAL.min(deadline - now)).await;
+        }
+
+        let mut messages = Vec::new();
+        let mut advanced = BTreeMap::new();
+
+        while messages.len() < max_rows {
+            let message = match timeout_at(deadline, self.consumer.recv()).await {
+                Err(_) => break, // batch window elapsed
+                Ok(message) => message.context("Failed to receive Kafka message")?,
+            };
+
+            if let Some(bytes) = message.payload() {
+                messages.push(bytes.to_vec());
+            }
+            advanced.insert(message.partition(), message.offset() + 1);
+        }
+
+        if advanced.is_empty() {
+            return Ok(None);
+        }
+
+        self.positions.extend(advanced.iter().map(|(p, o)| (*p, *o)));
+        {
+            let mut status = self.status.lock().unwrap();
+            status.messages_consumed += messages.len() as u64;
+            for (partition, next) in &advanced {
+                status.partitions.entry(partition.to_string()).or_default().consumed = *next;
+            }
+        }
+
+        let df = if messages.is_empty() {
+            DataFrame::empty()
+        } else {
+            decode_json_messages(&messages, &self.config.topic, &self.status)?
+        };
+
+        let checkpoints = advanced
+            .into_iter()
+            .map(|(partition, next)| Transaction::new(self.app_id(partition), next))
+            .collect();
+
+        Ok(Some(SourceBatch { df, checkpoints, metadata: Vec::new() }))
+    }
+
+    async fn committed(&mut self, checkpoints: &[Transaction]) -> Result<()> {
+        {
+            let mut status = self.status.lock().unwrap();
+            for txn in checkpoints {
+                if let Some(partition) = txn.app_id.strip_prefix(&self.app_id_prefix()) {
+                    status.partitions.entry(partition.to_string()).or_default().committed = txn.version;
+                }
+            }
+        }
+
+        // Offsets in Kafka are informational only (lag dashboards); the
+        // Delta log stays authoritative for resuming
+        let mut offsets = TopicPartitionList::new();
+        for (partition, next) in &self.positions {
+            offsets.add_partition_offset(&self.config.topic, *partition, Offset::Offset(*next))?;
+        }
+        if let Err(e) = self.consumer.commit(&offsets, CommitMode::Async) {
+            log::debug!("Failed to commit Kafka offsets: {}", e);
+        }
+        Ok(())
+    }
+
+    fn status(&self) -> Option<SourceStatusHandle> {
+        Some(self.status.clone())
+    }
+}
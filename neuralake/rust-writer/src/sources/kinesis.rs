@@ -0,0 +1,403 @@
+use anyhow::{bail, Context, Result};
+use aws_config::{BehaviorVersion, Region};
+use aws_sdk_kinesis::types::{Shard, ShardIteratorType};
+use aws_sdk_kinesis::Client;
+use deltalake::kernel::Transaction;
+use deltalake::DeltaTable;
+use polars::prelude::DataFrame;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::Duration;
+use tokio::time::{sleep, Instant};
+use crate::config::KinesisSourceConfig;
+use crate::sources::{committed_positions, decode_json_messages, Source, SourceBatch, SourceStatusHandle};
+
+/// Commit info entry holding the sequence number each shard was read up to
+const CHECKPOINTS_KEY: &str = "kinesisCheckpoints";
+/// Most records a single GetRecords call may return
+const MAX_RECORDS_PER_CALL: usize = 10_000;
+/// Pause after a pass over every shard returned nothing, or was throttled
+const IDLE_BACKOFF: Duration = Duration::from_millis(250);
+/// Commits searched for checkpoints before reading the whole history
+const RECENT_COMMITS: usize = 100;
+
+/// Read state of one shard
+#[derive(Debug, Default)]
+struct ShardReader {
+    /// Iterator for the next GetRecords call; fetched lazily
+    iterator: Option<String>,
+    /// Last sequence number read, committed or not
+    sequence: Option<String>,
+    /// Batches with records from this shard committed so far, the version
+    /// of its application transaction
+    committed_batches: i64,
+    /// Parents that must be read to their end first, so that records of a
+    /// key stay in order across a split or merge
+    parents: Vec<String>,
+    /// Read to its end after being closed by resharding
+    closed: bool,
+}
+
+/// Kinesis source with exactly-once delivery into Delta. Sequence numbers do
+/// not fit a transaction version, so each shard's transaction counts the
+/// batches committed from it, and the sequence number of the last record is
+/// recorded in the commit info of the same commit.
+pub struct KinesisSource {
+    config: KinesisSourceConfig,
+    client: Client,
+    shards: BTreeMap<String, ShardReader>,
+    /// Shards read to their end in an earlier run or already trimmed
+    finished: HashSet<String>,
+    next_discovery: Instant,
+    status: SourceStatusHandle,
+}
+
+impl KinesisSource {
+    /// Create a client for the configured stream; shards are listed on `resume`
+    pub async fn new(config: KinesisSourceConfig, status: SourceStatusHandle) -> Result<Self> {
+        let mut loader = aws_config::defaults(BehaviorVersion::latest());
+        if let Some(region) = &config.region {
+            loader = loader.region(Region::new(region.clone()));
+        }
+        if let Some(endpoint) = &config.endpoint_url {
+            loader = loader.endpoint_url(endpoint);
+        }
+        let client = Client::new(&loader.load().await);
+
+        status.lock().unwrap().name = config.stream_name.clone();
+        Ok(Self {
+            config,
+            client,
+            shards: BTreeMap::new(),
+            finished: HashSet::new(),
+            next_discovery: Instant::now(),
+            status,
+        })
+    }
+
+    /// Application transaction id prefix shared by all shards of this stream
+    fn app_id_prefix(&self) -> String {
+        format!("kinesis:{}:", self.config.stream_name)
+    }
+
+    fn app_id(&self, shard_id: &str) -> String {
+        format!("{}{}", self.app_id_prefix(), shard_id)
+    }
+
+    /// Every shard of the stream, open or closed, that is still retained
+    async fn list_shards(&self) -> Result<Vec<Shard>> {
+        let mut shards = Vec::new();
+        let mut next_token: Option<String> = None;
+        loop {
+            // The stream name must be left out once paginating
+            let request = match &next_token {
+                Some(token) => self.client.list_shards().next_token(token),
+                None => self.client.list_shards().stream_name(&self.config.stream_name),
+            };
+            let page = request
+                .send()
+                .await
+                .with_context(|| format!("Failed to list shards of Kinesis stream {}", self.config.stream_name))?;
+            shards.extend(page.shards().iter().cloned());
+            match page.next_token() {
+                Some(token) => next_token = Some(token.to_string()),
+                None => return Ok(shards),
+            }
+        }
+    }
+
+    /// Start reading shards created since the last look, e.g. by resharding.
+    /// Shards trimmed from the stream are forgotten once read.
+    async fn discover_shards(&mut self) -> Result<()> {
+        let listed = self.list_shards().await?;
+        let listed_ids: HashSet<&str> = listed.iter().map(|s| s.shard_id()).collect();
+        for shard in &listed {
+            let id = shard.shard_id();
+            if self.shards.contains_key(id) || self.finished.contains(id) {
+                continue;
+            }
+            let parents: Vec<String> = [shard.parent_shard_id(), shard.adjacent_parent_shard_id()]
+                .into_iter()
+                .flatten()
+                .filter(|parent| listed_ids.contains(parent))
+                .map(str::to_string)
+                .collect();
+            log::info!("Kinesis {} found shard {} (parents {:?})", self.config.stream_name, id, parents);
+            self.shards.insert(id.to_string(), ShardReader { parents, ..Default::default() });
+            self.status.lock().unwrap().partitions.entry(id.to_string()).or_default();
+        }
+        self.next_discovery = Instant::now() + self.config.shard_discovery_interval();
+        Ok(())
+    }
+
+    /// A shard is read once every parent still in the stream is finished
+    fn readable(&self, shard: &ShardReader) -> bool {
+        !shard.closed && shard.parents.iter().all(|parent| self.finished.contains(parent))
+    }
+
+    /// Iterator positioned after the shard's last read record, or at the
+    /// configured initial position for a shard never read
+    async fn shard_iterator(&self, shard_id: &str, sequence: Option<&str>, has_parents: bool) -> Result<Option<String>> {
+        let request = self
+            .client
+            .get_shard_iterator()
+            .stream_name(&self.config.stream_name)
+            .shard_id(shard_id);
+        let request = match sequence {
+            Some(sequence) => request
+                .shard_iterator_type(ShardIteratorType::AfterSequenceNumber)
+                .starting_sequence_number(sequence),
+            // Children of a resharding start at their beginning, wherever
+            // the stream as a whole started
+            None if has_parents => request.shard_iterator_type(ShardIteratorType::TrimHorizon),
+            None => match self.config.initial_position.as_str() {
+                "earliest" => request.shard_iterator_type(ShardIteratorType::TrimHorizon),
+                "latest" => request.shard_iterator_type(ShardIteratorType::Latest),
+                other => bail!("Kinesis initial_position must be earliest or latest, got '{}'", other),
+            },
+        };
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("Failed to get an iterator for Kinesis shard {}", shard_id))?;
+        Ok(response.shard_iterator().map(str::to_string))
+    }
+
+    /// Latest checkpoint per shard recorded in the commit info, newest commit
+    /// first. Only the most recent commits are read unless some shard's
+    /// checkpoint is older.
+    async fn committed_sequences(&self, table: &DeltaTable, shards: &HashSet<String>) -> Result<HashMap<String, Value>> {
+        let mut found = HashMap::new();
+        if shards.is_empty() {
+            return Ok(found);
+        }
+        for limit in [Some(RECENT_COMMITS), None] {
+            let history = table.history(limit).await
+                .context("Failed to read table history for Kinesis checkpoints")?;
+            for commit in history {
+                let Some(checkpoints) = commit.info.get(CHECKPOINTS_KEY) else {
+                    continue;
+                };
+                if checkpoints.get("stream").and_then(Value::as_str) != Some(self.config.stream_name.as_str()) {
+                    continue;
+                }
+                let Some(Value::Object(committed)) = checkpoints.get("shards") else {
+                    continue;
+                };
+                for (shard_id, checkpoint) in committed {
+                    found.entry(shard_id.clone()).or_insert_with(|| checkpoint.clone());
+                }
+            }
+            if shards.iter().all(|shard| found.contains_key(shard)) {
+                break;
+            }
+        }
+        Ok(found)
+    }
+}
+
+impl Source for KinesisSource {
+    fn name(&self) -> &str {
+        &self.config.stream_name
+    }
+
+    async fn resume(&mut self, table: &DeltaTable) -> Result<()> {
+        let prefix = self.app_id_prefix();
+        let committed: HashMap<String, i64> = committed_positions(table, &prefix)
+            .into_iter()
+            .filter_map(|(app_id, version)| app_id.strip_prefix(&prefix).map(|shard| (shard.to_string(), version)))
+            .collect();
+        let checkpoints = self.committed_sequences(table, &committed.keys().cloned().collect()).await?;
+
+        self.shards.clear();
+        self.finished.clear();
+        self.status.lock().unwrap().partitions.clear();
+        for (shard_id, batches) in &committed {
+            let checkpoint = checkpoints.get(shard_id);
+            if checkpoint.and_then(|c| c.get("batch")).and_then(Value::as_i64) != Some(*batches) {
+                bail!(
+                    "Kinesis shard {} has {} committed batches but no matching checkpoint in the table history",
+                    shard_id,
+                    batches
+                );
+            }
+            if checkpoint.and_then(|c| c.get("closed")).and_then(Value::as_bool) == Some(true) {
+                self.finished.insert(shard_id.clone());
+                continue;
+            }
+            let sequence = checkpoint.and_then(|c| c.get("sequenceNumber")).and_then(Value::as_str);
+            log::info!("Kinesis {}[{}] resuming after {:?}", self.config.stream_name, shard_id, sequence);
+            self.shards.insert(
+                shard_id.clone(),
+                ShardReader { sequence: sequence.map(str::to_string), committed_batches: *batches, ..Default::default() },
+            );
+        }
+
+        let listed = self.list_shards().await?;
+        let listed_ids: HashSet<&str> = listed.iter().map(|s| s.shard_id()).collect();
+        let trimmed: Vec<String> = self.shards.keys().filter(|id| !listed_ids.contains(id.as_str())).cloned().collect();
+        for shard_id in trimmed {
+            log::warn!("Kinesis shard {} expired from {} before being read to its end", shard_id, self.config.stream_name);
+            self.shards.remove(&shard_id);
+            self.finished.insert(shard_id);
+        }
+        // Parents of committed shards were read to their end before them
+        for shard in &listed {
+            if !committed.contains_key(shard.shard_id()) {
+                continue;
+            }
+            for parent in [shard.parent_shard_id(), shard.adjacent_parent_shard_id()].into_iter().flatten() {
+                if !committed.contains_key(parent) {
+                    self.finished.insert(parent.to_string());
+                }
+            }
+        }
+        // Starting at the tip of a stream read nothing before, so closed shards hold nothing new
+        if committed.is_empty() && self.config.initial_position == "latest" {
+            for shard in &listed {
+                if shard.sequence_number_range().and_then(|r| r.ending_sequence_number()).is_some() {
+                    self.finished.insert(shard.shard_id().to_string());
+                }
+            }
+        }
+
+        self.status.lock().unwrap().assignment_changes += 1;
+        self.discover_shards().await
+    }
+
+    async fn next_batch(&mut self, max_rows: usize, max_wait: Duration) -> Result<Option<SourceBatch>> {
+        if Instant::now() >= self.next_discovery {
+            self.discover_shards().await?;
+        }
+        let deadline = Instant::now() + max_wait;
+        let mut messages: Vec<Vec<u8>> = Vec::new();
+        // Shards read in this batch, with whether they reached their end
+        let mut touched: BTreeMap<String, bool> = BTreeMap::new();
+
+        while messages.len() < max_rows && Instant::now() < deadline {
+            let readable: Vec<String> = self
+                .shards
+                .iter()
+                .filter(|(_, shard)| self.readable(shard))
+                .map(|(id, _)| id.clone())
+                .collect();
+            let mut idle = true;
+            for shard_id in readable {
+                if messages.len() >= max_rows {
+                    break;
+                }
+                let shard = &self.shards[&shard_id];
+                let iterator = match &shard.iterator {
+                    Some(iterator) => iterator.clone(),
+                    None => {
+                        let has_parents = !shard.parents.is_empty();
+                        let sequence = shard.sequence.clone();
+                        match self.shard_iterator(&shard_id, sequence.as_deref(), has_parents).await? {
+                            Some(iterator) => iterator,
+                            None => continue,
+                        }
+                    }
+                };
+
+                let limit = (max_rows - messages.len()).min(MAX_RECORDS_PER_CALL) as i32;
+                let response = match self.client.get_records().shard_iterator(&iterator).limit(limit).send().await {
+                    Ok(response) => response,
+                    Err(e) => {
+                        let service_error = e.as_service_error();
+                        if service_error.is_some_and(|e| e.is_expired_iterator_exception()) {
+                            // Positioned again from the last read record on the next pass
+                            self.shards.get_mut(&shard_id).unwrap().iterator = None;
+                            continue;
+                        }
+                        if service_error.is_some_and(|e| e.is_provisioned_throughput_exceeded_exception()) {
+                            log::debug!("Kinesis shard {} throttled", shard_id);
+                            continue;
+                        }
+                        return Err(e).with_context(|| format!("Failed to read Kinesis shard {}", shard_id));
+                    }
+                };
+
+                let records = response.records();
+                let shard = self.shards.get_mut(&shard_id).unwrap();
+                if let Some(last) = records.last() {
+                    shard.sequence = Some(last.sequence_number().to_string());
+                    idle = false;
+                }
+                messages.extend(records.iter().map(|record| record.data().as_ref().to_vec()));
+                shard.iterator = response.next_shard_iterator().map(str::to_string);
+                // No next iterator: the shard was closed by resharding and is read to its end
+                let reached_end = shard.iterator.is_none();
+                if !records.is_empty() || reached_end {
+                    *touched.entry(shard_id.clone()).or_default() |= reached_end;
+                }
+                if reached_end {
+                    shard.closed = true;
+                    log::info!("Kinesis {} shard {} read to its end", self.config.stream_name, shard_id);
+                }
+
+                let mut status = self.status.lock().unwrap();
+                status.messages_consumed += records.len() as u64;
+                let entry = status.partitions.entry(shard_id.clone()).or_default();
+                entry.consumed += records.len() as i64;
+            }
+            if idle {
+                sleep(IDLE_BACKOFF.min(deadline.saturating_duration_since(Instant::now()))).await;
+            }
+        }
+
+        if touched.is_empty() {
+            return Ok(None);
+        }
+
+        let df = if messages.is_empty() {
+            DataFrame::empty()
+        } else {
+            decode_json_messages(&messages, &self.config.stream_name, &self.status)?
+        };
+
+        let mut checkpoints = Vec::with_capacity(touched.len());
+        let mut shards = serde_json::Map::new();
+        for (shard_id, closed) in &touched {
+            let shard = &self.shards[shard_id];
+            let batch = shard.committed_batches + 1;
+            checkpoints.push(Transaction::new(self.app_id(shard_id), batch));
+            shards.insert(
+                shard_id.clone(),
+                json!({ "batch": batch, "sequenceNumber": shard.sequence, "closed": closed }),
+            );
+        }
+        let metadata = vec![(
+            CHECKPOINTS_KEY.to_string(),
+            json!({ "stream": self.config.stream_name, "shards": shards }),
+        )];
+
+        Ok(Some(SourceBatch { df, checkpoints, metadata }))
+    }
+
+    async fn committed(&mut self, checkpoints: &[Transaction]) -> Result<()> {
+        let prefix = self.app_id_prefix();
+        let mut status = self.status.lock().unwrap();
+        for txn in checkpoints {
+            let Some(shard_id) = txn.app_id.strip_prefix(&prefix) else {
+                continue;
+            };
+            let Some(shard) = self.shards.get_mut(shard_id) else {
+                continue;
+            };
+            let entry = status.partitions.entry(shard_id.to_string()).or_default();
+            entry.committed = entry.consumed;
+            shard.committed_batches = txn.version;
+            if shard.closed {
+                // Its children can be read now
+                self.shards.remove(shard_id);
+                self.finished.insert(shard_id.to_string());
+                status.assignment_changes += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn status(&self) -> Option<SourceStatusHandle> {
+        Some(self.status.clone())
+    }
+}
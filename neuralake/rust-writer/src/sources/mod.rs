@@ -0,0 +1,338 @@
+//! Ingestion sources that feed the writer directly. Each source records its
+//! read position as Delta application transactions in the same commit as the
+//! data, so a restart resumes exactly where the last commit left off.
+
+#[cfg(feature = "kafka")]
+pub mod kafka;
+#[cfg(feature = "kinesis")]
+pub mod kinesis;
+pub mod postgres_cdc;
+#[cfg(feature = "pulsar")]
+pub mod pulsar;
+#[cfg(feature = "sqs")]
+pub mod sqs;
+pub mod stdin;
+
+use anyhow::{Context, Result};
+use deltalake::kernel::Transaction;
+use deltalake::{DeltaTable, StorageOptions};
+use polars::prelude::{DataFrame, JsonFormat, JsonReader, SerReader};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::Cursor;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+use crate::config::SequenceTrackingConfig;
+use crate::retry;
+use crate::sequence::{self, GapsReport, SequenceTracker};
+use crate::writer::WriterProcess;
+
+/// Rows pulled from a source together with the positions they advance to
+pub struct SourceBatch {
+    pub df: DataFrame,
+    /// Committed atomically with `df`; `version` is the next position to read
+    pub checkpoints: Vec<Transaction>,
+    /// Commit info entries recorded with `df`, for positions that do not fit
+    /// a transaction version
+    pub metadata: Vec<(String, Value)>,
+}
+
+/// Read progress of one partition (or shard) of a source
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PartitionStatus {
+    /// Next position the source will read
+    pub consumed: i64,
+    /// Next position durably committed to the table
+    pub committed: i64,
+    /// Next position the broker will assign, when known
+    pub high_watermark: Option<i64>,
+}
+
+impl PartitionStatus {
+    /// Messages available in the broker but not yet read
+    pub fn consumer_lag(&self) -> Option<i64> {
+        self.high_watermark.map(|hw| (hw - self.consumed).max(0))
+    }
+
+    /// Messages available in the broker but not yet in the table
+    pub fn commit_lag(&self) -> Option<i64> {
+        self.high_watermark.map(|hw| (hw - self.committed).max(0))
+    }
+}
+
+/// Live counters for a running source, shared with the HTTP API
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SourceStatus {
+    pub name: String,
+    pub partitions: BTreeMap<String, PartitionStatus>,
+    /// Times the set of read partitions was (re)assigned
+    pub assignment_changes: u64,
+    /// Consumer group rebalances observed
+    pub rebalances: u64,
+    /// Messages skipped because they could not be decoded
+    pub decode_errors: u64,
+    pub messages_consumed: u64,
+    pub batches_committed: u64,
+    /// Latest gaps report, when sequence tracking is configured
+    #[serde(default)]
+    pub sequence_gaps: Option<GapsReport>,
+    /// Times reading was paused because the writer queue was saturated
+    #[serde(default)]
+    pub backpressure_pauses: u64,
+    /// Total duration of the pauses that have ended
+    #[serde(default)]
+    pub paused_ms: u64,
+    /// Whether reading is paused right now
+    #[serde(default)]
+    pub paused: bool,
+}
+
+/// Handle through which a source publishes its status
+pub type SourceStatusHandle = Arc<std::sync::Mutex<SourceStatus>>;
+
+impl fmt::Display for SourceStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Source: {}", self.name)?;
+        writeln!(
+            f,
+            "Messages consumed: {}, batches committed: {}, decode errors: {}",
+            self.messages_consumed, self.batches_committed, self.decode_errors
+        )?;
+        writeln!(
+            f,
+            "Assignment changes: {}, rebalances: {}",
+            self.assignment_changes, self.rebalances
+        )?;
+        if self.backpressure_pauses > 0 {
+            writeln!(
+                f,
+                "Backpressure pauses: {}, paused for {}ms{}",
+                self.backpressure_pauses,
+                self.paused_ms,
+                if self.paused { " (paused now)" } else { "" }
+            )?;
+        }
+        writeln!(
+            f,
+            "{:>10}  {:>12}  {:>12}  {:>14}  {:>12}  {:>10}",
+            "PARTITION", "CONSUMED", "COMMITTED", "HIGH_WATERMARK", "CONSUMER_LAG", "COMMIT_LAG"
+        )?;
+        let unknown = || "-".to_string();
+        for (partition, status) in &self.partitions {
+            writeln!(
+                f,
+                "{:>10}  {:>12}  {:>12}  {:>14}  {:>12}  {:>10}",
+                partition,
+                status.consumed,
+                status.committed,
+                status.high_watermark.map_or_else(unknown, |v| v.to_string()),
+                status.consumer_lag().map_or_else(unknown, |v| v.to_string()),
+                status.commit_lag().map_or_else(unknown, |v| v.to_string()),
+            )?;
+        }
+        if let Some(gaps) = &self.sequence_gaps {
+            write!(f, "{}", gaps)?;
+        }
+        Ok(())
+    }
+}
+
+/// Decode newline-delimited JSON messages. If the batch as a whole does not
+/// parse, messages are decoded one by one and the malformed ones skipped.
+pub(crate) fn decode_json_messages(messages: &[Vec<u8>], source: &str, status: &SourceStatusHandle) -> Result<DataFrame> {
+    let parse = |bytes: Vec<u8>| {
+        JsonReader::new(Cursor::new(bytes))
+            .with_json_format(JsonFormat::JsonLines)
+            .finish()
+    };
+
+    if let Ok(df) = parse(messages.join(&b'\n')) {
+        return Ok(df);
+    }
+
+    let mut frames = Vec::with_capacity(messages.len());
+    let mut errors = 0u64;
+    for message in messages {
+        match parse(message.clone()) {
+            Ok(df) => frames.push(df),
+            Err(e) => {
+                errors += 1;
+                log::warn!("Skipping undecodable message from {}: {}", source, e);
+            }
+        }
+    }
+    status.lock().unwrap().decode_errors += errors;
+
+    if frames.is_empty() {
+        return Ok(DataFrame::empty());
+    }
+    let mut df = frames.remove(0);
+    for frame in &frames {
+        df.vstack_mut(frame)
+            .with_context(|| format!("Messages from {} in one batch have incompatible schemas", source))?;
+    }
+    Ok(df)
+}
+
+/// A pull-based source of rows with resumable positions
+#[allow(async_fn_in_trait)]
+pub trait Source {
+    /// Name used in logs
+    fn name(&self) -> &str;
+
+    /// Restore read positions from checkpoints previously committed to the table
+    async fn resume(&mut self, table: &DeltaTable) -> Result<()>;
+
+    /// Pull up to `max_rows` rows, waiting at most `max_wait` for them
+    async fn next_batch(&mut self, max_rows: usize, max_wait: Duration) -> Result<Option<SourceBatch>>;
+
+    /// Notify the source that a batch has been durably committed
+    async fn committed(&mut self, checkpoints: &[Transaction]) -> Result<()>;
+
+    /// Live status, for sources that track it
+    fn status(&self) -> Option<SourceStatusHandle> {
+        None
+    }
+
+    /// Whether the source has reached the end of a finite input
+    fn is_exhausted(&self) -> bool {
+        false
+    }
+}
+
+/// Committed application transactions whose id starts with `prefix`
+pub fn committed_positions(table: &DeltaTable, prefix: &str) -> HashMap<String, i64> {
+    table
+        .get_app_transaction_version()
+        .into_iter()
+        .filter(|(app_id, _)| app_id.starts_with(prefix))
+        .map(|(app_id, txn)| (app_id, txn.version))
+        .collect()
+}
+
+/// Drive a source into the table until shutdown, or until a finite source is
+/// exhausted. A batch that cannot be written on any attempt (a transform,
+/// validation or schema failure) is moved to the dead letter queue and its
+/// positions committed without it. A write that still fails after its
+/// retries stops the source without acknowledging positions, so a restart
+/// replays the batch. With `sequence_tracking`, the
+/// sequence numbers of committed rows are tracked and a gaps report
+/// published every interval and on exit.
+pub async fn run_source<S: Source>(
+    mut source: S,
+    writer: &WriterProcess,
+    table: Arc<Mutex<DeltaTable>>,
+    storage_options: StorageOptions,
+    shutdown: CancellationToken,
+    sequence_tracking: Option<SequenceTrackingConfig>,
+) -> Result<()> {
+    let table_uri = {
+        let mut locked = table.lock().await;
+        locked.update().await
+            .context("Failed to refresh table before resuming source")?;
+        source.resume(&locked).await?;
+        locked.table_uri()
+    };
+
+    log::info!("Starting source {} for {}", source.name(), table_uri);
+
+    let max_rows = writer.config().max_batch_size;
+    let max_wait = writer.config().max_batch_time();
+
+    let mut tracker = sequence_tracking.map(SequenceTracker::new);
+    let report_interval = tracker.as_ref().map_or(Duration::from_secs(60), |t| t.config().report_interval())
+        .max(Duration::from_secs(1));
+    let mut report_timer = tokio::time::interval(report_interval);
+    report_timer.tick().await;
+
+    let control = writer.control();
+    loop {
+        let paused = control.is_paused();
+        tokio::select! {
+            batch = source.next_batch(max_rows, max_wait), if !paused => {
+                let Some(batch) = batch? else {
+                    if source.is_exhausted() {
+                        log::info!("Source {} exhausted", source.name());
+                        break;
+                    }
+                    continue;
+                };
+
+                let sequences = match &tracker {
+                    Some(tracker) => tracker.sequences(&batch.df).unwrap_or_else(|e| {
+                        log::warn!("Source {} batch not tracked for sequence gaps: {:#}", source.name(), e);
+                        Vec::new()
+                    }),
+                    None => Vec::new(),
+                };
+
+                let rows = batch.df.clone();
+                let written = writer
+                    .write_batch_with_checkpoint(batch.df, &batch.checkpoints, batch.metadata.clone(), &storage_options, &table_uri)
+                    .await;
+                if let Err(e) = written {
+                    // Replaying it would fail the same way, so set it aside to keep the source moving
+                    if retry::is_retryable(&e) || !writer.dead_letter(&table_uri, &rows, &[], &e).await {
+                        return Err(e).with_context(|| format!("Source {} failed to commit batch", source.name()));
+                    }
+                    log::error!("Source {} skipped a batch of {} rows that cannot be written: {:#}", source.name(), rows.height(), e);
+                    writer
+                        .write_batch_with_checkpoint(DataFrame::empty(), &batch.checkpoints, batch.metadata, &storage_options, &table_uri)
+                        .await
+                        .with_context(|| format!("Source {} failed to commit positions past a dead-lettered batch", source.name()))?;
+                }
+
+                source.committed(&batch.checkpoints).await?;
+                if let Some(status) = source.status() {
+                    status.lock().unwrap().batches_committed += 1;
+                }
+                if let Some(tracker) = &mut tracker {
+                    tracker.observe(&sequences);
+                }
+            }
+            _ = report_timer.tick(), if tracker.is_some() => {
+                if let Some(tracker) = &tracker {
+                    publish_gaps(tracker, &source, writer, &storage_options).await;
+                }
+            }
+            _ = control.resumed(), if paused => {}
+            _ = shutdown.cancelled() => {
+                log::info!("Source {} received shutdown signal", source.name());
+                break;
+            }
+        }
+    }
+
+    if let Some(tracker) = &tracker {
+        publish_gaps(tracker, &source, writer, &storage_options).await;
+    }
+    Ok(())
+}
+
+/// Send the tracker's gaps report to the writer metrics, the source status
+/// and the gaps table. A failed table append is logged and retried with the
+/// next report, which carries the same cumulative counts.
+async fn publish_gaps<S: Source>(
+    tracker: &SequenceTracker,
+    source: &S,
+    writer: &WriterProcess,
+    storage_options: &StorageOptions,
+) {
+    let report = tracker.report();
+    if report.missing() > 0 {
+        log::warn!("Source {}: {}", source.name(), report);
+    }
+    writer.metrics().record_gaps_report(&report);
+    if let Some(gaps_table_uri) = &tracker.config().gaps_table_uri {
+        if let Err(e) = sequence::append_report(&report, gaps_table_uri, storage_options).await {
+            log::error!("Failed to record gaps report of source {}: {:#}", source.name(), e);
+        }
+    }
+    if let Some(status) = source.status() {
+        status.lock().unwrap().sequence_gaps = Some(report);
+    }
+}
@@ -0,0 +1,382 @@
+use anyhow::{bail, Context, Result};
+use deltalake::kernel::Transaction;
+use deltalake::DeltaTable;
+use polars::prelude::DataFrame;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::{sleep, Instant};
+use tokio_postgres::{Client, NoTls};
+use crate::config::PostgresCdcConfig;
+use crate::sources::{committed_positions, decode_json_messages, Source, SourceBatch, SourceStatusHandle};
+
+/// Commit info entry holding the LSN the commit reads up to, in Postgres notation
+const LSN_KEY: &str = "postgresLsn";
+/// Column naming the kind of change of each row
+pub const CHANGE_TYPE_COLUMN: &str = "_change_type";
+/// Column holding the end LSN of the transaction that made the change
+pub const COMMIT_LSN_COLUMN: &str = "_commit_lsn";
+
+/// A column of a replicated relation
+#[derive(Debug, Clone)]
+struct RelationColumn {
+    name: String,
+    type_oid: u32,
+}
+
+/// Layout of a table, sent before its first change in each decoding session
+#[derive(Debug, Clone)]
+struct Relation {
+    /// `schema.table`
+    name: String,
+    columns: Vec<RelationColumn>,
+}
+
+/// One column value of a tuple
+#[derive(Debug, Clone, PartialEq)]
+enum TupleValue {
+    Null,
+    /// A TOASTed value the update did not change, which is not sent
+    Unchanged,
+    Text(String),
+}
+
+/// The `pgoutput` messages the source acts on
+#[derive(Debug)]
+enum Message {
+    Begin,
+    Commit { end_lsn: u64 },
+    Relation { id: u32, relation: Relation },
+    Change { relation_id: u32, change_type: &'static str, tuple: Vec<TupleValue> },
+    /// Truncate, type, origin and logical messages
+    Other,
+}
+
+/// Reads the big-endian fields of a `pgoutput` message
+struct MessageReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> MessageReader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.bytes.len() < n {
+            bail!("Truncated pgoutput message");
+        }
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn i16(&mut self) -> Result<i16> {
+        Ok(i16::from_be_bytes(self.take(2)?.try_into()?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into()?))
+    }
+
+    /// Null-terminated string
+    fn string(&mut self) -> Result<String> {
+        let end = self.bytes.iter().position(|b| *b == 0).context("Unterminated string in pgoutput message")?;
+        let value = String::from_utf8_lossy(&self.bytes[..end]).into_owned();
+        self.bytes = &self.bytes[end + 1..];
+        Ok(value)
+    }
+
+    fn tuple(&mut self) -> Result<Vec<TupleValue>> {
+        let columns = self.i16()?;
+        (0..columns)
+            .map(|_| match self.u8()? {
+                b'n' => Ok(TupleValue::Null),
+                b'u' => Ok(TupleValue::Unchanged),
+                b't' => {
+                    let len = self.u32()? as usize;
+                    Ok(TupleValue::Text(String::from_utf8_lossy(self.take(len)?).into_owned()))
+                }
+                kind => bail!("Unknown tuple value kind {:?}", kind as char),
+            })
+            .collect()
+    }
+}
+
+/// Decode one message of `pgoutput` protocol version 1
+fn parse_message(bytes: &[u8]) -> Result<Message> {
+    let mut reader = MessageReader { bytes };
+    Ok(match reader.u8()? {
+        b'B' => Message::Begin,
+        b'C' => {
+            let _flags = reader.u8()?;
+            let _commit_lsn = reader.u64()?;
+            Message::Commit { end_lsn: reader.u64()? }
+        }
+        b'R' => {
+            let id = reader.u32()?;
+            let namespace = reader.string()?;
+            let table = reader.string()?;
+            let _replica_identity = reader.u8()?;
+            let count = reader.i16()?;
+            let columns = (0..count)
+                .map(|_| {
+                    let _flags = reader.u8()?;
+                    let name = reader.string()?;
+                    let type_oid = reader.u32()?;
+                    let _type_modifier = reader.u32()?;
+                    Ok(RelationColumn { name, type_oid })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Message::Relation { id, relation: Relation { name: format!("{}.{}", namespace, table), columns } }
+        }
+        b'I' => {
+            let relation_id = reader.u32()?;
+            let _new = reader.u8()?;
+            Message::Change { relation_id, change_type: "insert", tuple: reader.tuple()? }
+        }
+        b'U' => {
+            let relation_id = reader.u32()?;
+            // The old key or row comes first when the key changed or the
+            // table has REPLICA IDENTITY FULL
+            let mut kind = reader.u8()?;
+            if kind == b'K' || kind == b'O' {
+                reader.tuple()?;
+                kind = reader.u8()?;
+            }
+            if kind != b'N' {
+                bail!("Update without a new tuple");
+            }
+            Message::Change { relation_id, change_type: "update", tuple: reader.tuple()? }
+        }
+        b'D' => {
+            let relation_id = reader.u32()?;
+            let _old = reader.u8()?;
+            Message::Change { relation_id, change_type: "delete", tuple: reader.tuple()? }
+        }
+        _ => Message::Other,
+    })
+}
+
+/// JSON value of a column's text representation, typed by its type OID so
+/// numbers and booleans are not inferred as strings
+fn json_value(text: &str, type_oid: u32) -> Value {
+    match type_oid {
+        // bool
+        16 => Value::Bool(text == "t"),
+        // int8, int2, int4, oid
+        20 | 21 | 23 | 26 => text.parse::<i64>().map_or_else(|_| Value::from(text), Value::from),
+        // float4, float8; NaN and infinities have no JSON form
+        700 | 701 => text.parse::<f64>().ok().and_then(serde_json::Number::from_f64).map_or(Value::Null, Value::Number),
+        _ => Value::from(text),
+    }
+}
+
+/// Postgres notation of an LSN, e.g. `16/B374D848`
+pub fn format_lsn(lsn: u64) -> String {
+    format!("{:X}/{:X}", lsn >> 32, lsn & 0xFFFF_FFFF)
+}
+
+/// Change data capture of one PostgreSQL table through a `pgoutput`
+/// logical replication slot. Changes are peeked rather than consumed, and
+/// whole transactions go into a batch. The end LSN of the last one is the
+/// version of the slot's application transaction, committed with the rows;
+/// the slot is advanced only after that commit, and transactions at or
+/// before the committed LSN are skipped if peeked again after a restart.
+pub struct PostgresCdcSource {
+    config: PostgresCdcConfig,
+    client: Client,
+    /// End LSN of the last transaction committed to the table
+    committed_lsn: u64,
+    status: SourceStatusHandle,
+}
+
+impl PostgresCdcSource {
+    /// Connect, spawn the connection driver and create the slot if missing
+    pub async fn connect(config: PostgresCdcConfig, status: SourceStatusHandle) -> Result<Self> {
+        let (client, connection) = tokio_postgres::connect(&config.connection_string, NoTls)
+            .await
+            .context("Failed to connect to PostgreSQL CDC source")?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                log::error!("PostgreSQL CDC connection closed: {}", e);
+            }
+        });
+
+        let existing = client
+            .query_opt("SELECT plugin FROM pg_replication_slots WHERE slot_name = $1", &[&config.slot])
+            .await
+            .context("Failed to look up replication slot")?;
+        match existing {
+            Some(row) => {
+                let plugin: Option<String> = row.get(0);
+                if plugin.as_deref() != Some("pgoutput") {
+                    bail!("Replication slot {} uses {:?}, not pgoutput", config.slot, plugin);
+                }
+            }
+            None => {
+                log::info!("Creating replication slot {}", config.slot);
+                client
+                    .execute("SELECT pg_create_logical_replication_slot($1, 'pgoutput')", &[&config.slot])
+                    .await
+                    .with_context(|| format!("Failed to create replication slot {}", config.slot))?;
+            }
+        }
+
+        status.lock().unwrap().name = format!("{} ({})", config.table, config.slot);
+        Ok(Self { config, client, committed_lsn: 0, status })
+    }
+
+    fn app_id(&self) -> String {
+        format!("postgres-cdc:{}", self.config.slot)
+    }
+
+    /// Confirm everything up to `lsn` to the slot, so Postgres can recycle its WAL
+    async fn advance_slot(&self, lsn: u64) -> Result<()> {
+        self.client
+            .execute("SELECT pg_replication_slot_advance($1, $2::text::pg_lsn)", &[&self.config.slot, &format_lsn(lsn)])
+            .await
+            .with_context(|| format!("Failed to advance replication slot {}", self.config.slot))?;
+        Ok(())
+    }
+
+    /// Complete transactions after the slot's confirmed position, at least
+    /// `max_changes` changes' worth if available
+    async fn peek(&self, max_changes: usize) -> Result<Vec<Message>> {
+        let rows = self
+            .client
+            .query(
+                "SELECT data FROM pg_logical_slot_peek_binary_changes($1, NULL, $2, \
+                 'proto_version', '1', 'publication_names', $3)",
+                &[&self.config.slot, &(max_changes.min(i32::MAX as usize) as i32), &self.config.publication],
+            )
+            .await
+            .with_context(|| format!("Failed to read replication slot {}", self.config.slot))?;
+        rows.iter().map(|row| parse_message(row.get::<_, &[u8]>(0))).collect()
+    }
+}
+
+impl Source for PostgresCdcSource {
+    fn name(&self) -> &str {
+        &self.config.table
+    }
+
+    async fn resume(&mut self, table: &DeltaTable) -> Result<()> {
+        let app_id = self.app_id();
+        self.committed_lsn = committed_positions(table, &app_id).get(&app_id).map_or(0, |lsn| *lsn as u64);
+        if self.committed_lsn > 0 {
+            log::info!("PostgreSQL CDC {} resuming after {}", self.config.slot, format_lsn(self.committed_lsn));
+            // The slot may trail the table if the last run stopped between commit and confirmation
+            self.advance_slot(self.committed_lsn).await?;
+        }
+        let mut status = self.status.lock().unwrap();
+        let entry = status.partitions.entry(self.config.table.clone()).or_default();
+        entry.consumed = self.committed_lsn as i64;
+        entry.committed = self.committed_lsn as i64;
+        status.assignment_changes += 1;
+        Ok(())
+    }
+
+    async fn next_batch(&mut self, max_rows: usize, max_wait: Duration) -> Result<Option<SourceBatch>> {
+        let deadline = Instant::now() + max_wait;
+        loop {
+            let messages = self.peek(max_rows).await?;
+
+            let mut relations: HashMap<u32, Relation> = HashMap::new();
+            let mut rows: Vec<Vec<u8>> = Vec::new();
+            let mut transaction: Vec<Map<String, Value>> = Vec::new();
+            let mut end_lsn = None;
+            for message in messages {
+                match message {
+                    Message::Begin => transaction.clear(),
+                    Message::Relation { id, relation } => {
+                        relations.insert(id, relation);
+                    }
+                    Message::Change { relation_id, change_type, tuple } => {
+                        let relation = relations
+                            .get(&relation_id)
+                            .with_context(|| format!("Change to relation {} before its description", relation_id))?;
+                        if relation.name != self.config.table {
+                            continue;
+                        }
+                        let mut row = Map::new();
+                        for (column, value) in relation.columns.iter().zip(tuple) {
+                            match value {
+                                TupleValue::Text(text) => {
+                                    row.insert(column.name.clone(), json_value(&text, column.type_oid));
+                                }
+                                TupleValue::Null => {
+                                    row.insert(column.name.clone(), Value::Null);
+                                }
+                                TupleValue::Unchanged => {}
+                            }
+                        }
+                        row.insert(CHANGE_TYPE_COLUMN.to_string(), Value::from(change_type));
+                        transaction.push(row);
+                    }
+                    Message::Commit { end_lsn: lsn } => {
+                        if lsn > self.committed_lsn {
+                            // The commit LSN is known only now, so it is added to every row here
+                            for mut row in transaction.drain(..) {
+                                row.insert(COMMIT_LSN_COLUMN.to_string(), Value::from(lsn as i64));
+                                rows.push(serde_json::to_vec(&Value::Object(row))?);
+                            }
+                            end_lsn = Some(lsn);
+                        }
+                        transaction.clear();
+                        if rows.len() >= max_rows {
+                            break;
+                        }
+                    }
+                    Message::Other => {}
+                }
+            }
+
+            let Some(end_lsn) = end_lsn else {
+                // Nothing after the committed LSN yet
+                if Instant::now() >= deadline {
+                    return Ok(None);
+                }
+                sleep(self.config.poll_interval().min(deadline.saturating_duration_since(Instant::now()))).await;
+                continue;
+            };
+
+            if rows.is_empty() {
+                // Only other tables changed; nothing to write, so confirm them right away
+                self.advance_slot(end_lsn).await?;
+                self.committed_lsn = end_lsn;
+                continue;
+            }
+
+            {
+                let mut status = self.status.lock().unwrap();
+                status.messages_consumed += rows.len() as u64;
+                status.partitions.entry(self.config.table.clone()).or_default().consumed = end_lsn as i64;
+            }
+            let df: DataFrame = decode_json_messages(&rows, &self.config.table, &self.status)?;
+            return Ok(Some(SourceBatch {
+                df,
+                checkpoints: vec![Transaction::new(self.app_id(), end_lsn as i64)],
+                metadata: vec![(LSN_KEY.to_string(), json!(format_lsn(end_lsn)))],
+            }));
+        }
+    }
+
+    async fn committed(&mut self, checkpoints: &[Transaction]) -> Result<()> {
+        let app_id = self.app_id();
+        let Some(txn) = checkpoints.iter().find(|txn| txn.app_id == app_id) else {
+            return Ok(());
+        };
+        self.committed_lsn = txn.version as u64;
+        self.status.lock().unwrap().partitions.entry(self.config.table.clone()).or_default().committed = txn.version;
+        self.advance_slot(self.committed_lsn).await
+    }
+
+    fn status(&self) -> Option<SourceStatusHandle> {
+        Some(self.status.clone())
+    }
+}
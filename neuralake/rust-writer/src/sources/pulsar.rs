@@ -0,0 +1,346 @@
+use anyhow::{bail, Context, Result};
+use deltalake::kernel::Transaction;
+use deltalake::DeltaTable;
+use futures::StreamExt;
+use polars::prelude::*;
+use pulsar::consumer::InitialPosition;
+use pulsar::proto::MessageIdData;
+use pulsar::{Authentication, Consumer, ConsumerOptions, Pulsar, SubType, TokioExecutor};
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Cursor;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::{timeout_at, Instant};
+use crate::config::{PulsarSourceConfig, PulsarSubscriptionType};
+use crate::schema::avro_record_to_polars;
+use crate::sources::{committed_positions, Source, SourceBatch, SourceStatusHandle};
+use crate::writer::concat_frames;
+
+/// How often a `topic_pattern` subscription looks for new matching topics
+const TOPIC_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Pulsar source that acknowledges messages only once their rows are
+/// committed. With a failover subscription each topic partition's position
+/// is also committed as an application transaction alongside the rows, and
+/// redelivered messages at or before it are dropped.
+pub struct PulsarSource {
+    config: PulsarSourceConfig,
+    consumer: Consumer<Vec<u8>, TokioExecutor>,
+    http: reqwest::Client,
+    /// Registered schema per topic, `None` when types are inferred
+    schemas: HashMap<String, Option<SchemaRef>>,
+    /// Next position per topic partition already in the table
+    committed: HashMap<String, i64>,
+    /// Messages of the batch in flight, acknowledged once it is committed
+    pending_acks: Vec<(String, MessageIdData)>,
+    status: SourceStatusHandle,
+}
+
+/// Schema registry entry as returned by the admin API
+#[derive(Debug, Deserialize)]
+struct RegisteredSchema {
+    #[serde(rename = "type")]
+    schema_type: String,
+    data: String,
+}
+
+impl PulsarSource {
+    /// Connect and subscribe to the configured topics
+    pub async fn new(config: PulsarSourceConfig, status: SourceStatusHandle) -> Result<Self> {
+        let mut client = Pulsar::builder(&config.service_url, TokioExecutor);
+        if let Some(token) = &config.auth_token {
+            client = client.with_auth(Authentication {
+                name: "token".to_string(),
+                data: token.clone().into_bytes(),
+            });
+        }
+        let client: Pulsar<TokioExecutor> = client
+            .build()
+            .await
+            .with_context(|| format!("Failed to connect to Pulsar at {}", config.service_url))?;
+
+        let initial_position = match config.initial_position.as_str() {
+            "earliest" => InitialPosition::Earliest,
+            "latest" => InitialPosition::Latest,
+            other => bail!("Pulsar initial_position must be earliest or latest, got '{}'", other),
+        };
+        let mut builder = client
+            .consumer()
+            .with_subscription(&config.subscription)
+            .with_subscription_type(match config.subscription_type {
+                PulsarSubscriptionType::Failover => SubType::Failover,
+                PulsarSubscriptionType::Shared => SubType::Shared,
+            })
+            .with_options(ConsumerOptions::default().with_initial_position(initial_position));
+        builder = match (&config.topic_pattern, config.topics.is_empty()) {
+            (Some(pattern), true) => {
+                let regex = Regex::new(pattern).with_context(|| format!("Invalid topic_pattern '{}'", pattern))?;
+                let builder = builder.with_topic_regex(regex).with_topic_refresh(TOPIC_REFRESH_INTERVAL);
+                match &config.namespace {
+                    Some(namespace) => builder.with_lookup_namespace(namespace),
+                    None => builder,
+                }
+            }
+            (None, false) => builder.with_topics(&config.topics),
+            _ => bail!("Configure exactly one of `topics` and `topic_pattern` for the Pulsar source"),
+        };
+        let consumer = builder
+            .build()
+            .await
+            .with_context(|| format!("Failed to subscribe {} to Pulsar", config.subscription))?;
+
+        status.lock().unwrap().name = config.subscription.clone();
+        Ok(Self {
+            config,
+            consumer,
+            http: reqwest::Client::new(),
+            schemas: HashMap::new(),
+            committed: HashMap::new(),
+            pending_acks: Vec::new(),
+            status,
+        })
+    }
+
+    /// Application transaction id prefix shared by all topics of this subscription
+    fn app_id_prefix(&self) -> String {
+        format!("pulsar:{}:", self.config.subscription)
+    }
+
+    fn app_id(&self, topic: &str) -> String {
+        format!("{}{}", self.app_id_prefix(), topic)
+    }
+
+    fn exactly_once(&self) -> bool {
+        self.config.subscription_type == PulsarSubscriptionType::Failover
+    }
+
+    /// Schema registered for `topic`, looked up once per topic
+    async fn schema(&mut self, topic: &str) -> Result<Option<SchemaRef>> {
+        if let Some(schema) = self.schemas.get(topic) {
+            return Ok(schema.clone());
+        }
+        let schema = match &self.config.admin_url {
+            Some(admin_url) => self.fetch_schema(admin_url, topic).await?,
+            None => None,
+        };
+        self.schemas.insert(topic.to_string(), schema.clone());
+        Ok(schema)
+    }
+
+    async fn fetch_schema(&self, admin_url: &str, topic: &str) -> Result<Option<SchemaRef>> {
+        // Partitions share the schema of their partitioned topic
+        let name = topic.split_once("://").map_or(topic, |(_, name)| name);
+        let name = match name.rsplit_once("-partition-") {
+            Some((base, index)) if index.parse::<u32>().is_ok() => base,
+            _ => name,
+        };
+        let mut request = self
+            .http
+            .get(format!("{}/admin/v2/schemas/{}/schema", admin_url.trim_end_matches('/'), name));
+        if let Some(token) = &self.config.auth_token {
+            request = request.bearer_auth(token);
+        }
+        let response = request.send().await
+            .with_context(|| format!("Failed to look up the schema of {}", topic))?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let registered: RegisteredSchema = response
+            .error_for_status()
+            .with_context(|| format!("Failed to look up the schema of {}", topic))?
+            .json()
+            .await?;
+
+        match registered.schema_type.as_str() {
+            "JSON" => {
+                let record = serde_json::from_str(&registered.data)
+                    .with_context(|| format!("Invalid schema definition for {}", topic))?;
+                let schema = avro_record_to_polars(&record)
+                    .with_context(|| format!("Unsupported schema for {}", topic))?;
+                log::info!("Decoding {} with its registered schema: {:?}", topic, schema);
+                Ok(Some(Arc::new(schema)))
+            }
+            "NONE" | "STRING" | "BYTES" => Ok(None),
+            other => bail!("{} has a {} schema; only JSON-encoded messages are supported", topic, other),
+        }
+    }
+
+    /// Decode newline-delimited JSON messages of one topic. If the batch as a
+    /// whole does not parse, messages are decoded one by one and the
+    /// malformed ones skipped.
+    fn decode(&self, topic: &str, messages: &[Vec<u8>], schema: Option<SchemaRef>) -> Result<DataFrame> {
+        let parse = |bytes: Vec<u8>| {
+            let reader = JsonReader::new(Cursor::new(bytes)).with_json_format(JsonFormat::JsonLines);
+            match &schema {
+                Some(schema) => reader.with_schema(schema.clone()).finish(),
+                None => reader.finish(),
+            }
+        };
+
+        if let Ok(df) = parse(messages.join(&b'\n')) {
+            return Ok(df);
+        }
+
+        let mut frames = Vec::with_capacity(messages.len());
+        let mut errors = 0u64;
+        for message in messages {
+            match parse(message.clone()) {
+                Ok(df) => frames.push(df),
+                Err(e) => {
+                    errors += 1;
+                    log::warn!("Skipping undecodable message from {}: {}", topic, e);
+                }
+            }
+        }
+        self.status.lock().unwrap().decode_errors += errors;
+
+        concat_frames(frames.into_iter())
+            .with_context(|| format!("Pulsar messages from {} in one batch have incompatible schemas", topic))
+    }
+}
+
+impl Source for PulsarSource {
+    fn name(&self) -> &str {
+        &self.config.subscription
+    }
+
+    async fn resume(&mut self, table: &DeltaTable) -> Result<()> {
+        // The subscription cursor decides where reading resumes; the table
+        // only tells which redelivered messages are already committed
+        let prefix = self.app_id_prefix();
+        self.committed = committed_positions(table, &prefix)
+            .into_iter()
+            .filter_map(|(app_id, next)| Some((app_id.strip_prefix(&prefix)?.to_string(), next)))
+            .collect();
+
+        let mut status = self.status.lock().unwrap();
+        status.partitions.clear();
+        for (topic, next) in &self.committed {
+            log::info!("Pulsar {} committed up to {:?}", topic, unpack_position(*next));
+            let entry = status.partitions.entry(topic.clone()).or_default();
+            entry.consumed = *next;
+            entry.committed = *next;
+        }
+        status.assignment_changes += 1;
+        Ok(())
+    }
+
+    async fn next_batch(&mut self, max_rows: usize, max_wait: Duration) -> Result<Option<SourceBatch>> {
+        let deadline = Instant::now() + max_wait;
+        let mut messages: BTreeMap<String, Vec<Vec<u8>>> = BTreeMap::new();
+        let mut advanced: BTreeMap<String, i64> = BTreeMap::new();
+        let mut received = 0usize;
+        // Positions are per entry, so a batch never ends inside a
+        // producer-batched entry: the rest of it is already buffered
+        let mut mid_entry = false;
+
+        while received < max_rows || mid_entry {
+            let next = if mid_entry {
+                self.consumer.next().await
+            } else {
+                match timeout_at(deadline, self.consumer.next()).await {
+                    Err(_) => break, // batch window elapsed
+                    Ok(next) => next,
+                }
+            };
+            let message = next
+                .context("Pulsar consumer stream ended")?
+                .context("Failed to receive Pulsar message")?;
+
+            let id = message.message_id.id.clone();
+            mid_entry = match (id.batch_index, message.message_id.batch_size) {
+                (Some(index), Some(size)) => index + 1 < size,
+                _ => false,
+            };
+            let next_position = pack_position(id.ledger_id, id.entry_id + 1)?;
+
+            if self.exactly_once() && self.committed.get(&message.topic).is_some_and(|c| next_position <= *c) {
+                log::debug!("Skipping already committed Pulsar message {:?} from {}", id, message.topic);
+                self.consumer.ack(&message).await
+                    .context("Failed to acknowledge Pulsar message")?;
+                continue;
+            }
+
+            received += 1;
+            advanced.insert(message.topic.clone(), next_position);
+            self.pending_acks.push((message.topic.clone(), id));
+            messages.entry(message.topic).or_default().push(message.payload.data);
+        }
+
+        if advanced.is_empty() {
+            return Ok(None);
+        }
+
+        {
+            let mut status = self.status.lock().unwrap();
+            status.messages_consumed += received as u64;
+            for (topic, next) in &advanced {
+                status.partitions.entry(topic.clone()).or_default().consumed = *next;
+            }
+        }
+
+        let mut frames = Vec::with_capacity(messages.len());
+        for (topic, payloads) in &messages {
+            let schema = self.schema(topic).await?;
+            frames.push(self.decode(topic, payloads, schema)?);
+        }
+        let df = concat_frames(frames.into_iter())
+            .context("Pulsar topics in one batch have incompatible schemas")?;
+
+        let checkpoints = if self.exactly_once() {
+            advanced
+                .into_iter()
+                .map(|(topic, next)| Transaction::new(self.app_id(&topic), next))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        Ok(Some(SourceBatch { df, checkpoints, metadata: Vec::new() }))
+    }
+
+    async fn committed(&mut self, checkpoints: &[Transaction]) -> Result<()> {
+        {
+            let mut status = self.status.lock().unwrap();
+            for txn in checkpoints {
+                if let Some(topic) = txn.app_id.strip_prefix(&self.app_id_prefix()) {
+                    status.partitions.entry(topic.to_string()).or_default().committed = txn.version;
+                    self.committed.insert(topic.to_string(), txn.version);
+                }
+            }
+        }
+
+        // A lost acknowledgement only means redelivery, which a failover
+        // subscription drops and a shared one writes again
+        for (topic, id) in std::mem::take(&mut self.pending_acks) {
+            if let Err(e) = self.consumer.ack_with_id(&topic, id).await {
+                log::warn!("Failed to acknowledge Pulsar message on {}: {}", topic, e);
+            }
+        }
+        Ok(())
+    }
+
+    fn status(&self) -> Option<SourceStatusHandle> {
+        Some(self.status.clone())
+    }
+}
+
+/// Bits of a packed position holding the entry id
+const ENTRY_BITS: u32 = 31;
+
+/// Pack a ledger and entry id into one increasing transaction version.
+/// Ledger ids are allocated sequentially and entries per ledger are capped
+/// by ledger rollover, so both fit with room to spare.
+fn pack_position(ledger_id: u64, entry_id: u64) -> Result<i64> {
+    if ledger_id >= 1 << 32 || entry_id >= 1 << ENTRY_BITS {
+        bail!("Pulsar position {}:{} is too large to checkpoint", ledger_id, entry_id);
+    }
+    Ok(((ledger_id << ENTRY_BITS) | entry_id) as i64)
+}
+
+fn unpack_position(position: i64) -> (u64, u64) {
+    let position = position as u64;
+    (position >> ENTRY_BITS, position & ((1 << ENTRY_BITS) - 1))
+}
@@ -0,0 +1,430 @@
+use anyhow::{Context, Result};
+use aws_config::{BehaviorVersion, Region};
+use aws_sdk_sqs::types::DeleteMessageBatchRequestEntry;
+use aws_sdk_sqs::Client;
+use deltalake::kernel::Transaction;
+use deltalake::logstore::ObjectStoreRef;
+use deltalake::{DeltaTable, DeltaTableBuilder, ObjectStore, ObjectStoreError, Path, StorageOptions};
+use polars::prelude::*;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::Cursor;
+use std::time::Duration;
+use tokio::time::Instant;
+use crate::config::{ObjectFormat, SqsSourceConfig};
+use crate::import::glob_match;
+use crate::sources::{committed_positions, Source, SourceBatch, SourceStatusHandle};
+use crate::writer::concat_frames;
+
+/// Commit info entry listing the objects appended by the commit
+const OBJECTS_KEY: &str = "sqsObjects";
+/// Most messages a single receive or delete call handles
+const MAX_MESSAGES_PER_CALL: usize = 10;
+/// Longest long-poll wait SQS allows, in seconds
+const MAX_WAIT_TIME_SECS: u64 = 20;
+/// Commits searched on resume for objects already appended
+const RECENT_COMMITS: usize = 100;
+/// Appended objects remembered to recognise redelivered notifications
+const REMEMBERED_OBJECTS: usize = 100_000;
+
+/// S3 event notification as delivered to the queue
+#[derive(Debug, Deserialize)]
+struct S3Event {
+    /// Absent from the test event S3 sends when notifications are set up
+    #[serde(rename = "Records", default)]
+    records: Vec<S3EventRecord>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct S3EventRecord {
+    event_name: String,
+    s3: S3Entity,
+}
+
+#[derive(Debug, Deserialize)]
+struct S3Entity {
+    bucket: S3Bucket,
+    object: S3Object,
+}
+
+#[derive(Debug, Deserialize)]
+struct S3Bucket {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct S3Object {
+    key: String,
+    #[serde(rename = "eTag")]
+    e_tag: Option<String>,
+    sequencer: Option<String>,
+}
+
+/// An object announced by an `ObjectCreated` notification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreatedObject {
+    pub bucket: String,
+    /// Key as stored, with the notification's URL encoding undone
+    pub key: String,
+    /// `s3://bucket/key@sequencer`, the same for every delivery of one
+    /// notification and different for each write of the key
+    pub id: String,
+}
+
+/// Objects created according to a notification body, unwrapping an SNS
+/// envelope. Test events and other event types yield nothing.
+pub fn created_objects(body: &str) -> Result<Vec<CreatedObject>> {
+    let mut value: Value = serde_json::from_str(body).context("Notification is not JSON")?;
+    if value.get("Type").and_then(Value::as_str) == Some("Notification") {
+        let message = value
+            .get("Message")
+            .and_then(Value::as_str)
+            .context("SNS notification without a message")?;
+        value = serde_json::from_str(message).context("SNS message is not JSON")?;
+    }
+    let event: S3Event = serde_json::from_value(value).context("Not an S3 event notification")?;
+
+    Ok(event
+        .records
+        .into_iter()
+        .filter(|record| record.event_name.starts_with("ObjectCreated:"))
+        .map(|record| {
+            let S3Entity { bucket, object } = record.s3;
+            let key = decode_key(&object.key);
+            let version = object.sequencer.or(object.e_tag).unwrap_or_default();
+            CreatedObject { id: format!("s3://{}/{}@{}", bucket.name, key, version), bucket: bucket.name, key }
+        })
+        .collect())
+}
+
+/// Keys in S3 notifications are URL-encoded, with `+` for spaces
+fn decode_key(key: &str) -> String {
+    let bytes = key.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => match key.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                Some(byte) => {
+                    decoded.push(byte);
+                    i += 2;
+                }
+                None => decoded.push(b'%'),
+            },
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn decode_object<B: AsRef<[u8]> + Send + Sync>(bytes: B, format: ObjectFormat) -> PolarsResult<DataFrame> {
+    let array = bytes.as_ref().iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'[');
+    let cursor = Cursor::new(bytes);
+    match format {
+        ObjectFormat::Json => JsonReader::new(cursor)
+            .with_json_format(if array { JsonFormat::Json } else { JsonFormat::JsonLines })
+            .finish(),
+        ObjectFormat::Csv => CsvReadOptions::default()
+            .with_has_header(true)
+            .into_reader_with_file_handle(cursor)
+            .finish(),
+        ObjectFormat::Parquet => ParquetReader::new(cursor).finish(),
+    }
+}
+
+/// Source appending the objects announced on an SQS queue of S3
+/// notifications. A notification is deleted from the queue only once its
+/// objects are committed; the objects of each commit are listed in its
+/// commit info, so a notification delivered again, e.g. after a crash
+/// between commit and delete, is recognised and not appended twice.
+pub struct SqsSource {
+    config: SqsSourceConfig,
+    client: Client,
+    storage_options: StorageOptions,
+    /// Object store per bucket, opened on first use
+    stores: HashMap<String, ObjectStoreRef>,
+    /// Batches committed so far, the version of the queue's application transaction
+    committed_batches: i64,
+    /// Receipt handles of the notifications in the batch awaiting commit
+    pending: Vec<String>,
+    /// Objects in the batch awaiting commit
+    pending_objects: Vec<String>,
+    /// Objects appended recently, with their order of appending for eviction
+    appended: HashSet<String>,
+    appended_order: VecDeque<String>,
+    status: SourceStatusHandle,
+}
+
+impl SqsSource {
+    /// Create a client for the configured queue. Objects are read with
+    /// `storage_options`, normally those of the table.
+    pub async fn new(config: SqsSourceConfig, storage_options: StorageOptions, status: SourceStatusHandle) -> Result<Self> {
+        let mut loader = aws_config::defaults(BehaviorVersion::latest());
+        if let Some(region) = &config.region {
+            loader = loader.region(Region::new(region.clone()));
+        }
+        if let Some(endpoint) = &config.endpoint_url {
+            loader = loader.endpoint_url(endpoint);
+        }
+        let client = Client::new(&loader.load().await);
+
+        status.lock().unwrap().name = config.queue_url.clone();
+        Ok(Self {
+            config,
+            client,
+            storage_options,
+            stores: HashMap::new(),
+            committed_batches: 0,
+            pending: Vec::new(),
+            pending_objects: Vec::new(),
+            appended: HashSet::new(),
+            appended_order: VecDeque::new(),
+            status,
+        })
+    }
+
+    fn app_id(&self) -> String {
+        format!("sqs:{}", self.config.queue_url)
+    }
+
+    fn remember(&mut self, id: String) {
+        if self.appended.insert(id.clone()) {
+            self.appended_order.push_back(id);
+        }
+        if self.appended_order.len() > REMEMBERED_OBJECTS {
+            if let Some(oldest) = self.appended_order.pop_front() {
+                self.appended.remove(&oldest);
+            }
+        }
+    }
+
+    /// Whether an announced object should be read into the table
+    fn wanted(&self, object: &CreatedObject) -> bool {
+        let matches = self.config.key_pattern.as_deref().is_none_or(|pattern| glob_match(pattern, &object.key));
+        matches && !self.appended.contains(&object.id) && !self.pending_objects.contains(&object.id)
+    }
+
+    fn store(&mut self, bucket: &str) -> Result<ObjectStoreRef> {
+        if let Some(store) = self.stores.get(bucket) {
+            return Ok(store.clone());
+        }
+        let store = DeltaTableBuilder::from_uri(format!("s3://{}", bucket))
+            .with_storage_options(self.storage_options.0.clone())
+            .build_storage()
+            .with_context(|| format!("Failed to open bucket {}", bucket))?
+            .object_store(None);
+        self.stores.insert(bucket.to_string(), store.clone());
+        Ok(store)
+    }
+
+    /// The object's rows, or `None` if it was deleted before being read or
+    /// cannot be decoded
+    async fn read_object(&mut self, object: &CreatedObject) -> Result<Option<DataFrame>> {
+        let Some(format) = self.config.format.or_else(|| ObjectFormat::from_key(&object.key)) else {
+            log::warn!("Skipping {}: no format configured and none implied by its extension", object.id);
+            self.status.lock().unwrap().decode_errors += 1;
+            return Ok(None);
+        };
+        let store = self.store(&object.bucket)?;
+        let bytes = match store.get(&Path::from(object.key.as_str())).await {
+            Ok(result) => result.bytes().await.with_context(|| format!("Failed to read {}", object.id))?,
+            Err(ObjectStoreError::NotFound { .. }) => {
+                log::warn!("Skipping {}: deleted before it was read", object.id);
+                return Ok(None);
+            }
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", object.id)),
+        };
+
+        match decode_object(bytes, format) {
+            Ok(df) => Ok(Some(df)),
+            Err(e) => {
+                log::warn!("Skipping {}: not valid {:?}: {}", object.id, format, e);
+                self.status.lock().unwrap().decode_errors += 1;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Delete notifications from the queue. One that fails to delete is
+    /// delivered again later and recognised as already appended.
+    async fn delete(&self, handles: &[String]) -> Result<()> {
+        for chunk in handles.chunks(MAX_MESSAGES_PER_CALL) {
+            let entries = chunk
+                .iter()
+                .enumerate()
+                .map(|(i, handle)| DeleteMessageBatchRequestEntry::builder().id(i.to_string()).receipt_handle(handle).build())
+                .collect::<Result<Vec<_>, _>>()?;
+            let response = self
+                .client
+                .delete_message_batch()
+                .queue_url(&self.config.queue_url)
+                .set_entries(Some(entries))
+                .send()
+                .await
+                .with_context(|| format!("Failed to delete notifications from {}", self.config.queue_url))?;
+            for failed in response.failed() {
+                log::warn!(
+                    "Failed to delete a notification from {}: {}",
+                    self.config.queue_url,
+                    failed.message().unwrap_or(failed.code())
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Source for SqsSource {
+    fn name(&self) -> &str {
+        &self.config.queue_url
+    }
+
+    async fn resume(&mut self, table: &DeltaTable) -> Result<()> {
+        let app_id = self.app_id();
+        self.committed_batches = committed_positions(table, &app_id).get(&app_id).copied().unwrap_or(0);
+        self.pending.clear();
+        self.pending_objects.clear();
+        self.appended.clear();
+        self.appended_order.clear();
+
+        // Notifications are deleted right after their commit, so only those
+        // of the last few commits can still be delivered again
+        let history = table.history(Some(RECENT_COMMITS)).await
+            .context("Failed to read table history for appended objects")?;
+        for commit in history.iter().rev() {
+            let Some(objects) = commit.info.get(OBJECTS_KEY) else {
+                continue;
+            };
+            if objects.get("queue").and_then(Value::as_str) != Some(self.config.queue_url.as_str()) {
+                continue;
+            }
+            let ids = objects.get("objects").and_then(Value::as_array).into_iter().flatten();
+            for id in ids.filter_map(Value::as_str) {
+                self.remember(id.to_string());
+            }
+        }
+
+        log::info!(
+            "SQS {} resuming after {} batches ({} recently appended objects)",
+            self.config.queue_url,
+            self.committed_batches,
+            self.appended.len()
+        );
+        self.status.lock().unwrap().assignment_changes += 1;
+        Ok(())
+    }
+
+    async fn next_batch(&mut self, max_rows: usize, max_wait: Duration) -> Result<Option<SourceBatch>> {
+        let deadline = Instant::now() + max_wait;
+        let mut frames = Vec::new();
+        let mut rows = 0usize;
+        // Notifications with nothing left to append, acknowledged right away
+        let mut skipped = Vec::new();
+
+        while rows < max_rows {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            let wait = remaining.as_secs().min(self.config.wait_time_secs).min(MAX_WAIT_TIME_SECS);
+            let mut request = self
+                .client
+                .receive_message()
+                .queue_url(&self.config.queue_url)
+                .max_number_of_messages(MAX_MESSAGES_PER_CALL as i32)
+                .wait_time_seconds(wait as i32);
+            if let Some(timeout) = self.config.visibility_timeout_secs {
+                request = request.visibility_timeout(timeout as i32);
+            }
+            let response = request
+                .send()
+                .await
+                .with_context(|| format!("Failed to receive from {}", self.config.queue_url))?;
+            let messages = response.messages();
+            self.status.lock().unwrap().messages_consumed += messages.len() as u64;
+            if messages.is_empty() && wait == 0 {
+                break;
+            }
+
+            for message in messages {
+                let Some(handle) = message.receipt_handle() else {
+                    continue;
+                };
+                let objects = match created_objects(message.body().unwrap_or_default()) {
+                    Ok(objects) => objects,
+                    Err(e) => {
+                        log::warn!("Skipping notification from {}: {:#}", self.config.queue_url, e);
+                        self.status.lock().unwrap().decode_errors += 1;
+                        skipped.push(handle.to_string());
+                        continue;
+                    }
+                };
+
+                let mut appended = false;
+                let mut unreadable = false;
+                let wanted: Vec<CreatedObject> = objects.into_iter().filter(|object| self.wanted(object)).collect();
+                for object in &wanted {
+                    let Some(df) = self.read_object(object).await? else {
+                        unreadable = true;
+                        continue;
+                    };
+                    appended = true;
+                    rows += df.height();
+                    if df.height() > 0 {
+                        frames.push(df);
+                    }
+                    self.pending_objects.push(object.id.clone());
+                    let mut status = self.status.lock().unwrap();
+                    status.partitions.entry(object.bucket.clone()).or_default().consumed += 1;
+                }
+                // Notifications of objects that cannot be read stay on the
+                // queue, for its redrive policy to move to a dead-letter queue
+                if appended {
+                    self.pending.push(handle.to_string());
+                } else if !unreadable {
+                    skipped.push(handle.to_string());
+                }
+            }
+        }
+
+        self.delete(&skipped).await?;
+        if self.pending.is_empty() {
+            return Ok(None);
+        }
+
+        let df = concat_frames(frames.into_iter())
+            .with_context(|| format!("Objects from {} in one batch have incompatible schemas", self.config.queue_url))?;
+        let checkpoints = vec![Transaction::new(self.app_id(), self.committed_batches + 1)];
+        let metadata = vec![(
+            OBJECTS_KEY.to_string(),
+            json!({ "queue": self.config.queue_url, "objects": self.pending_objects }),
+        )];
+        Ok(Some(SourceBatch { df, checkpoints, metadata }))
+    }
+
+    async fn committed(&mut self, checkpoints: &[Transaction]) -> Result<()> {
+        let app_id = self.app_id();
+        if let Some(txn) = checkpoints.iter().find(|txn| txn.app_id == app_id) {
+            self.committed_batches = txn.version;
+        }
+        for id in std::mem::take(&mut self.pending_objects) {
+            self.remember(id);
+        }
+        {
+            let mut status = self.status.lock().unwrap();
+            for partition in status.partitions.values_mut() {
+                partition.committed = partition.consumed;
+            }
+        }
+        let handles = std::mem::take(&mut self.pending);
+        self.delete(&handles).await
+    }
+
+    fn status(&self) -> Option<SourceStatusHandle> {
+        Some(self.status.clone())
+    }
+}
@@ -0,0 +1,132 @@
+use anyhow::{Context, Result};
+use deltalake::kernel::Transaction;
+use deltalake::DeltaTable;
+use polars::prelude::*;
+use std::io::Cursor;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader, Lines, Stdin};
+use tokio::time::{timeout_at, Instant};
+use crate::sources::{Source, SourceBatch, SourceStatusHandle};
+
+/// Newline-delimited JSON read from standard input. The schema is inferred
+/// from the first batch and every later line must match it. Stdin cannot be
+/// rewound, so there are no positions to checkpoint or resume from.
+pub struct StdinSource {
+    lines: Lines<BufReader<Stdin>>,
+    schema: Option<SchemaRef>,
+    eof: bool,
+    status: SourceStatusHandle,
+}
+
+impl StdinSource {
+    pub fn new(status: SourceStatusHandle) -> Self {
+        status.lock().unwrap().name = "stdin".to_string();
+        Self {
+            lines: BufReader::new(tokio::io::stdin()).lines(),
+            schema: None,
+            eof: false,
+            status,
+        }
+    }
+
+    fn parse(&self, bytes: Vec<u8>) -> PolarsResult<DataFrame> {
+        let reader = JsonReader::new(Cursor::new(bytes)).with_json_format(JsonFormat::JsonLines);
+        match &self.schema {
+            Some(schema) => reader.with_schema(schema.clone()).finish(),
+            None => reader.finish(),
+        }
+    }
+
+    /// Decode a batch of lines. If the batch does not parse as a whole, lines
+    /// are decoded one by one and those not matching the schema are skipped.
+    fn decode(&mut self, lines: &[String]) -> Result<DataFrame> {
+        if let Ok(df) = self.parse(lines.join("\n").into_bytes()) {
+            self.schema.get_or_insert_with(|| df.schema().clone());
+            return Ok(df);
+        }
+
+        let mut frames: Vec<DataFrame> = Vec::with_capacity(lines.len());
+        let mut errors = 0u64;
+        for (i, line) in lines.iter().enumerate() {
+            match self.parse(line.clone().into_bytes()) {
+                Ok(df) => {
+                    self.schema.get_or_insert_with(|| df.schema().clone());
+                    frames.push(df);
+                }
+                Err(e) => {
+                    errors += 1;
+                    log::warn!("Skipping invalid line {} of stdin batch: {}", i + 1, e);
+                }
+            }
+        }
+        self.status.lock().unwrap().decode_errors += errors;
+
+        if frames.is_empty() {
+            return Ok(DataFrame::empty());
+        }
+        let mut df = frames.remove(0);
+        for frame in &frames {
+            df.vstack_mut(frame)
+                .context("Lines in one stdin batch have incompatible schemas")?;
+        }
+        Ok(df)
+    }
+}
+
+impl Source for StdinSource {
+    fn name(&self) -> &str {
+        "stdin"
+    }
+
+    async fn resume(&mut self, _table: &DeltaTable) -> Result<()> {
+        Ok(())
+    }
+
+    async fn next_batch(&mut self, max_rows: usize, max_wait: Duration) -> Result<Option<SourceBatch>> {
+        let mut lines = Vec::new();
+        let mut deadline = None;
+
+        while lines.len() < max_rows {
+            // The batch window starts at the first line, so an idle pipe
+            // does not produce a stream of empty batches
+            let next = match deadline {
+                Some(deadline) => match timeout_at(deadline, self.lines.next_line()).await {
+                    Err(_) => break,
+                    Ok(next) => next,
+                },
+                None => self.lines.next_line().await,
+            };
+            match next.context("Failed to read from stdin")? {
+                Some(line) if line.trim().is_empty() => continue,
+                Some(line) => {
+                    deadline.get_or_insert_with(|| Instant::now() + max_wait);
+                    lines.push(line);
+                }
+                None => {
+                    self.eof = true;
+                    break;
+                }
+            }
+        }
+
+        if lines.is_empty() {
+            return Ok(None);
+        }
+
+        self.status.lock().unwrap().messages_consumed += lines.len() as u64;
+        let df = self.decode(&lines)?;
+        Ok(Some(SourceBatch { df, checkpoints: Vec::new(), metadata: Vec::new() }))
+    }
+
+    async fn committed(&mut self, _checkpoints: &[Transaction]) -> Result<()> {
+        Ok(())
+    }
+
+    fn status(&self) -> Option<SourceStatusHandle> {
+        Some(self.status.clone())
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.eof
+    }
+}
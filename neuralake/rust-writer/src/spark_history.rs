@@ -0,0 +1,85 @@
+use deltalake::kernel::Add;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+/// Metrics delta-rs records under a different name than Spark, by operation
+const SPARK_METRIC_NAMES: &[(&str, &str, &str)] = &[
+    ("OPTIMIZE", "numFilesAdded", "numAddedFiles"),
+    ("OPTIMIZE", "numFilesRemoved", "numRemovedFiles"),
+    ("RESTORE", "numRemovedFile", "numRemovedFiles"),
+    ("RESTORE", "numRestoredFile", "numRestoredFiles"),
+];
+
+/// Operation metrics in the shape Spark's `DESCRIBE HISTORY` reports them:
+/// every value a string, under the names Spark uses. Names delta-rs uses
+/// instead are kept alongside, so tooling written against either engine
+/// finds its keys whichever engine made the commit.
+pub fn spark_operation_metrics(operation: Option<&str>, metrics: &Map<String, Value>) -> BTreeMap<String, String> {
+    let mut spark: BTreeMap<String, String> = metrics
+        .iter()
+        .map(|(name, value)| (name.clone(), metric_string(value)))
+        .collect();
+
+    let operation = operation.unwrap_or_default();
+    for (op, rust_name, spark_name) in SPARK_METRIC_NAMES {
+        if *op == operation && !spark.contains_key(*spark_name) {
+            if let Some(value) = spark.get(*rust_name).cloned() {
+                spark.insert(spark_name.to_string(), value);
+            }
+        }
+    }
+
+    // delta-rs summarises OPTIMIZE file sizes as `filesAdded`/`filesRemoved`
+    // objects where Spark has flat byte counts
+    if operation == "OPTIMIZE" {
+        for (field, bytes) in [("filesAdded", "numAddedBytes"), ("filesRemoved", "numRemovedBytes")] {
+            let Some(details) = metrics.get(field).and_then(metric_details) else {
+                continue;
+            };
+            if let Some(total) = details.get("totalSize") {
+                spark.entry(bytes.to_string()).or_insert_with(|| metric_string(total));
+            }
+            if field == "filesAdded" {
+                for (stat, name) in [("min", "minFileSize"), ("max", "maxFileSize")] {
+                    if let Some(value) = details.get(stat) {
+                        spark.entry(name.to_string()).or_insert_with(|| metric_string(value));
+                    }
+                }
+            }
+        }
+    }
+    spark
+}
+
+/// Spark stores metrics as strings; nested values are JSON-encoded
+fn metric_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// `filesAdded`-style details, written as an object or as a JSON string
+fn metric_details(value: &Value) -> Option<Map<String, Value>> {
+    match value {
+        Value::Object(details) => Some(details.clone()),
+        Value::String(encoded) => serde_json::from_str(encoded).ok(),
+        _ => None,
+    }
+}
+
+/// `operationMetrics` of a rollback's inverse commit, under the names
+/// Spark's DELETE uses for files and bytes
+pub fn rollback_metrics(removed: &[Add], restored: &[Add]) -> Value {
+    let size = |files: &[Add]| files.iter().map(|add| add.size).sum::<i64>();
+    let metrics: Map<String, Value> = [
+        ("numRemovedFiles", removed.len().to_string()),
+        ("numRemovedBytes", size(removed).to_string()),
+        ("numAddedFiles", restored.len().to_string()),
+        ("numAddedBytes", size(restored).to_string()),
+    ]
+    .into_iter()
+    .map(|(name, value)| (name.to_string(), Value::String(value)))
+    .collect();
+    Value::Object(metrics)
+}
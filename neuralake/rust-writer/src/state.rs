@@ -0,0 +1,243 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use crate::config::{FsyncPolicy, StateConfig};
+
+/// First bytes of every entry, naming the format version
+const MAGIC: &str = "SSSTATE1";
+/// Extension of committed entries
+const ENTRY_EXTENSION: &str = "state";
+/// Marker in the names of entries being written; one left behind is an
+/// interrupted write
+const TEMP_MARKER: &str = ".tmp-";
+
+/// Health of one entry in the state directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", content = "reason", rename_all = "snake_case")]
+pub enum EntryStatus {
+    Ok,
+    /// Header or checksum does not match the contents
+    Corrupt(String),
+    /// Temporary file of a write that never completed
+    Incomplete,
+}
+
+/// An entry as listed by [`StateDir::inspect`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateEntry {
+    /// Name the entry is read and written under; for incomplete writes, the
+    /// file name relative to the directory
+    pub name: String,
+    /// Size of the payload, or of the whole file if it is not intact
+    pub bytes: u64,
+    pub modified: Option<DateTime<Utc>>,
+    #[serde(flatten)]
+    pub status: EntryStatus,
+}
+
+impl fmt::Display for StateEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let modified = self.modified.map(|m| m.to_rfc3339()).unwrap_or_else(|| "-".to_string());
+        let status = match &self.status {
+            EntryStatus::Ok => "ok".to_string(),
+            EntryStatus::Corrupt(reason) => format!("corrupt: {}", reason),
+            EntryStatus::Incomplete => "incomplete write".to_string(),
+        };
+        write!(f, "{:<40}  {:>10}  {}  {}", self.name, self.bytes, modified, status)
+    }
+}
+
+/// Directory holding the orchestrator's local state (checkpoints, logs,
+/// caches) as named entries.
+///
+/// Each entry is one file with a header carrying the SHA-256 and length of
+/// its payload, so a torn or bit-rotted file is reported instead of parsed.
+/// Writes go to a temporary file that is renamed over the entry, so readers
+/// see either the old or the new contents, never a mix; `fsync` decides how
+/// much of that survives a power loss. Names are `/`-separated paths of
+/// letters, digits, `_`, `-` and `.`, e.g. `orders/wal`.
+#[derive(Debug, Clone)]
+pub struct StateDir {
+    root: PathBuf,
+    fsync: FsyncPolicy,
+}
+
+impl StateDir {
+    /// State below `root`; nothing is created until the first write
+    pub fn new(root: impl Into<PathBuf>, fsync: FsyncPolicy) -> Self {
+        Self { root: root.into(), fsync }
+    }
+
+    pub fn from_config(config: &StateConfig) -> Self {
+        Self::new(&config.dir, config.fsync)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn path(&self, name: &str) -> Result<PathBuf> {
+        let valid_segment = |segment: &str| {
+            !segment.is_empty()
+                && !segment.starts_with('.')
+                && segment.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        };
+        if !name.split('/').all(valid_segment) || name.contains(TEMP_MARKER) {
+            bail!("Invalid state entry name '{}'", name);
+        }
+        Ok(self.root.join(format!("{}.{}", name, ENTRY_EXTENSION)))
+    }
+
+    /// Atomically replace entry `name` with `payload`
+    pub fn write(&self, name: &str, payload: &[u8]) -> Result<()> {
+        let path = self.path(name)?;
+        let dir = path.parent().unwrap_or(&self.root);
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create state directory {}", dir.display()))?;
+
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let temp = dir.join(format!("{}{}{}", file_name, TEMP_MARKER, std::process::id()));
+        let header = format!("{} {:x} {}\n", MAGIC, Sha256::digest(payload), payload.len());
+        let written = File::create(&temp).and_then(|mut file| {
+            file.write_all(header.as_bytes())?;
+            file.write_all(payload)?;
+            if self.fsync != FsyncPolicy::Off {
+                file.sync_all()?;
+            }
+            Ok(())
+        });
+        if let Err(e) = written.and_then(|_| fs::rename(&temp, &path)) {
+            let _ = fs::remove_file(&temp);
+            return Err(e).with_context(|| format!("Failed to write state entry {}", path.display()));
+        }
+        if self.fsync == FsyncPolicy::Full {
+            // Persist the rename itself
+            File::open(dir)
+                .and_then(|dir| dir.sync_all())
+                .with_context(|| format!("Failed to sync state directory {}", dir.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Payload of entry `name`, `None` if it does not exist. Fails if the
+    /// entry is corrupt.
+    pub fn read(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        let path = self.path(name)?;
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read state entry {}", path.display())),
+        };
+        match decode(&bytes) {
+            Ok(payload) => Ok(Some(payload.to_vec())),
+            Err(reason) => bail!("State entry {} is corrupt: {}", path.display(), reason),
+        }
+    }
+
+    pub fn write_json<T: Serialize>(&self, name: &str, value: &T) -> Result<()> {
+        self.write(name, &serde_json::to_vec(value)?)
+    }
+
+    pub fn read_json<T: DeserializeOwned>(&self, name: &str) -> Result<Option<T>> {
+        self.read(name)?
+            .map(|payload| serde_json::from_slice(&payload))
+            .transpose()
+            .with_context(|| format!("State entry {} is not valid JSON for its type", name))
+    }
+
+    /// Delete entry `name`; returns false if it did not exist
+    pub fn remove(&self, name: &str) -> Result<bool> {
+        let path = self.path(name)?;
+        match fs::remove_file(&path) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e).with_context(|| format!("Failed to remove state entry {}", path.display())),
+        }
+    }
+
+    /// Every entry and leftover temporary file, with its health, by name
+    pub fn inspect(&self) -> Result<Vec<StateEntry>> {
+        let mut entries = Vec::new();
+        if self.root.exists() {
+            self.inspect_dir(&self.root, &mut entries)?;
+        }
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(entries)
+    }
+
+    fn inspect_dir(&self, dir: &Path, entries: &mut Vec<StateEntry>) -> Result<()> {
+        let listing = fs::read_dir(dir).with_context(|| format!("Failed to list {}", dir.display()))?;
+        for entry in listing {
+            let entry = entry?;
+            let path = entry.path();
+            if entry.file_type()?.is_dir() {
+                self.inspect_dir(&path, entries)?;
+                continue;
+            }
+            let relative = path.strip_prefix(&self.root).unwrap_or(&path).to_string_lossy().replace('\\', "/");
+            let metadata = entry.metadata()?;
+            let modified = metadata.modified().ok().map(DateTime::<Utc>::from);
+
+            if relative.contains(TEMP_MARKER) {
+                entries.push(StateEntry { name: relative, bytes: metadata.len(), modified, status: EntryStatus::Incomplete });
+                continue;
+            }
+            let Some(name) = relative.strip_suffix(&format!(".{}", ENTRY_EXTENSION)).map(str::to_string) else {
+                continue;
+            };
+            let bytes = fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+            let (bytes, status) = match decode(&bytes) {
+                Ok(payload) => (payload.len() as u64, EntryStatus::Ok),
+                Err(reason) => (bytes.len() as u64, EntryStatus::Corrupt(reason)),
+            };
+            entries.push(StateEntry { name, bytes, modified, status });
+        }
+        Ok(())
+    }
+
+    /// Delete the entries `names`, or with none given every entry and
+    /// temporary file, leaving the directory empty. Returns the files removed.
+    pub fn reset(&self, names: &[String]) -> Result<usize> {
+        if !names.is_empty() {
+            let mut removed = 0;
+            for name in names {
+                removed += self.remove(name)? as usize;
+            }
+            return Ok(removed);
+        }
+        let files = self.inspect()?.len();
+        if self.root.exists() {
+            for entry in fs::read_dir(&self.root).with_context(|| format!("Failed to list {}", self.root.display()))? {
+                let path = entry?.path();
+                let removed = if path.is_dir() { fs::remove_dir_all(&path) } else { fs::remove_file(&path) };
+                removed.with_context(|| format!("Failed to remove {}", path.display()))?;
+            }
+        }
+        Ok(files)
+    }
+}
+
+/// Payload of an entry file, checked against its header
+fn decode(bytes: &[u8]) -> std::result::Result<&[u8], String> {
+    let newline = bytes.iter().position(|b| *b == b'\n').ok_or("missing header")?;
+    let header = std::str::from_utf8(&bytes[..newline]).map_err(|_| "unreadable header")?;
+    let payload = &bytes[newline + 1..];
+    let [magic, checksum, length] = header.split(' ').collect::<Vec<_>>()[..] else {
+        return Err("malformed header".to_string());
+    };
+    if magic != MAGIC {
+        return Err(format!("unknown format '{}'", magic));
+    }
+    if length.parse::<usize>().ok() != Some(payload.len()) {
+        return Err(format!("expected {} bytes, found {}", length, payload.len()));
+    }
+    if format!("{:x}", Sha256::digest(payload)) != checksum {
+        return Err("checksum mismatch".to_string());
+    }
+    Ok(payload)
+}
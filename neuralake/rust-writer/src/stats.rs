@@ -0,0 +1,502 @@
+use anyhow::{bail, Context, Result};
+use deltalake::kernel::{Action, Add, DataType, PrimitiveType, Remove};
+use deltalake::operations::transaction::{CommitBuilder, CommitProperties};
+use deltalake::parquet::arrow::parquet_to_arrow_schema;
+use deltalake::protocol::DeltaOperation;
+use deltalake::{DeltaOps, DeltaTable};
+use futures::{stream, StreamExt, TryStreamExt};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use crate::register::{file_stats, read_footer};
+
+/// Table property naming the columns delta-rs collects statistics for
+pub const STATS_COLUMNS_PROPERTY: &str = "delta.dataSkippingStatsColumns";
+/// Without the property above, statistics cover this many leading columns
+const NUM_INDEXED_COLS_PROPERTY: &str = "delta.dataSkippingNumIndexedCols";
+/// Delta's default for `delta.dataSkippingNumIndexedCols`
+const DEFAULT_NUM_INDEXED_COLS: usize = 32;
+/// Parquet footers read at once while re-computing statistics
+const RESTAT_CONCURRENCY: usize = 16;
+
+/// Statistics recorded for one data file
+#[derive(Debug, Clone, Serialize)]
+pub struct FileStats {
+    pub path: String,
+    /// `None` if the file has no statistics at all
+    pub num_records: Option<i64>,
+    /// Columns with a min/max value for this file
+    pub min_max: Vec<String>,
+    /// Columns with a null count for this file
+    pub null_count: Vec<String>,
+}
+
+/// How many files carry statistics for one column
+#[derive(Debug, Clone, Serialize)]
+pub struct ColumnCoverage {
+    pub column: String,
+    pub files_with_min_max: usize,
+    pub files_with_null_count: usize,
+}
+
+/// Data-skipping statistics of the active files at one version
+#[derive(Debug, Clone, Serialize)]
+pub struct StatsCoverage {
+    pub table_uri: String,
+    pub version: i64,
+    /// Columns statistics are collected for on write, as the table declares
+    pub stats_columns: Vec<String>,
+    pub num_files: usize,
+    pub files_without_stats: usize,
+    pub columns: Vec<ColumnCoverage>,
+    pub files: Vec<FileStats>,
+}
+
+impl fmt::Display for StatsCoverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Statistics of {} at version {}", self.table_uri, self.version)?;
+        writeln!(f, "Collected for: {}", self.stats_columns.join(", "))?;
+        writeln!(
+            f,
+            "Files with statistics: {}/{}",
+            self.num_files - self.files_without_stats,
+            self.num_files
+        )?;
+        writeln!(f, "  {:<30} {:>12} {:>12}", "column", "min/max", "null count")?;
+        for column in &self.columns {
+            writeln!(
+                f,
+                "  {:<30} {:>12} {:>12}",
+                column.column,
+                format!("{}/{}", column.files_with_min_max, self.num_files),
+                format!("{}/{}", column.files_with_null_count, self.num_files),
+            )?;
+        }
+        for file in self.files.iter().filter(|file| file.num_records.is_none()) {
+            writeln!(f, "  no statistics: {}", file.path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Outcome of re-computing the statistics of files missing some
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RestatReport {
+    /// Version that re-committed the statistics; `None` for a dry run or
+    /// when nothing could be improved
+    pub version: Option<i64>,
+    pub dry_run: bool,
+    /// Active files lacking statistics on a stats column
+    pub files_missing_stats: usize,
+    /// Files whose statistics were (or would be) re-committed
+    pub files_restated: usize,
+    /// Files whose footers lack the statistics too; only rewriting them,
+    /// e.g. by compaction, adds them
+    pub files_unresolved: Vec<String>,
+}
+
+impl fmt::Display for RestatReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verb = if self.dry_run { "Would restate" } else { "Restated" };
+        write!(f, "{} {} of {} files missing statistics", verb, self.files_restated, self.files_missing_stats)?;
+        if let Some(version) = self.version {
+            write!(f, " (version {})", version)?;
+        }
+        writeln!(f)?;
+        for path in &self.files_unresolved {
+            writeln!(f, "  footer has no statistics either: {}", path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Statistics of one column aggregated over the active files, for query
+/// planners and catalogs
+#[derive(Debug, Clone, Serialize)]
+pub struct ColumnProfile {
+    pub column: String,
+    pub data_type: String,
+    pub partition: bool,
+    /// Smallest and largest value across the files with statistics for it
+    pub min: Option<Value>,
+    pub max: Option<Value>,
+    /// `None` unless every file has a null count (and a row count) for it
+    pub null_count: Option<i64>,
+    pub null_fraction: Option<f64>,
+    /// Estimated distinct non-null values: exact for partition columns; for
+    /// integer columns the smaller of the value range and the non-null rows;
+    /// otherwise the distinct file-level bounds, a lower bound
+    pub approx_ndv: Option<u64>,
+    pub files_with_stats: usize,
+}
+
+/// Column statistics of the active files at one version
+#[derive(Debug, Clone, Serialize)]
+pub struct TableProfile {
+    pub table_uri: String,
+    pub version: i64,
+    pub num_files: usize,
+    /// `None` if any file lacks a row count
+    pub num_rows: Option<i64>,
+    pub columns: Vec<ColumnProfile>,
+}
+
+impl fmt::Display for TableProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unknown = || "-".to_string();
+        writeln!(
+            f,
+            "  {:<30} {:>20} {:>20} {:>10} {:>10}",
+            "column", "min", "max", "null %", "ndv"
+        )?;
+        for column in &self.columns {
+            writeln!(
+                f,
+                "  {:<30} {:>20} {:>20} {:>10} {:>10}",
+                column.column,
+                column.min.as_ref().map_or_else(unknown, display_value),
+                column.max.as_ref().map_or_else(unknown, display_value),
+                column.null_fraction.map_or_else(unknown, |n| format!("{:.2}", n * 100.0)),
+                column.approx_ndv.map_or_else(unknown, |n| n.to_string()),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn display_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Aggregate the per-file statistics of the loaded version into global
+/// min/max, null fraction and an approximate distinct count per column,
+/// without reading any data file
+pub fn table_profile(table: &DeltaTable) -> Result<TableProfile> {
+    let metadata = table.metadata()?;
+    let schema = table.get_schema()?;
+    let adds = table.snapshot()?.file_actions()?;
+    let stats: Vec<Value> = adds
+        .iter()
+        .map(|add| add.stats.as_deref().and_then(|stats| serde_json::from_str(stats).ok()).unwrap_or(Value::Null))
+        .collect();
+    let rows: Vec<Option<i64>> = stats.iter().map(|s| s["numRecords"].as_i64()).collect();
+    let num_rows = rows.iter().copied().sum::<Option<i64>>();
+
+    let mut columns = Vec::new();
+    for field in schema.fields() {
+        let name = field.name();
+        let partition = metadata.partition_columns.contains(name);
+        let mut profile = ColumnProfile {
+            column: name.clone(),
+            data_type: field.data_type().to_string(),
+            partition,
+            min: None,
+            max: None,
+            null_count: None,
+            null_fraction: None,
+            approx_ndv: None,
+            files_with_stats: 0,
+        };
+        if partition {
+            let values: Vec<Option<&String>> =
+                adds.iter().map(|add| add.partition_values.get(name).and_then(Option::as_ref)).collect();
+            let distinct: BTreeSet<&String> = values.iter().flatten().copied().collect();
+            profile.min = distinct.first().map(|v| Value::from(v.as_str()));
+            profile.max = distinct.last().map(|v| Value::from(v.as_str()));
+            profile.approx_ndv = Some(distinct.len() as u64);
+            profile.files_with_stats = adds.len();
+            profile.null_count =
+                values.iter().zip(&rows).map(|(value, rows)| if value.is_none() { *rows } else { Some(0) }).sum();
+        } else {
+            let mut bounds = BTreeSet::new();
+            let mut null_count = Some(0i64);
+            for (file, rows) in stats.iter().zip(&rows) {
+                let min = scalar(&file["minValues"][name]);
+                let max = scalar(&file["maxValues"][name]);
+                let nulls = file["nullCount"][name].as_i64();
+                if min.is_some() || nulls.is_some() {
+                    profile.files_with_stats += 1;
+                }
+                null_count = null_count.zip(nulls).zip(*rows).map(|((total, nulls), _)| total + nulls);
+                if let Some(min) = min {
+                    bounds.insert(min.to_string());
+                    profile.min = Some(extreme(profile.min.take(), min, Ordering::Less));
+                }
+                if let Some(max) = max {
+                    bounds.insert(max.to_string());
+                    profile.max = Some(extreme(profile.max.take(), max, Ordering::Greater));
+                }
+            }
+            profile.null_count = null_count;
+            let non_null = num_rows.zip(null_count).map(|(rows, nulls)| (rows - nulls).max(0) as u64);
+            let range = match field.data_type() {
+                DataType::Primitive(
+                    PrimitiveType::Byte | PrimitiveType::Short | PrimitiveType::Integer | PrimitiveType::Long,
+                ) => profile
+                    .min
+                    .as_ref()
+                    .and_then(Value::as_i64)
+                    .zip(profile.max.as_ref().and_then(Value::as_i64))
+                    .map(|(min, max)| max.abs_diff(min).saturating_add(1)),
+                _ => None,
+            };
+            profile.approx_ndv = match (range, non_null) {
+                (Some(range), Some(non_null)) => Some(range.min(non_null)),
+                (Some(range), None) => Some(range),
+                (None, non_null) if profile.files_with_stats > 0 => {
+                    let seen = bounds.len() as u64;
+                    Some(non_null.map_or(seen, |n| seen.min(n)))
+                }
+                _ => None,
+            };
+        }
+        profile.null_fraction = profile
+            .null_count
+            .zip(num_rows)
+            .filter(|(_, rows)| *rows > 0)
+            .map(|(nulls, rows)| nulls as f64 / rows as f64);
+        columns.push(profile);
+    }
+
+    Ok(TableProfile { table_uri: table.table_uri(), version: table.version(), num_files: adds.len(), num_rows, columns })
+}
+
+/// Scalar statistic; nested columns have objects instead
+fn scalar(value: &Value) -> Option<&Value> {
+    (!value.is_null() && !value.is_object()).then_some(value)
+}
+
+/// `candidate` if it compares as `wanted` against `current`; numbers compare
+/// numerically, anything else by its JSON text (ISO dates and timestamps
+/// sort correctly)
+fn extreme(current: Option<Value>, candidate: &Value, wanted: Ordering) -> Value {
+    let Some(current) = current else {
+        return candidate.clone();
+    };
+    let ordering = match (candidate.as_f64(), current.as_f64()) {
+        (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+        _ => candidate.to_string().cmp(&current.to_string()),
+    };
+    if ordering == wanted { candidate.clone() } else { current }
+}
+
+/// Point `delta.dataSkippingStatsColumns` at `columns`, so flushes and
+/// compaction collect min/max/null counts for them. Leaves the table alone
+/// when `columns` is empty or already configured.
+pub async fn ensure_stats_columns(table: DeltaTable, columns: &[String]) -> Result<DeltaTable> {
+    if columns.is_empty() {
+        return Ok(table);
+    }
+    let schema = table.get_schema()?;
+    let unknown: Vec<&String> = columns.iter().filter(|c| schema.field(c).is_none()).collect();
+    if !unknown.is_empty() {
+        bail!("Stats columns {:?} are not in the table schema", unknown);
+    }
+
+    let value = columns.join(",");
+    let current = table.metadata()?.configuration.get(STATS_COLUMNS_PROPERTY).cloned().flatten();
+    if current.as_deref() == Some(value.as_str()) {
+        return Ok(table);
+    }
+    log::info!("Setting {}={} on {}", STATS_COLUMNS_PROPERTY, value, table.table_uri());
+    DeltaOps(table)
+        .set_tbl_properties()
+        .with_properties(HashMap::from([(STATS_COLUMNS_PROPERTY.to_string(), value)]))
+        .await
+        .context("Failed to set stats columns")
+}
+
+/// Which columns the active files of the loaded version have statistics for
+pub fn stats_coverage(table: &DeltaTable) -> Result<StatsCoverage> {
+    let metadata = table.metadata()?;
+    let schema = table.get_schema()?;
+    let property = |key: &str| metadata.configuration.get(key).cloned().flatten();
+    let columns: Vec<String> = schema
+        .fields()
+        .map(|f| f.name().clone())
+        .filter(|name| !metadata.partition_columns.contains(name))
+        .collect();
+    let stats_columns = match property(STATS_COLUMNS_PROPERTY) {
+        Some(listed) => listed.split(',').map(|c| c.trim().to_string()).filter(|c| !c.is_empty()).collect(),
+        None => {
+            let indexed = property(NUM_INDEXED_COLS_PROPERTY)
+                .and_then(|n| n.parse::<i64>().ok())
+                .map_or(DEFAULT_NUM_INDEXED_COLS, |n| if n < 0 { usize::MAX } else { n as usize });
+            columns.iter().take(indexed).cloned().collect()
+        }
+    };
+
+    let mut files = Vec::new();
+    for add in table.snapshot()?.file_actions()? {
+        let stats = add.get_stats().ok().flatten();
+        let present = |values: Option<Vec<&String>>| {
+            let values = values.unwrap_or_default();
+            columns.iter().filter(|c| values.contains(c)).cloned().collect()
+        };
+        files.push(FileStats {
+            path: add.path.clone(),
+            num_records: stats.as_ref().map(|s| s.num_records),
+            min_max: present(stats.as_ref().map(|s| s.min_values.keys().collect())),
+            null_count: present(stats.as_ref().map(|s| s.null_count.keys().collect())),
+        });
+    }
+
+    let coverage = columns
+        .iter()
+        .map(|column| ColumnCoverage {
+            column: column.clone(),
+            files_with_min_max: files.iter().filter(|f| f.min_max.contains(column)).count(),
+            files_with_null_count: files.iter().filter(|f| f.null_count.contains(column)).count(),
+        })
+        .collect();
+
+    Ok(StatsCoverage {
+        table_uri: table.table_uri(),
+        version: table.version(),
+        stats_columns,
+        num_files: files.len(),
+        files_without_stats: files.iter().filter(|f| f.num_records.is_none()).count(),
+        columns: coverage,
+        files,
+    })
+}
+
+/// Re-compute statistics for active files missing them on a stats column,
+/// e.g. files written before the column was indexed, from the row group
+/// statistics in their Parquet footers. Each file is removed and re-added
+/// as it is with the new statistics and `dataChange` false, so no data is
+/// rewritten, streaming readers skip the commit, and a concurrent commit
+/// removing one of the files (e.g. compaction) makes it conflict instead of
+/// bringing the file back.
+pub async fn restat(table: &mut DeltaTable, dry_run: bool) -> Result<RestatReport> {
+    let coverage = stats_coverage(table)?;
+    let indexed: Vec<&String> = coverage
+        .stats_columns
+        .iter()
+        .filter(|c| coverage.columns.iter().any(|column| &column.column == *c))
+        .collect();
+    let lacking = |file: &FileStats| {
+        file.num_records.is_none()
+            || indexed.iter().any(|c| !file.min_max.contains(c) || !file.null_count.contains(c))
+    };
+    let missing: Vec<&FileStats> = coverage.files.iter().filter(|file| lacking(file)).collect();
+    let mut report = RestatReport { dry_run, files_missing_stats: missing.len(), ..Default::default() };
+    if missing.is_empty() {
+        return Ok(report);
+    }
+
+    let adds: HashMap<String, Add> = table
+        .snapshot()?
+        .file_actions()?
+        .into_iter()
+        .map(|add| (add.path.clone(), add))
+        .collect();
+    let store = table.object_store();
+    let indexed = &indexed;
+    let restated: Vec<(Add, FileStats)> = stream::iter(missing)
+        .map(|file| {
+            let store = store.clone();
+            let add = adds[&file.path].clone();
+            async move {
+                let location = deltalake::Path::from_url_path(&add.path)?;
+                let meta = store.head(&location).await.with_context(|| format!("Failed to read {}", add.path))?;
+                let footer = read_footer(&store, &meta).await?;
+                let schema = parquet_to_arrow_schema(
+                    footer.file_metadata().schema_descr(),
+                    footer.file_metadata().key_value_metadata(),
+                )
+                .with_context(|| format!("Failed to read the schema of {}", add.path))?;
+                let existing = add.stats.as_deref().and_then(|stats| serde_json::from_str(stats).ok());
+                let stats = merge_stats(existing, file_stats(&footer, &schema), indexed);
+                let add = Add { stats: Some(stats.to_string()), data_change: false, ..add };
+                let recomputed = FileStats {
+                    path: add.path.clone(),
+                    num_records: stats["numRecords"].as_i64(),
+                    min_max: keys(&stats["minValues"]),
+                    null_count: keys(&stats["nullCount"]),
+                };
+                Ok::<_, anyhow::Error>((add, recomputed))
+            }
+        })
+        .buffered(RESTAT_CONCURRENCY)
+        .try_collect()
+        .await?;
+
+    let mut improved = Vec::new();
+    for (add, recomputed) in restated {
+        let before = coverage.files.iter().find(|file| file.path == add.path);
+        let gained = before.is_none_or(|before| {
+            before.num_records.is_none()
+                || recomputed.min_max.len() > before.min_max.len()
+                || recomputed.null_count.len() > before.null_count.len()
+        });
+        if lacking(&recomputed) {
+            report.files_unresolved.push(add.path.clone());
+        }
+        if gained {
+            improved.push(add);
+        }
+    }
+    report.files_restated = improved.len();
+    if dry_run || improved.is_empty() {
+        return Ok(report);
+    }
+
+    // delta-rs has no operation of its own for this; like OPTIMIZE, the
+    // commit swaps files for identical data, and `restatFiles` tells them apart
+    let operation = DeltaOperation::Optimize { predicate: None, target_size: 0 };
+    let commit_properties =
+        CommitProperties::default().with_metadata(vec![("restatFiles".to_string(), json!(improved.len()))]);
+    let now = chrono::Utc::now().timestamp_millis();
+    let actions = improved
+        .into_iter()
+        .flat_map(|add| {
+            let remove = Remove {
+                path: add.path.clone(),
+                data_change: false,
+                deletion_timestamp: Some(now),
+                extended_file_metadata: Some(true),
+                partition_values: Some(add.partition_values.clone()),
+                size: Some(add.size),
+                tags: add.tags.clone(),
+                ..Default::default()
+            };
+            [Action::Remove(remove), Action::Add(add)]
+        })
+        .collect();
+    let commit = CommitBuilder::from(commit_properties)
+        .with_actions(actions)
+        .build(Some(table.snapshot()?), table.log_store(), operation)
+        .await
+        .context("Failed to commit re-computed statistics")?;
+    table.update_incremental(Some(commit.version())).await
+        .context("Failed to load committed version")?;
+    log::info!("Re-committed statistics of {} files of {}", report.files_restated, table.table_uri());
+    report.version = Some(commit.version());
+    Ok(report)
+}
+
+/// `recomputed` statistics of the `indexed` columns over the `existing`
+/// ones, which are kept for every other column
+fn merge_stats(existing: Option<Value>, mut recomputed: Value, indexed: &[&String]) -> Value {
+    let existing = existing.unwrap_or(Value::Null);
+    for section in ["minValues", "maxValues", "nullCount"] {
+        let Some(values) = recomputed[section].as_object_mut() else {
+            continue;
+        };
+        values.retain(|column, _| indexed.contains(&column));
+        for (column, value) in existing[section].as_object().into_iter().flatten() {
+            values.entry(column.clone()).or_insert_with(|| value.clone());
+        }
+    }
+    recomputed
+}
+
+fn keys(section: &Value) -> Vec<String> {
+    section.as_object().map(|values| values.keys().cloned().collect()).unwrap_or_default()
+}
@@ -0,0 +1,191 @@
+use deltalake::StorageOptions;
+use std::collections::HashMap;
+use crate::config::{AzureConfig, AzureCredential};
+
+/// Standard AWS environment variables passed through to the object store.
+/// Credentials not set here are resolved by the AWS provider chain: shared
+/// profile (`AWS_PROFILE`), web identity/IRSA, then IMDS.
+const AWS_ENV_KEYS: &[&str] = &[
+    "AWS_ACCESS_KEY_ID",
+    "AWS_SECRET_ACCESS_KEY",
+    "AWS_SESSION_TOKEN",
+    "AWS_REGION",
+    "AWS_PROFILE",
+    "AWS_ENDPOINT_URL",
+    "AWS_ALLOW_HTTP",
+    "AWS_S3_ALLOW_UNSAFE_RENAME",
+    "AWS_WEB_IDENTITY_TOKEN_FILE",
+    "AWS_ROLE_ARN",
+    "AWS_ROLE_SESSION_NAME",
+];
+
+/// Google credentials passed through to the object store. Without any of
+/// them Application Default Credentials are used: the gcloud credentials
+/// file, then the GCE/GKE metadata server.
+const GCS_ENV_KEYS: &[&str] = &[
+    "GOOGLE_SERVICE_ACCOUNT",
+    "GOOGLE_SERVICE_ACCOUNT_PATH",
+    "GOOGLE_SERVICE_ACCOUNT_KEY",
+    "GOOGLE_APPLICATION_CREDENTIALS",
+];
+
+/// Azure environment variables passed through to the object store. Without
+/// a key, SAS token, service principal, workload identity or Azure CLI login,
+/// the host's managed identity is used.
+const AZURE_ENV_KEYS: &[&str] = &[
+    "AZURE_STORAGE_ACCOUNT_NAME",
+    "AZURE_STORAGE_ACCOUNT_KEY",
+    "AZURE_STORAGE_SAS_KEY",
+    "AZURE_STORAGE_ENDPOINT",
+    "AZURE_STORAGE_USE_EMULATOR",
+    "AZURE_TENANT_ID",
+    "AZURE_CLIENT_ID",
+    "AZURE_CLIENT_SECRET",
+    "AZURE_FEDERATED_TOKEN_FILE",
+    "AZURE_MSI_ENDPOINT",
+    "AZURE_USE_AZURE_CLI",
+];
+
+/// Options selecting an Azure credential; a credential from the config file
+/// replaces any of them picked up from the environment
+const AZURE_CREDENTIAL_KEYS: &[&str] = &[
+    "AZURE_STORAGE_ACCOUNT_KEY",
+    "AZURE_STORAGE_SAS_KEY",
+    "AZURE_TENANT_ID",
+    "AZURE_CLIENT_ID",
+    "AZURE_CLIENT_SECRET",
+    "AZURE_FEDERATED_TOKEN_FILE",
+    "AZURE_MSI_ENDPOINT",
+    "AZURE_USE_AZURE_CLI",
+];
+
+/// Option prefixes that only mean something to a cloud backend
+const CLOUD_OPTION_PREFIXES: &[&str] = &["AWS_", "GOOGLE_", "AZURE_"];
+
+/// Object store a table lives on, from the scheme of its URI
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageBackend {
+    /// `s3://` and `s3a://`, including S3-compatible stores such as MinIO
+    S3,
+    /// `gs://`
+    Gcs,
+    /// `abfss://`, `abfs://` and `az://` on Azure Data Lake Storage Gen2
+    Azure,
+    /// Local paths, `file:///` and anything else delta-rs handles natively;
+    /// needs no storage options
+    Local,
+}
+
+impl StorageBackend {
+    pub fn from_uri(uri: &str) -> Self {
+        match uri.split_once("://").map(|(scheme, _)| scheme.to_ascii_lowercase()).as_deref() {
+            Some("s3" | "s3a") => Self::S3,
+            Some("gs") => Self::Gcs,
+            Some("abfss" | "abfs" | "az" | "adl" | "azure") => Self::Azure,
+            _ => Self::Local,
+        }
+    }
+
+    /// Environment variables carrying this backend's credentials and settings
+    fn env_keys(self) -> &'static [&'static str] {
+        match self {
+            Self::S3 => AWS_ENV_KEYS,
+            Self::Gcs => GCS_ENV_KEYS,
+            Self::Azure => AZURE_ENV_KEYS,
+            Self::Local => &[],
+        }
+    }
+}
+
+/// Make `s3://` (with the DynamoDB log store), `gs://` and Azure URIs resolvable by delta-rs
+pub fn register_handlers() {
+    deltalake::aws::register_handlers(None);
+    deltalake::gcp::register_handlers(None);
+    deltalake::azure::register_handlers(None);
+}
+
+/// Storage options for `table_uri` from its backend's environment, with
+/// `overrides` (from the config file) taking precedence
+pub fn resolve_storage_options(table_uri: &str, overrides: &HashMap<String, String>) -> StorageOptions {
+    let backend = StorageBackend::from_uri(table_uri);
+    let mut options: HashMap<String, String> = backend
+        .env_keys()
+        .iter()
+        .filter_map(|key| std::env::var(key).ok().map(|value| (key.to_string(), value)))
+        .collect();
+    if backend == StorageBackend::S3 {
+        if let (None, Ok(region)) = (options.get("AWS_REGION"), std::env::var("AWS_DEFAULT_REGION")) {
+            options.insert("AWS_REGION".to_string(), region);
+        }
+    }
+    options.extend(overrides.iter().map(|(k, v)| (k.clone(), v.clone())));
+
+    // Shared config often carries cloud credentials a local table has no use for
+    if backend == StorageBackend::Local {
+        options.retain(|key, _| {
+            let cloud = CLOUD_OPTION_PREFIXES.iter().any(|p| key.to_ascii_uppercase().starts_with(p));
+            if cloud {
+                log::debug!("Ignoring storage option {} for local table {}", key, table_uri);
+            }
+            !cloud
+        });
+    }
+
+    // A plain-http endpoint (MinIO, LocalStack) is refused unless explicitly
+    // allowed; credentials would travel unencrypted, so it is not assumed
+    if backend == StorageBackend::S3
+        && options.get("AWS_ENDPOINT_URL").is_some_and(|url| url.starts_with("http://"))
+        && !options.get("AWS_ALLOW_HTTP").is_some_and(|allow| allow.eq_ignore_ascii_case("true"))
+    {
+        log::warn!(
+            "S3 endpoint {} for {} is plain HTTP; set AWS_ALLOW_HTTP=true to use it",
+            options["AWS_ENDPOINT_URL"],
+            table_uri
+        );
+    }
+
+    StorageOptions(options)
+}
+
+impl AzureConfig {
+    /// Add the configured account and credential to a set of storage options
+    pub fn apply_to(&self, storage_options: &mut HashMap<String, String>) {
+        if self.credential.is_some() {
+            for key in AZURE_CREDENTIAL_KEYS {
+                storage_options.remove(*key);
+            }
+        }
+        let mut set = |key: &str, value: &str| {
+            storage_options.insert(key.to_string(), value.to_string());
+        };
+
+        if let Some(account) = &self.account_name {
+            set("AZURE_STORAGE_ACCOUNT_NAME", account);
+        }
+        if let Some(endpoint) = &self.endpoint {
+            set("AZURE_STORAGE_ENDPOINT", endpoint);
+        }
+        if self.use_emulator {
+            set("AZURE_STORAGE_USE_EMULATOR", "true");
+        }
+
+        match &self.credential {
+            None => {}
+            Some(AzureCredential::AccountKey { key }) => set("AZURE_STORAGE_ACCOUNT_KEY", key),
+            Some(AzureCredential::Sas { token }) => set("AZURE_STORAGE_SAS_KEY", token.trim_start_matches('?')),
+            Some(AzureCredential::ServicePrincipal { tenant_id, client_id, client_secret }) => {
+                set("AZURE_TENANT_ID", tenant_id);
+                set("AZURE_CLIENT_ID", client_id);
+                set("AZURE_CLIENT_SECRET", client_secret);
+            }
+            Some(AzureCredential::ManagedIdentity { client_id, msi_endpoint }) => {
+                if let Some(client_id) = client_id {
+                    set("AZURE_CLIENT_ID", client_id);
+                }
+                if let Some(endpoint) = msi_endpoint {
+                    set("AZURE_MSI_ENDPOINT", endpoint);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use anyhow::{Context, Result};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::runtime::Tokio;
+use opentelemetry_sdk::trace::{Tracer, TracerProvider};
+use opentelemetry_sdk::Resource;
+use tracing::Subscriber;
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::registry::LookupSpan;
+
+/// Service name spans are reported under
+const SERVICE_NAME: &str = "surgical-strike-writer";
+
+/// Batches tracing spans (write_batch and its arrow_convert, parquet_encode,
+/// upload and commit phases; compaction; vacuum) to an OTLP collector over
+/// gRPC. Spans still buffered are flushed when the exporter is dropped.
+pub struct OtlpExporter {
+    provider: TracerProvider,
+}
+
+impl OtlpExporter {
+    /// Export to `endpoint`, e.g. `http://localhost:4317`
+    pub fn new(endpoint: &str) -> Result<Self> {
+        let exporter = SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint(endpoint)
+            .build()
+            .with_context(|| format!("Failed to create OTLP exporter for {}", endpoint))?;
+        let provider = TracerProvider::builder()
+            .with_batch_exporter(exporter, Tokio)
+            .with_resource(Resource::new(vec![KeyValue::new("service.name", SERVICE_NAME)]))
+            .build();
+        Ok(Self { provider })
+    }
+
+    /// Layer forwarding the spans of a subscriber to the collector
+    pub fn layer<S>(&self) -> OpenTelemetryLayer<S, Tracer>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        tracing_opentelemetry::layer().with_tracer(self.provider.tracer(env!("CARGO_PKG_NAME")))
+    }
+}
+
+impl Drop for OtlpExporter {
+    fn drop(&mut self) {
+        if let Err(e) = self.provider.shutdown() {
+            log::warn!("Failed to flush spans to the OTLP collector: {}", e);
+        }
+    }
+}
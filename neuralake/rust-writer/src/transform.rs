@@ -0,0 +1,111 @@
+use anyhow::{bail, Context, Result};
+use deltalake::kernel::{DataType as DeltaDataType, PrimitiveType};
+use polars::prelude::*;
+use polars::sql::sql_expr;
+use std::collections::HashMap;
+use crate::config::{EventTimePartitioning, Transform};
+use crate::enrichment::LookupTable;
+use crate::schema::parse_delta_type;
+
+/// Apply `transforms` to `df` in order, enriching from `lookups`
+pub fn apply_transforms(
+    mut df: DataFrame,
+    transforms: &[Transform],
+    lookups: &HashMap<String, LookupTable>,
+) -> Result<DataFrame> {
+    for transform in transforms {
+        df = apply(df, transform, lookups).with_context(|| format!("Failed to apply transform {:?}", transform))?;
+    }
+    Ok(df)
+}
+
+fn apply(mut df: DataFrame, transform: &Transform, lookups: &HashMap<String, LookupTable>) -> Result<DataFrame> {
+    match transform {
+        Transform::Rename { from, to } => {
+            df.rename(from, to.as_str().into())?;
+        }
+        Transform::Cast { column, data_type } => {
+            let data_type = polars_type(data_type)?;
+            let cast = df.column(column)?.cast(&data_type)?;
+            df.with_column(cast)?;
+        }
+        Transform::Derive { column, expr } => {
+            let expr = sql_expr(expr).with_context(|| format!("Invalid expression for {}", column))?;
+            df = df.lazy().with_column(expr.alias(column.as_str())).collect()?;
+        }
+        Transform::Drop { columns } => {
+            if let Some(missing) = columns.iter().find(|c| df.column(c).is_err()) {
+                bail!("Batch has no column {} to drop", missing);
+            }
+            df = df.drop_many(columns.iter().map(String::as_str));
+        }
+        Transform::Enrich { lookup, on } => {
+            let lookup = lookups.get(lookup).with_context(|| format!("Lookup {} is not loaded", lookup))?;
+            let key_type = df.column(on).with_context(|| format!("Batch has no column {} to enrich on", on))?.dtype().clone();
+            let replaced: Vec<PlSmallStr> = lookup
+                .rows
+                .get_column_names()
+                .into_iter()
+                .filter(|name| name.as_str() != lookup.key_column && name.as_str() != on && df.column(name.as_str()).is_ok())
+                .cloned()
+                .collect();
+            let rows = lookup
+                .rows
+                .as_ref()
+                .clone()
+                .lazy()
+                .with_column(col(lookup.key_column.as_str()).cast(key_type));
+            df = df
+                .drop_many(replaced)
+                .lazy()
+                .join(rows, [col(on.as_str())], [col(lookup.key_column.as_str())], JoinArgs::new(JoinType::Left))
+                .collect()?;
+        }
+    }
+    Ok(df)
+}
+
+/// Add the partition columns derived from the event time of each row,
+/// replacing any the batch already has. Rows without an event time get a
+/// null partition value.
+pub fn derive_partitions(df: DataFrame, config: &EventTimePartitioning) -> Result<DataFrame> {
+    let column = config.event_time_column.as_str();
+    let data_type = df
+        .column(column)
+        .with_context(|| format!("Batch has no event-time column {}", column))?
+        .dtype();
+    if !matches!(data_type, DataType::Date | DataType::Datetime(..)) {
+        bail!("Event-time column {} is {}, not a date or timestamp", column, data_type);
+    }
+    let derived: Vec<Expr> = config
+        .partitions
+        .iter()
+        .map(|partition| col(column).dt().strftime(&partition.format).alias(partition.column.as_str()))
+        .collect();
+    df.lazy()
+        .with_columns(derived)
+        .collect()
+        .context("Failed to derive partition values from the event time")
+}
+
+/// Polars type a column is cast to for a Delta type name. Timestamps are
+/// cast without a time zone; the write path converts them to the table type.
+pub fn polars_type(name: &str) -> Result<DataType> {
+    let DeltaDataType::Primitive(primitive) = parse_delta_type(name)? else {
+        bail!("Cannot cast to '{}'", name);
+    };
+    Ok(match primitive {
+        PrimitiveType::String => DataType::String,
+        PrimitiveType::Long => DataType::Int64,
+        PrimitiveType::Integer => DataType::Int32,
+        PrimitiveType::Short => DataType::Int16,
+        PrimitiveType::Byte => DataType::Int8,
+        PrimitiveType::Float => DataType::Float32,
+        PrimitiveType::Double => DataType::Float64,
+        PrimitiveType::Boolean => DataType::Boolean,
+        PrimitiveType::Binary => DataType::Binary,
+        PrimitiveType::Date => DataType::Date,
+        PrimitiveType::Timestamp | PrimitiveType::TimestampNtz => DataType::Datetime(TimeUnit::Microseconds, None),
+        PrimitiveType::Decimal(_) => bail!("Casting to decimal is not supported, cast to double instead"),
+    })
+}
@@ -0,0 +1,248 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use deltalake::arrow::array::{Array, AsArray, RecordBatch};
+use deltalake::arrow::compute::cast;
+use deltalake::arrow::datatypes::DataType as ArrowDataType;
+use deltalake::datafusion::prelude::SessionContext;
+use deltalake::delta_datafusion::{DeltaScanConfig, DeltaTableProvider};
+use deltalake::kernel::DataType as DeltaDataType;
+use deltalake::DeltaTable;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::fmt;
+use std::sync::Arc;
+
+/// Widest decimal Delta supports
+const MAX_DECIMAL_PRECISION: usize = 38;
+
+/// Timestamp layouts recognized besides RFC 3339
+const TIMESTAMP_FORMATS: &[&str] = &["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"];
+
+/// Tighter type every sampled value of a string column parses as
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SuggestedType {
+    Boolean,
+    /// Integers are suggested as 64-bit, so later rows do not overflow
+    Long,
+    Decimal { precision: usize, scale: usize },
+    Double,
+    Date,
+    Timestamp,
+}
+
+impl fmt::Display for SuggestedType {
+    /// Type names as accepted by `[[schema.columns]]`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Boolean => write!(f, "boolean"),
+            Self::Long => write!(f, "long"),
+            Self::Decimal { precision, scale } => write!(f, "decimal({},{})", precision, scale),
+            Self::Double => write!(f, "double"),
+            Self::Date => write!(f, "date"),
+            Self::Timestamp => write!(f, "timestamp"),
+        }
+    }
+}
+
+/// A string column that could be stored with a tighter type
+#[derive(Debug, Clone, Serialize)]
+pub struct TypeSuggestion {
+    pub column: String,
+    pub suggested: SuggestedType,
+    /// Non-empty values the suggestion is based on
+    pub values_sampled: usize,
+}
+
+/// Type suggestions for the string columns of a table
+#[derive(Debug, Clone, Serialize)]
+pub struct TypeAnalysis {
+    pub table_uri: String,
+    pub version: i64,
+    pub rows_sampled: usize,
+    pub suggestions: Vec<TypeSuggestion>,
+}
+
+impl fmt::Display for TypeAnalysis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Sampled {} recent rows of version {}", self.rows_sampled, self.version)?;
+        if self.suggestions.is_empty() {
+            return writeln!(f, "No string column parses as a tighter type");
+        }
+        writeln!(f, "{:<32}  {:<16}  {:>8}", "COLUMN", "SUGGESTED", "VALUES")?;
+        for s in &self.suggestions {
+            writeln!(f, "{:<32}  {:<16}  {:>8}", s.column, s.suggested.to_string(), s.values_sampled)?;
+        }
+        Ok(())
+    }
+}
+
+/// Sample up to `sample_rows` rows from the most recently added files and
+/// suggest a type for each string column whose values all parse as one
+pub async fn suggest_types(table: &DeltaTable, sample_rows: usize) -> Result<TypeAnalysis> {
+    let string_columns: Vec<String> = table
+        .snapshot()?
+        .schema()
+        .fields()
+        .filter(|field| field.data_type() == &DeltaDataType::STRING)
+        .map(|field| field.name().clone())
+        .collect();
+
+    let batches = sample_recent(table, sample_rows).await?;
+    let rows_sampled = batches.iter().map(|b| b.num_rows()).sum();
+
+    let mut suggestions = Vec::new();
+    for column in string_columns {
+        let mut values = Vec::new();
+        for batch in &batches {
+            let Some(array) = batch.column_by_name(&column) else { continue };
+            let array = cast(array, &ArrowDataType::Utf8)?;
+            values.extend(array.as_string::<i32>().iter().flatten().map(str::to_string));
+        }
+        let values: Vec<&str> = values.iter().map(|v| v.trim()).filter(|v| !v.is_empty()).collect();
+        if let Some(suggested) = infer_type(values.iter().copied()) {
+            suggestions.push(TypeSuggestion { column, suggested, values_sampled: values.len() });
+        }
+    }
+
+    Ok(TypeAnalysis { table_uri: table.table_uri(), version: table.version(), rows_sampled, suggestions })
+}
+
+/// Tightest type all `values` parse as, or None if some are free text or
+/// there is nothing to go on. Numbers with leading zeros (ids, zip codes)
+/// stay strings.
+pub fn infer_type<'a>(values: impl IntoIterator<Item = &'a str>) -> Option<SuggestedType> {
+    let (mut boolean, mut integer, mut decimal, mut double, mut date, mut timestamp) =
+        (true, true, true, true, true, true);
+    let (mut integer_digits, mut scale) = (0, 0);
+    let mut seen = false;
+
+    for value in values {
+        seen = true;
+        boolean &= value.eq_ignore_ascii_case("true") || value.eq_ignore_ascii_case("false");
+        match decimal_digits(value) {
+            Some((int_digits, frac_digits)) => {
+                integer_digits = integer_digits.max(int_digits);
+                scale = scale.max(frac_digits);
+                integer &= frac_digits == 0 && value.parse::<i64>().is_ok();
+            }
+            None => {
+                integer = false;
+                decimal = false;
+            }
+        }
+        double &= !has_leading_zero(value) && value.parse::<f64>().is_ok_and(f64::is_finite);
+        date &= NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok();
+        timestamp &= DateTime::parse_from_rfc3339(value).is_ok()
+            || TIMESTAMP_FORMATS.iter().any(|format| NaiveDateTime::parse_from_str(value, format).is_ok());
+    }
+
+    if !seen {
+        return None;
+    }
+    if boolean {
+        Some(SuggestedType::Boolean)
+    } else if integer {
+        Some(SuggestedType::Long)
+    } else if decimal && integer_digits + scale <= MAX_DECIMAL_PRECISION {
+        Some(SuggestedType::Decimal { precision: integer_digits + scale, scale })
+    } else if double {
+        Some(SuggestedType::Double)
+    } else if date {
+        Some(SuggestedType::Date)
+    } else if timestamp {
+        Some(SuggestedType::Timestamp)
+    } else {
+        None
+    }
+}
+
+/// Digits before and after the point of a plain decimal literal
+fn decimal_digits(value: &str) -> Option<(usize, usize)> {
+    if has_leading_zero(value) {
+        return None;
+    }
+    let unsigned = value.strip_prefix(['-', '+']).unwrap_or(value);
+    let (int, frac) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+    let digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    (!int.is_empty() && digits(int) && digits(frac)).then_some((int.len(), frac.len()))
+}
+
+fn has_leading_zero(value: &str) -> bool {
+    let unsigned = value.strip_prefix(['-', '+']).unwrap_or(value);
+    unsigned.len() > 1 && unsigned.starts_with('0') && !unsigned.starts_with("0.")
+}
+
+/// Statements turning each suggested column into its typed form: add a
+/// typed column, backfill it, then swap it in under the original name. The
+/// swap renames columns, which needs column mapping enabled on the table.
+pub fn migration_plan(analysis: &TypeAnalysis) -> Vec<String> {
+    if analysis.suggestions.is_empty() {
+        return Vec::new();
+    }
+    let table = format!("delta.`{}`", analysis.table_uri);
+    let typed = |column: &str| quote(&format!("{}__typed", column));
+
+    let added = analysis
+        .suggestions
+        .iter()
+        .map(|s| format!("{} {}", typed(&s.column), s.suggested.to_string().to_uppercase()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let backfill = analysis
+        .suggestions
+        .iter()
+        .map(|s| {
+            format!(
+                "{} = CAST(NULLIF(TRIM({}), '') AS {})",
+                typed(&s.column),
+                quote(&s.column),
+                s.suggested.to_string().to_uppercase()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let mut plan = vec![
+        format!("ALTER TABLE {} ADD COLUMNS ({});", table, added),
+        format!("UPDATE {} SET {};", table, backfill),
+        format!(
+            "ALTER TABLE {} SET TBLPROPERTIES ('delta.columnMapping.mode' = 'name', \
+             'delta.minReaderVersion' = '2', 'delta.minWriterVersion' = '5');",
+            table
+        ),
+    ];
+    for s in &analysis.suggestions {
+        plan.push(format!("ALTER TABLE {} DROP COLUMN {};", table, quote(&s.column)));
+        plan.push(format!("ALTER TABLE {} RENAME COLUMN {} TO {};", table, typed(&s.column), quote(&s.column)));
+    }
+    plan
+}
+
+fn quote(identifier: &str) -> String {
+    format!("`{}`", identifier.replace('`', "``"))
+}
+
+/// Rows of the newest files, enough to cover `limit` rows by their stats
+async fn sample_recent(table: &DeltaTable, limit: usize) -> Result<Vec<RecordBatch>> {
+    let mut files = table.snapshot()?.file_actions()?;
+    files.sort_by_key(|add| Reverse(add.modification_time));
+    let mut covered = 0;
+    let newest: Vec<_> = files
+        .into_iter()
+        .take_while(|add| {
+            let take = covered < limit;
+            covered += add.get_stats().ok().flatten().map_or(0, |s| s.num_records as usize);
+            take
+        })
+        .collect();
+
+    let provider = DeltaTableProvider::try_new(table.snapshot()?.clone(), table.log_store(), DeltaScanConfig::default())?
+        .with_files(newest);
+    SessionContext::new()
+        .read_table(Arc::new(provider))?
+        .limit(0, Some(limit))?
+        .collect()
+        .await
+        .context("Failed to sample recent rows")
+}
@@ -1,37 +1,113 @@
 use anyhow::{Context, Result};
-use deltalake::DeltaTable;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use deltalake::{DeltaOps, DeltaTable, Path};
+use std::fmt;
+use std::future::IntoFuture;
 use std::sync::Arc;
-use tokio::sync::Mutex;
-use tokio::time::{interval, Instant};
-use crate::config::VacuumConfig;
+use tokio::sync::{broadcast, Mutex};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+use crate::alerts::Alerter;
+use crate::compat;
+use crate::config::{AlertKind, VacuumConfig};
+use crate::events::{EventBus, OrchestratorEvent};
+use crate::inventory::{FileInventory, InventoryCache};
+use crate::maintenance::{Maintenance, MaintenanceControl};
+use crate::metrics::MetricsRegistry;
+use crate::schedule::Ticker;
+use crate::partition_gc::{remove_empty_partitions, PartitionCleanup};
 
 /// The Vacuum process - cleans up stale files beyond retention period
 #[derive(Debug, Clone)]
 pub struct VacuumProcess {
     config: VacuumConfig,
+    metrics: Arc<MetricsRegistry>,
+    alerts: Arc<Alerter>,
+    /// File listing shared with verify runs
+    inventory: Arc<InventoryCache>,
+    control: Arc<MaintenanceControl>,
+    events: EventBus,
 }
 
 impl VacuumProcess {
     /// Create a new vacuum process
     pub fn new(config: VacuumConfig) -> Self {
-        Self { config }
+        let inventory = Arc::new(InventoryCache::new(config.inventory_ttl(), config.list_concurrency));
+        Self {
+            config,
+            metrics: Arc::default(),
+            alerts: Arc::default(),
+            inventory,
+            control: Arc::default(),
+            events: EventBus::default(),
+        }
+    }
+
+    /// The file inventory vacuum lists, for reuse by verify
+    pub fn inventory(&self) -> Arc<InventoryCache> {
+        self.inventory.clone()
+    }
+
+    /// Record into a registry shared with the other processes of the table
+    pub fn with_metrics(mut self, metrics: Arc<MetricsRegistry>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Raise cycles overrunning their interval through `alerts`
+    pub fn with_alerts(mut self, alerts: Arc<Alerter>) -> Self {
+        self.alerts = alerts;
+        self
     }
 
-    /// Main run loop for the vacuum process
-    pub async fn run(&self, table: Arc<Mutex<DeltaTable>>) -> Result<()> {
+    /// Publish a `VacuumCompleted` event for every cycle on `events`
+    pub fn with_events(mut self, events: EventBus) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Skip scheduled cycles while `control` has vacuum paused
+    pub fn with_control(mut self, control: Arc<MaintenanceControl>) -> Self {
+        self.control = control;
+        self
+    }
+
+    /// Main run loop for the vacuum process. A cycle in progress when shutdown
+    /// is requested is abandoned: vacuum only deletes unreferenced files, so
+    /// an interrupted pass leaves the table consistent and the next pass
+    /// picks up the remainder.
+    pub async fn run(&self, table: Arc<Mutex<DeltaTable>>, shutdown: CancellationToken) -> Result<()> {
         log::info!("Starting Vacuum process");
         
-        let mut interval_timer = interval(self.config.vacuum_interval());
+        let mut ticker = Ticker::new(self.config.vacuum_schedule.as_ref(), self.config.vacuum_interval());
+        let mut events = self.events.subscribe();
         
         loop {
             tokio::select! {
-                _ = interval_timer.tick() => {
-                    if let Err(e) = self.run_vacuum_cycle(&table).await {
+                _ = ticker.tick() => {}
+                _ = compaction_completed(&mut events), if self.config.after_compaction => {
+                    log::debug!("Vacuuming after compaction");
+                }
+                _ = shutdown.cancelled() => {
+                    log::info!("Vacuum process received shutdown signal");
+                    break;
+                }
+            }
+            if self.control.is_paused(Maintenance::Vacuum) {
+                log::debug!("Skipping vacuum cycle: vacuum is paused");
+                self.metrics.record_paused_cycle(Maintenance::Vacuum);
+                continue;
+            }
+            tokio::select! {
+                result = self.run_vacuum_cycle(&table) => {
+                    if let Err(e) = result {
                         log::error!("Vacuum cycle failed: {}", e);
+                        self.metrics.record_error("vacuum", format!("{:#}", e));
                     }
                 }
-                _ = tokio::signal::ctrl_c() => {
-                    log::info!("Vacuum process received shutdown signal");
+                _ = shutdown.cancelled() => {
+                    log::warn!("Aborting vacuum cycle for shutdown");
                     break;
                 }
             }
@@ -53,68 +129,209 @@ impl VacuumProcess {
             self.config.dry_run
         );
         
-        // Get file count before vacuum
-        let files_before = locked_table.get_files_iter()?.count();
-        
         // Run the actual vacuum
-        self.run_once(&mut locked_table).await?;
-        
-        // Get file count after vacuum
-        locked_table.update().await
-            .with_context("Failed to refresh table after vacuum")?;
-        let files_after = locked_table.get_files_iter()?.count();
+        let report = self.run_once(&mut locked_table).await?;
         
         let elapsed = start_time.elapsed();
-        let files_removed = files_before.saturating_sub(files_after);
         
         log::info!(
-            "Vacuum completed in {:?}: {} files removed ({} -> {})",
+            "Vacuum completed in {:?}: {} files {} ({} bytes)",
             elapsed,
-            files_removed,
-            files_before,
-            files_after
+            report.files.len(),
+            if report.dry_run { "eligible for deletion" } else { "removed" },
+            report.total_bytes
         );
+        self.events.publish(OrchestratorEvent::VacuumCompleted {
+            table_uri: locked_table.table_uri(),
+            files: report.files.len(),
+            bytes: report.total_bytes,
+            dry_run: report.dry_run,
+            elapsed_ms: elapsed.as_millis() as u64,
+        });
+        if elapsed > self.config.vacuum_interval() {
+            self.alerts.fire(
+                AlertKind::VacuumOverrun,
+                format!("Vacuum took {:?}, longer than its {:?} interval", elapsed, self.config.vacuum_interval()),
+            );
+        }
         
         Ok(())
     }
 
-    /// Run vacuum once on the given table
-    pub async fn run_once(&self, table: &mut DeltaTable) -> Result<()> {
+    /// Run vacuum once on the given table, returning the files it deleted
+    /// (or, in dry-run mode, the files it would delete)
+    #[tracing::instrument(
+        name = "vacuum",
+        skip_all,
+        fields(table_uri = %table.table_uri(), dry_run = self.config.dry_run)
+    )]
+    pub async fn run_once(&self, table: &mut DeltaTable) -> Result<VacuumReport> {
+        let start_time = Instant::now();
+        
         // Refresh the table to get latest state
         table.update().await
-            .with_context("Failed to refresh table before vacuum")?;
-            
-        // Run the vacuum operation
-        // Note: In delta-rs, vacuum() handles the cleanup logic
-        table.vacuum(
-            Some(self.config.retention_hours),
-            self.config.dry_run,
-            None, // enforce_retention_duration
-        ).await
-        .with_context("Failed to run vacuum operation")?;
-            
-        Ok(())
+            .context("Failed to refresh table before vacuum")?;
+        
+        // Deletion vector files live next to the data and are referenced from
+        // add actions; refuse rather than risk treating them as orphans
+        compat::ensure_no_deletion_vectors(table, "vacuum")?;
+        
+        let retention = ChronoDuration::hours(self.config.retention_hours as i64);
+        let oldest_retained_timestamp = Utc::now() - retention;
+        
+        // Sizes come from the inventory, listed (or reused) before anything
+        // is deleted, rather than a HEAD request per candidate
+        let mut inventory = self
+            .inventory
+            .get(table)
+            .instrument(tracing::info_span!("list_inventory"))
+            .await?;
+        
+        // A real run reports what it deleted, so only dry runs list candidates
+        let candidates = if self.config.dry_run {
+            DeltaOps(table.clone())
+                .vacuum()
+                .with_retention_period(retention)
+                .with_dry_run(true)
+                .into_future()
+                .instrument(tracing::info_span!("list_candidates"))
+                .await
+                .context("Failed to list vacuum candidates")?
+                .1
+                .files_deleted
+        } else {
+            let (updated, metrics) = DeltaOps(table.clone())
+                .vacuum()
+                .with_retention_period(retention)
+                .into_future()
+                .instrument(tracing::info_span!("delete_files"))
+                .await
+                .context("Failed to run vacuum operation")?;
+            *table = updated;
+            metrics.files_deleted
+        };
+        let total_bytes = candidate_bytes(table, &inventory, &candidates).await;
+        
+        if !self.config.dry_run {
+            self.metrics.record_vacuum(candidates.len() as u64, total_bytes, start_time.elapsed());
+            if let Some(remaining) = self.inventory.forget(&candidates).await {
+                inventory = remaining;
+            }
+        }
+
+        // Dry runs only see partitions that are empty already, since the
+        // candidates above are still in place
+        let partitions = if self.config.remove_empty_partitions {
+            remove_empty_partitions(table, &inventory, self.config.dry_run)
+                .instrument(tracing::info_span!("remove_empty_partitions"))
+                .await?
+        } else {
+            PartitionCleanup::default()
+        };
+        if !partitions.partitions_removed.is_empty() {
+            log::info!(
+                "{} empty partitions: {}",
+                if self.config.dry_run { "Would remove" } else { "Removed" },
+                partitions.partitions_removed.join(", ")
+            );
+        }
+        
+        Ok(VacuumReport {
+            dry_run: self.config.dry_run,
+            files: candidates,
+            total_bytes,
+            oldest_retained_timestamp,
+            partitions,
+        })
     }
 
     /// Get metrics about the vacuum performance
     pub fn get_metrics(&self) -> VacuumMetrics {
         VacuumMetrics {
             config: self.config.clone(),
-            // In a real implementation, these would be tracked
-            total_vacuum_runs: 0,
-            total_files_removed: 0,
-            total_bytes_freed: 0,
-            average_vacuum_time_ms: 0.0,
+            total_vacuum_runs: self.metrics.vacuum_runs(),
+            total_files_removed: self.metrics.files_vacuumed(),
+            total_bytes_freed: self.metrics.bytes_vacuumed(),
+            average_vacuum_time_ms: self.metrics.vacuum_time().mean_ms(),
         }
     }
 }
 
+/// Wait for a compaction that removed files. Events missed by lagging
+/// behind count as one, since vacuuming an extra time is harmless.
+async fn compaction_completed(events: &mut broadcast::Receiver<OrchestratorEvent>) {
+    loop {
+        match events.recv().await {
+            Ok(OrchestratorEvent::CompactionCompleted { files_removed, .. }) if files_removed > 0 => return,
+            Ok(_) => {}
+            Err(broadcast::error::RecvError::Lagged(_)) => return,
+            Err(broadcast::error::RecvError::Closed) => std::future::pending().await,
+        }
+    }
+}
+
+/// Bytes of the candidate files, from the inventory where it has them
+async fn candidate_bytes(table: &DeltaTable, inventory: &FileInventory, candidates: &[String]) -> u64 {
+    let store = table.object_store();
+    let mut total_bytes = 0u64;
+    for file in candidates {
+        if let Some(meta) = inventory.get(file) {
+            total_bytes += meta.size as u64;
+            continue;
+        }
+        match store.head(&Path::from(file.as_str())).await {
+            Ok(meta) => total_bytes += meta.size as u64,
+            Err(e) => log::debug!("Could not stat vacuum candidate {}: {}", file, e),
+        }
+    }
+    total_bytes
+}
+
 /// Metrics for the vacuum process
 #[derive(Debug, Clone)]
 pub struct VacuumMetrics {
     pub config: VacuumConfig,
+    /// Passes that deleted files; dry runs are not counted
     pub total_vacuum_runs: u64,
     pub total_files_removed: u64,
     pub total_bytes_freed: u64,
     pub average_vacuum_time_ms: f64,
-} 
\ No newline at end of file
+}
+
+/// Files removed (or, for a dry run, eligible for removal) by a vacuum pass
+#[derive(Debug, Clone)]
+pub struct VacuumReport {
+    pub dry_run: bool,
+    /// Paths relative to the table root
+    pub files: Vec<String>,
+    pub total_bytes: u64,
+    /// Files tombstoned before this instant are past retention; newer ones are kept
+    pub oldest_retained_timestamp: DateTime<Utc>,
+    /// Partitions left without data and the markers removed with them
+    pub partitions: PartitionCleanup,
+}
+
+impl fmt::Display for VacuumReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verb = if self.dry_run { "Would delete" } else { "Deleted" };
+        writeln!(f, "{} {} files ({} bytes)", verb, self.files.len(), self.total_bytes)?;
+        writeln!(f, "Oldest retained timestamp: {}", self.oldest_retained_timestamp.to_rfc3339())?;
+        for file in &self.files {
+            writeln!(f, "  {}", file)?;
+        }
+        if !self.partitions.partitions_removed.is_empty() {
+            writeln!(
+                f,
+                "{} {} empty partitions ({} markers, {} directories)",
+                verb,
+                self.partitions.partitions_removed.len(),
+                self.partitions.markers_removed.len(),
+                self.partitions.directories_removed
+            )?;
+            for partition in &self.partitions.partitions_removed {
+                writeln!(f, "  {}", partition)?;
+            }
+        }
+        Ok(())
+    }
+}
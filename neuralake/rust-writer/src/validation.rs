@@ -0,0 +1,103 @@
+use anyhow::{Context, Result};
+use deltalake::protocol::SaveMode;
+use deltalake::{DeltaOps, StorageOptions};
+use polars::prelude::*;
+use regex::Regex;
+use std::collections::HashSet;
+use crate::config::ValidationRule;
+
+/// String column naming the rule a quarantined row broke
+pub const VIOLATION_COLUMN: &str = "_violation";
+
+/// A rule ready to be checked, with its pattern compiled
+enum Check<'a> {
+    NotNull,
+    Range { min: Option<f64>, max: Option<f64> },
+    Regex(Regex),
+    AllowedValues(HashSet<&'a str>),
+}
+
+/// For each row of `df`, the first rule it breaks, described
+pub fn violations(df: &DataFrame, rules: &[ValidationRule]) -> Result<Vec<Option<String>>> {
+    let mut violations: Vec<Option<String>> = vec![None; df.height()];
+    for rule in rules {
+        let column = rule.column();
+        let values = df
+            .column(column)
+            .with_context(|| format!("Batch has no column {} to validate", column))?;
+        let check = match rule {
+            ValidationRule::NotNull { .. } => Check::NotNull,
+            ValidationRule::Range { min, max, .. } => Check::Range { min: *min, max: *max },
+            ValidationRule::Regex { pattern, .. } => {
+                // Anchored, so the pattern has to match the whole value
+                let anchored = format!("^(?:{})$", pattern);
+                Check::Regex(Regex::new(&anchored).with_context(|| format!("Invalid pattern for {}", column))?)
+            }
+            ValidationRule::AllowedValues { values, .. } => {
+                Check::AllowedValues(values.iter().map(String::as_str).collect())
+            }
+        };
+        let failed = failing(values, &check).with_context(|| format!("Failed to validate {}", column))?;
+        for (row, failed) in failed.into_iter().enumerate() {
+            if failed && violations[row].is_none() {
+                violations[row] = Some(rule.to_string());
+            }
+        }
+    }
+    Ok(violations)
+}
+
+/// Which values break `check`. Only the not-null rule rejects nulls.
+fn failing(values: &Column, check: &Check) -> Result<Vec<bool>> {
+    Ok(match check {
+        Check::NotNull => values.is_null().into_iter().map(|null| null.unwrap_or(false)).collect(),
+        Check::Range { min, max } => values
+            .cast(&DataType::Float64)?
+            .f64()?
+            .into_iter()
+            .map(|v| v.is_some_and(|v| min.is_some_and(|min| v < min) || max.is_some_and(|max| v > max)))
+            .collect(),
+        Check::Regex(regex) => values
+            .str()
+            .context("Pattern rules only apply to string columns")?
+            .into_iter()
+            .map(|v| v.is_some_and(|v| !regex.is_match(v)))
+            .collect(),
+        Check::AllowedValues(allowed) => values
+            .cast(&DataType::String)?
+            .str()?
+            .into_iter()
+            .map(|v| v.is_some_and(|v| !allowed.contains(v)))
+            .collect(),
+    })
+}
+
+/// Split `df` into the rows passing every rule and the rows breaking one,
+/// the latter with a [`VIOLATION_COLUMN`]; `None` when every row passes
+pub fn split_invalid(df: DataFrame, rules: &[ValidationRule]) -> Result<(DataFrame, Option<DataFrame>)> {
+    let violations = violations(&df, rules)?;
+    if violations.iter().all(Option::is_none) {
+        return Ok((df, None));
+    }
+    let mask: BooleanChunked = violations.iter().map(|v| Some(v.is_some())).collect();
+    let valid = df.filter(&!&mask).context("Failed to select valid rows")?;
+    let mut invalid = df.filter(&mask).context("Failed to select invalid rows")?;
+    let reasons: Vec<&str> = violations.iter().flatten().map(String::as_str).collect();
+    invalid
+        .with_column(Series::new(VIOLATION_COLUMN.into(), reasons))
+        .context("Failed to add violation column")?;
+    Ok((valid, Some(invalid)))
+}
+
+/// Append quarantined rows to the quarantine table, creating it on first use
+pub async fn append_quarantined(df: &DataFrame, table_uri: &str, storage_options: &StorageOptions) -> Result<()> {
+    let batch = df.to_arrow(None)
+        .context("Failed to convert quarantined rows to Arrow")?;
+    DeltaOps::try_from_uri_with_storage_options(table_uri, storage_options.0.clone())
+        .await?
+        .write(vec![batch])
+        .with_save_mode(SaveMode::Append)
+        .await
+        .with_context(|| format!("Failed to write quarantined rows to {}", table_uri))?;
+    Ok(())
+}
@@ -0,0 +1,125 @@
+use anyhow::{Context, Result};
+use deltalake::arrow::array::{Array, AsArray, RecordBatch, UInt32Array};
+use deltalake::arrow::compute::{cast, lexsort_to_indices, take_record_batch, SortColumn};
+use deltalake::arrow::datatypes::{DataType, SchemaRef};
+use deltalake::kernel::Add;
+use deltalake::operations::transaction::CommitProperties;
+use deltalake::parquet::file::properties::WriterProperties;
+use deltalake::writer::{DeltaWriter, RecordBatchWriter, WriteMode as DeltaWriteMode};
+use deltalake::DeltaTable;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use tracing::Instrument;
+
+/// Split `batch` into at most `shards` batches for parallel writing. Rows of
+/// one partition always land in the same shard, so each partition's files
+/// come from a single worker; unpartitioned batches are cut into contiguous
+/// slices.
+pub fn shard_batch(batch: &RecordBatch, partition_columns: &[String], shards: usize) -> Result<Vec<RecordBatch>> {
+    let rows = batch.num_rows();
+    if shards <= 1 || rows == 0 {
+        return Ok(vec![batch.clone()]);
+    }
+    if partition_columns.is_empty() {
+        let chunk = rows.div_ceil(shards);
+        return Ok((0..rows)
+            .step_by(chunk)
+            .map(|offset| batch.slice(offset, chunk.min(rows - offset)))
+            .collect());
+    }
+
+    let keys = partition_columns
+        .iter()
+        .map(|column| {
+            let values = batch
+                .column_by_name(column)
+                .with_context(|| format!("Batch has no partition column {}", column))?;
+            cast(values, &DataType::Utf8).context("Failed to read partition values")
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let keys: Vec<_> = keys.iter().map(|key| key.as_string::<i32>()).collect();
+
+    let mut indices = vec![Vec::new(); shards];
+    for row in 0..rows {
+        let mut hasher = DefaultHasher::new();
+        for key in &keys {
+            key.is_valid(row).then(|| key.value(row)).hash(&mut hasher);
+        }
+        indices[(hasher.finish() % shards as u64) as usize].push(row as u32);
+    }
+
+    indices
+        .into_iter()
+        .filter(|rows| !rows.is_empty())
+        .map(|rows| take_record_batch(batch, &UInt32Array::from(rows)).context("Failed to shard batch"))
+        .collect()
+}
+
+/// Sort `batch` by `columns` in order, ascending with nulls first. Runs of
+/// equal values let Parquet's dictionary and run-length encodings compress
+/// the sorted columns far better.
+pub fn sort_batch(batch: &RecordBatch, columns: &[String]) -> Result<RecordBatch> {
+    if columns.is_empty() || batch.num_rows() < 2 {
+        return Ok(batch.clone());
+    }
+    let sort_columns = columns
+        .iter()
+        .map(|column| {
+            let values = batch
+                .column_by_name(column)
+                .with_context(|| format!("Batch has no sort column {}", column))?;
+            Ok(SortColumn { values: values.clone(), options: None })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let indices = lexsort_to_indices(&sort_columns, None).context("Failed to sort batch")?;
+    take_record_batch(batch, &indices).context("Failed to sort batch")
+}
+
+/// Encode and upload each shard as Parquet on its own task, returning the
+/// files to commit and the schema they were written with (the merged schema
+/// with `merge_schema`). Nothing is committed here.
+pub async fn write_shards(
+    table: &DeltaTable,
+    shards: Vec<RecordBatch>,
+    merge_schema: bool,
+    commit_properties: &CommitProperties,
+    writer_properties: &WriterProperties,
+) -> Result<(Vec<Add>, SchemaRef)> {
+    let mut workers = Vec::with_capacity(shards.len());
+    for (shard, batch) in shards.into_iter().enumerate() {
+        // Bound to the opened table, reusing its object store
+        let mut writer = RecordBatchWriter::for_table(table)
+            .context("Failed to create RecordBatchWriter")?
+            .with_commit_properties(commit_properties.clone())
+            .with_writer_properties(writer_properties.clone());
+        let span = tracing::info_span!("write_shard", shard, rows = batch.num_rows());
+        workers.push(tokio::spawn(
+            async move {
+                let mode = if merge_schema { DeltaWriteMode::MergeSchema } else { DeltaWriteMode::Default };
+                writer.write_with_mode(batch, mode)
+                    .instrument(tracing::info_span!("parquet_encode"))
+                    .await
+                    .context("Failed to write batch")?;
+                let schema = writer.arrow_schema();
+                // Finishes the Parquet files and puts them to the object store
+                let adds = writer
+                    .flush()
+                    .instrument(tracing::info_span!("upload"))
+                    .await
+                    .context("Failed to write batch")?;
+                Ok::<_, anyhow::Error>((adds, schema))
+            }
+            .instrument(span),
+        ));
+    }
+
+    let mut adds = Vec::new();
+    let mut schema = None;
+    for worker in workers {
+        let (written, written_schema) = worker.await.context("Write worker panicked")??;
+        adds.extend(written);
+        schema.get_or_insert(written_schema);
+    }
+    let schema = schema.context("No shards to write")?;
+    Ok((adds, schema))
+}
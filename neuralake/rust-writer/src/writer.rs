@@ -1,67 +1,618 @@
-use anyhow::{Context, Result};
-use deltalake::writer::RecordBatchWriter;
+use anyhow::{anyhow, Context, Result};
+use deltalake::arrow::datatypes::{Schema as ArrowSchema, SchemaRef};
+use deltalake::kernel::{Action, Add, Metadata, StructType, Transaction};
+use deltalake::operations::transaction::{CommitBuilder, CommitProperties};
+use deltalake::protocol::{DeltaOperation, SaveMode};
+use deltalake::writer::WriteMode as DeltaWriteMode;
 use deltalake::{DeltaTable, StorageOptions};
-use polars::prelude::DataFrame;
+use polars::prelude::{Column, DataFrame, DataType, PlSmallStr, PolarsResult, UniqueKeepStrategy};
+use chrono::{DateTime, Utc};
+use serde_json::Value;
 use std::sync::Arc;
-use tokio::sync::Mutex;
-use tokio::time::{Duration, Instant, interval};
-use crate::config::WriterConfig;
+use tokio::sync::{mpsc, watch, Mutex, Notify};
+use tokio::task::JoinSet;
+use tokio::time::{Duration, Instant, interval, sleep_until};
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+use crate::alerts::Alerter;
+use crate::checksums;
+use crate::coalescer::CommitCoalescer;
+use crate::guardrails::Guardrails;
+use crate::config::{
+    AlertKind, ChecksumConfig, LatePolicy, ParquetCompression, QuarantineTarget, WriteMode, WriterConfig,
+};
+use crate::correlation::new_batch_id;
+use crate::dead_letter::DeadLetterQueue;
+use crate::enrichment::EnrichmentCache;
+use crate::events::{EventBus, OrchestratorEvent};
+use crate::flush_scheduler::{FlushPermit, FlushScheduler};
+use crate::lateness;
+use crate::merge;
+use crate::metrics::{FileReceipt, MetricsRegistry};
+use crate::retry;
+use crate::rollup;
+use crate::compat;
+use crate::partition_metrics::{self, PartitionWriteTracker, PartitionWrites};
+use crate::replication::Replicator;
+use crate::schema::{align_batch, plan_schema_change, SchemaChange};
+use crate::sinks::{SinkBatch, SinkSender};
+use crate::transform;
+use crate::validation;
+use crate::write_pool;
+
+/// Flushes that may wait on a coalesced commit at once
+const MAX_IN_FLIGHT_FLUSHES: usize = 16;
+/// Written files listed in writer metrics
+const RECENT_FILES_REPORTED: usize = 20;
+
+/// A batch in the writer queue with the id that correlates it across logs,
+/// commit metadata, sinks and receipts
+#[derive(Debug, Clone)]
+pub struct QueuedBatch {
+    pub id: String,
+    pub df: DataFrame,
+}
+
+/// Sending half of the writer queue
+#[derive(Debug, Clone)]
+pub struct BatchSender {
+    sender: mpsc::Sender<QueuedBatch>,
+    replication: Option<Arc<Replicator>>,
+    metrics: Arc<MetricsRegistry>,
+}
+
+impl BatchSender {
+    pub fn new(
+        sender: mpsc::Sender<QueuedBatch>,
+        replication: Option<Arc<Replicator>>,
+        metrics: Arc<MetricsRegistry>,
+    ) -> Self {
+        Self { sender, replication, metrics }
+    }
+
+    /// Queue a batch under a new id, waiting if the queue is full
+    pub async fn send(&self, df: DataFrame) -> Result<String> {
+        self.send_with_id(df, None).await
+    }
+
+    /// Queue a batch under the caller's id, or a generated one, returning the
+    /// id. With replication the batch is only queued once the standby holds
+    /// a copy.
+    pub async fn send_with_id(&self, df: DataFrame, id: Option<String>) -> Result<String> {
+        let id = id.unwrap_or_else(new_batch_id);
+        let bytes = df.estimated_size() as u64;
+        self.metrics.record_enqueued(bytes);
+        let batch = QueuedBatch { id: id.clone(), df };
+        let result = match &self.replication {
+            Some(replication) => replication.queue(batch, &self.sender).await,
+            None => self.sender.send(batch).await.map_err(|_| anyhow!("Writer queue is closed")),
+        };
+        if result.is_err() {
+            self.metrics.record_dequeued(bytes);
+        }
+        result.map(|()| id)
+    }
+
+    /// Batches waiting in the queue for the writer
+    pub fn queued(&self) -> usize {
+        self.sender.max_capacity() - self.sender.capacity()
+    }
+
+    /// Batches the queue holds before senders have to wait
+    pub fn max_queued(&self) -> usize {
+        self.sender.max_capacity()
+    }
+}
+
+/// Operator controls of a running writer, shared with the admin endpoints.
+/// While paused the writer stops taking batches off its queue, so producers
+/// wait once it fills, and sources stop reading.
+#[derive(Debug)]
+pub struct WriterControl {
+    flush: Notify,
+    paused: watch::Sender<bool>,
+}
+
+impl Default for WriterControl {
+    fn default() -> Self {
+        Self { flush: Notify::new(), paused: watch::channel(false).0 }
+    }
+}
+
+impl WriterControl {
+    /// Flush the pending and queued batches now instead of at the next
+    /// size or time trigger
+    pub fn request_flush(&self) {
+        self.flush.notify_one();
+    }
+
+    /// Stop flushing and reading sources; returns false if already paused
+    pub fn pause(&self) -> bool {
+        !self.paused.send_replace(true)
+    }
+
+    /// Undo [`Self::pause`]; returns false if not paused
+    pub fn resume(&self) -> bool {
+        self.paused.send_replace(false)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.paused.borrow()
+    }
+
+    /// Wait until the writer is not paused
+    pub async fn resumed(&self) {
+        let mut paused = self.paused.subscribe();
+        // The sender lives as long as `self`, so this cannot fail
+        let _ = paused.wait_for(|paused| !paused).await;
+    }
+
+    async fn flush_requested(&self) {
+        self.flush.notified().await
+    }
+}
+
+/// Wall-clock window whose rows an aligned flush commits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlushWindow {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl FlushWindow {
+    /// The window of length `period`, aligned to the Unix epoch, containing `at`
+    pub fn containing(at: DateTime<Utc>, period: Duration) -> Self {
+        let period_ms = (period.as_millis() as i64).max(1);
+        let start_ms = at.timestamp_millis() - at.timestamp_millis().rem_euclid(period_ms);
+        let start = DateTime::from_timestamp_millis(start_ms).unwrap_or(at);
+        Self { start, end: start + chrono::Duration::milliseconds(period_ms) }
+    }
+
+    /// Monotonic deadline at which the window closes
+    fn deadline(&self) -> Instant {
+        Instant::now() + (self.end - Utc::now()).to_std().unwrap_or_default()
+    }
+
+    /// Commit info entries identifying the window
+    fn commit_metadata(&self) -> Vec<(String, Value)> {
+        vec![
+            ("flushWindowStart".to_string(), Value::String(self.start.to_rfc3339())),
+            ("flushWindowEnd".to_string(), Value::String(self.end.to_rfc3339())),
+        ]
+    }
+}
+
+/// Arrow form of the table schema, reused until the table metadata changes
+#[derive(Debug)]
+struct CachedSchema {
+    schema_string: String,
+    arrow: Arc<ArrowSchema>,
+}
 
 /// The Writer process - continuously appends small files to Delta tables with minimal latency
 #[derive(Debug, Clone)]
 pub struct WriterProcess {
     config: WriterConfig,
+    schema_cache: Arc<std::sync::Mutex<Option<CachedSchema>>>,
+    /// Secondary sinks receiving every committed batch
+    sinks: Vec<SinkSender>,
+    /// Recent rows/bytes/files written per partition
+    partition_writes: Arc<std::sync::Mutex<PartitionWriteTracker>>,
+    /// Standby replication of the queue, when this writer is the primary
+    replication: Option<Arc<Replicator>>,
+    metrics: Arc<MetricsRegistry>,
+    /// Where flushed batches that exhaust their retries are kept
+    dead_letter: Option<Arc<DeadLetterQueue>>,
+    checksums: ChecksumConfig,
+    /// Flush slots shared with other tables, and this table's name there
+    flush_scheduler: Option<(Arc<FlushScheduler>, String)>,
+    alerts: Arc<Alerter>,
+    /// Groups the commits of flushes within `commit_interval_ms`
+    coalescer: Option<Arc<CommitCoalescer>>,
+    guardrails: Arc<Guardrails>,
+    /// The table as of the last flush, with the URI it was opened from.
+    /// Reused by the next flush instead of loading the log again; commit
+    /// conflicts bring it up to date.
+    table_handle: Arc<std::sync::Mutex<Option<(String, DeltaTable)>>>,
+    /// Dimension rows of the lookups the transforms enrich from
+    enrichment: Arc<EnrichmentCache>,
+    control: Arc<WriterControl>,
+    events: EventBus,
 }
 
 impl WriterProcess {
     /// Create a new writer process
     pub fn new(config: WriterConfig) -> Self {
-        Self { config }
+        let tracker = PartitionWriteTracker::new(config.partition_metrics_window());
+        let coalescer = config.commit_interval().map(|interval| Arc::new(CommitCoalescer::new(interval)));
+        let guardrails = Arc::new(Guardrails::new(config.guardrails.clone()));
+        Self {
+            config,
+            schema_cache: Arc::new(std::sync::Mutex::new(None)),
+            sinks: Vec::new(),
+            partition_writes: Arc::new(std::sync::Mutex::new(tracker)),
+            replication: None,
+            metrics: Arc::default(),
+            dead_letter: None,
+            checksums: ChecksumConfig::default(),
+            flush_scheduler: None,
+            alerts: Arc::default(),
+            coalescer,
+            guardrails,
+            table_handle: Arc::default(),
+            enrichment: Arc::default(),
+            control: Arc::default(),
+            events: EventBus::default(),
+        }
+    }
+
+    /// Record into a registry shared with the other processes of the table
+    pub fn with_metrics(mut self, metrics: Arc<MetricsRegistry>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Commit the replication sequence of flushed batches, so a standby
+    /// knows which of its copies the table already contains
+    pub fn with_replication(mut self, replication: Arc<Replicator>) -> Self {
+        self.replication = Some(replication);
+        self
+    }
+
+    /// Raise SLA misses and failing flushes through `alerts`
+    pub fn with_alerts(mut self, alerts: Arc<Alerter>) -> Self {
+        self.alerts = alerts;
+        self
+    }
+
+    /// Publish commits, SLA misses and given-up flushes on `events`
+    pub fn with_events(mut self, events: EventBus) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Keep batches that fail to flush in `dead_letter` instead of dropping them
+    pub fn with_dead_letter(mut self, dead_letter: Arc<DeadLetterQueue>) -> Self {
+        self.dead_letter = Some(dead_letter);
+        self
+    }
+
+    /// Record a checksum manifest of the files added by every commit
+    pub fn with_checksums(mut self, checksums: ChecksumConfig) -> Self {
+        self.checksums = checksums;
+        self
+    }
+
+    /// Wait for a slot from `scheduler`, shared with other tables, before
+    /// each queued flush; `table` gets `flush_weight` shares of the slots
+    pub fn with_flush_scheduler(mut self, scheduler: Arc<FlushScheduler>, table: &str) -> Self {
+        scheduler.register(table, self.config.flush_weight);
+        self.flush_scheduler = Some((scheduler, table.to_string()));
+        self
     }
 
-    /// Main run loop for the writer process
+    /// Copy committed batches to the given secondary sinks
+    pub fn with_sinks(mut self, sinks: Vec<SinkSender>) -> Self {
+        self.sinks = sinks;
+        self
+    }
+
+    /// Registry the writer records into
+    pub(crate) fn metrics(&self) -> &MetricsRegistry {
+        &self.metrics
+    }
+
+    /// The configuration this writer was built with
+    pub fn config(&self) -> &WriterConfig {
+        &self.config
+    }
+
+    /// Flush, pause and resume controls of this writer
+    pub fn control(&self) -> Arc<WriterControl> {
+        self.control.clone()
+    }
+
+    /// Main run loop for the writer process. Batches arriving on `batches`
+    /// are accumulated and flushed once `max_batch_size` rows are pending or
+    /// `max_batch_time` has elapsed, whichever comes first. With
+    /// `flush_alignment_secs` the time-based flush instead happens when the
+    /// current wall-clock window closes. [`WriterControl`] can force a flush
+    /// or pause the loop. On shutdown the queue is closed and everything
+    /// already accepted is flushed.
     pub async fn run(
         &self,
         table: Arc<Mutex<DeltaTable>>,
         storage_options: StorageOptions,
+        mut batches: mpsc::Receiver<QueuedBatch>,
+        shutdown: CancellationToken,
     ) -> Result<()> {
         log::info!("Starting Writer process");
         
+        let table_uri = table.lock().await.table_uri();
+        let mut pending: Vec<QueuedBatch> = Vec::new();
+        let mut pending_rows = 0usize;
+        // Replication sequence of the last batch received, in queue order
+        let mut seq = self.replication.as_ref().map(|r| r.committed_seq());
         let mut interval = interval(self.config.max_batch_time());
+        let alignment = self.config.flush_alignment();
+        let mut window = alignment.map(|period| FlushWindow::containing(Utc::now(), period));
+        let mut in_flight = JoinSet::new();
         
         loop {
+            let window_deadline = window.map(|w| w.deadline());
+            let paused = self.control.is_paused();
             tokio::select! {
-                _ = interval.tick() => {
-                    // Periodic flush - in a real implementation, this would flush
-                    // accumulated batches from a queue
-                    log::debug!("Writer process tick - would flush accumulated batches");
+                Some(batch) = batches.recv(), if !paused => {
+                    pending_rows += batch.df.height();
+                    pending.push(batch);
+                    seq = seq.map(|s| s + 1);
+                    
+                    if pending_rows >= self.config.max_batch_size {
+                        self.dispatch_flush(&mut pending, seq, window.as_ref(), &storage_options, &table_uri, &mut in_flight).await;
+                        pending_rows = 0;
+                    }
+                }
+                _ = interval.tick(), if window.is_none() => {
+                    if !pending.is_empty() && !paused {
+                        self.dispatch_flush(&mut pending, seq, None, &storage_options, &table_uri, &mut in_flight).await;
+                        pending_rows = 0;
+                    }
+                }
+                _ = sleep_until(window_deadline.unwrap_or_else(Instant::now)), if window.is_some() => {
+                    if !pending.is_empty() && !paused {
+                        self.dispatch_flush(&mut pending, seq, window.as_ref(), &storage_options, &table_uri, &mut in_flight).await;
+                        pending_rows = 0;
+                    }
+                    // Recomputed from the clock so a slow flush skips ahead
+                    // rather than committing a backlog of stale windows
+                    window = alignment.map(|period| FlushWindow::containing(Utc::now(), period));
+                }
+                _ = self.control.flush_requested() => {
+                    // A forced flush also takes what is still queued, even while paused
+                    while let Ok(batch) = batches.try_recv() {
+                        pending_rows += batch.df.height();
+                        pending.push(batch);
+                        seq = seq.map(|s| s + 1);
+                    }
+                    if !pending.is_empty() {
+                        log::info!("Flushing {} batches on request", pending.len());
+                        self.dispatch_flush(&mut pending, seq, window.as_ref(), &storage_options, &table_uri, &mut in_flight).await;
+                        pending_rows = 0;
+                    }
                 }
-                _ = tokio::signal::ctrl_c() => {
+                _ = self.control.resumed(), if paused => {}
+                _ = shutdown.cancelled() => {
                     log::info!("Writer process received shutdown signal");
                     break;
                 }
             }
         }
         
+        // Stop accepting new batches, then drain what producers already queued
+        batches.close();
+        while in_flight.join_next().await.is_some() {}
+        while let Some(batch) = batches.recv().await {
+            pending.push(batch);
+            seq = seq.map(|s| s + 1);
+        }
+        
+        if !pending.is_empty() {
+            log::info!("Flushing {} pending batches before exit", pending.len());
+            self.flush(&mut pending, seq, window.as_ref(), &storage_options, &table_uri).await;
+        }
+        
         Ok(())
     }
 
+    /// Flush the pending batches. With commit coalescing (and no replication,
+    /// whose checkpoints must commit in queue order) the flush runs in the
+    /// background, so the next flushes can join its commit.
+    async fn dispatch_flush(
+        &self,
+        pending: &mut Vec<QueuedBatch>,
+        seq: Option<i64>,
+        window: Option<&FlushWindow>,
+        storage_options: &StorageOptions,
+        table_uri: &str,
+        in_flight: &mut JoinSet<()>,
+    ) {
+        if self.coalescer.is_none() || self.replication.is_some() {
+            return self.flush(pending, seq, window, storage_options, table_uri).await;
+        }
+        while in_flight.len() >= MAX_IN_FLIGHT_FLUSHES {
+            in_flight.join_next().await;
+        }
+        let writer = self.clone();
+        let mut batches = std::mem::take(pending);
+        let window = window.copied();
+        let storage_options = storage_options.clone();
+        let table_uri = table_uri.to_string();
+        in_flight.spawn(async move {
+            writer.flush(&mut batches, seq, window.as_ref(), &storage_options, &table_uri).await
+        });
+    }
+
+    /// Combine pending batches into one DataFrame and write it, together
+    /// with the replication sequence of the last one
+    async fn flush(
+        &self,
+        pending: &mut Vec<QueuedBatch>,
+        seq: Option<i64>,
+        window: Option<&FlushWindow>,
+        storage_options: &StorageOptions,
+        table_uri: &str,
+    ) {
+        let rows: usize = pending.iter().map(|b| b.df.height()).sum();
+        let bytes: usize = pending.iter().map(|b| b.df.estimated_size()).sum();
+        self.metrics.record_dequeued(bytes as u64);
+        let ids: Vec<String> = pending.iter().map(|b| b.id.clone()).collect();
+        
+        let combined = match concat_frames(pending.iter().map(|b| b.df.clone())) {
+            Ok(df) => df,
+            Err(e) => {
+                // Columns of the same name but different types; each batch is
+                // kept on its own, as it was acknowledged
+                let e = anyhow!(e).context("Queued batches could not be combined");
+                log::error!("Failed to combine {} rows of batches {:?}: {:#}", rows, ids, e);
+                self.metrics.record_error("writer", format!("Failed to combine batches {:?}: {:#}", ids, e));
+                for batch in pending.drain(..) {
+                    let _ = self.dead_letter(table_uri, &batch.df, &[batch.id], &e).await;
+                }
+                return;
+            }
+        };
+        // Clear rather than take so the buffer keeps its capacity across flushes
+        pending.clear();
+        
+        let replication = self.replication.as_ref().zip(seq);
+        let txns: Vec<Transaction> = replication.map(|(r, seq)| r.checkpoint(seq)).into_iter().collect();
+        let slot = self.flush_slot().await;
+        let metadata = window.map(FlushWindow::commit_metadata).unwrap_or_default();
+        let result = self.write_batches(combined.clone(), &ids, &txns, metadata, storage_options, table_uri).await;
+        drop(slot);
+        match result {
+            Ok(()) => {
+                if let Some((replication, seq)) = replication {
+                    replication.committed(seq).await;
+                }
+            }
+            Err(e) => {
+                log::error!("Failed to flush {} rows of batches {:?}: {:#}", rows, ids, e);
+                self.metrics.record_error("writer", format!("Failed to flush batches {:?}: {:#}", ids, e));
+                self.alerts.fire(AlertKind::CircuitOpen, format!("Gave up flushing {} rows: {:#}", rows, e));
+                self.events.publish(OrchestratorEvent::CircuitOpened {
+                    table_uri: table_uri.to_string(),
+                    rows,
+                    batch_ids: ids.clone(),
+                    error: format!("{:#}", e),
+                });
+                let _ = self.dead_letter(table_uri, &combined, &ids, &e).await;
+            }
+        }
+    }
+
+    /// Keep batches that will not be written in the dead letter queue.
+    /// Returns false if there is none or the rows could not be stored.
+    pub(crate) async fn dead_letter(&self, table_uri: &str, df: &DataFrame, ids: &[String], error: &anyhow::Error) -> bool {
+        let Some(dead_letter) = &self.dead_letter else { return false };
+        match dead_letter.put(table_uri, df, ids, error).await {
+            Ok(entry) => {
+                self.metrics.record_dead_lettered();
+                log::warn!("Dead-lettered batches {:?} as {} in {}", ids, entry, dead_letter.uri());
+                true
+            }
+            Err(e) => {
+                log::error!("Failed to dead-letter batches {:?}: {:#}", ids, e);
+                false
+            }
+        }
+    }
+
+    /// Slot to flush in, when uploads are shared with other tables
+    async fn flush_slot(&self) -> Option<FlushPermit> {
+        let (scheduler, table) = self.flush_scheduler.as_ref()?;
+        let started = Instant::now();
+        let permit = scheduler.acquire(table).await;
+        self.metrics.record_flush_slot_wait(started.elapsed(), self.config.max_latency());
+        Some(permit)
+    }
+
     /// Write a single batch to the Delta table
     pub async fn write_batch(
         &self,
         df: DataFrame,
         storage_options: &StorageOptions,
         table_uri: &str,
+    ) -> Result<()> {
+        self.write_batch_with_txns(df, &[], storage_options, table_uri).await
+    }
+
+    /// Write a batch at most once per idempotency key: the key is recorded as
+    /// an application transaction, so a client resubmitting it (e.g. after a
+    /// crash) has the write skipped for as long as the key is retained
+    pub async fn write_batch_idempotent(
+        &self,
+        df: DataFrame,
+        idempotency_key: &str,
+        storage_options: &StorageOptions,
+        table_uri: &str,
+    ) -> Result<()> {
+        let txn = idempotency_txn(idempotency_key)?;
+        self.write_batch_with_txns(df, &[txn], storage_options, table_uri).await
+    }
+
+    /// Write a batch and record the given application transactions (e.g.
+    /// source offsets) in the same commit. A batch whose transactions are
+    /// already committed is skipped, which makes retries across crashes safe.
+    pub async fn write_batch_with_txns(
+        &self,
+        df: DataFrame,
+        txns: &[Transaction],
+        storage_options: &StorageOptions,
+        table_uri: &str,
+    ) -> Result<()> {
+        self.write_batches(df, &[new_batch_id()], txns, Vec::new(), storage_options, table_uri).await
+    }
+
+    /// [`Self::write_batch_with_txns`], also recording `metadata` in the
+    /// commit info, e.g. source positions too large for a transaction version
+    pub async fn write_batch_with_checkpoint(
+        &self,
+        df: DataFrame,
+        txns: &[Transaction],
+        metadata: Vec<(String, Value)>,
+        storage_options: &StorageOptions,
+        table_uri: &str,
+    ) -> Result<()> {
+        self.write_batches(df, &[new_batch_id()], txns, metadata, storage_options, table_uri).await
+    }
+
+    /// [`Self::write_batch_with_txns`] for the rows of the batches with the
+    /// given ids, recording the ids and `metadata` (such as the aligned
+    /// flush window the rows were collected in) in the commit
+    #[tracing::instrument(
+        name = "write_batch",
+        skip_all,
+        fields(table_uri = %table_uri, rows = df.height(), batches = batch_ids.len())
+    )]
+    pub(crate) async fn write_batches(
+        &self,
+        mut df: DataFrame,
+        batch_ids: &[String],
+        txns: &[Transaction],
+        mut metadata: Vec<(String, Value)>,
+        storage_options: &StorageOptions,
+        table_uri: &str,
     ) -> Result<()> {
         let start_time = Instant::now();
+        // Batches carrying only source positions have no columns to reshape
+        if df.width() > 0 {
+            let lookups = self
+                .enrichment
+                .resolve(&self.config.transforms, &self.config.lookups, storage_options)
+                .await?;
+            df = transform::apply_transforms(df, &self.config.transforms, &lookups).map_err(retry::non_retryable)?;
+            if let Some(partitioning) = &self.config.partition_by_event_time {
+                df = transform::derive_partitions(df, partitioning).map_err(retry::non_retryable)?;
+            }
+            df = self.apply_lateness(df, storage_options).await?;
+            df = self.apply_validation(df, batch_ids, storage_options, table_uri).await?;
+        }
+        if df.height() == 0 && txns.is_empty() {
+            return Ok(());
+        }
+        metadata.push(("batchIds".to_string(), Value::from(batch_ids.to_vec())));
         
         let mut retry_count = 0;
         
         while retry_count <= self.config.max_retries {
-            match self.try_write_batch(&df, storage_options, table_uri).await {
-                Ok(()) => {
+            match self.try_write_batch(&df, txns, &metadata, storage_options, table_uri).await {
+                Ok(committed) => {
+                    if let Some(table) = &committed {
+                        self.remember_table(table_uri, table);
+                    }
                     let elapsed = start_time.elapsed();
-                    log::debug!("Write completed in {:?}", elapsed);
+                    log::debug!("Write of batches {:?} completed in {:?}", batch_ids, elapsed);
                     
                     // Check if we exceeded our latency SLA
                     if elapsed > self.config.max_latency() {
@@ -70,23 +621,74 @@ impl WriterProcess {
                             elapsed,
                             self.config.max_latency()
                         );
+                        self.alerts.fire(
+                            AlertKind::SlaMiss,
+                            format!("Write took {:?}, over the {:?} SLA", elapsed, self.config.max_latency()),
+                        );
+                        self.events.publish(OrchestratorEvent::SlaMissed {
+                            table_uri: table_uri.to_string(),
+                            latency_ms: elapsed.as_millis() as u64,
+                            sla_ms: self.config.max_latency().as_millis() as u64,
+                        });
+                    }
+                    
+                    if let Some(table) = committed {
+                        self.metrics.record_write(df.height(), elapsed);
+                        let version = table.version();
+                        tracing::info!(
+                            table_uri,
+                            rows = df.height(),
+                            bytes = df.estimated_size(),
+                            latency_ms = elapsed.as_millis() as u64,
+                            version,
+                            batches = batch_ids.len(),
+                            "Committed batch"
+                        );
+                        self.metrics.record_batches_committed(batch_ids, version);
+                        self.events.publish(OrchestratorEvent::BatchCommitted {
+                            table_uri: table_uri.to_string(),
+                            version,
+                            rows: df.height(),
+                            batch_ids: batch_ids.to_vec(),
+                            latency_ms: elapsed.as_millis() as u64,
+                        });
+                        for sink in self.sinks.iter().filter(|_| df.height() > 0) {
+                            sink.dispatch(SinkBatch { df: df.clone(), version, batch_ids: batch_ids.to_vec() });
+                        }
+                        if df.height() > 0 {
+                            self.update_rollups(&table, &df, version, storage_options).await;
+                        }
+                        self.record_partition_writes(&table, version);
+                        self.record_checksums(&table, version);
                     }
                     
                     return Ok(());
                 }
                 Err(e) => {
+                    // The next attempt starts from a freshly loaded table
+                    self.forget_table();
+                    if !retry::is_retryable(&e) {
+                        self.metrics.record_write_failure();
+                        return Err(e).context("Write failed with a non-retryable error");
+                    }
+                    
                     retry_count += 1;
                     if retry_count > self.config.max_retries {
-                        return Err(e).with_context("All write retries exhausted");
+                        self.metrics.record_write_failure();
+                        return Err(e).context("All write retries exhausted");
                     }
+                    self.metrics.record_write_retry();
                     
+                    let delay = self.config.retry_backoff(retry_count);
                     log::warn!(
-                        "Write attempt {} failed, retrying: {}",
+                        "Write attempt {} of batches {:?} failed, retrying in {:?}: {}",
                         retry_count,
+                        batch_ids,
+                        delay,
                         e
                     );
                     
-                    tokio::time::sleep(self.config.retry_delay()).await;
+                    tokio::time::sleep(delay).await;
                 }
             }
         }
@@ -94,46 +696,495 @@ impl WriterProcess {
         unreachable!()
     }
 
+    /// The table as this writer last saw it, loaded from storage only on the
+    /// first flush or after a failed one
+    async fn load_table(&self, table_uri: &str, storage_options: &StorageOptions) -> Result<DeltaTable> {
+        let cached = self
+            .table_handle
+            .lock()
+            .unwrap()
+            .as_ref()
+            .filter(|(uri, _)| uri == table_uri)
+            .map(|(_, table)| table.clone());
+        if let Some(table) = cached {
+            self.metrics.record_table_reuse();
+            return Ok(table);
+        }
+
+        let start = Instant::now();
+        let table = deltalake::open_table_with_storage_options(table_uri, storage_options.0.clone())
+            .instrument(tracing::info_span!("open_table"))
+            .await
+            .context("Failed to open Delta table")?;
+        self.metrics.record_table_open(start.elapsed());
+        self.remember_table(table_uri, &table);
+        Ok(table)
+    }
+
+    /// Keep `table` for the next flush unless a newer version is kept already
+    fn remember_table(&self, table_uri: &str, table: &DeltaTable) {
+        let mut cached = self.table_handle.lock().unwrap();
+        let newer = match cached.as_ref() {
+            Some((uri, kept)) if uri == table_uri => table.version() > kept.version(),
+            _ => true,
+        };
+        if newer {
+            *cached = Some((table_uri.to_string(), table.clone()));
+        }
+    }
+
+    fn forget_table(&self) {
+        self.table_handle.lock().unwrap().take();
+    }
+
+    /// Route or flag rows older than the event-time watermark, returning the
+    /// rows to write to the table. Late rows are routed before the table
+    /// commit is attempted, so replaying a batch that failed afterwards from
+    /// the dead letter queue routes them again.
+    async fn apply_lateness(&self, df: DataFrame, storage_options: &StorageOptions) -> Result<DataFrame> {
+        let Some(lateness) = &self.config.lateness else { return Ok(df) };
+        let mask = lateness::late_mask(&df, &lateness.event_time_column, lateness::watermark(lateness))
+            .map_err(retry::non_retryable)?;
+        let late_rows = mask.sum().unwrap_or(0) as usize;
+        if late_rows > 0 {
+            self.metrics.record_late_rows(late_rows);
+        }
+
+        match &lateness.policy {
+            LatePolicy::Flag => lateness::flag_late(df, mask).map_err(retry::non_retryable),
+            LatePolicy::Route { .. } | LatePolicy::Drop if late_rows == 0 => Ok(df),
+            LatePolicy::Drop => {
+                let (on_time, _) = lateness::split_late(&df, &mask)?;
+                log::info!("Dropped {} late rows", late_rows);
+                Ok(on_time)
+            }
+            LatePolicy::Route { late_table_uri } => {
+                let (on_time, late) = lateness::split_late(&df, &mask)?;
+                lateness::append_late(&late, late_table_uri, storage_options).await?;
+                log::info!("Routed {} late rows to {}", late_rows, late_table_uri);
+                Ok(on_time)
+            }
+        }
+    }
+
+    /// Quarantine rows breaking a validation rule, returning the rest. Like
+    /// late rows, they are moved before the table commit is attempted.
+    async fn apply_validation(
+        &self,
+        df: DataFrame,
+        batch_ids: &[String],
+        storage_options: &StorageOptions,
+        table_uri: &str,
+    ) -> Result<DataFrame> {
+        let Some(validation) = &self.config.validation else { return Ok(df) };
+        let (valid, invalid) = validation::split_invalid(df, &validation.rules).map_err(retry::non_retryable)?;
+        let Some(invalid) = invalid else { return Ok(valid) };
+        let rows = invalid.height();
+        self.metrics.record_quarantined_rows(rows);
+
+        match &validation.quarantine {
+            QuarantineTarget::Table { quarantine_table_uri } => {
+                validation::append_quarantined(&invalid, quarantine_table_uri, storage_options).await?;
+                log::warn!("Quarantined {} invalid rows in {}", rows, quarantine_table_uri);
+            }
+            QuarantineTarget::DeadLetter => {
+                let Some(dead_letter) = &self.dead_letter else {
+                    anyhow::bail!("Validation quarantines to the dead letter queue, but no dead_letter_uri is set");
+                };
+                let mut rows_as_sent = invalid;
+                let _ = rows_as_sent.drop_in_place(validation::VIOLATION_COLUMN);
+                let error = anyhow!("{} rows broke validation rules", rows);
+                let id = dead_letter.put(table_uri, &rows_as_sent, batch_ids, &error).await?;
+                log::warn!("Quarantined {} invalid rows as dead letter {}", rows, id);
+            }
+        }
+        Ok(valid)
+    }
+
+    /// Attribute the files of a commit to partitions, off the write path
+    fn record_partition_writes(&self, table: &DeltaTable, version: i64) {
+        let Ok(metadata) = table.metadata() else { return };
+        let partition_columns = metadata.partition_columns.clone();
+        let log_store = table.log_store();
+        let tracker = self.partition_writes.clone();
+
+        tokio::spawn(async move {
+            match partition_metrics::read_commit_adds(&log_store, version).await {
+                Ok((timestamp, adds)) => tracker.lock().unwrap().record(
+                    timestamp.unwrap_or_else(chrono::Utc::now),
+                    &adds,
+                    &partition_columns,
+                ),
+                Err(e) => log::debug!("Failed to read commit {} for partition metrics: {:#}", version, e),
+            }
+        });
+    }
+
+    /// Off the write path: compaction picks up any commit missed here
+    pub(crate) fn record_checksums(&self, table: &DeltaTable, version: i64) {
+        if !self.checksums.enabled {
+            return;
+        }
+        let log_store = table.log_store();
+        let sha256 = self.checksums.sha256;
+
+        tokio::spawn(async move {
+            if let Err(e) = checksums::record_commit(&log_store, version, sha256).await {
+                log::warn!("Failed to record checksums of version {}: {:#}", version, e);
+            }
+        });
+    }
+
+    /// Fold a committed raw batch into every configured rollup table.
+    /// The raw commit has already succeeded, so a failing rollup is not
+    /// surfaced, which would make the caller re-append the raw batch; the
+    /// next commit catches the rollup up from its recorded progress.
+    async fn update_rollups(&self, table: &DeltaTable, df: &DataFrame, version: i64, storage_options: &StorageOptions) {
+        for rollup in &self.config.rollups {
+            if let Err(e) = rollup::apply_rollup(table, df, version, rollup, storage_options).await {
+                log::error!("Failed to update rollup table {}, retrying with the next commit: {:#}", rollup.table_uri, e);
+                self.metrics.record_error("rollup", format!("Failed to update {}: {:#}", rollup.table_uri, e));
+            }
+        }
+    }
+
     /// Internal method to attempt writing a batch
     async fn try_write_batch(
         &self,
         df: &DataFrame,
+        txns: &[Transaction],
+        metadata: &[(String, Value)],
         storage_options: &StorageOptions,
         table_uri: &str,
-    ) -> Result<()> {
+    ) -> Result<Option<DeltaTable>> {
+        // Merge sources must have unique keys; the last row for a key wins
+        let deduplicated;
+        let df = match &self.config.write_mode {
+            WriteMode::Merge { key_columns, .. } => {
+                deduplicated = df
+                    .unique_stable(Some(key_columns), UniqueKeepStrategy::Last, None)
+                    .context("Failed to deduplicate batch on merge keys")?;
+                &deduplicated
+            }
+            WriteMode::Append => df,
+        };
+
+        self.guardrails.check_commit_rate()?;
+
+        let mut table = self.load_table(table_uri, storage_options).await?;
+
+        if !txns.is_empty() && txns_already_committed(&table, txns) {
+            log::info!("Skipping batch: application transactions already committed");
+            return Ok(None);
+        }
+
+        let commit_properties = CommitProperties::default()
+            .with_application_transactions(txns.to_vec())
+            .with_metadata(metadata.to_vec());
+
+        // A poll that only advanced source positions (tombstones, undecodable
+        // messages) commits the transactions alone, without any files
+        if df.height() == 0 {
+            let empty_schema: SchemaRef = Arc::new(ArrowSchema::empty());
+            let committed = self
+                .commit_files(&mut table, Vec::new(), None, &empty_schema, txns, commit_properties)
+                .await?;
+            return Ok(committed.then_some(table));
+        }
+
         // Convert Polars DataFrame to Arrow RecordBatch
-        let batch = df.to_arrow(None)
-            .with_context("Failed to convert DataFrame to Arrow")?;
-            
-        // Create a new writer with storage options
-        let mut writer = RecordBatchWriter::for_table_path(table_uri)
-            .with_context("Failed to create RecordBatchWriter")?
-            .with_storage_options(storage_options.clone());
-            
-        // Write the batch
-        writer.write(batch)
-            .await
-            .with_context("Failed to write batch")?;
-            
-        // Close the writer to commit the transaction
-        writer.close()
-            .await
-            .with_context("Failed to close writer")?;
-            
-        Ok(())
+        let batch = tracing::info_span!("arrow_convert")
+            .in_scope(|| df.to_arrow(None))
+            .context("Failed to convert DataFrame to Arrow")?;
+        let writer_properties = self
+            .config
+            .parquet
+            .writer_properties(ParquetCompression::Snappy)
+            .map_err(retry::non_retryable)?;
+
+        // Refuse tables carrying features we cannot honour (e.g. deletion vectors)
+        compat::ensure_writable(&table).map_err(retry::non_retryable)?;
+
+        let mut table_schema = self.table_arrow_schema(&table)?;
+        // A reused handle may predate columns another writer added since
+        if batch.schema().fields().iter().any(|f| table_schema.field_with_name(f.name()).is_err()) {
+            table.update().await.context("Failed to refresh table schema")?;
+            table_schema = self.table_arrow_schema(&table)?;
+        }
+
+        // Match columns by name rather than position
+        let batch = align_batch(batch, &table_schema).map_err(retry::non_retryable)?;
+
+        let write_mode = match plan_schema_change(
+            self.config.schema_evolution,
+            &table_schema,
+            &batch.schema(),
+        )
+        .map_err(retry::non_retryable)?
+        {
+            SchemaChange::Unchanged => DeltaWriteMode::Default,
+            SchemaChange::AddColumns(fields) => {
+                self.guardrails.check_columns(table_schema.fields().len() + fields.len())?;
+                log::info!(
+                    "Adding columns {:?} to table schema",
+                    fields.iter().map(|f| f.name()).collect::<Vec<_>>()
+                );
+                DeltaWriteMode::MergeSchema
+            }
+        };
+
+        if let WriteMode::Merge { key_columns, delete_predicate } = &self.config.write_mode {
+            let merge_schema = matches!(write_mode, DeltaWriteMode::MergeSchema);
+            let (table, _) = merge::upsert(
+                table,
+                batch,
+                key_columns,
+                delete_predicate.as_deref(),
+                merge_schema,
+                commit_properties,
+                writer_properties,
+            )
+            .instrument(tracing::info_span!("merge"))
+            .await?;
+            self.guardrails.record_commit();
+            return Ok(Some(table));
+        }
+
+        // Encode and upload the files in parallel; they are committed at once
+        let batch_schema = batch.schema();
+        let merge_schema = matches!(write_mode, DeltaWriteMode::MergeSchema);
+        let partition_columns = table.metadata()?.partition_columns.clone();
+        let batch = write_pool::sort_batch(&batch, &self.config.sort_columns).map_err(retry::non_retryable)?;
+        let arrow_bytes_per_row = batch.get_array_memory_size() as f64 / batch.num_rows().max(1) as f64;
+        let shards = write_pool::shard_batch(&batch, &partition_columns, self.config.write_workers)?;
+        let (adds, written_schema) =
+            write_pool::write_shards(&table, shards, merge_schema, &commit_properties, &writer_properties).await?;
+        // The uploaded files stay unreferenced and are left to vacuum
+        self.guardrails.check_files(adds.len())?;
+        self.metrics
+            .record_files_written(adds.iter().map(|add| FileReceipt::for_add(add, arrow_bytes_per_row)).collect());
+
+        // Plain appends can share a commit with other flushes
+        if let Some(coalescer) = self.coalescer.as_ref().filter(|_| !merge_schema && txns.is_empty()) {
+            let table = coalescer.commit(self, table, adds, metadata, &batch_schema).await?;
+            return Ok(Some(table));
+        }
+
+        // New columns are committed as a metadata action next to the files
+        let schema_metadata = if merge_schema {
+            let schema = StructType::try_from(written_schema.as_ref())
+                .context("Failed to convert merged schema")?;
+            let mut metadata = table.metadata()?.clone();
+            metadata.schema_string = serde_json::to_string(&schema)?;
+            Some(metadata)
+        } else {
+            None
+        };
+
+        let committed = self
+            .commit_files(&mut table, adds, schema_metadata, &batch_schema, txns, commit_properties)
+            .await?;
+        Ok(committed.then_some(table))
+    }
+
+    /// Commit already written data files (and the merged schema, if any),
+    /// leaving `table` at the new version. When another writer or the
+    /// compactor commits first, or the log write fails transiently, only the
+    /// commit is retried on the latest version; the files are never rewritten.
+    /// Returns false if the racing commit already recorded `txns`.
+    #[tracing::instrument(name = "commit", skip_all, fields(files = adds.len()))]
+    pub(crate) async fn commit_files(
+        &self,
+        table: &mut DeltaTable,
+        adds: Vec<Add>,
+        metadata: Option<Metadata>,
+        batch_schema: &SchemaRef,
+        txns: &[Transaction],
+        commit_properties: CommitProperties,
+    ) -> Result<bool> {
+        // A merged schema extends the schema it was derived from
+        let base_schema = table.metadata()?.schema_string.clone();
+        let merged_schema = metadata.as_ref().map(|m| m.schema_string.clone());
+        let mut actions: Vec<Action> = metadata.into_iter().map(Action::Metadata).collect();
+        actions.extend(adds.into_iter().map(Action::Add));
+
+        let mut conflicts = 0;
+        let mut failures = 0;
+        loop {
+            let partition_columns = table.metadata()?.partition_columns.clone();
+            let operation = DeltaOperation::Write {
+                mode: SaveMode::Append,
+                partition_by: (!partition_columns.is_empty()).then_some(partition_columns),
+                predicate: None,
+            };
+            let result = CommitBuilder::from(commit_properties.clone())
+                .with_actions(actions.clone())
+                .build(Some(table.snapshot()?), table.log_store(), operation)
+                .await;
+
+            let error = match result {
+                Ok(commit) => {
+                    self.guardrails.record_commit();
+                    table.update_incremental(Some(commit.version())).await
+                        .context("Failed to load committed version")?;
+                    return Ok(true);
+                }
+                Err(e) => anyhow::Error::from(e),
+            };
+            if retry::is_commit_conflict(&error) {
+                if conflicts >= self.config.max_conflict_retries {
+                    return Err(error).context("Failed to commit batch");
+                }
+                conflicts += 1;
+                self.metrics.record_commit_conflict();
+                log::info!("Commit lost a race with another writer, retrying on the latest version: {}", error);
+            } else {
+                // Re-adding a file a lost-but-landed attempt already added
+                // leaves the table unchanged, so the commit alone is retried
+                if !retry::is_retryable(&error) || failures >= self.config.max_retries {
+                    return Err(error).context("Failed to commit batch");
+                }
+                failures += 1;
+                self.metrics.record_write_retry();
+                let delay = self.config.retry_backoff(failures);
+                log::warn!("Commit of uploaded files failed, retrying in {:?}: {}", delay, error);
+                tokio::time::sleep(delay).await;
+            }
+
+            table.update().await
+                .context("Failed to refresh table before retrying commit")?;
+            if !txns.is_empty() && txns_already_committed(table, txns) {
+                log::info!("Skipping batch: application transactions committed concurrently");
+                return Ok(false);
+            }
+            compat::ensure_writable(table).map_err(retry::non_retryable)?;
+            if let Some(merged) = &merged_schema {
+                let current = &table.metadata()?.schema_string;
+                if current == merged {
+                    // Our columns are already there (perhaps from a landed
+                    // attempt), so only the files remain to be added
+                    actions.retain(|action| matches!(action, Action::Add(_)));
+                } else if current != &base_schema {
+                    anyhow::bail!("Table schema changed concurrently, rewriting batch");
+                }
+                continue;
+            }
+            // Files written for the old schema can only be reused if it still matches
+            let table_schema = self.table_arrow_schema(table)?;
+            let change = plan_schema_change(self.config.schema_evolution, &table_schema, batch_schema)
+                .map_err(retry::non_retryable)?;
+            if change != SchemaChange::Unchanged {
+                anyhow::bail!("Table schema changed concurrently, rewriting batch");
+            }
+        }
+    }
+
+    /// Arrow schema of the table, converted only when the table schema changes
+    pub(crate) fn table_arrow_schema(&self, table: &DeltaTable) -> Result<Arc<ArrowSchema>> {
+        let metadata = table.metadata()?;
+        let mut cache = self.schema_cache.lock().unwrap();
+        
+        if let Some(cached) = cache.as_ref().filter(|c| c.schema_string == metadata.schema_string) {
+            return Ok(cached.arrow.clone());
+        }
+        
+        let arrow = Arc::new(
+            ArrowSchema::try_from(table.get_schema()?)
+                .context("Failed to convert table schema to Arrow")?,
+        );
+        *cache = Some(CachedSchema {
+            schema_string: metadata.schema_string.clone(),
+            arrow: arrow.clone(),
+        });
+        
+        Ok(arrow)
     }
 
     /// Get metrics about the writer performance
     pub fn get_metrics(&self) -> WriterMetrics {
         WriterMetrics {
             config: self.config.clone(),
-            // In a real implementation, these would be tracked
-            total_batches_written: 0,
-            total_rows_written: 0,
-            average_latency_ms: 0.0,
-            p99_latency_ms: 0.0,
+            total_batches_written: self.metrics.batches_written(),
+            total_rows_written: self.metrics.rows_written(),
+            total_retries: self.metrics.write_retries(),
+            total_failures: self.metrics.write_failures(),
+            total_commit_conflicts: self.metrics.commit_conflicts(),
+            average_latency_ms: self.metrics.write_latency().mean_ms(),
+            p99_latency_ms: self.metrics.write_latency().quantile_ms(0.99),
+            table_opens: self.metrics.table_opens(),
+            table_reuses: self.metrics.table_reuses(),
+            average_table_open_ms: self.metrics.table_open_time().mean_ms(),
+            compression_ratio: self.metrics.compression_ratio(),
+            recent_files: self.metrics.recent_files(RECENT_FILES_REPORTED),
+            hot_partitions: self
+                .partition_writes
+                .lock()
+                .unwrap()
+                .top(self.config.hot_partitions, chrono::Utc::now()),
+        }
+    }
+}
+
+/// Whether every transaction's version is already recorded in the table
+pub fn txns_already_committed(table: &DeltaTable, txns: &[Transaction]) -> bool {
+    let committed = table.get_app_transaction_version();
+    txns.iter().all(|txn| {
+        committed
+            .get(&txn.app_id)
+            .is_some_and(|existing| existing.version >= txn.version)
+    })
+}
+
+/// Application transaction marking the write with `key` as done; the key is
+/// used verbatim as the appId. Each key stays in the table state until
+/// `delta.setTransactionRetentionDuration` expires it, measured from the
+/// time stamped here (see `writer.idempotency_key_retention_days`).
+pub fn idempotency_txn(key: &str) -> Result<Transaction> {
+    if key.is_empty() {
+        return Err(anyhow!("Idempotency key must not be empty"));
+    }
+    Ok(Transaction::new_with_last_update(key, 0, Some(Utc::now().timestamp_millis())))
+}
+
+/// Stack queued batches into a single DataFrame, aligning columns by name.
+/// The result has every column of any batch, in order of first appearance;
+/// batches without one get nulls, so schema evolution still sees columns
+/// only later batches bring. A column with different types fails.
+pub(crate) fn concat_frames(frames: impl Iterator<Item = DataFrame>) -> PolarsResult<DataFrame> {
+    let frames: Vec<DataFrame> = frames.collect();
+    let mut columns: Vec<(PlSmallStr, DataType)> = Vec::new();
+    for df in &frames {
+        for column in df.get_columns() {
+            if !columns.iter().any(|(name, _)| name == column.name()) {
+                columns.push((column.name().clone(), column.dtype().clone()));
+            }
+        }
+    }
+
+    let mut combined: Option<DataFrame> = None;
+    for mut df in frames {
+        let aligned = df.width() == columns.len()
+            && df.get_column_names().into_iter().zip(&columns).all(|(name, (expected, _))| name == expected);
+        if !aligned {
+            let height = df.height();
+            df = DataFrame::new(
+                columns
+                    .iter()
+                    .map(|(name, dtype)| match df.column(name) {
+                        Ok(column) => column.clone(),
+                        Err(_) => Column::full_null(name.clone(), height, dtype),
+                    })
+                    .collect(),
+            )?;
+        }
+        match combined.as_mut() {
+            Some(combined) => {
+                combined.vstack_mut(&df)?;
+            }
+            None => combined = Some(df),
         }
     }
+    Ok(combined.unwrap_or_else(DataFrame::empty))
 }
 
 /// Metrics for the writer process
@@ -142,6 +1193,23 @@ pub struct WriterMetrics {
     pub config: WriterConfig,
     pub total_batches_written: u64,
     pub total_rows_written: u64,
+    /// Write attempts that failed and were retried
+    pub total_retries: u64,
+    /// Batches abandoned after a permanent error or exhausted retries
+    pub total_failures: u64,
+    /// Commits lost to a concurrent writer and retried without rewriting data
+    pub total_commit_conflicts: u64,
     pub average_latency_ms: f64,
     pub p99_latency_ms: f64,
+    /// Flushes that loaded the table from storage, against those reusing the
+    /// handle of an earlier flush; each reuse saves about one average open
+    pub table_opens: u64,
+    pub table_reuses: u64,
+    pub average_table_open_ms: f64,
+    /// Arrow size of the rows written over the size of their Parquet files
+    pub compression_ratio: f64,
+    /// The latest files written, newest first, with their compression
+    pub recent_files: Vec<FileReceipt>,
+    /// Partitions with the most rows written in the metrics window
+    pub hot_partitions: Vec<PartitionWrites>,
 } 
\ No newline at end of file
@@ -46,4 +46,69 @@ pub(crate) fn setup_docker() -> (Container<'static, GenericImage>, Container<'st
 pub(crate) async fn create_delta_table(table_name: &str) -> Result<DeltaTable> {
     // TODO: implement real delta table creation
     todo!("Implement delta table creation with MinIO backend")
-} 
\ No newline at end of file
+} 
+/// A table in a temporary directory, with the config and orchestrator it was
+/// created from; the directory is removed when this is dropped
+pub(crate) struct LocalTable {
+    pub dir: tempfile::TempDir,
+    pub uri: String,
+    pub config: surgical_strike_writer::SurgicalStrikeConfig,
+    pub orchestrator: surgical_strike_writer::SurgicalStrikeOrchestrator,
+}
+
+/// Config of the table at `table_uri` declaring `columns` as (name, Delta
+/// type) pairs. `extra` is TOML placed right after `table_uri`, e.g. a
+/// `[writer]` section.
+pub(crate) fn local_config(
+    table_uri: &str,
+    columns: &[(&str, &str)],
+    extra: &str,
+) -> Result<surgical_strike_writer::SurgicalStrikeConfig> {
+    let columns: String = columns
+        .iter()
+        .map(|(name, data_type)| format!("\n[[schema.columns]]\nname = \"{}\"\ntype = \"{}\"\n", name, data_type))
+        .collect();
+    let toml = format!("table_uri = \"{}\"\n{}\n{}", table_uri, extra, columns);
+    Ok(surgical_strike_writer::config::parse_config(&toml)?.remove(0))
+}
+
+/// Orchestrator over a new local table; see [`local_config`] for the arguments
+pub(crate) async fn local_table(columns: &[(&str, &str)], extra: &str) -> Result<LocalTable> {
+    let dir = tempfile::tempdir()?;
+    let uri = format!("file://{}", dir.path().display());
+    let config = local_config(&uri, columns, extra)?;
+    let orchestrator = surgical_strike_writer::SurgicalStrikeOrchestrator::new(config.clone()).await?;
+    Ok(LocalTable { dir, uri, config, orchestrator })
+}
+
+/// Source replaying a fixed list of batches in order, then exhausted
+pub(crate) struct ScriptedSource(Vec<surgical_strike_writer::sources::SourceBatch>);
+
+impl ScriptedSource {
+    pub fn new(mut batches: Vec<surgical_strike_writer::sources::SourceBatch>) -> Self {
+        batches.reverse();
+        Self(batches)
+    }
+}
+
+impl surgical_strike_writer::sources::Source for ScriptedSource {
+    fn name(&self) -> &str {
+        "scripted"
+    }
+    async fn resume(&mut self, _: &DeltaTable) -> Result<()> {
+        Ok(())
+    }
+    async fn next_batch(
+        &mut self,
+        _: usize,
+        _: std::time::Duration,
+    ) -> Result<Option<surgical_strike_writer::sources::SourceBatch>> {
+        Ok(self.0.pop())
+    }
+    async fn committed(&mut self, _: &[deltalake::kernel::Transaction]) -> Result<()> {
+        Ok(())
+    }
+    fn is_exhausted(&self) -> bool {
+        self.0.is_empty()
+    }
+}
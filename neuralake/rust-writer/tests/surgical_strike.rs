@@ -104,6 +104,52 @@ mod helpers {
             dynamo_container,
         }
     }
+
+    /// Well-known account of the Azurite emulator
+    pub const AZURITE_ACCOUNT: &str = "devstoreaccount1";
+    pub const AZURITE_KEY: &str =
+        "Eby8vdM02xNOcqFlqUwJPLlmEXsl5oN1eI8hbLlMdW6R3hI2vtt7eMoaCtGhGiT4hVfZjp1eyEr5wQJxG/rPdQ==";
+
+    /// Start the Azurite blob service, returning it with its blob endpoint
+    pub fn spin_up_azurite() -> (Container<'static, clients::Cli, GenericImage>, String) {
+        static DOCKER_CLIENT: std::sync::OnceLock<clients::Cli> = std::sync::OnceLock::new();
+        let docker_client = DOCKER_CLIENT.get_or_init(clients::Cli::default);
+
+        let image = GenericImage::new("mcr.microsoft.com/azure-storage/azurite", "latest")
+            .with_wait_for(WaitFor::message_on_stdout("Azurite Blob service is successfully listening"))
+            .with_cmd(vec!["azurite-blob".to_string(), "--blobHost".to_string(), "0.0.0.0".to_string()]);
+        let container = docker_client.run(image);
+        let endpoint = format!("http://127.0.0.1:{}", container.get_host_port_ipv4(10000));
+        (container, endpoint)
+    }
+
+    /// Create a blob container, signing the request with the account key
+    /// (Azurite rejects anonymous writes and object stores cannot create containers)
+    pub async fn create_azurite_container(endpoint: &str, container: &str) -> Result<()> {
+        use base64::Engine;
+        use hmac::{Hmac, Mac};
+
+        let date = chrono::Utc::now().format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+        let version = "2021-08-06";
+        let string_to_sign = format!(
+            "PUT\n\n\n\n\n\n\n\n\n\n\n\nx-ms-date:{date}\nx-ms-version:{version}\n/{account}/{account}/{container}\nrestype:container",
+            account = AZURITE_ACCOUNT,
+        );
+        let key = base64::engine::general_purpose::STANDARD.decode(AZURITE_KEY)?;
+        let mut mac = Hmac::<sha2::Sha256>::new_from_slice(&key)?;
+        mac.update(string_to_sign.as_bytes());
+        let signature = base64::engine::general_purpose::STANDARD.encode(mac.finalize().into_bytes());
+
+        let response = reqwest::Client::new()
+            .put(format!("{}/{}/{}?restype=container", endpoint, AZURITE_ACCOUNT, container))
+            .header("x-ms-date", date)
+            .header("x-ms-version", version)
+            .header("Authorization", format!("SharedKey {}:{}", AZURITE_ACCOUNT, signature))
+            .send()
+            .await?;
+        anyhow::ensure!(response.status().is_success(), "Creating container failed: {}", response.status());
+        Ok(())
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -504,4 +550,1723 @@ mod integration {
 
         Ok(())
     }
-} 
\ No newline at end of file
+
+    // 19 --------------------------------------------------------------------
+    #[tokio::test]
+    #[ignore]
+    async fn azure_table_is_created_and_written_through_azurite() -> Result<()> {
+        use surgical_strike_writer::config::parse_config;
+        use surgical_strike_writer::SurgicalStrikeOrchestrator;
+
+        // • Start Azurite and create the container the table lives in.
+        let (_azurite, endpoint) = helpers::spin_up_azurite();
+        helpers::create_azurite_container(&endpoint, "delta").await?;
+        // The emulator endpoint is read from the environment by the object store.
+        env::set_var("AZURITE_BLOB_STORAGE_URL", &endpoint);
+
+        // • Configure credentials through the TOML [azure] section.
+        let mut configs = parse_config(&format!(
+            r#"
+            table_uri = "abfss://delta@{account}.dfs.core.windows.net/azure-table"
+
+            [azure]
+            use_emulator = true
+            account_name = "{account}"
+            credential = {{ type = "account_key", key = "{key}" }}
+
+            [[schema.columns]]
+            name = "id"
+            type = "long"
+            "#,
+            account = helpers::AZURITE_ACCOUNT,
+            key = helpers::AZURITE_KEY,
+        ))?;
+        let orchestrator = SurgicalStrikeOrchestrator::new(configs.remove(0)).await?;
+
+        // • Write two batches and read the row count back from the log.
+        for _ in 0..2 {
+            orchestrator.write_batch(df! {"id" => &[1i64, 2, 3]}?).await?;
+        }
+        let description = orchestrator.describe().await?;
+        assert_eq!(description.num_rows, Some(6));
+
+        Ok(())
+    }
+} 
+// ===========================================================================
+// LOGIC TESTS – pure planning/config logic, no infrastructure required
+// ===========================================================================
+mod logic {
+    use super::*;
+    use surgical_strike_writer::schema::{plan_schema_change, SchemaChange};
+    use surgical_strike_writer::SchemaEvolutionMode;
+
+    // 8 ---------------------------------------------------------------------
+    #[test]
+    fn schema_evolution_modes_plan_expected_changes() -> Result<()> {
+        let table = Schema::new(vec![Field::new("id", DataType::Int32, false)]);
+        let batch = Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("extra", DataType::Utf8, false),
+        ]);
+
+        // Default mode rejects the new column.
+        assert!(plan_schema_change(SchemaEvolutionMode::None, &table, &batch).is_err());
+
+        // add-columns adds it as a nullable field.
+        match plan_schema_change(SchemaEvolutionMode::AddColumns, &table, &batch)? {
+            SchemaChange::AddColumns(fields) => {
+                assert_eq!(fields.len(), 1);
+                assert_eq!(fields[0].name(), "extra");
+                assert!(fields[0].is_nullable());
+            }
+            other => panic!("unexpected plan: {:?}", other),
+        }
+
+        // Type changes are never allowed in add-columns mode.
+        let retyped = Schema::new(vec![Field::new("id", DataType::Utf8, false)]);
+        assert!(plan_schema_change(SchemaEvolutionMode::AddColumns, &table, &retyped).is_err());
+
+        // overwrite updates the schema in place, so it cannot retype either.
+        assert!(plan_schema_change(SchemaEvolutionMode::Overwrite, &table, &retyped).is_err());
+        assert!(matches!(
+            plan_schema_change(SchemaEvolutionMode::Overwrite, &table, &batch)?,
+            SchemaChange::AddColumns(_)
+        ));
+        Ok(())
+    }
+
+    // 9 ---------------------------------------------------------------------
+    #[test]
+    fn job_queue_claims_jobs_in_order_and_records_outcome() -> Result<()> {
+        use surgical_strike_writer::{JobKind, JobQueue, JobStatus};
+
+        let dir = tempfile::tempdir()?;
+        let queue = JobQueue::open(dir.path().join("jobs.db"))?;
+
+        let first = queue.submit("s3://bucket/a", &JobKind::Compact)?;
+        let vacuum = JobKind::Vacuum { retention_hours: Some(1), dry_run: true };
+        let second = queue.submit("s3://bucket/a", &vacuum)?;
+        queue.submit("s3://bucket/other", &JobKind::Compact)?;
+
+        let claimed = queue.claim_next("s3://bucket/a")?.expect("a queued job");
+        assert_eq!(claimed.id, first);
+        assert_eq!(claimed.status, JobStatus::Running);
+
+        queue.finish(first, &Ok("done".to_string()))?;
+        assert_eq!(queue.get(first)?.unwrap().status, JobStatus::Succeeded);
+
+        // A job left running by a crash goes back to the queue.
+        assert_eq!(queue.claim_next("s3://bucket/a")?.unwrap().id, second);
+        assert_eq!(queue.requeue_interrupted("s3://bucket/a")?, 1);
+        assert_eq!(queue.claim_next("s3://bucket/a")?.unwrap().kind, vacuum);
+        assert!(queue.claim_next("s3://bucket/a")?.is_none());
+
+        // Partition compactions and exports round-trip with their arguments.
+        let partition = JobKind::CompactPartitions { filters: vec!["day=2024-01-01".to_string()] };
+        let export = JobKind::Export {
+            version: Some(3),
+            format: surgical_strike_writer::ExportFormat::Csv,
+            output: "/tmp/a.csv".to_string(),
+        };
+        queue.submit("s3://bucket/a", &partition)?;
+        queue.submit("s3://bucket/a", &export)?;
+        assert_eq!(queue.claim_next("s3://bucket/a")?.unwrap().kind, partition);
+        assert_eq!(queue.claim_next("s3://bucket/a")?.unwrap().kind, export);
+        Ok(())
+    }
+
+    // 10 --------------------------------------------------------------------
+    #[test]
+    fn config_file_tables_override_shared_defaults() -> Result<()> {
+        use surgical_strike_writer::config::parse_config;
+
+        let configs = parse_config(
+            r#"
+            [storage_options]
+            AWS_REGION = "us-east-1"
+
+            [writer]
+            max_batch_size = 500
+            max_retries = 5
+
+            [[tables]]
+            table_uri = "s3://bucket/events"
+
+            [[tables]]
+            table_uri = "s3://bucket/metrics"
+            [tables.writer]
+            max_batch_size = 10
+            "#,
+        )?;
+
+        assert_eq!(configs.len(), 2);
+        assert_eq!(configs[0].writer.max_batch_size, 500);
+        assert_eq!(configs[1].writer.max_batch_size, 10);
+        // Keys not overridden by a table keep the shared value.
+        assert_eq!(configs[1].writer.max_retries, 5);
+        assert_eq!(configs[1].storage_options.0["AWS_REGION"], "us-east-1");
+
+        // Duplicate tables are rejected.
+        let duplicate = "[[tables]]\ntable_uri = \"s3://a\"\n[[tables]]\ntable_uri = \"s3://a\"\n";
+        assert!(parse_config(duplicate).is_err());
+        Ok(())
+    }
+
+    // 11 --------------------------------------------------------------------
+    #[test]
+    fn retry_backoff_grows_to_cap_and_classifies_errors() {
+        use surgical_strike_writer::retry::{is_retryable, non_retryable};
+        use surgical_strike_writer::WriterConfig;
+
+        let config = WriterConfig {
+            retry_delay_ms: 100,
+            retry_backoff_multiplier: 2.0,
+            max_retry_delay_ms: 500,
+            retry_jitter: 0.0,
+            ..Default::default()
+        };
+        assert_eq!(config.retry_backoff(1), Duration::from_millis(100));
+        assert_eq!(config.retry_backoff(2), Duration::from_millis(200));
+        assert_eq!(config.retry_backoff(3), Duration::from_millis(400));
+        assert_eq!(config.retry_backoff(4), Duration::from_millis(500));
+
+        // Jitter stays within the configured spread.
+        let jittered = WriterConfig { retry_jitter: 0.5, ..config };
+        let delay = jittered.retry_backoff(1);
+        assert!(delay >= Duration::from_millis(50) && delay <= Duration::from_millis(150));
+
+        assert!(is_retryable(&anyhow::anyhow!("503 Slow Down")));
+        let schema = non_retryable(anyhow::anyhow!("column type mismatch"));
+        assert!(!is_retryable(&schema));
+        assert!(!is_retryable(&schema.context("while writing batch")));
+    }
+
+    // 12 --------------------------------------------------------------------
+    #[test]
+    fn partition_tracker_ranks_hot_partitions_within_window() {
+        use deltalake::kernel::Add;
+        use surgical_strike_writer::partition_metrics::PartitionWriteTracker;
+
+        let add = |day: &str, size: i64, rows: i64, data_change: bool| Add {
+            path: format!("day={}/part-{}.parquet", day, size),
+            partition_values: HashMap::from([("day".to_string(), Some(day.to_string()))]),
+            size,
+            data_change,
+            stats: Some(format!(r#"{{"numRecords":{}}}"#, rows)),
+            ..Default::default()
+        };
+        let columns = vec!["day".to_string()];
+        let now = chrono::Utc::now();
+        let mut tracker = PartitionWriteTracker::new(Duration::from_secs(600));
+
+        tracker.record(now, &[add("2024-01-01", 100, 10, true), add("2024-01-02", 50, 40, true)], &columns);
+        tracker.record(now, &[add("2024-01-01", 200, 20, true)], &columns);
+        // Compaction rewrites are not new writes.
+        tracker.record(now, &[add("2024-01-03", 999, 999, false)], &columns);
+        // Outside the window.
+        tracker.record(now - chrono::Duration::hours(1), &[add("2024-01-04", 1, 500, true)], &columns);
+
+        let top = tracker.top(10, now);
+        assert_eq!(top.len(), 2);
+        assert_eq!(top[0].partition, "day=2024-01-02");
+        assert_eq!(top[0].rows, 40);
+        assert_eq!(top[1].partition, "day=2024-01-01");
+        assert_eq!((top[1].rows, top[1].bytes, top[1].files), (30, 300, 2));
+        assert_eq!(tracker.top(1, now).len(), 1);
+    }
+
+    // 13 --------------------------------------------------------------------
+    #[test]
+    fn import_globs_split_root_and_match_segments() {
+        use surgical_strike_writer::import::{glob_match, split_glob};
+
+        assert_eq!(
+            split_glob("s3://bucket/events/2023-*/*.parquet"),
+            ("s3://bucket/events/", "2023-*/*.parquet")
+        );
+        assert_eq!(split_glob("/data/history/"), ("/data/history/", ""));
+        assert_eq!(split_glob("/data/part-0.parquet"), ("/data/", "part-0.parquet"));
+
+        assert!(glob_match("2023-*/*.parquet", "2023-01/part-0.parquet"));
+        // `*` does not cross directories, `**` does.
+        assert!(!glob_match("*.parquet", "2023-01/part-0.parquet"));
+        assert!(glob_match("**/*.parquet", "2023/01/part-0.parquet"));
+        assert!(glob_match("**/*.parquet", "part-0.parquet"));
+        assert!(glob_match("part-?.parquet", "part-7.parquet"));
+        assert!(!glob_match("part-?.parquet", "part-17.parquet"));
+        assert!(!glob_match("*.parquet", "part-0.parquet.crc"));
+    }
+
+    // 14 --------------------------------------------------------------------
+    #[test]
+    fn latency_histogram_reports_quantiles_within_bucket_precision() {
+        use surgical_strike_writer::metrics::LatencyHistogram;
+
+        let histogram = LatencyHistogram::default();
+        assert_eq!(histogram.quantile_ms(0.99), 0.0);
+
+        for ms in 1..=100 {
+            histogram.record(Duration::from_millis(ms));
+        }
+        assert_eq!(histogram.count(), 100);
+        assert!((histogram.mean_ms() - 50.5).abs() < 1e-9);
+
+        // Buckets keep values within 1/16 of their true size.
+        let p50 = histogram.quantile_ms(0.5);
+        let p99 = histogram.quantile_ms(0.99);
+        assert!((50.0..=50.0 * 17.0 / 16.0).contains(&p50), "p50 = {}", p50);
+        assert!((99.0..=100.0).contains(&p99), "p99 = {}", p99);
+        assert_eq!(histogram.quantile_ms(1.0), 100.0);
+    }
+
+    // 15 --------------------------------------------------------------------
+    #[test]
+    fn data_tests_compile_to_queries_returning_offending_rows() {
+        use surgical_strike_writer::quality::test_sql;
+        use surgical_strike_writer::QualityTestKind;
+
+        let unique = test_sql(&QualityTestKind::Unique { columns: vec!["id".into(), "day".into()] });
+        assert_eq!(
+            unique,
+            "SELECT \"id\", \"day\", COUNT(*) AS occurrences FROM this GROUP BY \"id\", \"day\" HAVING COUNT(*) > 1"
+        );
+
+        // Embedded quotes are escaped rather than ending the identifier.
+        let not_null = test_sql(&QualityTestKind::NotNull { column: "odd\"name".into() });
+        assert_eq!(not_null, "SELECT * FROM this WHERE \"odd\"\"name\" IS NULL");
+
+        let bounded = test_sql(&QualityTestKind::RowCount { min: Some(1), max: None });
+        assert!(bounded.ends_with("WHERE row_count < 1"), "{}", bounded);
+        let unbounded = test_sql(&QualityTestKind::RowCount { min: None, max: None });
+        assert!(unbounded.ends_with("WHERE FALSE"), "{}", unbounded);
+
+        let relationship = test_sql(&QualityTestKind::Relationship {
+            column: "user_id".into(),
+            to_table_uri: "s3://bucket/users".into(),
+            to_column: "id".into(),
+        });
+        assert!(relationship.contains("LEFT JOIN ref ON this.\"user_id\" = ref.\"id\""));
+        assert!(relationship.ends_with("ref.\"id\" IS NULL"));
+    }
+
+    // 16 --------------------------------------------------------------------
+    #[test]
+    fn proc_files_parse_into_cpu_ticks_and_resident_memory() {
+        use surgical_strike_writer::metrics::{parse_cpu_ticks, parse_rss_bytes};
+
+        // A command name with spaces and parentheses must not shift the fields.
+        let stat = "4242 (writer (main) x) S 1 4242 4242 0 -1 4194560 \
+                    2100 0 0 0 350 120 0 0 20 0 9 0 12345 104857600 2560";
+        assert_eq!(parse_cpu_ticks(stat), Some(470));
+        assert_eq!(parse_cpu_ticks("4242 (truncated"), None);
+
+        let status = "Name:\twriter\nVmPeak:\t  204800 kB\nVmRSS:\t   10240 kB\nThreads:\t9\n";
+        assert_eq!(parse_rss_bytes(status), Some(10240 * 1024));
+        assert_eq!(parse_rss_bytes("Name:\tkthreadd\n"), None);
+    }
+
+    // 17 --------------------------------------------------------------------
+    #[test]
+    fn manifest_ranges_prune_only_files_proven_not_to_match() {
+        use serde_json::json;
+        use std::collections::HashMap;
+        use surgical_strike_writer::manifest::{FileRange, RangePredicate};
+
+        let file = |min: serde_json::Value, max: serde_json::Value| FileRange {
+            path: "part-0.parquet".into(),
+            size: 1024,
+            num_records: Some(10),
+            min: HashMap::from([("id".to_string(), min)]),
+            max: HashMap::from([("id".to_string(), max)]),
+        };
+
+        let range = RangePredicate::parse("id=10..20").unwrap();
+        assert_eq!((range.min.clone(), range.max.clone()), (Some(json!(10)), Some(json!(20))));
+        assert!(range.may_match(&file(json!(15), json!(30))));
+        assert!(range.may_match(&file(json!(20), json!(20))));
+        assert!(!range.may_match(&file(json!(21), json!(30))));
+        assert!(!range.may_match(&file(json!(0), json!(9))));
+
+        // Unquoted bounds that are not JSON are strings, compared lexicographically.
+        let day = RangePredicate::parse("day=2024-01-02").unwrap();
+        let days = FileRange {
+            min: HashMap::from([("day".to_string(), json!("2024-01-01"))]),
+            max: HashMap::from([("day".to_string(), json!("2024-01-03"))]),
+            ..file(json!(0), json!(0))
+        };
+        assert!(day.may_match(&days));
+        assert!(!RangePredicate::parse("day=2024-02-01..").unwrap().may_match(&days));
+
+        // Missing statistics never prune.
+        assert!(RangePredicate::parse("other=..5").unwrap().may_match(&days));
+        assert!(RangePredicate::parse("no-equals-sign").is_err());
+    }
+
+    // 18 --------------------------------------------------------------------
+    #[test]
+    fn declared_column_types_parse_to_delta_types() {
+        use deltalake::kernel::{DataType, PrimitiveType};
+        use surgical_strike_writer::schema::parse_delta_type;
+
+        let primitive = |name: &str| match parse_delta_type(name).unwrap() {
+            DataType::Primitive(p) => p,
+            other => panic!("{} parsed to non-primitive {:?}", name, other),
+        };
+        assert_eq!(primitive("long"), PrimitiveType::Long);
+        assert_eq!(primitive("BIGINT"), PrimitiveType::Long);
+        assert_eq!(primitive(" string "), PrimitiveType::String);
+        assert_eq!(primitive("timestamp_ntz"), PrimitiveType::TimestampNtz);
+        assert!(matches!(primitive("decimal(10, 2)"), PrimitiveType::Decimal(_)));
+
+        assert!(parse_delta_type("decimal(10)").is_err());
+        assert!(parse_delta_type("map<string,long>").is_err());
+    }
+
+    // 20 --------------------------------------------------------------------
+    #[test]
+    fn registered_avro_records_map_to_polars_schemas() {
+        use polars::prelude::{DataType as PolarsType, TimeUnit};
+        use surgical_strike_writer::schema::avro_record_to_polars;
+
+        let record = serde_json::json!({
+            "type": "record",
+            "name": "Order",
+            "fields": [
+                {"name": "id", "type": "long"},
+                {"name": "note", "type": ["null", "string"]},
+                {"name": "amount", "type": "double"},
+                {"name": "placed_at", "type": {"type": "long", "logicalType": "timestamp-millis"}},
+            ],
+        });
+        let schema = avro_record_to_polars(&record).unwrap();
+        assert_eq!(schema.get("id"), Some(&PolarsType::Int64));
+        assert_eq!(schema.get("note"), Some(&PolarsType::String));
+        assert_eq!(schema.get("amount"), Some(&PolarsType::Float64));
+        assert_eq!(schema.get("placed_at"), Some(&PolarsType::Datetime(TimeUnit::Milliseconds, None)));
+
+        let union = serde_json::json!({"type": "record", "fields": [{"name": "x", "type": ["int", "string"]}]});
+        assert!(avro_record_to_polars(&union).is_err());
+        assert!(avro_record_to_polars(&serde_json::json!({"type": "string"})).is_err());
+    }
+
+    // 22 --------------------------------------------------------------------
+    #[tokio::test]
+    async fn flush_slots_are_shared_in_proportion_to_weights() -> Result<()> {
+        use surgical_strike_writer::FlushScheduler;
+
+        let scheduler = FlushScheduler::new(1);
+        scheduler.register("busy", 3);
+        scheduler.register("quiet", 1);
+        let order = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        // • Hold the only slot while both tables queue up flushes.
+        let held = scheduler.acquire("busy").await;
+        let mut tasks = Vec::new();
+        for table in ["busy", "quiet"] {
+            for _ in 0..8 {
+                let (scheduler, order) = (scheduler.clone(), order.clone());
+                tasks.push(tokio::spawn(async move {
+                    let _slot = scheduler.acquire(table).await;
+                    order.lock().unwrap().push(table);
+                }));
+            }
+        }
+        sleep(Duration::from_millis(20)).await;
+        drop(held);
+        for task in tasks {
+            task.await?;
+        }
+
+        // • While both are backlogged the busy table gets three slots for
+        //   every one of the quiet table, which is never left waiting long.
+        let order = order.lock().unwrap();
+        assert_eq!(order.len(), 16);
+        assert_eq!(order[0], "quiet");
+        assert_eq!(order[..8].iter().filter(|table| **table == "busy").count(), 6);
+
+        Ok(())
+    }
+
+    // 23 --------------------------------------------------------------------
+    #[test]
+    fn string_columns_get_the_tightest_type_all_values_parse_as() {
+        use surgical_strike_writer::type_inference::{infer_type, SuggestedType};
+
+        assert_eq!(infer_type(["true", "FALSE"]), Some(SuggestedType::Boolean));
+        assert_eq!(infer_type(["12", "-7", "+300"]), Some(SuggestedType::Long));
+        assert_eq!(
+            infer_type(["12.5", "-0.125", "1000"]),
+            Some(SuggestedType::Decimal { precision: 7, scale: 3 })
+        );
+        assert_eq!(infer_type(["1e3", "2.5"]), Some(SuggestedType::Double));
+        assert_eq!(infer_type(["2024-02-29", "2023-12-31"]), Some(SuggestedType::Date));
+        assert_eq!(
+            infer_type(["2024-02-29T10:00:00Z", "2024-03-01 08:30:00.250"]),
+            Some(SuggestedType::Timestamp)
+        );
+
+        // • Leading zeros are significant (zip codes, account ids), and one
+        //   free-text value keeps the column a string.
+        assert_eq!(infer_type(["02134", "10001"]), None);
+        assert_eq!(infer_type(["12", "n/a"]), None);
+        assert_eq!(infer_type(std::iter::empty()), None);
+    }
+
+    // 24 --------------------------------------------------------------------
+    #[test]
+    fn shared_alert_routes_match_by_type_and_table() -> Result<()> {
+        use surgical_strike_writer::config::{parse_config, AlertDestination, AlertKind};
+
+        let configs = parse_config(
+            r#"
+            [[alerts.routes]]
+            alerts = ["sla_miss", "circuit_open"]
+            tables = ["events"]
+            destination = { type = "pagerduty", routing_key = "events-team" }
+
+            [[alerts.routes]]
+            destination = { type = "log" }
+            cooldown_secs = 60
+
+            [[tables]]
+            table_uri = "s3://bucket/events"
+
+            [[tables]]
+            table_uri = "s3://bucket/metrics"
+            "#,
+        )?;
+
+        // • Both tables share the top-level routes.
+        let routes = &configs[1].alerts.routes;
+        assert_eq!(routes.len(), 2);
+        assert_eq!(routes[0].destination, AlertDestination::Pagerduty { routing_key: "events-team".into() });
+        assert_eq!(routes[0].cooldown_secs, 300);
+
+        // • The paging route only takes its alert types for its own table;
+        //   the catch-all route takes everything.
+        assert!(routes[0].matches(AlertKind::SlaMiss, "events"));
+        assert!(!routes[0].matches(AlertKind::SlaMiss, "metrics"));
+        assert!(!routes[0].matches(AlertKind::VacuumOverrun, "events"));
+        assert!(routes[1].matches(AlertKind::ValidationSpike, "metrics"));
+        Ok(())
+    }
+
+    // 25 --------------------------------------------------------------------
+    #[test]
+    fn write_shards_keep_each_partition_on_one_worker() -> Result<()> {
+        use deltalake::arrow::array::{Int64Array, StringArray};
+        use surgical_strike_writer::write_pool::shard_batch;
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("day", DataType::Utf8, false),
+            Field::new("value", DataType::Int64, false),
+        ]));
+        let days: Vec<String> = (0..100).map(|i| format!("2024-01-{:02}", i % 7 + 1)).collect();
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(days)),
+                Arc::new(Int64Array::from_iter_values(0..100)),
+            ],
+        )?;
+
+        // • Every row is written exactly once, and no day is split across shards.
+        let shards = shard_batch(&batch, &["day".to_string()], 4)?;
+        assert!(shards.len() > 1 && shards.len() <= 4);
+        assert_eq!(shards.iter().map(|s| s.num_rows()).sum::<usize>(), 100);
+        let mut seen = std::collections::HashSet::new();
+        for shard in &shards {
+            let days = shard.column(0).as_any().downcast_ref::<StringArray>().unwrap();
+            let mut shard_days: Vec<&str> = days.iter().flatten().collect();
+            shard_days.sort();
+            shard_days.dedup();
+            for day in shard_days {
+                assert!(seen.insert(day.to_string()), "{} written by two workers", day);
+            }
+        }
+
+        // • Unpartitioned batches are cut into contiguous slices.
+        let slices = shard_batch(&batch, &[], 3)?;
+        assert_eq!(slices.iter().map(|s| s.num_rows()).collect::<Vec<_>>(), vec![34, 34, 32]);
+        Ok(())
+    }
+
+    // 26 --------------------------------------------------------------------
+    #[test]
+    fn guardrail_violations_are_typed_and_never_retried() {
+        use surgical_strike_writer::guardrails::{GuardrailViolation, Guardrails};
+        use surgical_strike_writer::GuardrailsConfig;
+        use surgical_strike_writer::retry::is_retryable;
+
+        let guardrails = Guardrails::new(GuardrailsConfig {
+            max_columns: Some(3),
+            max_files_per_commit: Some(10),
+            max_commits_per_minute: Some(2),
+        });
+
+        // • Limits are inclusive.
+        assert!(guardrails.check_columns(3).is_ok());
+        assert_eq!(
+            guardrails.check_columns(4),
+            Err(GuardrailViolation::TooManyColumns { columns: 4, limit: 3 })
+        );
+        assert!(guardrails.check_files(10).is_ok());
+        assert!(guardrails.check_files(11).is_err());
+
+        // • The rate limit counts recorded commits.
+        guardrails.record_commit();
+        assert!(guardrails.check_commit_rate().is_ok());
+        guardrails.record_commit();
+        assert_eq!(
+            guardrails.check_commit_rate(),
+            Err(GuardrailViolation::CommitRateExceeded { commits: 2 })
+        );
+
+        // • A violation fails the write at once, even under added context.
+        let error = anyhow::Error::from(guardrails.check_files(11).unwrap_err()).context("Failed to write batch");
+        assert!(!is_retryable(&error));
+        assert!(error.chain().any(|cause| cause.is::<GuardrailViolation>()));
+
+        // • Without limits nothing is enforced.
+        let unlimited = Guardrails::default();
+        assert!(unlimited.check_columns(10_000).is_ok());
+        assert!(unlimited.check_commit_rate().is_ok());
+    }
+
+    // 27 --------------------------------------------------------------------
+    #[test]
+    fn flushes_are_sorted_by_the_configured_columns() -> Result<()> {
+        use deltalake::arrow::array::{Int64Array, StringArray};
+        use surgical_strike_writer::write_pool::sort_batch;
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("device_id", DataType::Utf8, true),
+            Field::new("ts", DataType::Int64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec![Some("b"), Some("a"), None, Some("b"), Some("a")])),
+                Arc::new(Int64Array::from(vec![2, 3, 5, 1, 1])),
+            ],
+        )?;
+
+        // • Rows are ordered by device, then time, with nulls first.
+        let sorted = sort_batch(&batch, &["device_id".to_string(), "ts".to_string()])?;
+        let devices = sorted.column(0).as_any().downcast_ref::<StringArray>().unwrap();
+        let ts = sorted.column(1).as_any().downcast_ref::<Int64Array>().unwrap();
+        assert_eq!(devices.iter().collect::<Vec<_>>(), vec![None, Some("a"), Some("a"), Some("b"), Some("b")]);
+        assert_eq!(ts.values().to_vec(), vec![5, 1, 3, 1, 2]);
+
+        // • No sort columns leaves the batch as it came; unknown ones are an error.
+        assert_eq!(sort_batch(&batch, &[])?, batch);
+        assert!(sort_batch(&batch, &["missing".to_string()]).is_err());
+        Ok(())
+    }
+
+    // 28 --------------------------------------------------------------------
+    #[test]
+    fn registered_files_take_partition_values_from_their_path() -> Result<()> {
+        use surgical_strike_writer::register::partition_values_from_path;
+
+        let columns = vec!["day".to_string(), "region".to_string()];
+
+        // • Values come from `column=value` directories, in partition column order.
+        let values = partition_values_from_path("raw/region=eu/day=2024-01-01/part-0.parquet", &columns)?;
+        assert_eq!(
+            values,
+            vec![
+                ("day".to_string(), Some("2024-01-01".to_string())),
+                ("region".to_string(), Some("eu".to_string())),
+            ]
+        );
+
+        // • Hive escapes and its null directory are understood.
+        let values = partition_values_from_path(
+            "day=2024-01-01%2012%3A00/region=__HIVE_DEFAULT_PARTITION__/part-0.parquet",
+            &columns,
+        )?;
+        assert_eq!(values[0].1.as_deref(), Some("2024-01-01 12:00"));
+        assert_eq!(values[1].1, None);
+
+        // • A file outside the partition layout cannot be registered.
+        assert!(partition_values_from_path("raw/day=2024-01-01/part-0.parquet", &columns).is_err());
+        Ok(())
+    }
+
+    // 29 --------------------------------------------------------------------
+    #[test]
+    fn invalid_rows_are_split_off_with_the_rule_they_broke() -> Result<()> {
+        use surgical_strike_writer::validation::{split_invalid, VIOLATION_COLUMN};
+        use surgical_strike_writer::ValidationRule;
+
+        let df = DataFrame::new(vec![
+            Series::new("id".into(), &[Some("a1"), None, Some("b2"), Some("zz")]).into(),
+            Series::new("amount".into(), &[10.0, 5.0, -1.0, 3.0]).into(),
+            Series::new("status".into(), &["ok", "ok", "ok", "lost"]).into(),
+        ])?;
+        let rules = vec![
+            ValidationRule::NotNull { column: "id".to_string() },
+            ValidationRule::Range { column: "amount".to_string(), min: Some(0.0), max: None },
+            ValidationRule::Regex { column: "id".to_string(), pattern: "[a-z][0-9]".to_string() },
+            ValidationRule::AllowedValues { column: "status".to_string(), values: vec!["ok".to_string()] },
+        ];
+
+        // • Only the first row passes; the others carry the first rule they broke.
+        let (valid, invalid) = split_invalid(df.clone(), &rules)?;
+        assert_eq!(valid.height(), 1);
+        let invalid = invalid.expect("rows should have been quarantined");
+        let reasons: Vec<_> = invalid.column(VIOLATION_COLUMN)?.str()?.into_iter().flatten().collect();
+        assert_eq!(reasons, vec!["id is null", "amount outside [0, -]", "id does not match [a-z][0-9]"]);
+
+        // • Without violations the batch is returned untouched.
+        let (valid, invalid) = split_invalid(df.head(Some(1)), &rules)?;
+        assert_eq!(valid.height(), 1);
+        assert!(invalid.is_none());
+        Ok(())
+    }
+
+    // 30 --------------------------------------------------------------------
+    #[test]
+    fn sequence_gaps_are_tracked_per_stream() -> Result<()> {
+        use surgical_strike_writer::sequence::SequenceTracker;
+        use surgical_strike_writer::SequenceTrackingConfig;
+
+        let mut tracker = SequenceTracker::new(SequenceTrackingConfig {
+            column: "seq".to_string(),
+            key_columns: vec!["producer".to_string()],
+            gaps_table_uri: None,
+            report_interval_secs: 60,
+        });
+        let df = DataFrame::new(vec![
+            Series::new("producer".into(), &["a", "a", "a", "a", "a", "a", "b"]).into(),
+            Series::new("seq".into(), &[1i64, 2, 5, 3, 3, 8, 40]).into(),
+        ])?;
+        tracker.observe(&tracker.sequences(&df)?);
+        let report = tracker.report();
+
+        // • Each producer is numbered independently, starting where it was first seen.
+        assert_eq!(report.streams.len(), 2);
+        let a = &report.streams[0];
+        assert_eq!((a.key.as_str(), a.last_sequence, a.received), ("a", 8, 6));
+
+        // • 3 arrived late and filled part of a gap; its second delivery is a duplicate.
+        assert_eq!((a.out_of_order, a.duplicates), (1, 1));
+        assert_eq!(a.missing, 3);
+        assert_eq!(a.open_gaps, vec![(4, 4), (6, 7)]);
+
+        let b = &report.streams[1];
+        assert_eq!((b.key.as_str(), b.missing), ("b", 0));
+        assert_eq!(report.to_dataframe()?.height(), 2);
+        Ok(())
+    }
+
+    // 31 --------------------------------------------------------------------
+    #[test]
+    fn compaction_concurrency_cap_keeps_at_least_one_task() {
+        use surgical_strike_writer::CompactionConfig;
+
+        let mut config = CompactionConfig::default();
+
+        // • Unconfigured, delta-rs keeps its own concurrency.
+        assert_eq!(config.concurrent_tasks(), None);
+
+        // • A configured cap is passed through.
+        config.max_concurrent_tasks = Some(4);
+        assert_eq!(config.concurrent_tasks(), Some(4));
+
+        // • A cap of zero still rewrites one group at a time.
+        config.max_concurrent_tasks = Some(0);
+        assert_eq!(config.concurrent_tasks(), Some(1));
+    }
+
+    // 32 --------------------------------------------------------------------
+    #[test]
+    fn time_travel_days_derive_vacuum_retention() -> Result<()> {
+        use surgical_strike_writer::config::parse_config;
+        use surgical_strike_writer::retention::parse_interval;
+
+        let configs = parse_config(
+            r#"
+            time_travel_days = 14
+
+            [[tables]]
+            table_uri = "s3://bucket/events"
+
+            [[tables]]
+            table_uri = "s3://bucket/metrics"
+            [tables.vacuum]
+            retention_hours = 720
+            "#,
+        )?;
+
+        // • Without an explicit retention vacuum keeps exactly the guaranteed window.
+        assert_eq!(configs[0].vacuum.retention_hours, 14 * 24);
+        // • A longer explicit retention is kept as written.
+        assert_eq!(configs[1].vacuum.retention_hours, 720);
+
+        // • A shorter override is refused rather than silently weakening the guarantee.
+        let conflicting = "time_travel_days = 14
+[[tables]]
+table_uri = \"s3://a\"
+[tables.vacuum]
+retention_hours = 72
+";
+        assert!(parse_config(conflicting).is_err());
+
+        // • Delta interval strings are read in any of their units.
+        assert_eq!(parse_interval("interval 2 weeks"), Some(chrono::Duration::days(14)));
+        assert_eq!(parse_interval("interval 1 day"), Some(chrono::Duration::days(1)));
+        assert_eq!(parse_interval("30 days"), None);
+        Ok(())
+    }
+
+    // 33 --------------------------------------------------------------------
+    #[test]
+    fn optimize_metrics_read_in_spark_shape() {
+        use surgical_strike_writer::spark_history::spark_operation_metrics;
+
+        let metrics = serde_json::json!({
+            "numFilesAdded": 1,
+            "numFilesRemoved": 4,
+            "filesAdded": "{\"avg\":1287.0,\"max\":1287,\"min\":1287,\"totalFiles\":1,\"totalSize\":1287}",
+            "filesRemoved": {"avg": 611.0, "max": 700, "min": 500, "totalFiles": 4, "totalSize": 2444},
+            "preserveInsertionOrder": true
+        });
+        let spark = spark_operation_metrics(Some("OPTIMIZE"), metrics.as_object().unwrap());
+
+        // • Spark's names sit next to the delta-rs ones, every value a string.
+        assert_eq!(spark["numAddedFiles"], "1");
+        assert_eq!(spark["numFilesAdded"], "1");
+        assert_eq!(spark["numRemovedFiles"], "4");
+        assert_eq!(spark["preserveInsertionOrder"], "true");
+
+        // • File size summaries become Spark's flat byte counts.
+        assert_eq!(spark["numAddedBytes"], "1287");
+        assert_eq!(spark["numRemovedBytes"], "2444");
+        assert_eq!((spark["minFileSize"].as_str(), spark["maxFileSize"].as_str()), ("1287", "1287"));
+
+        // • Renames only apply to the operation they belong to.
+        let write = spark_operation_metrics(Some("WRITE"), metrics.as_object().unwrap());
+        assert!(!write.contains_key("numAddedFiles"));
+    }
+
+    // 34 --------------------------------------------------------------------
+    #[test]
+    fn enrich_transform_joins_cached_lookup_rows() -> Result<()> {
+        use surgical_strike_writer::enrichment::LookupTable;
+        use surgical_strike_writer::transform::apply_transforms;
+        use surgical_strike_writer::Transform;
+
+        let sites = DataFrame::new(vec![
+            Series::new("id".into(), &["d1", "d2"]).into(),
+            Series::new("site".into(), &["berlin", "lisbon"]).into(),
+        ])?;
+        let lookups = HashMap::from([(
+            "devices".to_string(),
+            LookupTable { key_column: "id".to_string(), rows: Arc::new(sites) },
+        )]);
+        let batch = DataFrame::new(vec![
+            Series::new("device_id".into(), &["d2", "d9", "d1"]).into(),
+            Series::new("site".into(), &["stale", "stale", "stale"]).into(),
+        ])?;
+
+        let enriched = apply_transforms(
+            batch,
+            &[Transform::Enrich { lookup: "devices".to_string(), on: "device_id".to_string() }],
+            &lookups,
+        )?;
+
+        // • Every row is kept in order; the lookup replaces the batch's own column.
+        assert_eq!(enriched.height(), 3);
+        let site: Vec<Option<&str>> = enriched.column("site")?.str()?.into_iter().collect();
+        assert_eq!(site, vec![Some("lisbon"), None, Some("berlin")]);
+        assert!(enriched.column("id").is_err());
+        Ok(())
+    }
+
+    // 35 --------------------------------------------------------------------
+    #[tokio::test]
+    async fn start_plan_previews_creation_without_writing() -> Result<()> {
+        use surgical_strike_writer::plan::plan_start;
+
+        let dir = tempfile::tempdir()?;
+        let uri = format!("file://{}/events", dir.path().display());
+        let mut configs = vec![common::local_config(&uri, &[("id", "long")], "")?];
+        configs[0].stats_columns = vec!["id".to_string()];
+        let plan = plan_start(&configs).await?;
+
+        // • A missing table with a declared schema is planned for creation, not created.
+        assert_eq!(plan.tables[0].version, None);
+        assert_eq!(plan.tables[0].changes.len(), 1);
+        assert!(!dir.path().join("events").join("_delta_log").exists());
+        assert!(!plan.has_problems());
+
+        // • Without a schema, the plan reports that startup would fail.
+        configs[0].schema = None;
+        let plan = plan_start(&configs).await?;
+        assert!(plan.has_problems());
+        assert!(plan.to_string().contains("! The table does not exist"));
+        Ok(())
+    }
+
+    // 36 --------------------------------------------------------------------
+    #[tokio::test]
+    async fn writer_control_pauses_until_resumed() -> Result<()> {
+        use surgical_strike_writer::WriterControl;
+
+        let control = Arc::new(WriterControl::default());
+
+        // • Pausing twice reports that the second call changed nothing.
+        assert!(control.pause());
+        assert!(!control.pause());
+        assert!(control.is_paused());
+
+        // • Waiters are released by resume, not before.
+        let waiter = tokio::spawn({
+            let control = control.clone();
+            async move { control.resumed().await }
+        });
+        sleep(Duration::from_millis(50)).await;
+        assert!(!waiter.is_finished());
+        assert!(control.resume());
+        tokio::time::timeout(Duration::from_secs(1), waiter).await??;
+        assert!(!control.resume());
+        Ok(())
+    }
+
+    // 37 --------------------------------------------------------------------
+    #[tokio::test]
+    async fn table_profile_aggregates_file_statistics() -> Result<()> {
+        let table = common::local_table(&[("id", "long"), ("name", "string")], "").await?;
+        let orchestrator = &table.orchestrator;
+        orchestrator.write_batch(polars::df! {"id" => &[1i64, 2, 3], "name" => &[Some("b"), None, Some("a")]}?).await?;
+        orchestrator.write_batch(polars::df! {"id" => &[10i64], "name" => &[Some("c")]}?).await?;
+
+        let profile = orchestrator.profile().await?;
+        assert_eq!(profile.num_files, 2);
+        assert_eq!(profile.num_rows, Some(4));
+
+        // • Bounds span both files; integer NDV is capped by the non-null rows.
+        let id = &profile.columns[0];
+        assert_eq!((id.min.clone(), id.max.clone()), (Some(1.into()), Some(10.into())));
+        assert_eq!(id.approx_ndv, Some(4));
+
+        // • Null counts add up across files into a fraction of all rows.
+        let name = &profile.columns[1];
+        assert_eq!(name.min, Some("a".into()));
+        assert_eq!(name.null_count, Some(1));
+        assert_eq!(name.null_fraction, Some(0.25));
+        Ok(())
+    }
+
+    // 38 --------------------------------------------------------------------
+    #[test]
+    fn cron_schedule_finds_the_next_off_peak_run() -> Result<()> {
+        use chrono::{TimeZone, Utc};
+        use surgical_strike_writer::schedule::CronSchedule;
+
+        // • Every 30 minutes between 01:00 and 04:59 on weekdays.
+        let schedule: CronSchedule = "*/30 1-4 * * 1-5".parse()?;
+        let friday_noon = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        assert_eq!(schedule.next_after(friday_noon), Some(Utc.with_ymd_and_hms(2024, 3, 4, 1, 0, 0).unwrap()));
+        let monday_run = Utc.with_ymd_and_hms(2024, 3, 4, 1, 0, 0).unwrap();
+        assert_eq!(schedule.next_after(monday_run), Some(Utc.with_ymd_and_hms(2024, 3, 4, 1, 30, 0).unwrap()));
+
+        // • Restricting both day fields fires on either; impossible dates never fire.
+        let either: CronSchedule = "0 0 1 * 0".parse()?;
+        assert_eq!(either.next_after(friday_noon), Some(Utc.with_ymd_and_hms(2024, 3, 3, 0, 0, 0).unwrap()));
+        assert_eq!("0 0 30 2 *".parse::<CronSchedule>()?.next_after(friday_noon), None);
+
+        // • Malformed expressions are rejected when the config is parsed.
+        assert!("0 25 * * *".parse::<CronSchedule>().is_err());
+        assert!("0 * * *".parse::<CronSchedule>().is_err());
+        Ok(())
+    }
+
+    // 39 --------------------------------------------------------------------
+    #[test]
+    fn state_dir_detects_torn_entries() -> Result<()> {
+        use surgical_strike_writer::{EntryStatus, FsyncPolicy, StateDir};
+
+        let dir = tempfile::tempdir()?;
+        let state = StateDir::new(dir.path(), FsyncPolicy::Full);
+
+        // • Entries round-trip and are replaced whole.
+        state.write_json("orders/offsets", &vec![1u64, 2])?;
+        state.write_json("orders/offsets", &vec![3u64])?;
+        assert_eq!(state.read_json::<Vec<u64>>("orders/offsets")?, Some(vec![3]));
+        assert_eq!(state.read("missing")?, None);
+        assert!(state.write("../escape", b"x").is_err());
+
+        // • A damaged payload is reported, not parsed.
+        state.write("cache", b"hello")?;
+        let path = dir.path().join("cache.state");
+        let mut bytes = std::fs::read(&path)?;
+        *bytes.last_mut().unwrap() = b'!';
+        std::fs::write(&path, bytes)?;
+        assert!(state.read("cache").is_err());
+
+        // • Inspect shows every entry's health and interrupted writes.
+        std::fs::write(dir.path().join("wal.state.tmp-1"), b"partial")?;
+        let entries = state.inspect()?;
+        let statuses: Vec<_> = entries.iter().map(|e| (e.name.as_str(), e.status.clone())).collect();
+        assert_eq!(statuses[0], ("cache", EntryStatus::Corrupt("checksum mismatch".to_string())));
+        assert_eq!(statuses[1], ("orders/offsets", EntryStatus::Ok));
+        assert_eq!(statuses[2], ("wal.state.tmp-1", EntryStatus::Incomplete));
+
+        // • Reset removes single entries or everything.
+        assert_eq!(state.reset(&["cache".to_string()])?, 1);
+        assert_eq!(state.reset(&[])?, 2);
+        assert!(state.inspect()?.is_empty());
+        Ok(())
+    }
+
+    // 40 --------------------------------------------------------------------
+    #[tokio::test]
+    async fn schema_overwrite_keeps_existing_rows() -> Result<()> {
+        let table = common::local_table(&[("id", "long")], "[writer]\nschema_evolution = \"overwrite\"").await?;
+        let orchestrator = &table.orchestrator;
+        orchestrator.write_batch(polars::df! {"id" => &[1i64, 2]}?).await?;
+
+        // • A wider batch adds its column next to the rows already there.
+        orchestrator.write_batch(polars::df! {"id" => &[3i64], "extra" => &["x"]}?).await?;
+        let profile = orchestrator.profile().await?;
+        assert_eq!(profile.num_rows, Some(3));
+        assert_eq!(profile.columns.iter().map(|c| c.column.as_str()).collect::<Vec<_>>(), ["id", "extra"]);
+        assert_eq!(profile.columns[1].null_count, Some(2));
+
+        // • A retyped column is refused instead of replacing the table.
+        assert!(orchestrator.write_batch(polars::df! {"id" => &["four"]}?).await.is_err());
+        assert_eq!(orchestrator.profile().await?.num_rows, Some(3));
+        Ok(())
+    }
+
+    // 41 --------------------------------------------------------------------
+    #[tokio::test]
+    async fn position_only_batches_commit_their_checkpoints() -> Result<()> {
+        use deltalake::kernel::Transaction;
+        use surgical_strike_writer::sources::{committed_positions, SourceBatch};
+
+        let local = common::local_table(&[("id", "long")], "").await?;
+        let orchestrator = &local.orchestrator;
+
+        // • One row at offset 0, then only tombstones up to 5.
+        let source = common::ScriptedSource::new(vec![
+            SourceBatch {
+                df: polars::df! {"id" => &[1i64]}?,
+                checkpoints: vec![Transaction::new("scripted-0", 1)],
+                metadata: Vec::new(),
+            },
+            SourceBatch {
+                df: DataFrame::empty(),
+                checkpoints: vec![Transaction::new("scripted-0", 5)],
+                metadata: Vec::new(),
+            },
+        ]);
+        orchestrator.ingest(source).await?;
+
+        // • The position advances past the tombstones without adding rows.
+        let table = open_table(&local.uri).await?;
+        assert_eq!(committed_positions(&table, "scripted-")["scripted-0"], 5);
+        assert_eq!(orchestrator.profile().await?.num_rows, Some(1));
+        Ok(())
+    }
+
+    // 42 --------------------------------------------------------------------
+    #[tokio::test]
+    async fn http_controls_require_the_configured_token() -> Result<()> {
+        use std::collections::BTreeMap;
+        use std::future::IntoFuture;
+        use surgical_strike_writer::config::HttpConfig;
+        use surgical_strike_writer::server::{router, ApiState};
+        use tokio_util::sync::CancellationToken;
+
+        // • Served with no tables: authorized requests get as far as the lookup.
+        let config = HttpConfig { auth_token: Some("s3cret".to_string()), ..Default::default() };
+        assert!(config.bind_address.starts_with("127.0.0.1:"));
+        let state = ApiState {
+            tables: Arc::new(BTreeMap::new()),
+            process_metrics: Default::default(),
+            shutdown: CancellationToken::new(),
+        };
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let base = format!("http://{}", listener.local_addr()?);
+        tokio::spawn(axum::serve(listener, router(state, &config)).into_future());
+        let http = reqwest::Client::new();
+
+        // • Writes without the token, or with another one, are refused.
+        let flush = format!("{}/tables/orders/flush", base);
+        assert_eq!(http.post(&flush).send().await?.status(), 401);
+        assert_eq!(http.post(&flush).bearer_auth("guess").send().await?.status(), 401);
+        assert_eq!(http.post(&flush).bearer_auth("s3cret").send().await?.status(), 404);
+
+        // • Reads stay open.
+        assert_eq!(http.get(format!("{}/tables", base)).send().await?.status(), 200);
+        Ok(())
+    }
+
+    // 43 --------------------------------------------------------------------
+    #[cfg(feature = "grpc")]
+    #[test]
+    fn admin_messages_match_the_proto_file() -> Result<()> {
+        use prost::Message;
+        use std::collections::BTreeMap;
+        use surgical_strike_writer::admin::*;
+
+        // Field number -> wire type of every message declared in the proto
+        let proto = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/proto/admin.proto"))?;
+        let field = regex::Regex::new(r"^(optional |repeated )?(map<[^>]+>|\w+) \w+ = (\d+);")?;
+        let mut declared: BTreeMap<String, BTreeMap<u64, u64>> = BTreeMap::new();
+        let mut current = None;
+        for line in proto.lines().map(|line| line.split("//").next().unwrap_or_default().trim()) {
+            if let Some(name) = line.strip_prefix("message ") {
+                current = Some(name.trim_end_matches('{').trim().to_string());
+                declared.entry(current.clone().unwrap()).or_default();
+            } else if line == "}" {
+                current = None;
+            } else if let (Some(message), Some(captures)) = (&current, field.captures(line)) {
+                let wire_type = match &captures[2] {
+                    _ if captures.get(1).is_some_and(|m| m.as_str() == "repeated ") => 2,
+                    "double" | "fixed64" | "sfixed64" => 1,
+                    "float" | "fixed32" | "sfixed32" => 5,
+                    "string" | "bytes" => 2,
+                    t if t.starts_with("map<") => 2,
+                    _ => 0,
+                };
+                declared.get_mut(message).unwrap().insert(captures[3].parse()?, wire_type);
+            }
+        }
+
+        // Field number -> wire type found in an encoding with every field set
+        fn encoded_fields(mut bytes: &[u8]) -> BTreeMap<u64, u64> {
+            fn varint(bytes: &mut &[u8]) -> u64 {
+                let (mut value, mut shift) = (0, 0);
+                loop {
+                    let byte = bytes[0];
+                    *bytes = &bytes[1..];
+                    value |= u64::from(byte & 0x7f) << shift;
+                    if byte < 0x80 {
+                        return value;
+                    }
+                    shift += 7;
+                }
+            }
+            let mut fields = BTreeMap::new();
+            while !bytes.is_empty() {
+                let key = varint(&mut bytes);
+                let skip = match key & 7 {
+                    0 => {
+                        varint(&mut bytes);
+                        0
+                    }
+                    1 => 8,
+                    2 => varint(&mut bytes) as usize,
+                    5 => 4,
+                    other => panic!("unexpected wire type {}", other),
+                };
+                bytes = &bytes[skip..];
+                fields.insert(key >> 3, key & 7);
+            }
+            fields
+        }
+
+        let (s, n) = (|| "x".to_string(), 7);
+        let encoded: BTreeMap<String, Vec<u8>> = [
+            ("WriteBatchRequest", WriteBatchRequest { table: s(), arrow_ipc: vec![1], batch_id: s() }.encode_to_vec()),
+            ("WriteBatchResponse", WriteBatchResponse { batch_id: s(), rows: n }.encode_to_vec()),
+            (
+                "TriggerCompactionRequest",
+                TriggerCompactionRequest { table: s(), partition_filters: vec![s()] }.encode_to_vec(),
+            ),
+            ("TriggerCompactionResponse", TriggerCompactionResponse { version: 3 }.encode_to_vec()),
+            ("TableRequest", TableRequest { table: s() }.encode_to_vec()),
+            (
+                "TriggerVacuumResponse",
+                TriggerVacuumResponse { dry_run: true, files_deleted: n, bytes_deleted: n }.encode_to_vec(),
+            ),
+            (
+                "GetMetricsResponse",
+                GetMetricsResponse { queued_batches: n, counters: HashMap::from([(s(), 1.5)]) }.encode_to_vec(),
+            ),
+            (
+                "GetStatusResponse",
+                GetStatusResponse {
+                    table_uri: s(),
+                    read_only: true,
+                    queued_batches: n,
+                    last_version: Some(3),
+                    last_commit_at: Some(s()),
+                    recent_errors: n,
+                    source_status_json: Some(s()),
+                    writer_paused: true,
+                    compaction_paused: true,
+                    vacuum_paused: true,
+                }
+                .encode_to_vec(),
+            ),
+        ]
+        .into_iter()
+        .map(|(name, bytes)| (name.to_string(), bytes))
+        .collect();
+
+        // • Same messages, and each with the same field numbers and wire types.
+        assert_eq!(declared.keys().collect::<Vec<_>>(), encoded.keys().collect::<Vec<_>>());
+        for (name, bytes) in &encoded {
+            assert_eq!(encoded_fields(bytes), declared[name], "{} differs from proto/admin.proto", name);
+        }
+        Ok(())
+    }
+
+    // 44 --------------------------------------------------------------------
+    #[test]
+    fn api_acknowledged_batches_always_have_a_dead_letter_queue() -> Result<()> {
+        use surgical_strike_writer::config::parse_config;
+
+        // • Without the API nothing is acknowledged early, so nothing is kept.
+        let mut config = parse_config("table_uri = \"s3://lake/orders\"")?.remove(0);
+        assert_eq!(config.dead_letter_uri(), None);
+
+        // • With it, failed flushes fall back to the state directory.
+        config.http.enabled = true;
+        assert_eq!(config.dead_letter_uri().as_deref(), Some("surgical_strike_state/dead_letter/orders"));
+
+        // • An explicit queue wins.
+        config.writer.dead_letter_uri = Some("s3://lake/dlq".to_string());
+        assert_eq!(config.dead_letter_uri().as_deref(), Some("s3://lake/dlq"));
+        Ok(())
+    }
+
+    // 45 --------------------------------------------------------------------
+    #[test]
+    fn rollup_batches_aggregate_per_window_and_key() -> Result<()> {
+        use polars::prelude::{col, DataType as PolarsType, IntoLazy, SortMultipleOptions, TimeUnit};
+        use surgical_strike_writer::config::{AggregateFunction, AggregateSpec, RollupConfig};
+        use surgical_strike_writer::rollup::{aggregate_batch, WINDOW_START_COLUMN};
+
+        let minute = 60_000_000i64;
+        let as_datetime = |df: DataFrame, column: &str| {
+            df.lazy().with_column(col(column).cast(PolarsType::Datetime(TimeUnit::Microseconds, None))).collect()
+        };
+        let df = as_datetime(
+            polars::df! {
+                "ts" => &[0, 10_000_000, minute + 5, minute + 7, 2 * minute],
+                "k" => &["a", "a", "a", "b", "a"],
+                "v" => &[Some(1i64), Some(2), Some(5), Some(7), None],
+            }?,
+            "ts",
+        )?;
+        let spec = |function, alias: &str| AggregateSpec { column: "v".to_string(), function, alias: alias.to_string() };
+        let mut config = RollupConfig {
+            table_uri: "memory://rollup".to_string(),
+            time_column: "ts".to_string(),
+            window: "1m".to_string(),
+            group_by: vec!["k".to_string()],
+            aggregates: vec![
+                spec(AggregateFunction::Count, "n"),
+                spec(AggregateFunction::Sum, "total"),
+                spec(AggregateFunction::Min, "lo"),
+                spec(AggregateFunction::Max, "hi"),
+            ],
+        };
+
+        // • One row per truncated window and key; nulls are not counted.
+        let rolled = aggregate_batch(&df, &config)?.sort([WINDOW_START_COLUMN, "k"], SortMultipleOptions::default())?;
+        let expected = as_datetime(
+            polars::df! {
+                WINDOW_START_COLUMN => &[0, minute, minute, 2 * minute],
+                "k" => &["a", "a", "b", "a"],
+                "n" => &[2i64, 1, 1, 0],
+                "total" => &[3i64, 5, 7, 0],
+                "lo" => &[Some(1i64), Some(5), Some(7), None],
+                "hi" => &[Some(2i64), Some(5), Some(7), None],
+            }?,
+            WINDOW_START_COLUMN,
+        )?;
+        assert!(rolled.equals_missing(&expected), "{}", rolled);
+
+        // • A rollup without aggregates is a configuration error.
+        config.aggregates.clear();
+        assert!(aggregate_batch(&df, &config).is_err());
+        Ok(())
+    }
+
+    // 46 --------------------------------------------------------------------
+    #[tokio::test]
+    async fn rollups_catch_up_on_commits_they_missed() -> Result<()> {
+        use polars::prelude::{col, DataType as PolarsType, IntoLazy, TimeUnit};
+        use surgical_strike_writer::query::run_query;
+        use surgical_strike_writer::rollup::rollup_progress;
+        use surgical_strike_writer::SurgicalStrikeOrchestrator;
+
+        let dir = tempfile::tempdir()?;
+        let raw_uri = format!("file://{}/raw", dir.path().display());
+        let rollup_uri = format!("file://{}/rollup", dir.path().display());
+        let config = |rollups: &str| common::local_config(&raw_uri, &[("ts", "timestamp_ntz"), ("v", "long")], rollups);
+        let rollups = format!(
+            r#"
+            [[writer.rollups]]
+            table_uri = "{rollup_uri}"
+            time_column = "ts"
+            window = "1h"
+            aggregates = [{{ column = "v", function = "sum", alias = "total" }}]
+            "#
+        );
+        let batch = |v: i64| {
+            polars::df! {"ts" => &[0i64], "v" => &[v]}?
+                .lazy()
+                .with_column(col("ts").cast(PolarsType::Datetime(TimeUnit::Microseconds, None)))
+                .collect()
+        };
+
+        let rolling = SurgicalStrikeOrchestrator::new(config(&rollups)?).await?;
+        // • A writer without the rollup stands in for a rollup update that failed.
+        let missing = SurgicalStrikeOrchestrator::new(config("")?).await?;
+        rolling.write_batch(batch(1)?).await?;
+        missing.write_batch(batch(10)?).await?;
+        rolling.write_batch(batch(100)?).await?;
+
+        // • The next update folds in the missed commit and records the version it reached.
+        let raw = open_table(&raw_uri).await?;
+        let rollup = open_table(&rollup_uri).await?;
+        assert_eq!(rollup_progress(&rollup, &raw.table_uri()), Some(raw.version()));
+        let totals = run_query(rollup, "rollup", "SELECT total FROM rollup").await?.to_json()?;
+        assert_eq!(totals, serde_json::json!([{"total": 111}]));
+        Ok(())
+    }
+
+    // 47 --------------------------------------------------------------------
+    #[tokio::test]
+    async fn rollback_plans_inverse_commits_and_falls_back_to_restore() -> Result<()> {
+        use surgical_strike_writer::rollback::{apply_rollback, plan_rollback};
+        use surgical_strike_writer::RollbackStrategy;
+
+        let local = common::local_table(&[("id", "long")], "[writer]\nschema_evolution = \"add-columns\"").await?;
+        let (table_uri, orchestrator) = (&local.uri, &local.orchestrator);
+        orchestrator.write_batch(polars::df! {"id" => &[1i64, 2]}?).await?;
+        orchestrator.write_batch(polars::df! {"id" => &[3i64]}?).await?;
+        let bad = open_table(table_uri).await?.version();
+
+        // • A plain append is undone by removing its file, recorded as a DELETE.
+        let table = open_table(table_uri).await?;
+        let plan = plan_rollback(&table, bad, bad).await?;
+        assert_eq!(plan.strategy, RollbackStrategy::Inverse);
+        assert_eq!((plan.files_to_remove.len(), plan.files_to_restore.len()), (1, 0));
+        let mut table = apply_rollback(table, &plan).await?;
+        assert_eq!(orchestrator.profile().await?.num_rows, Some(2));
+        let commit = table.history(Some(1)).await?.remove(0);
+        assert_eq!(commit.operation.as_deref(), Some("DELETE"));
+        assert_eq!(commit.info["rollbackOf"], serde_json::json!({"fromVersion": bad, "toVersion": bad}));
+
+        // • A range that changed the schema can only be undone by RESTORE...
+        orchestrator.write_batch(polars::df! {"id" => &[4i64], "extra" => &["x"]}?).await?;
+        let widened = open_table(table_uri).await?.version();
+        table.update().await?;
+        let plan = plan_rollback(&table, widened, widened).await?;
+        assert!(matches!(&plan.strategy, RollbackStrategy::Restore { reason } if reason.contains("schema")));
+
+        // • ...which is refused while it would also undo later commits.
+        orchestrator.write_batch(polars::df! {"id" => &[5i64], "extra" => &["y"]}?).await?;
+        table.update().await?;
+        assert!(plan_rollback(&table, widened, widened).await.is_err());
+        Ok(())
+    }
+
+    // 48 --------------------------------------------------------------------
+    #[test]
+    fn plain_http_s3_endpoints_need_an_explicit_opt_in() {
+        use surgical_strike_writer::storage::resolve_storage_options;
+
+        let endpoint = ("AWS_ENDPOINT_URL".to_string(), "http://localhost:9000".to_string());
+
+        // • An http:// endpoint alone no longer turns on AWS_ALLOW_HTTP.
+        let options = resolve_storage_options("s3://bucket/table", &HashMap::from([endpoint.clone()]));
+        assert_eq!(options.0.get("AWS_ALLOW_HTTP"), env::var("AWS_ALLOW_HTTP").ok().as_ref());
+
+        // • Setting it in the config is passed through.
+        let allow = ("AWS_ALLOW_HTTP".to_string(), "true".to_string());
+        let options = resolve_storage_options("s3://bucket/table", &HashMap::from([endpoint, allow]));
+        assert_eq!(options.0.get("AWS_ALLOW_HTTP").map(String::as_str), Some("true"));
+    }
+
+    // 21 --------------------------------------------------------------------
+    #[tokio::test]
+    async fn resubmitted_idempotency_key_is_not_appended_twice() -> Result<()> {
+        // • A local table needs no infrastructure.
+        let local = common::local_table(&[("id", "long")], "[writer]\nidempotency_key_retention_days = 14").await?;
+        let orchestrator = &local.orchestrator;
+
+        // • A client retry reuses its key; only the first attempt lands.
+        assert!(orchestrator.write_batch_idempotent(polars::df! {"id" => &[1i64, 2, 3]}?, "client-1:req-7").await?);
+        assert!(!orchestrator.write_batch_idempotent(polars::df! {"id" => &[1i64, 2, 3]}?, "client-1:req-7").await?);
+        assert!(orchestrator.write_batch_idempotent(polars::df! {"id" => &[4i64, 5]}?, "client-1:req-8").await?);
+
+        let description = orchestrator.describe().await?;
+        assert_eq!(description.num_rows, Some(5));
+
+        // • Keys are stamped so the configured retention can expire them.
+        let table = open_table(&local.uri).await?;
+        let retention = table.metadata()?.configuration.get("delta.setTransactionRetentionDuration").cloned().flatten();
+        assert_eq!(retention.as_deref(), Some("interval 14 days"));
+        let key = surgical_strike_writer::writer::idempotency_txn("client-1:req-7")?;
+        assert!(key.last_updated.is_some());
+
+        Ok(())
+    }
+
+    // 49 --------------------------------------------------------------------
+    #[tokio::test]
+    async fn dropped_late_rows_are_counted_and_not_written() -> Result<()> {
+        use polars::prelude::DataType as PolarsType;
+        use surgical_strike_writer::config::{LatePolicy, LatenessConfig};
+        use surgical_strike_writer::metrics::MetricsRegistry;
+        use surgical_strike_writer::query::run_query;
+        use surgical_strike_writer::WriterProcess;
+
+        // • A local table with an event-time column, created from the declared schema.
+        let local = common::local_table(&[("id", "long"), ("day", "date")], "").await?;
+        let (uri, config, orchestrator) = (&local.uri, &local.config, &local.orchestrator);
+
+        // • Rows more than two days behind the clock are dropped.
+        let mut writer_config = config.writer.clone();
+        writer_config.lateness = Some(LatenessConfig {
+            event_time_column: "day".to_string(),
+            horizon_secs: 2 * 24 * 3600,
+            policy: LatePolicy::Drop,
+        });
+        let metrics = Arc::new(MetricsRegistry::default());
+        let writer = WriterProcess::new(writer_config).with_metrics(metrics.clone());
+
+        // • Two of four rows are ten days old; a row without an event time is never late.
+        let today = (chrono::Utc::now().date_naive() - chrono::DateTime::UNIX_EPOCH.date_naive()).num_days() as i32;
+        let day = Series::new("day".into(), &[Some(today), Some(today - 10), Some(today - 10), None])
+            .cast(&PolarsType::Date)?;
+        let df = DataFrame::new(vec![Series::new("id".into(), &[1i64, 2, 3, 4]).into(), day.into()])?;
+        writer.write_batch(df, &config.storage_options, uri).await?;
+
+        // • The dropped rows are counted and never reach the table.
+        assert_eq!(metrics.late_rows(), 2);
+        assert_eq!(metrics.rows_written(), 2);
+        assert_eq!(orchestrator.profile().await?.num_rows, Some(2));
+        let ids = run_query(open_table(uri).await?, "t", "SELECT id FROM t ORDER BY id").await?.to_json()?;
+        assert_eq!(ids, serde_json::json!([{"id": 1}, {"id": 4}]));
+
+        Ok(())
+    }
+
+    // 50 --------------------------------------------------------------------
+    #[tokio::test]
+    async fn queued_batches_with_different_columns_are_written_or_dead_lettered() -> Result<()> {
+        use surgical_strike_writer::dead_letter::DeadLetterQueue;
+        use surgical_strike_writer::{QueuedBatch, WriterProcess};
+        use tokio_util::sync::CancellationToken;
+
+        let local = common::local_table(&[("id", "long")], "[writer]\nschema_evolution = \"add-columns\"").await?;
+        let dead_letter_uri = format!("file://{}/dead_letter", local.dir.path().display());
+        let dead_letter = Arc::new(DeadLetterQueue::open(&dead_letter_uri, &local.config.storage_options)?);
+
+        // • The queue is paused, so everything sent is flushed together on shutdown.
+        let flush_together = |batches: Vec<DataFrame>| {
+            let writer = WriterProcess::new(local.config.writer.clone()).with_dead_letter(dead_letter.clone());
+            let (uri, storage_options) = (local.uri.clone(), local.config.storage_options.clone());
+            async move {
+                let (sender, receiver) = tokio::sync::mpsc::channel(batches.len());
+                for (i, df) in batches.into_iter().enumerate() {
+                    sender.send(QueuedBatch { id: format!("batch-{}", i), df }).await?;
+                }
+                writer.control().pause();
+                let shutdown = CancellationToken::new();
+                shutdown.cancel();
+                let table = Arc::new(Mutex::new(open_table(&uri).await?));
+                writer.run(table, storage_options, receiver, shutdown).await
+            }
+        };
+
+        // • A column only a later batch brings reaches schema evolution; earlier rows get nulls.
+        flush_together(vec![polars::df! {"id" => &[1i64]}?, polars::df! {"id" => &[2i64], "extra" => &["x"]}?]).await?;
+        let profile = local.orchestrator.profile().await?;
+        assert_eq!(profile.num_rows, Some(2));
+        assert_eq!(profile.columns.iter().map(|c| c.column.as_str()).collect::<Vec<_>>(), ["id", "extra"]);
+        assert_eq!(profile.columns[1].null_count, Some(1));
+
+        // • Batches that cannot be stacked are each kept in the dead letter queue.
+        flush_together(vec![polars::df! {"id" => &[3i64]}?, polars::df! {"id" => &["four"]}?]).await?;
+        let mut kept: Vec<_> = dead_letter.list().await?.into_iter().flat_map(|entry| entry.batch_ids).collect();
+        kept.sort();
+        assert_eq!(kept, ["batch-0", "batch-1"]);
+        assert_eq!(local.orchestrator.profile().await?.num_rows, Some(2));
+        Ok(())
+    }
+
+    // 51 --------------------------------------------------------------------
+    #[tokio::test]
+    async fn sources_dead_letter_batches_that_cannot_be_written() -> Result<()> {
+        use deltalake::kernel::Transaction;
+        use surgical_strike_writer::sources::{committed_positions, SourceBatch};
+
+        let dead_letter = tempfile::tempdir()?;
+        let extra = format!("[writer]\ndead_letter_uri = \"file://{}\"", dead_letter.path().display());
+        let local = common::local_table(&[("id", "long")], &extra).await?;
+        let orchestrator = &local.orchestrator;
+
+        // • The middle batch does not fit the table's schema, on any attempt.
+        let batch = |df: DataFrame, position: i64| SourceBatch {
+            df,
+            checkpoints: vec![Transaction::new("scripted-0", position)],
+            metadata: Vec::new(),
+        };
+        let source = common::ScriptedSource::new(vec![
+            batch(polars::df! {"id" => &[1i64]}?, 1),
+            batch(polars::df! {"id" => &["two"]}?, 2),
+            batch(polars::df! {"id" => &[3i64]}?, 3),
+        ]);
+        orchestrator.ingest(source).await?;
+
+        // • The source keeps going; the bad rows wait in the dead letter queue.
+        let table = open_table(&local.uri).await?;
+        assert_eq!(committed_positions(&table, "scripted-")["scripted-0"], 3);
+        assert_eq!(orchestrator.profile().await?.num_rows, Some(2));
+        let dead_letters = orchestrator.dead_letters().await?;
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].rows, 1);
+        Ok(())
+    }
+
+    // 52 --------------------------------------------------------------------
+    #[tokio::test]
+    async fn deletion_vectors_stop_writes_compaction_and_vacuum_alike() -> Result<()> {
+        use deltalake::DeltaOps;
+        use surgical_strike_writer::compat::{check_table, deletion_vectors};
+
+        let local = common::local_table(&[("id", "long")], "").await?;
+        let orchestrator = &local.orchestrator;
+        orchestrator.write_batch(polars::df! {"id" => &[1i64]}?).await?;
+        orchestrator.write_batch(polars::df! {"id" => &[2i64]}?).await?;
+
+        // • Another engine enables deletion vectors but has not written any yet.
+        let table = DeltaOps(open_table(&local.uri).await?)
+            .set_tbl_properties()
+            .with_properties(HashMap::from([("delta.enableDeletionVectors".to_string(), "true".to_string())]))
+            .await?;
+        let dvs = deletion_vectors(&table)?;
+        assert!(dvs.enabled && dvs.files_with_dvs == 0);
+        assert!(!check_table(&table)?.is_writable());
+
+        // • The same rule refuses every process that would rewrite or delete files.
+        let compaction = orchestrator.compact().await.unwrap_err();
+        assert!(format!("{:#}", compaction).contains("Refusing to compact: table uses deletion vectors"));
+        let vacuum = orchestrator.vacuum().await.unwrap_err();
+        assert!(format!("{:#}", vacuum).contains("Refusing to vacuum: table uses deletion vectors"));
+        assert!(orchestrator.write_batch(polars::df! {"id" => &[3i64]}?).await.is_err());
+        assert_eq!(open_table(&local.uri).await?.version(), table.version());
+        Ok(())
+    }
+
+    // 53 --------------------------------------------------------------------
+    #[tokio::test]
+    async fn coalesced_commits_only_group_flushes_of_one_window() -> Result<()> {
+        use surgical_strike_writer::WriterProcess;
+
+        let local = common::local_table(&[("id", "long")], "[writer]\ncommit_interval_ms = 300").await?;
+        let writer = WriterProcess::new(local.config.writer.clone());
+        let start = open_table(&local.uri).await?.version();
+
+        // • Three flushes finish together, two of them in the same aligned window.
+        let flush = |id: i64, window: &str| {
+            let metadata = vec![("flushWindowStart".to_string(), serde_json::json!(window))];
+            let (writer, local) = (&writer, &local);
+            async move {
+                let df = polars::df! {"id" => &[id]}?;
+                writer.write_batch_with_checkpoint(df, &[], metadata, &local.config.storage_options, &local.uri).await
+            }
+        };
+        let (a, b, c) = tokio::join!(flush(1, "10:00"), flush(2, "10:00"), flush(3, "10:15"));
+        a?;
+        b?;
+        c?;
+
+        // • No commit mixes windows, and every batch is listed under its own.
+        let table = open_table(&local.uri).await?;
+        let commits = table.history(Some((table.version() - start) as usize)).await?;
+        assert!(commits.len() <= 3);
+        let mut batches = HashMap::new();
+        for commit in &commits {
+            let window = commit.info["flushWindowStart"].as_str().unwrap().to_string();
+            *batches.entry(window).or_insert(0) += commit.info["batchIds"].as_array().unwrap().len();
+        }
+        assert_eq!(batches, HashMap::from([("10:00".to_string(), 2), ("10:15".to_string(), 1)]));
+        assert_eq!(local.orchestrator.profile().await?.num_rows, Some(3));
+        Ok(())
+    }
+
+    // 54 --------------------------------------------------------------------
+    #[tokio::test]
+    async fn cached_table_handle_catches_up_with_commits_of_other_writers() -> Result<()> {
+        use surgical_strike_writer::metrics::MetricsRegistry;
+        use surgical_strike_writer::WriterProcess;
+
+        let local = common::local_table(&[("id", "long")], "").await?;
+        let (uri, storage_options) = (&local.uri, &local.config.storage_options);
+        let metrics = Arc::new(MetricsRegistry::default());
+        let writer = WriterProcess::new(local.config.writer.clone()).with_metrics(metrics.clone());
+
+        // • The first flush opens the table and keeps the handle at version 1.
+        writer.write_batch(polars::df! {"id" => &[1i64]}?, storage_options, uri).await?;
+        assert_eq!(metrics.table_opens(), 1);
+
+        // • A second handle commits version 2 behind the writer's back.
+        let other = WriterProcess::new(local.config.writer.clone());
+        other.write_batch(polars::df! {"id" => &[2i64]}?, storage_options, uri).await?;
+        assert_eq!(open_table(uri).await?.version(), 2);
+
+        // • The next flush reuses the stale handle yet lands on top, as version 3.
+        writer.write_batch(polars::df! {"id" => &[3i64]}?, storage_options, uri).await?;
+        assert_eq!((metrics.table_opens(), metrics.table_reuses()), (1, 1));
+        assert_eq!(open_table(uri).await?.version(), 3);
+
+        // • The kept handle moved along with it, so the flush after that follows directly.
+        writer.write_batch(polars::df! {"id" => &[4i64]}?, storage_options, uri).await?;
+        assert_eq!((metrics.table_opens(), metrics.table_reuses()), (1, 2));
+        assert_eq!(open_table(uri).await?.version(), 4);
+        assert_eq!(local.orchestrator.profile().await?.num_rows, Some(4));
+        Ok(())
+    }
+
+    // 55 --------------------------------------------------------------------
+    #[tokio::test]
+    async fn commit_lost_to_another_writer_is_retried_on_the_latest_version() -> Result<()> {
+        use deltalake::DeltaOps;
+        use surgical_strike_writer::metrics::MetricsRegistry;
+        use surgical_strike_writer::WriterProcess;
+
+        let local = common::local_table(&[("id", "long")], "").await?;
+        let (uri, storage_options) = (&local.uri, &local.config.storage_options);
+        let metrics = Arc::new(MetricsRegistry::default());
+        let writer = WriterProcess::new(local.config.writer.clone()).with_metrics(metrics.clone());
+        writer.write_batch(polars::df! {"id" => &[1i64]}?, storage_options, uri).await?;
+
+        // • Another writer changes the table metadata while the writer still holds version 1.
+        DeltaOps(open_table(uri).await?)
+            .set_tbl_properties()
+            .with_properties(HashMap::from([("delta.logRetentionDuration".to_string(), "interval 60 days".to_string())]))
+            .await?;
+
+        // • The commit conflicts once and lands on top; only the commit is retried.
+        writer.write_batch(polars::df! {"id" => &[2i64]}?, storage_options, uri).await?;
+        assert_eq!(metrics.commit_conflicts(), 1);
+        assert_eq!(metrics.write_retries(), 0);
+        assert_eq!(open_table(uri).await?.version(), 3);
+        assert_eq!(local.orchestrator.profile().await?.num_rows, Some(2));
+        Ok(())
+    }
+
+    // 56 --------------------------------------------------------------------
+    #[tokio::test]
+    async fn commit_skips_batch_whose_transactions_another_writer_committed() -> Result<()> {
+        use deltalake::kernel::Transaction;
+        use surgical_strike_writer::metrics::MetricsRegistry;
+        use surgical_strike_writer::query::run_query;
+        use surgical_strike_writer::WriterProcess;
+
+        let local = common::local_table(&[("id", "long")], "").await?;
+        let (uri, storage_options) = (&local.uri, &local.config.storage_options);
+        let metrics = Arc::new(MetricsRegistry::default());
+        let writer = WriterProcess::new(local.config.writer.clone()).with_metrics(metrics.clone());
+        writer.write_batch(polars::df! {"id" => &[1i64]}?, storage_options, uri).await?;
+
+        // • Another writer commits the same source position first, as after a rebalance.
+        let position = [Transaction::new("source-0", 7)];
+        let other = WriterProcess::new(local.config.writer.clone());
+        other.write_batch_with_txns(polars::df! {"id" => &[2i64]}?, &position, storage_options, uri).await?;
+
+        // • The stale writer only learns of it from the conflict, then drops its batch.
+        writer.write_batch_with_txns(polars::df! {"id" => &[3i64]}?, &position, storage_options, uri).await?;
+        assert_eq!(metrics.commit_conflicts(), 1);
+        assert_eq!(open_table(uri).await?.version(), 2);
+        let ids = run_query(open_table(uri).await?, "t", "SELECT id FROM t ORDER BY id").await?.to_json()?;
+        assert_eq!(ids, serde_json::json!([{"id": 1}, {"id": 2}]));
+        Ok(())
+    }
+
+    // 57 --------------------------------------------------------------------
+    #[tokio::test]
+    async fn commit_rewrites_batch_after_a_concurrent_schema_change() -> Result<()> {
+        use deltalake::arrow::array::Int64Array;
+        use deltalake::operations::write::SchemaMode;
+        use deltalake::protocol::SaveMode;
+        use deltalake::DeltaOps;
+        use surgical_strike_writer::metrics::MetricsRegistry;
+        use surgical_strike_writer::WriterProcess;
+
+        let local = common::local_table(
+            &[("id", "long"), ("name", "string")],
+            "[writer]\nschema_evolution = \"add-columns\"",
+        )
+        .await?;
+        let (uri, storage_options) = (&local.uri, &local.config.storage_options);
+        let metrics = Arc::new(MetricsRegistry::default());
+        let writer = WriterProcess::new(local.config.writer.clone()).with_metrics(metrics.clone());
+        writer.write_batch(polars::df! {"id" => &[1i64], "name" => &["a"]}?, storage_options, uri).await?;
+
+        // • Another writer replaces the table with one lacking the name column.
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, true)]));
+        let batch = RecordBatch::try_new(schema, vec![Arc::new(Int64Array::from(vec![10]))])?;
+        DeltaOps(open_table(uri).await?)
+            .write(vec![batch])
+            .with_save_mode(SaveMode::Overwrite)
+            .with_schema_mode(SchemaMode::Overwrite)
+            .await?;
+
+        // • Files written for the old schema are not committed; the batch is
+        //   written again, adding its column back.
+        writer.write_batch(polars::df! {"id" => &[2i64], "name" => &["b"]}?, storage_options, uri).await?;
+        assert_eq!((metrics.commit_conflicts(), metrics.write_retries()), (1, 1));
+        assert_eq!(open_table(uri).await?.version(), 3);
+        let profile = local.orchestrator.profile().await?;
+        assert_eq!(profile.num_rows, Some(2));
+        assert_eq!(profile.columns.iter().map(|c| c.column.as_str()).collect::<Vec<_>>(), ["id", "name"]);
+        assert_eq!(profile.columns[1].null_count, Some(1));
+        Ok(())
+    }
+
+    // 58 --------------------------------------------------------------------
+    #[tokio::test]
+    async fn read_only_orchestrator_never_changes_the_table() -> Result<()> {
+        use surgical_strike_writer::{JobKind, SurgicalStrikeOrchestrator};
+
+        let columns = [("id", "long")];
+        let local = common::local_table(&columns, "").await?;
+        local.orchestrator.write_batch(polars::df! {"id" => &[1i64]}?).await?;
+        let version = open_table(&local.uri).await?.version();
+
+        // • Opening read-only skips the retention settings a writer would commit.
+        let config = common::local_config(
+            &local.uri,
+            &columns,
+            "read_only = true\ntime_travel_days = 30\n[writer]\nidempotency_key_retention_days = 14",
+        )?;
+        let orchestrator = SurgicalStrikeOrchestrator::new(config).await?;
+        assert_eq!(open_table(&local.uri).await?.version(), version);
+
+        // • A missing table is not created from the declared schema either.
+        let missing = common::local_config(&format!("{}/missing", local.uri), &columns, "read_only = true")?;
+        assert!(SurgicalStrikeOrchestrator::new(missing).await.is_err());
+        assert!(!local.dir.path().join("missing").exists());
+
+        // • Every operation that would change the table is refused up front.
+        let refusals = [
+            orchestrator.write_batch(polars::df! {"id" => &[2i64]}?).await.map(|_| ()),
+            orchestrator.compact().await,
+            orchestrator.vacuum().await.map(|_| ()),
+            orchestrator.ingest(common::ScriptedSource::new(Vec::new())).await,
+            orchestrator.execute_job(&JobKind::Compact).await.map(|_| ()),
+        ];
+        for refusal in refusals {
+            assert!(format!("{:#}", refusal.unwrap_err()).contains("is open read-only"));
+        }
+        assert_eq!(open_table(&local.uri).await?.version(), version);
+        assert_eq!(orchestrator.profile().await?.num_rows, Some(1));
+        Ok(())
+    }
+}